@@ -0,0 +1,87 @@
+use tracing::info;
+
+use crate::examine;
+use crate::json::{self, Json};
+
+pub(crate) const USAGE: &str = "checkpoint [list|restore <n>|delete <n>]";
+
+fn print_checkpoints(target: &sdb::Target) {
+    if json::enabled() {
+        let checkpoints = target
+            .process()
+            .checkpoints()
+            .iter()
+            .map(|checkpoint| {
+                Json::object([
+                    ("id", Json::number(checkpoint.id)),
+                    ("pid", Json::number(checkpoint.pid.as_raw())),
+                    ("pc", Json::string(checkpoint.pc)),
+                ])
+            })
+            .collect();
+        json::emit("checkpoints", [("checkpoints", Json::Array(checkpoints))]);
+        return;
+    }
+
+    if target.process().checkpoints().is_empty() {
+        info!("No checkpoints");
+        return;
+    }
+    for checkpoint in target.process().checkpoints() {
+        println!(
+            "{}: process {} at {}",
+            checkpoint.id,
+            checkpoint.pid,
+            examine::describe_address(target, checkpoint.pc)
+        );
+    }
+}
+
+pub(crate) fn handle_checkpoint_command(
+    target: &mut sdb::Target,
+    args: &[&str],
+) -> anyhow::Result<()> {
+    match args {
+        [] => {
+            let checkpoint = target.process_mut().create_checkpoint()?.clone();
+            if json::enabled() {
+                json::emit(
+                    "checkpoint",
+                    [
+                        ("id", Json::number(checkpoint.id)),
+                        ("pid", Json::number(checkpoint.pid.as_raw())),
+                        ("pc", Json::string(checkpoint.pc)),
+                    ],
+                );
+            } else {
+                println!(
+                    "Checkpoint {} at {}",
+                    checkpoint.id,
+                    examine::describe_address(target, checkpoint.pc)
+                );
+            }
+            Ok(())
+        }
+        ["list"] => {
+            print_checkpoints(target);
+            Ok(())
+        }
+        ["restore", id] => {
+            target.restore_checkpoint(id.parse()?)?;
+            Ok(())
+        }
+        ["delete", id] => {
+            target.process_mut().delete_checkpoint(id.parse()?)?;
+            Ok(())
+        }
+        _ => anyhow::bail!("Usage: {}", USAGE),
+    }
+}
+
+pub(crate) fn handle_reverse_continue_command(target: &mut sdb::Target) -> anyhow::Result<()> {
+    let reason = target.reverse_continue()?;
+    if reason.breakpoint_site.is_none() {
+        info!("No breakpoint hit to go back to, it's back where the oldest checkpoint was taken");
+    }
+    Ok(())
+}
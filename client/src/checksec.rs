@@ -0,0 +1,80 @@
+use std::path::Path;
+
+use crate::json::{self, Json};
+
+pub(crate) const USAGE: &str = "checksec [module]";
+
+// a loaded module by its path, or the start of its file name, like libc for libc.so.6
+pub(crate) fn find_module<'a>(target: &'a sdb::Target, name: &str) -> anyhow::Result<&'a sdb::Elf> {
+    let matches = |path: &Path| {
+        path == Path::new(name)
+            || path
+                .file_name()
+                .is_some_and(|file_name| file_name.to_string_lossy().starts_with(name))
+    };
+    target
+        .modules()
+        .iter()
+        .find(|elf| matches(elf.path()))
+        .ok_or_else(|| anyhow::anyhow!("No module named {} is loaded", name))
+}
+
+fn fortify_text(mitigations: &sdb::Mitigations) -> String {
+    let mut text = match mitigations.fortified.len() {
+        0 => "none".to_owned(),
+        count => format!("{} fortified ({})", count, mitigations.fortified.join(", ")),
+    };
+    if !mitigations.fortifiable.is_empty() {
+        text += &format!(
+            ", {} that could be ({})",
+            mitigations.fortifiable.len(),
+            mitigations.fortifiable.join(", ")
+        );
+    }
+    text
+}
+
+pub(crate) fn handle_checksec_command(target: &sdb::Target, args: &[&str]) -> anyhow::Result<()> {
+    let elf = match args {
+        [] => target
+            .main_elf()
+            .ok_or_else(|| anyhow::anyhow!("Couldn't find the executable's module"))?,
+        [name] => find_module(target, name)?,
+        _ => anyhow::bail!("Usage: {}", USAGE),
+    };
+    let mitigations = elf.mitigations()?;
+
+    if json::enabled() {
+        json::emit(
+            "checksec",
+            [
+                ("path", Json::string(elf.path().display())),
+                ("pie", Json::string(mitigations.pie)),
+                ("relro", Json::string(mitigations.relro)),
+                ("nx", Json::Bool(mitigations.nx)),
+                ("canary", Json::Bool(mitigations.canary)),
+                (
+                    "fortified",
+                    Json::Array(mitigations.fortified.iter().map(Json::string).collect()),
+                ),
+                (
+                    "fortifiable",
+                    Json::Array(mitigations.fortifiable.iter().map(Json::string).collect()),
+                ),
+            ],
+        );
+        return Ok(());
+    }
+
+    let enabled = |enabled| if enabled { "enabled" } else { "disabled" };
+    println!("{}:", elf.path().display());
+    println!("  PIE:      {}", mitigations.pie);
+    println!("  RELRO:    {}", mitigations.relro);
+    println!("  NX:       {}", enabled(mitigations.nx));
+    println!(
+        "  Canary:   {}",
+        if mitigations.canary { "found" } else { "none" }
+    );
+    println!("  Fortify:  {}", fortify_text(&mitigations));
+    Ok(())
+}
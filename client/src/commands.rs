@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use tracing::info;
+
+use crate::json::{self, Json};
+use crate::script;
+
+pub const ALIAS_USAGE: &str = "alias [[--save] <name> <command>...|--delete <name>]";
+
+// the user's own names for commands, shared by every kind of session
+static USER_ALIASES: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+// a command a session understands, run is whatever the session calls it with
+pub struct ReplCommand<H> {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub usage: &'static str,
+    pub description: &'static str,
+    pub run: H,
+}
+
+// exact names and aliases win, otherwise any unambiguous prefix of a name will do
+pub fn find<'a, H>(
+    commands: &'a [ReplCommand<H>],
+    word: &str,
+) -> anyhow::Result<&'a ReplCommand<H>> {
+    if let Some(command) = commands
+        .iter()
+        .find(|command| command.name == word || command.aliases.contains(&word))
+    {
+        return Ok(command);
+    }
+
+    let matches = commands
+        .iter()
+        .filter(|command| command.name.starts_with(word))
+        .collect::<Vec<_>>();
+    match matches.as_slice() {
+        [] => anyhow::bail!("Unknown command '{}', try help", word),
+        [command] => Ok(command),
+        matches => anyhow::bail!(
+            "Ambiguous command '{}' could be {}",
+            word,
+            matches
+                .iter()
+                .map(|command| command.name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+// a user alias at the start of the line is replaced with what it stands for
+// before anything else, so it wins over a command it happens to be a prefix of
+pub fn expand_alias(line: &str) -> String {
+    let line = line.trim_start();
+    let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    match USER_ALIASES.lock().unwrap().get(word) {
+        Some(command) => format!("{} {}", command, rest),
+        None => line.to_owned(),
+    }
+}
+
+// splits the line into a command and its arguments and runs it,
+// gdb's print/x style format goes to the command as its first argument
+pub fn dispatch<H>(
+    commands: &[ReplCommand<H>],
+    line: &str,
+    run: impl FnOnce(&H, &[&str]) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let line = expand_alias(line);
+    let mut words = line.split_whitespace().collect::<Vec<_>>();
+    let Some(&word) = words.first() else {
+        return Ok(());
+    };
+
+    let word = match word.find('/') {
+        Some(idx) if idx > 0 => {
+            words[0] = &word[idx..];
+            &word[..idx]
+        }
+        _ => {
+            words.remove(0);
+            word
+        }
+    };
+
+    run(&find(commands, word)?.run, &words)
+}
+
+fn describe<H>(command: &ReplCommand<H>) -> Json {
+    Json::object([
+        ("name", Json::string(command.name)),
+        (
+            "aliases",
+            Json::Array(command.aliases.iter().map(Json::string).collect()),
+        ),
+        ("usage", Json::string(command.usage)),
+        ("description", Json::string(command.description)),
+    ])
+}
+
+// help lists every command, help <command> shows how to use one
+pub fn help<H>(commands: &[ReplCommand<H>], args: &[&str]) -> anyhow::Result<()> {
+    match args {
+        [] if json::enabled() => {
+            json::emit(
+                "help",
+                [(
+                    "commands",
+                    Json::Array(commands.iter().map(describe).collect()),
+                )],
+            );
+        }
+        [] => {
+            let width = commands
+                .iter()
+                .map(|command| command.name.len())
+                .max()
+                .unwrap_or_default();
+            for command in commands {
+                println!("{:width$}  {}", command.name, command.description);
+            }
+            println!();
+            println!("Any unambiguous prefix of a command works, help <command> for more");
+        }
+        [word] => {
+            let command = find(commands, word)?;
+            if json::enabled() {
+                json::emit("help", [("commands", Json::Array(vec![describe(command)]))]);
+                return Ok(());
+            }
+
+            println!("Usage: {}", command.usage);
+            println!("{}", command.description);
+            if !command.aliases.is_empty() {
+                println!("Aliases: {}", command.aliases.join(", "));
+            }
+        }
+        _ => anyhow::bail!("Usage: help [command]"),
+    }
+
+    Ok(())
+}
+
+fn define_alias<H>(
+    commands: &[ReplCommand<H>],
+    name: &str,
+    command: &[&str],
+) -> anyhow::Result<()> {
+    if commands
+        .iter()
+        .any(|existing| existing.name == name || existing.aliases.contains(&name))
+    {
+        anyhow::bail!("{} is already a command", name);
+    }
+
+    // aliases stand for commands, not other aliases
+    let Some(word) = command.first() else {
+        anyhow::bail!("Usage: {}", ALIAS_USAGE);
+    };
+    find(commands, word)?;
+
+    let command = command.join(" ");
+    info!("{} is now an alias for {}", name, command);
+    USER_ALIASES
+        .lock()
+        .unwrap()
+        .insert(name.to_owned(), command);
+
+    Ok(())
+}
+
+fn list_aliases() {
+    let aliases = USER_ALIASES.lock().unwrap();
+    if json::enabled() {
+        let aliases = aliases
+            .iter()
+            .map(|(name, command)| {
+                Json::object([
+                    ("name", Json::string(name)),
+                    ("command", Json::string(command)),
+                ])
+            })
+            .collect();
+        json::emit("aliases", [("aliases", Json::Array(aliases))]);
+        return;
+    }
+
+    if aliases.is_empty() {
+        println!("No aliases defined");
+    }
+    for (name, command) in aliases.iter() {
+        println!("{} = {}", name, command);
+    }
+}
+
+// alias lists them, alias <name> <command> defines one, --save keeps it in the rc file
+pub fn alias<H>(commands: &[ReplCommand<H>], args: &[&str]) -> anyhow::Result<()> {
+    match args {
+        [] => list_aliases(),
+        ["--delete", name] => {
+            if USER_ALIASES.lock().unwrap().remove(*name).is_none() {
+                anyhow::bail!("No alias named {}", name);
+            }
+        }
+        ["--save", name, command @ ..] => {
+            define_alias(commands, name, command)?;
+            let path = script::save_to_rc(&format!("alias {} {}", name, command.join(" ")))?;
+            info!("Saved alias {} to {}", name, path.display());
+        }
+        [name, command @ ..] if !name.starts_with("--") => define_alias(commands, name, command)?,
+        _ => anyhow::bail!("Usage: {}", ALIAS_USAGE),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,179 @@
+use nix::unistd::Pid;
+
+use crate::{print_current_stop, print_stop_info, scripting, wait_for_stop};
+
+// work for the control thread, the process has to be stopped for it
+pub(crate) type ProcessJob = Box<dyn FnOnce(&mut sdb::Process) -> anyhow::Result<()> + Send>;
+
+pub(crate) type TargetJob = Box<dyn FnOnce(&mut sdb::Target) -> anyhow::Result<()> + Send>;
+
+// what commands against a live process go through, so the command table
+// can be exercised without one
+pub(crate) trait DebuggerContext {
+    // only known for a process in this one
+    fn pid(&self) -> anyhow::Result<Pid>;
+
+    fn request(&self, request: sdb::Request) -> anyhow::Result<sdb::Response>;
+
+    // whether the process is in this one, or only reachable through requests
+    fn is_local(&self) -> bool;
+
+    fn resume(&self) -> anyhow::Result<()>;
+
+    // Ctrl-C interrupts the process while this waits
+    fn wait_for_stop(&self) -> anyhow::Result<()>;
+
+    fn step_instruction(&self) -> anyhow::Result<()>;
+
+    // true if a hook wants the process to carry on
+    fn run_stop_hooks(&self) -> anyhow::Result<bool>;
+
+    fn print_current_stop(&self) -> anyhow::Result<()>;
+
+    fn with_process(&self, job: ProcessJob) -> anyhow::Result<()>;
+
+    fn with_target(&self, job: TargetJob) -> anyhow::Result<()>;
+
+    // for the commands that run alongside the process, like monitor and script
+    fn controller(&self) -> anyhow::Result<&sdb::ProcessController>;
+}
+
+// everything the protocol can express goes through requests,
+// the rest needs the process to be in this one
+impl<T: sdb::DebugTransport> DebuggerContext for T {
+    fn pid(&self) -> anyhow::Result<Pid> {
+        Ok(DebuggerContext::controller(self)?.handle().get_id())
+    }
+
+    fn request(&self, request: sdb::Request) -> anyhow::Result<sdb::Response> {
+        Ok(sdb::DebugTransport::request(self, request)?)
+    }
+
+    fn is_local(&self) -> bool {
+        self.local_controller().is_some()
+    }
+
+    fn resume(&self) -> anyhow::Result<()> {
+        DebuggerContext::request(self, sdb::Request::Resume)?;
+        Ok(())
+    }
+
+    fn wait_for_stop(&self) -> anyhow::Result<()> {
+        wait_for_stop(self)?;
+        Ok(())
+    }
+
+    fn step_instruction(&self) -> anyhow::Result<()> {
+        DebuggerContext::request(self, sdb::Request::StepInstruction)?;
+        Ok(())
+    }
+
+    fn run_stop_hooks(&self) -> anyhow::Result<bool> {
+        match self.local_controller() {
+            Some(controller) => scripting::run_stop_hooks(controller),
+            None => Ok(false),
+        }
+    }
+
+    fn print_current_stop(&self) -> anyhow::Result<()> {
+        if let Some(controller) = self.local_controller() {
+            return print_current_stop(controller);
+        }
+
+        let response = DebuggerContext::request(self, sdb::Request::StopInfo)?;
+        if let sdb::Response::StopInfo(info) = response {
+            print_stop_info(None, info);
+        }
+        Ok(())
+    }
+
+    fn with_process(&self, job: ProcessJob) -> anyhow::Result<()> {
+        DebuggerContext::controller(self)?.execute(job)?
+    }
+
+    fn with_target(&self, job: TargetJob) -> anyhow::Result<()> {
+        DebuggerContext::controller(self)?.execute_target(job)?
+    }
+
+    fn controller(&self) -> anyhow::Result<&sdb::ProcessController> {
+        self.local_controller()
+            .ok_or_else(|| anyhow::anyhow!("Not available for a process debugged elsewhere"))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod mock {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    // records what a command asked for instead of doing it,
+    // jobs for the control thread are dropped without running
+    #[derive(Default)]
+    pub(crate) struct MockContext {
+        pub calls: RefCell<Vec<&'static str>>,
+        pub continue_after_stop: RefCell<Vec<bool>>,
+        pub remote: bool,
+    }
+
+    impl MockContext {
+        fn record(&self, call: &'static str) {
+            self.calls.borrow_mut().push(call);
+        }
+    }
+
+    impl DebuggerContext for MockContext {
+        fn pid(&self) -> anyhow::Result<Pid> {
+            Ok(Pid::from_raw(1234))
+        }
+
+        fn request(&self, _: sdb::Request) -> anyhow::Result<sdb::Response> {
+            self.record("request");
+            Ok(sdb::Response::Done)
+        }
+
+        fn is_local(&self) -> bool {
+            !self.remote
+        }
+
+        fn resume(&self) -> anyhow::Result<()> {
+            self.record("resume");
+            Ok(())
+        }
+
+        fn wait_for_stop(&self) -> anyhow::Result<()> {
+            self.record("wait_for_stop");
+            Ok(())
+        }
+
+        fn step_instruction(&self) -> anyhow::Result<()> {
+            self.record("step_instruction");
+            Ok(())
+        }
+
+        fn run_stop_hooks(&self) -> anyhow::Result<bool> {
+            self.record("run_stop_hooks");
+            Ok(self.continue_after_stop.borrow_mut().pop().unwrap_or(false))
+        }
+
+        fn print_current_stop(&self) -> anyhow::Result<()> {
+            self.record("print_current_stop");
+            Ok(())
+        }
+
+        fn with_process(&self, _: ProcessJob) -> anyhow::Result<()> {
+            self.record("with_process");
+            Ok(())
+        }
+
+        fn with_target(&self, _: TargetJob) -> anyhow::Result<()> {
+            self.record("with_target");
+            Ok(())
+        }
+
+        fn controller(&self) -> anyhow::Result<&sdb::ProcessController> {
+            self.record("controller");
+            anyhow::bail!("No process in a mock")
+        }
+    }
+}
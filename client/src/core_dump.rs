@@ -0,0 +1,158 @@
+use sdb::Inferior;
+use tracing::info;
+
+use crate::{
+    INFO_USAGE,
+    commands::{self, ReplCommand},
+    handle_info_command,
+    json::{self, Json},
+    print_backtrace, print_registers,
+};
+
+fn print_stop(target: &sdb::CoreTarget) -> anyhow::Result<()> {
+    let thread = target.current_thread();
+    let pc = target.get_pc()?;
+
+    if json::enabled() {
+        json::emit(
+            "stop",
+            [
+                ("reason", Json::string("core")),
+                ("thread", Json::number(thread.tid())),
+                ("signal", Json::optional(thread.signal(), Json::string)),
+                ("pc", Json::string(pc)),
+                (
+                    "function",
+                    Json::optional(target.function_name_at(pc), Json::string),
+                ),
+                (
+                    "location",
+                    Json::optional(target.source_location_at(pc).as_ref(), json::location),
+                ),
+            ],
+        );
+        return Ok(());
+    }
+
+    let mut message = format!("Thread {}", thread.tid());
+    if let Some(signal) = thread.signal() {
+        message += &format!(" stopped with signal {:?}", signal);
+    }
+    message += &format!(" at {}", pc);
+    if let Some(function) = target.function_name_at(pc) {
+        message += &format!(" in {}", function);
+    }
+    if let Some(location) = target.source_location_at(pc) {
+        message += &format!(" ({})", location);
+    }
+
+    info!("{}", message);
+
+    Ok(())
+}
+
+fn handle_thread_command(target: &mut sdb::CoreTarget, args: &[&str]) -> anyhow::Result<()> {
+    match args {
+        [] if json::enabled() => {
+            let threads = target
+                .threads()
+                .iter()
+                .map(|thread| {
+                    Json::object([
+                        ("tid", Json::number(thread.tid())),
+                        (
+                            "current",
+                            Json::Bool(thread.tid() == target.current_thread().tid()),
+                        ),
+                    ])
+                })
+                .collect();
+            json::emit("threads", [("threads", Json::Array(threads))]);
+        }
+        [] => {
+            for thread in target.threads() {
+                let current = thread.tid() == target.current_thread().tid();
+                println!("{} {}", if current { "*" } else { " " }, thread.tid());
+            }
+        }
+        [tid] => {
+            target.set_current_thread(nix::unistd::Pid::from_raw(tid.parse()?))?;
+            print_stop(target)?;
+        }
+        _ => anyhow::bail!("Usage: thread [tid]"),
+    }
+
+    Ok(())
+}
+
+type Handler = fn(&mut sdb::CoreTarget, &[&str]) -> anyhow::Result<()>;
+
+static COMMANDS: &[ReplCommand<Handler>] = &[
+    ReplCommand {
+        name: "backtrace",
+        aliases: &["bt"],
+        usage: "backtrace",
+        description: "Show the call stack of the current thread",
+        run: |target, args| {
+            let [] = args else {
+                anyhow::bail!("Usage: backtrace");
+            };
+
+            print_backtrace(target)
+        },
+    },
+    ReplCommand {
+        name: "register",
+        aliases: &[],
+        usage: "register read [name|all]",
+        description: "Read registers of the current thread",
+        run: |target, args| match args {
+            ["read", args @ ..] => print_registers(target, args),
+            _ => anyhow::bail!("Usage: register read [name|all], core dumps can't be changed"),
+        },
+    },
+    ReplCommand {
+        name: "info",
+        aliases: &[],
+        usage: INFO_USAGE,
+        description: "Show the current thread's thread-local storage, or the auxiliary vector",
+        run: |target, args| handle_info_command(target, args),
+    },
+    ReplCommand {
+        name: "thread",
+        aliases: &[],
+        usage: "thread [tid]",
+        description: "List the threads, or switch to one",
+        run: handle_thread_command,
+    },
+    ReplCommand {
+        name: "alias",
+        aliases: &[],
+        usage: commands::ALIAS_USAGE,
+        description: "Define your own names for commands, or list them",
+        run: |_, args| commands::alias(COMMANDS, args),
+    },
+    ReplCommand {
+        name: "help",
+        aliases: &[],
+        usage: "help [command]",
+        description: "List the commands, or show how to use one",
+        run: |_, args| commands::help(COMMANDS, args),
+    },
+];
+
+fn handle_command(target: &mut sdb::CoreTarget, command: String) -> anyhow::Result<()> {
+    commands::dispatch(COMMANDS, &command, |run, args| run(target, args))
+}
+
+/// Looks around a core dump, nothing can be run
+pub fn run(mut target: sdb::CoreTarget) -> anyhow::Result<()> {
+    info!(
+        "Core dumped by process {} from {}",
+        target.get_id(),
+        target.path().display()
+    );
+    print_stop(&target)?;
+
+    crate::run(|line| handle_command(&mut target, line))
+}
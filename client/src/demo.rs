@@ -0,0 +1,58 @@
+use std::io::BufRead;
+use std::path::PathBuf;
+
+use crate::handle_command;
+
+// (what's being shown, the command that shows it)
+const STEPS: &[(&str, &str)] = &[
+    ("Set a breakpoint on main", "break main"),
+    ("Run until the breakpoint is hit", "continue"),
+    ("Look at the general purpose registers", "register read"),
+    ("Step a single instruction", "stepi"),
+    ("The program counter has moved on", "register read rip"),
+    ("Break whenever the looper prints something", "break write"),
+    ("Continue to the next print", "continue"),
+    (
+        "The third argument is how many bytes it's writing",
+        "register read rdx",
+    ),
+];
+
+// the looper is installed alongside the client
+pub fn default_target() -> anyhow::Result<PathBuf> {
+    let path = std::env::current_exe()?.with_file_name("looper");
+    if !path.exists() {
+        anyhow::bail!(
+            "Demo target {} not found, install it with `cargo install --path looper`",
+            path.display()
+        );
+    }
+
+    Ok(path)
+}
+
+/// Walks through a scripted session against the demo target
+///
+/// Every step goes through the normal command handling,
+/// so any failure aborts the demo
+pub fn run(controller: &sdb::ProcessController, pause: bool) -> anyhow::Result<()> {
+    let mut stdin = std::io::stdin().lock();
+
+    for (idx, (description, command)) in STEPS.iter().enumerate() {
+        println!();
+        println!("[{}/{}] {}", idx + 1, STEPS.len(), description);
+        println!(">> {}", command);
+
+        if pause {
+            println!("(press enter to run it)");
+            stdin.read_line(&mut String::new())?;
+        }
+
+        handle_command(controller, *command)?;
+    }
+
+    println!();
+    println!("That's the tour, the session is yours from here");
+
+    Ok(())
+}
@@ -0,0 +1,176 @@
+use std::path::PathBuf;
+
+use sdb::Inferior;
+
+use crate::examine;
+use crate::json::{self, Json};
+
+pub const USAGE: &str = "disassemble[/s] [<function|address> [count]]";
+
+// from an address no symbol has, without a count
+const DEFAULT_COUNT: usize = 10;
+
+// a source line this far past the last one shown is shown on its own
+const MAX_GAP: u64 = 10;
+
+/// The instruction's text with the symbols its call or jump target and its
+/// rip-relative operand are in, like gdb shows them
+pub(crate) fn annotate(target: &sdb::Target, instruction: &sdb::Instruction) -> String {
+    let mut text = instruction.text.clone();
+    if let Some(address) = instruction.target {
+        text += &examine::symbol_suffix(target, address);
+    }
+    if let Some(referenced) = instruction.referenced {
+        text += &format!(
+            "        # {}",
+            examine::describe_address(target, referenced)
+        );
+    }
+    text
+}
+
+// a function's instructions, or count of them from an address
+fn instructions(
+    target: &sdb::Target,
+    location: Option<&str>,
+    count: Option<usize>,
+) -> anyhow::Result<Vec<sdb::Instruction>> {
+    let function = location.and_then(|name| target.modules().find_function(name));
+    let address = match (function, location) {
+        (Some((elf, symbol)), _) => elf.symbol_address(symbol),
+        (None, Some(location)) => {
+            let address = location
+                .parse::<sdb::expr::Expr>()?
+                .evaluate(target)
+                .map_err(|_| anyhow::anyhow!("No function or address {}", location))?;
+            sdb::VirtAddr::new(address as u64)
+        }
+        (None, None) => target.get_pc()?,
+    };
+    if let Some(count) = count {
+        return Ok(target.process().disassemble(address, count)?);
+    }
+
+    let symbol = target
+        .modules()
+        .get_elf_containing_symbol_address(address)
+        .and_then(|elf| Some((elf, elf.symbol_containing_address(address)?)))
+        .filter(|(_, symbol)| symbol.size > 0);
+    Ok(match symbol {
+        Some((elf, symbol)) => {
+            let low = elf.symbol_address(symbol);
+            target
+                .process()
+                .disassemble_range(low, low + symbol.size as i64)?
+        }
+        None => target.process().disassemble(address, DEFAULT_COUNT)?,
+    })
+}
+
+// the lines to show before an instruction on a new one, every line since the last one shown
+// when it's not far past that, so a loop's header isn't left out
+fn source_lines(
+    target: &sdb::Target,
+    last: &mut Option<(PathBuf, u64)>,
+    entry: &sdb::LineEntry,
+) -> (Option<PathBuf>, Vec<(u64, String)>) {
+    let file = target.source_map().resolve(&entry.file);
+    let (new_file, first) = match last {
+        Some((last_file, line)) if *last_file == file => match entry.line {
+            current if current == *line => return (None, Vec::new()),
+            current if current > *line && current - *line <= MAX_GAP => (None, *line + 1),
+            current => (None, current),
+        },
+        _ => (Some(file.clone()), entry.line),
+    };
+
+    let lines = target
+        .source_lines(&file, first, entry.line)
+        .unwrap_or_else(|_| vec![(entry.line, String::new())]);
+    *last = Some((file, entry.line));
+    (new_file, lines)
+}
+
+pub(crate) fn handle_disassemble_command(
+    target: &mut sdb::Target,
+    args: &[&str],
+) -> anyhow::Result<()> {
+    let (with_source, args) = match args {
+        ["/s", rest @ ..] => (true, rest),
+        [spec, ..] if spec.starts_with('/') => anyhow::bail!("Usage: {}", USAGE),
+        args => (false, args),
+    };
+    let (location, count) = match args {
+        [] => (None, None),
+        [location] => (Some(*location), None),
+        [location, count] => (Some(*location), Some(count.parse::<usize>()?)),
+        _ => anyhow::bail!("Usage: {}", USAGE),
+    };
+
+    let instructions = instructions(target, location, count)?;
+    let pc = target.get_pc().ok();
+
+    let mut last = None;
+    let mut json_lines = Vec::new();
+    for instruction in &instructions {
+        let source = match with_source {
+            true => target
+                .line_entry_at(instruction.address)
+                .map(|entry| source_lines(target, &mut last, entry)),
+            false => None,
+        };
+        let text = annotate(target, instruction);
+        let current = pc == Some(instruction.address);
+
+        if json::enabled() {
+            json_lines.push(Json::object([
+                ("address", Json::string(instruction.address)),
+                (
+                    "symbol",
+                    Json::string(examine::symbol_suffix(target, instruction.address).trim()),
+                ),
+                ("text", Json::string(&text)),
+                ("target", Json::optional(instruction.target, Json::string)),
+                ("current", Json::Bool(current)),
+                (
+                    "source",
+                    Json::Array(
+                        source
+                            .into_iter()
+                            .flat_map(|(_, lines)| lines)
+                            .map(|(line, text)| {
+                                Json::object([
+                                    ("line", Json::number(line)),
+                                    ("text", Json::string(text)),
+                                ])
+                            })
+                            .collect(),
+                    ),
+                ),
+            ]));
+            continue;
+        }
+
+        if let Some((file, lines)) = source {
+            if let Some(file) = file {
+                println!("{}:", file.display());
+            }
+            for (line, text) in lines {
+                println!("{}\t{}", line, text);
+            }
+        }
+        let marker = if current { "=> " } else { "   " };
+        println!(
+            "{}{}:\t{}",
+            marker,
+            examine::describe_address(target, instruction.address),
+            text
+        );
+    }
+
+    if json::enabled() {
+        json::emit("disassembly", [("instructions", Json::Array(json_lines))]);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,115 @@
+use std::sync::Mutex;
+
+use tracing::info;
+
+use crate::context::DebuggerContext;
+use crate::examine;
+use crate::json::{self, Json};
+
+pub const USAGE: &str = "display[/x|d|u|o|t|c] [<expression>]";
+
+pub const UNDISPLAY_USAGE: &str = "undisplay [<n>]...";
+
+// ids aren't reused, so undisplay 2 still means the same expression after 1's gone
+static DISPLAYS: Mutex<Displays> = Mutex::new(Displays {
+    next_id: 1,
+    expressions: Vec::new(),
+});
+
+struct Displays {
+    next_id: usize,
+    // the print arguments, /x and all
+    expressions: Vec<(usize, Vec<String>)>,
+}
+
+// an expression that can't be evaluated here, like a local of another function,
+// shows why in its place and stays on the list
+fn show(target: &mut sdb::Target, id: usize, args: &[String]) {
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    if json::enabled() {
+        json::emit(
+            "display",
+            [
+                ("id", Json::number(id)),
+                ("expression", Json::string(args.join(" "))),
+            ],
+        );
+    } else {
+        print!("{}: ", id);
+    }
+
+    if let Err(err) = examine::handle_print_command(target, &args) {
+        if json::enabled() {
+            json::emit(
+                "display_error",
+                [("id", Json::number(id)), ("message", Json::string(err))],
+            );
+        } else {
+            println!("<error: {}>", err);
+        }
+    }
+}
+
+/// Prints every display, after a stop or when asked to
+pub(crate) fn show_all(target: &mut sdb::Target) {
+    let expressions = DISPLAYS.lock().unwrap().expressions.clone();
+    for (id, args) in &expressions {
+        show(target, *id, args);
+    }
+}
+
+pub(crate) fn handle_display_command(
+    context: &dyn DebuggerContext,
+    args: &[&str],
+) -> anyhow::Result<()> {
+    if args.is_empty() {
+        return context.with_target(Box::new(|target| {
+            show_all(target);
+            Ok(())
+        }));
+    }
+    examine::check_print_args(args).map_err(|_| anyhow::anyhow!("Usage: {}", USAGE))?;
+
+    let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+    let id = {
+        let mut displays = DISPLAYS.lock().unwrap();
+        let id = displays.next_id;
+        displays.next_id += 1;
+        displays.expressions.push((id, args.clone()));
+        id
+    };
+
+    context.with_target(Box::new(move |target| {
+        show(target, id, &args);
+        Ok(())
+    }))
+}
+
+pub(crate) fn handle_undisplay_command(
+    _: &dyn DebuggerContext,
+    args: &[&str],
+) -> anyhow::Result<()> {
+    let ids = args
+        .iter()
+        .map(|id| id.parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| anyhow::anyhow!("Usage: {}", UNDISPLAY_USAGE))?;
+
+    let mut displays = DISPLAYS.lock().unwrap();
+    if ids.is_empty() {
+        let expressions = std::mem::take(&mut displays.expressions);
+        info!("Deleted {} displays", expressions.len());
+        return Ok(());
+    }
+
+    // all or nothing, so a typo doesn't leave half of them deleted
+    if let Some(id) = ids
+        .iter()
+        .find(|id| !displays.expressions.iter().any(|(other, _)| other == *id))
+    {
+        anyhow::bail!("No display {}", id);
+    }
+    displays.expressions.retain(|(id, _)| !ids.contains(id));
+
+    Ok(())
+}
@@ -0,0 +1,209 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::io::Write;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+
+use tracing::error;
+
+use crate::context::DebuggerContext;
+use crate::json::{self, Json};
+use crate::trace;
+use crate::{state_name, watched_value};
+
+pub const USAGE: &str = "events [count]";
+
+// older events are dropped past this
+const CAPACITY: usize = 10_000;
+
+const DEFAULT_COUNT: usize = 20;
+
+// the session's events in the order they happened, output lines with the times they were read
+static LOG: Mutex<VecDeque<Entry>> = Mutex::new(VecDeque::new());
+
+// logpoints whose messages go to a file rather than the terminal
+static LOG_FILES: Mutex<BTreeMap<sdb::StoppointId, File>> = Mutex::new(BTreeMap::new());
+
+// times are shown relative to this
+static SESSION_START: OnceLock<SystemTime> = OnceLock::new();
+
+struct Entry {
+    time: SystemTime,
+    event: sdb::DebugEvent,
+}
+
+impl Entry {
+    fn elapsed(&self) -> Duration {
+        let start = *SESSION_START.get_or_init(SystemTime::now);
+        self.time.duration_since(start).unwrap_or_default()
+    }
+
+    fn kind(&self) -> &'static str {
+        match &self.event {
+            sdb::DebugEvent::Stopped(reason) => state_name(reason.reason),
+            sdb::DebugEvent::BreakpointHit { .. } => "breakpoint",
+            sdb::DebugEvent::WatchpointHit { .. } => "watchpoint",
+            sdb::DebugEvent::LibraryLoaded(_) => "library-loaded",
+            sdb::DebugEvent::LibraryUnloaded(_) => "library-unloaded",
+            sdb::DebugEvent::ThreadCreated(_) => "thread-created",
+            sdb::DebugEvent::ThreadExited(_) => "thread-exited",
+            sdb::DebugEvent::Exited(_) => "exited",
+            sdb::DebugEvent::Terminated(_) => "terminated",
+            sdb::DebugEvent::Logged { .. } => "log",
+            sdb::DebugEvent::Syscall(_) => "syscall",
+            sdb::DebugEvent::Allocation(_) => "allocation",
+            sdb::DebugEvent::Output { stream, .. } => stream.as_str(),
+            _ => "event",
+        }
+    }
+
+    fn text(&self) -> String {
+        match &self.event {
+            sdb::DebugEvent::Stopped(reason) => match reason.signal() {
+                Some(signal) => signal.as_str().to_owned(),
+                None => reason.info.to_string(),
+            },
+            sdb::DebugEvent::BreakpointHit { id, address } => {
+                format!("{} at {:#x}", id, address.addr())
+            }
+            sdb::DebugEvent::WatchpointHit { id, old, new } => match old {
+                Some(old) => format!("{}: {} -> {}", id, watched_value(old), watched_value(new)),
+                None => format!("{}: {}", id, watched_value(new)),
+            },
+            sdb::DebugEvent::LibraryLoaded(path) | sdb::DebugEvent::LibraryUnloaded(path) => {
+                path.display().to_string()
+            }
+            sdb::DebugEvent::ThreadCreated(tid) | sdb::DebugEvent::ThreadExited(tid) => {
+                tid.to_string()
+            }
+            sdb::DebugEvent::Exited(code) => code.to_string(),
+            sdb::DebugEvent::Terminated(signal) => signal
+                .map(|signal| signal.as_str().to_owned())
+                .unwrap_or_default(),
+            sdb::DebugEvent::Logged { id, message } => {
+                format!("{}: {}", id, message.trim_end_matches('\n'))
+            }
+            sdb::DebugEvent::Syscall(record) => format!("[{}] {}", record.tid, record),
+            sdb::DebugEvent::Allocation(event) => format!("[{}] {}", event.tid, event),
+            sdb::DebugEvent::Output { line, .. } => line.clone(),
+            event => format!("{:?}", event),
+        }
+    }
+
+    fn to_json(&self) -> Json {
+        Json::object([
+            ("elapsed", Json::number(self.elapsed().as_secs_f64())),
+            ("kind", Json::string(self.kind())),
+            ("text", Json::string(self.text())),
+        ])
+    }
+}
+
+impl std::fmt::Display for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:>12.6}s  {:<16}  {}",
+            self.elapsed().as_secs_f64(),
+            self.kind(),
+            self.text()
+        )
+    }
+}
+
+// logpoints print as they're hit, one line each whether or not the format ends with a newline
+fn print_logged(id: sdb::StoppointId, message: &str) {
+    if let Some(file) = LOG_FILES.lock().unwrap().get_mut(&id) {
+        let line = message.strip_suffix('\n').unwrap_or(message);
+        if let Err(err) = writeln!(file, "{}", line) {
+            error!("Couldn't write logpoint {}'s message: {}", id, err);
+        }
+        return;
+    }
+
+    if json::enabled() {
+        json::emit(
+            "log",
+            [("id", Json::number(id)), ("message", Json::string(message))],
+        );
+    } else {
+        println!("{}", message.strip_suffix('\n').unwrap_or(message));
+    }
+}
+
+/// Sends a logpoint's messages to a file from now on, they're still in the log
+pub fn log_to_file(id: sdb::StoppointId, file: File) {
+    LOG_FILES.lock().unwrap().insert(id, file);
+}
+
+/// Starts recording the process's events into the session's log
+pub fn record(controller: &sdb::ProcessController) -> anyhow::Result<()> {
+    SESSION_START.get_or_init(SystemTime::now);
+
+    let events = controller.execute_target(|target| target.events())??;
+    std::thread::spawn(move || {
+        for event in events {
+            // output knows when it was read, anything else is stamped as it comes in
+            let time = match &event {
+                sdb::DebugEvent::Output { time, .. } => *time,
+                _ => SystemTime::now(),
+            };
+
+            match &event {
+                sdb::DebugEvent::Logged { id, message } => print_logged(*id, message),
+                sdb::DebugEvent::Syscall(record) => trace::print_syscall(record),
+                sdb::DebugEvent::Allocation(event) => trace::print_allocation(event),
+                _ => (),
+            }
+
+            let mut log = LOG.lock().unwrap();
+            if log.len() == CAPACITY {
+                log.pop_front();
+            }
+            log.push_back(Entry { time, event });
+        }
+    });
+
+    Ok(())
+}
+
+pub(crate) fn handle_events_command(_: &dyn DebuggerContext, args: &[&str]) -> anyhow::Result<()> {
+    let count = match args {
+        [] => DEFAULT_COUNT,
+        [count] => count.parse()?,
+        _ => anyhow::bail!("Usage: {}", USAGE),
+    };
+
+    let log = LOG.lock().unwrap();
+    let entries = log.iter().skip(log.len().saturating_sub(count));
+
+    if json::enabled() {
+        let entries = entries.map(Entry::to_json).collect();
+        json::emit("events", [("events", Json::Array(entries))]);
+        return Ok(());
+    }
+
+    if log.is_empty() {
+        println!("No events yet");
+    }
+    for entry in entries {
+        println!("{}", entry);
+    }
+
+    Ok(())
+}
+
+/// Writes the whole log to a file, one JSON object a line for a trace, returns how many events
+pub(crate) fn export(path: &str, trace: bool) -> anyhow::Result<usize> {
+    let mut file = File::create(path)?;
+
+    let log = LOG.lock().unwrap();
+    for entry in log.iter() {
+        match trace {
+            true => writeln!(file, "{}", entry.to_json())?,
+            false => writeln!(file, "{}", entry)?,
+        }
+    }
+
+    Ok(log.len())
+}
@@ -0,0 +1,610 @@
+use std::sync::Mutex;
+
+use sdb::Inferior;
+use tracing::info;
+
+use crate::disassemble;
+use crate::json::{self, Json};
+use crate::source;
+
+pub const PRINT_USAGE: &str = "print[/x|d|u|o|t|c] <expression>";
+
+pub const SET_USAGE: &str = "set var <variable> = <value> | set substitute-path [<from> <to>|--delete <from>] | set follow-fork-mode [parent|child] | set inferior-tty [<tty>] | set scheduler-locking [on|off] | set step-into-no-debug [on|off]";
+
+pub const EXAMINE_USAGE: &str = "x[/<count><x|d|u|o|t|c|s|i><b|h|w|g>] [expression]";
+
+pub const FIND_USAGE: &str =
+    "find[/b|h|w|g] [--perms <rwx>] [--in <mapping>] <\"string\"|expression[, expression]...>";
+
+// x/s stops here if there's no NUL before it
+const MAX_STRING: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Hex,
+    Signed,
+    Unsigned,
+    Octal,
+    Binary,
+    Char,
+    String,
+    Instruction,
+}
+
+impl Format {
+    fn from_char(c: char) -> Option<Self> {
+        Some(match c {
+            'x' => Self::Hex,
+            'd' => Self::Signed,
+            'u' => Self::Unsigned,
+            'o' => Self::Octal,
+            't' => Self::Binary,
+            'c' => Self::Char,
+            's' => Self::String,
+            'i' => Self::Instruction,
+            _ => return None,
+        })
+    }
+
+    // how many fit on a line at each size
+    fn per_line(&self, size: usize) -> usize {
+        match (self, size) {
+            (Self::Binary, 8) => 1,
+            (Self::Binary, _) => 4,
+            (_, 8) => 2,
+            (_, 4) => 4,
+            _ => 8,
+        }
+    }
+
+    // memory is padded to the size it was read at, a printed value is as short as it can be
+    fn render(&self, value: u64, size: Option<usize>) -> String {
+        let bits = size.unwrap_or(8) as u32 * 8;
+        let digits = |per_digit: u32| match size {
+            Some(_) => bits.div_ceil(per_digit) as usize,
+            None => 1,
+        };
+        match self {
+            Self::Hex => format!("0x{:0width$x}", value, width = digits(4)),
+            Self::Signed => {
+                // sign extend from the size that was read
+                let shift = 64 - bits;
+                (((value << shift) as i64) >> shift).to_string()
+            }
+            Self::Unsigned => value.to_string(),
+            Self::Octal if value == 0 => "0".to_owned(),
+            Self::Octal => format!("0{:o}", value),
+            Self::Binary => format!("{:0width$b}", value, width = digits(1)),
+            Self::Char => format!("{} {:?}", value as u8 as i8, char::from(value as u8)),
+            Self::String | Self::Instruction => unreachable!(),
+        }
+    }
+}
+
+// x/<count><format><size>, any of them can be left out
+#[derive(Debug, Clone, Copy)]
+struct Spec {
+    count: usize,
+    format: Format,
+    size: usize,
+}
+
+// x with no expression carries on after what the last one showed, in the same format
+static LAST: Mutex<Option<(Spec, sdb::VirtAddr)>> = Mutex::new(None);
+
+fn parse_spec(spec: &str, last: Option<Spec>) -> anyhow::Result<Spec> {
+    let digits = spec
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(spec.len());
+    let (count, letters) = spec.split_at(digits);
+
+    let mut format = None;
+    let mut size = None;
+    for c in letters.chars() {
+        match (c, Format::from_char(c)) {
+            ('b', _) => size = Some(1),
+            ('h', _) => size = Some(2),
+            ('w', _) => size = Some(4),
+            ('g', _) => size = Some(8),
+            (_, Some(f)) => format = Some(f),
+            _ => anyhow::bail!("Unknown format letter '{}', usage: {}", c, EXAMINE_USAGE),
+        }
+    }
+
+    let format = format
+        .or(last.map(|last| last.format))
+        .unwrap_or(Format::Hex);
+    let size = size.unwrap_or(match (format, last) {
+        (Format::Char, _) => 1,
+        (_, Some(last)) if last.format != Format::Char => last.size,
+        _ => 4,
+    });
+
+    Ok(Spec {
+        count: if count.is_empty() { 1 } else { count.parse()? },
+        format,
+        size,
+    })
+}
+
+// print/x and x/4xg come through as the command with the spec as its first argument
+fn split_spec<'a, 'b>(args: &'a [&'b str]) -> (Option<&'b str>, &'a [&'b str]) {
+    match args.split_first() {
+        Some((spec, rest)) if spec.starts_with('/') => (Some(&spec[1..]), rest),
+        _ => (None, args),
+    }
+}
+
+fn evaluate(target: &sdb::Target, args: &[&str]) -> anyhow::Result<(String, i64)> {
+    let expr = args.join(" ").parse::<sdb::expr::Expr>()?;
+    let value = expr.evaluate(target)?;
+    Ok((expr.to_string(), value))
+}
+
+// name(arg, ...), a comma inside parentheses belongs to the argument
+fn parse_call(text: &str) -> Option<(&str, Vec<&str>)> {
+    let (name, rest) = text.split_once('(')?;
+    let name = name.trim();
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+    {
+        return None;
+    }
+    let inner = rest.trim_end().strip_suffix(')')?;
+    if inner.trim().is_empty() {
+        return Some((name, Vec::new()));
+    }
+
+    let mut args = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (idx, c) in inner.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return None,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(inner[start..idx].trim());
+                start = idx + 1;
+            }
+            _ => (),
+        }
+    }
+    args.push(inner[start..].trim());
+    Some((name, args))
+}
+
+// the function runs in the process, with each argument an expression
+fn print_call(
+    target: &mut sdb::Target,
+    name: &str,
+    args: &[&str],
+    format: Format,
+    spec: Option<&str>,
+) -> anyhow::Result<()> {
+    let args = args
+        .iter()
+        .map(|arg| arg.parse::<sdb::expr::Expr>())
+        .collect::<Result<Vec<_>, _>>()?;
+    let values = args
+        .iter()
+        .map(|arg| arg.evaluate(&*target).map(|value| value as u64))
+        .collect::<Result<Vec<_>, _>>()?;
+    let radix = match format {
+        Format::Hex => sdb::Radix::Hex,
+        _ => sdb::Radix::Decimal,
+    };
+
+    let call = format!(
+        "{}({})",
+        name,
+        args.iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let value = target
+        .call_function_by_name(name, &values, radix)?
+        .unwrap_or_else(|| sdb::VariableValue {
+            type_name: "void".to_owned(),
+            address: None,
+            scalar: None,
+            text: "void".to_owned(),
+        });
+    print_variable(call, value, spec.map(|_| format));
+
+    Ok(())
+}
+
+fn print_format(spec: Option<&str>) -> anyhow::Result<Format> {
+    match spec.map(|spec| spec.chars().collect::<Vec<_>>()).as_deref() {
+        None => Ok(Format::Signed),
+        Some(&[c]) => Format::from_char(c)
+            .filter(|format| !matches!(format, Format::String | Format::Instruction))
+            .ok_or_else(|| anyhow::anyhow!("Usage: {}", PRINT_USAGE)),
+        Some(_) => anyhow::bail!("Usage: {}", PRINT_USAGE),
+    }
+}
+
+// what print would accept, without evaluating it
+pub(crate) fn check_print_args(args: &[&str]) -> anyhow::Result<()> {
+    let (spec, args) = split_spec(args);
+    if args.is_empty() {
+        anyhow::bail!("Usage: {}", PRINT_USAGE);
+    }
+    print_format(spec)?;
+    Ok(())
+}
+
+pub(crate) fn handle_print_command(target: &mut sdb::Target, args: &[&str]) -> anyhow::Result<()> {
+    let (spec, args) = split_spec(args);
+    if args.is_empty() {
+        anyhow::bail!("Usage: {}", PRINT_USAGE);
+    }
+    let format = print_format(spec)?;
+
+    if let Some((name, call_args)) = parse_call(&args.join(" ")) {
+        return print_call(target, name, &call_args, format, spec);
+    }
+
+    // a parameter or local of the current function is shown as its type says
+    if let Ok(path) = args.join(" ").parse::<sdb::VariablePath>() {
+        let radix = match format {
+            Format::Hex => sdb::Radix::Hex,
+            _ => sdb::Radix::Decimal,
+        };
+        match target.read_variable(&path, radix) {
+            Ok(variable) => {
+                print_variable(path, variable, spec.map(|_| format));
+                return Ok(());
+            }
+            // an expression can't have fields, so that's the error that makes sense
+            Err(err) if !path.accessors.is_empty() && evaluate(target, args).is_err() => {
+                return Err(err.into());
+            }
+            Err(_) => (),
+        }
+    }
+
+    let (expr, value) = evaluate(target, args)?;
+    print_value(expr, value, format);
+
+    Ok(())
+}
+
+fn print_value(expr: String, value: i64, format: Format) {
+    let text = format.render(value as u64, None);
+    if json::enabled() {
+        json::emit(
+            "value",
+            [
+                ("expression", Json::string(expr)),
+                ("value", Json::number(value)),
+                ("text", Json::string(text)),
+            ],
+        );
+    } else {
+        println!("{} = {}", expr, text);
+    }
+}
+
+// /x and /d are the radix the value's formatted with, /o and the like only make sense for a
+// value that fits in a register
+fn print_variable(
+    expr: impl std::fmt::Display,
+    variable: sdb::VariableValue,
+    format: Option<Format>,
+) {
+    let text = match (format, variable.scalar) {
+        (Some(format), Some(scalar)) if !matches!(format, Format::Hex | Format::Signed) => {
+            format.render(scalar, None)
+        }
+        _ => variable.text,
+    };
+    if json::enabled() {
+        json::emit(
+            "value",
+            [
+                ("expression", Json::string(expr)),
+                ("type", Json::string(&variable.type_name)),
+                ("address", Json::optional(variable.address, Json::string)),
+                ("value", Json::optional(variable.scalar, Json::number)),
+                ("text", Json::string(text)),
+            ],
+        );
+    } else {
+        println!("({}) {} = {}", variable.type_name, expr, text);
+    }
+}
+
+// set var x = 5, or set variable, and set substitute-path, gdb has other things to set that sdb doesn't
+pub(crate) fn handle_set_command(target: &mut sdb::Target, args: &[&str]) -> anyhow::Result<()> {
+    let args = match args {
+        ["substitute-path", args @ ..] => return source::handle_substitute_path(target, args),
+        ["follow-fork-mode", args @ ..] => return set_follow_fork_mode(target, args),
+        ["inferior-tty", args @ ..] => return set_inferior_tty(target, args),
+        ["scheduler-locking", args @ ..] => return set_scheduler_locking(target, args),
+        ["step-into-no-debug", args @ ..] => return set_step_into_no_debug(target, args),
+        ["var" | "variable", args @ ..] => args,
+        _ => anyhow::bail!("Usage: {}", SET_USAGE),
+    };
+    let assignment = args.join(" ");
+    let Some((path, value)) = assignment.split_once('=') else {
+        anyhow::bail!("Usage: {}", SET_USAGE);
+    };
+    if value.trim().is_empty() {
+        anyhow::bail!("Usage: {}", SET_USAGE);
+    }
+
+    let path = path.parse::<sdb::VariablePath>()?;
+    let variable = target.write_variable(&path, value)?;
+    print_variable(path, variable, None);
+
+    Ok(())
+}
+
+fn set_follow_fork_mode(target: &mut sdb::Target, args: &[&str]) -> anyhow::Result<()> {
+    let mode = match args {
+        [] => {
+            let mode = match target.process().follow_fork_mode() {
+                sdb::FollowForkMode::Parent => "parent",
+                sdb::FollowForkMode::Child => "child",
+            };
+            info!("Following the {} of a fork", mode);
+            return Ok(());
+        }
+        ["parent"] => sdb::FollowForkMode::Parent,
+        ["child"] => sdb::FollowForkMode::Child,
+        _ => anyhow::bail!("Usage: set follow-fork-mode [parent|child]"),
+    };
+    target.process_mut().set_follow_fork_mode(mode);
+
+    Ok(())
+}
+
+fn set_scheduler_locking(target: &mut sdb::Target, args: &[&str]) -> anyhow::Result<()> {
+    let locking = match args {
+        [] => {
+            match target.process().scheduler_locking() {
+                true => info!("Only the current thread runs when resumed"),
+                false => info!("All threads run when resumed"),
+            }
+            return Ok(());
+        }
+        ["on"] => true,
+        ["off"] => false,
+        _ => anyhow::bail!("Usage: set scheduler-locking [on|off]"),
+    };
+    target.process_mut().set_scheduler_locking(locking);
+
+    Ok(())
+}
+
+fn set_step_into_no_debug(target: &mut sdb::Target, args: &[&str]) -> anyhow::Result<()> {
+    let step_into = match args {
+        [] => {
+            match target.step_into_no_debug() {
+                true => info!("Step stops in functions without line info"),
+                false => info!("Step runs over functions without line info"),
+            }
+            return Ok(());
+        }
+        ["on"] => true,
+        ["off"] => false,
+        _ => anyhow::bail!("Usage: set step-into-no-debug [on|off]"),
+    };
+    target.set_step_into_no_debug(step_into);
+
+    Ok(())
+}
+
+fn set_inferior_tty(target: &mut sdb::Target, args: &[&str]) -> anyhow::Result<()> {
+    match args {
+        [] => match target.inferior_tty() {
+            Some(tty) => info!("The process runs on {}", tty.display()),
+            None => info!("No inferior tty is set"),
+        },
+        [tty] => {
+            target.set_inferior_tty(tty)?;
+            info!("The process runs on {} from the next restart", tty);
+        }
+        _ => anyhow::bail!("Usage: set inferior-tty [<tty>]"),
+    }
+
+    Ok(())
+}
+
+// " <main+4>", or nothing if no symbol has the address
+pub(crate) fn symbol_suffix(target: &sdb::Target, address: sdb::VirtAddr) -> String {
+    target
+        .modules()
+        .get_elf_containing_symbol_address(address)
+        .and_then(|elf| {
+            let symbol = elf.symbol_containing_address(address)?;
+            let offset = address.addr() - elf.symbol_address(symbol).addr();
+            Some(match offset {
+                0 => format!(" <{}>", symbol.demangled_name),
+                offset => format!(" <{}+{}>", symbol.demangled_name, offset),
+            })
+        })
+        .unwrap_or_default()
+}
+
+// main+4 style, for the start of each line
+pub(crate) fn describe_address(target: &sdb::Target, address: sdb::VirtAddr) -> String {
+    format!("{}{}", address, symbol_suffix(target, address))
+}
+
+// each line is where it starts and what's there
+type Lines = Vec<(sdb::VirtAddr, Vec<String>)>;
+
+// returns where the next x carries on from too
+fn examine(
+    target: &sdb::Target,
+    spec: Spec,
+    mut address: sdb::VirtAddr,
+) -> anyhow::Result<(Lines, sdb::VirtAddr)> {
+    let mut lines = Vec::new();
+    match spec.format {
+        Format::Instruction => {
+            for instruction in target.process().disassemble(address, spec.count)? {
+                address = instruction.address + instruction.bytes.len() as i64;
+                let text = disassemble::annotate(target, &instruction);
+                lines.push((instruction.address, vec![text]));
+            }
+        }
+        Format::String => {
+            for _ in 0..spec.count {
+                let string = target.read_c_string(address, MAX_STRING)?;
+                let text = String::from_utf8_lossy(&string);
+                lines.push((address, vec![format!("{:?}", text)]));
+                // the next one starts past the NUL, if there was one
+                address += string.len().min(MAX_STRING - 1) as i64 + 1;
+            }
+        }
+        format => {
+            let memory = target.read_memory_without_traps(address, spec.count * spec.size)?;
+            for line in memory.chunks(spec.size * format.per_line(spec.size)) {
+                let values = line
+                    .chunks(spec.size)
+                    .map(|bytes| {
+                        let mut value = [0; 8];
+                        value[..bytes.len()].copy_from_slice(bytes);
+                        format.render(u64::from_le_bytes(value), Some(spec.size))
+                    })
+                    .collect();
+                lines.push((address, values));
+                address += line.len() as i64;
+            }
+        }
+    }
+    Ok((lines, address))
+}
+
+pub(crate) fn handle_examine_command(target: &sdb::Target, args: &[&str]) -> anyhow::Result<()> {
+    let (spec, args) = split_spec(args);
+
+    let mut last = LAST.lock().unwrap();
+    let spec = parse_spec(spec.unwrap_or_default(), last.map(|(spec, _)| spec))?;
+    let address = match (args, *last) {
+        ([], Some((_, next))) => next,
+        ([], None) => anyhow::bail!("Usage: {}", EXAMINE_USAGE),
+        (args, _) => sdb::VirtAddr::new(evaluate(target, args)?.1 as u64),
+    };
+
+    let (lines, next) = examine(target, spec, address)?;
+    *last = Some((spec, next));
+
+    if json::enabled() {
+        let lines = lines
+            .into_iter()
+            .map(|(address, values)| {
+                Json::object([
+                    ("address", Json::string(address)),
+                    (
+                        "values",
+                        Json::Array(values.into_iter().map(Json::string).collect()),
+                    ),
+                ])
+            })
+            .collect();
+        json::emit("memory", [("lines", Json::Array(lines))]);
+        return Ok(());
+    }
+
+    for (address, values) in lines {
+        println!(
+            "{}:  {}",
+            describe_address(target, address),
+            values.join("  ")
+        );
+    }
+
+    Ok(())
+}
+
+// the bytes to look for, a string without its NUL or each value at the spec's size
+fn find_pattern(target: &sdb::Target, text: &str, size: usize) -> anyhow::Result<Vec<u8>> {
+    if text.starts_with('"') {
+        let (string, rest) = crate::parse_quoted(text, FIND_USAGE)?;
+        if !rest.trim().is_empty() {
+            anyhow::bail!("Usage: {}", FIND_USAGE);
+        }
+        return Ok(string.into_bytes());
+    }
+
+    let mut pattern = Vec::new();
+    for expr in text.split(',') {
+        let value = expr.parse::<sdb::expr::Expr>()?.evaluate(target)?;
+        pattern.extend_from_slice(&value.to_le_bytes()[..size]);
+    }
+    Ok(pattern)
+}
+
+pub(crate) fn handle_find_command(target: &sdb::Target, args: &[&str]) -> anyhow::Result<()> {
+    let (spec, mut args) = split_spec(args);
+    let size = match spec {
+        None | Some("w") => 4,
+        Some("b") => 1,
+        Some("h") => 2,
+        Some("g") => 8,
+        Some(_) => anyhow::bail!("Usage: {}", FIND_USAGE),
+    };
+
+    let mut regions = sdb::SearchRegions::default();
+    loop {
+        regions = match args {
+            ["--perms", permissions, rest @ ..] => {
+                args = rest;
+                regions.permissions(permissions)?
+            }
+            ["--in", name, rest @ ..] => {
+                args = rest;
+                regions.name(*name)
+            }
+            _ => break,
+        };
+    }
+    if args.is_empty() {
+        anyhow::bail!("Usage: {}", FIND_USAGE);
+    }
+
+    let text = args.join(" ");
+    let pattern = find_pattern(target, &text, size)?;
+    let matches = target.process().search_memory(&pattern, &regions)?;
+
+    if json::enabled() {
+        let matches = matches
+            .into_iter()
+            .map(|found| {
+                Json::object([
+                    ("address", Json::string(found.address)),
+                    ("mapping", Json::string(found.mapping)),
+                ])
+            })
+            .collect();
+        json::emit(
+            "matches",
+            [
+                ("pattern", Json::string(text)),
+                ("matches", Json::Array(matches)),
+            ],
+        );
+        return Ok(());
+    }
+
+    for found in &matches {
+        match found.mapping.is_empty() {
+            true => println!("{}", describe_address(target, found.address)),
+            false => println!(
+                "{} in {}",
+                describe_address(target, found.address),
+                found.mapping
+            ),
+        }
+    }
+    info!("{} matches for {} bytes", matches.len(), pattern.len());
+
+    Ok(())
+}
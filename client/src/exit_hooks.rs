@@ -0,0 +1,191 @@
+use std::sync::Mutex;
+
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
+use tracing::{error, info, warn};
+
+use crate::context::DebuggerContext;
+use crate::json::{self, Json};
+use crate::{event_log, handle_command, scripting};
+
+pub const USAGE: &str = "on-exit [core [path]|events <path>|trace <path>|script <path>|command <line>|delete <n>|clear]";
+
+// signals the process won't live through
+const CRASH_SIGNALS: &[Signal] = &[
+    Signal::SIGSEGV,
+    Signal::SIGBUS,
+    Signal::SIGILL,
+    Signal::SIGFPE,
+    Signal::SIGABRT,
+];
+
+// run in the order they were added, paths can have {pid} in them
+static ACTIONS: Mutex<Vec<Action>> = Mutex::new(Vec::new());
+
+// a crash and the termination that follows it only run the actions once
+static FIRED_FOR: Mutex<Option<Pid>> = Mutex::new(None);
+
+#[derive(Debug, Clone)]
+enum Action {
+    Core(Option<String>),
+    Events(String),
+    Trace(String),
+    Script(String),
+    Command(String),
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::Core(Some(path)) => write!(f, "core {}", path),
+            Action::Core(None) => write!(f, "core"),
+            Action::Events(path) => write!(f, "events {}", path),
+            Action::Trace(path) => write!(f, "trace {}", path),
+            Action::Script(path) => write!(f, "script {}", path),
+            Action::Command(line) => write!(f, "command {}", line),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Trigger {
+    // still there to be looked at
+    Crashed,
+    Gone,
+}
+
+fn trigger(info: &sdb::StopInfo) -> Option<Trigger> {
+    match info.reason.reason {
+        sdb::ProcessState::Exited | sdb::ProcessState::Terminated => Some(Trigger::Gone),
+        sdb::ProcessState::Stopped
+            if info.reason.fatal_function.is_some()
+                || info
+                    .signal()
+                    .is_some_and(|signal| CRASH_SIGNALS.contains(&signal)) =>
+        {
+            Some(Trigger::Crashed)
+        }
+        _ => None,
+    }
+}
+
+fn expand(path: &str, pid: Pid) -> String {
+    path.replace("{pid}", &pid.to_string())
+}
+
+fn run_action(
+    controller: &sdb::ProcessController,
+    action: &Action,
+    trigger: Trigger,
+) -> anyhow::Result<()> {
+    let pid = controller.handle().get_id();
+    match action {
+        Action::Core(_) if trigger == Trigger::Gone => {
+            warn!("Process {} is gone, no core to write", pid);
+        }
+        Action::Core(path) => {
+            let path = expand(path.as_deref().unwrap_or("core.{pid}"), pid);
+            let (path, transfer) = controller.execute(move |process| {
+                process.write_core(&path).map(|transfer| (path, transfer))
+            })??;
+            info!("Saved core file {}", path);
+            info!("Read {}", transfer);
+        }
+        Action::Events(path) | Action::Trace(path) => {
+            let path = expand(path, pid);
+            let count = event_log::export(&path, matches!(action, Action::Trace(_)))?;
+            info!("Saved {} events to {}", count, path);
+        }
+        Action::Script(path) => scripting::run_script(controller, &expand(path, pid))?,
+        Action::Command(line) => handle_command(controller, line)?,
+    }
+
+    Ok(())
+}
+
+/// Runs the exit actions the first time the process crashes or goes away
+pub(crate) fn run_exit_hooks(controller: &sdb::ProcessController, info: &sdb::StopInfo) {
+    let Some(trigger) = trigger(info) else {
+        return;
+    };
+
+    let pid = controller.handle().get_id();
+    if FIRED_FOR.lock().unwrap().replace(pid) == Some(pid) {
+        return;
+    }
+
+    // an action can run commands that stop the process again
+    let actions = ACTIONS.lock().unwrap().clone();
+    if actions.is_empty() {
+        return;
+    }
+
+    info!("Running {} exit actions ...", actions.len());
+    for action in &actions {
+        if let Err(err) = run_action(controller, action, trigger) {
+            error!("Exit action '{}' failed: {}", action, err);
+        }
+    }
+}
+
+fn print_actions() {
+    let actions = ACTIONS.lock().unwrap();
+
+    if json::enabled() {
+        let actions = actions
+            .iter()
+            .enumerate()
+            .map(|(idx, action)| {
+                Json::object([
+                    ("id", Json::number(idx + 1)),
+                    ("action", Json::string(action)),
+                ])
+            })
+            .collect();
+        json::emit("exit_actions", [("actions", Json::Array(actions))]);
+        return;
+    }
+
+    if actions.is_empty() {
+        println!("No exit actions");
+    }
+    for (idx, action) in actions.iter().enumerate() {
+        println!("{}: {}", idx + 1, action);
+    }
+}
+
+pub(crate) fn handle_on_exit_command(_: &dyn DebuggerContext, args: &[&str]) -> anyhow::Result<()> {
+    let action = match args {
+        [] => {
+            print_actions();
+            return Ok(());
+        }
+        ["clear"] => {
+            let actions = std::mem::take(&mut *ACTIONS.lock().unwrap());
+            info!("Cleared {} exit actions", actions.len());
+            return Ok(());
+        }
+        ["delete", id] => {
+            let id = id.parse::<usize>()?;
+            let mut actions = ACTIONS.lock().unwrap();
+            if id == 0 || id > actions.len() {
+                anyhow::bail!("No exit action {}", id);
+            }
+            actions.remove(id - 1);
+            return Ok(());
+        }
+        ["core"] => Action::Core(None),
+        ["core", path] => Action::Core(Some(path.to_string())),
+        ["events", path] => Action::Events(path.to_string()),
+        ["trace", path] => Action::Trace(path.to_string()),
+        ["script", path] => Action::Script(path.to_string()),
+        ["command", line @ ..] if !line.is_empty() => Action::Command(line.join(" ")),
+        _ => anyhow::bail!("Usage: {}", USAGE),
+    };
+
+    let mut actions = ACTIONS.lock().unwrap();
+    actions.push(action);
+    info!("Exit action {}: {}", actions.len(), actions.last().unwrap());
+
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use tracing::{info, warn};
+
+use crate::checksec;
+use crate::examine;
+use crate::json::{self, Json};
+
+fn status_text(status: &sdb::GotStatus) -> &str {
+    match status {
+        sdb::GotStatus::Lazy => "lazy",
+        sdb::GotStatus::Resolved => "resolved",
+        sdb::GotStatus::Unrelocated => "unrelocated",
+        sdb::GotStatus::Null => "null",
+        sdb::GotStatus::Unexpected(_) => "unexpected",
+    }
+}
+
+pub(crate) fn handle_info_got(target: &sdb::Target, args: &[&str]) -> anyhow::Result<()> {
+    let elf = match args {
+        [] => target
+            .main_elf()
+            .ok_or_else(|| anyhow::anyhow!("Couldn't find the executable's module"))?,
+        [name] => checksec::find_module(target, name)?,
+        _ => anyhow::bail!("Usage: info got [module]"),
+    };
+    let entries = target.got(elf)?;
+
+    if json::enabled() {
+        let entries = entries
+            .iter()
+            .map(|entry| {
+                let message = match &entry.status {
+                    sdb::GotStatus::Unexpected(message) => Some(message),
+                    _ => None,
+                };
+                Json::object([
+                    ("symbol", Json::string(&entry.symbol)),
+                    ("slot", Json::string(entry.slot)),
+                    ("plt", Json::optional(entry.plt, Json::string)),
+                    ("target", Json::string(entry.target)),
+                    (
+                        "target_module",
+                        Json::optional(entry.target_module.as_ref(), |path| {
+                            Json::string(path.display())
+                        }),
+                    ),
+                    ("status", Json::string(status_text(&entry.status))),
+                    ("message", Json::optional(message, Json::string)),
+                ])
+            })
+            .collect();
+        json::emit(
+            "got",
+            [
+                ("path", Json::string(elf.path().display())),
+                ("entries", Json::Array(entries)),
+            ],
+        );
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        info!("{} imports nothing through its GOT", elf.path().display());
+        return Ok(());
+    }
+    for entry in &entries {
+        let plt = entry
+            .plt
+            .map_or(String::new(), |plt| format!(" plt {}", plt));
+        let target = match entry.status {
+            sdb::GotStatus::Lazy => format!("{} (lazy, not called yet)", entry.target),
+            sdb::GotStatus::Unrelocated => {
+                format!("{:#x} (not relocated yet)", entry.target.addr())
+            }
+            sdb::GotStatus::Null => "0 (undefined weak symbol)".to_owned(),
+            _ => examine::describe_address(target, entry.target),
+        };
+        println!("{} {}{} -> {}", entry.slot, entry.symbol, plt, target);
+        if let sdb::GotStatus::Unexpected(message) = &entry.status {
+            warn!("{} {}", entry.symbol, message);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,203 @@
+use tracing::info;
+
+use crate::json::{self, Json};
+
+pub(crate) const USAGE: &str = "heap chunks|stats|check";
+
+fn flag_names(chunk: &sdb::HeapChunk) -> String {
+    [
+        (chunk.prev_inuse(), "PREV_INUSE"),
+        (chunk.is_mmapped(), "IS_MMAPPED"),
+        (chunk.non_main_arena(), "NON_MAIN_ARENA"),
+    ]
+    .into_iter()
+    .filter(|(set, _)| *set)
+    .map(|(_, name)| name)
+    .collect::<Vec<_>>()
+    .join("|")
+}
+
+fn arena_heading(arena: &sdb::Arena) -> String {
+    let name = if arena.main { "Main arena" } else { "Arena" };
+    match arena.address {
+        Some(address) => format!("{} at {}", name, address),
+        None => format!("{}, not found so nothing's known to be binned", name),
+    }
+}
+
+fn arena_json(arena: &sdb::Arena, fields: Vec<(&str, Json)>) -> Json {
+    let mut object = vec![
+        ("address", Json::optional(arena.address, Json::string)),
+        ("main", Json::Bool(arena.main)),
+        ("system_mem", Json::number(arena.system_mem)),
+    ];
+    object.extend(fields);
+    Json::object(object)
+}
+
+fn print_chunks(heap: &sdb::HeapInfo) {
+    if json::enabled() {
+        let arenas = heap
+            .arenas
+            .iter()
+            .map(|arena| {
+                let chunks = arena
+                    .chunks
+                    .iter()
+                    .map(|chunk| {
+                        Json::object([
+                            ("address", Json::string(chunk.address)),
+                            ("user_address", Json::string(chunk.user_address())),
+                            ("size", Json::number(chunk.size)),
+                            ("flags", Json::string(flag_names(chunk))),
+                            ("state", Json::string(chunk.state)),
+                        ])
+                    })
+                    .collect();
+                arena_json(arena, vec![("chunks", Json::Array(chunks))])
+            })
+            .collect();
+        json::emit("heap_chunks", [("arenas", Json::Array(arenas))]);
+        return;
+    }
+
+    for arena in &heap.arenas {
+        println!("{}:", arena_heading(arena));
+        for chunk in &arena.chunks {
+            println!(
+                "  {}  {:#8x}  {:<16}{}",
+                chunk.address,
+                chunk.size,
+                chunk.state.to_string(),
+                flag_names(chunk)
+            );
+        }
+    }
+}
+
+fn print_stats(heap: &sdb::HeapInfo) {
+    if json::enabled() {
+        let totals = |totals: &sdb::ChunkTotals| {
+            Json::object([
+                ("count", Json::number(totals.count)),
+                ("bytes", Json::number(totals.bytes)),
+            ])
+        };
+        let arenas = heap
+            .arenas
+            .iter()
+            .map(|arena| {
+                let stats = arena.stats();
+                let bins = stats
+                    .bins
+                    .iter()
+                    .map(|(bin, bin_totals)| {
+                        Json::object([
+                            ("bin", Json::optional(*bin, Json::string)),
+                            ("count", Json::number(bin_totals.count)),
+                            ("bytes", Json::number(bin_totals.bytes)),
+                        ])
+                    })
+                    .collect();
+                let in_use_by_size = stats
+                    .in_use_by_size
+                    .iter()
+                    .map(|(size, count)| {
+                        Json::object([("size", Json::number(size)), ("count", Json::number(count))])
+                    })
+                    .collect();
+                arena_json(
+                    arena,
+                    vec![
+                        ("in_use", totals(&stats.in_use)),
+                        ("free", totals(&stats.free)),
+                        ("bins", Json::Array(bins)),
+                        ("top", Json::number(stats.top)),
+                        ("largest_free", Json::number(stats.largest_free)),
+                        ("in_use_by_size", Json::Array(in_use_by_size)),
+                    ],
+                )
+            })
+            .collect();
+        json::emit("heap_stats", [("arenas", Json::Array(arenas))]);
+        return;
+    }
+
+    for arena in &heap.arenas {
+        let stats = arena.stats();
+        println!(
+            "{}, {:#x} bytes from the system:",
+            arena_heading(arena),
+            arena.system_mem
+        );
+        println!(
+            "  In use: {} chunks, {:#x} bytes",
+            stats.in_use.count, stats.in_use.bytes
+        );
+        println!(
+            "  Free: {} chunks, {:#x} bytes, the largest {:#x}",
+            stats.free.count, stats.free.bytes, stats.largest_free
+        );
+        for (bin, totals) in &stats.bins {
+            let bin = bin.map_or("no".to_owned(), |bin| bin.to_string());
+            println!(
+                "    In {} bin: {} chunks, {:#x} bytes",
+                bin, totals.count, totals.bytes
+            );
+        }
+        println!("  Top: {:#x} bytes", stats.top);
+        if !stats.in_use_by_size.is_empty() {
+            let sizes = stats
+                .in_use_by_size
+                .iter()
+                .map(|(size, count)| format!("{:#x} x{}", size, count))
+                .collect::<Vec<_>>();
+            println!("  In use by size: {}", sizes.join(", "));
+        }
+    }
+}
+
+fn print_problems(heap: &sdb::HeapInfo) {
+    if json::enabled() {
+        let problems = heap
+            .problems
+            .iter()
+            .map(|problem| {
+                Json::object([
+                    ("address", Json::string(problem.address)),
+                    ("message", Json::string(&problem.message)),
+                ])
+            })
+            .collect();
+        json::emit("heap_problems", [("problems", Json::Array(problems))]);
+        return;
+    }
+
+    if heap.problems.is_empty() {
+        info!("The heap's metadata is consistent");
+        return;
+    }
+    for problem in &heap.problems {
+        println!("{}", problem);
+    }
+}
+
+pub(crate) fn handle_heap_command(target: &sdb::Target, args: &[&str]) -> anyhow::Result<()> {
+    let print = match args {
+        ["chunks"] => print_chunks,
+        ["stats"] => print_stats,
+        ["check"] => print_problems,
+        _ => anyhow::bail!("Usage: {}", USAGE),
+    };
+
+    let heap = target.heap()?;
+    print(&heap);
+    // the other views still make what they can of a corrupt heap
+    if !matches!(args, ["check"]) && !heap.problems.is_empty() {
+        info!(
+            "Found {} problems in the heap's metadata, heap check lists them",
+            heap.problems.len()
+        );
+    }
+    Ok(())
+}
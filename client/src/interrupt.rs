@@ -0,0 +1,37 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use nix::{
+    libc,
+    sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet, Signal},
+    unistd::{self, Pid},
+};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_sigint(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+// Ctrl-C just gets noted so that a running inferior can be interrupted
+// instead of killing the debugger
+pub fn install() -> nix::Result<()> {
+    let action = SigAction::new(
+        SigHandler::Handler(on_sigint),
+        SaFlags::SA_RESTART,
+        SigSet::empty(),
+    );
+    unsafe { signal::sigaction(Signal::SIGINT, &action) }?;
+
+    Ok(())
+}
+
+// returns true if Ctrl-C was pressed since the last time this was called
+#[inline]
+pub fn take() -> bool {
+    INTERRUPTED.swap(false, Ordering::Relaxed)
+}
+
+// a process in our process group already got the SIGINT from the terminal
+pub fn shares_terminal(pid: Pid) -> bool {
+    unistd::getpgid(Some(pid)).is_ok_and(|pgid| pgid == unistd::getpgrp())
+}
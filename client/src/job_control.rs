@@ -0,0 +1,58 @@
+use std::io::IsTerminal;
+
+use nix::{
+    sys::signal::{SigSet, SigmaskHow, Signal, pthread_sigmask},
+    unistd::{self, Pid},
+};
+
+// the terminal is the process's while it runs, so it can read from it and Ctrl-C goes to it
+// like it would without the debugger, and it's ours again as soon as the process stops
+pub struct Foreground {
+    ours: Pid,
+}
+
+impl Foreground {
+    // None if there's nothing to hand over, the process is on another terminal,
+    // shares our process group, or we aren't in the foreground ourselves
+    pub fn give_to(pid: Pid) -> Option<Self> {
+        let stdin = std::io::stdin();
+        if !stdin.is_terminal() {
+            return None;
+        }
+
+        let ours = unistd::getpgrp();
+        if unistd::tcgetpgrp(&stdin).ok()? != ours
+            || unistd::getsid(Some(pid)).ok()? != unistd::getsid(None).ok()?
+        {
+            return None;
+        }
+        let theirs = unistd::getpgid(Some(pid)).ok()?;
+        if theirs == ours {
+            return None;
+        }
+
+        set_foreground(theirs).then_some(Self { ours })
+    }
+}
+
+impl Drop for Foreground {
+    fn drop(&mut self) {
+        set_foreground(self.ours);
+    }
+}
+
+// once the process has the terminal we're a background group, and changing it from one
+// gets a SIGTTOU unless it's blocked
+fn set_foreground(pgrp: Pid) -> bool {
+    let mut ttou = SigSet::empty();
+    ttou.add(Signal::SIGTTOU);
+    let mut old = SigSet::empty();
+    if pthread_sigmask(SigmaskHow::SIG_BLOCK, Some(&ttou), Some(&mut old)).is_err() {
+        return false;
+    }
+
+    let result = unistd::tcsetpgrp(std::io::stdin(), pgrp);
+    let _ = pthread_sigmask(SigmaskHow::SIG_SETMASK, Some(&old), None);
+
+    result.is_ok()
+}
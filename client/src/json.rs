@@ -0,0 +1,263 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+// --json turns every command result and stop into a JSON object on its own line of stdout
+#[inline]
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+#[inline]
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+// only what the client emits, numbers are written as they display
+#[derive(Debug, Clone)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn number(value: impl fmt::Display) -> Self {
+        Self::Number(value.to_string())
+    }
+
+    pub fn string(value: impl fmt::Display) -> Self {
+        Self::String(value.to_string())
+    }
+
+    pub fn object<'a>(fields: impl IntoIterator<Item = (&'a str, Json)>) -> Self {
+        Self::Object(
+            fields
+                .into_iter()
+                .map(|(name, value)| (name.to_owned(), value))
+                .collect(),
+        )
+    }
+
+    pub fn optional<T>(value: Option<T>, f: impl FnOnce(T) -> Json) -> Self {
+        value.map(f).unwrap_or(Self::Null)
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => write!(f, "null"),
+            Self::Bool(value) => write!(f, "{}", value),
+            Self::Number(value) => write!(f, "{}", value),
+            Self::String(value) => write_string(f, value),
+            Self::Array(values) => {
+                write!(f, "[")?;
+                for (idx, value) in values.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Self::Object(fields) => {
+                write!(f, "{{")?;
+                for (idx, (name, value)) in fields.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, name)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+// every record says what it is in its type field
+pub fn emit<'a>(kind: &str, fields: impl IntoIterator<Item = (&'a str, Json)>) {
+    let mut record = vec![("type".to_owned(), Json::string(kind))];
+    record.extend(
+        fields
+            .into_iter()
+            .map(|(name, value)| (name.to_owned(), value)),
+    );
+    println!("{}", Json::Object(record));
+}
+
+pub fn location(location: &sdb::SourceLocation) -> Json {
+    Json::object([
+        ("file", Json::string(location.file.display())),
+        ("line", Json::number(location.line)),
+        ("column", Json::number(location.column)),
+    ])
+}
+
+// just enough to read back what the client writes, numbers are kept as their text
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.text[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> anyhow::Result<char> {
+        let c = self.text[self.pos..]
+            .chars()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Unexpected end of JSON"))?;
+        self.pos += c.len_utf8();
+        Ok(c)
+    }
+
+    fn expect(&mut self, expected: char) -> anyhow::Result<()> {
+        self.skip_whitespace();
+        match self.next()? {
+            c if c == expected => Ok(()),
+            c => anyhow::bail!("Expected {} at {} but found {}", expected, self.pos - 1, c),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Json) -> anyhow::Result<Json> {
+        if !self.text[self.pos..].starts_with(keyword) {
+            anyhow::bail!("Unexpected JSON at {}", self.pos);
+        }
+        self.pos += keyword.len();
+        Ok(value)
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.next()? {
+                '"' => return Ok(string),
+                '\\' => string.push(match self.next()? {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'u' => {
+                        let hex = self.text.get(self.pos..self.pos + 4).unwrap_or_default();
+                        self.pos += 4;
+                        u32::from_str_radix(hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| anyhow::anyhow!("Bad escape \\u{}", hex))?
+                    }
+                    c => c,
+                }),
+                c => string.push(c),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Json {
+        let rest = &self.text[self.pos..];
+        let len = rest
+            .find(|c: char| !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        Json::Number(rest[..len].to_owned())
+    }
+
+    fn value(&mut self) -> anyhow::Result<Json> {
+        Ok(match self.peek() {
+            Some('{') => {
+                self.expect('{')?;
+                let mut fields = Vec::new();
+                if self.peek() == Some('}') {
+                    self.expect('}')?;
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    let name = self.string()?;
+                    self.expect(':')?;
+                    fields.push((name, self.value()?));
+                    match self.peek() {
+                        Some(',') => self.expect(',')?,
+                        _ => break,
+                    }
+                }
+                self.expect('}')?;
+                Json::Object(fields)
+            }
+            Some('[') => {
+                self.expect('[')?;
+                let mut values = Vec::new();
+                if self.peek() == Some(']') {
+                    self.expect(']')?;
+                    return Ok(Json::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    match self.peek() {
+                        Some(',') => self.expect(',')?,
+                        _ => break,
+                    }
+                }
+                self.expect(']')?;
+                Json::Array(values)
+            }
+            Some('"') => Json::String(self.string()?),
+            Some('t') => self.keyword("true", Json::Bool(true))?,
+            Some('f') => self.keyword("false", Json::Bool(false))?,
+            Some('n') => self.keyword("null", Json::Null)?,
+            Some('-' | '0'..='9') => self.number(),
+            _ => anyhow::bail!("Unexpected JSON at {}", self.pos),
+        })
+    }
+}
+
+impl Json {
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut parser = Parser { text, pos: 0 };
+        let value = parser.value()?;
+        if parser.peek().is_some() {
+            anyhow::bail!("Unexpected JSON after the value at {}", parser.pos);
+        }
+        Ok(value)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Json> {
+        match self {
+            Self::Object(fields) => fields
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
@@ -1,72 +1,2289 @@
+mod checkpoint;
+mod checksec;
+mod commands;
+mod context;
+mod core_dump;
+mod demo;
+mod disassemble;
+mod display;
+mod event_log;
+mod examine;
+mod exit_hooks;
+mod got;
+mod heap;
+mod interrupt;
+mod job_control;
+mod json;
+mod monitor;
 mod options;
+mod perf;
+mod proc_info;
+mod profile;
+mod pty;
+mod remote;
+mod restart;
+mod script;
+mod scripting;
+mod source;
+mod status;
+mod trace;
+mod waitfor;
+
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::Duration;
 
-use nix::sys::wait;
 use rustyline::{
     DefaultEditor,
     error::ReadlineError,
     history::{History, SearchDirection},
 };
-use tracing::{Level, info};
+use tracing::{Level, error, info, warn};
 use tracing_subscriber::FmtSubscriber;
 
+use nix::{sys::signal::Signal, unistd::Pid};
+use sdb::{Inferior, Stoppoint};
+
+use commands::ReplCommand;
+use context::DebuggerContext;
+use json::Json;
 use options::*;
+use status::StatusLine;
 
 fn init_logging() -> anyhow::Result<()> {
-    let subscriber = FmtSubscriber::builder()
-        .with_max_level(Level::INFO)
-        .finish();
+    let builder = FmtSubscriber::builder().with_max_level(Level::INFO);
 
-    tracing::subscriber::set_global_default(subscriber)?;
+    // stdout is only for JSON records in JSON mode
+    if json::enabled() {
+        tracing::subscriber::set_global_default(
+            builder
+                .with_writer(std::io::stderr)
+                .with_ansi(false)
+                .finish(),
+        )?;
+    } else {
+        tracing::subscriber::set_global_default(builder.finish())?;
+    }
 
     Ok(())
 }
 
-#[allow(dead_code)]
-fn print_stop_reason(process: &sdb::Process, status: wait::WaitStatus) {
-    match process.get_state() {
-        sdb::ProcessState::Stopped => info!(
-            "Process {} stopped with signal {:?}",
-            process.get_id(),
-            status
-        ),
-        sdb::ProcessState::Exited => info!(
-            "Process {} exited with status {:?}",
-            process.get_id(),
-            status
-        ),
+pub(crate) const INTERRUPT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+pub(crate) fn state_name(state: sdb::ProcessState) -> &'static str {
+    match state {
+        sdb::ProcessState::Stopped => "stopped",
+        sdb::ProcessState::Exited => "exited",
+        sdb::ProcessState::Terminated => "terminated",
+        sdb::ProcessState::Running => "running",
+    }
+}
+
+// remote sessions only know the thread that stopped, not the process
+fn emit_stop_info(pid: Option<Pid>, info: &sdb::StopInfo) {
+    let reason = state_name(info.reason.reason);
+    let exit_code = (info.reason.reason == sdb::ProcessState::Exited).then_some(info.reason.info);
+
+    json::emit(
+        "stop",
+        [
+            ("reason", Json::string(reason)),
+            ("pid", Json::optional(pid, Json::number)),
+            ("thread", Json::number(info.thread)),
+            ("exit_code", Json::optional(exit_code, Json::number)),
+            ("signal", Json::optional(info.signal(), Json::string)),
+            (
+                "breakpoint",
+                Json::optional(info.breakpoint_site(), Json::number),
+            ),
+            (
+                "fatal_function",
+                Json::optional(info.reason.fatal_function.as_ref(), Json::string),
+            ),
+            (
+                "catchpoint",
+                Json::optional(info.reason.catchpoint, Json::number),
+            ),
+            (
+                "event",
+                Json::optional(info.reason.lifecycle_event, Json::string),
+            ),
+            (
+                "watchpoint",
+                Json::optional(info.reason.watchpoint, Json::number),
+            ),
+            (
+                "watch_access",
+                Json::optional(info.reason.watch_access, Json::string),
+            ),
+            ("pc", Json::optional(info.pc, Json::string)),
+            (
+                "function",
+                Json::optional(info.function.as_ref(), Json::string),
+            ),
+            (
+                "location",
+                Json::optional(info.location.as_ref(), json::location),
+            ),
+        ],
+    );
+}
+
+pub(crate) fn print_stop_info(pid: Option<Pid>, info: sdb::StopInfo) {
+    if json::enabled() {
+        emit_stop_info(pid, &info);
+        return;
+    }
+
+    let process = match pid {
+        Some(pid) => format!("Process {}", pid),
+        None => "Process".to_owned(),
+    };
+
+    match info.reason.reason {
+        sdb::ProcessState::Stopped => {
+            let mut message = process;
+            if pid != Some(info.thread) {
+                message += &format!(" thread {}", info.thread);
+            }
+
+            if let Some(function) = &info.reason.fatal_function {
+                message += &format!(" stopped in fatal function {}", function);
+            } else if let (Some(id), Some(event)) =
+                (info.reason.catchpoint, info.reason.lifecycle_event)
+            {
+                message += &format!(" caught {} at catchpoint {}", event, id);
+            } else if let (Some(id), Some(signal)) = (info.reason.catchpoint, info.signal()) {
+                message += &format!(" caught signal {} at catchpoint {}", signal, id);
+            } else if let Some(id) = info.breakpoint_site() {
+                message += &format!(" stopped at breakpoint {}", id);
+            } else if let (Some(id), Some(access)) =
+                (info.reason.watchpoint, info.reason.watch_access)
+            {
+                message += &format!(" stopped at watchpoint {} on a {}", id, access);
+            } else if let Some(id) = info.reason.watchpoint {
+                message += &format!(" stopped at watchpoint {}", id);
+            } else if info.reason.trap_reason == Some(sdb::TrapType::HardwareBreak) {
+                message += " stopped at a hardware breakpoint";
+            } else {
+                message += &format!(" stopped with signal {:?}", info.signal());
+            }
+
+            if let Some(pc) = info.pc {
+                message += &format!(" at {}", pc);
+            }
+            if let Some(function) = &info.function {
+                message += &format!(" in {}", function);
+            }
+            if let Some(location) = &info.location {
+                message += &format!(" ({})", location);
+            }
+
+            info!("{}", message);
+        }
+        sdb::ProcessState::Exited => info!("{} exited with status {}", process, info.reason.info),
         sdb::ProcessState::Terminated => {
-            info!(
-                "Process {} terminated with signal {:?}",
-                process.get_id(),
-                status
-            )
+            info!("{} terminated with signal {:?}", process, info.signal())
         }
         _ => (),
     }
 }
 
-fn handle_command(process: &mut sdb::Process, command: impl Into<String>) -> anyhow::Result<()> {
-    let command = command.into();
-    let v = command.split_whitespace().collect::<Vec<_>>();
-    if v.is_empty() {
+fn handle_hook_command(target: &mut sdb::Target, args: &[&str]) -> anyhow::Result<()> {
+    let Some(&subcommand) = args.first() else {
+        anyhow::bail!("Usage: hook <function|list|enable|disable|delete>");
+    };
+
+    if subcommand == "function" {
+        // hook function <name> --replace <addr|nop|ret0>
+        let (Some(function), Some(&"--replace"), Some(replacement)) =
+            (args.get(1), args.get(2), args.get(3))
+        else {
+            anyhow::bail!("Usage: hook function <name> --replace <addr|nop|ret0>");
+        };
+
+        let id = target.create_function_hook(*function, replacement.parse()?)?;
+        info!("Hook {} set on {}", id, function);
+        return Ok(());
+    }
+
+    let process = target.process_mut();
+
+    if subcommand == "list" && json::enabled() {
+        let hooks = process
+            .function_hooks()
+            .iter()
+            .map(|hook| {
+                Json::object([
+                    ("id", Json::number(hook.id())),
+                    ("function", Json::string(hook.function())),
+                    ("address", Json::string(hook.address())),
+                    ("replacement", Json::string(hook.replacement())),
+                    ("enabled", Json::Bool(hook.is_enabled())),
+                ])
+            })
+            .collect();
+        json::emit("hooks", [("hooks", Json::Array(hooks))]);
+        return Ok(());
+    }
+
+    if subcommand == "list" {
+        if process.function_hooks().is_empty() {
+            println!("No hooks set");
+        }
+
+        for hook in process.function_hooks().iter() {
+            println!(
+                "{}: {} at {}, {}, {}",
+                hook.id(),
+                hook.function(),
+                hook.address(),
+                hook.replacement(),
+                if hook.is_enabled() {
+                    "enabled"
+                } else {
+                    "disabled"
+                }
+            );
+        }
+        return Ok(());
+    }
+
+    let Some(id) = args
+        .get(1)
+        .and_then(|id| id.parse::<sdb::StoppointId>().ok())
+    else {
+        anyhow::bail!("Usage: hook {} <id>", subcommand);
+    };
+
+    match subcommand {
+        "enable" => process.enable_function_hook(id)?,
+        "disable" => process.disable_function_hook(id)?,
+        "delete" => process.remove_function_hook(id)?,
+        _ => anyhow::bail!("Unknown hook command {}", subcommand),
+    }
+
+    Ok(())
+}
+
+const CATCH_USAGE: &str = "catch <signal <SIG|all>|panic|fork|vfork|exec|exit>";
+
+// SIGUSR1, usr1, or 10
+fn parse_signal(name: &str) -> anyhow::Result<Signal> {
+    if let Ok(number) = name.parse::<i32>() {
+        return Ok(Signal::try_from(number)?);
+    }
+
+    let name = name.to_uppercase();
+    match name.starts_with("SIG") {
+        true => name.parse(),
+        false => format!("SIG{}", name).parse(),
+    }
+    .map_err(|_| anyhow::anyhow!("Unknown signal {}", name))
+}
+
+fn handle_catch_command(target: &mut sdb::Target, args: &[&str]) -> anyhow::Result<()> {
+    if let ["panic"] = args {
+        let id = target.catch_panic()?;
+        info!("Catchpoint {} set on panics", id);
+        return Ok(());
+    }
+
+    if let [event] = args {
+        let event = event
+            .parse::<sdb::LifecycleEvent>()
+            .map_err(|_| anyhow::anyhow!("Usage: {}", CATCH_USAGE))?;
+        let id = target.process_mut().catch_event(event)?;
+        info!("Catchpoint {} set on {}", id, event);
+        return Ok(());
+    }
+
+    let signal = match args {
+        ["signal", "all"] => None,
+        ["signal", signal] => Some(parse_signal(signal)?),
+        _ => anyhow::bail!("Usage: {}", CATCH_USAGE),
+    };
+
+    let id = target.process_mut().catch_signal(signal)?;
+    match signal {
+        Some(signal) => info!("Catchpoint {} set on {}", id, signal),
+        None => info!("Catchpoint {} set on every signal", id),
+    }
+
+    Ok(())
+}
+
+// waits for the process to stop on its own thread
+// so that Ctrl-C can interrupt it in the meantime
+fn wait_for_stop(transport: &dyn sdb::DebugTransport) -> anyhow::Result<sdb::StopReason> {
+    // nothing's drawing on the terminal in JSON mode, or over the process's own output
+    let status_line = transport
+        .local_controller()
+        .filter(|_| !json::enabled() && !pty::enabled())
+        .map(|controller| StatusLine::spawn(controller.handle()));
+    let reason = wait_for_stop_polling(transport, INTERRUPT_POLL_INTERVAL, || {
+        if let Err(err) = pty::forward_input() {
+            warn!("Failed to send input to the process: {}", err);
+        }
+    });
+    if let Some(status_line) = status_line {
+        status_line.stop();
+    }
+
+    reason
+}
+
+// poll runs every interval while the process runs, Ctrl-C is checked as often
+pub(crate) fn wait_for_stop_polling(
+    transport: &dyn sdb::DebugTransport,
+    interval: Duration,
+    mut poll: impl FnMut(),
+) -> anyhow::Result<sdb::StopReason> {
+    // a process somewhere else has its own terminal
+    let pid = transport
+        .local_controller()
+        .map(|controller| controller.handle().get_id());
+
+    std::thread::scope(|scope| {
+        let (sender, stopped) = mpsc::channel();
+        scope.spawn(move || {
+            let _ = sender.send(transport.request(sdb::Request::Wait));
+        });
+        let foreground = pid.map(job_control::Foreground::give_to);
+
+        // anything from before we started waiting is stale
+        interrupt::take();
+
+        let reason = loop {
+            match stopped.recv_timeout(interval) {
+                Ok(Ok(sdb::Response::Stopped(reason))) => break Ok(reason),
+                Ok(Ok(response)) => {
+                    break Err(anyhow::anyhow!("Unexpected response {:?}", response));
+                }
+                Ok(Err(err)) => break Err(err.into()),
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    poll();
+                    if interrupt::take() && !pid.is_some_and(interrupt::shares_terminal) {
+                        info!("Interrupting process ...");
+                        if let Err(err) = transport.request(sdb::Request::Interrupt) {
+                            break Err(err.into());
+                        }
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    break Err(anyhow::anyhow!("Session closed"));
+                }
+            }
+        };
+
+        // before anything's printed about the stop
+        drop(foreground);
+        reason
+    })
+}
+
+// shared with core dumps, which can only be read
+pub(crate) fn print_registers(inferior: &impl Inferior, args: &[&str]) -> anyhow::Result<()> {
+    let infos = match args {
+        [] | ["all"] => {
+            let all = !args.is_empty();
+            sdb::register_infos()
+                .iter()
+                .filter(|info| {
+                    all || (info.register_type() == sdb::RegisterType::Gpr
+                        && info.name() != "orig_rax")
+                })
+                .collect()
+        }
+        [name] => vec![register_info(name)?],
+        _ => anyhow::bail!("Usage: register read [name|all]"),
+    };
+
+    if json::enabled() {
+        json::emit(
+            "registers",
+            [("registers", registers_json(inferior, infos)?)],
+        );
+        return Ok(());
+    }
+
+    for info in infos {
+        println!(
+            "{}:\t{}",
+            info.name(),
+            inferior.read_register_by_id(info.id())?
+        );
+    }
+
+    Ok(())
+}
+
+// names to values as they're displayed, which is what register load reads back
+fn registers_json(
+    inferior: &impl Inferior,
+    infos: Vec<&sdb::RegisterInfo>,
+) -> anyhow::Result<Json> {
+    let mut registers = Vec::new();
+    for info in infos {
+        let value = inferior.read_register_by_id(info.id())?;
+        registers.push((info.name().to_owned(), Json::string(value)));
+    }
+    Ok(Json::Object(registers))
+}
+
+// the same record register read all prints with --json
+fn save_registers(target: &sdb::Target, path: &str) -> anyhow::Result<()> {
+    let infos = sdb::register_infos().iter().collect();
+    let record = Json::object([
+        ("type", Json::string("registers")),
+        ("registers", registers_json(target, infos)?),
+    ]);
+    std::fs::write(path, format!("{}\n", record))?;
+    info!("Saved registers to {}", path);
+
+    Ok(())
+}
+
+// the order they're written in, where registers overlap the later ones win
+fn load_order(info: &sdb::RegisterInfo) -> u8 {
+    match (info.register_type(), info.format()) {
+        // the full registers they're part of
+        (sdb::RegisterType::SubGpr, _) => 0,
+        // only as precise as a double, mm has the exact mantissa
+        (_, sdb::RegisterFormat::LongDouble) => 1,
+        _ => 2,
+    }
+}
+
+// a saved record or just an object of names to values, as strings or numbers
+fn load_registers(process: &mut sdb::Process, path: &str) -> anyhow::Result<()> {
+    let json = Json::parse(&std::fs::read_to_string(path)?)
+        .map_err(|err| anyhow::anyhow!("{}: {}", path, err))?;
+    let Json::Object(fields) = json.get("registers").unwrap_or(&json) else {
+        anyhow::bail!("{} doesn't have an object of registers", path);
+    };
+
+    // nothing is written unless everything is understood
+    let mut values = Vec::new();
+    let mut skipped = 0;
+    for (name, value) in fields {
+        let info = register_info(name)?;
+        let (Json::String(text) | Json::Number(text)) = value else {
+            anyhow::bail!("{} has to be a string or a number", name);
+        };
+        let value = sdb::RegisterValue::parse(info, text)?;
+
+        // they're the debugger's, for its own stoppoints
+        if info.register_type() == sdb::RegisterType::Dr {
+            skipped += 1;
+            continue;
+        }
+        values.push((info, value));
+    }
+    values.sort_by_key(|(info, _)| load_order(info));
+
+    let count = values.len();
+    process
+        .write_registers_journaled(values.into_iter().map(|(info, value)| (info.id(), value)))?;
+    if skipped > 0 {
+        info!(
+            "Loaded {} registers from {}, left {} debug registers alone",
+            count, path, skipped
+        );
+    } else {
+        info!("Loaded {} registers from {}", count, path);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn register_info(name: &str) -> anyhow::Result<&'static sdb::RegisterInfo> {
+    sdb::register_info_by_name(name).ok_or_else(|| anyhow::anyhow!("No such register {}", name))
+}
+
+const REGISTER_USAGE: &str =
+    "register <read [name|all]|write <name> <value>|save <file.json>|load <file.json>>";
+
+fn handle_register_command(target: &mut sdb::Target, args: &[&str]) -> anyhow::Result<()> {
+    match args {
+        ["read", args @ ..] => print_registers(target, args)?,
+        ["save", path] => save_registers(target, path)?,
+        ["load", path] => load_registers(target.process_mut(), path)?,
+        ["write", name, value] => {
+            let info = register_info(name)?;
+            let value = match value.strip_prefix("0x") {
+                Some(hex) => u64::from_str_radix(hex, 16)?,
+                None => value.parse::<u64>()?,
+            };
+            target
+                .process_mut()
+                .write_registers_journaled([(info.id(), value.into())])?;
+        }
+        _ => anyhow::bail!("Usage: {}", REGISTER_USAGE),
+    }
+
+    Ok(())
+}
+
+fn handle_memory_dump(process: &mut sdb::Process, args: &[&str]) -> anyhow::Result<()> {
+    let (method, args) = match args {
+        ["--memfd", args @ ..] => (sdb::TransferMethod::Memfd, args),
+        ["--vm-readv", args @ ..] => (sdb::TransferMethod::ProcessVmReadv, args),
+        args => (sdb::TransferMethod::ProcMem, args),
+    };
+    let [address, size, path] = args else {
+        anyhow::bail!("Usage: memory dump [--memfd|--vm-readv] <address> <size> <file>");
+    };
+
+    let address = sdb::VirtAddr::new(u64::from_str_radix(
+        address.strip_prefix("0x").unwrap_or(address),
+        16,
+    )?);
+    let size = match size.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16)?,
+        None => size.parse()?,
+    };
+
+    let (memory, transfer) = process.read_memory_bulk(address, size, method)?;
+    std::fs::write(path, &memory)?;
+    info!("Read {}", transfer);
+
+    Ok(())
+}
+
+fn handle_memory_command(process: &mut sdb::Process, args: &[&str]) -> anyhow::Result<()> {
+    if let ["dump", args @ ..] = args {
+        return handle_memory_dump(process, args);
+    }
+
+    let (assist, args) = match args {
+        ["write", "--mprotect", args @ ..] => (true, args),
+        ["write", args @ ..] => (false, args),
+        _ => anyhow::bail!("Usage: memory write [--mprotect] <address> <byte>..."),
+    };
+    let [address, bytes @ ..] = args else {
+        anyhow::bail!("Usage: memory write [--mprotect] <address> <byte>...");
+    };
+
+    let address = sdb::VirtAddr::new(u64::from_str_radix(
+        address.strip_prefix("0x").unwrap_or(address),
+        16,
+    )?);
+    let data = bytes
+        .iter()
+        .map(|byte| u8::from_str_radix(byte.strip_prefix("0x").unwrap_or(byte), 16))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if let sdb::WriteAssist::Mprotect(regions) =
+        process.write_memory_journaled(address, &data, assist)?
+    {
+        for region in regions {
+            info!(
+                "Made {}-{} writable for the write, protections restored",
+                region.start, region.end
+            );
+        }
+    }
+
+    Ok(())
+}
+
+const BREAK_USAGE_LINE: &str = "break <function|*function|file:line|line|+n|-n|address> [--max-hits-per-sec <n>|--sample 1/<n>] [--thread <tid|name>] [--if <expression>]";
+
+fn parse_hit_limit(args: &[&str]) -> anyhow::Result<Option<sdb::HitLimit>> {
+    match args {
+        [] => Ok(None),
+        ["--max-hits-per-sec", count] => Ok(Some(sdb::HitLimit::MaxPerSecond(count.parse()?))),
+        ["--sample", sample] => {
+            let every = sample.strip_prefix("1/").unwrap_or(sample);
+            Ok(Some(sdb::HitLimit::Sample(every.parse()?)))
+        }
+        _ => anyhow::bail!("Usage: {}", BREAK_USAGE_LINE),
+    }
+}
+
+// +n or -n lines from here or a line number, within the file if how long it is is known
+fn line_in_file(here: &sdb::SourceLocation, count: Option<u64>, line: &str) -> anyhow::Result<u64> {
+    let offset = |offset: &str| -> anyhow::Result<u64> {
+        match offset.parse()? {
+            0 => anyhow::bail!("{} is the line it's stopped on, give an offset", line),
+            offset => Ok(offset),
+        }
+    };
+
+    match line.split_at_checked(1) {
+        Some(("+", by)) => here.line.checked_add(offset(by)?),
+        Some(("-", by)) => here.line.checked_sub(offset(by)?),
+        _ => Some(line.parse()?),
+    }
+    .filter(|&line| line > 0 && count.is_none_or(|count| line <= count))
+    .ok_or_else(|| anyhow::anyhow!("{} is outside {}", line, here.file.display()))
+}
+
+// a line in the file the process is stopped in, +n and -n are relative to where it is
+fn line_here(target: &sdb::Target, line: &str) -> anyhow::Result<(PathBuf, u64)> {
+    let here = target
+        .stop_info()?
+        .location
+        .ok_or_else(|| anyhow::anyhow!("Not stopped on a source line, give a file:line"))?;
+
+    // without the source there's nothing to check the end against
+    let count = target
+        .source_lines(&here.file, 1, u64::MAX)
+        .ok()
+        .map(|lines| lines.len() as u64);
+    let line = line_in_file(&here, count, line)?;
+
+    Ok((here.file, line))
+}
+
+// a function past its prologue or *function at its entry, file:line, line in the current file,
+// +n or -n lines from here, or hex address
+pub(crate) fn set_breakpoint(
+    target: &mut sdb::Target,
+    location: &str,
+) -> anyhow::Result<sdb::StoppointId> {
+    let address = if let Some(hex) = location.strip_prefix("0x") {
+        Some(sdb::VirtAddr::new(u64::from_str_radix(hex, 16)?))
+    } else if let Some(name) = location.strip_prefix('*') {
+        let address = target
+            .function_address(name)
+            .ok_or_else(|| anyhow::anyhow!("No function named {}", name))?;
+        Some(address)
+    } else {
+        None
+    };
+
+    Ok(if let Some(address) = address {
+        let process = target.process_mut();
+        let id = process.create_breakpoint_site(address, false)?;
+        process.enable_breakpoint_site(id)?;
+        id
+    } else if let Some((file, line)) = location.rsplit_once(':')
+        && let Ok(line) = line.parse()
+    {
+        target.create_line_breakpoint(file, line)?
+    } else if location.starts_with(['+', '-']) || location.bytes().all(|c| c.is_ascii_digit()) {
+        let (file, line) = line_here(target, location)?;
+        target.create_line_breakpoint(file, line)?
+    } else {
+        target.create_function_breakpoint(location)?
+    })
+}
+
+fn handle_break_command(target: &mut sdb::Target, args: &[&str]) -> anyhow::Result<()> {
+    let Some((&location, args)) = args.split_first() else {
+        anyhow::bail!("Usage: {}", BREAK_USAGE_LINE);
+    };
+
+    // the condition is the rest of the line, spaces and all
+    let (args, condition) = match args.iter().position(|&arg| arg == "--if") {
+        Some(idx) => (&args[..idx], Some(args[idx + 1..].join(" "))),
+        None => (args, None),
+    };
+    let (args, thread) = match args.iter().position(|&arg| arg == "--thread") {
+        Some(idx) => {
+            let Some(&thread) = args.get(idx + 1) else {
+                anyhow::bail!("Usage: {}", BREAK_USAGE_LINE);
+            };
+            let rest = [&args[..idx], &args[idx + 2..]].concat();
+            (rest, Some(target.process().find_thread(thread)?))
+        }
+        None => (args.to_vec(), None),
+    };
+    let limit = parse_hit_limit(&args)?;
+    let condition = condition
+        .map(|condition| condition.parse::<sdb::expr::Expr>())
+        .transpose()?;
+
+    let id = set_breakpoint(target, location)?;
+
+    if limit.is_some() {
+        target.process_mut().set_breakpoint_hit_limit(id, limit)?;
+    }
+    if condition.is_some() {
+        target.set_breakpoint_condition(id, condition.clone())?;
+    }
+    if thread.is_some() {
+        target.process_mut().set_breakpoint_thread(id, thread)?;
+    }
+
+    let address = target.process().breakpoint_sites().get_by_id(id)?.address();
+    if json::enabled() {
+        json::emit(
+            "breakpoint",
+            [
+                ("id", Json::number(id)),
+                ("address", Json::string(address)),
+                ("hit_limit", Json::optional(limit, Json::string)),
+                ("condition", Json::optional(condition, Json::string)),
+                (
+                    "thread",
+                    Json::optional(thread, |tid| Json::number(tid.as_raw())),
+                ),
+            ],
+        );
+    } else {
+        let when = [
+            limit.map(|limit| format!("stopping {}", limit)),
+            thread.map(|tid| format!("in thread {}", tid)),
+            condition.map(|condition| format!("if {}", condition)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        if when.is_empty() {
+            info!("Breakpoint {} set at {}", id, address);
+        } else {
+            info!("Breakpoint {} set at {}, {}", id, address, when.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+const WATCH_USAGE: &str = "watch <variable|address> [size]";
+
+const RWATCH_USAGE: &str = "rwatch <variable|address> [size]";
+
+const AWATCH_USAGE: &str = "awatch <variable|address> [size]";
+
+// up to 8 bytes is a number like the variable would be, anything bigger is its bytes
+pub(crate) fn watched_value(bytes: &[u8]) -> String {
+    if bytes.len() > 8 {
+        return bytes
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<_>>()
+            .join(" ");
+    }
+
+    let mut value = [0; 8];
+    value[..bytes.len()].copy_from_slice(bytes);
+    u64::from_le_bytes(value).to_string()
+}
+
+fn handle_watch_command(
+    target: &mut sdb::Target,
+    args: &[&str],
+    mode: sdb::WatchpointMode,
+) -> anyhow::Result<()> {
+    let (usage, kind) = match mode {
+        sdb::WatchpointMode::Write => (WATCH_USAGE, "Watchpoint"),
+        sdb::WatchpointMode::Read => (RWATCH_USAGE, "Read watchpoint"),
+        sdb::WatchpointMode::ReadWrite => (AWATCH_USAGE, "Access watchpoint"),
+    };
+    let (location, size) = match args {
+        [location] => (*location, None),
+        [location, size] => (*location, Some(size.parse::<usize>()?)),
+        _ => anyhow::bail!("Usage: {}", usage),
+    };
+
+    // a variable is watched whole, then a global without debug info, anything else is an address,
+    // and if that fails too it's the variable's error that says why
+    let variable = location
+        .parse::<sdb::VariablePath>()
+        .map_err(anyhow::Error::from)
+        .and_then(|path| Ok(target.create_variable_watchpoint(&path, size, mode)?));
+    let id = match variable {
+        Ok(id) => id,
+        Err(err) => {
+            let (address, size) = match target.variable_address(location) {
+                Some((address, symbol_size)) => (address, size.unwrap_or(symbol_size)),
+                None => {
+                    let Ok(address) = location
+                        .parse::<sdb::expr::Expr>()
+                        .map_err(anyhow::Error::from)
+                        .and_then(|expr| Ok(expr.evaluate(&*target)?))
+                    else {
+                        return Err(err);
+                    };
+                    (sdb::VirtAddr::new(address as u64), size.unwrap_or(8))
+                }
+            };
+            target
+                .process_mut()
+                .create_watchpoint(address, size, mode)?
+        }
+    };
+
+    let watchpoint = target.process().watchpoints().get_by_id(id)?;
+    let (address, size) = (watchpoint.address(), watchpoint.size());
+    if json::enabled() {
+        json::emit(
+            "watchpoint",
+            [
+                ("id", Json::number(id)),
+                ("address", Json::string(address)),
+                ("size", Json::number(size)),
+                ("mode", Json::string(mode)),
+                (
+                    "debug_register",
+                    Json::optional(watchpoint.debug_register(), Json::number),
+                ),
+            ],
+        );
+    } else {
+        match watchpoint.debug_register() {
+            Some(slot) => info!(
+                "{} {} set on {} bytes at {} in dr{}",
+                kind, id, size, address, slot
+            ),
+            None => info!(
+                "{} {} set on {} bytes at {}, the thread single-steps while it's enabled",
+                kind, id, size, address
+            ),
+        }
+        if watchpoint.frame().is_some() {
+            info!("It's disabled once the local's frame returns");
+        }
+    }
+
+    Ok(())
+}
+
+const DPRINTF_USAGE: &str =
+    "dprintf [--output <file>] <function|file:line|line|+n|-n|address> \"<format>\" [arg]...";
+
+// a quoted string with C escapes, and whatever's left after it
+pub(crate) fn parse_quoted<'a>(text: &'a str, usage: &str) -> anyhow::Result<(String, &'a str)> {
+    let Some(text) = text.strip_prefix('"') else {
+        anyhow::bail!("Usage: {}", usage);
+    };
+
+    let mut format = String::new();
+    let mut chars = text.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '"' => return Ok((format, &text[idx + 1..])),
+            '\\' => match chars.next() {
+                Some((_, 'n')) => format.push('\n'),
+                Some((_, 't')) => format.push('\t'),
+                Some((_, c @ ('"' | '\\'))) => format.push(c),
+                Some((_, c)) => anyhow::bail!("Unknown escape \\{}", c),
+                None => break,
+            },
+            c => format.push(c),
+        }
+    }
+
+    anyhow::bail!("Missing closing quote in {}", text)
+}
+
+// the words are joined back up, so runs of spaces in the format become one
+fn handle_dprintf_command(target: &mut sdb::Target, args: &[&str]) -> anyhow::Result<()> {
+    let (output, args) = match args {
+        ["--output", path, args @ ..] => (Some(*path), args),
+        args => (None, args),
+    };
+    let Some((&location, args)) = args.split_first() else {
+        anyhow::bail!("Usage: {}", DPRINTF_USAGE);
+    };
+    let args = args.join(" ");
+    let (format, rest) = parse_quoted(&args, DPRINTF_USAGE)?;
+
+    // gdb separates the arguments with commas
+    let args = rest
+        .split([',', ' '])
+        .filter(|arg| !arg.is_empty())
+        .map(str::parse)
+        .collect::<sdb::Result<Vec<sdb::LogArg>>>()?;
+    let log = sdb::LogFormat::new(format, args)?;
+
+    // appended to, so a session can pick up where the last left off
+    let file = output
+        .map(|path| {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|err| anyhow::anyhow!("{}: {}", path, err))
+        })
+        .transpose()?;
+
+    let id = set_breakpoint(target, location)?;
+    if let Some(file) = file {
+        event_log::log_to_file(id, file);
+    }
+    target.set_breakpoint_log(id, Some(log.clone()))?;
+
+    let address = target.process().breakpoint_sites().get_by_id(id)?.address();
+    if json::enabled() {
+        json::emit(
+            "breakpoint",
+            [
+                ("id", Json::number(id)),
+                ("address", Json::string(address)),
+                ("log", Json::string(&log)),
+                ("output", Json::optional(output, Json::string)),
+            ],
+        );
+    } else {
+        match output {
+            Some(path) => info!(
+                "Logpoint {} set at {}, printing {} to {}",
+                id, address, log, path
+            ),
+            None => info!("Logpoint {} set at {}, printing {}", id, address, log),
+        }
+    }
+
+    Ok(())
+}
+
+fn print_stoppoints(process: &sdb::Process, args: &[&str]) -> anyhow::Result<()> {
+    let all = match args {
+        [] => false,
+        ["--all"] => true,
+        _ => anyhow::bail!("Usage: stoppoints [--all]"),
+    };
+
+    // the debugger's own are only interesting when debugging the debugger
+    let stoppoints = process
+        .stoppoints()
+        .into_iter()
+        .filter(|stoppoint| all || stoppoint.kind() != sdb::StoppointKind::Internal)
+        .collect::<Vec<_>>();
+
+    if json::enabled() {
+        let stoppoints = stoppoints
+            .iter()
+            .map(|stoppoint| {
+                Json::object([
+                    ("id", Json::number(stoppoint.id())),
+                    ("kind", Json::string(stoppoint.kind())),
+                    ("spec", Json::string(stoppoint.spec())),
+                    (
+                        "address",
+                        Json::optional(
+                            stoppoint.has_address().then(|| stoppoint.address()),
+                            Json::string,
+                        ),
+                    ),
+                    ("enabled", Json::Bool(stoppoint.is_enabled())),
+                    ("hit_count", Json::number(stoppoint.hit_count())),
+                ])
+            })
+            .collect();
+        json::emit("stoppoints", [("stoppoints", Json::Array(stoppoints))]);
         return Ok(());
     }
 
-    let command = v[0];
-    let _args = &v[1..];
+    if stoppoints.is_empty() {
+        println!("No breakpoints, catchpoints, hooks, or watchpoints set");
+    }
+
+    for stoppoint in stoppoints {
+        let spec = stoppoint.spec();
+        let address = stoppoint.address().to_string();
+        let mut line = match (stoppoint.has_address(), spec == address) {
+            (false, _) => format!("{}: {} {}", stoppoint.id(), stoppoint.kind(), spec),
+            (true, true) => format!("{}: {} at {}", stoppoint.id(), stoppoint.kind(), address),
+            (true, false) => format!(
+                "{}: {} {} at {}",
+                stoppoint.id(),
+                stoppoint.kind(),
+                spec,
+                address
+            ),
+        };
+        line.push_str(if stoppoint.is_enabled() {
+            ", enabled"
+        } else {
+            ", disabled"
+        });
+        if stoppoint.kind() != sdb::StoppointKind::Hook {
+            line.push_str(&format!(", hit {} times", stoppoint.hit_count()));
+        }
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+fn stoppoint_id(command: &str, args: &[&str]) -> anyhow::Result<sdb::StoppointId> {
+    match args {
+        [id] => Ok(id.parse()?),
+        _ => anyhow::bail!("Usage: {} <id>", command),
+    }
+}
+
+const UNDO_USAGE: &str = "undo [last|all|list]";
+
+fn print_journal_entries(kind: &str, entries: &[sdb::JournalEntry]) {
+    if json::enabled() {
+        let entries = entries
+            .iter()
+            .map(|entry| {
+                Json::object([
+                    ("id", Json::number(entry.id)),
+                    ("description", Json::string(entry)),
+                    ("reversible", Json::Bool(entry.is_reversible())),
+                ])
+            })
+            .collect();
+        json::emit(kind, [("entries", Json::Array(entries))]);
+        return;
+    }
+
+    for entry in entries {
+        match (kind, entry.is_reversible()) {
+            ("journal", _) => println!("{}", entry),
+            (_, true) => info!("Undid {}", entry),
+            (_, false) => warn!(
+                "Couldn't undo {}, it's been dropped from the journal",
+                entry
+            ),
+        }
+    }
+}
+
+// undo on its own takes back the latest change
+fn handle_undo_command(process: &mut sdb::Process, args: &[&str]) -> anyhow::Result<()> {
+    let undone = match args {
+        [] | ["last"] => process.undo_last()?.into_iter().collect(),
+        ["all"] => process.undo_all()?,
+        ["list"] => {
+            if process.journal().is_empty() && !json::enabled() {
+                println!("Nothing to undo");
+            }
+            print_journal_entries("journal", process.journal());
+            return Ok(());
+        }
+        _ => anyhow::bail!("Usage: {}", UNDO_USAGE),
+    };
+
+    if undone.is_empty() && !json::enabled() {
+        info!("Nothing to undo");
+    }
+    print_journal_entries("undone", &undone);
+
+    Ok(())
+}
+
+const SYSCALL_USAGE: &str = "syscall <number> [arg]...";
+
+fn parse_number(text: &str) -> anyhow::Result<u64> {
+    Ok(match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16)?,
+        None => text.parse::<i64>()? as u64,
+    })
+}
+
+fn handle_syscall_command(process: &mut sdb::Process, args: &[&str]) -> anyhow::Result<()> {
+    let [number, args @ ..] = args else {
+        anyhow::bail!("Usage: {}", SYSCALL_USAGE);
+    };
+    let number = parse_number(number)? as i64;
+    let args = args
+        .iter()
+        .map(|arg| parse_number(arg))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let ret = process.inject_syscall_journaled(number, &args)?;
+    if json::enabled() {
+        json::emit(
+            "syscall",
+            [
+                ("number", Json::number(number)),
+                ("return", Json::number(ret)),
+            ],
+        );
+    } else {
+        println!("syscall {} returned {}", number, ret);
+    }
+
+    Ok(())
+}
+
+fn print_return_value(function: Option<String>, value: sdb::VariableValue) {
+    if json::enabled() {
+        json::emit(
+            "return_value",
+            [
+                ("function", Json::optional(function, Json::string)),
+                ("type", Json::string(&value.type_name)),
+                ("value", Json::optional(value.scalar, Json::number)),
+                ("text", Json::string(&value.text)),
+            ],
+        );
+        return;
+    }
+
+    let function = function.unwrap_or_else(|| "Function".to_owned());
+    info!("{} returned ({}) {}", function, value.type_name, value.text);
+}
+
+pub(crate) fn print_backtrace(target: &impl Inferior) -> anyhow::Result<()> {
+    print_frames(&target.backtrace()?);
+
+    Ok(())
+}
+
+pub(crate) const INFO_USAGE: &str = "info tls|auxv";
+
+const LIVE_INFO_USAGE: &str =
+    "info tls|auxv|threads|allocations|got [module]|proc [status|stat|cmdline|environ|fd]";
+
+pub(crate) fn handle_info_command(inferior: &impl Inferior, args: &[&str]) -> anyhow::Result<()> {
+    match args {
+        ["tls"] => print_tls(&sdb::TlsInfo::read(inferior)?),
+        ["auxv"] => print_auxv(inferior, &inferior.auxv()?),
+        _ => anyhow::bail!("Usage: {}", INFO_USAGE),
+    }
+
+    Ok(())
+}
+
+// names and states come from /proc, a thread that's just exited has neither
+fn print_threads(process: &sdb::Process) {
+    let current = process.current_thread();
+
+    if json::enabled() {
+        let threads = process
+            .threads()
+            .map(|thread| {
+                Json::object([
+                    ("tid", Json::number(thread.tid())),
+                    ("name", Json::optional(thread.name(), Json::string)),
+                    ("state", Json::optional(thread.kernel_state(), Json::string)),
+                    ("current", Json::Bool(thread.tid() == current)),
+                ])
+            })
+            .collect();
+        json::emit("threads", [("threads", Json::Array(threads))]);
+        return;
+    }
+
+    for thread in process.threads() {
+        let marker = if thread.tid() == current { "*" } else { " " };
+        let name = thread.name().unwrap_or_else(|| "?".to_owned());
+        let state = thread.kernel_state().unwrap_or("gone");
+        println!("{} {:<7} {} ({})", marker, thread.tid(), name, state);
+    }
+}
+
+// the strings are read from the program, a core may not have them
+fn print_auxv(inferior: &impl Inferior, auxv: &sdb::Auxv) {
+    let string = |key: sdb::AuxvKey, value: u64| {
+        key.is_string()
+            .then(|| inferior.read_c_string(sdb::VirtAddr::new(value), 4096).ok())
+            .flatten()
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+    };
+
+    if json::enabled() {
+        let entries = auxv
+            .iter()
+            .map(|(key, value)| {
+                Json::object([
+                    ("type", Json::number(key.raw())),
+                    ("name", Json::string(key)),
+                    ("value", Json::number(value)),
+                    ("string", Json::optional(string(key, value), Json::string)),
+                ])
+            })
+            .collect();
+        json::emit("auxv", [("entries", Json::Array(entries))]);
+        return;
+    }
+
+    for (key, value) in auxv.iter() {
+        let text = if key.is_address() {
+            format!("{:#018x}", value)
+        } else if key == sdb::AuxvKey::Hwcap || key == sdb::AuxvKey::Hwcap2 {
+            format!("{:#x}", value)
+        } else {
+            value.to_string()
+        };
+        match string(key, value) {
+            Some(string) => println!("{:>3}  {:<21} {} {:?}", key.raw(), key, text, string),
+            None => println!("{:>3}  {:<21} {}", key.raw(), key, text),
+        }
+    }
+}
+
+fn print_tls(tls: &sdb::TlsInfo) {
+    if json::enabled() {
+        let tcb = tls.tcb.as_ref().map(|tcb| {
+            Json::object([
+                ("tcb", Json::string(tcb.tcb)),
+                ("dtv", Json::string(tcb.dtv)),
+                ("thread", Json::string(tcb.thread)),
+                ("multiple_threads", Json::number(tcb.multiple_threads)),
+                (
+                    "stack_guard",
+                    Json::string(format!("{:#x}", tcb.stack_guard)),
+                ),
+                (
+                    "pointer_guard",
+                    Json::string(format!("{:#x}", tcb.pointer_guard)),
+                ),
+                ("dtv_slots", Json::number(tcb.dtv_slots)),
+                ("dtv_generation", Json::number(tcb.dtv_generation)),
+            ])
+        });
+        let blocks = tls
+            .blocks
+            .iter()
+            .map(|block| {
+                Json::object([
+                    ("path", Json::string(block.path.display())),
+                    ("module_id", Json::optional(block.module_id, Json::number)),
+                    ("address", Json::optional(block.address, Json::string)),
+                    ("size", Json::number(block.template.size)),
+                    ("initialized_size", Json::number(block.template.file_size)),
+                    ("align", Json::number(block.template.align)),
+                ])
+            })
+            .collect();
+        json::emit(
+            "tls",
+            [
+                ("fs_base", Json::string(tls.fs_base)),
+                ("gs_base", Json::string(tls.gs_base)),
+                ("tcb", Json::optional(tcb, |tcb| tcb)),
+                ("blocks", Json::Array(blocks)),
+            ],
+        );
+        return;
+    }
+
+    println!("fs_base  {}", tls.fs_base);
+    println!("gs_base  {}", tls.gs_base);
+
+    match &tls.tcb {
+        Some(tcb) => {
+            println!("TCB (glibc tcbhead_t) at {}", tls.fs_base);
+            println!("  tcb               {}", tcb.tcb);
+            println!(
+                "  dtv               {} ({} slots, generation {})",
+                tcb.dtv, tcb.dtv_slots, tcb.dtv_generation
+            );
+            println!("  self              {}", tcb.thread);
+            println!("  multiple_threads  {}", tcb.multiple_threads);
+            println!("  stack_guard       {:#018x}", tcb.stack_guard);
+            println!("  pointer_guard     {:#018x}", tcb.pointer_guard);
+        }
+        None => println!("No TCB layout, glibc's _r_debug isn't loaded"),
+    }
+
+    if tls.blocks.is_empty() {
+        println!("No modules have thread-locals");
+        return;
+    }
+    println!("TLS blocks");
+    for block in &tls.blocks {
+        let id = block
+            .module_id
+            .map(|id| id.to_string())
+            .unwrap_or_else(|| "?".to_owned());
+        let address = match block.address {
+            Some(address) => format!("{}-{}", address, address + block.template.size as i64),
+            None if block.module_id.is_some() => "not allocated yet".to_owned(),
+            None => "unknown".to_owned(),
+        };
+        println!(
+            "  {:>3}  {}  size {:#x} ({:#x} initialized), align {:#x}  {}",
+            id,
+            address,
+            block.template.size,
+            block.template.file_size,
+            block.template.align,
+            block.path.display()
+        );
+    }
+}
+
+// remote sessions get their frames over the transport
+pub(crate) fn print_frames(frames: &[sdb::Frame]) {
+    if json::enabled() {
+        let frames = frames
+            .iter()
+            .map(|frame| {
+                Json::object([
+                    ("pc", Json::string(frame.pc)),
+                    (
+                        "function",
+                        Json::optional(frame.function.as_ref(), Json::string),
+                    ),
+                    (
+                        "location",
+                        Json::optional(frame.location.as_ref(), json::location),
+                    ),
+                ])
+            })
+            .collect();
+        json::emit("backtrace", [("frames", Json::Array(frames))]);
+        return;
+    }
+
+    for (idx, frame) in frames.iter().enumerate() {
+        let mut line = format!("#{} {}", idx, frame.pc);
+        if let Some(function) = &frame.function {
+            line += &format!(" in {}", function);
+        }
+        if let Some(location) = &frame.location {
+            line += &format!(" ({})", location);
+        }
+        println!("{}", line);
+    }
+}
+
+fn handle_maintenance_command(process: &mut sdb::Process, args: &[&str]) -> anyhow::Result<()> {
+    match args {
+        ["timings"] if json::enabled() => {
+            let timings = process
+                .timings()
+                .iter()
+                .map(|(operation, timings)| {
+                    Json::object([
+                        ("operation", Json::string(operation)),
+                        ("count", Json::number(timings.count())),
+                        ("total_ns", Json::number(timings.total().as_nanos())),
+                        ("mean_ns", Json::number(timings.mean().as_nanos())),
+                        ("min_ns", Json::number(timings.min().as_nanos())),
+                        ("max_ns", Json::number(timings.max().as_nanos())),
+                    ])
+                })
+                .collect();
+            json::emit("timings", [("timings", Json::Array(timings))]);
+        }
+        ["timings"] => {
+            if process.timings().is_empty() {
+                println!("Nothing timed yet");
+            }
+
+            for (operation, timings) in process.timings().iter() {
+                println!(
+                    "{}: {} in {:?}, mean {:?}, min {:?}, max {:?}",
+                    operation,
+                    timings.count(),
+                    timings.total(),
+                    timings.mean(),
+                    timings.min(),
+                    timings.max()
+                );
+            }
+        }
+        ["timings", "reset"] => process.reset_timings(),
+        _ => anyhow::bail!("Usage: maintenance timings [reset]"),
+    }
+
+    Ok(())
+}
+
+// commands run on the control thread so their arguments have to be owned
+fn owned_args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+fn borrowed_args(args: &[String]) -> Vec<&str> {
+    args.iter().map(String::as_str).collect()
+}
+
+pub(crate) fn print_current_stop(controller: &sdb::ProcessController) -> anyhow::Result<()> {
+    let (info, hits, watched, forked, panic) = controller.execute_target(|target| {
+        let mut info = target.stop_info()?;
+        source::remap(target, &mut info.location);
+
+        // how much a limited breakpoint has skipped to get here
+        let hits = info
+            .breakpoint_site()
+            .and_then(|id| target.process().breakpoint_sites().get_by_id(id).ok())
+            .filter(|site| site.hit_limit().is_some())
+            .map(|site| (site.id(), site.hit_count(), site.skipped_count()));
+        // a local's watchpoint stops without a change when its frame returns,
+        // and a read has no old value
+        let watched = info
+            .reason
+            .watchpoint
+            .and_then(|id| target.process().watchpoints().get_by_id(id).ok())
+            .map(|watchpoint| match watchpoint.is_out_of_scope() {
+                true => None,
+                false => {
+                    let previous = (info.reason.watch_access != Some(sdb::WatchpointMode::Read))
+                        .then(|| watched_value(watchpoint.previous().unwrap_or_default()));
+                    Some((previous, watched_value(watchpoint.value())))
+                }
+            });
+        let process = target.process();
+        sdb::Result::Ok((
+            info,
+            hits,
+            watched,
+            process.forked_child(),
+            process.panic_message(),
+        ))
+    })??;
+    print_stop_info(Some(controller.handle().get_id()), info.clone());
+
+    if let (Some(id), Some(None)) = (info.reason.watchpoint, &watched) {
+        if json::enabled() {
+            json::emit("watchpoint_scope", [("id", Json::number(id))]);
+        } else {
+            info!(
+                "Watchpoint {} disabled, the frame of the local it watches has returned",
+                id
+            );
+        }
+    }
+    if let Some(Some((None, value))) = &watched {
+        if json::enabled() {
+            json::emit(
+                "watchpoint_read",
+                [
+                    ("id", Json::optional(info.reason.watchpoint, Json::number)),
+                    ("value", Json::string(value)),
+                ],
+            );
+        } else {
+            info!("Value = {}", value);
+        }
+    }
+    if let Some(Some((Some(old), new))) = watched {
+        if json::enabled() {
+            json::emit(
+                "watchpoint_change",
+                [
+                    ("id", Json::optional(info.reason.watchpoint, Json::number)),
+                    ("old", Json::string(old)),
+                    ("new", Json::string(new)),
+                ],
+            );
+        } else {
+            info!("Old value = {}", old);
+            info!("New value = {}", new);
+        }
+    }
+
+    if let Some((id, hits, skipped)) = hits {
+        if json::enabled() {
+            json::emit(
+                "breakpoint_hits",
+                [
+                    ("id", Json::number(id)),
+                    ("hits", Json::number(hits)),
+                    ("skipped", Json::number(skipped)),
+                ],
+            );
+        } else {
+            info!("Breakpoint {} hit {} times, {} skipped", id, hits, skipped);
+        }
+    }
+
+    if let Some(message) = panic {
+        if json::enabled() {
+            json::emit("panic", [("message", Json::string(message))]);
+        } else {
+            info!("Panicked: {}", message);
+        }
+    }
+
+    if let Some(child) = forked {
+        if json::enabled() {
+            json::emit("forked", [("child", Json::number(child.as_raw()))]);
+        } else {
+            info!(
+                "Forked child {}, set follow-fork-mode picks the side to follow",
+                child
+            );
+        }
+    }
+
+    if info.reason.reason == sdb::ProcessState::Stopped {
+        controller.execute_target(display::show_all)?;
+    }
+
+    exit_hooks::run_exit_hooks(controller, &info);
+
+    Ok(())
+}
+
+type Handler = fn(&dyn DebuggerContext, &[&str]) -> anyhow::Result<()>;
+
+pub(crate) static COMMANDS: &[ReplCommand<Handler>] = &[
+    ReplCommand {
+        name: "continue",
+        aliases: &["c"],
+        usage: "continue",
+        description: "Resume the process until it stops, Ctrl-C interrupts it",
+        run: |context, args| {
+            let [] = args else {
+                anyhow::bail!("Usage: continue");
+            };
+
+            info!("Resuming process ...");
+            loop {
+                context.resume()?;
+                context.wait_for_stop()?;
+
+                // a script's stop hooks can let a breakpoint go on without stopping
+                if !context.run_stop_hooks()? {
+                    break;
+                }
+            }
+            context.print_current_stop()
+        },
+    },
+    ReplCommand {
+        name: "reverse-continue",
+        aliases: &["rc"],
+        usage: "reverse-continue",
+        description: "Go back to the last breakpoint hit by replaying a checkpoint to the same number of hits, only breakpoint hits are counted so it needs one to go back to, for programs that run the same way every time",
+        run: |context, args| {
+            let [] = args else {
+                anyhow::bail!("Usage: reverse-continue");
+            };
+
+            info!("Replaying from a checkpoint ...");
+            context.with_target(Box::new(checkpoint::handle_reverse_continue_command))?;
+            context.print_current_stop()
+        },
+    },
+    ReplCommand {
+        name: "checkpoint",
+        aliases: &[],
+        usage: checkpoint::USAGE,
+        description: "Keep a copy of the process to go back to, or list, restore, or delete them",
+        run: |context, args| {
+            let restore = matches!(args, ["restore", _]);
+            let args = owned_args(args);
+            context.with_target(Box::new(move |target| {
+                checkpoint::handle_checkpoint_command(target, &borrowed_args(&args))
+            }))?;
+            if restore {
+                context.print_current_stop()?;
+            }
+            Ok(())
+        },
+    },
+    ReplCommand {
+        name: "restart",
+        aliases: &["run"],
+        usage: "restart [--entry]",
+        description: "Launch the process again with the same breakpoints and stop at main",
+        run: |context, args| {
+            let to_main = match args {
+                [] => true,
+                ["--entry"] => false,
+                _ => anyhow::bail!("Usage: restart [--entry]"),
+            };
+
+            info!("Restarting process ...");
+            context.with_target(Box::new(move |target| {
+                target.restart(to_main)?;
+                Ok(())
+            }))?;
+            context.print_current_stop()
+        },
+    },
+    ReplCommand {
+        name: "stepi",
+        aliases: &["si"],
+        usage: "stepi",
+        description: "Step a single instruction",
+        run: |context, args| {
+            let [] = args else {
+                anyhow::bail!("Usage: stepi");
+            };
+
+            context.step_instruction()?;
+            context.print_current_stop()
+        },
+    },
+    ReplCommand {
+        name: "step",
+        aliases: &["s"],
+        usage: "step",
+        description: "Step to the next source line, into calls",
+        run: |context, args| {
+            let [] = args else {
+                anyhow::bail!("Usage: step");
+            };
+
+            context.request(sdb::Request::StepIn)?;
+            context.print_current_stop()
+        },
+    },
+    ReplCommand {
+        name: "next",
+        aliases: &["n"],
+        usage: "next",
+        description: "Step to the next source line, over calls",
+        run: |context, args| {
+            let [] = args else {
+                anyhow::bail!("Usage: next");
+            };
+
+            context.request(sdb::Request::StepOver)?;
+            context.print_current_stop()
+        },
+    },
+    ReplCommand {
+        name: "finish",
+        aliases: &[],
+        usage: "finish",
+        description: "Run until the current function returns and show what it returned",
+        run: |context, args| {
+            let [] = args else {
+                anyhow::bail!("Usage: finish");
+            };
+
+            if !context.is_local() {
+                return remote::handle_finish_command(context);
+            }
+
+            context.with_target(Box::new(|target| {
+                let function = target.function_name_at(target.get_pc()?);
+                if let (_, Some(value)) = target.finish()? {
+                    print_return_value(function, value);
+                }
+                Ok(())
+            }))?;
+            context.print_current_stop()
+        },
+    },
+    ReplCommand {
+        name: "return",
+        aliases: &[],
+        usage: "return [expression]",
+        description: "Pop the current function's frame without running the rest of it, returning the expression's value in rax",
+        run: |context, args| {
+            let value = match args {
+                [] => None,
+                args => Some(args.join(" ").parse::<sdb::expr::Expr>()?),
+            };
+
+            context.with_target(Box::new(move |target| {
+                let function = target.function_name_at(target.get_pc()?);
+                let value = value.map(|value| value.evaluate(&*target)).transpose()?;
+                let caller = target.return_now(value.map(|value| value as u64))?;
+                match function {
+                    Some(function) => info!("Returned from {} to {}", function, caller),
+                    None => info!("Returned to {}", caller),
+                }
+                Ok(())
+            }))?;
+            context.print_current_stop()
+        },
+    },
+    ReplCommand {
+        name: "backtrace",
+        aliases: &["bt"],
+        usage: "backtrace",
+        description: "Show the call stack of the current thread",
+        run: |context, args| {
+            let [] = args else {
+                anyhow::bail!("Usage: backtrace");
+            };
+
+            if !context.is_local() {
+                return remote::handle_backtrace_command(context);
+            }
+
+            context.with_target(Box::new(|target| {
+                let mut frames = target.backtrace()?;
+                for frame in &mut frames {
+                    source::remap(target, &mut frame.location);
+                }
+                print_frames(&frames);
+                Ok(())
+            }))
+        },
+    },
+    ReplCommand {
+        name: "list",
+        aliases: &["l"],
+        usage: source::LIST_USAGE,
+        description: "Show source lines around the current stop, a function, or a line, again to carry on",
+        run: |context, args| {
+            let args = owned_args(args);
+            context.with_target(Box::new(move |target| {
+                source::handle_list_command(target, &borrowed_args(&args))
+            }))
+        },
+    },
+    ReplCommand {
+        name: "info",
+        aliases: &[],
+        usage: LIVE_INFO_USAGE,
+        description: "Show the current thread's thread-local storage, the auxiliary vector, or what /proc says about the process, the traced allocations that haven't been freed, or the GOT's slots and where they point",
+        run: |context, args| {
+            let args = owned_args(args);
+            match borrowed_args(&args).as_slice() {
+                ["proc", ..] => context.with_process(Box::new(move |process| {
+                    proc_info::handle_info_proc(process, &borrowed_args(&args)[1..])
+                })),
+                ["threads"] => context.with_process(Box::new(|process| {
+                    print_threads(process);
+                    Ok(())
+                })),
+                ["got", ..] => context.with_target(Box::new(move |target| {
+                    got::handle_info_got(target, &borrowed_args(&args)[1..])
+                })),
+                ["allocations"] => {
+                    context.with_target(Box::new(|target| trace::print_allocations(target)))
+                }
+                ["tls"] | ["auxv"] => context.with_target(Box::new(move |target| {
+                    handle_info_command(target, &borrowed_args(&args))
+                })),
+                _ => anyhow::bail!("Usage: {}", LIVE_INFO_USAGE),
+            }
+        },
+    },
+    ReplCommand {
+        name: "thread",
+        aliases: &[],
+        usage: "thread [tid|name]",
+        description: "List the threads, or switch to one by its tid or the start of its name",
+        run: |context, args| match args {
+            [] => context.with_process(Box::new(|process| {
+                print_threads(process);
+                Ok(())
+            })),
+            [spec] => {
+                let spec = spec.to_string();
+                context.with_process(Box::new(move |process| {
+                    let tid = process.find_thread(&spec)?;
+                    process.set_current_thread(tid)?;
+                    Ok(())
+                }))?;
+                context.print_current_stop()
+            }
+            _ => anyhow::bail!("Usage: thread [tid|name]"),
+        },
+    },
+    ReplCommand {
+        name: "break",
+        aliases: &["b"],
+        usage: BREAK_USAGE_LINE,
+        description: "Set a breakpoint on a function, a source line, a line near the current one, or an address",
+        run: |context, args| {
+            if !context.is_local() {
+                return remote::handle_break_command(context, args);
+            }
+
+            let args = owned_args(args);
+            context.with_target(Box::new(move |target| {
+                handle_break_command(target, &borrowed_args(&args))
+            }))
+        },
+    },
+    ReplCommand {
+        name: "dprintf",
+        aliases: &[],
+        usage: DPRINTF_USAGE,
+        description: "Set a breakpoint that prints a printf style message of registers, variables, and expressions and carries on",
+        run: |context, args| {
+            let args = owned_args(args);
+            context.with_target(Box::new(move |target| {
+                handle_dprintf_command(target, &borrowed_args(&args))
+            }))
+        },
+    },
+    ReplCommand {
+        name: "catch",
+        aliases: &[],
+        usage: CATCH_USAGE,
+        description: "Stop when the process receives a signal, even one that's passed through to it, panics, or forks, execs, or exits",
+        run: |context, args| {
+            let args = owned_args(args);
+            context.with_target(Box::new(move |target| {
+                handle_catch_command(target, &borrowed_args(&args))
+            }))
+        },
+    },
+    ReplCommand {
+        name: "stoppoints",
+        aliases: &[],
+        usage: "stoppoints [--all]",
+        description: "List breakpoints, catchpoints, and hooks, --all includes the debugger's own",
+        run: |context, args| {
+            let args = owned_args(args);
+            context.with_process(Box::new(move |process| {
+                print_stoppoints(process, &borrowed_args(&args))
+            }))
+        },
+    },
+    ReplCommand {
+        name: "enable",
+        aliases: &[],
+        usage: "enable <id>",
+        description: "Enable a breakpoint, catchpoint, or hook",
+        run: |context, args| {
+            let id = stoppoint_id("enable", args)?;
+            context.with_process(Box::new(move |process| Ok(process.enable_stoppoint(id)?)))
+        },
+    },
+    ReplCommand {
+        name: "disable",
+        aliases: &[],
+        usage: "disable <id>",
+        description: "Disable a breakpoint, catchpoint, or hook, keeping it to enable later",
+        run: |context, args| {
+            let id = stoppoint_id("disable", args)?;
+            context.with_process(Box::new(move |process| Ok(process.disable_stoppoint(id)?)))
+        },
+    },
+    ReplCommand {
+        name: "watch",
+        aliases: &[],
+        usage: WATCH_USAGE,
+        description: "Stop when a variable or the memory at an address is written, in a debug register if it fits or else checked after every instruction the current thread runs, a local is watched until its frame returns",
+        run: |context, args| {
+            let args = owned_args(args);
+            context.with_target(Box::new(move |target| {
+                handle_watch_command(target, &borrowed_args(&args), sdb::WatchpointMode::Write)
+            }))
+        },
+    },
+    ReplCommand {
+        name: "rwatch",
+        aliases: &[],
+        usage: RWATCH_USAGE,
+        description: "Stop when a variable or the memory at an address is read, it needs a free debug register",
+        run: |context, args| {
+            let args = owned_args(args);
+            context.with_target(Box::new(move |target| {
+                handle_watch_command(target, &borrowed_args(&args), sdb::WatchpointMode::Read)
+            }))
+        },
+    },
+    ReplCommand {
+        name: "awatch",
+        aliases: &[],
+        usage: AWATCH_USAGE,
+        description: "Stop when a variable or the memory at an address is read or written, it needs a free debug register",
+        run: |context, args| {
+            let args = owned_args(args);
+            context.with_target(Box::new(move |target| {
+                handle_watch_command(
+                    target,
+                    &borrowed_args(&args),
+                    sdb::WatchpointMode::ReadWrite,
+                )
+            }))
+        },
+    },
+    ReplCommand {
+        name: "delete",
+        aliases: &["d"],
+        usage: "delete <id>",
+        description: "Delete a breakpoint, catchpoint, or hook",
+        run: |context, args| {
+            let id = stoppoint_id("delete", args)?;
+            context.with_process(Box::new(move |process| Ok(process.remove_stoppoint(id)?)))
+        },
+    },
+    ReplCommand {
+        name: "register",
+        aliases: &[],
+        usage: REGISTER_USAGE,
+        description: "Read or write registers of the current thread, or save and load all of them",
+        run: |context, args| {
+            if !context.is_local() {
+                return remote::handle_register_command(context, args);
+            }
+
+            let args = owned_args(args);
+            context.with_target(Box::new(move |target| {
+                handle_register_command(target, &borrowed_args(&args))
+            }))
+        },
+    },
+    ReplCommand {
+        name: "memory",
+        aliases: &[],
+        usage: "memory <write [--mprotect] <address> <byte>...|dump [--memfd|--vm-readv] <address> <size> <file>>",
+        description: "Write memory, or dump a region of it to a file",
+        run: |context, args| {
+            let args = owned_args(args);
+            context.with_process(Box::new(move |process| {
+                handle_memory_command(process, &borrowed_args(&args))
+            }))
+        },
+    },
+    ReplCommand {
+        name: "syscall",
+        aliases: &[],
+        usage: SYSCALL_USAGE,
+        description: "Make the current thread run a syscall, errors come back as -errno",
+        run: |context, args| {
+            let args = owned_args(args);
+            context.with_process(Box::new(move |process| {
+                handle_syscall_command(process, &borrowed_args(&args))
+            }))
+        },
+    },
+    ReplCommand {
+        name: "undo",
+        aliases: &[],
+        usage: UNDO_USAGE,
+        description: "Put back memory and registers written from the debugger, or list what can be",
+        run: |context, args| {
+            let args = owned_args(args);
+            context.with_process(Box::new(move |process| {
+                handle_undo_command(process, &borrowed_args(&args))
+            }))
+        },
+    },
+    ReplCommand {
+        name: "print",
+        aliases: &["p"],
+        usage: examine::PRINT_USAGE,
+        description: "Show a local or global variable as its type says, call a function, or evaluate an expression of registers, memory, and symbols, /x and the like pick how it's shown",
+        run: |context, args| {
+            let args = owned_args(args);
+            context.with_target(Box::new(move |target| {
+                examine::handle_print_command(target, &borrowed_args(&args))
+            }))
+        },
+    },
+    ReplCommand {
+        name: "display",
+        aliases: &[],
+        usage: display::USAGE,
+        description: "Print an expression now and after every stop, with no expression print them all again",
+        run: display::handle_display_command,
+    },
+    ReplCommand {
+        name: "undisplay",
+        aliases: &[],
+        usage: display::UNDISPLAY_USAGE,
+        description: "Stop printing displays after stops, all of them with no ids",
+        run: display::handle_undisplay_command,
+    },
+    ReplCommand {
+        name: "set",
+        aliases: &[],
+        usage: examine::SET_USAGE,
+        description: "Set a local or global variable, or part of one, undo puts it back, or where sources are read from",
+        run: |context, args| {
+            let args = owned_args(args);
+            context.with_target(Box::new(move |target| {
+                examine::handle_set_command(target, &borrowed_args(&args))
+            }))
+        },
+    },
+    ReplCommand {
+        name: "x",
+        aliases: &[],
+        usage: examine::EXAMINE_USAGE,
+        description: "Examine memory at an expression's address as hex, decimal, chars, or strings, no expression carries on from the last",
+        run: |context, args| {
+            let args = owned_args(args);
+            context.with_target(Box::new(move |target| {
+                examine::handle_examine_command(target, &borrowed_args(&args))
+            }))
+        },
+    },
+    ReplCommand {
+        name: "disassemble",
+        aliases: &[],
+        usage: disassemble::USAGE,
+        description: "Disassemble a function or from an address, /s with its source lines",
+        run: |context, args| {
+            let args = owned_args(args);
+            context.with_target(Box::new(move |target| {
+                disassemble::handle_disassemble_command(target, &borrowed_args(&args))
+            }))
+        },
+    },
+    ReplCommand {
+        name: "heap",
+        aliases: &[],
+        usage: heap::USAGE,
+        description: "Walk glibc malloc's arenas: list their chunks, sum them up, or check their metadata",
+        run: |context, args| {
+            let args = owned_args(args);
+            context.with_target(Box::new(move |target| {
+                heap::handle_heap_command(target, &borrowed_args(&args))
+            }))
+        },
+    },
+    ReplCommand {
+        name: "checksec",
+        aliases: &[],
+        usage: checksec::USAGE,
+        description: "Report the executable's or a module's exploit mitigations: PIE, RELRO, NX, stack canaries, and fortified functions",
+        run: |context, args| {
+            let args = owned_args(args);
+            context.with_target(Box::new(move |target| {
+                checksec::handle_checksec_command(target, &borrowed_args(&args))
+            }))
+        },
+    },
+    ReplCommand {
+        name: "find",
+        aliases: &[],
+        usage: examine::FIND_USAGE,
+        description: "Search the process's memory for a string or values, /b /h /w /g pick their size, --perms and --in pick which mappings",
+        run: |context, args| {
+            let args = owned_args(args);
+            context.with_target(Box::new(move |target| {
+                examine::handle_find_command(target, &borrowed_args(&args))
+            }))
+        },
+    },
+    ReplCommand {
+        name: "monitor",
+        aliases: &[],
+        usage: "monitor variable <name> [--interval 100ms] [--csv <file>]",
+        description: "Sample a global while the process runs",
+        run: monitor::handle_monitor_command,
+    },
+    ReplCommand {
+        name: "profile",
+        aliases: &[],
+        usage: profile::USAGE,
+        description: "Sample every thread's stack while the process runs and report where the time goes",
+        run: profile::handle_profile_command,
+    },
+    ReplCommand {
+        name: "perf",
+        aliases: &[],
+        usage: perf::USAGE,
+        description: "Count instructions, cycles, and cache misses while the process runs, between two stops say",
+        run: |context, args| {
+            if !matches!(args.first(), Some(&("start" | "stop" | "report"))) {
+                anyhow::bail!("Usage: {}", perf::USAGE);
+            }
+            let args = owned_args(args);
+            context.with_process(Box::new(move |process| {
+                perf::handle_perf_command(process, &args)
+            }))
+        },
+    },
+    ReplCommand {
+        name: "trace",
+        aliases: &[],
+        usage: trace::USAGE,
+        description: "Print the syscalls the process makes as it runs, with their arguments and results, or its allocations and frees",
+        run: |context, args| {
+            let args = owned_args(args);
+            context.with_target(Box::new(move |target| {
+                trace::handle_trace_command(target, &args)
+            }))
+        },
+    },
+    ReplCommand {
+        name: "events",
+        aliases: &[],
+        usage: event_log::USAGE,
+        description: "Show the latest stops and captured output in the order they happened",
+        run: event_log::handle_events_command,
+    },
+    ReplCommand {
+        name: "script",
+        aliases: &[],
+        usage: scripting::USAGE,
+        description: "Run a Rhai script against the process, --clear drops its stop hooks",
+        run: scripting::handle_script_command,
+    },
+    ReplCommand {
+        name: "generate-core-file",
+        aliases: &[],
+        usage: "generate-core-file [path]",
+        description: "Dump a core file of the process",
+        run: |context, args| {
+            let path = match args {
+                [] => format!("core.{}", context.pid()?),
+                [path] => path.to_string(),
+                _ => anyhow::bail!("Usage: generate-core-file [path]"),
+            };
+            context.with_process(Box::new(move |process| {
+                let transfer = process.write_core(&path)?;
+                info!("Saved core file {}", path);
+                info!("Read {}", transfer);
+                Ok(())
+            }))
+        },
+    },
+    ReplCommand {
+        name: "detach",
+        aliases: &[],
+        usage: "detach",
+        description: "Take the breakpoints out and let the process run on without the debugger",
+        run: |context, args| {
+            let [] = args else {
+                anyhow::bail!("Usage: detach");
+            };
+
+            context.with_process(Box::new(|process| {
+                process.detach()?;
+                info!("Detached from process {}", process.get_id());
+                Ok(())
+            }))
+        },
+    },
+    ReplCommand {
+        name: "kill",
+        aliases: &[],
+        usage: "kill",
+        description: "Kill the process",
+        run: |context, args| {
+            let [] = args else {
+                anyhow::bail!("Usage: kill");
+            };
+
+            context.with_process(Box::new(|process| {
+                process.kill()?;
+                info!("Killed process {}", process.get_id());
+                Ok(())
+            }))
+        },
+    },
+    ReplCommand {
+        name: "on-exit",
+        aliases: &[],
+        usage: exit_hooks::USAGE,
+        description: "Add actions to run when the process crashes or exits, or list them",
+        run: exit_hooks::handle_on_exit_command,
+    },
+    ReplCommand {
+        name: "maintenance",
+        aliases: &[],
+        usage: "maintenance timings [reset]",
+        description: "Show or reset how long debugger operations have taken",
+        run: |context, args| {
+            let args = owned_args(args);
+            context.with_process(Box::new(move |process| {
+                handle_maintenance_command(process, &borrowed_args(&args))
+            }))
+        },
+    },
+    ReplCommand {
+        name: "hook",
+        aliases: &[],
+        usage: "hook <function <name> --replace <addr|nop|ret0>|list|enable <id>|disable <id>|delete <id>>",
+        description: "Replace functions so they never run",
+        run: |context, args| {
+            let args = owned_args(args);
+            context.with_target(Box::new(move |target| {
+                handle_hook_command(target, &borrowed_args(&args))
+            }))
+        },
+    },
+    ReplCommand {
+        name: "cd",
+        aliases: &[],
+        usage: "cd <dir>",
+        description: "Change sdb's working directory, the process starts there from the next restart",
+        run: |context, args| {
+            let [dir] = args else {
+                anyhow::bail!("Usage: cd <dir>");
+            };
+            std::env::set_current_dir(dir).map_err(|err| anyhow::anyhow!("{}: {}", dir, err))?;
+
+            let dir = std::env::current_dir()?;
+            info!("Working directory {}", dir.display());
+            context.with_target(Box::new(move |target| {
+                target.set_working_directory(dir);
+                Ok(())
+            }))
+        },
+    },
+    ReplCommand {
+        name: "pwd",
+        aliases: &[],
+        usage: "pwd",
+        description: "Print sdb's working directory, and the process's if it's somewhere else",
+        run: |context, args| {
+            if !args.is_empty() {
+                anyhow::bail!("Usage: pwd");
+            }
+
+            let dir = std::env::current_dir()?;
+            info!("Working directory {}", dir.display());
+            // gone along with the process, or somewhere else entirely
+            if let Ok(pid) = context.pid()
+                && let Ok(process_dir) = std::fs::read_link(format!("/proc/{}/cwd", pid))
+                && process_dir != dir
+            {
+                info!("Process {} is in {}", pid, process_dir.display());
+            }
+            Ok(())
+        },
+    },
+    ReplCommand {
+        name: "alias",
+        aliases: &[],
+        usage: commands::ALIAS_USAGE,
+        description: "Define your own names for commands, or list them",
+        run: |_, args| commands::alias(COMMANDS, args),
+    },
+    ReplCommand {
+        name: "help",
+        aliases: &[],
+        usage: "help [command]",
+        description: "List the commands, or show how to use one",
+        run: |_, args| commands::help(COMMANDS, args),
+    },
+];
+
+// a stopped process killed from outside is only noticed once it's waited on,
+// until then every command would fail on it without saying why
+fn reap_if_gone(controller: &sdb::ProcessController) {
+    let handle = controller.handle();
+    if handle.get_state() != sdb::ProcessState::Stopped || !handle.has_exited() {
+        return;
+    }
+
+    warn!("Process {} disappeared", handle.get_id());
+    if let Err(err) = controller
+        .wait_on_signal()
+        .map_err(anyhow::Error::from)
+        .and_then(|_| print_current_stop(controller))
+    {
+        error!("{}", err);
+    }
+}
 
-    if command.starts_with("cont") {
-        info!("Resuming process ...");
-        process.resume()?;
-        // TODO: this is hanging for some reason
-        /*let status = process.wait_on_signal()?;
-        print_stop_reason(process, status);*/
+pub(crate) fn handle_command(
+    context: &dyn DebuggerContext,
+    command: impl Into<String>,
+) -> anyhow::Result<()> {
+    commands::dispatch(COMMANDS, &command.into(), |run, args| run(context, args))
+}
+
+pub(crate) fn emit_result(line: &str, result: anyhow::Result<()>) {
+    let mut fields = vec![("command", Json::string(line))];
+    match result {
+        Ok(()) => fields.push(("status", Json::string("done"))),
+        Err(err) => {
+            fields.push(("status", Json::string("error")));
+            fields.push(("message", Json::string(err)));
+        }
+    }
+    json::emit("result", fields);
+}
+
+// one command per line of stdin, each followed by a result record
+fn run_json(mut handle_command: impl FnMut(String) -> anyhow::Result<()>) -> anyhow::Result<()> {
+    for line in std::io::stdin().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let result = handle_command(line.clone());
+        emit_result(&line, result);
     }
 
     Ok(())
 }
 
-fn run(mut process: sdb::Process) -> anyhow::Result<()> {
+// runs commands until the user quits, an empty line repeats the last command
+pub(crate) fn run(
+    mut handle_command: impl FnMut(String) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    script::run_startup(&mut handle_command);
+
+    if json::enabled() {
+        return run_json(handle_command);
+    }
+
     let mut rl = DefaultEditor::new()?;
     loop {
         let readline = rl.readline(">> ");
@@ -84,7 +2301,9 @@ fn run(mut process: sdb::Process) -> anyhow::Result<()> {
                 } else {
                     rl.add_history_entry(line.as_str())?;
                 }
-                handle_command(&mut process, line)?;
+                if let Err(err) = handle_command(line) {
+                    error!("{}", err);
+                }
             }
             Err(ReadlineError::Interrupted) => {
                 break;
@@ -104,21 +2323,433 @@ fn run(mut process: sdb::Process) -> anyhow::Result<()> {
 fn main() -> anyhow::Result<()> {
     let options = argh::from_env::<Options>();
 
+    if options.json {
+        json::enable();
+    }
+    sdb::set_debuginfod_enabled(options.debuginfod);
     init_logging()?;
+    interrupt::install()?;
+    script::init(options.no_rc, options.script);
 
-    let process = match options.command {
+    let mut demo = None;
+    let mut listen = None;
+    let mut gdbserver = None;
+    let mut follow_restarts = None;
+    let mut controller = match options.command {
         Command::Attach(command) => {
-            info!("Attaching to process {} ...", command.process_id);
-            sdb::Process::attach(command.process_id)?
+            listen = command.listen;
+            gdbserver = command.gdbserver;
+            let pid = match (command.process_id, command.waitfor) {
+                (Some(pid), None) => Pid::from_raw(pid),
+                (None, Some(name)) => waitfor::wait_for_process(&name)?,
+                _ => anyhow::bail!("attach takes a process id or --waitfor <name>"),
+            };
+            if command.follow_restarts {
+                follow_restarts = Some(pid);
+            }
+            info!("Attaching to process {} ...", pid);
+            sdb::ProcessController::attach(pid.as_raw())?
             // TODO: if the error from this is operation not permitted
             // print something like gdb does about how
             // "if the uid is the same, fix this at the system level"
         }
         Command::Spawn(command) => {
+            listen = command.listen;
+            gdbserver = command.gdbserver;
             info!("Spawning process from {} ...", command.path);
-            sdb::Process::launch(command.path, true, None)?
+            if command.capture_output && (command.stdout.is_some() || command.stderr.is_some()) {
+                anyhow::bail!("--capture-output can't be used with --stdout or --stderr");
+            }
+            let mut config = sdb::LaunchConfig::new(command.path);
+            if let Some(path) = command.stdin {
+                config = config.stdin(sdb::Redirect::file(path));
+            }
+            if let Some(path) = command.stdout {
+                config = config.stdout(sdb::Redirect::file(path));
+            }
+            if let Some(path) = command.stderr {
+                config = config.stderr(sdb::Redirect::file(path));
+            }
+            if command.capture_output {
+                // still shown as it's written, the log gets it line by line
+                let output = sdb::OutputCapture::new();
+                config = config
+                    .stdout(output.redirect_events_and_dup(std::io::stdout())?)
+                    .stderr(output.redirect_events_and_dup(std::io::stderr())?);
+            }
+            if command.pty {
+                // stdout and stdin are the JSON records
+                if json::enabled() {
+                    anyhow::bail!("--pty can't be used with --json");
+                }
+                config = config.terminal(pty::open()?);
+            }
+            if let Some(cwd) = command.cwd {
+                config = config.cwd(cwd);
+            }
+            if let Some(tty) = command.tty {
+                if command.pty {
+                    anyhow::bail!("--tty can't be used with --pty");
+                }
+                config = config.tty(tty)?;
+            }
+            sdb::ProcessController::launch_with(config)?
+        }
+        Command::Demo(command) => {
+            let path = match command.target {
+                Some(path) => path,
+                None => demo::default_target()?.to_string_lossy().into_owned(),
+            };
+            demo = Some(!command.auto);
+
+            info!("Spawning demo process from {} ...", path);
+            sdb::ProcessController::launch(path, true, None)?
+        }
+        Command::Core(command) => {
+            info!("Opening core {} ...", command.core);
+            let target = sdb::CoreTarget::open(command.exe, command.core)?;
+            return core_dump::run(target);
+        }
+        Command::Connect(command) => {
+            info!("Connecting to {} ...", command.address);
+            return remote::run(sdb::StreamTransport::connect(&command.address)?);
+        }
+        Command::Target(command) => {
+            if command.kind != "remote" {
+                anyhow::bail!("Usage: target remote <host:port>");
+            }
+
+            info!("Connecting to {} ...", command.address);
+            return remote::run(sdb::remote::RemoteProcess::connect(&command.address)?);
         }
     };
 
-    run(process)
+    if !options.no_catch_fatal {
+        controller.execute_target(|target| target.catch_fatal(sdb::DEFAULT_FATAL_FUNCTIONS))??;
+    }
+    event_log::record(&controller)?;
+
+    if let Some(address) = gdbserver {
+        info!("Waiting for gdb on {} ...", address);
+        sdb::remote::Server::new(sdb::InProcessTransport::new(controller)).listen(address)?;
+        return Ok(());
+    }
+
+    if let Some(address) = listen {
+        return remote::serve(&controller, &address);
+    }
+
+    if let Some(pause) = demo {
+        demo::run(&controller, pause)?;
+
+        // auto runs are a smoke test, there's no one to hand the session to
+        if !pause {
+            return Ok(());
+        }
+    }
+
+    let Some(pid) = follow_restarts else {
+        return run(|line| {
+            reap_if_gone(&controller);
+            handle_command(&controller, line)
+        });
+    };
+
+    // every line of the session says which run of the process it's about
+    let mut follow = restart::FollowRestarts::new(pid, !options.no_catch_fatal)?;
+    run(|line| {
+        reap_if_gone(&controller);
+        let span = tracing::info_span!("restart", count = follow.restarts());
+        let result = span.in_scope(|| handle_command(&controller, line.as_str()));
+        follow.record(&line, &result);
+
+        if let Some(pid) = span.in_scope(|| follow.wait(&controller)) {
+            let span = tracing::info_span!("restart", count = follow.restarts());
+            span.in_scope(|| -> anyhow::Result<()> {
+                controller = follow.reattach().map_err(|err| {
+                    anyhow::anyhow!("Couldn't attach to restarted process {}: {}", pid, err)
+                })?;
+                Ok(())
+            })?;
+        }
+
+        result
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use context::mock::MockContext;
+
+    // what a command line should ask of the process, or part of the error it should fail with
+    enum Expected {
+        Calls(&'static [&'static str]),
+        Error(&'static str),
+    }
+
+    use Expected::*;
+
+    const STOPS: &[&str] = &[
+        "resume",
+        "wait_for_stop",
+        "run_stop_hooks",
+        "print_current_stop",
+    ];
+
+    #[test]
+    fn command_dispatch() {
+        let cases: &[(&str, Expected)] = &[
+            ("", Calls(&[])),
+            ("continue", Calls(STOPS)),
+            ("c", Calls(STOPS)),
+            ("cont", Calls(STOPS)),
+            ("continue now", Error("Usage: continue")),
+            ("si", Calls(&["step_instruction", "print_current_stop"])),
+            ("restart", Calls(&["with_target", "print_current_stop"])),
+            ("run --entry", Calls(&["with_target", "print_current_stop"])),
+            ("restart now", Error("Usage: restart")),
+            ("stepi", Calls(&["step_instruction", "print_current_stop"])),
+            ("s", Calls(&["request", "print_current_stop"])),
+            ("n", Calls(&["request", "print_current_stop"])),
+            ("finish", Calls(&["with_target", "print_current_stop"])),
+            ("return", Calls(&["with_target", "print_current_stop"])),
+            ("return 1 +", Error("Expression ends too soon")),
+            ("st", Error("Ambiguous command 'st'")),
+            ("frobnicate", Error("Unknown command 'frobnicate'")),
+            ("bt", Calls(&["with_target"])),
+            ("backtrace full", Error("Usage: backtrace")),
+            ("b main", Calls(&["with_target"])),
+            ("b +2", Calls(&["with_target"])),
+            ("b -1", Calls(&["with_target"])),
+            ("b 12", Calls(&["with_target"])),
+            ("b marker --thread worker-1", Calls(&["with_target"])),
+            ("b marker --thread", Calls(&["with_target"])),
+            ("dprintf main,\"hi\\n\"", Calls(&["with_target"])),
+            (
+                "dprintf --output log.txt main \"{argc}\"",
+                Calls(&["with_target"]),
+            ),
+            ("info tls", Calls(&["with_target"])),
+            ("info auxv", Calls(&["with_target"])),
+            ("info proc", Calls(&["with_process"])),
+            ("info proc fd", Calls(&["with_process"])),
+            ("info threads", Calls(&["with_process"])),
+            ("info allocations", Calls(&["with_target"])),
+            ("info got libc", Calls(&["with_target"])),
+            ("thread", Calls(&["with_process"])),
+            (
+                "thread worker",
+                Calls(&["with_process", "print_current_stop"]),
+            ),
+            ("thread 1 2", Error("Usage: thread [tid|name]")),
+            (
+                "info",
+                Error("Usage: info tls|auxv|threads|allocations|got"),
+            ),
+            ("list", Calls(&["with_target"])),
+            ("disassemble", Calls(&["with_target"])),
+            ("checkpoint", Calls(&["with_target"])),
+            ("heap chunks", Calls(&["with_target"])),
+            ("checksec libc", Calls(&["with_target"])),
+            ("heap check", Calls(&["with_target"])),
+            ("checkpoint list", Calls(&["with_target"])),
+            (
+                "checkpoint restore 1",
+                Calls(&["with_target", "print_current_stop"]),
+            ),
+            (
+                "reverse-continue",
+                Calls(&["with_target", "print_current_stop"]),
+            ),
+            ("disassemble/s main", Calls(&["with_target"])),
+            ("l main", Calls(&["with_target"])),
+            ("catch signal SIGUSR1", Calls(&["with_target"])),
+            ("catch fork", Calls(&["with_target"])),
+            ("catch panic", Calls(&["with_target"])),
+            ("stoppoints --all", Calls(&["with_process"])),
+            ("enable 3", Calls(&["with_process"])),
+            ("disable 3", Calls(&["with_process"])),
+            ("d 3", Calls(&["with_process"])),
+            ("delete", Error("Usage: delete <id>")),
+            ("enable three", Error("invalid digit")),
+            ("register read rip", Calls(&["with_target"])),
+            ("memory write 0x1000 0x90", Calls(&["with_process"])),
+            ("p $rip", Calls(&["with_target"])),
+            ("print/x $rip", Calls(&["with_target"])),
+            ("x/4xg $rsp", Calls(&["with_target"])),
+            ("find/g --in [stack] 0", Calls(&["with_target"])),
+            ("set var numbers[2] = 5", Calls(&["with_target"])),
+            ("set substitute-path /build /src", Calls(&["with_target"])),
+            ("set inferior-tty /dev/pts/3", Calls(&["with_target"])),
+            ("set scheduler-locking on", Calls(&["with_target"])),
+            ("set step-into-no-debug on", Calls(&["with_target"])),
+            ("watch counter", Calls(&["with_target"])),
+            ("watch 0x1000 16", Calls(&["with_target"])),
+            ("rwatch counter", Calls(&["with_target"])),
+            ("awatch 0x1000 8", Calls(&["with_target"])),
+            ("display", Calls(&["with_target"])),
+            ("display/x $rip", Calls(&["with_target"])),
+            (
+                "display/s name",
+                Error("Usage: display[/x|d|u|o|t|c] [<expression>]"),
+            ),
+            ("undisplay 1 two", Error("Usage: undisplay [<n>]...")),
+            ("monitor", Error("monitor variable")),
+            (
+                "monitor variable counter --interval 10ms",
+                Error("No process"),
+            ),
+            ("profile --duration 1s", Error("No process")),
+            ("profile --top", Error("Usage: profile")),
+            ("perf start task-clock", Calls(&["with_process"])),
+            ("perf report", Calls(&["with_process"])),
+            ("perf", Error("Usage: perf")),
+            ("trace syscalls openat write", Calls(&["with_target"])),
+            ("trace allocations --quiet", Calls(&["with_target"])),
+            ("events 5", Calls(&[])),
+            ("events 5 6", Error("Usage: events")),
+            ("script", Error("Usage: script")),
+            ("script hooks.rhai", Error("No process")),
+            ("generate-core-file", Calls(&["with_process"])),
+            ("generate-core-file a b", Error("Usage: generate-core-file")),
+            ("detach", Calls(&["with_process"])),
+            ("detach now", Error("Usage: detach")),
+            ("kill", Calls(&["with_process"])),
+            ("kill 9", Error("Usage: kill")),
+            ("cd .", Calls(&["with_target"])),
+            ("cd", Error("Usage: cd <dir>")),
+            (
+                "cd /nowhere",
+                Error("/nowhere: No such file or directory (os error 2)"),
+            ),
+            ("pwd", Calls(&[])),
+            ("pwd /", Error("Usage: pwd")),
+            ("on-exit", Calls(&[])),
+            ("on-exit core crash.{pid}", Calls(&[])),
+            ("on-exit trace", Error("Usage: on-exit")),
+            ("on-exit command", Error("Usage: on-exit")),
+            ("on-exit delete 99", Error("No exit action 99")),
+            ("maintenance timings", Calls(&["with_process"])),
+            ("hook list", Calls(&["with_target"])),
+            ("undo", Calls(&["with_process"])),
+            ("undo all", Calls(&["with_process"])),
+            ("syscall 39", Calls(&["with_process"])),
+            ("help", Calls(&[])),
+            ("help bogus", Error("Unknown command 'bogus'")),
+        ];
+
+        for (line, expected) in cases {
+            let context = MockContext::default();
+            let result = handle_command(&context, *line);
+            match (expected, result) {
+                (Calls(calls), Ok(())) => {
+                    assert_eq!(context.calls.borrow().as_slice(), *calls, "{:?}", line)
+                }
+                (Error(message), Err(err)) => assert!(
+                    err.to_string().contains(message),
+                    "{:?} failed with {:?}, not {:?}",
+                    line,
+                    err.to_string(),
+                    message
+                ),
+                (Calls(_), Err(err)) => panic!("{:?} failed: {}", line, err),
+                (Error(message), Ok(())) => panic!("{:?} didn't fail with {:?}", line, message),
+            }
+        }
+    }
+
+    #[test]
+    fn continue_runs_until_stop_hooks_stop() {
+        let context = MockContext::default();
+        context
+            .continue_after_stop
+            .borrow_mut()
+            .extend([false, true]);
+
+        handle_command(&context, "continue").unwrap();
+        assert_eq!(
+            context.calls.borrow().as_slice(),
+            &[
+                "resume",
+                "wait_for_stop",
+                "run_stop_hooks",
+                "resume",
+                "wait_for_stop",
+                "run_stop_hooks",
+                "print_current_stop",
+            ]
+        );
+    }
+
+    #[test]
+    fn remote_sessions_use_requests() {
+        let cases: &[(&str, &[&str])] = &[
+            ("n", &["request", "print_current_stop"]),
+            ("finish", &["request", "print_current_stop"]),
+            ("bt", &["request"]),
+            ("b 0x401000", &["request"]),
+            ("b step.c:12", &["request"]),
+            ("register read rip", &["request"]),
+            ("register write rax 0x10", &["request"]),
+        ];
+
+        for (line, calls) in cases {
+            let context = MockContext {
+                remote: true,
+                ..Default::default()
+            };
+            handle_command(&context, *line).unwrap();
+            assert_eq!(context.calls.borrow().as_slice(), *calls, "{:?}", line);
+        }
+
+        let context = MockContext {
+            remote: true,
+            ..Default::default()
+        };
+        assert!(handle_command(&context, "register save regs.json").is_err());
+    }
+
+    #[test]
+    fn lines_relative_to_here() {
+        let here = sdb::SourceLocation {
+            file: "step.c".into(),
+            line: 10,
+            column: 0,
+        };
+        let line = |line| line_in_file(&here, Some(20), line);
+
+        assert_eq!(line("+3").unwrap(), 13);
+        assert_eq!(line("-9").unwrap(), 1);
+        assert_eq!(line("20").unwrap(), 20);
+        assert_eq!(line_in_file(&here, None, "+90").unwrap(), 100);
+
+        for invalid in ["+", "-", "-0", "+0", "-10", "0", "21", "+11", "+x", "ten"] {
+            assert!(line(invalid).is_err(), "{} should be rejected", invalid);
+        }
+        assert!(
+            line("21")
+                .unwrap_err()
+                .to_string()
+                .contains("21 is outside step.c")
+        );
+    }
+
+    #[test]
+    fn user_aliases_expand_before_dispatch() {
+        let context = MockContext::default();
+        handle_command(&context, "alias mock-step stepi").unwrap();
+        assert!(handle_command(&context, "alias mock-step next").is_ok());
+        assert!(handle_command(&context, "alias bt next").is_err());
+        assert!(handle_command(&context, "alias mock-none frobnicate").is_err());
+
+        handle_command(&context, "mock-step").unwrap();
+        assert_eq!(
+            context.calls.borrow().as_slice(),
+            &["request", "print_current_stop"]
+        );
+
+        handle_command(&context, "alias --delete mock-step").unwrap();
+        assert!(handle_command(&context, "mock-step").is_err());
+    }
 }
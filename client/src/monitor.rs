@@ -0,0 +1,230 @@
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use tracing::info;
+
+use crate::{
+    INTERRUPT_POLL_INTERVAL,
+    context::DebuggerContext,
+    json::{self, Json},
+    print_current_stop, wait_for_stop_polling,
+};
+
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(100);
+
+// how many of the latest samples the sparkline shows
+const SPARKLINE_WIDTH: usize = 60;
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+const USAGE: &str = "Usage: monitor variable <name> [--interval 100ms] [--csv <file>]";
+
+struct Sample {
+    elapsed: Duration,
+    value: i64,
+}
+
+// 100ms, 1s, 500us, or plain milliseconds
+pub(crate) fn parse_interval(interval: &str) -> anyhow::Result<Duration> {
+    let split = interval
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(interval.len());
+    let (amount, unit) = interval.split_at(split);
+    let amount = amount.parse()?;
+
+    let interval = match unit {
+        "" | "ms" => Duration::from_millis(amount),
+        "us" => Duration::from_micros(amount),
+        "s" => Duration::from_secs(amount),
+        _ => anyhow::bail!("Unknown interval unit {}, use us, ms, or s", unit),
+    };
+    if interval.is_zero() {
+        anyhow::bail!("The interval can't be zero");
+    }
+
+    Ok(interval)
+}
+
+// globals are read as signed integers of their size
+fn decode(bytes: &[u8]) -> Option<i64> {
+    Some(match bytes.len() {
+        1 => i8::from_ne_bytes(bytes.try_into().ok()?) as i64,
+        2 => i16::from_ne_bytes(bytes.try_into().ok()?) as i64,
+        4 => i32::from_ne_bytes(bytes.try_into().ok()?) as i64,
+        8 => i64::from_ne_bytes(bytes.try_into().ok()?),
+        _ => return None,
+    })
+}
+
+// scaled between the lowest and highest of the samples shown
+fn sparkline(samples: &[Sample]) -> String {
+    let samples = &samples[samples.len().saturating_sub(SPARKLINE_WIDTH)..];
+    let (Some(min), Some(max)) = (
+        samples.iter().map(|sample| sample.value).min(),
+        samples.iter().map(|sample| sample.value).max(),
+    ) else {
+        return String::new();
+    };
+
+    let range = (max as i128 - min as i128).max(1);
+    samples
+        .iter()
+        .map(|sample| {
+            let level = (sample.value as i128 - min as i128) * (SPARKS.len() as i128 - 1) / range;
+            SPARKS[level as usize]
+        })
+        .collect()
+}
+
+fn write_csv(path: &str, samples: &[Sample]) -> anyhow::Result<()> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    writeln!(file, "elapsed_ms,value")?;
+    for sample in samples {
+        writeln!(
+            file,
+            "{:.3},{}",
+            sample.elapsed.as_secs_f64() * 1000.0,
+            sample.value
+        )?;
+    }
+    file.flush()?;
+
+    Ok(())
+}
+
+// samples a global while the process runs, until it stops or Ctrl-C stops it
+fn monitor_variable(
+    controller: &sdb::ProcessController,
+    name: &str,
+    interval: Duration,
+    csv: Option<&str>,
+) -> anyhow::Result<()> {
+    let (address, size) = controller
+        .execute_target({
+            let name = name.to_owned();
+            move |target| target.variable_address(name)
+        })?
+        .ok_or_else(|| anyhow::anyhow!("No global variable named {}", name))?;
+    if decode(&vec![0; size]).is_none() {
+        anyhow::bail!("{} is {} bytes, only integers can be monitored", name, size);
+    }
+
+    info!(
+        "Monitoring {} at {} every {:?}, Ctrl-C to stop ...",
+        name, address, interval
+    );
+    controller.resume()?;
+
+    let handle = controller.handle();
+    let started = Instant::now();
+    let mut next_sample = started;
+    let mut samples = Vec::new();
+    let mut sample = || {
+        let now = Instant::now();
+        if now < next_sample {
+            return;
+        }
+        next_sample = now + interval;
+
+        // the process may have exited out from under us
+        let Some(value) = handle
+            .read_memory(address, size)
+            .ok()
+            .and_then(|bytes| decode(&bytes))
+        else {
+            return;
+        };
+        samples.push(Sample {
+            elapsed: now - started,
+            value,
+        });
+
+        if !json::enabled() {
+            eprint!("\r\x1b[K{} {} {}", name, sparkline(&samples), value);
+            let _ = std::io::stderr().flush();
+        }
+    };
+
+    sample();
+    let reason = wait_for_stop_polling(controller, interval.min(INTERRUPT_POLL_INTERVAL), sample);
+
+    // clear the live line
+    if !json::enabled() {
+        eprint!("\r\x1b[K");
+        let _ = std::io::stderr().flush();
+    }
+    reason?;
+
+    if json::enabled() {
+        let values = samples
+            .iter()
+            .map(|sample| {
+                Json::object([
+                    (
+                        "elapsed_ms",
+                        Json::number(format!("{:.3}", sample.elapsed.as_secs_f64() * 1000.0)),
+                    ),
+                    ("value", Json::number(sample.value)),
+                ])
+            })
+            .collect();
+        json::emit(
+            "monitor",
+            [
+                ("variable", Json::string(name)),
+                ("address", Json::string(address)),
+                ("samples", Json::Array(values)),
+            ],
+        );
+    } else if let (Some(min), Some(max), Some(last)) = (
+        samples.iter().map(|sample| sample.value).min(),
+        samples.iter().map(|sample| sample.value).max(),
+        samples.last(),
+    ) {
+        println!("{} {}", name, sparkline(&samples));
+        info!(
+            "{} samples of {}, min {}, max {}, last {}",
+            samples.len(),
+            name,
+            min,
+            max,
+            last.value
+        );
+    }
+
+    if let Some(path) = csv {
+        write_csv(path, &samples)?;
+        info!("Saved samples to {}", path);
+    }
+
+    print_current_stop(controller)
+}
+
+pub(crate) fn handle_monitor_command(
+    context: &dyn DebuggerContext,
+    args: &[&str],
+) -> anyhow::Result<()> {
+    let ["variable", name, options @ ..] = args else {
+        anyhow::bail!(USAGE);
+    };
+
+    let mut options = options;
+    let mut interval = DEFAULT_INTERVAL;
+    let mut csv = None;
+    loop {
+        options = match options {
+            [] => break,
+            ["--interval", value, rest @ ..] => {
+                interval = parse_interval(value)?;
+                rest
+            }
+            ["--csv", path, rest @ ..] => {
+                csv = Some(*path);
+                rest
+            }
+            _ => anyhow::bail!(USAGE),
+        };
+    }
+
+    monitor_variable(context.controller()?, name, interval, csv)
+}
@@ -5,6 +5,10 @@ use argh::FromArgs;
 pub enum Command {
     Attach(AttachCommand),
     Spawn(SpawnCommand),
+    Demo(DemoCommand),
+    Core(CoreCommand),
+    Connect(ConnectCommand),
+    Target(TargetCommand),
 }
 
 /// Attach to a process
@@ -12,7 +16,23 @@ pub enum Command {
 #[argh(subcommand, name = "attach")]
 pub struct AttachCommand {
     #[argh(positional)]
-    pub process_id: i32,
+    pub process_id: Option<i32>,
+
+    /// wait for a process with this name to start and attach to it as soon as it does
+    #[argh(option)]
+    pub waitfor: Option<String>,
+
+    /// serve a session on a TCP address or unix socket instead of debugging here
+    #[argh(option)]
+    pub listen: Option<String>,
+
+    /// serve gdb's remote protocol on a TCP address instead of debugging here
+    #[argh(option)]
+    pub gdbserver: Option<String>,
+
+    /// re-attach when the process exits and something restarts it with the same command line
+    #[argh(switch)]
+    pub follow_restarts: bool,
 }
 
 /// Spawn a process and attach to it
@@ -21,11 +41,113 @@ pub struct AttachCommand {
 pub struct SpawnCommand {
     #[argh(positional)]
     pub path: String,
+
+    /// serve a session on a TCP address or unix socket instead of debugging here
+    #[argh(option)]
+    pub listen: Option<String>,
+
+    /// serve gdb's remote protocol on a TCP address instead of debugging here
+    #[argh(option)]
+    pub gdbserver: Option<String>,
+
+    /// timestamp each line the process writes and keep it in the event log with its stops
+    #[argh(switch)]
+    pub capture_output: bool,
+
+    /// give the process its own terminal, shown here, with what's typed while it runs sent to it
+    #[argh(switch)]
+    pub pty: bool,
+
+    /// run the process on another terminal, like /dev/pts/3, to keep its output out of this one
+    #[argh(option)]
+    pub tty: Option<String>,
+
+    /// start the process in this directory instead of sdb's
+    #[argh(option)]
+    pub cwd: Option<String>,
+
+    /// read the process's stdin from a file
+    #[argh(option)]
+    pub stdin: Option<String>,
+
+    /// write the process's stdout to a file
+    #[argh(option)]
+    pub stdout: Option<String>,
+
+    /// write the process's stderr to a file
+    #[argh(option)]
+    pub stderr: Option<String>,
+}
+
+/// Run a guided session against a bundled target
+#[derive(Debug, PartialEq, FromArgs)]
+#[argh(subcommand, name = "demo")]
+pub struct DemoCommand {
+    /// the target to run instead of the installed looper
+    #[argh(option)]
+    pub target: Option<String>,
+
+    /// run every step without waiting and exit afterwards
+    #[argh(switch)]
+    pub auto: bool,
+}
+
+/// Inspect a core dump
+#[derive(Debug, PartialEq, FromArgs)]
+#[argh(subcommand, name = "core")]
+pub struct CoreCommand {
+    #[argh(positional)]
+    pub exe: String,
+
+    #[argh(positional)]
+    pub core: String,
+}
+
+/// Connect to a debugger started with --listen
+#[derive(Debug, PartialEq, FromArgs)]
+#[argh(subcommand, name = "connect")]
+pub struct ConnectCommand {
+    /// a TCP address or unix socket path
+    #[argh(positional)]
+    pub address: String,
+}
+
+/// Debug a target served over gdb's remote protocol
+#[derive(Debug, PartialEq, FromArgs)]
+#[argh(subcommand, name = "target")]
+pub struct TargetCommand {
+    /// only remote for now
+    #[argh(positional)]
+    pub kind: String,
+
+    /// the host:port the server is listening on
+    #[argh(positional)]
+    pub address: String,
 }
 
 /// Command line Options
 #[derive(Debug, FromArgs)]
 pub struct Options {
+    /// don't stop on abort, assertion failures, and panics
+    #[argh(switch)]
+    pub no_catch_fatal: bool,
+
+    /// print command results and stops as JSON objects, one per line
+    #[argh(switch)]
+    pub json: bool,
+
+    /// don't run the commands in ~/.sdbrc at startup
+    #[argh(switch)]
+    pub no_rc: bool,
+
+    /// download debug info nothing local has from the servers in DEBUGINFOD_URLS
+    #[argh(switch)]
+    pub debuginfod: bool,
+
+    /// run the commands in a script at startup, after ~/.sdbrc
+    #[argh(option, short = 'x')]
+    pub script: Vec<String>,
+
     #[argh(subcommand)]
     pub command: Command,
 }
@@ -0,0 +1,119 @@
+use std::sync::Mutex;
+
+use tracing::{info, warn};
+
+use crate::json::{self, Json};
+
+pub(crate) const USAGE: &str = "perf <start [event]...|stop|report>";
+
+// what start counts when it isn't told, the ones the machine can't count are left out
+const DEFAULT_EVENTS: [sdb::PerfEvent; 4] = [
+    sdb::PerfEvent::Instructions,
+    sdb::PerfEvent::Cycles,
+    sdb::PerfEvent::CacheMisses,
+    sdb::PerfEvent::TaskClock,
+];
+
+// the counters from the last start, kept after stop for report
+static COUNTERS: Mutex<Option<sdb::PerfCounters>> = Mutex::new(None);
+
+fn start(process: &sdb::Process, names: &[String]) -> anyhow::Result<()> {
+    let mut counters = match names {
+        [] => {
+            let mut counters = sdb::PerfCounters::default();
+            for event in DEFAULT_EVENTS {
+                if let Err(err) = counters.add(process, event) {
+                    warn!("{}", err);
+                }
+            }
+            if counters.is_empty() {
+                anyhow::bail!("None of the default counters can be used here");
+            }
+            counters
+        }
+        names => {
+            let events = names
+                .iter()
+                .map(|name| name.parse())
+                .collect::<sdb::Result<Vec<sdb::PerfEvent>>>()?;
+            sdb::PerfCounters::open(process, &events)?
+        }
+    };
+
+    counters.enable()?;
+    *COUNTERS.lock().unwrap() = Some(counters);
+    info!("Counting while the process runs, perf stop to stop");
+
+    Ok(())
+}
+
+fn report(counters: &sdb::PerfCounters) -> anyhow::Result<()> {
+    let readings = counters.read()?;
+
+    if json::enabled() {
+        let readings = readings
+            .into_iter()
+            .map(|reading| {
+                Json::object([
+                    ("event", Json::string(reading.event)),
+                    ("value", Json::number(reading.value)),
+                    ("scaled", Json::Bool(reading.scaled)),
+                ])
+            })
+            .collect();
+        json::emit(
+            "perf",
+            [
+                ("counting", Json::Bool(counters.is_enabled())),
+                ("counters", Json::Array(readings)),
+            ],
+        );
+        return Ok(());
+    }
+
+    for reading in readings {
+        let scaled = if reading.scaled { "  (scaled)" } else { "" };
+        match reading.event {
+            // nanoseconds
+            sdb::PerfEvent::TaskClock => println!(
+                "{:>18.3} ms  {}{}",
+                reading.value as f64 / 1e6,
+                reading.event,
+                scaled
+            ),
+            _ => println!("{:>21}  {}{}", reading.value, reading.event, scaled),
+        }
+    }
+    if counters.is_enabled() {
+        info!("Still counting");
+    }
+
+    Ok(())
+}
+
+pub(crate) fn handle_perf_command(process: &sdb::Process, args: &[String]) -> anyhow::Result<()> {
+    let Some((command, rest)) = args.split_first() else {
+        anyhow::bail!("Usage: {}", USAGE);
+    };
+
+    let mut counters = COUNTERS.lock().unwrap();
+    match (command.as_str(), rest) {
+        ("start", names) => {
+            drop(counters);
+            start(process, names)
+        }
+        ("stop", []) => {
+            let counters = counters
+                .as_mut()
+                .ok_or_else(|| anyhow::anyhow!("Nothing's being counted, perf start first"))?;
+            counters.disable()?;
+            report(counters)
+        }
+        ("report", []) => report(
+            counters
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("Nothing's been counted, perf start first"))?,
+        ),
+        _ => anyhow::bail!("Usage: {}", USAGE),
+    }
+}
@@ -0,0 +1,233 @@
+use crate::json::{self, Json};
+
+pub(crate) const USAGE: &str = "info proc [status|stat|cmdline|environ|fd]";
+
+pub(crate) fn handle_info_proc(process: &sdb::Process, args: &[&str]) -> anyhow::Result<()> {
+    match args {
+        [] => print_summary(process),
+        ["status"] => print_status(&process.proc_status()?),
+        ["stat"] => print_stat(&process.proc_stat()?),
+        ["cmdline"] => print_strings("cmdline", &process.cmdline()?),
+        ["environ"] => print_strings("environ", &process.environ()?),
+        ["fd"] => print_fds(&process.open_fds()?),
+        _ => anyhow::bail!("Usage: {}", USAGE),
+    }
+}
+
+fn print_summary(process: &sdb::Process) -> anyhow::Result<()> {
+    let pid = process.get_id();
+    let cmdline = process.cmdline()?;
+    let cwd = std::fs::read_link(format!("/proc/{}/cwd", pid))?;
+    let exe = std::fs::read_link(format!("/proc/{}/exe", pid))?;
+
+    if json::enabled() {
+        json::emit(
+            "proc",
+            [
+                ("pid", Json::number(pid)),
+                (
+                    "cmdline",
+                    Json::Array(cmdline.iter().map(Json::string).collect()),
+                ),
+                ("cwd", Json::string(cwd.display())),
+                ("exe", Json::string(exe.display())),
+            ],
+        );
+        return Ok(());
+    }
+
+    println!("process {}", pid);
+    println!("cmdline = {:?}", cmdline.join(" "));
+    println!("cwd = {:?}", cwd);
+    println!("exe = {:?}", exe);
+    Ok(())
+}
+
+// sizes the kernel didn't report are left out
+fn print_status(status: &sdb::ProcStatus) -> anyhow::Result<()> {
+    if json::enabled() {
+        json::emit(
+            "proc_status",
+            [
+                ("name", Json::string(&status.name)),
+                ("state", Json::string(&status.state)),
+                ("ppid", Json::number(status.ppid)),
+                ("tracer_pid", Json::number(status.tracer_pid)),
+                ("uid", Json::number(status.uid)),
+                ("euid", Json::number(status.euid)),
+                ("gid", Json::number(status.gid)),
+                ("egid", Json::number(status.egid)),
+                ("threads", Json::number(status.threads)),
+                ("vm_peak", Json::optional(status.vm_peak, Json::number)),
+                ("vm_size", Json::optional(status.vm_size, Json::number)),
+                ("vm_hwm", Json::optional(status.vm_hwm, Json::number)),
+                ("vm_rss", Json::optional(status.vm_rss, Json::number)),
+                (
+                    "voluntary_context_switches",
+                    Json::optional(status.voluntary_context_switches, Json::number),
+                ),
+                (
+                    "nonvoluntary_context_switches",
+                    Json::optional(status.nonvoluntary_context_switches, Json::number),
+                ),
+                (
+                    "signals_pending",
+                    Json::string(format!("{:#x}", status.signals_pending)),
+                ),
+                (
+                    "signals_blocked",
+                    Json::string(format!("{:#x}", status.signals_blocked)),
+                ),
+                (
+                    "signals_ignored",
+                    Json::string(format!("{:#x}", status.signals_ignored)),
+                ),
+                (
+                    "signals_caught",
+                    Json::string(format!("{:#x}", status.signals_caught)),
+                ),
+            ],
+        );
+        return Ok(());
+    }
+
+    println!("Name:       {}", status.name);
+    println!("State:      {}", status.state);
+    println!("PPid:       {}", status.ppid);
+    println!("TracerPid:  {}", status.tracer_pid);
+    println!("Uid:        {} (effective {})", status.uid, status.euid);
+    println!("Gid:        {} (effective {})", status.gid, status.egid);
+    println!("Threads:    {}", status.threads);
+    let sizes = [
+        ("VmPeak", status.vm_peak),
+        ("VmSize", status.vm_size),
+        ("VmHWM", status.vm_hwm),
+        ("VmRSS", status.vm_rss),
+    ];
+    for (name, size) in sizes {
+        if let Some(size) = size {
+            println!("{:<11} {} kB", format!("{}:", name), size / 1024);
+        }
+    }
+    if let (Some(voluntary), Some(nonvoluntary)) = (
+        status.voluntary_context_switches,
+        status.nonvoluntary_context_switches,
+    ) {
+        println!(
+            "Switches:   {} voluntary, {} involuntary",
+            voluntary, nonvoluntary
+        );
+    }
+    println!("SigPnd:     {:016x}", status.signals_pending);
+    println!("SigBlk:     {:016x}", status.signals_blocked);
+    println!("SigIgn:     {:016x}", status.signals_ignored);
+    println!("SigCgt:     {:016x}", status.signals_caught);
+    Ok(())
+}
+
+fn print_stat(stat: &sdb::ProcStat) -> anyhow::Result<()> {
+    if json::enabled() {
+        json::emit(
+            "proc_stat",
+            [
+                ("comm", Json::string(&stat.comm)),
+                ("state", Json::string(stat.state)),
+                ("ppid", Json::number(stat.ppid)),
+                ("pgrp", Json::number(stat.pgrp)),
+                ("session", Json::number(stat.session)),
+                ("tty_nr", Json::number(stat.tty_nr)),
+                ("minor_faults", Json::number(stat.minor_faults)),
+                ("major_faults", Json::number(stat.major_faults)),
+                ("user_time", Json::number(stat.user_time.as_secs_f64())),
+                ("system_time", Json::number(stat.system_time.as_secs_f64())),
+                ("priority", Json::number(stat.priority)),
+                ("nice", Json::number(stat.nice)),
+                ("threads", Json::number(stat.threads)),
+                ("start_time", Json::number(stat.start_time.as_secs_f64())),
+                ("vsize", Json::number(stat.vsize)),
+                ("rss", Json::number(stat.rss)),
+                ("processor", Json::optional(stat.processor, Json::number)),
+            ],
+        );
+        return Ok(());
+    }
+
+    println!("comm:          {}", stat.comm);
+    println!("state:         {}", stat.state);
+    println!("ppid:          {}", stat.ppid);
+    println!("pgrp:          {}", stat.pgrp);
+    println!("session:       {}", stat.session);
+    println!("tty_nr:        {}", stat.tty_nr);
+    println!("minor faults:  {}", stat.minor_faults);
+    println!("major faults:  {}", stat.major_faults);
+    println!("user time:     {:?}", stat.user_time);
+    println!("system time:   {:?}", stat.system_time);
+    println!("priority:      {}", stat.priority);
+    println!("nice:          {}", stat.nice);
+    println!("threads:       {}", stat.threads);
+    println!("started:       {:?} after boot", stat.start_time);
+    println!("vsize:         {} bytes", stat.vsize);
+    println!("rss:           {} bytes", stat.rss);
+    if let Some(processor) = stat.processor {
+        println!("processor:     {}", processor);
+    }
+    Ok(())
+}
+
+fn print_strings(kind: &str, strings: &[String]) -> anyhow::Result<()> {
+    if json::enabled() {
+        json::emit(
+            kind,
+            [(
+                "entries",
+                Json::Array(strings.iter().map(Json::string).collect()),
+            )],
+        );
+        return Ok(());
+    }
+
+    for string in strings {
+        println!("{}", string);
+    }
+    Ok(())
+}
+
+fn print_fds(fds: &[sdb::OpenFd]) -> anyhow::Result<()> {
+    if json::enabled() {
+        let fds = fds
+            .iter()
+            .map(|fd| {
+                Json::object([
+                    ("fd", Json::number(fd.fd)),
+                    ("target", Json::string(fd.target.display())),
+                    ("position", Json::optional(fd.position, Json::number)),
+                    (
+                        "flags",
+                        Json::optional(fd.flags, |flags| Json::string(format!("{:#o}", flags))),
+                    ),
+                ])
+            })
+            .collect();
+        json::emit("fds", [("fds", Json::Array(fds))]);
+        return Ok(());
+    }
+
+    for fd in fds {
+        let flags = fd
+            .flags
+            .map(|flags| format!("{:07o}", flags))
+            .unwrap_or_else(|| "?".to_owned());
+        let position = fd
+            .position
+            .map(|position| position.to_string())
+            .unwrap_or_else(|| "?".to_owned());
+        println!(
+            "{:>4}  flags {}  pos {:<8}  {}",
+            fd.fd,
+            flags,
+            position,
+            fd.target.display()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use std::time::{Duration, Instant};
+
+use tracing::info;
+
+use crate::{
+    context::DebuggerContext,
+    interrupt,
+    json::{self, Json},
+    monitor::parse_interval,
+    print_current_stop,
+};
+
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(10);
+
+// how many functions the report lists
+const DEFAULT_TOP: usize = 20;
+
+pub(crate) const USAGE: &str =
+    "profile [--interval 10ms] [--duration <time>] [--top <n>] [--folded <file>]";
+
+fn percent(count: u64, samples: u64) -> String {
+    format!("{:.1}%", count as f64 * 100.0 / samples.max(1) as f64)
+}
+
+fn print_report(profile: &sdb::Profile, top: usize) {
+    let functions = profile.functions();
+
+    if json::enabled() {
+        let functions = functions
+            .into_iter()
+            .take(top)
+            .map(|function| {
+                Json::object([
+                    ("function", Json::string(function.name)),
+                    ("own", Json::number(function.own)),
+                    ("total", Json::number(function.total)),
+                ])
+            })
+            .collect();
+        json::emit(
+            "profile",
+            [
+                ("samples", Json::number(profile.samples())),
+                ("functions", Json::Array(functions)),
+            ],
+        );
+        return;
+    }
+
+    info!("{} samples", profile.samples());
+    if functions.is_empty() {
+        return;
+    }
+    println!("{:>7} {:>7}  function", "own", "total");
+    for function in functions.into_iter().take(top) {
+        println!(
+            "{:>7} {:>7}  {}",
+            percent(function.own, profile.samples()),
+            percent(function.total, profile.samples()),
+            function.name
+        );
+    }
+}
+
+pub(crate) fn handle_profile_command(
+    context: &dyn DebuggerContext,
+    args: &[&str],
+) -> anyhow::Result<()> {
+    let mut options = args;
+    let mut interval = DEFAULT_INTERVAL;
+    let mut duration = None;
+    let mut top = DEFAULT_TOP;
+    let mut folded = None;
+    loop {
+        options = match options {
+            [] => break,
+            ["--interval", value, rest @ ..] => {
+                interval = parse_interval(value)?;
+                rest
+            }
+            ["--duration", value, rest @ ..] => {
+                duration = Some(parse_interval(value)?);
+                rest
+            }
+            ["--top", value, rest @ ..] => {
+                top = value.parse()?;
+                rest
+            }
+            ["--folded", path, rest @ ..] => {
+                folded = Some(*path);
+                rest
+            }
+            _ => anyhow::bail!("Usage: {}", USAGE),
+        };
+    }
+
+    let controller = context.controller()?;
+    match duration {
+        Some(duration) => info!("Profiling every {:?} for {:?} ...", interval, duration),
+        None => info!("Profiling every {:?}, Ctrl-C to stop ...", interval),
+    }
+
+    // anything from before we started is stale
+    interrupt::take();
+    let started = Instant::now();
+    let (profile, _) = controller.execute_target(move |target| {
+        target.profile(interval, |_| {
+            !interrupt::take() && duration.is_none_or(|duration| started.elapsed() < duration)
+        })
+    })??;
+
+    print_report(&profile, top);
+
+    if let Some(path) = folded {
+        std::fs::write(path, profile.folded())?;
+        info!("Saved folded stacks to {}", path);
+    }
+
+    print_current_stop(controller)
+}
@@ -0,0 +1,84 @@
+use std::io::{IsTerminal, Write};
+use std::os::fd::{AsFd, OwnedFd};
+use std::sync::OnceLock;
+
+use nix::{
+    errno::Errno,
+    poll::{self, PollFd, PollFlags, PollTimeout},
+    unistd,
+};
+
+// the master of the terminal the process was given with --pty
+static MASTER: OnceLock<OwnedFd> = OnceLock::new();
+
+#[inline]
+pub fn enabled() -> bool {
+    MASTER.get().is_some()
+}
+
+// returns the slave to launch the process with,
+// what the process writes is copied to our stdout from here on
+pub fn open() -> anyhow::Result<OwnedFd> {
+    let mut pty = sdb::Pty::new()?;
+    // our own terminal already echoes what's typed
+    pty.set_echo(false)?;
+
+    let master = pty.take_master().unwrap();
+    let slave = pty.take_slave().unwrap();
+    let relay = master.try_clone()?;
+    if MASTER.set(master).is_err() {
+        anyhow::bail!("A terminal's already open");
+    }
+
+    std::thread::spawn(move || {
+        let mut buf = [0; 4096];
+        loop {
+            let count = match unistd::read(&relay, &mut buf) {
+                Ok(0) => break,
+                Ok(count) => count,
+                Err(Errno::EINTR) => continue,
+                // EIO once nothing has the slave open
+                Err(_) => break,
+            };
+
+            let mut stdout = std::io::stdout().lock();
+            if stdout.write_all(&buf[..count]).is_err() || stdout.flush().is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(slave)
+}
+
+// sends whatever's been typed since the last call to the process, never blocks
+// the terminal's line editing is ours, so it comes a line at a time
+pub fn forward_input() -> anyhow::Result<()> {
+    let Some(master) = MASTER.get() else {
+        return Ok(());
+    };
+
+    // commands piped in are the debugger's
+    let stdin = std::io::stdin();
+    if !stdin.is_terminal() {
+        return Ok(());
+    }
+
+    let mut fds = [PollFd::new(stdin.as_fd(), PollFlags::POLLIN)];
+    if poll::poll(&mut fds, PollTimeout::ZERO)? == 0 {
+        return Ok(());
+    }
+
+    let mut buf = [0; 4096];
+    let count = unistd::read(&stdin, &mut buf)?;
+    let mut data = &buf[..count];
+    while !data.is_empty() {
+        match unistd::write(master, data) {
+            Ok(written) => data = &data[written..],
+            Err(Errno::EINTR) => continue,
+            Err(errno) => return Err(errno.into()),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,115 @@
+use std::net::{SocketAddr, TcpListener};
+use std::os::unix::net::UnixListener;
+
+use sdb::{DebugTransport, Request, Response};
+use tracing::info;
+
+use crate::{
+    context::DebuggerContext,
+    handle_command,
+    json::{self, Json},
+    parse_number, print_frames,
+};
+
+/// Serves a single session on a TCP address or unix socket path
+pub fn serve(controller: &sdb::ProcessController, address: &str) -> anyhow::Result<()> {
+    info!("Waiting for a connection on {} ...", address);
+
+    if let Ok(address) = address.parse::<SocketAddr>() {
+        let (stream, peer) = TcpListener::bind(address)?.accept()?;
+        info!("Serving {}", peer);
+        sdb::serve_session(controller, stream.try_clone()?, stream)?;
+    } else {
+        let listener = UnixListener::bind(address)?;
+        let accepted = listener.accept();
+        std::fs::remove_file(address)?;
+
+        let (stream, _) = accepted?;
+        info!("Serving {}", address);
+        sdb::serve_session(controller, stream.try_clone()?, stream)?;
+    }
+
+    info!("Session closed");
+    Ok(())
+}
+
+// the commands below stand in for the local ones when the process is somewhere else,
+// with only what the protocol can ask for
+
+pub(crate) fn handle_finish_command(context: &dyn DebuggerContext) -> anyhow::Result<()> {
+    context.request(Request::StepOut)?;
+    context.print_current_stop()
+}
+
+pub(crate) fn handle_backtrace_command(context: &dyn DebuggerContext) -> anyhow::Result<()> {
+    if let Response::Backtrace(frames) = context.request(Request::Backtrace)? {
+        print_frames(&frames);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn handle_break_command(
+    context: &dyn DebuggerContext,
+    args: &[&str],
+) -> anyhow::Result<()> {
+    let request = match args {
+        [location] if location.starts_with("0x") => {
+            Request::BreakAddress(sdb::VirtAddr::new(parse_number(location)?))
+        }
+        [location] => match location.rsplit_once(':') {
+            Some((file, line)) if line.parse::<u64>().is_ok() => Request::BreakLine {
+                file: file.into(),
+                line: line.parse()?,
+            },
+            _ => Request::BreakFunction(location.to_string()),
+        },
+        _ => anyhow::bail!("Usage: break <function|file:line|address> in a remote session"),
+    };
+    if let Response::Breakpoint(id) = context.request(request)? {
+        if json::enabled() {
+            json::emit("breakpoint", [("id", Json::number(id))]);
+        } else {
+            info!("Breakpoint {} set", id);
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn handle_register_command(
+    context: &dyn DebuggerContext,
+    args: &[&str],
+) -> anyhow::Result<()> {
+    match args {
+        ["read", name] => {
+            if let Response::Register(value) =
+                context.request(Request::ReadRegister(name.to_string()))?
+            {
+                if json::enabled() {
+                    let value = Json::string(format!("{:#018x}", value));
+                    json::emit("registers", [("registers", Json::object([(*name, value)]))]);
+                } else {
+                    println!("{}:\t{:#018x}", name, value);
+                }
+            }
+        }
+        ["write", name, value] => {
+            context.request(Request::WriteRegister(
+                name.to_string(),
+                parse_number(value)?,
+            ))?;
+        }
+        _ => {
+            anyhow::bail!("Usage: register <read <name>|write <name> <value>> in a remote session")
+        }
+    }
+
+    Ok(())
+}
+
+/// A session with a debugger somewhere else, through the same commands as a local one
+pub fn run(transport: impl DebugTransport) -> anyhow::Result<()> {
+    DebuggerContext::print_current_stop(&transport)?;
+    crate::run(|line| handle_command(&transport, line))
+}
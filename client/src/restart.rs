@@ -0,0 +1,163 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use nix::unistd::Pid;
+use tracing::{info, warn};
+
+use crate::{COMMANDS, commands, event_log, handle_command, interrupt, print_current_stop};
+
+// how often /proc is scanned for the restarted process
+const SCAN_INTERVAL: Duration = Duration::from_millis(100);
+
+// what makes a process the same daemon, its pid won't be
+#[derive(Debug, PartialEq, Eq)]
+struct Identity {
+    exe: PathBuf,
+    cmdline: Vec<u8>,
+}
+
+impl Identity {
+    fn of(pid: Pid) -> std::io::Result<Self> {
+        Ok(Self {
+            exe: std::fs::read_link(format!("/proc/{}/exe", pid))?,
+            cmdline: std::fs::read(format!("/proc/{}/cmdline", pid))?,
+        })
+    }
+
+    fn name(&self) -> String {
+        let argv0 = self.cmdline.split(|&byte| byte == 0).next().unwrap_or(&[]);
+        String::from_utf8_lossy(argv0).into_owned()
+    }
+}
+
+// re-attaches to a daemon when its supervisor restarts it, with the same breakpoints
+pub struct FollowRestarts {
+    identity: Identity,
+    pid: Pid,
+    restarts: u32,
+    catch_fatal: bool,
+
+    // set once the user stops waiting for a restart
+    given_up: bool,
+
+    // as they were typed, so they resolve again against the new process
+    breakpoints: Vec<String>,
+}
+
+impl FollowRestarts {
+    pub fn new(pid: Pid, catch_fatal: bool) -> anyhow::Result<Self> {
+        Ok(Self {
+            identity: Identity::of(pid)?,
+            pid,
+            restarts: 0,
+            catch_fatal,
+            given_up: false,
+            breakpoints: Vec::new(),
+        })
+    }
+
+    // restarts so far, to annotate the session with
+    #[inline]
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+
+    // breakpoints that were set are set again after a restart
+    pub fn record(&mut self, line: &str, result: &anyhow::Result<()>) {
+        if result.is_err() {
+            return;
+        }
+
+        // the alias may be gone by the time it's replayed
+        let line = commands::expand_alias(line);
+        let Some(word) = line.split_whitespace().next() else {
+            return;
+        };
+        if commands::find(COMMANDS, word).is_ok_and(|command| command.name == "break") {
+            self.breakpoints.push(line);
+        }
+    }
+
+    fn find_restarted(&self) -> Option<Pid> {
+        std::fs::read_dir("/proc")
+            .ok()?
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+            .map(Pid::from_raw)
+            .filter(|&pid| pid != self.pid && pid != nix::unistd::getpid())
+            .find(|&pid| Identity::of(pid).is_ok_and(|identity| identity == self.identity))
+    }
+
+    // None if the wait was given up with Ctrl-C
+    fn wait_for_restart(&self) -> Option<Pid> {
+        interrupt::take();
+        loop {
+            if let Some(pid) = self.find_restarted() {
+                return Some(pid);
+            }
+            if interrupt::take() {
+                return None;
+            }
+            std::thread::sleep(SCAN_INTERVAL);
+        }
+    }
+
+    /// Once the process is gone, waits for it to come back under a new pid
+    ///
+    /// None if it's still there or the wait was given up with Ctrl-C
+    pub fn wait(&mut self, controller: &sdb::ProcessController) -> Option<Pid> {
+        if self.given_up
+            || !matches!(
+                controller.handle().get_state(),
+                sdb::ProcessState::Exited | sdb::ProcessState::Terminated
+            )
+        {
+            return None;
+        }
+
+        info!(
+            "Waiting for {} to restart, Ctrl-C to stop waiting ...",
+            self.identity.name()
+        );
+        let Some(pid) = self.wait_for_restart() else {
+            info!("Stopped following restarts");
+            self.given_up = true;
+            return None;
+        };
+
+        self.pid = pid;
+        self.restarts += 1;
+        Some(pid)
+    }
+
+    /// Attaches to the restarted process and sets the breakpoints again
+    pub fn reattach(&self) -> anyhow::Result<sdb::ProcessController> {
+        // the supervisor may still be setting it up, it's attached to wherever it's got to
+        let controller = sdb::ProcessController::attach(self.pid.as_raw())?;
+        if self.catch_fatal {
+            controller
+                .execute_target(|target| target.catch_fatal(sdb::DEFAULT_FATAL_FUNCTIONS))??;
+        }
+        event_log::record(&controller)?;
+        info!(
+            "Restart {}: attached to process {}",
+            self.restarts, self.pid
+        );
+
+        let mut applied = 0;
+        for line in &self.breakpoints {
+            match handle_command(&controller, line.as_str()) {
+                Ok(()) => applied += 1,
+                Err(err) => warn!("Couldn't re-apply {}: {}", line, err),
+            }
+        }
+        info!(
+            "Re-applied {} of {} breakpoints",
+            applied,
+            self.breakpoints.len()
+        );
+
+        print_current_stop(&controller)?;
+
+        Ok(controller)
+    }
+}
@@ -0,0 +1,106 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use tracing::{error, info};
+
+use crate::emit_result;
+use crate::json;
+
+// read from the home directory unless --no-rc is given
+const RC_FILE: &str = ".sdbrc";
+
+// the rc file and then each -x script, run before the session takes commands
+static STARTUP: OnceLock<Vec<Startup>> = OnceLock::new();
+
+struct Startup {
+    path: PathBuf,
+
+    // only scripts asked for by name have to exist
+    required: bool,
+}
+
+fn rc_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(RC_FILE))
+}
+
+pub fn init(no_rc: bool, scripts: Vec<String>) {
+    let rc = rc_path().filter(|_| !no_rc).map(|path| Startup {
+        path,
+        required: false,
+    });
+
+    let startup = rc
+        .into_iter()
+        .chain(scripts.into_iter().map(|path| Startup {
+            path: path.into(),
+            required: true,
+        }))
+        .collect();
+    let _ = STARTUP.set(startup);
+}
+
+// one command per line, blank lines and lines starting with # are skipped
+// the first command that fails stops the rest of its script
+fn run_script(
+    startup: &Startup,
+    handle_command: &mut impl FnMut(String) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let script = match std::fs::read_to_string(&startup.path) {
+        Ok(script) => script,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound && !startup.required => {
+            return Ok(());
+        }
+        Err(err) => anyhow::bail!("Couldn't read {}: {}", startup.path.display(), err),
+    };
+
+    info!("Running commands from {} ...", startup.path.display());
+    for (idx, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let result = handle_command(line.to_owned());
+        if json::enabled() {
+            let failed = result.is_err();
+            emit_result(line, result);
+            if failed {
+                break;
+            }
+        } else if let Err(err) = result {
+            error!("{}:{}: {}", startup.path.display(), idx + 1, err);
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+// runs the startup scripts through the same commands the session does
+pub fn run_startup(handle_command: &mut impl FnMut(String) -> anyhow::Result<()>) {
+    for startup in STARTUP.get().into_iter().flatten() {
+        if let Err(err) = run_script(startup, handle_command) {
+            error!("{}", err);
+        }
+    }
+}
+
+// adds a command to the end of the rc file so every session runs it
+pub fn save_to_rc(command: &str) -> anyhow::Result<PathBuf> {
+    let path = rc_path().ok_or_else(|| anyhow::anyhow!("HOME isn't set, there's no rc file"))?;
+
+    // saving the same thing twice, say from the rc file itself, doesn't pile up copies
+    if std::fs::read_to_string(&path).is_ok_and(|rc| rc.lines().any(|line| line.trim() == command))
+    {
+        return Ok(path);
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    writeln!(file, "{}", command)?;
+
+    Ok(path)
+}
@@ -0,0 +1,251 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use num_traits::ToPrimitive;
+use rhai::{AST, Blob, Dynamic, Engine, EvalAltResult, FnPtr, Map};
+use sdb::Inferior;
+use tracing::{error, info};
+
+use crate::{
+    context::DebuggerContext,
+    handle_command,
+    json::{self, Json},
+    register_info, set_breakpoint, state_name,
+};
+
+pub const USAGE: &str = "script <file.rhai|--clear>";
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+// a callback a script asked to have called whenever continue stops the process
+#[derive(Clone)]
+struct StopHook {
+    // the script it came from, which it's called against
+    ast: Rc<AST>,
+    callback: FnPtr,
+}
+
+// the process the script functions act on, only set while a script is running
+scoped_tls::scoped_thread_local!(static CONTROLLER: sdb::ProcessController);
+
+thread_local! {
+    static STOP_HOOKS: RefCell<Vec<StopHook>> = const { RefCell::new(Vec::new()) };
+
+    // the script being run, for on_stop to remember
+    static RUNNING: RefCell<Option<Rc<AST>>> = const { RefCell::new(None) };
+}
+
+fn script_error(err: impl std::fmt::Display) -> Box<EvalAltResult> {
+    err.to_string().into()
+}
+
+fn with_controller<T>(
+    f: impl FnOnce(&sdb::ProcessController) -> anyhow::Result<T>,
+) -> ScriptResult<T> {
+    CONTROLLER.with(f).map_err(script_error)
+}
+
+// integers are 64 bit signed, registers and addresses keep their bits
+fn read_register(name: &str) -> ScriptResult<i64> {
+    with_controller(|controller| {
+        let value = controller.read_register_by_id(register_info(name)?.id())?;
+        value
+            .to_u64()
+            .map(|value| value as i64)
+            .ok_or_else(|| anyhow::anyhow!("{} doesn't fit in an integer", name))
+    })
+}
+
+fn write_register(name: &str, value: i64) -> ScriptResult<()> {
+    with_controller(|controller| {
+        let id = register_info(name)?.id();
+        controller.execute(move |process| {
+            process.write_registers_journaled([(id, (value as u64).into())])
+        })??;
+        Ok(())
+    })
+}
+
+fn read_memory(address: i64, amount: i64) -> ScriptResult<Blob> {
+    let address = sdb::VirtAddr::new(address as u64);
+    let amount = usize::try_from(amount).map_err(script_error)?;
+    with_controller(|controller| {
+        Ok(controller
+            .execute_target(move |target| target.read_memory_without_traps(address, amount))??)
+    })
+}
+
+fn read_u64(address: i64) -> ScriptResult<i64> {
+    let address = sdb::VirtAddr::new(address as u64);
+    with_controller(|controller| {
+        Ok(controller.execute_target(move |target| target.read_i64(address))??)
+    })
+}
+
+fn write_memory(address: i64, data: Blob) -> ScriptResult<()> {
+    with_controller(|controller| {
+        let address = sdb::VirtAddr::new(address as u64);
+        controller
+            .execute(move |process| process.write_memory_journaled(address, &data, false))??;
+        Ok(())
+    })
+}
+
+fn break_at(location: &str) -> ScriptResult<i64> {
+    let location = location.to_owned();
+    with_controller(|controller| {
+        let id = controller.execute_target(move |target| set_breakpoint(target, &location))??;
+        Ok(id as i64)
+    })
+}
+
+fn command(line: &str) -> ScriptResult<()> {
+    with_controller(|controller| handle_command(controller, line))
+}
+
+fn on_stop(callback: FnPtr) -> ScriptResult<()> {
+    let ast = RUNNING
+        .with_borrow(Clone::clone)
+        .ok_or_else(|| script_error("on_stop can only be called while a script runs"))?;
+    STOP_HOOKS.with_borrow_mut(|hooks| hooks.push(StopHook { ast, callback }));
+    Ok(())
+}
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .register_fn("read_register", read_register)
+        .register_fn("write_register", write_register)
+        .register_fn("read_memory", read_memory)
+        .register_fn("read_u64", read_u64)
+        .register_fn("write_memory", write_memory)
+        .register_fn("break_at", break_at)
+        .register_fn("command", command)
+        .register_fn("on_stop", on_stop);
+
+    engine.on_print(|text| {
+        if json::enabled() {
+            json::emit("print", [("text", Json::string(text))]);
+        } else {
+            println!("{}", text);
+        }
+    });
+
+    engine
+}
+
+fn stop_map(info: &sdb::StopInfo) -> Map {
+    let optional = |value: Option<Dynamic>| value.unwrap_or(Dynamic::UNIT);
+
+    let mut map = Map::new();
+    map.insert("reason".into(), state_name(info.reason.reason).into());
+    map.insert(
+        "signal".into(),
+        optional(info.signal().map(|signal| signal.as_str().into())),
+    );
+    map.insert(
+        "breakpoint".into(),
+        optional(info.breakpoint_site().map(|id| (id as i64).into())),
+    );
+    map.insert(
+        "pc".into(),
+        optional(info.pc.map(|pc| (pc.addr() as i64).into())),
+    );
+    map.insert(
+        "function".into(),
+        optional(info.function.clone().map(Into::into)),
+    );
+    map.insert(
+        "file".into(),
+        optional(
+            info.location
+                .as_ref()
+                .map(|location| location.file.display().to_string().into()),
+        ),
+    );
+    map.insert(
+        "line".into(),
+        optional(
+            info.location
+                .as_ref()
+                .map(|location| (location.line as i64).into()),
+        ),
+    );
+    map
+}
+
+/// Calls the stop hooks, returning true if they've all let the process go on
+///
+/// A hook returns true to resume, false to stop, anything else leaves it to the others.
+/// Only breakpoint stops can be resumed, so Ctrl-C always gets through
+pub fn run_stop_hooks(controller: &sdb::ProcessController) -> anyhow::Result<bool> {
+    let hooks = STOP_HOOKS.with_borrow(Clone::clone);
+    if hooks.is_empty() {
+        return Ok(false);
+    }
+
+    let info = controller.execute_target(|target| target.stop_info())??;
+    let stop = stop_map(&info);
+    let engine = engine();
+
+    let mut resume = false;
+    let mut stop_anyway = info.breakpoint_site().is_none();
+    CONTROLLER.set(controller, || {
+        for hook in &hooks {
+            match hook
+                .callback
+                .call::<Dynamic>(&engine, &hook.ast, (stop.clone(),))
+            {
+                Ok(result) => match result.as_bool() {
+                    Ok(true) => resume = true,
+                    Ok(false) => stop_anyway = true,
+                    Err(_) => (),
+                },
+                Err(err) => {
+                    error!("Stop hook failed: {}", err);
+                    stop_anyway = true;
+                }
+            }
+        }
+    });
+
+    Ok(resume && !stop_anyway)
+}
+
+pub(crate) fn run_script(controller: &sdb::ProcessController, path: &str) -> anyhow::Result<()> {
+    let engine = engine();
+    let ast = Rc::new(
+        engine
+            .compile_file(path.into())
+            .map_err(|err| anyhow::anyhow!("{}: {}", path, err))?,
+    );
+
+    let hooks = STOP_HOOKS.with_borrow(Vec::len);
+    // scripts can run scripts with command
+    let outer = RUNNING.replace(Some(ast.clone()));
+    let result = CONTROLLER.set(controller, || engine.run_ast(&ast));
+    RUNNING.set(outer);
+    result.map_err(|err| anyhow::anyhow!("{}: {}", path, err))?;
+
+    let added = STOP_HOOKS.with_borrow(Vec::len) - hooks;
+    if added > 0 {
+        info!("{} added {} stop hooks", path, added);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn handle_script_command(
+    context: &dyn DebuggerContext,
+    args: &[&str],
+) -> anyhow::Result<()> {
+    match args {
+        ["--clear"] => {
+            let hooks = STOP_HOOKS.take();
+            info!("Cleared {} stop hooks", hooks.len());
+            Ok(())
+        }
+        [path] => run_script(context.controller()?, path),
+        _ => anyhow::bail!("Usage: {}", USAGE),
+    }
+}
@@ -0,0 +1,146 @@
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use tracing::info;
+
+use crate::json::{self, Json};
+
+pub const LIST_USAGE: &str = "list [function|file:line|line]";
+
+pub const SUBSTITUTE_PATH_USAGE: &str = "set substitute-path [<from> <to>|--delete <from>]";
+
+// how many lines a listing shows
+const LINES: u64 = 10;
+
+// where a bare list carries on from, as long as the process hasn't moved since
+struct Listing {
+    file: PathBuf,
+    next: u64,
+    pc: Option<sdb::VirtAddr>,
+}
+
+static LAST: Mutex<Option<Listing>> = Mutex::new(None);
+
+// where a location from the debug info is on this machine, for showing it
+pub(crate) fn remap(target: &sdb::Target, location: &mut Option<sdb::SourceLocation>) {
+    if let Some(location) = location {
+        location.file = target.source_map().resolve(&location.file);
+    }
+}
+
+fn centered(line: u64) -> u64 {
+    line.saturating_sub(LINES / 2).max(1)
+}
+
+pub(crate) fn handle_list_command(target: &mut sdb::Target, args: &[&str]) -> anyhow::Result<()> {
+    let info = target.stop_info()?;
+    let here = info.location.clone();
+    let not_on_line = || anyhow::anyhow!("Not stopped on a source line, give a file:line");
+
+    let mut last = LAST.lock().unwrap();
+    let (file, first) = match args {
+        [] => match last.take().filter(|listing| listing.pc == info.pc) {
+            Some(listing) => (listing.file, listing.next),
+            None => {
+                let here = here.as_ref().ok_or_else(not_on_line)?;
+                (here.file.clone(), centered(here.line))
+            }
+        },
+        [line] if line.bytes().all(|c| c.is_ascii_digit()) => {
+            let here = here.as_ref().ok_or_else(not_on_line)?;
+            (here.file.clone(), centered(line.parse()?))
+        }
+        [location] => {
+            let location = target.find_source_location(location)?;
+            (location.file, centered(location.line))
+        }
+        _ => anyhow::bail!("Usage: {}", LIST_USAGE),
+    };
+
+    let lines = target.source_lines(&file, first, first + LINES - 1)?;
+    let current = here.filter(|here| here.file == file).map(|here| here.line);
+
+    *last = Some(Listing {
+        file: file.clone(),
+        next: lines.last().map_or(first, |(number, _)| number + 1),
+        pc: info.pc,
+    });
+
+    if json::enabled() {
+        let lines = lines
+            .into_iter()
+            .map(|(number, text)| {
+                Json::object([
+                    ("line", Json::number(number)),
+                    ("text", Json::string(text)),
+                    ("current", Json::Bool(current == Some(number))),
+                ])
+            })
+            .collect();
+        json::emit(
+            "source",
+            [
+                ("file", Json::string(file.display())),
+                ("lines", Json::Array(lines)),
+            ],
+        );
+        return Ok(());
+    }
+
+    for (number, text) in lines {
+        let marker = if current == Some(number) { '>' } else { ' ' };
+        println!("{} {:>4}  {}", marker, number, text);
+    }
+
+    Ok(())
+}
+
+fn print_substitutions(map: &sdb::SourceMap) {
+    if json::enabled() {
+        let substitutions = map
+            .substitutions()
+            .iter()
+            .map(|(from, to)| {
+                Json::object([
+                    ("from", Json::string(from.display())),
+                    ("to", Json::string(to.display())),
+                ])
+            })
+            .collect();
+        json::emit(
+            "substitute_paths",
+            [("substitutions", Json::Array(substitutions))],
+        );
+        return;
+    }
+
+    if map.substitutions().is_empty() {
+        println!("No source path substitutions");
+    }
+    for (from, to) in map.substitutions() {
+        println!("{} -> {}", from.display(), to.display());
+    }
+}
+
+pub(crate) fn handle_substitute_path(
+    target: &mut sdb::Target,
+    args: &[&str],
+) -> anyhow::Result<()> {
+    let map = target.source_map_mut();
+    match args {
+        [] => print_substitutions(map),
+        ["--delete", from] => {
+            if !map.remove(from) {
+                anyhow::bail!("No substitution for {}", from);
+            }
+            info!("Removed substitution for {}", from);
+        }
+        [from, to] if !from.starts_with('-') => {
+            map.substitute(from, to);
+            info!("Source paths under {} are read from {}", from, to);
+        }
+        _ => anyhow::bail!("Usage: {}", SUBSTITUTE_PATH_USAGE),
+    }
+
+    Ok(())
+}
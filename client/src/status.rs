@@ -0,0 +1,114 @@
+use std::io::Write;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+fn describe_status(status: char) -> &'static str {
+    match status {
+        'R' => "running",
+        'S' => "sleeping",
+        'D' => "blocked",
+        'T' | 't' => "stopped",
+        'Z' => "zombie",
+        'X' | 'x' => "dead",
+        _ => "unknown",
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+// periodically samples a running process and renders
+// a status line so the user can tell what it's doing
+pub struct StatusLine {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl StatusLine {
+    pub fn spawn(handle: sdb::ProcessHandle) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let stop = stop.clone();
+            std::thread::spawn(move || Self::sample(handle, stop))
+        };
+
+        Self {
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    fn sample(handle: sdb::ProcessHandle, stop: Arc<AtomicBool>) {
+        let mut last = handle
+            .get_resource_usage()
+            .ok()
+            .map(|usage| (Instant::now(), usage.cpu_time));
+
+        let mut next_sample = Instant::now() + SAMPLE_INTERVAL;
+        while !stop.load(Ordering::Relaxed) {
+            std::thread::sleep(POLL_INTERVAL);
+            if Instant::now() < next_sample {
+                continue;
+            }
+            next_sample = Instant::now() + SAMPLE_INTERVAL;
+
+            // the process may have exited out from under us
+            let Ok(usage) = handle.get_resource_usage() else {
+                continue;
+            };
+
+            let now = Instant::now();
+            let cpu = last
+                .map(|(when, cpu_time)| {
+                    let elapsed = now.duration_since(when).as_secs_f64();
+                    (usage.cpu_time.saturating_sub(cpu_time)).as_secs_f64() / elapsed * 100.0
+                })
+                .unwrap_or_default();
+            last = Some((now, usage.cpu_time));
+
+            // only children it's waited for count, most processes never have any
+            let children = match usage.children_cpu_time {
+                Duration::ZERO => String::new(),
+                time => format!(" children cpu {:.1}s", time.as_secs_f64()),
+            };
+            eprint!(
+                "\r\x1b[K[{}] {} ({}) cpu {:.1}%{} rss {}",
+                handle.get_id(),
+                describe_status(usage.status),
+                usage.status,
+                cpu,
+                children,
+                format_bytes(usage.rss)
+            );
+            let _ = std::io::stderr().flush();
+        }
+
+        // clear the status line
+        eprint!("\r\x1b[K");
+        let _ = std::io::stderr().flush();
+    }
+
+    pub fn stop(mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use std::fs::File;
+use std::io::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tracing::{error, info};
+
+use crate::examine;
+use crate::json::{self, Json};
+
+pub(crate) const USAGE: &str =
+    "trace <syscalls [name]... [--output <file>]|allocations [--quiet]|off>";
+
+// traced syscalls go here rather than the terminal
+static OUTPUT: Mutex<Option<File>> = Mutex::new(None);
+
+// allocations are only kept track of, for info allocations
+static QUIET_ALLOCATIONS: AtomicBool = AtomicBool::new(false);
+
+// each one's printed as it returns, like strace
+pub(crate) fn print_syscall(record: &sdb::SyscallRecord) {
+    if let Some(file) = OUTPUT.lock().unwrap().as_mut() {
+        if let Err(err) = writeln!(file, "[{}] {}", record.tid, record) {
+            error!("Couldn't write a traced syscall: {}", err);
+        }
+        return;
+    }
+
+    if json::enabled() {
+        json::emit(
+            "syscall",
+            [
+                ("tid", Json::number(record.tid)),
+                ("number", Json::number(record.number)),
+                ("name", Json::optional(record.name(), Json::string)),
+                (
+                    "args",
+                    Json::Array(record.args.iter().map(Json::string).collect()),
+                ),
+                (
+                    "result",
+                    Json::optional(record.result.as_ref(), Json::string),
+                ),
+            ],
+        );
+    } else {
+        println!("[{}] {}", record.tid, record);
+    }
+}
+
+pub(crate) fn print_allocation(event: &sdb::AllocationEvent) {
+    if QUIET_ALLOCATIONS.load(Ordering::Relaxed) {
+        return;
+    }
+
+    if json::enabled() {
+        json::emit(
+            "allocation",
+            [
+                ("tid", Json::number(event.tid)),
+                ("function", Json::string(event.function)),
+                (
+                    "args",
+                    Json::Array(event.args.iter().map(Json::number).collect()),
+                ),
+                ("result", Json::optional(event.result, Json::string)),
+                ("size", Json::number(event.size())),
+                (
+                    "callers",
+                    Json::Array(event.callers.iter().map(Json::string).collect()),
+                ),
+            ],
+        );
+    } else {
+        println!("[{}] {}", event.tid, event);
+    }
+}
+
+// what's still allocated, with where it was allocated from
+pub(crate) fn print_allocations(target: &sdb::Target) -> anyhow::Result<()> {
+    if !target.process().is_tracing_allocations() {
+        anyhow::bail!("Allocations aren't being traced, trace allocations starts");
+    }
+
+    let allocations = target.process().outstanding_allocations();
+    if json::enabled() {
+        let allocations = allocations
+            .iter()
+            .map(|allocation| {
+                Json::object([
+                    ("address", Json::string(allocation.address)),
+                    ("size", Json::number(allocation.size)),
+                    ("function", Json::string(allocation.function)),
+                    ("tid", Json::number(allocation.tid)),
+                    (
+                        "callers",
+                        Json::Array(allocation.callers.iter().map(Json::string).collect()),
+                    ),
+                ])
+            })
+            .collect();
+        json::emit("allocations", [("allocations", Json::Array(allocations))]);
+        return Ok(());
+    }
+
+    if allocations.is_empty() {
+        info!("Everything allocated since tracing started has been freed");
+        return Ok(());
+    }
+    for allocation in &allocations {
+        println!(
+            "{} bytes at {} from {} in thread {}",
+            allocation.size, allocation.address, allocation.function, allocation.tid
+        );
+        for caller in &allocation.callers {
+            println!("    {}", examine::describe_address(target, *caller));
+        }
+    }
+    let bytes = allocations
+        .iter()
+        .map(|allocation| allocation.size)
+        .sum::<u64>();
+    info!("{} blocks, {} bytes outstanding", allocations.len(), bytes);
+    Ok(())
+}
+
+pub(crate) fn handle_trace_command(
+    target: &mut sdb::Target,
+    args: &[String],
+) -> anyhow::Result<()> {
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    match args.as_slice() {
+        ["off"] => {
+            if target.process().syscall_trace().is_none()
+                && !target.process().is_tracing_allocations()
+            {
+                anyhow::bail!("Nothing's being traced");
+            }
+            if target.process().syscall_trace().is_some() {
+                target.process_mut().stop_tracing_syscalls();
+                *OUTPUT.lock().unwrap() = None;
+                info!("Stopped tracing syscalls");
+            }
+            if target.process().is_tracing_allocations() {
+                target.process_mut().stop_tracing_allocations()?;
+                info!("Stopped tracing allocations");
+            }
+        }
+        ["allocations", rest @ ..] => {
+            let quiet = match rest {
+                [] => false,
+                ["--quiet"] => true,
+                _ => anyhow::bail!("Usage: {}", USAGE),
+            };
+            QUIET_ALLOCATIONS.store(quiet, Ordering::Relaxed);
+            target.trace_allocations()?;
+            match quiet {
+                true => info!("Tracing allocations, info allocations lists what's outstanding"),
+                false => info!("Tracing allocations as the process runs"),
+            }
+        }
+        ["syscalls", rest @ ..] => {
+            let (names, output) = match rest {
+                [names @ .., "--output", path] => (names, Some(*path)),
+                names => (names, None),
+            };
+            if names.iter().any(|name| name.starts_with('-')) {
+                anyhow::bail!("Usage: {}", USAGE);
+            }
+
+            let filter = match names {
+                [] => sdb::SyscallFilter::all(),
+                names => sdb::SyscallFilter::names(names)?,
+            };
+            *OUTPUT.lock().unwrap() = output.map(File::create).transpose()?;
+            target.process_mut().trace_syscalls(filter);
+
+            let what = match names {
+                [] => "all syscalls".to_owned(),
+                names => names.join(", "),
+            };
+            match output {
+                Some(path) => info!("Tracing {} into {} as the process runs", what, path),
+                None => info!("Tracing {} as the process runs", what),
+            }
+        }
+        _ => anyhow::bail!("Usage: {}", USAGE),
+    }
+
+    Ok(())
+}
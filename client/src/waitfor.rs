@@ -0,0 +1,64 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::Duration;
+
+use nix::unistd::Pid;
+use tracing::info;
+
+use crate::interrupt;
+
+// short so a short-lived process is caught early on, /proc is cheap to list
+const SCAN_INTERVAL: Duration = Duration::from_millis(1);
+
+fn pids() -> anyhow::Result<Vec<Pid>> {
+    Ok(std::fs::read_dir("/proc")?
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .map(Pid::from_raw)
+        .collect())
+}
+
+fn file_name(path: impl AsRef<Path>) -> Option<String> {
+    Some(path.as_ref().file_name()?.to_string_lossy().into_owned())
+}
+
+// the kernel's name for it, its executable's, or what it calls itself in argv[0]
+fn is_named(pid: Pid, name: &str) -> bool {
+    let comm = std::fs::read_to_string(format!("/proc/{}/comm", pid));
+    if comm.is_ok_and(|comm| comm.trim_end() == name) {
+        return true;
+    }
+
+    let exe = std::fs::read_link(format!("/proc/{}/exe", pid));
+    if exe.ok().and_then(file_name).as_deref() == Some(name) {
+        return true;
+    }
+
+    let cmdline = std::fs::read(format!("/proc/{}/cmdline", pid)).unwrap_or_default();
+    let argv0 = cmdline.split(|&byte| byte == 0).next().unwrap_or(&[]);
+    file_name(String::from_utf8_lossy(argv0).as_ref()).as_deref() == Some(name)
+}
+
+/// Waits for a process with the name to start, processes already running don't count
+///
+/// A new process is checked until it matches, since it has its parent's name until it execs
+pub fn wait_for_process(name: &str) -> anyhow::Result<Pid> {
+    let running = pids()?.into_iter().collect::<HashSet<_>>();
+    let me = nix::unistd::getpid();
+
+    info!("Waiting for {} to start, Ctrl-C to stop waiting ...", name);
+    interrupt::take();
+    loop {
+        if let Some(pid) = pids()?
+            .into_iter()
+            .filter(|pid| *pid != me && !running.contains(pid))
+            .find(|&pid| is_named(pid, name))
+        {
+            return Ok(pid);
+        }
+
+        if interrupt::take() {
+            anyhow::bail!("Stopped waiting for {}", name);
+        }
+        std::thread::sleep(SCAN_INTERVAL);
+    }
+}
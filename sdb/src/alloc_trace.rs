@@ -0,0 +1,432 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+
+use nix::unistd::Pid;
+use num_traits::ToPrimitive;
+
+use crate::{
+    DebugEvent, Process, RegisterId, Result, Target,
+    stoppoint_collection::{Stoppoint, StoppointId},
+    types::VirtAddr,
+};
+
+// how far up the stack an allocation's callers are kept
+const MAX_CALLERS: usize = 16;
+
+/// The allocator functions an allocation trace breaks on
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AllocFunction {
+    Malloc,
+    Calloc,
+    Realloc,
+    Free,
+}
+
+impl AllocFunction {
+    const ALL: [Self; 4] = [Self::Malloc, Self::Calloc, Self::Realloc, Self::Free];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Malloc => "malloc",
+            Self::Calloc => "calloc",
+            Self::Realloc => "realloc",
+            Self::Free => "free",
+        }
+    }
+
+    pub(crate) fn by_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|function| function.name() == name)
+    }
+
+    fn arg_count(&self) -> usize {
+        match self {
+            Self::Malloc | Self::Free => 1,
+            Self::Calloc | Self::Realloc => 2,
+        }
+    }
+}
+
+impl std::fmt::Display for AllocFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A call the process made to the allocator while allocations were traced
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllocationEvent {
+    pub tid: Pid,
+    pub function: AllocFunction,
+    pub args: Vec<u64>,
+    /// What it returned, None for free
+    pub result: Option<VirtAddr>,
+    /// Where it was called from and the calls that led there, innermost first
+    pub callers: Vec<VirtAddr>,
+}
+
+impl AllocationEvent {
+    /// How many bytes were asked for
+    pub fn size(&self) -> u64 {
+        match (self.function, self.args.as_slice()) {
+            (AllocFunction::Malloc, [size]) => *size,
+            (AllocFunction::Calloc, [count, size]) => count.saturating_mul(*size),
+            (AllocFunction::Realloc, [_, size]) => *size,
+            _ => 0,
+        }
+    }
+
+    /// The block that was handed back, realloc's only when it's given up
+    pub fn freed(&self) -> Option<VirtAddr> {
+        let pointer = match (self.function, self.args.as_slice()) {
+            (AllocFunction::Free, [pointer]) => *pointer,
+            // a failed realloc leaves the block alone, one to no size frees it
+            (AllocFunction::Realloc, [pointer, size])
+                if self.result.is_some_and(|result| result.addr() != 0) || *size == 0 =>
+            {
+                *pointer
+            }
+            _ => 0,
+        };
+        (pointer != 0).then(|| VirtAddr::new(pointer))
+    }
+
+    /// The block that was handed out, if the call succeeded
+    pub fn allocated(&self) -> Option<VirtAddr> {
+        self.result.filter(|result| result.addr() != 0)
+    }
+}
+
+impl std::fmt::Display for AllocationEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let args = match (self.function, self.args.as_slice()) {
+            (AllocFunction::Realloc, [pointer, size]) => format!("{:#x}, {}", pointer, size),
+            (AllocFunction::Free, [pointer]) => format!("{:#x}", pointer),
+            (_, args) => args
+                .iter()
+                .map(u64::to_string)
+                .collect::<Vec<_>>()
+                .join(", "),
+        };
+        write!(f, "{}({})", self.function, args)?;
+        if let Some(result) = self.result {
+            write!(f, " = {:#x}", result.addr())?;
+        }
+        Ok(())
+    }
+}
+
+/// A block the process allocated while allocations were traced and hasn't freed since
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Allocation {
+    pub address: VirtAddr,
+    pub size: u64,
+    pub function: AllocFunction,
+    pub tid: Pid,
+    /// Counts the traced calls, the oldest allocation has the lowest
+    pub sequence: u64,
+    /// Where it was allocated from and the calls that led there, innermost first
+    pub callers: Vec<VirtAddr>,
+}
+
+// a call waiting on its return breakpoint
+#[derive(Debug)]
+struct PendingCall {
+    tid: Pid,
+    function: AllocFunction,
+    args: Vec<u64>,
+    return_address: VirtAddr,
+    // rsp once it's returned
+    stack_pointer: u64,
+    callers: Vec<VirtAddr>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct AllocationTrace {
+    entries: HashMap<VirtAddr, AllocFunction>,
+    // the entry breakpoints this put there, rather than found there
+    entry_sites: Vec<StoppointId>,
+    // where calls return to, with the site if it's this one's
+    returns: HashMap<VirtAddr, Option<StoppointId>>,
+    pending: Vec<PendingCall>,
+    outstanding: BTreeMap<VirtAddr, Allocation>,
+    sequence: u64,
+    // where the modules are loaded, a frame that returns anywhere else ends the callers
+    code: Vec<Range<VirtAddr>>,
+}
+
+impl Process {
+    /// Removes the trace's breakpoints and forgets what it had outstanding
+    pub fn stop_tracing_allocations(&mut self) -> Result<()> {
+        let Some(trace) = self.allocation_trace.take() else {
+            return Ok(());
+        };
+
+        let sites = trace
+            .entry_sites
+            .into_iter()
+            .chain(trace.returns.into_values().flatten());
+        for id in sites {
+            if self.breakpoint_sites.get_by_id(id).is_ok() {
+                self.remove_breakpoint_site(id)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn is_tracing_allocations(&self) -> bool {
+        self.allocation_trace.is_some()
+    }
+
+    /// The blocks allocated since allocations started being traced that haven't been freed,
+    /// oldest first
+    pub fn outstanding_allocations(&self) -> Vec<&Allocation> {
+        let mut allocations = self
+            .allocation_trace
+            .iter()
+            .flat_map(|trace| trace.outstanding.values())
+            .collect::<Vec<_>>();
+        allocations.sort_by_key(|allocation| allocation.sequence);
+        allocations
+    }
+
+    // breaks on the entries that aren't already traced, code is where the modules are now
+    pub(crate) fn trace_allocation_entries(
+        &mut self,
+        entries: Vec<(VirtAddr, AllocFunction)>,
+        code: Vec<Range<VirtAddr>>,
+    ) -> Result<()> {
+        let Some(trace) = self.allocation_trace.as_mut() else {
+            return Ok(());
+        };
+        trace.code = code;
+
+        for (address, function) in entries {
+            // the symbol table and the dynamic one both have it
+            let trace = self.allocation_trace.as_mut().unwrap();
+            if trace.entries.insert(address, function).is_some() {
+                continue;
+            }
+
+            // a breakpoint of the user's there is shared, it still stops
+            if !self.breakpoint_sites.contains_address(address) {
+                let id = self.create_breakpoint_site(address, true)?;
+                self.enable_breakpoint_site(id)?;
+                self.allocation_trace.as_mut().unwrap().entry_sites.push(id);
+            }
+        }
+
+        Ok(())
+    }
+
+    // returns true if the stop was only the trace's, and the process should be resumed
+    pub(crate) fn handle_allocation_stop(&mut self, id: StoppointId) -> Result<bool> {
+        let Some(trace) = &self.allocation_trace else {
+            return Ok(false);
+        };
+
+        let address = self.breakpoint_sites.get_by_id(id)?.address();
+        let own = trace.entry_sites.contains(&id) || trace.returns.get(&address) == Some(&Some(id));
+        let tid = self.current_thread;
+        if trace.returns.contains_key(&address) {
+            self.allocation_return(tid, address)?;
+        }
+        self.allocation_entry(tid, address)?;
+
+        Ok(own)
+    }
+
+    // run_until leaves a site that's already there alone, so it takes the trace's return site
+    // over rather than have its stop resumed from
+    pub(crate) fn take_allocation_return_site(&mut self, address: VirtAddr) -> Option<StoppointId> {
+        self.allocation_trace
+            .as_mut()?
+            .returns
+            .get_mut(&address)?
+            .take()
+    }
+
+    // a thread at an allocator function's entry, by a breakpoint or a step onto it, and noted
+    // again as it's stepped off of it
+    pub(crate) fn allocation_entry(&mut self, tid: Pid, pc: VirtAddr) -> Result<()> {
+        let Some(&function) = self
+            .allocation_trace
+            .as_ref()
+            .and_then(|trace| trace.entries.get(&pc))
+        else {
+            return Ok(());
+        };
+
+        let stack_pointer = self.thread_register(tid, RegisterId::rsp)?;
+        let trace = self.allocation_trace.as_mut().unwrap();
+        // one the thread's unwound past returned without being seen
+        trace
+            .pending
+            .retain(|call| call.tid != tid || call.stack_pointer - 8 >= stack_pointer);
+        // the allocator calling itself, or this call already noted
+        if trace.pending.iter().any(|call| call.tid == tid) {
+            return Ok(());
+        }
+
+        let args = [RegisterId::rdi, RegisterId::rsi]
+            .into_iter()
+            .take(function.arg_count())
+            .map(|id| self.thread_register(tid, id))
+            .collect::<Result<Vec<_>>>()?;
+        let return_address = VirtAddr::new(self.read_u64(VirtAddr::new(stack_pointer))?);
+        let callers = self.callers(return_address, self.thread_register(tid, RegisterId::rbp)?);
+
+        if !self.breakpoint_sites.contains_address(return_address) {
+            let id = self.create_breakpoint_site(return_address, true)?;
+            self.enable_breakpoint_site(id)?;
+            let trace = self.allocation_trace.as_mut().unwrap();
+            trace.returns.insert(return_address, Some(id));
+        }
+        let trace = self.allocation_trace.as_mut().unwrap();
+        trace.returns.entry(return_address).or_default();
+        trace.pending.push(PendingCall {
+            tid,
+            function,
+            args,
+            return_address,
+            stack_pointer: stack_pointer + 8,
+            callers,
+        });
+
+        Ok(())
+    }
+
+    fn allocation_return(&mut self, tid: Pid, address: VirtAddr) -> Result<()> {
+        let stack_pointer = self.thread_register(tid, RegisterId::rsp)?;
+        let result = self.thread_register(tid, RegisterId::rax)?;
+
+        // a recursive caller's return address is the same, its stack isn't
+        let trace = self.allocation_trace.as_mut().unwrap();
+        let Some(index) = trace.pending.iter().position(|call| {
+            call.tid == tid && call.return_address == address && call.stack_pointer == stack_pointer
+        }) else {
+            return Ok(());
+        };
+        let call = trace.pending.remove(index);
+
+        if !trace
+            .pending
+            .iter()
+            .any(|pending| pending.return_address == address)
+            && let Some(Some(id)) = trace.returns.remove(&address)
+        {
+            self.remove_breakpoint_site(id)?;
+        }
+
+        let result = match call.function {
+            AllocFunction::Free => None,
+            _ => Some(VirtAddr::new(result)),
+        };
+        self.record_allocation(AllocationEvent {
+            tid,
+            function: call.function,
+            args: call.args,
+            result,
+            callers: call.callers,
+        });
+        Ok(())
+    }
+
+    fn record_allocation(&mut self, event: AllocationEvent) {
+        let trace = self.allocation_trace.as_mut().unwrap();
+        trace.sequence += 1;
+
+        if let Some(freed) = event.freed() {
+            trace.outstanding.remove(&freed);
+        }
+        if let Some(address) = event.allocated() {
+            trace.outstanding.insert(
+                address,
+                Allocation {
+                    address,
+                    size: event.size(),
+                    function: event.function,
+                    tid: event.tid,
+                    sequence: trace.sequence,
+                    callers: event.callers.clone(),
+                },
+            );
+        }
+
+        self.subscribers.emit(DebugEvent::Allocation(event));
+    }
+
+    // the return address, then up the frame pointer chain, the function hasn't pushed a frame yet
+    fn callers(&self, return_address: VirtAddr, mut frame_pointer: u64) -> Vec<VirtAddr> {
+        let mut callers = vec![return_address];
+        while frame_pointer != 0 && callers.len() < MAX_CALLERS {
+            let (Ok(next), Ok(address)) = (
+                self.read_u64(VirtAddr::new(frame_pointer)),
+                self.read_u64(VirtAddr::new(frame_pointer + 8)),
+            ) else {
+                break;
+            };
+
+            let address = VirtAddr::new(address);
+            let code = &self.allocation_trace.as_ref().unwrap().code;
+            if next <= frame_pointer || !code.iter().any(|range| range.contains(&address)) {
+                break;
+            }
+            callers.push(address);
+            frame_pointer = next;
+        }
+        callers
+    }
+
+    fn thread_register(&self, tid: Pid, id: RegisterId) -> Result<u64> {
+        let value = self.threads[&tid]
+            .registers
+            .lock()
+            .unwrap()
+            .read_by_id(id)?;
+        Ok(value.to_u64().unwrap())
+    }
+}
+
+impl Target {
+    /// Breaks on malloc, calloc, realloc, and free to keep track of the blocks the process
+    /// allocates and doesn't free, and where they were allocated from
+    ///
+    /// Each call is reported as a [`DebugEvent::Allocation`] once it returns. Calls the allocator
+    /// makes to itself aren't counted, and neither are blocks allocated before the trace started
+    pub fn trace_allocations(&mut self) -> Result<()> {
+        if self.process.allocation_trace.is_some() {
+            return Ok(());
+        }
+
+        self.track_modules()?;
+        self.process.allocation_trace = Some(AllocationTrace::default());
+        self.resolve_allocation_breakpoints()
+    }
+
+    // libc's loaded after the process starts, so this runs again as libraries are loaded
+    pub(crate) fn resolve_allocation_breakpoints(&mut self) -> Result<()> {
+        if !self.process.is_tracing_allocations() {
+            return Ok(());
+        }
+
+        let entries = AllocFunction::ALL
+            .into_iter()
+            .flat_map(|function| {
+                self.modules.iter().flat_map(move |elf| {
+                    elf.symbols_by_name(function.name())
+                        .filter(|symbol| symbol.is_function)
+                        .map(move |symbol| (elf.symbol_address(symbol), function))
+                })
+            })
+            .collect();
+        let code = self
+            .modules
+            .iter()
+            .flat_map(|elf| elf.load_ranges())
+            .collect();
+        self.process.trace_allocation_entries(entries, code)
+    }
+}
@@ -0,0 +1,211 @@
+use nix::libc;
+
+use crate::{Process, Result, SdbError, types::VirtAddr};
+
+/// A key in the auxiliary vector, what the kernel tells a program about itself at startup
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AuxvKey {
+    Phdr,
+    Phent,
+    Phnum,
+    PageSize,
+    Base,
+    Flags,
+    Entry,
+    Uid,
+    Euid,
+    Gid,
+    Egid,
+    Platform,
+    Hwcap,
+    ClockTick,
+    Secure,
+    BasePlatform,
+    Random,
+    Hwcap2,
+    Execfn,
+    SysinfoEhdr,
+    MinSigStackSize,
+    RseqFeatureSize,
+    RseqAlign,
+    Other(u64),
+}
+
+// newer than libc's
+const AT_RSEQ_FEATURE_SIZE: libc::c_ulong = 27;
+const AT_RSEQ_ALIGN: libc::c_ulong = 28;
+
+// the keys sdb knows, with their names in <elf.h>
+const KEYS: &[(AuxvKey, libc::c_ulong, &str)] = &[
+    (AuxvKey::Phdr, libc::AT_PHDR, "AT_PHDR"),
+    (AuxvKey::Phent, libc::AT_PHENT, "AT_PHENT"),
+    (AuxvKey::Phnum, libc::AT_PHNUM, "AT_PHNUM"),
+    (AuxvKey::PageSize, libc::AT_PAGESZ, "AT_PAGESZ"),
+    (AuxvKey::Base, libc::AT_BASE, "AT_BASE"),
+    (AuxvKey::Flags, libc::AT_FLAGS, "AT_FLAGS"),
+    (AuxvKey::Entry, libc::AT_ENTRY, "AT_ENTRY"),
+    (AuxvKey::Uid, libc::AT_UID, "AT_UID"),
+    (AuxvKey::Euid, libc::AT_EUID, "AT_EUID"),
+    (AuxvKey::Gid, libc::AT_GID, "AT_GID"),
+    (AuxvKey::Egid, libc::AT_EGID, "AT_EGID"),
+    (AuxvKey::Platform, libc::AT_PLATFORM, "AT_PLATFORM"),
+    (AuxvKey::Hwcap, libc::AT_HWCAP, "AT_HWCAP"),
+    (AuxvKey::ClockTick, libc::AT_CLKTCK, "AT_CLKTCK"),
+    (AuxvKey::Secure, libc::AT_SECURE, "AT_SECURE"),
+    (
+        AuxvKey::BasePlatform,
+        libc::AT_BASE_PLATFORM,
+        "AT_BASE_PLATFORM",
+    ),
+    (AuxvKey::Random, libc::AT_RANDOM, "AT_RANDOM"),
+    (AuxvKey::Hwcap2, libc::AT_HWCAP2, "AT_HWCAP2"),
+    (AuxvKey::Execfn, libc::AT_EXECFN, "AT_EXECFN"),
+    (
+        AuxvKey::SysinfoEhdr,
+        libc::AT_SYSINFO_EHDR,
+        "AT_SYSINFO_EHDR",
+    ),
+    (
+        AuxvKey::MinSigStackSize,
+        libc::AT_MINSIGSTKSZ,
+        "AT_MINSIGSTKSZ",
+    ),
+    (
+        AuxvKey::RseqFeatureSize,
+        AT_RSEQ_FEATURE_SIZE,
+        "AT_RSEQ_FEATURE_SIZE",
+    ),
+    (AuxvKey::RseqAlign, AT_RSEQ_ALIGN, "AT_RSEQ_ALIGN"),
+];
+
+impl AuxvKey {
+    fn from_raw(raw: u64) -> Self {
+        KEYS.iter()
+            .find(|(_, value, _)| *value == raw)
+            .map_or(AuxvKey::Other(raw), |(key, _, _)| *key)
+    }
+
+    pub fn raw(&self) -> u64 {
+        match self {
+            AuxvKey::Other(raw) => *raw,
+            key => KEYS.iter().find(|(k, _, _)| k == key).unwrap().1,
+        }
+    }
+
+    /// The AT_ name, None for keys sdb doesn't know
+    pub fn name(&self) -> Option<&'static str> {
+        KEYS.iter()
+            .find(|(key, _, _)| key == self)
+            .map(|(_, _, name)| *name)
+    }
+
+    /// Whether the value is an address in the process rather than a number
+    pub fn is_address(&self) -> bool {
+        matches!(
+            self,
+            AuxvKey::Phdr
+                | AuxvKey::Base
+                | AuxvKey::Entry
+                | AuxvKey::Platform
+                | AuxvKey::BasePlatform
+                | AuxvKey::Random
+                | AuxvKey::Execfn
+                | AuxvKey::SysinfoEhdr
+        )
+    }
+
+    /// Whether the value is the address of a NUL terminated string
+    pub fn is_string(&self) -> bool {
+        matches!(
+            self,
+            AuxvKey::Platform | AuxvKey::BasePlatform | AuxvKey::Execfn
+        )
+    }
+}
+
+impl std::fmt::Display for AuxvKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.name() {
+            Some(name) => f.pad(name),
+            None => f.pad(&format!("AT_{}", self.raw())),
+        }
+    }
+}
+
+/// The auxiliary vector, in the order the kernel laid it out
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Auxv {
+    entries: Vec<(AuxvKey, u64)>,
+}
+
+impl Auxv {
+    /// Parses the key and value pairs of /proc/pid/auxv or an NT_AUXV note, up to AT_NULL
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let words = data
+            .chunks_exact(8)
+            .map(|word| u64::from_ne_bytes(word.try_into().unwrap()))
+            .collect::<Vec<_>>();
+
+        let mut entries = Vec::new();
+        for pair in words.chunks(2) {
+            let &[key, value] = pair else {
+                return Err(SdbError::Other("Truncated auxiliary vector".to_owned()));
+            };
+            if key == libc::AT_NULL {
+                break;
+            }
+            entries.push((AuxvKey::from_raw(key), value));
+        }
+
+        Ok(Self { entries })
+    }
+
+    #[inline]
+    pub fn get(&self, key: AuxvKey) -> Option<u64> {
+        self.entries
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| *value)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (AuxvKey, u64)> + '_ {
+        self.entries.iter().copied()
+    }
+
+    /// Where the executable starts running, with its load bias applied
+    #[inline]
+    pub fn entry(&self) -> Option<VirtAddr> {
+        self.get(AuxvKey::Entry).map(VirtAddr::new)
+    }
+
+    /// Where the executable's program headers were loaded
+    #[inline]
+    pub fn program_headers(&self) -> Option<VirtAddr> {
+        self.get(AuxvKey::Phdr).map(VirtAddr::new)
+    }
+
+    /// Where the dynamic loader was loaded, None for a static executable
+    #[inline]
+    pub fn interpreter_base(&self) -> Option<VirtAddr> {
+        self.get(AuxvKey::Base)
+            .filter(|base| *base != 0)
+            .map(VirtAddr::new)
+    }
+
+    /// The 16 random bytes the kernel gave the process, the stack guard comes from them
+    #[inline]
+    pub fn random(&self) -> Option<VirtAddr> {
+        self.get(AuxvKey::Random).map(VirtAddr::new)
+    }
+}
+
+impl Process {
+    /// The auxiliary vector the kernel gave the process when it last exec'd
+    pub fn auxv(&self) -> Result<Auxv> {
+        let data = std::fs::read(format!("/proc/{}/auxv", self.pid)).map_err(|err| {
+            SdbError::Other(format!("Couldn't read the auxiliary vector: {}", err))
+        })?;
+        Auxv::parse(&data)
+    }
+}
@@ -0,0 +1,273 @@
+use std::time::{Duration, Instant};
+
+use nix::{sys::ptrace, unistd::Pid};
+
+use crate::{
+    LogFormat, Result, SdbError,
+    expr::Expr,
+    stoppoint_collection::{Stoppoint, StoppointId, StoppointKind},
+    types::VirtAddr,
+};
+
+const INT3: u8 = 0xcc;
+
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Which hits of a hot breakpoint stop, the rest are stepped over without stopping
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HitLimit {
+    // at most this many stops in any second
+    MaxPerSecond(u32),
+    // the first hit and every nth after it
+    Sample(u64),
+}
+
+impl std::fmt::Display for HitLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MaxPerSecond(count) => write!(f, "at most {} stops per second", count),
+            Self::Sample(every) => write!(f, "1 in {} hits", every),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BreakpointSite {
+    id: StoppointId,
+    address: VirtAddr,
+    is_enabled: bool,
+    saved_data: u8,
+    is_internal: bool,
+
+    // the fatal function this catches, if it's a catchpoint
+    catching: Option<String>,
+
+    // hits where this is 0 aren't counted and don't stop
+    condition: Option<Expr>,
+
+    // hits by other threads are stepped over like the condition didn't hold
+    thread: Option<Pid>,
+
+    // printed on every hit that would have stopped, which then carries on instead
+    log: Option<LogFormat>,
+
+    hit_limit: Option<HitLimit>,
+    hit_count: u64,
+    skipped_count: u64,
+
+    // when the current second started and how many stops it's had
+    window: Option<(Instant, u32)>,
+}
+
+impl Stoppoint for BreakpointSite {
+    #[inline]
+    fn id(&self) -> StoppointId {
+        self.id
+    }
+
+    #[inline]
+    fn address(&self) -> VirtAddr {
+        self.address
+    }
+
+    #[inline]
+    fn is_enabled(&self) -> bool {
+        self.is_enabled
+    }
+
+    fn kind(&self) -> StoppointKind {
+        match (&self.catching, &self.log, self.is_internal) {
+            (Some(_), _, _) => StoppointKind::Catchpoint,
+            (None, _, true) => StoppointKind::Internal,
+            (None, Some(_), false) => StoppointKind::Logpoint,
+            (None, None, false) => StoppointKind::Breakpoint,
+        }
+    }
+
+    /// Stopped or not
+    #[inline]
+    fn hit_count(&self) -> u64 {
+        self.hit_count
+    }
+
+    fn spec(&self) -> String {
+        let spec = match (&self.catching, &self.log) {
+            (Some(function), _) => function.clone(),
+            (None, Some(log)) => log.to_string(),
+            (None, None) if self.condition.is_none() && self.thread.is_none() => {
+                self.address.to_string()
+            }
+            (None, None) => String::new(),
+        };
+        let when = [
+            self.thread.map(|tid| format!("thread {}", tid)),
+            self.condition
+                .as_ref()
+                .map(|condition| format!("if {}", condition)),
+        ];
+        std::iter::once(spec)
+            .chain(when.into_iter().flatten())
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl BreakpointSite {
+    pub(crate) fn new(id: StoppointId, address: VirtAddr, is_internal: bool) -> Self {
+        Self {
+            id,
+            address,
+            is_enabled: false,
+            saved_data: 0,
+            is_internal,
+            catching: None,
+            condition: None,
+            thread: None,
+            log: None,
+            hit_limit: None,
+            hit_count: 0,
+            skipped_count: 0,
+            window: None,
+        }
+    }
+
+    #[inline]
+    pub fn hit_limit(&self) -> Option<HitLimit> {
+        self.hit_limit
+    }
+
+    // starts counting again from the next hit
+    pub(crate) fn set_hit_limit(&mut self, limit: Option<HitLimit>) {
+        self.hit_limit = limit;
+        self.window = None;
+    }
+
+    #[inline]
+    pub fn condition(&self) -> Option<&Expr> {
+        self.condition.as_ref()
+    }
+
+    #[inline]
+    pub(crate) fn set_condition(&mut self, condition: Option<Expr>) {
+        self.condition = condition;
+    }
+
+    /// The only thread the breakpoint stops, None for every thread
+    #[inline]
+    pub fn thread(&self) -> Option<Pid> {
+        self.thread
+    }
+
+    #[inline]
+    pub(crate) fn set_thread(&mut self, tid: Option<Pid>) {
+        self.thread = tid;
+    }
+
+    #[inline]
+    pub fn log(&self) -> Option<&LogFormat> {
+        self.log.as_ref()
+    }
+
+    #[inline]
+    pub(crate) fn set_log(&mut self, log: Option<LogFormat>) {
+        self.log = log;
+    }
+
+    /// Hits the hit limit stepped over without stopping
+    #[inline]
+    pub fn skipped_count(&self) -> u64 {
+        self.skipped_count
+    }
+
+    // counts a hit, returning false if the hit limit skips it
+    pub(crate) fn record_hit(&mut self) -> bool {
+        self.hit_count += 1;
+
+        let stops = match self.hit_limit {
+            None => true,
+            Some(HitLimit::Sample(every)) => (self.hit_count - 1).is_multiple_of(every.max(1)),
+            Some(HitLimit::MaxPerSecond(count)) => {
+                let now = Instant::now();
+                let (started, stops) = match self.window {
+                    Some((started, stops)) if now - started < RATE_WINDOW => (started, stops),
+                    _ => (now, 0),
+                };
+                let stops_now = stops < count;
+                self.window = Some((started, stops + stops_now as u32));
+                stops_now
+            }
+        };
+
+        if !stops {
+            self.skipped_count += 1;
+        }
+        stops
+    }
+
+    #[inline]
+    pub fn is_internal(&self) -> bool {
+        self.is_internal
+    }
+
+    #[inline]
+    pub fn caught_function(&self) -> Option<&str> {
+        self.catching.as_deref()
+    }
+
+    #[inline]
+    pub(crate) fn catch(&mut self, function: String) {
+        self.catching = Some(function);
+    }
+
+    #[inline]
+    pub(crate) fn saved_data(&self) -> u8 {
+        self.saved_data
+    }
+
+    pub(crate) fn enable(&mut self, pid: Pid) -> Result<()> {
+        if self.is_enabled {
+            return Ok(());
+        }
+
+        let data = ptrace::read(pid, self.address.addr() as ptrace::AddressType)
+            .map_err(SdbError::ptrace_at("peekdata", pid, self.address))? as u64;
+        self.saved_data = (data & 0xff) as u8;
+
+        let data_with_int3 = (data & !0xff) | INT3 as u64;
+        ptrace::write(
+            pid,
+            self.address.addr() as ptrace::AddressType,
+            data_with_int3 as i64,
+        )
+        .map_err(SdbError::ptrace_at("pokedata", pid, self.address))?;
+
+        self.is_enabled = true;
+        Ok(())
+    }
+
+    // the int3 went away with the memory it was in
+    #[inline]
+    pub(crate) fn forget(&mut self) {
+        self.is_enabled = false;
+    }
+
+    pub(crate) fn disable(&mut self, pid: Pid) -> Result<()> {
+        if !self.is_enabled {
+            return Ok(());
+        }
+
+        let data = ptrace::read(pid, self.address.addr() as ptrace::AddressType)
+            .map_err(SdbError::ptrace_at("peekdata", pid, self.address))? as u64;
+        let restored_data = (data & !0xff) | self.saved_data as u64;
+        ptrace::write(
+            pid,
+            self.address.addr() as ptrace::AddressType,
+            restored_data as i64,
+        )
+        .map_err(SdbError::ptrace_at("pokedata", pid, self.address))?;
+
+        self.is_enabled = false;
+        Ok(())
+    }
+}
@@ -0,0 +1,202 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::os::unix::fs::FileExt;
+use std::time::{Duration, Instant};
+
+use nix::{errno::Errno, libc};
+use num_traits::ToPrimitive;
+
+use crate::{Process, RegisterId, Result, SdbError, types::VirtAddr};
+
+// how much is read from /proc/pid/mem at a time
+const PROC_MEM_CHUNK_SIZE: usize = 16 << 20;
+
+// how much the memfd holds at a time, it's memory the process is charged for
+const MEMFD_CHUNK_SIZE: usize = 64 << 20;
+
+// under the stack pointer and past the red zone
+const SCRATCH_OFFSET: i64 = 512;
+
+/// How a large region of memory gets out of the process
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum TransferMethod {
+    /// process_vm_readv a page at a time
+    ProcessVmReadv,
+
+    /// Big reads from /proc/pid/mem
+    #[default]
+    ProcMem,
+
+    /// The process writes the region into a memfd that's read from here,
+    /// which needs the process stopped to inject the syscalls
+    Memfd,
+}
+
+impl fmt::Display for TransferMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ProcessVmReadv => write!(f, "process_vm_readv"),
+            Self::ProcMem => write!(f, "/proc/pid/mem"),
+            Self::Memfd => write!(f, "memfd"),
+        }
+    }
+}
+
+/// How much was transferred and how long it took
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct TransferReport {
+    pub method: TransferMethod,
+    pub bytes: usize,
+    pub elapsed: Duration,
+}
+
+impl TransferReport {
+    /// In bytes per second
+    pub fn throughput(&self) -> f64 {
+        match self.elapsed.as_secs_f64() {
+            0.0 => 0.0,
+            elapsed => self.bytes as f64 / elapsed,
+        }
+    }
+}
+
+impl fmt::Display for TransferReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes in {:?} through {} ({:.1} MiB/s)",
+            self.bytes,
+            self.elapsed,
+            self.method,
+            self.throughput() / (1 << 20) as f64
+        )
+    }
+}
+
+fn io_error(err: std::io::Error) -> SdbError {
+    SdbError::Read(Errno::from_raw(err.raw_os_error().unwrap_or(0)))
+}
+
+fn check_syscall(ret: i64) -> Result<i64> {
+    if ret < 0 {
+        return Err(SdbError::Read(Errno::from_raw(-ret as i32)));
+    }
+    Ok(ret)
+}
+
+impl Process {
+    // a short read stops at the first unreadable page like read_memory does
+    pub(crate) fn read_proc_mem(&self, address: VirtAddr, amount: usize) -> Result<Vec<u8>> {
+        let mem = File::open(format!("/proc/{}/mem", self.pid)).map_err(io_error)?;
+
+        let mut memory = vec![0; amount];
+        let mut read = 0;
+        while read < amount {
+            let chunk = (amount - read).min(PROC_MEM_CHUNK_SIZE);
+            match mem.read_at(
+                &mut memory[read..read + chunk],
+                address.addr() + read as u64,
+            ) {
+                Ok(0) => break,
+                Ok(count) => read += count,
+                Err(err) if read == 0 => return Err(io_error(err)),
+                Err(_) => break,
+            }
+        }
+        memory.truncate(read);
+
+        Ok(memory)
+    }
+
+    // puts the memfd's name somewhere the process can see it and creates it
+    fn create_memfd(&mut self) -> Result<i64> {
+        let name = c"sdb-transfer";
+        let rsp = self.read_register_by_id(RegisterId::rsp)?.to_u64().unwrap();
+        let scratch = VirtAddr::new(rsp) - SCRATCH_OFFSET;
+
+        let saved = self.read_memory(scratch, name.count_bytes() + 1)?;
+        self.write_memory(scratch, name.to_bytes_with_nul())?;
+        let fd = self.inject_syscall(
+            libc::SYS_memfd_create,
+            &[scratch.addr(), libc::MFD_CLOEXEC as u64],
+        );
+        self.write_memory(scratch, &saved)?;
+
+        check_syscall(fd?)
+    }
+
+    fn read_memfd(&mut self, address: VirtAddr, amount: usize) -> Result<Vec<u8>> {
+        let fd = self.create_memfd()?;
+
+        let transfer = (|| {
+            let mut file = File::open(format!("/proc/{}/fd/{}", self.pid, fd)).map_err(io_error)?;
+
+            let mut memory = Vec::with_capacity(amount);
+            while memory.len() < amount {
+                let chunk = (amount - memory.len()).min(MEMFD_CHUNK_SIZE);
+                let start = address + memory.len() as i64;
+
+                // the process writes as much as it can read, stopping short at a bad page
+                let written = match self
+                    .inject_syscall(libc::SYS_write, &[fd as u64, start.addr(), chunk as u64])?
+                {
+                    ret if ret < 0 && memory.is_empty() => {
+                        return check_syscall(ret).map(|_| memory);
+                    }
+                    ret if ret <= 0 => break,
+                    written => written as usize,
+                };
+
+                let before = memory.len();
+                file.by_ref()
+                    .take(written as u64)
+                    .read_to_end(&mut memory)
+                    .map_err(io_error)?;
+                if memory.len() - before < written || written < chunk {
+                    break;
+                }
+
+                // hands the pages back before the next chunk
+                check_syscall(self.inject_syscall(libc::SYS_ftruncate, &[fd as u64, 0])?)?;
+                check_syscall(
+                    self.inject_syscall(libc::SYS_lseek, &[fd as u64, 0, libc::SEEK_SET as u64])?,
+                )?;
+                file.rewind().map_err(io_error)?;
+            }
+
+            Ok(memory)
+        })();
+
+        let closed = self.inject_syscall(libc::SYS_close, &[fd as u64]);
+        let memory = transfer?;
+        check_syscall(closed?)?;
+
+        Ok(memory)
+    }
+
+    /// Reads a large region of memory without breakpoint traps and reports how fast it went
+    ///
+    /// Like read_memory, the read stops short at the first page that can't be read
+    pub fn read_memory_bulk(
+        &mut self,
+        address: VirtAddr,
+        amount: usize,
+        method: TransferMethod,
+    ) -> Result<(Vec<u8>, TransferReport)> {
+        let started = Instant::now();
+        let mut memory = match method {
+            TransferMethod::ProcessVmReadv => self.read_memory(address, amount)?,
+            TransferMethod::ProcMem => self.read_proc_mem(address, amount)?,
+            TransferMethod::Memfd => self.read_memfd(address, amount)?,
+        };
+        self.hide_breakpoint_traps(address, &mut memory);
+
+        let report = TransferReport {
+            method,
+            bytes: memory.len(),
+            elapsed: started.elapsed(),
+        };
+        Ok((memory, report))
+    }
+}
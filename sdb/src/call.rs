@@ -0,0 +1,249 @@
+use nix::sys::ptrace;
+use num_traits::ToPrimitive;
+
+use crate::{
+    Process, ProcessState, Radix, RegisterId, Result, SdbError, StopHandler, Stoppoint,
+    StoppointId, Target, VariableValue, types::VirtAddr, variables,
+};
+
+// the function being interrupted may have data below its stack pointer
+const RED_ZONE: u64 = 128;
+
+// integer and pointer arguments, in order
+const ARG_REGISTERS: usize = 6;
+
+impl Process {
+    // where called functions return to, the entry point won't be run again
+    fn call_return_address(&self) -> Result<VirtAddr> {
+        self.auxv()?
+            .entry()
+            .ok_or_else(|| SdbError::Other("The process has no entry point".to_owned()))
+    }
+
+    /// Calls a function in the current thread with up to 6 integer or pointer arguments,
+    /// leaving the thread as it was found, and returns what it left in rax
+    ///
+    /// Only the current thread runs. A breakpoint, signal, or exit before the function
+    /// returns abandons the call
+    pub fn call_function(&mut self, address: VirtAddr, args: &[u64]) -> Result<u64> {
+        Self::call_function_with(self, address, args, 0, |process| {
+            Ok(process
+                .read_register_by_id(RegisterId::rax)?
+                .to_u64()
+                .unwrap())
+        })
+    }
+
+    // reserved is space on the stack for a returned struct, its address is passed first
+    // capture reads the result while the thread's still stopped where the function returned
+    pub(crate) fn call_function_with<H: StopHandler, T>(
+        owner: &mut H,
+        address: VirtAddr,
+        args: &[u64],
+        reserved: u64,
+        capture: impl FnOnce(&H) -> Result<T>,
+    ) -> Result<T> {
+        let process = owner.process_mut();
+        if process.get_state() != ProcessState::Stopped {
+            return Err(SdbError::Other(
+                "The process has to be stopped to call a function".to_owned(),
+            ));
+        }
+        let hidden = (reserved > 0) as usize;
+        if args.len() + hidden > ARG_REGISTERS {
+            return Err(SdbError::Other(format!(
+                "Functions can be called with at most {} arguments",
+                ARG_REGISTERS - hidden
+            )));
+        }
+
+        let tid = process.current_thread;
+        let saved = ptrace::getregs(tid).map_err(SdbError::ptrace("getregs", tid))?;
+        let saved_fprs = ptrace::getregset::<ptrace::regset::NT_PRFPREG>(tid)
+            .map_err(SdbError::ptrace("getfpregs", tid))?;
+        let thread = &process.threads[&tid];
+        let saved_reason = thread.reason().cloned();
+        let saved_rehit = thread.rehit_breakpoint;
+        // the signal the thread stopped with is for its own code, not the call
+        let saved_signal = process.threads.get_mut(&tid).unwrap().pending_signal.take();
+        let saved_position = process.hit_position();
+
+        // a breakpoint already there does the job, one we add or enable is put back after
+        let return_address = process.call_return_address()?;
+        let existing = process
+            .breakpoint_sites
+            .get_by_address(return_address)
+            .ok()
+            .map(|site| (site.id(), site.is_enabled()));
+        let (site, added) = match existing {
+            Some((id, true)) => (id, None),
+            Some((id, false)) => (id, Some(false)),
+            None => (
+                process.create_breakpoint_site(return_address, true)?,
+                Some(true),
+            ),
+        };
+        if added.is_some() {
+            process.enable_breakpoint_site(site)?;
+        }
+
+        let ret = Self::run_call(owner, address, args, reserved, return_address, site, saved)
+            .and_then(|_| capture(owner));
+
+        let process = owner.process_mut();
+
+        match added {
+            Some(true) => process.remove_breakpoint_site(site)?,
+            Some(false) => process.disable_breakpoint_site(site)?,
+            None => (),
+        }
+
+        if process.get_state() == ProcessState::Stopped {
+            ptrace::setregs(tid, saved).map_err(SdbError::ptrace("setregs", tid))?;
+            process.write_fprs(saved_fprs)?;
+            process.read_all_registers(tid)?;
+            if let Some(reason) = &saved_reason {
+                process.record_thread_stop(tid, reason);
+            }
+            let thread = process.threads.get_mut(&tid).unwrap();
+            thread.rehit_breakpoint = saved_rehit;
+            thread.pending_signal = saved_signal;
+            process.set_hit_position(saved_position);
+        }
+
+        ret
+    }
+
+    fn run_call(
+        owner: &mut impl StopHandler,
+        address: VirtAddr,
+        args: &[u64],
+        reserved: u64,
+        return_address: VirtAddr,
+        site: StoppointId,
+        saved: nix::libc::user_regs_struct,
+    ) -> Result<()> {
+        let process = owner.process_mut();
+        let tid = process.current_thread;
+
+        // the struct buffer goes under the red zone, then the return address so the stack's
+        // 16 byte aligned as the function expects once it's been called
+        let buffer = (saved.rsp - RED_ZONE - reserved) & !0xf;
+        let sp = buffer - 8;
+        process.write_memory(VirtAddr::new(sp), &return_address.addr().to_le_bytes())?;
+
+        let mut regs = saved;
+        regs.rip = address.addr();
+        regs.rsp = sp;
+        // no vector registers for a variadic function
+        regs.rax = 0;
+        // stops the kernel restarting whatever syscall the thread was stopped in
+        regs.orig_rax = u64::MAX;
+        let arg_registers = [
+            &mut regs.rdi,
+            &mut regs.rsi,
+            &mut regs.rdx,
+            &mut regs.rcx,
+            &mut regs.r8,
+            &mut regs.r9,
+        ];
+        let hidden = (reserved > 0).then_some(buffer);
+        for (register, arg) in arg_registers.into_iter().zip(hidden.iter().chain(args)) {
+            *register = *arg;
+        }
+        ptrace::setregs(tid, regs).map_err(SdbError::ptrace("setregs", tid))?;
+        process.read_all_registers(tid)?;
+
+        ptrace::cont(tid, None).map_err(SdbError::ptrace("cont", tid))?;
+        process.set_state(ProcessState::Running);
+        let reason = Self::wait_on_signal_with(owner)?;
+
+        let process = owner.process();
+
+        if reason.reason == ProcessState::Stopped
+            && reason.breakpoint_site == Some(site)
+            && process.get_pc()? == return_address
+            && process.read_register_by_id(RegisterId::rsp)?.to_u64() == Some(sp + 8)
+        {
+            return Ok(());
+        }
+
+        let how = match (reason.reason, reason.breakpoint_site) {
+            (ProcessState::Stopped, Some(id)) => format!("it hit breakpoint {}", id),
+            (ProcessState::Stopped, None) => match reason.signal() {
+                Some(signal) => format!("it stopped with {}", signal.as_str()),
+                None => "it stopped".to_owned(),
+            },
+            (ProcessState::Exited, _) => format!("the process exited with {}", reason.info),
+            (ProcessState::Terminated, _) => format!(
+                "the process was killed by {}",
+                reason.signal().map_or("a signal", |signal| signal.as_str())
+            ),
+            (ProcessState::Running, _) => "it's still running".to_owned(),
+        };
+        Err(SdbError::Other(format!(
+            "The call to {} was abandoned, {}",
+            address, how
+        )))
+    }
+}
+
+impl Target {
+    /// Calls a function by name, reading its return value as its debug info says,
+    /// None for void. One without debug info returns a long
+    pub fn call_function_by_name(
+        &mut self,
+        name: &str,
+        args: &[u64],
+        radix: Radix,
+    ) -> Result<Option<VariableValue>> {
+        // the function may be in a library that's been loaded since we last looked
+        if self.modules.find_function(name).is_none() {
+            self.refresh_modules()?;
+        }
+
+        let (address, reserved) = {
+            let (elf, symbol) = self
+                .modules
+                .find_function(name)
+                .ok_or_else(|| SdbError::Other(format!("No function named {}", name)))?;
+            let address = elf.symbol_address(symbol);
+            let file_address = elf.file_address(address).unwrap_or_default();
+            let function = elf
+                .dwarf()
+                .and_then(|dwarf| dwarf.function_containing_address(file_address));
+            let reserved = match function {
+                Some(function) => variables::returned_in_memory(self, elf, function),
+                None => 0,
+            };
+            (address, reserved)
+        };
+
+        Process::call_function_with(self, address, args, reserved, |target| {
+            let elf = target.modules.get_elf_containing_address(address);
+            let function = elf.and_then(|elf| {
+                let file_address = elf.file_address(address)?;
+                Some((elf, elf.dwarf()?.function_containing_address(file_address)?))
+            });
+            match function {
+                Some((elf, function)) => variables::return_value(target, elf, function, radix),
+                None => {
+                    let rax = target
+                        .process
+                        .read_register_by_id(RegisterId::rax)?
+                        .to_u64()
+                        .unwrap();
+                    Ok(Some(VariableValue {
+                        type_name: "long".to_owned(),
+                        address: None,
+                        scalar: Some(rax),
+                        text: match radix {
+                            Radix::Hex => format!("{:#x}", rax),
+                            Radix::Decimal => (rax as i64).to_string(),
+                        },
+                    }))
+                }
+            }
+        })
+    }
+}
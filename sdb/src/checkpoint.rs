@@ -0,0 +1,495 @@
+use nix::{
+    errno::Errno,
+    libc,
+    sys::{ptrace, signal, wait},
+    unistd::Pid,
+};
+use tracing::trace;
+
+use crate::{
+    Auxv, ElfCollection, Inferior, Process, ProcessState, RegisterId, RegisterValue, Result,
+    SdbError, StopReason, Target, TrapType,
+    journal::Journal,
+    read_process_memory,
+    registers::Registers,
+    stoppoint_collection::{Stoppoint, StoppointId, StoppointKind},
+    threads,
+    types::VirtAddr,
+};
+
+/// A copy of the process forked where it was stopped, and left stopped there to go back to
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub id: usize,
+    pub pid: Pid,
+    pub pc: VirtAddr,
+    // where it is in the process's breakpoint hits, and the breakpoints they were counted for
+    position: HitPosition,
+    sites: Vec<(VirtAddr, StoppointId)>,
+}
+
+// how many times the process has stopped at a breakpoint, and whether it's stopped at one now
+#[derive(Debug, Default, Copy, Clone)]
+pub(crate) struct HitPosition {
+    hits: usize,
+    at_hit: bool,
+}
+
+// oldest first, going back to one drops the ones after it so they're all in the process's past
+#[derive(Debug, Default)]
+pub(crate) struct Checkpoints {
+    checkpoints: Vec<Checkpoint>,
+    next_id: usize,
+    position: HitPosition,
+}
+
+// a copy would carry on by itself once the debugger's gone
+impl Drop for Checkpoints {
+    fn drop(&mut self) {
+        for checkpoint in self.checkpoints.drain(..) {
+            kill_copy(checkpoint.pid);
+        }
+    }
+}
+
+fn kill_copy(pid: Pid) {
+    trace!("Killing copy {} ...", pid);
+    let _ = signal::kill(pid, signal::SIGKILL);
+    let _ = wait::waitpid(pid, Some(wait::WaitPidFlag::__WALL));
+}
+
+// a copy run forward to a breakpoint hit
+#[derive(Debug)]
+struct Replay {
+    pid: Pid,
+    last: StoppointId,
+}
+
+// a replay's copy, for evaluating breakpoint conditions in
+struct CopyInferior<'a> {
+    process: &'a Process,
+    modules: &'a ElfCollection,
+    pid: Pid,
+    registers: Registers,
+}
+
+impl<'a> CopyInferior<'a> {
+    fn new(process: &'a Process, modules: &'a ElfCollection, pid: Pid) -> Result<Self> {
+        let mut registers = Registers::new();
+        registers.get_data_mut().regs =
+            ptrace::getregs(pid).map_err(SdbError::ptrace("getregs", pid))?;
+        registers.get_data_mut().i387 = ptrace::getregset::<ptrace::regset::NT_PRFPREG>(pid)
+            .map_err(SdbError::ptrace("getfpregs", pid))?;
+        Ok(Self {
+            process,
+            modules,
+            pid,
+            registers,
+        })
+    }
+}
+
+impl Inferior for CopyInferior<'_> {
+    fn read_register_by_id(&self, id: RegisterId) -> Result<RegisterValue> {
+        self.registers.read_by_id(id)
+    }
+
+    fn read_memory(&self, address: VirtAddr, amount: usize) -> Result<Vec<u8>> {
+        read_process_memory(self.pid, address, amount)
+    }
+
+    fn modules(&self) -> &ElfCollection {
+        self.modules
+    }
+
+    fn auxv(&self) -> Result<Auxv> {
+        self.process.auxv()
+    }
+}
+
+fn write_int3(pid: Pid, address: VirtAddr) -> Result<u8> {
+    let data = ptrace::read(pid, address.addr() as ptrace::AddressType)
+        .map_err(SdbError::ptrace_at("peekdata", pid, address))? as u64;
+    ptrace::write(
+        pid,
+        address.addr() as ptrace::AddressType,
+        ((data & !0xff) | 0xcc) as i64,
+    )
+    .map_err(SdbError::ptrace_at("pokedata", pid, address))?;
+    Ok(data as u8)
+}
+
+fn write_byte(pid: Pid, address: VirtAddr, byte: u8) -> Result<()> {
+    let data = ptrace::read(pid, address.addr() as ptrace::AddressType)
+        .map_err(SdbError::ptrace_at("peekdata", pid, address))? as u64;
+    ptrace::write(
+        pid,
+        address.addr() as ptrace::AddressType,
+        ((data & !0xff) | byte as u64) as i64,
+    )
+    .map_err(SdbError::ptrace_at("pokedata", pid, address))
+}
+
+impl Process {
+    /// The checkpoints that can be gone back to, oldest first
+    #[inline]
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints.checkpoints
+    }
+
+    // every stop counts towards where the process is for reverse_continue
+    pub(crate) fn record_checkpoint_stop(&mut self, reason: &StopReason) {
+        let at_hit = reason.breakpoint_site.is_some_and(|id| {
+            self.breakpoint_sites
+                .get_by_id(id)
+                .is_ok_and(|site| site.kind() == StoppointKind::Breakpoint)
+        });
+        let position = &mut self.checkpoints.position;
+        position.hits += at_hit as usize;
+        position.at_hit = at_hit;
+    }
+
+    // a function called in the process has its stops taken back with everything else
+    #[inline]
+    pub(crate) fn hit_position(&self) -> HitPosition {
+        self.checkpoints.position
+    }
+
+    #[inline]
+    pub(crate) fn set_hit_position(&mut self, position: HitPosition) {
+        self.checkpoints.position = position;
+    }
+
+    fn check_checkpointable(&self) -> Result<()> {
+        if self.get_state() != ProcessState::Stopped {
+            return Err(SdbError::Other(
+                "The process has to be stopped for checkpoints".to_owned(),
+            ));
+        }
+        if self.threads.len() > 1 {
+            return Err(SdbError::Other(
+                "Checkpoints only work for single threaded processes".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn checkpoint_index(&self, id: usize) -> Result<usize> {
+        self.checkpoints
+            .checkpoints
+            .iter()
+            .position(|checkpoint| checkpoint.id == id)
+            .ok_or_else(|| SdbError::Other(format!("No checkpoint {}", id)))
+    }
+
+    // a stopped copy of the thread's process, where it is now
+    fn fork_copy(&mut self, tid: Pid) -> Result<Pid> {
+        match self.inject_syscall_on(tid, libc::SYS_fork, &[])? {
+            (_, Some(child)) => Ok(child),
+            (ret, None) => Err(SdbError::Other(format!(
+                "Couldn't fork a copy: {}",
+                Errno::from_raw(-ret as i32)
+            ))),
+        }
+    }
+
+    /// Forks a copy of the process to go back to, which sits stopped with none of our int3s
+    ///
+    /// Only single threaded processes can be copied, a fork only has the thread that made it
+    pub fn create_checkpoint(&mut self) -> Result<&Checkpoint> {
+        self.check_checkpointable()?;
+
+        let pc = self.get_pc()?;
+        let pid = self.fork_copy(self.current_thread)?;
+        if let Err(err) = self.scrub_sites(pid) {
+            kill_copy(pid);
+            return Err(err);
+        }
+        trace!("Checkpoint copy {} at {}", pid, pc);
+
+        self.checkpoints.next_id += 1;
+        self.checkpoints.checkpoints.push(Checkpoint {
+            id: self.checkpoints.next_id,
+            pid,
+            pc,
+            position: self.checkpoints.position,
+            sites: self.replay_sites(),
+        });
+        Ok(self.checkpoints.checkpoints.last().unwrap())
+    }
+
+    pub fn delete_checkpoint(&mut self, id: usize) -> Result<()> {
+        let idx = self.checkpoint_index(id)?;
+        let checkpoint = self.checkpoints.checkpoints.remove(idx);
+        kill_copy(checkpoint.pid);
+        Ok(())
+    }
+
+    pub(crate) fn restore_checkpoint(&mut self, id: usize) -> Result<StopReason> {
+        let idx = self.checkpoint_index(id)?;
+        let copy = self.fork_copy(self.checkpoints.checkpoints[idx].pid)?;
+        let reason = StopReason::from(wait::WaitStatus::Stopped(copy, signal::SIGTRAP));
+        let position = self.checkpoints.checkpoints[idx].position;
+        self.become_copy(copy, idx, reason, position)
+    }
+
+    // carries on as the copy, which is a replay of the checkpoint at idx
+    fn become_copy(
+        &mut self,
+        copy: Pid,
+        idx: usize,
+        reason: StopReason,
+        position: HitPosition,
+    ) -> Result<StopReason> {
+        // they're of a future that won't happen now
+        for checkpoint in self.checkpoints.checkpoints.drain(idx + 1..) {
+            kill_copy(checkpoint.pid);
+        }
+
+        let sites = self
+            .breakpoint_sites
+            .iter()
+            .filter(|site| site.is_enabled())
+            .map(|site| site.address())
+            .collect::<Vec<_>>();
+        for address in sites {
+            if let Err(err) = write_int3(copy, address) {
+                kill_copy(copy);
+                return Err(err);
+            }
+        }
+
+        if !matches!(
+            self.get_state(),
+            ProcessState::Exited | ProcessState::Terminated
+        ) {
+            kill_copy(self.pid);
+        }
+        self.forget_stashed_statuses();
+        trace!("Carrying on as copy {}", copy);
+
+        self.end_status = None;
+        self.is_attached = true;
+        // the copy's the debugger's own, even if the process it came from was attached to
+        self.terminate_on_drop = true;
+        self.switch_to(copy)?;
+        self.set_state(ProcessState::Stopped);
+        if self.uses_debug_registers() {
+            self.write_debug_registers(copy)?;
+        }
+        self.refresh_watchpoints()?;
+        // the changes were made to a process that's gone
+        self.journal = Journal::default();
+        self.checkpoints.position = position;
+
+        self.record_thread_stop(copy, &reason);
+        Ok(reason)
+    }
+
+    // the breakpoints the process stops at for the user, where they are
+    fn replay_sites(&self) -> Vec<(VirtAddr, StoppointId)> {
+        self.breakpoint_sites
+            .iter()
+            .filter(|site| site.is_enabled() && site.kind() == StoppointKind::Breakpoint)
+            .map(|site| (site.address(), site.id()))
+            .collect()
+    }
+
+    // a condition that can't be evaluated stops, like it would have when the process was there
+    fn replay_condition_holds(
+        &self,
+        modules: &ElfCollection,
+        copy: Pid,
+        id: StoppointId,
+    ) -> Result<bool> {
+        let Some(condition) = self.breakpoint_sites.get_by_id(id)?.condition() else {
+            return Ok(true);
+        };
+        Ok(!matches!(
+            condition.evaluate(&CopyInferior::new(self, modules, copy)?),
+            Ok(0)
+        ))
+    }
+
+    // runs a copy of the checkpoint forward with only the user's breakpoints in it, to the nth
+    // time it would have stopped at one, and hands back the copy stopped there
+    fn replay(&mut self, modules: &ElfCollection, checkpoint: Pid, hits: usize) -> Result<Replay> {
+        let copy = self.fork_copy(checkpoint)?;
+        let replay = self.run_replay(modules, copy, hits);
+        if replay.is_err() {
+            kill_copy(copy);
+        }
+        replay
+    }
+
+    fn run_replay(&mut self, modules: &ElfCollection, copy: Pid, hits: usize) -> Result<Replay> {
+        let sites = self.replay_sites();
+        let mut traps = Vec::new();
+        for (address, _) in &sites {
+            if !traps.iter().any(|(trap, _)| trap == address) {
+                traps.push((*address, write_int3(copy, *address)?));
+            }
+        }
+
+        // a checkpoint taken at a breakpoint has already been stopped there
+        let mut stepping_over = self.get_pc_of(copy)?;
+        let mut pending = None;
+        let mut count = 0;
+        loop {
+            if let Some(&(address, saved)) = traps.iter().find(|(trap, _)| *trap == stepping_over) {
+                write_byte(copy, address, saved)?;
+                ptrace::step(copy, None).map_err(SdbError::ptrace("singlestep", copy))?;
+                let status = threads::wait_for_thread(copy)?;
+                write_int3(copy, address)?;
+                if !matches!(status, wait::WaitStatus::Stopped(_, signal::SIGTRAP)) {
+                    return Err(self.replay_ended(status));
+                }
+            }
+            stepping_over = VirtAddr::default();
+
+            ptrace::cont(copy, pending.take()).map_err(SdbError::ptrace("cont", copy))?;
+            match threads::wait_for_thread(copy)? {
+                wait::WaitStatus::Stopped(_, signal::SIGTRAP) => (),
+                wait::WaitStatus::Stopped(_, signal) => {
+                    pending = Some(signal);
+                    continue;
+                }
+                wait::WaitStatus::PtraceEvent(_, _, libc::PTRACE_EVENT_EXIT) => continue,
+                wait::WaitStatus::PtraceEvent(
+                    _,
+                    _,
+                    libc::PTRACE_EVENT_FORK | libc::PTRACE_EVENT_VFORK | libc::PTRACE_EVENT_CLONE,
+                ) => {
+                    if let Ok(child) = self.new_child(copy) {
+                        kill_copy(child);
+                    }
+                    return Err(SdbError::Other(
+                        "The replay started a thread or a process, only single threaded \
+                         programs can be replayed"
+                            .to_owned(),
+                    ));
+                }
+                wait::WaitStatus::PtraceEvent(..) => continue,
+                status => return Err(self.replay_ended(status)),
+            }
+
+            let mut regs = ptrace::getregs(copy).map_err(SdbError::ptrace("getregs", copy))?;
+            let address = VirtAddr::new(regs.rip - 1);
+            if !traps.iter().any(|(trap, _)| *trap == address) {
+                // the program's own
+                pending = Some(signal::SIGTRAP);
+                continue;
+            }
+            regs.rip = address.addr();
+            ptrace::setregs(copy, regs).map_err(SdbError::ptrace("setregs", copy))?;
+            stepping_over = address;
+
+            let hit = sites
+                .iter()
+                .find(|(site, _)| *site == address)
+                .map(|(_, id)| *id);
+            if let Some(id) = hit
+                && self.replay_condition_holds(modules, copy, id)?
+            {
+                count += 1;
+                if count == hits {
+                    return Ok(Replay {
+                        pid: copy,
+                        last: id,
+                    });
+                }
+            }
+        }
+    }
+
+    fn get_pc_of(&self, pid: Pid) -> Result<VirtAddr> {
+        Ok(VirtAddr::new(
+            ptrace::getregs(pid)
+                .map_err(SdbError::ptrace("getregs", pid))?
+                .rip,
+        ))
+    }
+
+    fn replay_ended(&self, status: wait::WaitStatus) -> SdbError {
+        SdbError::Other(format!(
+            "The replay never got back to the breakpoint hit ({:?}), it has to run the same way \
+             every time",
+            status
+        ))
+    }
+
+    // conditions are evaluated in the replay with the modules of the process it came from
+    pub(crate) fn reverse_continue(&mut self, modules: &ElfCollection) -> Result<StopReason> {
+        self.check_checkpointable()?;
+        if self.checkpoints.checkpoints.is_empty() {
+            return Err(SdbError::Other(
+                "There's no checkpoint to go back from".to_owned(),
+            ));
+        }
+
+        // the hit before the one it's stopped at, or the last one if it's carried on from there
+        let HitPosition { hits, at_hit } = self.checkpoints.position;
+        let target = hits - at_hit as usize;
+        let found = self.checkpoints.checkpoints.iter().rposition(|checkpoint| {
+            let position = checkpoint.position;
+            position.hits < target || (position.hits == target && position.at_hit)
+        });
+        let Some(idx) = found else {
+            let oldest = self.checkpoints.checkpoints[0].id;
+            return self.restore_checkpoint(oldest);
+        };
+
+        let checkpoint = &self.checkpoints.checkpoints[idx];
+        if checkpoint.position.hits == target {
+            let id = checkpoint.id;
+            return self.restore_checkpoint(id);
+        }
+        if checkpoint.sites != self.replay_sites() {
+            return Err(SdbError::Other(format!(
+                "The breakpoints have changed since checkpoint {} was taken, replaying it \
+                 wouldn't count the same hits",
+                checkpoint.id
+            )));
+        }
+
+        let (pid, hits) = (checkpoint.pid, target - checkpoint.position.hits);
+        let replay = self.replay(modules, pid, hits)?;
+        let mut reason = StopReason::from(wait::WaitStatus::Stopped(replay.pid, signal::SIGTRAP));
+        reason.trap_reason = Some(TrapType::SoftwareBreak);
+        reason.breakpoint_site = Some(replay.last);
+        let position = HitPosition {
+            hits: target,
+            at_hit: true,
+        };
+        self.become_copy(replay.pid, idx, reason, position)
+    }
+}
+
+impl Target {
+    /// Goes back to where a checkpoint was taken, in a new copy of it so it can be gone back to
+    /// again. The process is killed, and the checkpoints after this one go with it
+    ///
+    /// Works after the process has ended too
+    pub fn restore_checkpoint(&mut self, id: usize) -> Result<StopReason> {
+        let reason = self.process.restore_checkpoint(id)?;
+        self.refresh_modules()?;
+        Ok(reason)
+    }
+
+    /// Goes back to the last breakpoint hit before where the process is now
+    ///
+    /// The process counts the times it stops at a breakpoint, and a copy of the newest
+    /// checkpoint taken before that hit is run forward with the breakpoints in it to the same
+    /// count. Only hits are counted, not instructions, so a hit is needed to go back to,
+    /// with none since the first checkpoint it's back to that checkpoint instead. The
+    /// breakpoints, and their conditions, have to be the ones the process ran with since the
+    /// checkpoint, and it's an error to replay one taken with others
+    ///
+    /// The program has to run the same way every time, so it's for single threaded programs
+    /// that don't depend on the time, their pid, or input. Output is written again
+    pub fn reverse_continue(&mut self) -> Result<StopReason> {
+        let reason = self.process.reverse_continue(&self.modules)?;
+        self.refresh_modules()?;
+        Ok(reason)
+    }
+}
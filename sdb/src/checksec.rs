@@ -0,0 +1,238 @@
+use std::collections::BTreeSet;
+
+use object::{
+    Object, ObjectSymbol, elf,
+    read::elf::{ElfFile64, FileHeader, ProgramHeader},
+};
+
+use crate::{Elf, Result, SdbError};
+
+// the functions glibc has a `__<name>_chk` version of that _FORTIFY_SOURCE swaps in
+const FORTIFIABLE: &[&str] = &[
+    "asprintf",
+    "confstr",
+    "dprintf",
+    "explicit_bzero",
+    "fgets",
+    "fgets_unlocked",
+    "fprintf",
+    "fread",
+    "fread_unlocked",
+    "fwprintf",
+    "getcwd",
+    "getdomainname",
+    "getgroups",
+    "gethostname",
+    "getlogin_r",
+    "gets",
+    "getwd",
+    "longjmp",
+    "mbsnrtowcs",
+    "mbsrtowcs",
+    "mbstowcs",
+    "memcpy",
+    "memmove",
+    "mempcpy",
+    "memset",
+    "poll",
+    "ppoll",
+    "pread",
+    "pread64",
+    "printf",
+    "read",
+    "readlink",
+    "readlinkat",
+    "realpath",
+    "recv",
+    "recvfrom",
+    "snprintf",
+    "sprintf",
+    "stpcpy",
+    "stpncpy",
+    "strcat",
+    "strcpy",
+    "strncat",
+    "strncpy",
+    "swprintf",
+    "syslog",
+    "ttyname_r",
+    "vasprintf",
+    "vdprintf",
+    "vfprintf",
+    "vfwprintf",
+    "vprintf",
+    "vsnprintf",
+    "vsprintf",
+    "vswprintf",
+    "vsyslog",
+    "vwprintf",
+    "wcpcpy",
+    "wcpncpy",
+    "wcrtomb",
+    "wcscat",
+    "wcscpy",
+    "wcsncat",
+    "wcsncpy",
+    "wcsnrtombs",
+    "wcsrtombs",
+    "wcstombs",
+    "wctomb",
+    "wmemcpy",
+    "wmemmove",
+    "wmempcpy",
+    "wmemset",
+    "wprintf",
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Pie {
+    /// Always loaded at the address it was linked at
+    Disabled,
+    Enabled,
+    /// A library, which is position independent either way
+    SharedObject,
+}
+
+impl std::fmt::Display for Pie {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Disabled => "disabled",
+            Self::Enabled => "enabled",
+            Self::SharedObject => "shared object",
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Relro {
+    None,
+    /// Made read-only after relocation, except the GOT entries that are bound lazily
+    Partial,
+    /// Everything's bound at load, so the whole GOT's read-only
+    Full,
+}
+
+impl std::fmt::Display for Relro {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::None => "none",
+            Self::Partial => "partial",
+            Self::Full => "full",
+        })
+    }
+}
+
+/// The exploit mitigations a module was built with, like checksec reports them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mitigations {
+    pub pie: Pie,
+    pub relro: Relro,
+    /// The stack isn't executable, there's a PT_GNU_STACK without PF_X
+    pub nx: bool,
+    /// It uses `__stack_chk_fail`, so some function checks a stack canary
+    pub canary: bool,
+    /// The `__*_chk` functions it calls, from _FORTIFY_SOURCE
+    pub fortified: Vec<String>,
+    /// The functions it calls that have a `__*_chk` version it doesn't call
+    pub fortifiable: Vec<String>,
+}
+
+fn is_fortified(name: &str) -> bool {
+    name.strip_prefix("__")
+        .and_then(|name| name.strip_suffix("_chk"))
+        .is_some_and(|name| FORTIFIABLE.contains(&name))
+}
+
+impl Elf {
+    /// Reads the mitigations from the file's program headers, dynamic section, and symbols
+    pub fn mitigations(&self) -> Result<Mitigations> {
+        let file = ElfFile64::<object::Endianness>::parse(self.data())
+            .map_err(|err| SdbError::Elf(format!("{}: {}", self.path().display(), err)))?;
+        let endian = file.endian();
+        let headers = file.elf_program_headers();
+        let has_segment = |kind| headers.iter().any(|header| header.p_type(endian) == kind);
+
+        let (mut bind_now, mut executable) = (false, false);
+        if let Ok(dynamic) = file.elf_dynamic_table() {
+            for entry in &dynamic {
+                match entry.tag {
+                    elf::DT_BIND_NOW => bind_now = true,
+                    elf::DT_DEBUG => executable = true,
+                    elf::DT_FLAGS => bind_now |= entry.val & elf::DF_BIND_NOW.0 != 0,
+                    elf::DT_FLAGS_1 => {
+                        bind_now |= entry.val & elf::DF_1_NOW.0 != 0;
+                        executable |= entry.val & elf::DF_1_PIE.0 != 0;
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        // only executables get a DT_DEBUG for the dynamic linker to fill in, libc has an
+        // interpreter too so it can be run
+        let pie = match file.elf_header().e_type(endian) {
+            elf::ET_DYN if executable => Pie::Enabled,
+            elf::ET_DYN => Pie::SharedObject,
+            _ => Pie::Disabled,
+        };
+
+        let relro = match (has_segment(elf::PT_GNU_RELRO), bind_now) {
+            (false, _) => Relro::None,
+            (true, false) => Relro::Partial,
+            (true, true) => Relro::Full,
+        };
+
+        // without the header the kernel makes the stack executable
+        let nx = headers.iter().any(|header| {
+            header.p_type(endian) == elf::PT_GNU_STACK
+                && header.p_flags(endian).0 & elf::PF_X.0 == 0
+        });
+
+        let symbols = file
+            .symbols()
+            .chain(file.dynamic_symbols())
+            .filter_map(|symbol| {
+                let name = symbol.name().ok().filter(|name| !name.is_empty())?;
+                Some((
+                    name.split('@').next().unwrap_or(name),
+                    symbol.is_undefined(),
+                ))
+            })
+            .collect::<Vec<_>>();
+        let names = symbols
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<BTreeSet<_>>();
+        // libc defines every __*_chk, what a module calls is what it imports,
+        // unless it's linked statically and imports nothing
+        let imports = symbols
+            .iter()
+            .filter(|(_, undefined)| *undefined)
+            .map(|(name, _)| *name)
+            .collect::<BTreeSet<_>>();
+        let called = if imports.is_empty() { &names } else { &imports };
+
+        let canary = names.contains("__stack_chk_fail") || names.contains("__stack_chk_guard");
+        let fortified = called
+            .iter()
+            .filter(|name| is_fortified(name))
+            .map(|name| (*name).to_owned())
+            .collect::<Vec<_>>();
+        let fortifiable = called
+            .iter()
+            .filter(|name| {
+                FORTIFIABLE.contains(name) && !called.contains(format!("__{}_chk", name).as_str())
+            })
+            .map(|name| (*name).to_owned())
+            .collect();
+
+        Ok(Mitigations {
+            pie,
+            relro,
+            nx,
+            canary,
+            fortified,
+            fortifiable,
+        })
+    }
+}
@@ -0,0 +1,185 @@
+use std::os::fd::OwnedFd;
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::{
+    LaunchConfig, Process, ProcessHandle, Redirect, RegisterId, RegisterValue, Result, SdbError,
+    StopReason, Target, types::VirtAddr,
+};
+
+type Job = Box<dyn FnOnce(&mut Target) + Send>;
+
+/// Owns a Target and its Process on a dedicated thread
+///
+/// ptrace requests have to come from the thread that attached,
+/// so every operation is sent to the control thread over a channel.
+/// The controller itself can be shared between threads
+#[derive(Debug)]
+pub struct ProcessController {
+    sender: Option<mpsc::Sender<Job>>,
+    handle: ProcessHandle,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for ProcessController {
+    fn drop(&mut self) {
+        // closing the channel stops the control thread
+        // which drops the Process from the thread that owns it
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl ProcessController {
+    pub fn launch(
+        path: impl Into<String>,
+        debug: bool,
+        stdout_replacement: Option<OwnedFd>,
+    ) -> Result<Self> {
+        let mut config = LaunchConfig::new(path).debug(debug);
+        if let Some(stdout_replacement) = stdout_replacement {
+            config = config.stdout(Redirect::Fd(stdout_replacement));
+        }
+        Self::launch_with(config)
+    }
+
+    pub fn launch_with(config: LaunchConfig) -> Result<Self> {
+        Self::spawn(move || Target::launch_with(config))
+    }
+
+    pub fn attach(pid: i32) -> Result<Self> {
+        Self::spawn(move || Target::attach(pid))
+    }
+
+    fn spawn(create: impl FnOnce() -> Result<Target> + Send + 'static) -> Result<Self> {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let (created_sender, created_receiver) = mpsc::sync_channel(1);
+
+        let thread = std::thread::Builder::new()
+            .name("sdb-ptrace".to_owned())
+            .spawn(move || {
+                let mut target = match create() {
+                    Ok(target) => {
+                        let _ = created_sender.send(Ok(target.process().handle()));
+                        target
+                    }
+                    Err(err) => {
+                        let _ = created_sender.send(Err(err));
+                        return;
+                    }
+                };
+
+                while let Ok(job) = receiver.recv() {
+                    job(&mut target);
+                }
+            })
+            .map_err(|err| SdbError::Controller(err.to_string()))?;
+
+        let handle = created_receiver
+            .recv()
+            .map_err(|_| SdbError::Controller("Control thread exited".to_owned()))??;
+
+        Ok(Self {
+            sender: Some(sender),
+            handle,
+            thread: Some(thread),
+        })
+    }
+
+    #[inline]
+    pub fn handle(&self) -> ProcessHandle {
+        self.handle.clone()
+    }
+
+    /// Queues an operation on the control thread without waiting for it
+    pub fn submit_target<F, R>(&self, f: F) -> Result<mpsc::Receiver<R>>
+    where
+        F: FnOnce(&mut Target) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel(1);
+
+        // logging from the control thread belongs to whatever submitted the job
+        let span = tracing::Span::current();
+        self.sender
+            .as_ref()
+            .unwrap()
+            .send(Box::new(move |target| {
+                let _entered = span.enter();
+                let _ = sender.send(f(target));
+            }))
+            .map_err(|_| SdbError::Controller("Control thread exited".to_owned()))?;
+
+        Ok(receiver)
+    }
+
+    /// Runs an operation on the control thread and waits for the result
+    pub fn execute_target<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Target) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.submit_target(f)?
+            .recv()
+            .map_err(|_| SdbError::Controller("Control thread exited".to_owned()))
+    }
+
+    #[inline]
+    pub fn submit<F, R>(&self, f: F) -> Result<mpsc::Receiver<R>>
+    where
+        F: FnOnce(&mut Process) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.submit_target(move |target| f(target.process_mut()))
+    }
+
+    #[inline]
+    pub fn execute<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Process) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        self.execute_target(move |target| f(target.process_mut()))
+    }
+
+    pub fn resume(&self) -> Result<()> {
+        self.execute(|process| process.resume())?
+    }
+
+    pub fn wait_on_signal(&self) -> Result<StopReason> {
+        self.execute_target(|target| target.wait_on_signal())?
+    }
+
+    pub fn wait_on_signal_timeout(&self, timeout: Duration) -> Result<StopReason> {
+        self.execute_target(move |target| target.wait_on_signal_timeout(timeout))?
+    }
+
+    pub fn step_instruction(&self) -> Result<StopReason> {
+        self.execute_target(|target| target.step_instruction())?
+    }
+
+    pub fn get_pc(&self) -> Result<VirtAddr> {
+        self.execute(|process| process.get_pc())?
+    }
+
+    pub fn read_register_by_id(&self, id: RegisterId) -> Result<RegisterValue> {
+        self.execute(move |process| process.read_register_by_id(id))?
+    }
+
+    pub fn write_register_by_id(&self, id: RegisterId, val: RegisterValue) -> Result<()> {
+        self.execute(move |process| process.write_register_by_id(id, val))?
+    }
+
+    /// Breakpoints don't show in what's read
+    pub fn read_memory(&self, address: VirtAddr, amount: usize) -> Result<Vec<u8>> {
+        self.execute_target(move |target| target.read_memory_without_traps(address, amount))?
+    }
+
+    pub fn write_memory(&self, address: VirtAddr, data: impl Into<Vec<u8>>) -> Result<()> {
+        let data = data.into();
+        self.execute(move |process| process.write_memory(address, &data))?
+    }
+}
@@ -0,0 +1,630 @@
+use std::collections::HashMap;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use nix::{libc, sys::signal::Signal, unistd::Pid};
+use object::{
+    LittleEndian, U16, U32, U64, elf,
+    pod::bytes_of,
+    read::elf::{ElfFile64, FileHeader, ProgramHeader},
+};
+use procfs::process::{MMPermissions, MMapPath};
+
+use crate::{
+    Auxv, ElfCollection, Inferior, Process, ProcessState, RegisterId, RegisterValue, Result,
+    SdbError, TransferMethod, TransferReport, bit::as_bytes, registers::Registers, types::VirtAddr,
+};
+
+// offsets into the kernel's struct elf_prstatus on x86-64
+const PRSTATUS_CURSIG_OFFSET: usize = 12;
+const PRSTATUS_PID_OFFSET: usize = 32;
+const PRSTATUS_PPID_OFFSET: usize = 36;
+const PRSTATUS_PGRP_OFFSET: usize = 40;
+const PRSTATUS_SID_OFFSET: usize = 44;
+const PRSTATUS_REGS_OFFSET: usize = 112;
+const PRSTATUS_FPVALID_OFFSET: usize = 328;
+const PRSTATUS_SIZE: usize = 336;
+
+const PAGE_SIZE: u64 = 0x1000;
+
+/// A thread as it was when the core was dumped
+#[derive(Debug)]
+pub struct CoreThread {
+    tid: Pid,
+    signal: Option<Signal>,
+    registers: Registers,
+}
+
+impl CoreThread {
+    #[inline]
+    pub fn tid(&self) -> Pid {
+        self.tid
+    }
+
+    /// The signal the thread was handling when the core was dumped
+    #[inline]
+    pub fn signal(&self) -> Option<Signal> {
+        self.signal
+    }
+}
+
+// a PT_LOAD segment, memory past file_size wasn't dumped
+#[derive(Debug)]
+struct CoreSegment {
+    address: u64,
+    size: u64,
+    offset: u64,
+    file_size: u64,
+}
+
+// an NT_FILE entry, with the offset in bytes rather than pages
+#[derive(Debug)]
+struct FileMapping {
+    start: u64,
+    end: u64,
+    offset: u64,
+    path: PathBuf,
+}
+
+/// A core dump, for looking at a program after it's gone
+///
+/// Memory the kernel didn't dump, like unmodified code,
+/// is read from the mapped files instead
+#[derive(Debug)]
+pub struct CoreTarget {
+    path: PathBuf,
+    data: Vec<u8>,
+    segments: Vec<CoreSegment>,
+    mappings: Vec<FileMapping>,
+
+    // the thread that dumped the core comes first
+    threads: Vec<CoreThread>,
+    current_thread: usize,
+
+    modules: ElfCollection,
+    auxv: Option<Auxv>,
+}
+
+impl CoreTarget {
+    /// Opens a core dumped by the given executable
+    ///
+    /// The executable doesn't have to be where it was when the core was dumped
+    pub fn open(exe: impl AsRef<Path>, core: impl AsRef<Path>) -> Result<Self> {
+        let path = core.as_ref().to_path_buf();
+        let data = std::fs::read(&path)
+            .map_err(|err| SdbError::Core(format!("{}: {}", path.display(), err)))?;
+
+        let (segments, mappings, threads, auxv) = Self::parse(&data)
+            .map_err(|err| SdbError::Core(format!("{}: {}", path.display(), err)))?;
+        if threads.is_empty() {
+            return Err(SdbError::Core(format!(
+                "{}: no threads in the core",
+                path.display()
+            )));
+        }
+
+        let mut mapped: HashMap<PathBuf, u64> = HashMap::new();
+        for mapping in mappings.iter().filter(|mapping| mapping.offset == 0) {
+            let start = mapped.entry(mapping.path.clone()).or_insert(mapping.start);
+            *start = (*start).min(mapping.start);
+        }
+
+        // the executable may have moved since, so match it up by name
+        let exe = exe.as_ref();
+        let exe = exe.canonicalize().unwrap_or_else(|_| exe.to_path_buf());
+        if !mapped.contains_key(&exe)
+            && let Some(original) = mapped
+                .keys()
+                .find(|path| path.file_name() == exe.file_name())
+                .cloned()
+        {
+            let start = mapped.remove(&original).unwrap();
+            mapped.insert(exe, start);
+        }
+
+        let mut modules = ElfCollection::default();
+        modules.sync(mapped);
+
+        Ok(Self {
+            path,
+            data,
+            segments,
+            mappings,
+            threads,
+            current_thread: 0,
+            modules,
+            auxv,
+        })
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn parse(
+        data: &[u8],
+    ) -> std::result::Result<
+        (
+            Vec<CoreSegment>,
+            Vec<FileMapping>,
+            Vec<CoreThread>,
+            Option<Auxv>,
+        ),
+        String,
+    > {
+        let file = ElfFile64::<object::Endianness>::parse(data).map_err(|err| err.to_string())?;
+        let endian = file.endian();
+        if file.elf_header().e_type(endian) != elf::ET_CORE {
+            return Err("not a core file".to_owned());
+        }
+
+        let mut segments = Vec::new();
+        let mut mappings = Vec::new();
+        let mut threads = Vec::new();
+        let mut auxv = None;
+
+        for header in file.elf_program_headers() {
+            match header.p_type(endian) {
+                elf::PT_LOAD => segments.push(CoreSegment {
+                    address: header.p_vaddr(endian),
+                    size: header.p_memsz(endian),
+                    offset: header.p_offset(endian),
+                    file_size: header.p_filesz(endian),
+                }),
+                elf::PT_NOTE => {
+                    let Some(mut notes) =
+                        header.notes(endian, data).map_err(|err| err.to_string())?
+                    else {
+                        continue;
+                    };
+
+                    while let Some(note) = notes.next().map_err(|err| err.to_string())? {
+                        if note.name() != elf::ELF_NOTE_CORE {
+                            continue;
+                        }
+
+                        match note.n_type(endian) {
+                            elf::NT_PRSTATUS => threads.push(parse_prstatus(note.desc())?),
+                            elf::NT_PRFPREG => {
+                                let thread = threads
+                                    .last_mut()
+                                    .ok_or("floating point registers before any thread")?;
+                                parse_fpregset(&mut thread.registers, note.desc())?;
+                            }
+                            elf::NT_FILE => mappings = parse_file_note(note.desc())?,
+                            elf::NT_AUXV => {
+                                auxv =
+                                    Some(Auxv::parse(note.desc()).map_err(|err| err.to_string())?)
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        Ok((segments, mappings, threads, auxv))
+    }
+
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[inline]
+    pub fn threads(&self) -> &[CoreThread] {
+        &self.threads
+    }
+
+    #[inline]
+    pub fn current_thread(&self) -> &CoreThread {
+        &self.threads[self.current_thread]
+    }
+
+    pub fn set_current_thread(&mut self, tid: Pid) -> Result<()> {
+        self.current_thread = self
+            .threads
+            .iter()
+            .position(|thread| thread.tid == tid)
+            .ok_or_else(|| SdbError::Other(format!("No such thread {}", tid)))?;
+        Ok(())
+    }
+
+    /// The process the core was dumped from
+    #[inline]
+    pub fn get_id(&self) -> Pid {
+        self.threads[0].tid
+    }
+
+    #[inline]
+    pub fn signal(&self) -> Option<Signal> {
+        self.threads[0].signal
+    }
+
+    // as much as can be read starting at the address, from either the core or the mapped file
+    fn read_chunk(&self, address: u64, amount: usize) -> Option<&[u8]> {
+        if let Some(segment) = self
+            .segments
+            .iter()
+            .find(|segment| segment.address <= address && address < segment.address + segment.size)
+        {
+            let offset = address - segment.address;
+            if offset < segment.file_size {
+                let start = (segment.offset + offset) as usize;
+                let len = amount.min((segment.file_size - offset) as usize);
+                return self.data.get(start..start + len);
+            }
+        }
+
+        let mapping = self
+            .mappings
+            .iter()
+            .find(|mapping| mapping.start <= address && address < mapping.end)?;
+        let elf = self.modules.get_elf_by_path(&mapping.path)?;
+
+        let start = (mapping.offset + address - mapping.start) as usize;
+        let len = amount.min((mapping.end - address) as usize);
+        let data = elf.data();
+        data.get(start..data.len().min(start + len))
+    }
+}
+
+impl Inferior for CoreTarget {
+    fn read_register_by_id(&self, id: RegisterId) -> Result<RegisterValue> {
+        self.current_thread().registers.read_by_id(id)
+    }
+
+    fn read_memory(&self, address: VirtAddr, amount: usize) -> Result<Vec<u8>> {
+        let mut ret = Vec::with_capacity(amount);
+        while ret.len() < amount {
+            let next = address.addr() + ret.len() as u64;
+            match self.read_chunk(next, amount - ret.len()) {
+                Some(chunk) if !chunk.is_empty() => ret.extend_from_slice(chunk),
+                _ => break,
+            }
+        }
+
+        // a short read is fine, but there has to be something
+        if ret.is_empty() && amount > 0 {
+            return Err(SdbError::Core(format!("{} isn't in the core", address)));
+        }
+
+        Ok(ret)
+    }
+
+    fn modules(&self) -> &ElfCollection {
+        &self.modules
+    }
+
+    fn auxv(&self) -> Result<Auxv> {
+        self.auxv
+            .clone()
+            .ok_or_else(|| SdbError::Core("No auxiliary vector in the core".to_owned()))
+    }
+}
+
+fn read_u64(data: &[u8], offset: usize) -> std::result::Result<u64, String> {
+    data.get(offset..offset + 8)
+        .map(|bytes| u64::from_ne_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| "truncated note".to_owned())
+}
+
+fn parse_prstatus(desc: &[u8]) -> std::result::Result<CoreThread, String> {
+    let regs_size = size_of::<libc::user_regs_struct>();
+    if desc.len() < PRSTATUS_REGS_OFFSET + regs_size {
+        return Err("truncated NT_PRSTATUS".to_owned());
+    }
+
+    let signal = u16::from_ne_bytes(
+        desc[PRSTATUS_CURSIG_OFFSET..PRSTATUS_CURSIG_OFFSET + 2]
+            .try_into()
+            .unwrap(),
+    );
+    let tid = i32::from_ne_bytes(
+        desc[PRSTATUS_PID_OFFSET..PRSTATUS_PID_OFFSET + 4]
+            .try_into()
+            .unwrap(),
+    );
+
+    let mut registers = Registers::new();
+    // pr_reg is laid out exactly like user_regs_struct
+    registers.get_data_mut().regs = unsafe {
+        desc[PRSTATUS_REGS_OFFSET..]
+            .as_ptr()
+            .cast::<libc::user_regs_struct>()
+            .read_unaligned()
+    };
+
+    Ok(CoreThread {
+        tid: Pid::from_raw(tid),
+        signal: Signal::try_from(signal as i32).ok(),
+        registers,
+    })
+}
+
+fn parse_fpregset(registers: &mut Registers, desc: &[u8]) -> std::result::Result<(), String> {
+    if desc.len() < size_of::<libc::user_fpregs_struct>() {
+        return Err("truncated NT_FPREGSET".to_owned());
+    }
+
+    registers.get_data_mut().i387 = unsafe {
+        desc.as_ptr()
+            .cast::<libc::user_fpregs_struct>()
+            .read_unaligned()
+    };
+    Ok(())
+}
+
+// count, page size, count * (start, end, offset in pages), then count file names
+fn parse_file_note(desc: &[u8]) -> std::result::Result<Vec<FileMapping>, String> {
+    let count = read_u64(desc, 0)? as usize;
+    let page_size = read_u64(desc, 8)?;
+
+    let names_offset = 16 + count * 24;
+    let mut names = desc
+        .get(names_offset..)
+        .ok_or("truncated NT_FILE")?
+        .split(|&byte| byte == 0);
+
+    (0..count)
+        .map(|idx| {
+            let entry = 16 + idx * 24;
+            let name = names.next().ok_or("truncated NT_FILE")?;
+            Ok(FileMapping {
+                start: read_u64(desc, entry)?,
+                end: read_u64(desc, entry + 8)?,
+                offset: read_u64(desc, entry + 16)? * page_size,
+                path: PathBuf::from(String::from_utf8_lossy(name).into_owned()),
+            })
+        })
+        .collect()
+}
+
+// a mapping to dump, with whatever of it could be read
+struct DumpedMapping {
+    address: u64,
+    size: u64,
+    flags: elf::ProgramFlags,
+    data: Vec<u8>,
+}
+
+fn write_note(notes: &mut Vec<u8>, note_type: elf::NoteType, desc: &[u8]) {
+    let name = b"CORE\0";
+    let header = elf::NoteHeader64 {
+        n_namesz: U32::new(LittleEndian, name.len() as u32),
+        n_descsz: U32::new(LittleEndian, desc.len() as u32),
+        n_type: U32::new(LittleEndian, note_type),
+    };
+
+    // the name and desc are each padded out to 4 bytes
+    notes.extend_from_slice(bytes_of(&header));
+    notes.extend_from_slice(name);
+    notes.resize(notes.len().next_multiple_of(4), 0);
+    notes.extend_from_slice(desc);
+    notes.resize(notes.len().next_multiple_of(4), 0);
+}
+
+fn prstatus(
+    tid: Pid,
+    signal: Option<Signal>,
+    stat: &procfs::process::Stat,
+    regs: &[u8],
+) -> Vec<u8> {
+    let mut desc = vec![0; PRSTATUS_SIZE];
+    let mut put = |offset: usize, bytes: &[u8]| {
+        desc[offset..offset + bytes.len()].copy_from_slice(bytes);
+    };
+
+    put(
+        PRSTATUS_CURSIG_OFFSET,
+        &(signal.map_or(0, |signal| signal as u16)).to_ne_bytes(),
+    );
+    put(PRSTATUS_PID_OFFSET, &tid.as_raw().to_ne_bytes());
+    put(PRSTATUS_PPID_OFFSET, &stat.ppid.to_ne_bytes());
+    put(PRSTATUS_PGRP_OFFSET, &stat.pgrp.to_ne_bytes());
+    put(PRSTATUS_SID_OFFSET, &stat.session.to_ne_bytes());
+    put(PRSTATUS_REGS_OFFSET, regs);
+    put(PRSTATUS_FPVALID_OFFSET, &1_u32.to_ne_bytes());
+
+    desc
+}
+
+impl Process {
+    /// Writes an ELF core file of the process as it is now
+    ///
+    /// Every readable mapping is dumped, including code,
+    /// so the core can be opened without the original files.
+    /// Returns how long reading the mappings out of the process took
+    pub fn write_core(&self, path: impl AsRef<Path>) -> Result<TransferReport> {
+        if self.get_state() != ProcessState::Stopped {
+            return Err(SdbError::Core(
+                "The process has to be stopped to dump a core".to_owned(),
+            ));
+        }
+
+        let process = procfs::process::Process::new(self.pid.as_raw())?;
+        let stat = process.stat()?;
+        let maps = process.maps()?;
+
+        let mut notes = Vec::new();
+
+        // the current thread goes first, that's the one readers look at
+        let mut threads = self.threads.values().collect::<Vec<_>>();
+        threads.sort_by_key(|thread| thread.tid() != self.current_thread);
+        for thread in threads {
+            let registers = thread.registers.lock().unwrap();
+            let data = registers.get_data();
+            let signal = thread.reason().and_then(|reason| reason.signal());
+
+            write_note(
+                &mut notes,
+                elf::NT_PRSTATUS,
+                &prstatus(thread.tid(), signal, &stat, as_bytes(&data.regs)),
+            );
+            write_note(&mut notes, elf::NT_PRFPREG, as_bytes(&data.i387));
+        }
+
+        // nothing reads the aux vector yet, but other debuggers do
+        if let Ok(auxv) = std::fs::read(format!("/proc/{}/auxv", self.pid)) {
+            write_note(&mut notes, elf::NT_AUXV, &auxv);
+        }
+
+        let mut files = Vec::new();
+        let mut names = Vec::new();
+        let mut mappings = Vec::new();
+        let mut transfer = TransferReport {
+            method: TransferMethod::ProcMem,
+            ..Default::default()
+        };
+        for map in maps {
+            if let MMapPath::Path(path) = &map.pathname {
+                files.extend_from_slice(&map.address.0.to_ne_bytes());
+                files.extend_from_slice(&map.address.1.to_ne_bytes());
+                files.extend_from_slice(&(map.offset / PAGE_SIZE).to_ne_bytes());
+                names.extend_from_slice(path.as_os_str().as_encoded_bytes());
+                names.push(0);
+            }
+
+            // vsyscall can't be read, and isn't in the process anyway
+            if !map.perms.contains(MMPermissions::READ) || map.pathname == MMapPath::Vsyscall {
+                continue;
+            }
+
+            let mut flags = elf::PF_R;
+            if map.perms.contains(MMPermissions::WRITE) {
+                flags |= elf::PF_W;
+            }
+            if map.perms.contains(MMPermissions::EXECUTE) {
+                flags |= elf::PF_X;
+            }
+
+            let size = map.address.1 - map.address.0;
+            mappings.push(DumpedMapping {
+                address: map.address.0,
+                size,
+                flags,
+                // a short read leaves the rest of the mapping out of the file
+                data: {
+                    let address = VirtAddr::new(map.address.0);
+                    let started = Instant::now();
+                    let mut data = self
+                        .read_proc_mem(address, size as usize)
+                        .unwrap_or_default();
+                    transfer.bytes += data.len();
+                    transfer.elapsed += started.elapsed();
+                    self.hide_breakpoint_traps(address, &mut data);
+                    data
+                },
+            });
+        }
+
+        let count = (files.len() / 24) as u64;
+        let mut desc = Vec::new();
+        desc.extend_from_slice(&count.to_ne_bytes());
+        desc.extend_from_slice(&PAGE_SIZE.to_ne_bytes());
+        desc.extend_from_slice(&files);
+        desc.extend_from_slice(&names);
+        write_note(&mut notes, elf::NT_FILE, &desc);
+
+        self.write_core_file(path.as_ref(), &notes, &mappings)?;
+
+        Ok(transfer)
+    }
+
+    fn write_core_file(&self, path: &Path, notes: &[u8], mappings: &[DumpedMapping]) -> Result<()> {
+        let e = LittleEndian;
+        let header_size = size_of::<elf::FileHeader64<LittleEndian>>() as u64;
+        let program_header_size = size_of::<elf::ProgramHeader64<LittleEndian>>() as u64;
+        let program_header_count = mappings.len() as u64 + 1;
+
+        let notes_offset = header_size + program_header_size * program_header_count;
+
+        let header = elf::FileHeader64 {
+            e_ident: elf::Ident {
+                magic: elf::ELFMAG,
+                class: elf::ELFCLASS64,
+                data: elf::ELFDATA2LSB,
+                version: elf::EV_CURRENT,
+                os_abi: elf::ELFOSABI_NONE,
+                abi_version: 0,
+                padding: [0; 7],
+            },
+            e_type: U16::new(e, elf::ET_CORE),
+            e_machine: U16::new(e, elf::EM_X86_64),
+            e_version: U32::new(e, elf::EV_CURRENT.0.into()),
+            e_entry: U64::new(e, 0),
+            e_phoff: U64::new(e, header_size),
+            e_shoff: U64::new(e, 0),
+            e_flags: U32::new(e, Default::default()),
+            e_ehsize: U16::new(e, header_size as u16),
+            e_phentsize: U16::new(e, program_header_size as u16),
+            e_phnum: U16::new(e, program_header_count as u16),
+            e_shentsize: U16::new(e, 0),
+            e_shnum: U16::new(e, 0),
+            e_shstrndx: U16::new(e, Default::default()),
+        };
+
+        let program_header =
+            |p_type, p_flags, offset: u64, address: u64, file_size, size, align| {
+                elf::ProgramHeader64 {
+                    p_type: U32::new(e, p_type),
+                    p_flags: U32::new(e, p_flags),
+                    p_offset: U64::new(e, offset),
+                    p_vaddr: U64::new(e, address),
+                    p_paddr: U64::new(e, 0),
+                    p_filesz: U64::new(e, file_size),
+                    p_memsz: U64::new(e, size),
+                    p_align: U64::new(e, align),
+                }
+            };
+
+        let mut program_headers = vec![program_header(
+            elf::PT_NOTE,
+            Default::default(),
+            notes_offset,
+            0,
+            notes.len() as u64,
+            0,
+            0,
+        )];
+
+        // mapped memory starts on the next page after the notes
+        let mut offset = notes_offset + notes.len() as u64;
+        let mut offsets = Vec::new();
+        for mapping in mappings {
+            offset = offset.next_multiple_of(PAGE_SIZE);
+            offsets.push(offset);
+            program_headers.push(program_header(
+                elf::PT_LOAD,
+                mapping.flags,
+                offset,
+                mapping.address,
+                mapping.data.len() as u64,
+                mapping.size,
+                PAGE_SIZE,
+            ));
+            offset += mapping.data.len() as u64;
+        }
+
+        let write = || -> std::io::Result<()> {
+            let mut file = BufWriter::new(std::fs::File::create(path)?);
+            let mut written = 0;
+
+            file.write_all(bytes_of(&header))?;
+            for program_header in &program_headers {
+                file.write_all(bytes_of(program_header))?;
+            }
+            file.write_all(notes)?;
+            written += notes_offset + notes.len() as u64;
+
+            for (mapping, offset) in mappings.iter().zip(offsets) {
+                file.write_all(&vec![0; (offset - written) as usize])?;
+                file.write_all(&mapping.data)?;
+                written = offset + mapping.data.len() as u64;
+            }
+
+            file.flush()
+        };
+
+        write().map_err(|err| SdbError::Core(format!("{}: {}", path.display(), err)))
+    }
+}
@@ -0,0 +1,217 @@
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use object::Object;
+use tracing::{debug, warn};
+
+use crate::{Result, SdbError};
+
+// where distros install the debug info they strip out of their packages
+const DEBUG_DIRECTORY: &str = "/usr/lib/debug";
+
+// a server that doesn't answer shouldn't hold up loading a library for long
+const DEBUGINFOD_TIMEOUT: Duration = Duration::from_secs(10);
+
+static DEBUGINFOD: AtomicBool = AtomicBool::new(false);
+
+/// Lets debug info nothing local has be downloaded from the servers in DEBUGINFOD_URLS
+pub fn set_debuginfod_enabled(enabled: bool) {
+    DEBUGINFOD.store(enabled, Ordering::Relaxed);
+}
+
+#[inline]
+pub fn debuginfod_enabled() -> bool {
+    DEBUGINFOD.load(Ordering::Relaxed)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// the CRC .gnu_debuglink uses, the same one as zlib's
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let table: [u32; 256] = std::array::from_fn(|idx| {
+        (0..8).fold(idx as u32, |crc, _| match crc & 1 {
+            1 => 0xedb88320 ^ (crc >> 1),
+            _ => crc >> 1,
+        })
+    });
+
+    !data.iter().fold(!0, |crc, &byte| {
+        table[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// A separate file with the debug info stripped out of this one, by build id or .gnu_debuglink
+pub(crate) fn find_local(path: &Path, file: &object::File) -> Option<PathBuf> {
+    if let Ok(Some(build_id)) = file.build_id()
+        && build_id.len() > 1
+    {
+        let id = hex(build_id);
+        let candidate = Path::new(DEBUG_DIRECTORY)
+            .join(".build-id")
+            .join(&id[..2])
+            .join(format!("{}.debug", &id[2..]));
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+    }
+
+    let (name, crc) = file.gnu_debuglink().ok()??;
+    let name = Path::new(std::str::from_utf8(name).ok()?);
+    let dir = path.parent()?;
+    [
+        dir.join(name),
+        dir.join(".debug").join(name),
+        Path::new(DEBUG_DIRECTORY)
+            .join(dir.strip_prefix("/").unwrap_or(dir))
+            .join(name),
+    ]
+    .into_iter()
+    // the link can name the file itself
+    .filter(|candidate| candidate != path)
+    .find(|candidate| {
+        // a debug file from a different build has the same name
+        std::fs::read(candidate).is_ok_and(|data| crc32(&data) == crc)
+    })
+}
+
+// debuginfod clients share a cache, so anything gdb has downloaded is there too
+fn cache_directory() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("DEBUGINFOD_CACHE_PATH") {
+        return Some(path.into());
+    }
+    let cache = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+    Some(cache.join("debuginfod_client"))
+}
+
+/// Downloads the debug info for a build id from the DEBUGINFOD_URLS servers, or finds it already downloaded
+pub(crate) fn fetch(build_id: &[u8]) -> Result<PathBuf> {
+    let id = hex(build_id);
+    let cached = cache_directory()
+        .ok_or_else(|| SdbError::DebugInfo("No debuginfod cache directory".to_owned()))?
+        .join(&id)
+        .join("debuginfo");
+    if cached.is_file() {
+        return Ok(cached);
+    }
+
+    let urls = std::env::var("DEBUGINFOD_URLS").unwrap_or_default();
+    for server in urls.split_whitespace() {
+        let url = format!("{}/buildid/{}/debuginfo", server.trim_end_matches('/'), id);
+        match http_get(&url) {
+            Ok(data) => {
+                let dir = cached.parent().unwrap();
+                let partial = dir.join("debuginfo.partial");
+                std::fs::create_dir_all(dir)
+                    .and_then(|_| std::fs::write(&partial, &data))
+                    .and_then(|_| std::fs::rename(&partial, &cached))
+                    .map_err(|err| SdbError::DebugInfo(format!("{}: {}", cached.display(), err)))?;
+                debug!("Downloaded {} bytes of debug info from {}", data.len(), url);
+                return Ok(cached);
+            }
+            Err(err) => debug!("{}: {}", url, err),
+        }
+    }
+
+    Err(SdbError::DebugInfo(format!(
+        "No debuginfod server has {}",
+        id
+    )))
+}
+
+// just enough HTTP for a debuginfod server, plain http only
+pub(crate) fn http_get(url: &str) -> Result<Vec<u8>> {
+    let error = |message: String| SdbError::DebugInfo(format!("{}: {}", url, message));
+
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| error("Only http:// servers are supported".to_owned()))?;
+    let (host, path) = match rest.find('/') {
+        Some(idx) => rest.split_at(idx),
+        None => (rest, "/"),
+    };
+    let authority = match host.contains(':') {
+        true => host.to_owned(),
+        false => format!("{}:80", host),
+    };
+
+    let address = authority
+        .to_socket_addrs()
+        .map_err(|err| error(err.to_string()))?
+        .next()
+        .ok_or_else(|| error("No address for the server".to_owned()))?;
+    let mut stream = TcpStream::connect_timeout(&address, DEBUGINFOD_TIMEOUT)
+        .map_err(|err| error(err.to_string()))?;
+    stream
+        .set_read_timeout(Some(DEBUGINFOD_TIMEOUT))
+        .map_err(|err| error(err.to_string()))?;
+
+    // HTTP/1.0 so the body isn't chunked and ends when the connection does
+    let request = format!(
+        "GET {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: sdb\r\n\r\n",
+        path, host
+    );
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|err| error(err.to_string()))?;
+
+    let mut response = Vec::new();
+    stream
+        .read_to_end(&mut response)
+        .map_err(|err| error(err.to_string()))?;
+
+    let header_end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| error("Truncated response".to_owned()))?;
+    let headers = String::from_utf8_lossy(&response[..header_end]);
+    let status = headers
+        .split_whitespace()
+        .nth(1)
+        .ok_or_else(|| error("Malformed response".to_owned()))?;
+    if status != "200" {
+        return Err(error(format!("HTTP {}", status)));
+    }
+
+    Ok(response.split_off(header_end + 4))
+}
+
+/// The debug info for a stripped file, found locally or downloaded if debuginfod is enabled
+pub(crate) fn separate_debug_data(
+    path: &Path,
+    local: Option<&Path>,
+    build_id: Option<&[u8]>,
+) -> Option<Vec<u8>> {
+    let debug_file = match (local, build_id) {
+        (Some(local), _) => local.to_path_buf(),
+        (None, Some(build_id)) if debuginfod_enabled() => match fetch(build_id) {
+            Ok(debug_file) => debug_file,
+            Err(err) => {
+                debug!("No debug info for {}: {}", path.display(), err);
+                return None;
+            }
+        },
+        _ => return None,
+    };
+
+    match std::fs::read(&debug_file) {
+        Ok(data) => {
+            debug!(
+                "Reading debug info for {} from {}",
+                path.display(),
+                debug_file.display()
+            );
+            Some(data)
+        }
+        Err(err) => {
+            warn!("{}: {}", debug_file.display(), err);
+            None
+        }
+    }
+}
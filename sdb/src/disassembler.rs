@@ -0,0 +1,79 @@
+//! Decoding the process's machine code, in the AT&T syntax gdb and objdump show
+
+use iced_x86::{Decoder, DecoderOptions, Formatter, GasFormatter, OpKind};
+
+use crate::{Process, Result, types::VirtAddr};
+
+// the longest an x86 instruction can be
+const MAX_INSTRUCTION: usize = 15;
+
+/// One decoded instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub address: VirtAddr,
+    pub bytes: Vec<u8>,
+    /// Like `call   0x401126`, a branch's target is a bare address
+    pub text: String,
+    /// Where a direct call or jump goes
+    pub target: Option<VirtAddr>,
+    /// What a `0x2edc(%rip)` operand is the address of
+    pub referenced: Option<VirtAddr>,
+}
+
+impl Process {
+    /// Decodes `count` instructions from `address`, fewer if it runs into unreadable memory
+    ///
+    /// Breakpoints' int3s are hidden, so it's the program's own code
+    pub fn disassemble(&self, address: VirtAddr, count: usize) -> Result<Vec<Instruction>> {
+        let memory = self.read_memory_without_traps(address, count * MAX_INSTRUCTION)?;
+        Ok(decode(address, &memory, count))
+    }
+
+    /// Decodes the instructions that start from `low` up to `high`, like a function's
+    pub fn disassemble_range(&self, low: VirtAddr, high: VirtAddr) -> Result<Vec<Instruction>> {
+        let size = (high.addr().saturating_sub(low.addr())) as usize;
+        let memory = self.read_memory_without_traps(low, size)?;
+        Ok(decode(low, &memory, usize::MAX))
+    }
+}
+
+// an instruction cut off by the end of the memory isn't decoded
+fn decode(address: VirtAddr, memory: &[u8], count: usize) -> Vec<Instruction> {
+    let mut decoder = Decoder::with_ip(64, memory, address.addr(), DecoderOptions::NONE);
+    let mut formatter = GasFormatter::new();
+    let options = formatter.options_mut();
+    options.set_uppercase_hex(false);
+    options.set_branch_leading_zeros(false);
+    options.set_first_operand_char_index(7);
+    options.set_rip_relative_addresses(true);
+
+    let mut instructions = Vec::new();
+    while decoder.can_decode() && instructions.len() < count {
+        let offset = decoder.position();
+        let instruction = decoder.decode();
+        if instruction.is_invalid() && offset + instruction.len() > memory.len() {
+            break;
+        }
+
+        let mut text = String::new();
+        formatter.format(&instruction, &mut text);
+        let target = match instruction.op0_kind() {
+            OpKind::NearBranch16 | OpKind::NearBranch32 | OpKind::NearBranch64 => {
+                Some(VirtAddr::new(instruction.near_branch_target()))
+            }
+            _ => None,
+        };
+
+        instructions.push(Instruction {
+            address: VirtAddr::new(instruction.ip()),
+            bytes: memory[offset..offset + instruction.len()].to_vec(),
+            text,
+            target,
+            referenced: instruction
+                .is_ip_rel_memory_operand()
+                .then(|| VirtAddr::new(instruction.ip_rel_memory_address())),
+        });
+    }
+
+    instructions
+}
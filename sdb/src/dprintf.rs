@@ -0,0 +1,360 @@
+use num_traits::ToPrimitive;
+
+use crate::{
+    Inferior, Radix, RegisterId, Result, SdbError, Target, VariablePath,
+    expr::Expr,
+    register_info::{register_info_by_id, register_info_by_name},
+    types::VirtAddr,
+};
+
+// %s stops here if there's no NUL before it
+const MAX_STRING: usize = 256;
+
+// how a value is turned into text, the printf conversion it came from
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Conversion {
+    Signed,
+    Unsigned,
+    Hex,
+    UpperHex,
+    Octal,
+    Pointer,
+    Char,
+    String,
+}
+
+impl Conversion {
+    fn from_char(c: char) -> Option<Self> {
+        Some(match c {
+            'd' | 'i' => Self::Signed,
+            'u' => Self::Unsigned,
+            'x' => Self::Hex,
+            'X' => Self::UpperHex,
+            'o' => Self::Octal,
+            'p' => Self::Pointer,
+            'c' => Self::Char,
+            's' => Self::String,
+            _ => return None,
+        })
+    }
+}
+
+// {expr} or {expr:x}, a variable as its type says if it is one, otherwise an integer expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Placeholder {
+    variable: Option<VariablePath>,
+    expr: Option<Expr>,
+    conversion: Option<Conversion>,
+}
+
+impl std::str::FromStr for Placeholder {
+    type Err = SdbError;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let (text, conversion) = match text.rsplit_once(':') {
+            Some((text, conversion)) => {
+                let mut chars = conversion.trim().chars();
+                let conversion = match (chars.next().and_then(Conversion::from_char), chars.next())
+                {
+                    (Some(conversion), None) => conversion,
+                    _ => {
+                        return Err(SdbError::Format(format!(
+                            "Unsupported conversion :{}",
+                            conversion
+                        )));
+                    }
+                };
+                (text, Some(conversion))
+            }
+            None => (text, None),
+        };
+
+        let variable = text.parse::<VariablePath>().ok();
+        let expr = text.parse::<Expr>();
+        if variable.is_none()
+            && let Err(err) = expr
+        {
+            return Err(err);
+        }
+
+        Ok(Self {
+            variable,
+            expr: expr.ok(),
+            conversion,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    Value(Conversion),
+    Placeholder(Placeholder),
+}
+
+/// Something a logging breakpoint prints the value of
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogArg {
+    /// `rdi` or `$rdi`, `$` for when a variable has the same name
+    Register(RegisterId),
+    /// A global, looked up when it's printed so it can be in a library loaded later
+    Variable(String),
+    /// `&name`, where a global lives
+    AddressOf(String),
+    Literal(u64),
+    /// `*arg`, the 8 bytes the argument points to
+    Deref(Box<LogArg>),
+}
+
+impl std::str::FromStr for LogArg {
+    type Err = SdbError;
+
+    fn from_str(arg: &str) -> Result<Self> {
+        let register =
+            |name: &str| register_info_by_name(name).map(|info| Self::Register(info.id()));
+
+        Ok(if let Some(arg) = arg.strip_prefix('*') {
+            Self::Deref(Box::new(arg.parse()?))
+        } else if let Some(name) = arg.strip_prefix('&') {
+            Self::AddressOf(name.to_owned())
+        } else if let Some(name) = arg.strip_prefix('$') {
+            register(name).ok_or_else(|| SdbError::Format(format!("No register named {}", name)))?
+        } else if let Some(hex) = arg.strip_prefix("0x") {
+            Self::Literal(
+                u64::from_str_radix(hex, 16)
+                    .map_err(|err| SdbError::Format(format!("{}: {}", arg, err)))?,
+            )
+        } else if arg.starts_with(|c: char| c.is_ascii_digit()) {
+            Self::Literal(
+                arg.parse()
+                    .map_err(|err| SdbError::Format(format!("{}: {}", arg, err)))?,
+            )
+        } else if arg.is_empty() {
+            return Err(SdbError::Format("Missing argument".to_owned()));
+        } else {
+            register(arg).unwrap_or_else(|| Self::Variable(arg.to_owned()))
+        })
+    }
+}
+
+impl std::fmt::Display for LogArg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Register(id) => write!(f, "${}", register_info_by_id(*id).name()),
+            Self::Variable(name) => write!(f, "{}", name),
+            Self::AddressOf(name) => write!(f, "&{}", name),
+            Self::Literal(value) => write!(f, "{:#x}", value),
+            Self::Deref(arg) => write!(f, "*{}", arg),
+        }
+    }
+}
+
+/// What a logging breakpoint prints each time it's hit, a printf format and its arguments
+///
+/// Supports `%d %i %u %x %X %o %p %c %s` and `%%`, every value is 64 bits
+/// and `%s` reads a NUL terminated string from where its argument points.
+/// `{expr}` prints a variable as `print` would, or an integer expression,
+/// `{expr:x}` with one of the conversions, and `{{` and `}}` are braces
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFormat {
+    format: String,
+    pieces: Vec<Piece>,
+    args: Vec<LogArg>,
+}
+
+impl LogFormat {
+    pub fn new(format: impl Into<String>, args: Vec<LogArg>) -> Result<Self> {
+        let format = format.into();
+
+        let mut pieces = Vec::new();
+        let mut text = String::new();
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '%' => (),
+                '{' if chars.clone().next() == Some('{') => {
+                    chars.next();
+                    text.push('{');
+                    continue;
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest
+                        .find('}')
+                        .ok_or_else(|| SdbError::Format("Missing } in format".to_owned()))?;
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(Piece::Placeholder(rest[..end].parse()?));
+                    chars = rest[end + 1..].chars();
+                    continue;
+                }
+                '}' if chars.clone().next() == Some('}') => {
+                    chars.next();
+                    text.push('}');
+                    continue;
+                }
+                '}' => return Err(SdbError::Format("Unmatched } in format".to_owned())),
+                c => {
+                    text.push(c);
+                    continue;
+                }
+            }
+
+            match chars.next() {
+                Some('%') => text.push('%'),
+                Some(c) => {
+                    let conversion = Conversion::from_char(c).ok_or_else(|| {
+                        SdbError::Format(format!("Unsupported conversion %{}", c))
+                    })?;
+                    if !text.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut text)));
+                    }
+                    pieces.push(Piece::Value(conversion));
+                }
+                None => return Err(SdbError::Format("Format ends with %".to_owned())),
+            }
+        }
+        if !text.is_empty() {
+            pieces.push(Piece::Text(text));
+        }
+
+        let conversions = pieces
+            .iter()
+            .filter(|piece| matches!(piece, Piece::Value(_)))
+            .count();
+        if conversions != args.len() {
+            return Err(SdbError::Format(format!(
+                "Format has {} conversions but there are {} arguments",
+                conversions,
+                args.len()
+            )));
+        }
+
+        Ok(Self {
+            format,
+            pieces,
+            args,
+        })
+    }
+
+    #[inline]
+    pub fn format(&self) -> &str {
+        &self.format
+    }
+
+    #[inline]
+    pub fn args(&self) -> &[LogArg] {
+        &self.args
+    }
+}
+
+impl std::fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.format)?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+impl Target {
+    fn log_arg_value(&self, arg: &LogArg) -> Result<u64> {
+        match arg {
+            LogArg::Register(id) => self.read_register_by_id(*id)?.to_u64().ok_or_else(|| {
+                SdbError::Format(format!(
+                    "{} doesn't fit in 64 bits",
+                    register_info_by_id(*id).name()
+                ))
+            }),
+            LogArg::Variable(name) => {
+                let (address, size) = self.log_variable(name)?;
+                let mut bytes = self.read_memory_without_traps(address, size.min(8))?;
+                bytes.resize(8, 0);
+                Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+            }
+            LogArg::AddressOf(name) => Ok(self.log_variable(name)?.0.addr()),
+            LogArg::Literal(value) => Ok(*value),
+            LogArg::Deref(arg) => self.read_u64(VirtAddr::new(self.log_arg_value(arg)?)),
+        }
+    }
+
+    fn log_variable(&self, name: &str) -> Result<(VirtAddr, usize)> {
+        let (elf, symbol) = self
+            .modules
+            .find_variable(name)
+            .ok_or_else(|| SdbError::Format(format!("No variable named {}", name)))?;
+        Ok((elf.symbol_address(symbol), symbol.size as usize))
+    }
+
+    fn log_string(&self, address: VirtAddr) -> Result<String> {
+        let string = self.read_c_string(address, MAX_STRING)?;
+        Ok(String::from_utf8_lossy(&string).into_owned())
+    }
+
+    fn log_value(&self, conversion: Conversion, arg: &LogArg) -> Result<String> {
+        self.log_convert(conversion, self.log_arg_value(arg)?)
+    }
+
+    // a variable that can't be read falls back to the expression, where a symbol is its address
+    fn log_placeholder(&self, placeholder: &Placeholder) -> Result<String> {
+        if let Some(path) = &placeholder.variable {
+            match self.read_variable(path, Radix::Decimal) {
+                Ok(value) => {
+                    return match (placeholder.conversion, value.scalar) {
+                        (Some(conversion), Some(scalar)) => self.log_convert(conversion, scalar),
+                        _ => Ok(value.text),
+                    };
+                }
+                Err(err) if placeholder.expr.is_none() => return Err(err),
+                Err(_) => (),
+            }
+        }
+
+        let value = placeholder.expr.as_ref().unwrap().evaluate(self)?;
+        self.log_convert(
+            placeholder.conversion.unwrap_or(Conversion::Signed),
+            value as u64,
+        )
+    }
+
+    fn log_convert(&self, conversion: Conversion, value: u64) -> Result<String> {
+        Ok(match conversion {
+            Conversion::Signed => (value as i64).to_string(),
+            Conversion::Unsigned => value.to_string(),
+            Conversion::Hex => format!("{:x}", value),
+            Conversion::UpperHex => format!("{:X}", value),
+            Conversion::Octal => format!("{:o}", value),
+            Conversion::Pointer => format!("{:#x}", value),
+            Conversion::Char => char::from(value as u8).to_string(),
+            Conversion::String => self.log_string(VirtAddr::new(value))?,
+        })
+    }
+
+    /// Prints a log format against the process as it is now
+    ///
+    /// An argument that can't be read is printed as the error in angle brackets,
+    /// so one bad pointer doesn't lose the rest of the line
+    pub fn format_log(&self, format: &LogFormat) -> String {
+        let mut args = format.args.iter();
+        let mut message = String::new();
+        for piece in &format.pieces {
+            match piece {
+                Piece::Text(text) => message.push_str(text),
+                Piece::Value(conversion) => {
+                    let arg = args.next().unwrap();
+                    match self.log_value(*conversion, arg) {
+                        Ok(value) => message.push_str(&value),
+                        Err(err) => message.push_str(&format!("<{}>", err)),
+                    }
+                }
+                Piece::Placeholder(placeholder) => match self.log_placeholder(placeholder) {
+                    Ok(value) => message.push_str(&value),
+                    Err(err) => message.push_str(&format!("<{}>", err)),
+                },
+            }
+        }
+        message
+    }
+}
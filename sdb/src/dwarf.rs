@@ -0,0 +1,805 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use object::{Object, ObjectSection};
+
+use crate::{Result, SdbError};
+
+type Reader<'a> = gimli::EndianSlice<'a, gimli::RunTimeEndian>;
+
+// a unit's types are converted once each, by where they are in it
+type TypeCache = HashMap<gimli::UnitOffset, TypeId>;
+
+/// An index into [`Dwarf::get_type`]
+pub type TypeId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BaseEncoding {
+    Signed,
+    Unsigned,
+    SignedChar,
+    UnsignedChar,
+    Bool,
+    Float,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DwarfMember {
+    pub name: Option<String>,
+    pub type_id: Option<TypeId>,
+    // from the start of the struct, in bits since bitfields don't start on a byte
+    pub bit_offset: u64,
+    // only bitfields have one
+    pub bit_size: Option<u64>,
+}
+
+/// A type from the debug info, what's needed to read and show a value of it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DwarfType {
+    Base {
+        name: String,
+        encoding: BaseEncoding,
+        size: u64,
+    },
+    /// Pointers and references, to void if there's no pointee
+    Pointer {
+        pointee: Option<TypeId>,
+        size: u64,
+    },
+    /// Structs, unions, and classes
+    Struct {
+        keyword: &'static str,
+        name: Option<String>,
+        size: u64,
+        members: Vec<DwarfMember>,
+    },
+    /// Outermost dimension first, None where the size isn't known
+    Array {
+        element: Option<TypeId>,
+        counts: Vec<Option<u64>>,
+    },
+    Enum {
+        name: Option<String>,
+        size: u64,
+        enumerators: Vec<(String, i64)>,
+    },
+    Typedef {
+        name: String,
+        target: Option<TypeId>,
+    },
+    /// const, volatile, restrict, and _Atomic
+    Qualified {
+        qualifier: &'static str,
+        target: Option<TypeId>,
+    },
+    Function,
+    /// Anything else, by its tag
+    Unsupported(&'static str),
+}
+
+/// A DWARF expression kept with what it takes to evaluate it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DwarfExpression {
+    pub(crate) encoding: gimli::Encoding,
+    pub(crate) bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DwarfLocation {
+    // file addresses where the expression holds, None for anywhere in scope
+    pub range: Option<Range<u64>>,
+    pub expression: DwarfExpression,
+}
+
+/// A function's parameter or local
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DwarfVariable {
+    pub name: String,
+    pub type_id: Option<TypeId>,
+    pub is_parameter: bool,
+
+    // empty if it's been optimized out
+    pub locations: Vec<DwarfLocation>,
+
+    // file addresses of the block it's declared in, empty for the whole function
+    pub scope: Vec<Range<u64>>,
+}
+
+impl DwarfVariable {
+    #[inline]
+    pub fn in_scope(&self, address: u64) -> bool {
+        self.scope.is_empty() || self.scope.iter().any(|range| range.contains(&address))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineEntry {
+    // file address
+    pub address: u64,
+    pub file: PathBuf,
+    pub line: u64,
+    pub column: u64,
+    pub is_stmt: bool,
+
+    // where the compiler says the function's set up, not every compiler says
+    pub prologue_end: bool,
+
+    // first address past the end of a sequence
+    pub end_sequence: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DwarfFunction {
+    pub name: String,
+
+    // file addresses, high_pc is exclusive
+    pub low_pc: u64,
+    pub high_pc: u64,
+
+    pub file: Option<PathBuf>,
+    pub line: Option<u64>,
+
+    pub frame_base: Option<DwarfExpression>,
+
+    // None for void
+    pub return_type: Option<TypeId>,
+
+    // outer blocks' variables come before inner ones
+    pub variables: Vec<DwarfVariable>,
+}
+
+impl DwarfFunction {
+    #[inline]
+    pub fn contains(&self, address: u64) -> bool {
+        self.low_pc <= address && address < self.high_pc
+    }
+}
+
+/// Debug info for a single ELF file, parsed up front
+#[derive(Debug, Default)]
+pub struct Dwarf {
+    // sorted by address, end of sequence markers first
+    line_table: Vec<LineEntry>,
+
+    // sorted by low_pc
+    functions: Vec<DwarfFunction>,
+
+    // in the order they're defined, a file's statics can share a name with another's
+    globals: Vec<DwarfVariable>,
+
+    types: Vec<DwarfType>,
+    endian: gimli::RunTimeEndian,
+}
+
+impl Dwarf {
+    /// Returns None if the file has no debug info
+    pub fn parse(data: &[u8]) -> Result<Option<Self>> {
+        let file = object::File::parse(data).map_err(|err| SdbError::Elf(err.to_string()))?;
+        if file.section_by_name(".debug_info").is_none() {
+            return Ok(None);
+        }
+
+        let endian = if file.is_little_endian() {
+            gimli::RunTimeEndian::Little
+        } else {
+            gimli::RunTimeEndian::Big
+        };
+
+        let sections = gimli::DwarfSections::load(|id| -> Result<Cow<[u8]>> {
+            Ok(file
+                .section_by_name(id.name())
+                .and_then(|section| section.uncompressed_data().ok())
+                .unwrap_or_default())
+        })?;
+        let dwarf = sections.borrow(|section| gimli::EndianSlice::new(section, endian));
+
+        let mut this = Self {
+            endian,
+            ..Self::default()
+        };
+
+        let mut units = dwarf.units();
+        while let Some(header) = units.next()? {
+            let unit = dwarf.unit(header)?;
+            let unit = unit.unit_ref(&dwarf);
+
+            let files = this.parse_line_program(unit)?;
+            // functions and globals share the unit's types
+            let mut types = TypeCache::new();
+            this.parse_functions(unit, &files, &mut types)?;
+            this.parse_globals(unit, &mut types)?;
+        }
+
+        this.line_table
+            .sort_by_key(|entry| (entry.address, !entry.end_sequence));
+        this.functions.sort_by_key(|function| function.low_pc);
+
+        Ok(Some(this))
+    }
+
+    // returns the unit's file table for resolving DW_AT_decl_file
+    fn parse_line_program(&mut self, unit: gimli::UnitRef<Reader>) -> Result<Vec<PathBuf>> {
+        let Some(program) = unit.line_program.clone() else {
+            return Ok(Vec::new());
+        };
+
+        let comp_dir = unit
+            .comp_dir
+            .map(|dir| PathBuf::from(dir.to_string_lossy().into_owned()))
+            .unwrap_or_default();
+
+        let header = program.header().clone();
+        let mut files = Vec::new();
+
+        // DWARF 5 file indexes start at 0, earlier versions start at 1
+        if header.version() < 5 {
+            files.push(PathBuf::new());
+        }
+        for file in header.file_names() {
+            files.push(Self::file_path(unit, &header, file, &comp_dir)?);
+        }
+
+        let mut rows = program.rows();
+        while let Some((_, row)) = rows.next_row()? {
+            let file = files
+                .get(row.file_index() as usize)
+                .cloned()
+                .unwrap_or_default();
+
+            self.line_table.push(LineEntry {
+                address: row.address(),
+                file,
+                line: row.line().map(|line| line.get()).unwrap_or_default(),
+                column: match row.column() {
+                    gimli::ColumnType::LeftEdge => 0,
+                    gimli::ColumnType::Column(column) => column.get(),
+                },
+                is_stmt: row.is_stmt(),
+                prologue_end: row.prologue_end(),
+                end_sequence: row.end_sequence(),
+            });
+        }
+
+        Ok(files)
+    }
+
+    fn file_path<'a>(
+        unit: gimli::UnitRef<Reader<'a>>,
+        header: &gimli::LineProgramHeader<Reader<'a>>,
+        file: &gimli::FileEntry<Reader<'a>>,
+        comp_dir: &Path,
+    ) -> Result<PathBuf> {
+        let name = unit.attr_string(file.path_name())?;
+        let mut path = comp_dir.to_path_buf();
+
+        if let Some(dir) = file.directory(header) {
+            let dir = unit.attr_string(dir)?;
+            path.push(dir.to_string_lossy().as_ref());
+        }
+        path.push(name.to_string_lossy().as_ref());
+
+        Ok(path)
+    }
+
+    fn parse_functions(
+        &mut self,
+        unit: gimli::UnitRef<Reader>,
+        files: &[PathBuf],
+        types: &mut TypeCache,
+    ) -> Result<()> {
+        let mut entries = unit.entries();
+        while let Some(entry) = entries.next_dfs()? {
+            if entry.tag() != gimli::DW_TAG_subprogram {
+                continue;
+            }
+
+            let Some(low_pc) = entry.attr_value(gimli::DW_AT_low_pc) else {
+                continue;
+            };
+            let Some(low_pc) = unit.attr_address(low_pc)? else {
+                continue;
+            };
+
+            // high_pc is usually an offset from low_pc
+            let high_pc = match entry.attr_value(gimli::DW_AT_high_pc) {
+                Some(gimli::AttributeValue::Udata(size)) => low_pc + size,
+                Some(value) => unit.attr_address(value)?.unwrap_or(low_pc),
+                None => low_pc,
+            };
+
+            let Some(name) = Self::die_name(unit, entry)? else {
+                continue;
+            };
+
+            let file = match entry.attr_value(gimli::DW_AT_decl_file) {
+                Some(gimli::AttributeValue::FileIndex(index)) => files.get(index as usize).cloned(),
+                _ => None,
+            };
+            let line = entry
+                .attr_value(gimli::DW_AT_decl_line)
+                .and_then(|value| value.udata_value());
+
+            let frame_base = match entry.attr_value(gimli::DW_AT_frame_base) {
+                Some(gimli::AttributeValue::Exprloc(expression)) => Some(DwarfExpression {
+                    encoding: unit.encoding(),
+                    bytes: expression.0.to_vec(),
+                }),
+                _ => None,
+            };
+
+            let return_type = match Self::die_type(unit, entry)? {
+                Some(offset) => Some(self.parse_type(unit, offset, types)?),
+                None => None,
+            };
+
+            let mut variables = Vec::new();
+            let mut tree = unit.entries_tree(Some(entry.offset()))?;
+            self.parse_scope(unit, tree.root()?, &[], types, &mut variables)?;
+
+            self.functions.push(DwarfFunction {
+                name,
+                low_pc,
+                high_pc,
+                file,
+                line,
+                frame_base,
+                return_type,
+                variables,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn parse_globals(&mut self, unit: gimli::UnitRef<Reader>, types: &mut TypeCache) -> Result<()> {
+        let mut tree = unit.entries_tree(None)?;
+        self.parse_global_scope(unit, tree.root()?, types)
+    }
+
+    // variables outside any function, namespaces are looked in but don't go in the name
+    fn parse_global_scope<'a>(
+        &mut self,
+        unit: gimli::UnitRef<Reader<'a>>,
+        node: gimli::EntriesTreeNode<Reader<'a>>,
+        types: &mut TypeCache,
+    ) -> Result<()> {
+        let mut children = node.children();
+        while let Some(child) = children.next()? {
+            let entry = child.entry();
+            match entry.tag() {
+                gimli::DW_TAG_variable => {
+                    // declarations have nowhere to be read from
+                    let locations = Self::parse_locations(unit, entry)?;
+                    if locations.is_empty() {
+                        continue;
+                    }
+                    let Some(name) = Self::die_name(unit, entry)? else {
+                        continue;
+                    };
+
+                    let type_id = match Self::die_type(unit, entry)? {
+                        Some(offset) => Some(self.parse_type(unit, offset, types)?),
+                        None => None,
+                    };
+                    self.globals.push(DwarfVariable {
+                        name,
+                        type_id,
+                        is_parameter: false,
+                        locations,
+                        scope: Vec::new(),
+                    });
+                }
+                gimli::DW_TAG_namespace => self.parse_global_scope(unit, child, types)?,
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    // the variables in a function or one of its blocks, and in the blocks inside that
+    fn parse_scope<'a>(
+        &mut self,
+        unit: gimli::UnitRef<Reader<'a>>,
+        node: gimli::EntriesTreeNode<Reader<'a>>,
+        scope: &[Range<u64>],
+        types: &mut TypeCache,
+        variables: &mut Vec<DwarfVariable>,
+    ) -> Result<()> {
+        let mut children = node.children();
+        while let Some(child) = children.next()? {
+            let entry = child.entry();
+            match entry.tag() {
+                gimli::DW_TAG_variable | gimli::DW_TAG_formal_parameter => {
+                    // extern declarations are defined somewhere else
+                    if entry.attr_value(gimli::DW_AT_declaration).is_some() {
+                        continue;
+                    }
+                    let Some(name) = Self::die_name(unit, entry)? else {
+                        continue;
+                    };
+
+                    let type_id = match Self::die_type(unit, entry)? {
+                        Some(offset) => Some(self.parse_type(unit, offset, types)?),
+                        None => None,
+                    };
+                    variables.push(DwarfVariable {
+                        name,
+                        type_id,
+                        is_parameter: entry.tag() == gimli::DW_TAG_formal_parameter,
+                        locations: Self::parse_locations(unit, entry)?,
+                        scope: scope.to_vec(),
+                    });
+                }
+                gimli::DW_TAG_lexical_block => {
+                    let mut ranges = Vec::new();
+                    let mut iter = unit.die_ranges(entry)?;
+                    while let Some(range) = iter.next()? {
+                        ranges.push(range.begin..range.end);
+                    }
+                    self.parse_scope(unit, child, &ranges, types, variables)?;
+                }
+                // nested functions and inlined calls have their own variables
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_locations<'a>(
+        unit: gimli::UnitRef<Reader<'a>>,
+        entry: &gimli::DebuggingInformationEntry<Reader<'a>>,
+    ) -> Result<Vec<DwarfLocation>> {
+        let expression = |bytes: Reader<'a>| DwarfExpression {
+            encoding: unit.encoding(),
+            bytes: bytes.to_vec(),
+        };
+
+        let mut locations = Vec::new();
+        match entry.attr_value(gimli::DW_AT_location) {
+            None => (),
+            Some(gimli::AttributeValue::Exprloc(bytes)) => locations.push(DwarfLocation {
+                range: None,
+                expression: expression(bytes.0),
+            }),
+            Some(value) => {
+                // a location list, optimized code moves variables around
+                if let Some(mut iter) = unit.attr_locations(value)? {
+                    while let Some(location) = iter.next()? {
+                        locations.push(DwarfLocation {
+                            range: Some(location.range.begin..location.range.end),
+                            expression: expression(location.data.0),
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(locations)
+    }
+
+    // concrete copies of inlined functions leave the type to the original
+    fn die_type<'a>(
+        unit: gimli::UnitRef<Reader<'a>>,
+        entry: &gimli::DebuggingInformationEntry<Reader<'a>>,
+    ) -> Result<Option<gimli::UnitOffset>> {
+        if let Some(gimli::AttributeValue::UnitRef(offset)) = entry.attr_value(gimli::DW_AT_type) {
+            return Ok(Some(offset));
+        }
+
+        for attr in [gimli::DW_AT_specification, gimli::DW_AT_abstract_origin] {
+            if let Some(gimli::AttributeValue::UnitRef(offset)) = entry.attr_value(attr) {
+                let entry = unit.entry(offset)?;
+                return Self::die_type(unit, &entry);
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn parse_type_attr<'a>(
+        &mut self,
+        unit: gimli::UnitRef<Reader<'a>>,
+        entry: &gimli::DebuggingInformationEntry<Reader<'a>>,
+        types: &mut TypeCache,
+    ) -> Result<Option<TypeId>> {
+        match entry.attr_value(gimli::DW_AT_type) {
+            Some(gimli::AttributeValue::UnitRef(offset)) => {
+                Ok(Some(self.parse_type(unit, offset, types)?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn parse_type<'a>(
+        &mut self,
+        unit: gimli::UnitRef<Reader<'a>>,
+        offset: gimli::UnitOffset,
+        types: &mut TypeCache,
+    ) -> Result<TypeId> {
+        if let Some(&id) = types.get(&offset) {
+            return Ok(id);
+        }
+
+        // in place before its parts so a struct that points to itself finds it
+        let id = self.types.len();
+        self.types.push(DwarfType::Unsupported("incomplete"));
+        types.insert(offset, id);
+
+        let entry = unit.entry(offset)?;
+        let name = Self::die_name(unit, &entry)?;
+        let size = entry
+            .attr_value(gimli::DW_AT_byte_size)
+            .and_then(|value| value.udata_value())
+            .unwrap_or_default();
+
+        let r#type = match entry.tag() {
+            gimli::DW_TAG_base_type => DwarfType::Base {
+                name: name.unwrap_or_default(),
+                encoding: match entry.attr_value(gimli::DW_AT_encoding) {
+                    Some(gimli::AttributeValue::Encoding(gimli::DW_ATE_signed)) => {
+                        BaseEncoding::Signed
+                    }
+                    Some(gimli::AttributeValue::Encoding(gimli::DW_ATE_signed_char)) => {
+                        BaseEncoding::SignedChar
+                    }
+                    Some(gimli::AttributeValue::Encoding(gimli::DW_ATE_unsigned_char)) => {
+                        BaseEncoding::UnsignedChar
+                    }
+                    Some(gimli::AttributeValue::Encoding(gimli::DW_ATE_boolean)) => {
+                        BaseEncoding::Bool
+                    }
+                    Some(gimli::AttributeValue::Encoding(gimli::DW_ATE_float)) => {
+                        BaseEncoding::Float
+                    }
+                    _ => BaseEncoding::Unsigned,
+                },
+                size,
+            },
+            gimli::DW_TAG_pointer_type
+            | gimli::DW_TAG_reference_type
+            | gimli::DW_TAG_rvalue_reference_type => DwarfType::Pointer {
+                pointee: self.parse_type_attr(unit, &entry, types)?,
+                size: if size == 0 { 8 } else { size },
+            },
+            tag @ (gimli::DW_TAG_structure_type
+            | gimli::DW_TAG_union_type
+            | gimli::DW_TAG_class_type) => {
+                let mut members = Vec::new();
+                for child in Self::children(unit, offset, gimli::DW_TAG_member)? {
+                    members.push(self.parse_member(unit, &child, types)?);
+                }
+                DwarfType::Struct {
+                    keyword: match tag {
+                        gimli::DW_TAG_union_type => "union",
+                        gimli::DW_TAG_class_type => "class",
+                        _ => "struct",
+                    },
+                    name,
+                    size,
+                    members,
+                }
+            }
+            gimli::DW_TAG_array_type => DwarfType::Array {
+                element: self.parse_type_attr(unit, &entry, types)?,
+                counts: Self::children(unit, offset, gimli::DW_TAG_subrange_type)?
+                    .iter()
+                    .map(|subrange| {
+                        let value = |attr| {
+                            subrange
+                                .attr_value(attr)
+                                .and_then(|value| value.udata_value())
+                        };
+                        value(gimli::DW_AT_count)
+                            .or_else(|| value(gimli::DW_AT_upper_bound).map(|bound| bound + 1))
+                    })
+                    .collect(),
+            },
+            gimli::DW_TAG_enumeration_type => DwarfType::Enum {
+                name,
+                size,
+                enumerators: Self::children(unit, offset, gimli::DW_TAG_enumerator)?
+                    .iter()
+                    .filter_map(|enumerator| {
+                        let name = Self::die_name(unit, enumerator).ok()??;
+                        let value = match enumerator.attr_value(gimli::DW_AT_const_value)? {
+                            gimli::AttributeValue::Sdata(value) => value,
+                            value => value.udata_value()? as i64,
+                        };
+                        Some((name, value))
+                    })
+                    .collect(),
+            },
+            gimli::DW_TAG_typedef => DwarfType::Typedef {
+                name: name.unwrap_or_default(),
+                target: self.parse_type_attr(unit, &entry, types)?,
+            },
+            tag @ (gimli::DW_TAG_const_type
+            | gimli::DW_TAG_volatile_type
+            | gimli::DW_TAG_restrict_type
+            | gimli::DW_TAG_atomic_type) => DwarfType::Qualified {
+                qualifier: match tag {
+                    gimli::DW_TAG_const_type => "const",
+                    gimli::DW_TAG_volatile_type => "volatile",
+                    gimli::DW_TAG_restrict_type => "restrict",
+                    _ => "_Atomic",
+                },
+                target: self.parse_type_attr(unit, &entry, types)?,
+            },
+            gimli::DW_TAG_subroutine_type => DwarfType::Function,
+            tag => DwarfType::Unsupported(tag.static_string().unwrap_or("unknown")),
+        };
+
+        self.types[id] = r#type;
+        Ok(id)
+    }
+
+    fn parse_member<'a>(
+        &mut self,
+        unit: gimli::UnitRef<Reader<'a>>,
+        entry: &gimli::DebuggingInformationEntry<Reader<'a>>,
+        types: &mut TypeCache,
+    ) -> Result<DwarfMember> {
+        let value = |attr| entry.attr_value(attr).and_then(|value| value.udata_value());
+
+        // old compilers give the offset as an expression that adds it to the struct's address
+        let byte_offset = match entry.attr_value(gimli::DW_AT_data_member_location) {
+            Some(gimli::AttributeValue::Exprloc(expression)) => {
+                let mut evaluation = expression.evaluation(unit.encoding());
+                evaluation.set_initial_value(0);
+                match (evaluation.evaluate()?, evaluation.as_result()) {
+                    (
+                        gimli::EvaluationResult::Complete,
+                        [
+                            gimli::Piece {
+                                location: gimli::Location::Address { address },
+                                ..
+                            },
+                        ],
+                    ) => *address,
+                    _ => 0,
+                }
+            }
+            Some(value) => value.udata_value().unwrap_or_default(),
+            None => 0,
+        };
+
+        let bit_size = value(gimli::DW_AT_bit_size);
+        let bit_offset = match (value(gimli::DW_AT_data_bit_offset), bit_size) {
+            (Some(bit_offset), _) => bit_offset,
+            // DWARF 2 and 3 counted from the most significant bit of the storage unit
+            (None, Some(bit_size)) => match (
+                value(gimli::DW_AT_bit_offset),
+                value(gimli::DW_AT_byte_size),
+            ) {
+                (Some(bit_offset), Some(byte_size)) => {
+                    byte_offset * 8 + byte_size * 8 - bit_offset - bit_size
+                }
+                _ => byte_offset * 8,
+            },
+            (None, None) => byte_offset * 8,
+        };
+
+        Ok(DwarfMember {
+            name: Self::die_name(unit, entry)?,
+            type_id: self.parse_type_attr(unit, entry, types)?,
+            bit_offset,
+            bit_size,
+        })
+    }
+
+    // the children of a DIE with a tag, read out so the tree isn't borrowed
+    fn children<'a>(
+        unit: gimli::UnitRef<Reader<'a>>,
+        offset: gimli::UnitOffset,
+        tag: gimli::DwTag,
+    ) -> Result<Vec<gimli::DebuggingInformationEntry<Reader<'a>>>> {
+        let mut tree = unit.entries_tree(Some(offset))?;
+        let mut children = tree.root()?.children();
+        let mut entries = Vec::new();
+        while let Some(child) = children.next()? {
+            if child.entry().tag() == tag {
+                entries.push(child.entry().clone());
+            }
+        }
+        Ok(entries)
+    }
+
+    // out of line definitions get their name from the declaration
+    fn die_name<'a>(
+        unit: gimli::UnitRef<Reader<'a>>,
+        entry: &gimli::DebuggingInformationEntry<Reader<'a>>,
+    ) -> Result<Option<String>> {
+        if let Some(name) = entry.attr_value(gimli::DW_AT_name) {
+            let name = unit.attr_string(name)?;
+            return Ok(Some(name.to_string_lossy().into_owned()));
+        }
+
+        for attr in [gimli::DW_AT_specification, gimli::DW_AT_abstract_origin] {
+            if let Some(gimli::AttributeValue::UnitRef(offset)) = entry.attr_value(attr) {
+                let entry = unit.entry(offset)?;
+                return Self::die_name(unit, &entry);
+            }
+        }
+
+        Ok(None)
+    }
+
+    #[inline]
+    pub fn line_table(&self) -> &[LineEntry] {
+        &self.line_table
+    }
+
+    #[inline]
+    pub fn functions(&self) -> &[DwarfFunction] {
+        &self.functions
+    }
+
+    #[inline]
+    pub fn globals(&self) -> &[DwarfVariable] {
+        &self.globals
+    }
+
+    /// The first global defined with the name, if there's more than one
+    pub fn global(&self, name: &str) -> Option<&DwarfVariable> {
+        self.globals.iter().find(|global| global.name == name)
+    }
+
+    #[inline]
+    pub fn get_type(&self, id: TypeId) -> &DwarfType {
+        &self.types[id]
+    }
+
+    #[inline]
+    pub(crate) fn endian(&self) -> gimli::RunTimeEndian {
+        self.endian
+    }
+
+    pub fn line_entry_at_address(&self, address: u64) -> Option<&LineEntry> {
+        let idx = self
+            .line_table
+            .partition_point(|entry| entry.address <= address);
+        let entry = self.line_table[..idx].last()?;
+
+        // past the end of the last sequence
+        (!entry.end_sequence).then_some(entry)
+    }
+
+    /// The file address where a function's body starts, past the code that sets up its frame
+    ///
+    /// The line table's prologue end marker if there is one, otherwise the first
+    /// statement on a line after the function's first, or its entry if there isn't one
+    pub fn prologue_end(&self, function: &DwarfFunction) -> u64 {
+        let start = self
+            .line_table
+            .partition_point(|entry| entry.address < function.low_pc);
+        let entries = self.line_table[start..]
+            .iter()
+            .take_while(|entry| entry.address < function.high_pc && !entry.end_sequence);
+
+        if let Some(entry) = entries.clone().find(|entry| entry.prologue_end) {
+            return entry.address;
+        }
+
+        let mut entries = entries.filter(|entry| entry.is_stmt);
+        let Some(first) = entries.next() else {
+            return function.low_pc;
+        };
+        entries
+            .find(|entry| entry.address > function.low_pc && entry.line != first.line)
+            .map_or(function.low_pc, |entry| entry.address)
+    }
+
+    pub fn function_containing_address(&self, address: u64) -> Option<&DwarfFunction> {
+        let idx = self
+            .functions
+            .partition_point(|function| function.low_pc <= address);
+        self.functions[..idx]
+            .iter()
+            .rev()
+            .find(|function| function.contains(address))
+    }
+}
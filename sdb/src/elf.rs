@@ -0,0 +1,564 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use object::{
+    Object, ObjectSection, ObjectSegment, ObjectSymbol, SectionFlags, SectionKind, SymbolKind, elf,
+    read::elf::{ElfFile64, ProgramHeader},
+};
+use procfs::process::MMapPath;
+use tracing::trace;
+
+use crate::{Result, SdbError, debuginfo, dwarf::Dwarf, types::VirtAddr};
+
+#[derive(Debug, Clone)]
+pub struct ElfSymbol {
+    pub name: String,
+    pub demangled_name: String,
+    // file address, use Elf::symbol_address() for the loaded address
+    pub address: u64,
+    pub size: u64,
+    pub is_function: bool,
+}
+
+impl ElfSymbol {
+    // matches the raw name, the demangled name,
+    // or the last path component of the demangled name
+    pub fn matches(&self, name: impl AsRef<str>) -> bool {
+        let name = name.as_ref();
+        self.name == name
+            || self.demangled_name == name
+            || self
+                .demangled_name
+                .strip_suffix(name)
+                .is_some_and(|prefix| prefix.ends_with("::"))
+    }
+}
+
+/// The image every thread's copy of a module's thread-locals starts from, its PT_TLS segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TlsTemplate {
+    /// File address of the initialized part, the rest is zeroed
+    pub address: u64,
+    pub file_size: u64,
+    pub size: u64,
+    pub align: u64,
+}
+
+#[derive(Debug)]
+pub struct Elf {
+    path: PathBuf,
+    data: Vec<u8>,
+    entry: u64,
+    first_load_address: u64,
+    load_bias: VirtAddr,
+
+    // file address ranges of the loadable segments
+    load_ranges: Vec<std::ops::Range<u64>>,
+
+    // file address ranges of the sections that are mapped read-only, with where they are in the
+    // file, so reads of unmodified code and constants don't have to go through the process
+    read_only_sections: Vec<(std::ops::Range<u64>, usize)>,
+
+    // file address ranges of the .plt sections, the stubs calls to other modules go through
+    plt_ranges: Vec<std::ops::Range<u64>>,
+
+    // sorted by address
+    symbols: Vec<ElfSymbol>,
+
+    tls_template: Option<TlsTemplate>,
+
+    build_id: Option<Vec<u8>>,
+
+    // where the debug info stripped out of this file is, if it's been found locally
+    debug_file: Option<PathBuf>,
+
+    // parsed on first use, most libraries never need it
+    dwarf: OnceLock<Option<Dwarf>>,
+}
+
+impl Elf {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let data = std::fs::read(&path)
+            .map_err(|err| SdbError::Elf(format!("{}: {}", path.display(), err)))?;
+
+        let file = object::File::parse(&*data)
+            .map_err(|err| SdbError::Elf(format!("{}: {}", path.display(), err)))?;
+
+        // the lowest mapped address is whatever segment covers the start of the file
+        let first_load_address = file
+            .segments()
+            .filter(|segment| segment.file_range().0 == 0)
+            .map(|segment| segment.address())
+            .min()
+            .unwrap_or_default()
+            & !0xfff;
+
+        let load_ranges = file
+            .segments()
+            .map(|segment| segment.address()..segment.address() + segment.size())
+            .collect();
+
+        let read_only_sections = file
+            .sections()
+            .filter(|section| {
+                let SectionFlags::Elf { sh_flags, .. } = section.flags() else {
+                    return false;
+                };
+                sh_flags.0 & elf::SHF_ALLOC.0 != 0
+                    && sh_flags.0 & elf::SHF_WRITE.0 == 0
+                    && section.kind() != SectionKind::UninitializedData
+            })
+            .filter_map(|section| {
+                let (offset, size) = section.file_range()?;
+                (size == section.size())
+                    .then(|| (section.address()..section.address() + size, offset as usize))
+            })
+            .collect();
+
+        let plt_ranges = file
+            .sections()
+            .filter(|section| {
+                section
+                    .name()
+                    .is_ok_and(|name| name == ".plt" || name.starts_with(".plt."))
+            })
+            .map(|section| section.address()..section.address() + section.size())
+            .collect();
+
+        let build_id = file.build_id().ok().flatten().map(<[u8]>::to_vec);
+
+        // stripped files can have their debug info somewhere else
+        let debug_file = file
+            .section_by_name(".debug_info")
+            .is_none()
+            .then(|| debuginfo::find_local(&path, &file))
+            .flatten();
+
+        let mut symbols = read_symbols(&file);
+        if file.symbol_table().is_none()
+            && let Some(debug_file) = &debug_file
+            && let Ok(data) = std::fs::read(debug_file)
+            && let Ok(debug) = object::File::parse(&*data)
+        {
+            symbols.extend(read_symbols(&debug));
+        }
+        symbols.sort_by(|a, b| a.address.cmp(&b.address).then(a.name.cmp(&b.name)));
+        symbols.dedup_by(|a, b| a.address == b.address && a.name == b.name);
+
+        let entry = file.entry();
+
+        // object's segments don't say what type they are
+        let tls_template = ElfFile64::<object::Endianness>::parse(&*data)
+            .ok()
+            .and_then(|file| {
+                let endian = file.endian();
+                file.elf_program_headers()
+                    .iter()
+                    .find(|header| header.p_type(endian) == elf::PT_TLS)
+                    .map(|header| TlsTemplate {
+                        address: header.p_vaddr(endian),
+                        file_size: header.p_filesz(endian),
+                        size: header.p_memsz(endian),
+                        align: header.p_align(endian),
+                    })
+            });
+
+        Ok(Self {
+            path,
+            data,
+            entry,
+            first_load_address,
+            load_bias: VirtAddr::default(),
+            load_ranges,
+            read_only_sections,
+            plt_ranges,
+            symbols,
+            tls_template,
+            build_id,
+            debug_file,
+            dwarf: OnceLock::new(),
+        })
+    }
+
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    #[inline]
+    pub fn load_bias(&self) -> VirtAddr {
+        self.load_bias
+    }
+
+    #[inline]
+    pub(crate) fn notify_loaded(&mut self, load_bias: VirtAddr) {
+        self.load_bias = load_bias;
+    }
+
+    #[inline]
+    pub fn virtual_address(&self, address: u64) -> VirtAddr {
+        self.load_bias + address as i64
+    }
+
+    #[inline]
+    pub fn file_address(&self, address: VirtAddr) -> Option<u64> {
+        address.addr().checked_sub(self.load_bias.addr())
+    }
+
+    pub fn contains_address(&self, address: VirtAddr) -> bool {
+        self.file_address(address).is_some_and(|address| {
+            self.load_ranges
+                .iter()
+                .any(|range| range.contains(&address))
+        })
+    }
+
+    // where the segments are loaded
+    pub(crate) fn load_ranges(&self) -> impl Iterator<Item = std::ops::Range<VirtAddr>> {
+        self.load_ranges
+            .iter()
+            .map(|range| self.virtual_address(range.start)..self.virtual_address(range.end))
+    }
+
+    /// Whether the address is in a PLT stub rather than a function of this module
+    pub fn is_plt(&self, address: VirtAddr) -> bool {
+        self.file_address(address)
+            .is_some_and(|address| self.plt_ranges.iter().any(|range| range.contains(&address)))
+    }
+
+    // where the PLT stubs are loaded
+    pub(crate) fn plt_ranges(&self) -> impl Iterator<Item = std::ops::Range<VirtAddr>> {
+        self.plt_ranges
+            .iter()
+            .map(|range| self.virtual_address(range.start)..self.virtual_address(range.end))
+    }
+
+    /// The file's copy of memory that's all in one read-only section
+    ///
+    /// It's only what the process has if nothing's written over it since
+    pub fn read_only_bytes(&self, address: VirtAddr, amount: usize) -> Option<&[u8]> {
+        let start = self.file_address(address)?;
+        let end = start.checked_add(amount as u64)?;
+        let (range, offset) = self
+            .read_only_sections
+            .iter()
+            .find(|(range, _)| range.start <= start && end <= range.end)?;
+
+        let offset = offset + (start - range.start) as usize;
+        self.data.get(offset..offset + amount)
+    }
+
+    /// The GNU build id note, which ties a stripped file to its debug info
+    #[inline]
+    pub fn build_id(&self) -> Option<&[u8]> {
+        self.build_id.as_deref()
+    }
+
+    /// A separate file with this one's debug info in it, found by build id or .gnu_debuglink
+    #[inline]
+    pub fn debug_file(&self) -> Option<&Path> {
+        self.debug_file.as_deref()
+    }
+
+    /// Returns None if the file has no usable debug info
+    ///
+    /// A stripped file's comes from its separate debug file,
+    /// or a debuginfod server if that's enabled
+    pub fn dwarf(&self) -> Option<&Dwarf> {
+        self.dwarf
+            .get_or_init(|| match Dwarf::parse(&self.data) {
+                Ok(None) => {
+                    let data = debuginfo::separate_debug_data(
+                        &self.path,
+                        self.debug_file.as_deref(),
+                        self.build_id.as_deref(),
+                    )?;
+                    Dwarf::parse(&data).ok().flatten()
+                }
+                Ok(dwarf) => dwarf,
+                Err(err) => {
+                    trace!(
+                        "Failed to parse debug info for {}: {}",
+                        self.path.display(),
+                        err
+                    );
+                    None
+                }
+            })
+            .as_ref()
+    }
+
+    #[inline]
+    pub fn entry_point(&self) -> VirtAddr {
+        self.load_bias + self.entry as i64
+    }
+
+    /// How to find the canonical frame address at a file address, from the unwind info
+    ///
+    /// A DWARF register number and what to add to its value
+    #[inline]
+    pub fn cfa_rule(&self, address: u64) -> Option<(u16, i64)> {
+        self.unwind_row(address).map(|row| row.cfa)
+    }
+
+    /// Where the frame at a file address starts and where its caller's registers were saved
+    pub fn unwind_row(&self, address: u64) -> Option<UnwindRow> {
+        use gimli::UnwindSection;
+
+        let file = object::File::parse(&*self.data).ok()?;
+        let section = file.section_by_name(".eh_frame")?;
+        let endian = if file.is_little_endian() {
+            gimli::RunTimeEndian::Little
+        } else {
+            gimli::RunTimeEndian::Big
+        };
+
+        let mut eh_frame = gimli::EhFrame::new(section.data().ok()?, endian);
+        eh_frame.set_address_size(8);
+        let mut bases = gimli::BaseAddresses::default().set_eh_frame(section.address());
+        if let Some(text) = file.section_by_name(".text") {
+            bases = bases.set_text(text.address());
+        }
+
+        let mut context = gimli::UnwindContext::new();
+        let row = eh_frame
+            .unwind_info_for_address(
+                &bases,
+                &mut context,
+                address,
+                gimli::EhFrame::cie_from_offset,
+            )
+            .ok()?;
+        let cfa = match row.cfa() {
+            gimli::CfaRule::RegisterAndOffset { register, offset } => (register.0, *offset),
+            gimli::CfaRule::Expression(_) => return None,
+        };
+        // registers saved some other way are left as they are
+        let saved = row
+            .registers()
+            .filter_map(|(register, rule)| match rule {
+                gimli::RegisterRule::Offset(offset) => Some((register.0, *offset)),
+                _ => None,
+            })
+            .collect();
+
+        Some(UnwindRow { cfa, saved })
+    }
+
+    /// None if the module has no thread-locals
+    #[inline]
+    pub fn tls_template(&self) -> Option<TlsTemplate> {
+        self.tls_template
+    }
+
+    #[inline]
+    pub fn symbols(&self) -> &[ElfSymbol] {
+        &self.symbols
+    }
+
+    #[inline]
+    pub fn symbol_address(&self, symbol: &ElfSymbol) -> VirtAddr {
+        self.load_bias + symbol.address as i64
+    }
+
+    pub fn symbols_by_name(&self, name: impl AsRef<str>) -> impl Iterator<Item = &ElfSymbol> {
+        let name = name.as_ref().to_owned();
+        self.symbols
+            .iter()
+            .filter(move |symbol| symbol.matches(&name))
+    }
+
+    pub fn symbol_containing_address(&self, address: VirtAddr) -> Option<&ElfSymbol> {
+        let address = self.file_address(address)?;
+
+        let idx = self
+            .symbols
+            .partition_point(|symbol| symbol.address <= address);
+        self.symbols[..idx].iter().rev().find(|symbol| {
+            symbol.address == address
+                || (symbol.address < address && address < symbol.address + symbol.size)
+        })
+    }
+}
+
+fn read_symbols(file: &object::File) -> Vec<ElfSymbol> {
+    file.symbols()
+        .chain(file.dynamic_symbols())
+        .filter(|symbol| symbol.address() != 0 && !symbol.is_undefined())
+        .filter_map(|symbol| {
+            let name = symbol.name().ok()?;
+            if name.is_empty() {
+                return None;
+            }
+
+            Some(ElfSymbol {
+                name: name.to_owned(),
+                demangled_name: format!("{:#}", rustc_demangle::demangle(name)),
+                address: symbol.address(),
+                size: symbol.size(),
+                is_function: symbol.kind() == SymbolKind::Text,
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ModuleChanges {
+    pub loaded: Vec<PathBuf>,
+    pub unloaded: Vec<PathBuf>,
+}
+
+fn is_elf(path: impl AsRef<Path>) -> bool {
+    use std::io::Read;
+
+    let mut magic = [0; 4];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok()
+        && magic == *b"\x7fELF"
+}
+
+/// How to unwind a frame, from a module's unwind info
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnwindRow {
+    /// A DWARF register number and what to add to its value for the canonical frame address
+    pub cfa: (u16, i64),
+    /// DWARF register numbers and where they were saved, relative to the canonical frame address
+    pub saved: Vec<(u16, i64)>,
+}
+
+#[derive(Debug, Default)]
+pub struct ElfCollection {
+    elves: Vec<Elf>,
+}
+
+impl ElfCollection {
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &Elf> {
+        self.elves.iter()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.elves.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.elves.is_empty()
+    }
+
+    pub fn get_elf_by_path(&self, path: impl AsRef<Path>) -> Option<&Elf> {
+        self.elves.iter().find(|elf| elf.path() == path.as_ref())
+    }
+
+    // exact matches anywhere win over a path suffix match,
+    // so `write` finds libc's write rather than some `Foo::write`
+    pub fn find_function(&self, name: impl AsRef<str>) -> Option<(&Elf, &ElfSymbol)> {
+        let name = name.as_ref();
+        let find = |exact: bool| {
+            self.elves.iter().find_map(|elf| {
+                elf.symbols_by_name(name)
+                    .find(|symbol| {
+                        symbol.is_function
+                            && (!exact || symbol.name == name || symbol.demangled_name == name)
+                    })
+                    .map(|symbol| (elf, symbol))
+            })
+        };
+
+        find(true).or_else(|| find(false))
+    }
+
+    // data symbols only, sized so there's something to read
+    pub fn find_variable(&self, name: impl AsRef<str>) -> Option<(&Elf, &ElfSymbol)> {
+        let name = name.as_ref();
+        self.elves.iter().find_map(|elf| {
+            elf.symbols_by_name(name)
+                .find(|symbol| !symbol.is_function && symbol.size > 0)
+                .map(|symbol| (elf, symbol))
+        })
+    }
+
+    pub fn get_elf_containing_symbol_address(&self, address: VirtAddr) -> Option<&Elf> {
+        self.elves
+            .iter()
+            .find(|elf| elf.symbol_containing_address(address).is_some())
+    }
+
+    pub fn get_elf_containing_address(&self, address: VirtAddr) -> Option<&Elf> {
+        self.elves.iter().find(|elf| elf.contains_address(address))
+    }
+
+    #[inline]
+    pub fn read_only_bytes(&self, address: VirtAddr, amount: usize) -> Option<&[u8]> {
+        self.get_elf_containing_address(address)?
+            .read_only_bytes(address, amount)
+    }
+
+    // syncs the collection with what's currently mapped into the process
+    pub(crate) fn refresh(&mut self, pid: i32) -> Result<ModuleChanges> {
+        let maps = procfs::process::Process::new(pid)?.maps()?;
+
+        let mut mapped: HashMap<PathBuf, u64> = HashMap::new();
+        for map in maps {
+            if let MMapPath::Path(path) = map.pathname
+                && map.offset == 0
+            {
+                let start = mapped.entry(path).or_insert(map.address.0);
+                *start = (*start).min(map.address.0);
+            }
+        }
+
+        Ok(self.sync(mapped))
+    }
+
+    // takes each mapped file's lowest address to be where it was loaded
+    pub(crate) fn sync(&mut self, mapped: HashMap<PathBuf, u64>) -> ModuleChanges {
+        let mut changes = ModuleChanges::default();
+        self.elves.retain(|elf| {
+            let retain = mapped.contains_key(elf.path());
+            if !retain {
+                changes.unloaded.push(elf.path().to_path_buf());
+            }
+            retain
+        });
+
+        for (path, start) in mapped {
+            if self.get_elf_by_path(&path).is_some() {
+                continue;
+            }
+
+            // plenty of mapped files aren't ELF files
+            if !is_elf(&path) {
+                continue;
+            }
+
+            let mut elf = match Elf::open(&path) {
+                Ok(elf) => elf,
+                Err(err) => {
+                    trace!("Skipping mapped file {}: {}", path.display(), err);
+                    continue;
+                }
+            };
+            elf.notify_loaded(VirtAddr::new(start - elf.first_load_address));
+
+            trace!(
+                "Loaded module {} with bias {}",
+                path.display(),
+                elf.load_bias()
+            );
+            changes.loaded.push(path);
+            self.elves.push(elf);
+        }
+
+        changes
+    }
+}
@@ -0,0 +1,128 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::SystemTime;
+
+use nix::{sys::signal::Signal, unistd::Pid};
+
+use crate::{
+    AllocationEvent, OutputStream, Process, ProcessState, Result, StopReason, SyscallRecord,
+    Target, stoppoint_collection::StoppointId, types::VirtAddr,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DebugEvent {
+    Stopped(StopReason),
+    BreakpointHit {
+        id: StoppointId,
+        address: VirtAddr,
+    },
+    /// A watchpoint whose memory was accessed, with what it held before and after
+    ///
+    /// `old` is None until the memory's been seen to change
+    WatchpointHit {
+        id: StoppointId,
+        old: Option<Vec<u8>>,
+        new: Vec<u8>,
+    },
+    LibraryLoaded(PathBuf),
+    LibraryUnloaded(PathBuf),
+    ThreadCreated(Pid),
+    ThreadExited(Pid),
+    Exited(i32),
+    Terminated(Option<Signal>),
+
+    /// What a logging breakpoint printed when it was hit
+    Logged {
+        id: StoppointId,
+        message: String,
+    },
+
+    /// A syscall a thread made while syscalls were traced, reported once it returned
+    Syscall(SyscallRecord),
+
+    /// A call to the allocator while allocations were traced, reported once it returned
+    Allocation(AllocationEvent),
+
+    /// A line the process wrote to a stream launched with [`crate::Redirect::Events`]
+    ///
+    /// Anything written before a stop is emitted before the stop's event
+    Output {
+        stream: OutputStream,
+        line: String,
+        time: SystemTime,
+    },
+}
+
+// shared with the threads relaying captured output
+#[derive(Debug, Default, Clone)]
+pub(crate) struct EventSubscribers {
+    senders: Arc<Mutex<Vec<mpsc::Sender<DebugEvent>>>>,
+}
+
+impl EventSubscribers {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.senders.lock().unwrap().is_empty()
+    }
+
+    pub fn subscribe(&self) -> mpsc::Receiver<DebugEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.senders.lock().unwrap().push(sender);
+        receiver
+    }
+
+    pub fn emit(&self, event: DebugEvent) {
+        // dropped receivers just unsubscribe
+        self.senders
+            .lock()
+            .unwrap()
+            .retain(|sender| sender.send(event.clone()).is_ok());
+    }
+}
+
+impl Target {
+    /// Subscribes to events from this process
+    ///
+    /// Events are emitted as the process is waited on,
+    /// the receiver is unsubscribed when it's dropped
+    pub fn events(&mut self) -> Result<mpsc::Receiver<DebugEvent>> {
+        self.track_modules()?;
+
+        Ok(self.process.subscribers.subscribe())
+    }
+}
+
+impl Process {
+    pub(crate) fn emit_stop_events(&mut self, reason: &StopReason) -> Result<()> {
+        // whatever the process wrote on its way to the stop comes first
+        self.output.drain(&self.subscribers);
+
+        if self.subscribers.is_empty() {
+            return Ok(());
+        }
+
+        let event = match reason.reason {
+            ProcessState::Exited => DebugEvent::Exited(reason.info),
+            ProcessState::Terminated => DebugEvent::Terminated(reason.signal()),
+            _ => match (reason.watchpoint, reason.breakpoint_site) {
+                (Some(id), _) => {
+                    let watchpoint = self.watchpoints.get_by_id(id)?;
+                    DebugEvent::WatchpointHit {
+                        id,
+                        old: watchpoint.previous().map(<[u8]>::to_vec),
+                        new: watchpoint.value().to_vec(),
+                    }
+                }
+                (None, Some(id)) => DebugEvent::BreakpointHit {
+                    id,
+                    address: self.get_pc()?,
+                },
+                (None, None) => DebugEvent::Stopped(reason.clone()),
+            },
+        };
+        self.subscribers.emit(event);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,357 @@
+//! Integer expressions over registers, memory, and symbols
+//!
+//! `$rip`, `*($rsp + 8)`, `counter`, `$rdi == 3 && *flags & 0x4`, with C's operators and
+//! precedence. Every value is a 64 bit integer, a symbol is its address and `*` reads the
+//! 8 bytes at one. Comparisons and logical operators give 1 or 0
+
+use std::str::FromStr;
+
+use num_traits::ToPrimitive;
+
+use crate::{
+    Inferior, RegisterId, Result, SdbError,
+    register_info::{register_info_by_id, register_info_by_name},
+    types::VirtAddr,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+    BitNot,
+    Deref,
+}
+
+impl UnaryOp {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Neg => "-",
+            Self::Not => "!",
+            Self::BitNot => "~",
+            Self::Deref => "*",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BinaryOp {
+    Mul,
+    Div,
+    Rem,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    BitAnd,
+    BitXor,
+    BitOr,
+    And,
+    Or,
+}
+
+impl BinaryOp {
+    // longest first so << isn't read as <
+    const ALL: [Self; 18] = [
+        Self::Shl,
+        Self::Shr,
+        Self::Le,
+        Self::Ge,
+        Self::Eq,
+        Self::Ne,
+        Self::And,
+        Self::Or,
+        Self::Mul,
+        Self::Div,
+        Self::Rem,
+        Self::Add,
+        Self::Sub,
+        Self::Lt,
+        Self::Gt,
+        Self::BitAnd,
+        Self::BitXor,
+        Self::BitOr,
+    ];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Mul => "*",
+            Self::Div => "/",
+            Self::Rem => "%",
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Shl => "<<",
+            Self::Shr => ">>",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::BitAnd => "&",
+            Self::BitXor => "^",
+            Self::BitOr => "|",
+            Self::And => "&&",
+            Self::Or => "||",
+        }
+    }
+
+    // higher binds tighter, as in C
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Or => 1,
+            Self::And => 2,
+            Self::BitOr => 3,
+            Self::BitXor => 4,
+            Self::BitAnd => 5,
+            Self::Eq | Self::Ne => 6,
+            Self::Lt | Self::Le | Self::Gt | Self::Ge => 7,
+            Self::Shl | Self::Shr => 8,
+            Self::Add | Self::Sub => 9,
+            Self::Mul | Self::Div | Self::Rem => 10,
+        }
+    }
+
+    fn apply(&self, lhs: i64, rhs: i64) -> Result<i64> {
+        Ok(match self {
+            Self::Mul => lhs.wrapping_mul(rhs),
+            Self::Div | Self::Rem if rhs == 0 => {
+                return Err(SdbError::Expression("Division by zero".to_owned()));
+            }
+            Self::Div => lhs.wrapping_div(rhs),
+            Self::Rem => lhs.wrapping_rem(rhs),
+            Self::Add => lhs.wrapping_add(rhs),
+            Self::Sub => lhs.wrapping_sub(rhs),
+            Self::Shl => lhs.wrapping_shl(rhs as u32),
+            Self::Shr => lhs.wrapping_shr(rhs as u32),
+            Self::Lt => (lhs < rhs) as i64,
+            Self::Le => (lhs <= rhs) as i64,
+            Self::Gt => (lhs > rhs) as i64,
+            Self::Ge => (lhs >= rhs) as i64,
+            Self::Eq => (lhs == rhs) as i64,
+            Self::Ne => (lhs != rhs) as i64,
+            Self::BitAnd => lhs & rhs,
+            Self::BitXor => lhs ^ rhs,
+            Self::BitOr => lhs | rhs,
+            Self::And => (lhs != 0 && rhs != 0) as i64,
+            Self::Or => (lhs != 0 || rhs != 0) as i64,
+        })
+    }
+}
+
+/// A parsed expression, evaluated against an [`Inferior`] as it is at the time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Number(i64),
+    Register(RegisterId),
+    Symbol(String),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+fn expr_error(message: impl Into<String>) -> SdbError {
+    SdbError::Expression(message.into())
+}
+
+// a precedence climbing parser straight over the text
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&mut self) -> &'a str {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+        &self.text[self.pos..]
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        let found = self.rest().starts_with(token);
+        if found {
+            self.pos += token.len();
+        }
+        found
+    }
+
+    fn word(&mut self) -> &'a str {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+            .unwrap_or(rest.len());
+        let word = &self.text[self.pos..self.pos + len];
+        self.pos += len;
+        word
+    }
+
+    fn binary_op(&mut self) -> Option<BinaryOp> {
+        let rest = self.rest();
+        BinaryOp::ALL
+            .into_iter()
+            .find(|op| rest.starts_with(op.as_str()))
+    }
+
+    fn expr(&mut self, min_precedence: u8) -> Result<Expr> {
+        let mut lhs = self.unary()?;
+        while let Some(op) = self.binary_op()
+            && op.precedence() >= min_precedence
+        {
+            self.pos += op.as_str().len();
+            // left associative, the right side only takes tighter operators
+            let rhs = self.expr(op.precedence() + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        for op in [UnaryOp::Neg, UnaryOp::BitNot, UnaryOp::Deref] {
+            if self.eat(op.as_str()) {
+                return Ok(Expr::Unary(op, Box::new(self.unary()?)));
+            }
+        }
+
+        // != is a binary operator, but it can't come first
+        if self.eat("!") {
+            return Ok(Expr::Unary(UnaryOp::Not, Box::new(self.unary()?)));
+        }
+
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        if self.eat("(") {
+            let expr = self.expr(0)?;
+            if !self.eat(")") {
+                return Err(expr_error(format!("Missing ) at {}", self.pos)));
+            }
+            return Ok(expr);
+        }
+
+        if self.eat("$") {
+            let name = self.word();
+            return register_info_by_name(name)
+                .map(|info| Expr::Register(info.id()))
+                .ok_or_else(|| expr_error(format!("No register named {}", name)));
+        }
+
+        let word = self.word();
+        if word.is_empty() {
+            return Err(match self.rest().chars().next() {
+                Some(c) => expr_error(format!("Unexpected {} at {}", c, self.pos)),
+                None => expr_error("Expression ends too soon"),
+            });
+        }
+
+        if word.starts_with(|c: char| c.is_ascii_digit()) {
+            let value = match word.strip_prefix("0x") {
+                Some(hex) => u64::from_str_radix(hex, 16),
+                None => word.parse(),
+            }
+            .map_err(|err| expr_error(format!("{}: {}", word, err)))?;
+            return Ok(Expr::Number(value as i64));
+        }
+
+        Ok(Expr::Symbol(word.to_owned()))
+    }
+}
+
+impl FromStr for Expr {
+    type Err = SdbError;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let mut parser = Parser { text, pos: 0 };
+        let expr = parser.expr(0)?;
+        let rest = parser.rest();
+        if !rest.is_empty() {
+            return Err(expr_error(format!("Unexpected {} at {}", rest, parser.pos)));
+        }
+        Ok(expr)
+    }
+}
+
+impl Expr {
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Binary(op, ..) => op.precedence(),
+            _ => u8::MAX,
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut std::fmt::Formatter<'_>, min_precedence: u8) -> std::fmt::Result {
+        if self.precedence() < min_precedence {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+
+    /// The value of the expression, reading whatever it refers to from `inferior`
+    pub fn evaluate(&self, inferior: &(impl Inferior + ?Sized)) -> Result<i64> {
+        match self {
+            Self::Number(value) => Ok(*value),
+            Self::Register(id) => {
+                let value = inferior.read_register_by_id(*id)?;
+                value.to_u64().map(|value| value as i64).ok_or_else(|| {
+                    expr_error(format!(
+                        "{} isn't an integer",
+                        register_info_by_id(*id).name()
+                    ))
+                })
+            }
+            Self::Symbol(name) => {
+                let modules = inferior.modules();
+                let (elf, symbol) = modules
+                    .find_variable(name)
+                    .or_else(|| modules.find_function(name))
+                    .ok_or_else(|| expr_error(format!("No symbol named {}", name)))?;
+                Ok(elf.symbol_address(symbol).addr() as i64)
+            }
+            Self::Unary(op, operand) => {
+                let value = operand.evaluate(inferior)?;
+                Ok(match op {
+                    UnaryOp::Neg => value.wrapping_neg(),
+                    UnaryOp::Not => (value == 0) as i64,
+                    UnaryOp::BitNot => !value,
+                    UnaryOp::Deref => inferior.read_u64(VirtAddr::new(value as u64))? as i64,
+                })
+            }
+            // the right side of && and || isn't evaluated if it doesn't matter, like in C
+            Self::Binary(op @ (BinaryOp::And | BinaryOp::Or), lhs, rhs) => {
+                let lhs = lhs.evaluate(inferior)? != 0;
+                if lhs == (*op == BinaryOp::Or) {
+                    return Ok(lhs as i64);
+                }
+                Ok((rhs.evaluate(inferior)? != 0) as i64)
+            }
+            Self::Binary(op, lhs, rhs) => {
+                op.apply(lhs.evaluate(inferior)?, rhs.evaluate(inferior)?)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number(value) if *value < 0 => write!(f, "{:#x}", value),
+            Self::Number(value) => write!(f, "{}", value),
+            Self::Register(id) => write!(f, "${}", register_info_by_id(*id).name()),
+            Self::Symbol(name) => write!(f, "{}", name),
+            Self::Unary(op, operand) => {
+                write!(f, "{}", op.as_str())?;
+                operand.fmt_operand(f, u8::MAX)
+            }
+            Self::Binary(op, lhs, rhs) => {
+                lhs.fmt_operand(f, op.precedence())?;
+                write!(f, " {} ", op.as_str())?;
+                rhs.fmt_operand(f, op.precedence() + 1)
+            }
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{
+    Process, Result, Target,
+    stoppoint_collection::{Stoppoint, StoppointId},
+};
+
+// functions that are only called when the process is about to die
+pub const DEFAULT_FATAL_FUNCTIONS: &[&str] = &[
+    "abort",
+    "__assert_fail",
+    "__stack_chk_fail",
+    "__fortify_fail",
+    "__chk_fail",
+    "rust_begin_unwind",
+];
+
+#[derive(Debug, Default)]
+pub(crate) struct FatalBreakpoints {
+    functions: Vec<String>,
+}
+
+impl FatalBreakpoints {
+    #[inline]
+    pub(crate) fn functions(&self) -> &[String] {
+        &self.functions
+    }
+}
+
+impl Target {
+    /// Stops the process on entry to any of the given functions
+    ///
+    /// Functions in libraries that haven't been loaded yet
+    /// are resolved as the libraries are loaded
+    pub fn catch_fatal(&mut self, functions: &[impl AsRef<str>]) -> Result<()> {
+        self.clear_fatal()?;
+        self.track_modules()?;
+
+        self.fatal.functions = functions
+            .iter()
+            .map(|function| function.as_ref().to_owned())
+            .collect();
+        self.resolve_fatal_breakpoints()
+    }
+
+    pub fn clear_fatal(&mut self) -> Result<()> {
+        let functions = std::mem::take(&mut self.fatal.functions);
+
+        // catch_panic's are left alone
+        let sites = self
+            .process
+            .breakpoint_sites
+            .iter()
+            .filter(|site| {
+                site.caught_function()
+                    .is_some_and(|function| functions.iter().any(|fatal| fatal == function))
+            })
+            .map(|site| site.id())
+            .collect::<Vec<_>>();
+        for id in sites {
+            self.process.remove_breakpoint_site(id)?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn resolve_fatal_breakpoints(&mut self) -> Result<()> {
+        let addresses = self
+            .fatal
+            .functions
+            .iter()
+            .flat_map(|function| {
+                self.modules.iter().flat_map(move |elf| {
+                    elf.symbols_by_name(function)
+                        .filter(|symbol| symbol.is_function)
+                        .map(move |symbol| (elf.symbol_address(symbol), function.clone()))
+                })
+            })
+            .filter(|(address, _)| !self.process.breakpoint_sites.contains_address(*address))
+            .collect::<Vec<_>>();
+
+        for (address, function) in addresses {
+            // aliased symbols can resolve to the same address
+            if self.process.breakpoint_sites.contains_address(address) {
+                continue;
+            }
+
+            let id = self.process.create_breakpoint_site(address, true)?;
+            self.process
+                .breakpoint_sites
+                .get_by_id_mut(id)?
+                .catch(function);
+            self.process.enable_breakpoint_site(id)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Process {
+    #[inline]
+    pub(crate) fn fatal_function(&self, id: StoppointId) -> Option<&str> {
+        self.breakpoint_sites.get_by_id(id).ok()?.caught_function()
+    }
+}
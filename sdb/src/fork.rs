@@ -0,0 +1,179 @@
+use nix::{libc, sys::ptrace, unistd::Pid};
+use tracing::trace;
+
+use crate::{
+    Process, Result, SdbError,
+    stoppoint_collection::{Stoppoint, StoppointId},
+    threads,
+};
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum FollowForkMode {
+    #[default]
+    Parent,
+    Child,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ForkState {
+    mode: FollowForkMode,
+
+    // sites disabled while a vfork child borrows our memory
+    vfork_disabled_sites: Option<Vec<StoppointId>>,
+
+    // the parent and child of a caught fork, which side's followed is up to the mode when resumed
+    forked: Option<(Pid, Pid)>,
+}
+
+impl Process {
+    #[inline]
+    pub fn follow_fork_mode(&self) -> FollowForkMode {
+        self.fork.mode
+    }
+
+    #[inline]
+    pub fn set_follow_fork_mode(&mut self, mode: FollowForkMode) {
+        self.fork.mode = mode;
+    }
+
+    /// The child of a caught fork, until the process is resumed and it's followed or let go
+    #[inline]
+    pub fn forked_child(&self) -> Option<Pid> {
+        self.fork.forked.map(|(_, child)| child)
+    }
+
+    // returns true if the event was handled and the process should be resumed,
+    // a caught fork waits for the resume to pick a side
+    pub(crate) fn handle_fork_event(&mut self, tid: Pid, event: i32, caught: bool) -> Result<bool> {
+        match event {
+            libc::PTRACE_EVENT_FORK => {
+                let child = self.new_child(tid)?;
+                if caught {
+                    self.fork.forked = Some((tid, child));
+                    return Ok(false);
+                }
+
+                self.follow_fork(tid, child)?;
+                Ok(true)
+            }
+            libc::PTRACE_EVENT_VFORK => {
+                // the child shares our memory until it execs or exits
+                // so there's no way to scrub it without also scrubbing ourselves
+                // always follow the parent and keep the sites out of the way until it's done
+                let child = self.new_child(tid)?;
+
+                let enabled = self
+                    .breakpoint_sites
+                    .iter()
+                    .filter(|site| site.is_enabled())
+                    .map(|site| site.id())
+                    .collect::<Vec<_>>();
+                for id in &enabled {
+                    self.disable_breakpoint_site(*id)?;
+                }
+                self.fork.vfork_disabled_sites = Some(enabled);
+
+                ptrace::detach(child, None).map_err(SdbError::ptrace("detach", child))?;
+                Ok(!caught)
+            }
+            libc::PTRACE_EVENT_VFORK_DONE => {
+                if let Some(sites) = self.fork.vfork_disabled_sites.take() {
+                    for id in sites {
+                        self.enable_breakpoint_site(id)?;
+                    }
+                }
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    // follows a caught fork's side now that the mode's been picked
+    pub(crate) fn settle_fork(&mut self) -> Result<()> {
+        match self.fork.forked.take() {
+            Some((parent, child)) => self.follow_fork(parent, child),
+            None => Ok(()),
+        }
+    }
+
+    // lets a caught fork's child go without following it
+    pub(crate) fn release_forked_child(&mut self) -> Result<()> {
+        match self.fork.forked.take() {
+            Some((_, child)) => self.release(&[child]),
+            None => Ok(()),
+        }
+    }
+
+    fn follow_fork(&mut self, tid: Pid, child: Pid) -> Result<()> {
+        match self.fork.mode {
+            FollowForkMode::Parent => self.release(&[child]),
+            FollowForkMode::Child => {
+                // every thread has to be stopped to detach it
+                self.stop_other_threads(Some(tid)).into_result()?;
+                let parent = self.threads.keys().copied().collect::<Vec<_>>();
+
+                self.switch_to(child)?;
+                self.release(&parent)
+            }
+        }
+    }
+
+    pub(crate) fn new_child(&self, tid: Pid) -> Result<Pid> {
+        let child = ptrace::getevent(tid).map_err(SdbError::ptrace("geteventmsg", tid))?;
+        let child = Pid::from_raw(child as i32);
+        trace!("Process {} forked {}", self.pid, child);
+
+        // the child starts out stopped
+        threads::wait_for_thread(child)?;
+
+        Ok(child)
+    }
+
+    // removes our int3s from a process we're no longer following and lets it go
+    pub(crate) fn release(&self, tids: &[Pid]) -> Result<()> {
+        // threads share memory, scrubbing through one of them is enough
+        self.scrub_sites(tids[0])?;
+
+        for tid in tids {
+            ptrace::detach(*tid, None).map_err(SdbError::ptrace("detach", *tid))?;
+        }
+
+        Ok(())
+    }
+
+    // puts back what our int3s are over in a copy of the process
+    pub(crate) fn scrub_sites(&self, pid: Pid) -> Result<()> {
+        for site in self.breakpoint_sites.iter() {
+            if !site.is_enabled() {
+                continue;
+            }
+
+            let address = site.address().addr() as ptrace::AddressType;
+            let data = ptrace::read(pid, address).map_err(SdbError::ptrace_at(
+                "peekdata",
+                pid,
+                site.address(),
+            ))? as u64;
+            let restored = (data & !0xff) | site.saved_data() as u64;
+            ptrace::write(pid, address, restored as i64).map_err(SdbError::ptrace_at(
+                "pokedata",
+                pid,
+                site.address(),
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn switch_to(&mut self, pid: Pid) -> Result<()> {
+        trace!("Following child {}", pid);
+
+        self.pid = pid;
+        self.shared.set_pid(pid);
+
+        self.reset_threads(pid);
+
+        self.set_trace_options(pid)?;
+        self.read_all_registers(pid)
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use object::{Object, ObjectSymbol, ObjectSymbolTable, RelocationFlags, RelocationTarget, elf};
+
+use crate::{Elf, Inferior, Result, SdbError, Target, types::VirtAddr};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GotStatus {
+    /// Still points at its PLT stub, the symbol's bound the first time it's called
+    Lazy,
+    Resolved,
+    /// The dynamic linker hasn't gotten to the module yet, it's what's in the file
+    Unrelocated,
+    /// A weak symbol nothing defines
+    Null,
+    /// Points somewhere that doesn't define the symbol, like a hook
+    Unexpected(String),
+}
+
+/// A GOT slot a module calls or reads another module's symbol through
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GotEntry {
+    pub symbol: String,
+    pub slot: VirtAddr,
+    /// The PLT stub that jumps through the slot, None for a slot only read
+    pub plt: Option<VirtAddr>,
+    /// What the slot holds now
+    pub target: VirtAddr,
+    /// The module the target's in
+    pub target_module: Option<PathBuf>,
+    pub status: GotStatus,
+}
+
+impl Target {
+    /// The module's GOT slots for the symbols it imports, with where they point now
+    ///
+    /// Lazily bound slots point back into the module's own PLT until they're first called.
+    /// A slot is flagged when it points into memory no module defining its symbol was loaded at
+    pub fn got(&self, elf: &Elf) -> Result<Vec<GotEntry>> {
+        let file = object::File::parse(elf.data())
+            .map_err(|err| SdbError::Elf(format!("{}: {}", elf.path().display(), err)))?;
+        let (Some(relocations), Some(symbols)) =
+            (file.dynamic_relocations(), file.dynamic_symbol_table())
+        else {
+            return Ok(Vec::new());
+        };
+
+        let stubs = self.plt_stubs(elf)?;
+        let mut entries = Vec::new();
+        for (offset, relocation) in relocations {
+            let (
+                RelocationFlags::Elf {
+                    r_type: elf::R_X86_64_JUMP_SLOT | elf::R_X86_64_GLOB_DAT,
+                },
+                RelocationTarget::Symbol(index),
+            ) = (relocation.flags(), relocation.target())
+            else {
+                continue;
+            };
+            let Ok(symbol) = symbols.symbol_by_index(index) else {
+                continue;
+            };
+            let is_weak = symbol.is_weak();
+            let Some(symbol) = symbol.name().ok().filter(|name| !name.is_empty()) else {
+                continue;
+            };
+
+            let slot = elf.virtual_address(offset);
+            let target = VirtAddr::new(self.read_u64(slot)?);
+            let target_elf = self.modules.get_elf_containing_address(target);
+            // lazy slots start out as their stub's file address, the rest as 0
+            let status = if target.addr() == 0 && is_weak {
+                GotStatus::Null
+            } else if target.addr() == 0 || elf.is_plt(elf.virtual_address(target.addr())) {
+                GotStatus::Unrelocated
+            } else if elf.is_plt(target) {
+                GotStatus::Lazy
+            } else {
+                // an ifunc's implementation has another name, so it's the module that counts
+                match target_elf {
+                    Some(target_elf) if target_elf.symbols_by_name(symbol).next().is_some() => {
+                        GotStatus::Resolved
+                    }
+                    Some(target_elf) => GotStatus::Unexpected(format!(
+                        "points into {}, which doesn't define {}",
+                        target_elf.path().display(),
+                        symbol
+                    )),
+                    None => GotStatus::Unexpected(format!(
+                        "points outside every module, where nothing defines {}",
+                        symbol
+                    )),
+                }
+            };
+
+            entries.push(GotEntry {
+                symbol: symbol.to_owned(),
+                slot,
+                plt: stubs.get(&slot).copied(),
+                target,
+                target_module: target_elf.map(|elf| elf.path().to_path_buf()),
+                status,
+            });
+        }
+
+        entries.sort_by_key(|entry| entry.slot);
+        Ok(entries)
+    }
+
+    // which stub jumps through which slot, the stubs are `jmp *slot(%rip)`, after an endbr64
+    // with IBT
+    fn plt_stubs(&self, elf: &Elf) -> Result<HashMap<VirtAddr, VirtAddr>> {
+        let mut stubs = HashMap::new();
+        for range in elf.plt_ranges() {
+            let instructions = self.process.disassemble_range(range.start, range.end)?;
+            for (index, instruction) in instructions.iter().enumerate() {
+                let Some(slot) = instruction
+                    .referenced
+                    .filter(|_| instruction.text.contains("jmp"))
+                else {
+                    continue;
+                };
+                let stub = match index.checked_sub(1).map(|index| &instructions[index]) {
+                    Some(previous) if previous.text == "endbr64" => previous.address,
+                    _ => instruction.address,
+                };
+                stubs.entry(slot).or_insert(stub);
+            }
+        }
+        Ok(stubs)
+    }
+}
@@ -0,0 +1,805 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+use procfs::process::{MMPermissions, MMapPath, MemoryMap};
+
+use crate::{ElfCollection, Process, Result, SdbError, Target, types::VirtAddr};
+
+// glibc's x86-64 malloc, the arena layout is glibc 2.27's and later's
+const SIZE_SZ: u64 = 8;
+const MALLOC_ALIGNMENT: u64 = 16;
+const MIN_CHUNK_SIZE: u64 = 32;
+const PREV_INUSE: u64 = 1;
+const IS_MMAPPED: u64 = 2;
+const NON_MAIN_ARENA: u64 = 4;
+const SIZE_BITS: u64 = PREV_INUSE | IS_MMAPPED | NON_MAIN_ARENA;
+
+// a thread arena's heaps are aligned to this, with a heap_info at the start that grew in
+// glibc 2.35, the arena's right after it in its first heap
+const HEAP_MAX_SIZE: u64 = 64 << 20;
+const HEAP_INFO_FIELDS: usize = 0x18;
+
+// offsets into struct malloc_state
+const ARENA_FASTBINS: u64 = 0x10;
+const ARENA_TOP: u64 = 0x60;
+const ARENA_BINS: u64 = 0x70;
+const ARENA_NEXT: u64 = 0x870;
+const ARENA_SYSTEM_MEM: u64 = 0x888;
+const ARENA_SIZE: u64 = 0x898;
+const NFASTBINS: u64 = 10;
+const NBINS: u64 = 128;
+const NSMALLBINS: u64 = 64;
+
+// the tcache_perthread_struct chunk, with u16 counts since glibc 2.30 and chars before
+const TCACHE_CHUNK_SIZE: u64 = 0x290;
+const OLD_TCACHE_CHUNK_SIZE: u64 = 0x250;
+const TCACHE_MAX_BINS: u64 = 64;
+
+// bounds following a corrupt arena list
+const MAX_ARENAS: usize = 1024;
+
+// process_vm_readv takes so many pages at a time
+const READ_CHUNK: u64 = 1 << 20;
+
+/// Which of an arena's free lists a chunk is in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HeapBin {
+    Tcache,
+    Fast,
+    Unsorted,
+    Small,
+    Large,
+}
+
+impl fmt::Display for HeapBin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Tcache => "tcache",
+            Self::Fast => "fast",
+            Self::Unsorted => "unsorted",
+            Self::Small => "small",
+            Self::Large => "large",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkState {
+    InUse,
+    /// None when the next chunk's flag says it's free but it isn't in a bin
+    Free(Option<HeapBin>),
+    /// The rest of the heap, that chunks are split from
+    Top,
+}
+
+impl fmt::Display for ChunkState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InUse => f.write_str("in use"),
+            Self::Free(Some(bin)) => write!(f, "free ({})", bin),
+            Self::Free(None) => f.write_str("free"),
+            Self::Top => f.write_str("top"),
+        }
+    }
+}
+
+/// A malloc chunk, by its header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeapChunk {
+    /// The header's, malloc returns the address 16 bytes on
+    pub address: VirtAddr,
+    pub size: u64,
+    /// The size field's low bits, PREV_INUSE, IS_MMAPPED, and NON_MAIN_ARENA
+    pub flags: u64,
+    pub state: ChunkState,
+}
+
+impl HeapChunk {
+    pub fn user_address(&self) -> VirtAddr {
+        self.address + (2 * SIZE_SZ) as i64
+    }
+
+    pub fn prev_inuse(&self) -> bool {
+        self.flags & PREV_INUSE != 0
+    }
+
+    pub fn is_mmapped(&self) -> bool {
+        self.flags & IS_MMAPPED != 0
+    }
+
+    pub fn non_main_arena(&self) -> bool {
+        self.flags & NON_MAIN_ARENA != 0
+    }
+}
+
+/// One of malloc's arenas and the chunks in its heaps, lowest first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arena {
+    /// None when the main arena couldn't be found, then nothing's known to be in a bin
+    pub address: Option<VirtAddr>,
+    pub main: bool,
+    /// How much memory the arena's got from the system
+    pub system_mem: u64,
+    pub chunks: Vec<HeapChunk>,
+}
+
+/// Something wrong with a chunk's or a free list's metadata
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeapProblem {
+    pub address: VirtAddr,
+    pub message: String,
+}
+
+impl fmt::Display for HeapProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.address, self.message)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChunkTotals {
+    pub count: usize,
+    pub bytes: u64,
+}
+
+impl ChunkTotals {
+    fn add(&mut self, size: u64) {
+        self.count += 1;
+        self.bytes += size;
+    }
+}
+
+/// What an arena's chunks add up to
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeapStats {
+    pub in_use: ChunkTotals,
+    pub free: ChunkTotals,
+    /// The free chunks by bin, None for the ones in no bin
+    pub bins: BTreeMap<Option<HeapBin>, ChunkTotals>,
+    pub top: u64,
+    pub largest_free: u64,
+    /// How many chunks of each size are in use, where a leak shows up
+    pub in_use_by_size: BTreeMap<u64, usize>,
+}
+
+impl Arena {
+    pub fn stats(&self) -> HeapStats {
+        let mut stats = HeapStats::default();
+        for chunk in &self.chunks {
+            match chunk.state {
+                ChunkState::InUse => {
+                    stats.in_use.add(chunk.size);
+                    *stats.in_use_by_size.entry(chunk.size).or_default() += 1;
+                }
+                ChunkState::Free(bin) => {
+                    stats.free.add(chunk.size);
+                    stats.bins.entry(bin).or_default().add(chunk.size);
+                    stats.largest_free = stats.largest_free.max(chunk.size);
+                }
+                ChunkState::Top => stats.top += chunk.size,
+            }
+        }
+        stats
+    }
+}
+
+/// glibc malloc's arenas, read from the process's memory
+///
+/// Chunks mmapped on their own aren't in any arena's heaps so they aren't listed, and a
+/// thread's tcache is only found when it's the first chunk in its arena's heap
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeapInfo {
+    pub arenas: Vec<Arena>,
+    pub problems: Vec<HeapProblem>,
+}
+
+// a heap's memory, read in one go
+struct Region {
+    start: u64,
+    bytes: Vec<u8>,
+}
+
+impl Region {
+    fn end(&self) -> u64 {
+        self.start + self.bytes.len() as u64
+    }
+
+    fn read(&self, address: u64) -> Option<u64> {
+        let offset = address.checked_sub(self.start)? as usize;
+        let bytes = self.bytes.get(offset..offset + 8)?;
+        Some(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn read_u16(&self, address: u64) -> Option<u16> {
+        let offset = address.checked_sub(self.start)? as usize;
+        let bytes = self.bytes.get(offset..offset + 2)?;
+        Some(u16::from_le_bytes(bytes.try_into().unwrap()))
+    }
+}
+
+// a chunk as it's walked, before what its neighbours and the bins say is known
+struct RawChunk {
+    address: u64,
+    size: u64,
+    flags: u64,
+    top: bool,
+}
+
+// one heap's chunks, for an arena's first heap the chunk its tcache would be is first
+struct HeapChunks {
+    chunks: Vec<RawChunk>,
+    first_heap: bool,
+    main: bool,
+}
+
+fn align_up(address: u64) -> u64 {
+    address.next_multiple_of(MALLOC_ALIGNMENT)
+}
+
+fn field(arena: &[u8], offset: u64) -> u64 {
+    let offset = offset as usize;
+    u64::from_le_bytes(arena[offset..offset + 8].try_into().unwrap())
+}
+
+struct HeapReader<'a> {
+    process: &'a Process,
+    modules: &'a ElfCollection,
+    regions: Vec<Region>,
+    problems: Vec<HeapProblem>,
+}
+
+impl HeapReader<'_> {
+    fn problem(&mut self, address: u64, message: String) {
+        self.problems.push(HeapProblem {
+            address: VirtAddr::new(address),
+            message,
+        });
+    }
+
+    fn read_region(&mut self, start: u64, end: u64) -> Result<usize> {
+        let mut bytes = Vec::new();
+        let mut address = start;
+        while address < end {
+            let amount = (end - address).min(READ_CHUNK) as usize;
+            let memory = self.process.read_memory(VirtAddr::new(address), amount)?;
+            let short = memory.len() < amount;
+            bytes.extend(memory);
+            if short {
+                break;
+            }
+            address += amount as u64;
+        }
+        self.regions.push(Region { start, bytes });
+        Ok(self.regions.len() - 1)
+    }
+
+    fn read(&self, address: u64) -> Option<u64> {
+        self.regions.iter().find_map(|region| region.read(address))
+    }
+
+    fn read_u16(&self, address: u64) -> Option<u16> {
+        self.regions
+            .iter()
+            .find_map(|region| region.read_u16(address))
+    }
+
+    // somewhere a chunk's header and list pointers could be
+    fn is_chunk(&self, address: u64) -> bool {
+        address.is_multiple_of(MALLOC_ALIGNMENT) && self.read(address + 3 * SIZE_SZ).is_some()
+    }
+
+    // glibc 2.32 on mangles a singly linked list's pointer with where it's stored
+    fn reveal(&self, position: u64, raw: u64, offset: u64) -> Option<u64> {
+        [(position >> 12) ^ raw, raw]
+            .into_iter()
+            .find(|&pointer| pointer == 0 || self.is_chunk(pointer.wrapping_sub(offset)))
+    }
+
+    // the chunks from start up to the top or a fencepost, top is the one reaching the end when
+    // it isn't known
+    fn walk(&mut self, region: usize, start: u64, top: Option<u64>) -> Vec<RawChunk> {
+        let end = self.regions[region].end();
+        let mut chunks: Vec<RawChunk> = Vec::new();
+        let mut address = start;
+        loop {
+            let Some(size_field) = self.regions[region].read(address + SIZE_SZ) else {
+                self.problem(address, "the heap ends without a top chunk".to_owned());
+                break;
+            };
+            let size = size_field & !SIZE_BITS;
+            let flags = size_field & SIZE_BITS;
+
+            // what's left of an old top when the arena moved on to a new heap
+            if size == 2 * SIZE_SZ {
+                break;
+            }
+            if size < MIN_CHUNK_SIZE || !size.is_multiple_of(MALLOC_ALIGNMENT) {
+                self.problem(address, format!("bad size {:#x}", size));
+                break;
+            }
+            let is_top = match top {
+                Some(top) => address == top,
+                None => address + size == end,
+            };
+            if address + size > end {
+                self.problem(
+                    address,
+                    format!(
+                        "size {:#x} runs past the end of the heap at {:#x}",
+                        size, end
+                    ),
+                );
+                break;
+            }
+
+            if flags & IS_MMAPPED != 0 {
+                self.problem(address, "IS_MMAPPED is set in a heap".to_owned());
+            }
+            match chunks.last() {
+                None if flags & PREV_INUSE == 0 => {
+                    self.problem(
+                        address,
+                        "PREV_INUSE isn't set on the heap's first chunk".to_owned(),
+                    );
+                }
+                Some(previous) if flags & PREV_INUSE == 0 => {
+                    let prev_size = self.regions[region].read(address).unwrap_or_default();
+                    if prev_size != previous.size {
+                        let message = format!(
+                            "prev_size {:#x} doesn't match the previous chunk's size {:#x}",
+                            prev_size, previous.size
+                        );
+                        self.problem(address, message);
+                    }
+                }
+                _ => (),
+            }
+
+            chunks.push(RawChunk {
+                address,
+                size,
+                flags,
+                top: is_top,
+            });
+            if is_top {
+                break;
+            }
+            address += size;
+        }
+        chunks
+    }
+
+    // a thread arena's heaps, oldest first, from the one its top is in back through prev
+    fn walk_thread_arena(&mut self, arena: u64, top: u64) -> Vec<HeapChunks> {
+        let header_size = arena & (HEAP_MAX_SIZE - 1);
+        let mut heaps = Vec::new();
+        let mut heap = top & !(HEAP_MAX_SIZE - 1);
+        let mut seen = HashSet::new();
+        while heap != 0 && seen.insert(heap) {
+            let header = match self
+                .process
+                .read_memory(VirtAddr::new(heap), HEAP_INFO_FIELDS)
+            {
+                Ok(header) if header.len() == HEAP_INFO_FIELDS => header,
+                _ => {
+                    self.problem(heap, "the arena's heap can't be read".to_owned());
+                    break;
+                }
+            };
+            let (ar_ptr, prev, size) = (field(&header, 0), field(&header, 8), field(&header, 16));
+            if ar_ptr != arena || size > HEAP_MAX_SIZE {
+                let message = format!("the heap_info doesn't belong to arena {:#x}", arena);
+                self.problem(heap, message);
+                break;
+            }
+            let Ok(region) = self.read_region(heap, heap + size) else {
+                self.problem(heap, "the arena's heap can't be read".to_owned());
+                break;
+            };
+
+            // the arena's own struct is in its first heap, after the heap_info
+            let first_heap = arena > heap && arena < heap + size;
+            let start = match first_heap {
+                true => align_up(arena + ARENA_SIZE),
+                false => align_up(heap + header_size),
+            };
+            let chunks = self.walk(region, start, Some(top));
+            heaps.push(HeapChunks {
+                chunks,
+                first_heap,
+                main: false,
+            });
+            heap = prev;
+        }
+        heaps.reverse();
+        heaps
+    }
+
+    // the arena whose top the main heap ends with, by its symbol if libc's got one
+    fn find_main_arena(&self, maps: &[MemoryMap], top: u64) -> Option<u64> {
+        if let Some((elf, symbol)) = self.modules.find_variable("main_arena") {
+            return Some(elf.symbol_address(symbol).addr());
+        }
+
+        for map in maps.iter().filter(|map| {
+            map.perms
+                .contains(MMPermissions::READ | MMPermissions::WRITE)
+                && matches!(map.pathname, MMapPath::Path(_))
+        }) {
+            let (low, high) = map.address;
+            let Ok(memory) = self
+                .process
+                .read_memory(VirtAddr::new(low), (high - low) as usize)
+            else {
+                continue;
+            };
+            let found = memory
+                .chunks_exact(8)
+                .enumerate()
+                .filter(|(_, word)| u64::from_le_bytes((*word).try_into().unwrap()) == top)
+                .map(|(index, _)| (low + 8 * index as u64).wrapping_sub(ARENA_TOP))
+                .find(|&candidate| self.arena_ring(candidate).is_some());
+            if found.is_some() {
+                return found;
+            }
+        }
+        None
+    }
+
+    // the arenas from this one round through next back to it, the main one's always first
+    fn arena_ring(&self, main: u64) -> Option<Vec<u64>> {
+        let mut arenas = vec![main];
+        let mut arena = main;
+        loop {
+            arena = self
+                .process
+                .read_u64(VirtAddr::new(arena + ARENA_NEXT))
+                .ok()?;
+            if arena == main {
+                return Some(arenas);
+            }
+            if arena == 0 || !arena.is_multiple_of(8) || arenas.len() >= MAX_ARENAS {
+                return None;
+            }
+            arenas.push(arena);
+        }
+    }
+
+    fn add_to_bin(&mut self, binned: &mut HashMap<u64, HeapBin>, chunk: u64, bin: HeapBin) -> bool {
+        match binned.insert(chunk, bin) {
+            Some(other) => {
+                let message = format!("is in both the {} and {} bins", other, bin);
+                self.problem(chunk, message);
+                false
+            }
+            None => true,
+        }
+    }
+
+    // a tcache or fast bin, with each chunk's next pointer at its user address
+    fn follow_single(
+        &mut self,
+        binned: &mut HashMap<u64, HeapBin>,
+        bin: HeapBin,
+        size: u64,
+        head: u64,
+    ) -> usize {
+        // tcache entries point at user addresses rather than chunks
+        let offset = match bin {
+            HeapBin::Tcache => 2 * SIZE_SZ,
+            _ => 0,
+        };
+        let mut count = 0;
+        let mut pointer = head;
+        let mut seen = HashSet::new();
+        while pointer != 0 {
+            let chunk = pointer.wrapping_sub(offset);
+            if !self.is_chunk(chunk) {
+                let message = format!(
+                    "the {} bin for {:#x} chunks has a bad pointer {:#x}",
+                    bin, size, pointer
+                );
+                self.problem(chunk, message);
+                break;
+            }
+            if !seen.insert(chunk) {
+                let message = format!("the {} bin for {:#x} chunks loops back here", bin, size);
+                self.problem(chunk, message);
+                break;
+            }
+            let chunk_size = self.read(chunk + SIZE_SZ).unwrap_or_default() & !SIZE_BITS;
+            if chunk_size != size {
+                let message = format!(
+                    "size {:#x} is in the {} bin for {:#x} chunks",
+                    chunk_size, bin, size
+                );
+                self.problem(chunk, message);
+            }
+            if !self.add_to_bin(binned, chunk, bin) {
+                break;
+            }
+            count += 1;
+
+            let position = chunk + 2 * SIZE_SZ;
+            let raw = self.read(position).unwrap_or_default();
+            match self.reveal(position, raw, offset) {
+                Some(next) => pointer = next,
+                None => {
+                    let message = format!(
+                        "the {} bin for {:#x} chunks has a bad pointer {:#x}",
+                        bin, size, raw
+                    );
+                    self.problem(chunk, message);
+                    break;
+                }
+            }
+        }
+        count
+    }
+
+    // an unsorted, small, or large bin, a circular list through the bin's header in the arena
+    fn follow_double(
+        &mut self,
+        binned: &mut HashMap<u64, HeapBin>,
+        arena: &[u8],
+        arena_address: u64,
+        index: u64,
+    ) {
+        let bin = match index {
+            1 => HeapBin::Unsorted,
+            index if index < NSMALLBINS => HeapBin::Small,
+            _ => HeapBin::Large,
+        };
+        let offset = ARENA_BINS + (index - 1) * 2 * SIZE_SZ;
+        // bin_at, the header's fd and bk line up with a chunk's
+        let header = arena_address + offset - 2 * SIZE_SZ;
+
+        let mut previous = header;
+        let mut chunk = field(arena, offset);
+        let mut seen = HashSet::new();
+        while chunk != header {
+            if !self.is_chunk(chunk) {
+                let message = format!("the {} bin {} has a bad pointer {:#x}", bin, index, chunk);
+                self.problem(previous, message);
+                break;
+            }
+            if !seen.insert(chunk) {
+                let message = format!("the {} bin {} loops back here", bin, index);
+                self.problem(chunk, message);
+                break;
+            }
+            let bk = self.read(chunk + 3 * SIZE_SZ).unwrap_or_default();
+            if bk != previous {
+                let message = format!(
+                    "bk {:#x} in the {} bin {} isn't the chunk before it {:#x}",
+                    bk, bin, index, previous
+                );
+                self.problem(chunk, message);
+            }
+            let size = self.read(chunk + SIZE_SZ).unwrap_or_default() & !SIZE_BITS;
+            if bin == HeapBin::Small && size != index * MALLOC_ALIGNMENT {
+                let message = format!("size {:#x} is in the small bin {}", size, index);
+                self.problem(chunk, message);
+            }
+            if !self.add_to_bin(binned, chunk, bin) {
+                break;
+            }
+            previous = chunk;
+            chunk = self.read(chunk + 2 * SIZE_SZ).unwrap_or_default();
+        }
+    }
+
+    // the bins in the arena's struct
+    fn arena_bins(&mut self, binned: &mut HashMap<u64, HeapBin>, arena_address: u64, arena: &[u8]) {
+        for index in 0..NFASTBINS {
+            let head = field(arena, ARENA_FASTBINS + index * SIZE_SZ);
+            let size = (index + 2) * MALLOC_ALIGNMENT;
+            self.follow_single(binned, HeapBin::Fast, size, head);
+        }
+        for index in 1..NBINS {
+            self.follow_double(binned, arena, arena_address, index);
+        }
+    }
+
+    // the thread's tcache_perthread_struct when it's the first chunk in the heap
+    fn tcache_bins(&mut self, binned: &mut HashMap<u64, HeapBin>, first: &RawChunk) {
+        let (count_size, counts) = match first.size {
+            TCACHE_CHUNK_SIZE => (2, first.address + 2 * SIZE_SZ),
+            OLD_TCACHE_CHUNK_SIZE => (1, first.address + 2 * SIZE_SZ),
+            _ => return,
+        };
+        let entries = counts + count_size * TCACHE_MAX_BINS;
+        for index in 0..TCACHE_MAX_BINS {
+            let expected = match count_size {
+                2 => self.read_u16(counts + 2 * index).unwrap_or_default() as usize,
+                _ => (self.read_u16(counts + index).unwrap_or_default() & 0xff) as usize,
+            };
+            let head = self.read(entries + index * SIZE_SZ).unwrap_or_default();
+            let size = index * MALLOC_ALIGNMENT + MIN_CHUNK_SIZE;
+            let count = self.follow_single(binned, HeapBin::Tcache, size, head);
+            if count != expected {
+                let message = format!(
+                    "the tcache bin for {:#x} chunks counts {} but has {}",
+                    size, expected, count
+                );
+                self.problem(first.address, message);
+            }
+        }
+    }
+
+    // what the bins and each chunk's neighbour say about it
+    fn chunks(
+        &mut self,
+        heaps: Vec<HeapChunks>,
+        binned: &HashMap<u64, HeapBin>,
+        arena_known: bool,
+    ) -> Vec<HeapChunk> {
+        let mut chunks = Vec::new();
+        for heap in heaps {
+            for (index, chunk) in heap.chunks.iter().enumerate() {
+                let free_by_flag = heap
+                    .chunks
+                    .get(index + 1)
+                    .is_some_and(|next| next.flags & PREV_INUSE == 0);
+                let state = match (chunk.top, binned.get(&chunk.address)) {
+                    (true, _) => ChunkState::Top,
+                    (false, Some(&bin @ (HeapBin::Tcache | HeapBin::Fast))) => {
+                        ChunkState::Free(Some(bin))
+                    }
+                    (false, Some(&bin)) => {
+                        if !free_by_flag {
+                            let message = format!("is in the {} bin but marked in use", bin);
+                            self.problem(chunk.address, message);
+                        }
+                        ChunkState::Free(Some(bin))
+                    }
+                    (false, None) if free_by_flag => {
+                        if arena_known {
+                            self.problem(chunk.address, "is free but in no bin".to_owned());
+                        }
+                        ChunkState::Free(None)
+                    }
+                    (false, None) => ChunkState::InUse,
+                };
+
+                // malloc only leaves the flag alone in the headers of chunks it's handed out
+                let handed_out = matches!(
+                    state,
+                    ChunkState::InUse | ChunkState::Free(Some(HeapBin::Tcache | HeapBin::Fast))
+                );
+                if handed_out && (chunk.flags & NON_MAIN_ARENA != 0) == heap.main {
+                    let message = match heap.main {
+                        true => "NON_MAIN_ARENA is set in the main arena's heap",
+                        false => "NON_MAIN_ARENA isn't set in a thread arena's heap",
+                    };
+                    self.problem(chunk.address, message.to_owned());
+                }
+                chunks.push(HeapChunk {
+                    address: VirtAddr::new(chunk.address),
+                    size: chunk.size,
+                    flags: chunk.flags,
+                    state,
+                });
+            }
+        }
+        chunks
+    }
+}
+
+impl Target {
+    /// Walks glibc malloc's arenas and their chunks, checking their metadata on the way
+    ///
+    /// The main arena is found by the `main_arena` symbol, or else by the pointer to the main
+    /// heap's top chunk in a writable mapping when libc's stripped
+    pub fn heap(&self) -> Result<HeapInfo> {
+        let maps = procfs::process::Process::new(self.process.pid.as_raw())?
+            .maps()?
+            .into_iter()
+            .collect::<Vec<_>>();
+        let main_heap = maps
+            .iter()
+            .find(|map| map.pathname == MMapPath::Heap)
+            .ok_or_else(|| {
+                SdbError::Other("There's no heap, nothing's been allocated".to_owned())
+            })?;
+
+        let mut reader = HeapReader {
+            process: &self.process,
+            modules: &self.modules,
+            regions: Vec::new(),
+            problems: Vec::new(),
+        };
+        let (low, high) = main_heap.address;
+        let region = reader.read_region(low, high)?;
+        let main_chunks = reader.walk(region, align_up(low), None);
+        let main_top = main_chunks
+            .iter()
+            .find(|chunk| chunk.top)
+            .map(|chunk| chunk.address);
+
+        let main = main_top.and_then(|top| reader.find_main_arena(&maps, top));
+        let Some(main) = main else {
+            let mut binned = HashMap::new();
+            if let Some(first) = main_chunks.first() {
+                reader.tcache_bins(&mut binned, first);
+            }
+            let heaps = vec![HeapChunks {
+                chunks: main_chunks,
+                first_heap: true,
+                main: true,
+            }];
+            let chunks = reader.chunks(heaps, &binned, false);
+            return Ok(HeapInfo {
+                arenas: vec![Arena {
+                    address: None,
+                    main: true,
+                    system_mem: high - low,
+                    chunks,
+                }],
+                problems: reader.problems,
+            });
+        };
+
+        // every heap's read before following the bins, a tcache can have any arena's chunks
+        let mut main_chunks = Some(main_chunks);
+        let mut arenas = Vec::new();
+        for address in reader.arena_ring(main).unwrap_or_else(|| vec![main]) {
+            let arena = match self
+                .process
+                .read_memory(VirtAddr::new(address), ARENA_SIZE as usize)
+            {
+                Ok(arena) if arena.len() == ARENA_SIZE as usize => arena,
+                _ => {
+                    reader.problem(address, "the arena can't be read".to_owned());
+                    continue;
+                }
+            };
+            let top = field(&arena, ARENA_TOP);
+            let heaps = match address == main {
+                true => {
+                    if main_top != Some(top) {
+                        let message = format!(
+                            "the arena's top {:#x} isn't the chunk the heap ends with",
+                            top
+                        );
+                        reader.problem(address, message);
+                    }
+                    vec![HeapChunks {
+                        chunks: main_chunks.take().unwrap_or_default(),
+                        first_heap: true,
+                        main: true,
+                    }]
+                }
+                false => reader.walk_thread_arena(address, top),
+            };
+            arenas.push((address, arena, heaps));
+        }
+
+        let mut binned = HashMap::new();
+        for (address, arena, heaps) in &arenas {
+            let first = heaps
+                .iter()
+                .find(|heap| heap.first_heap)
+                .and_then(|heap| heap.chunks.first());
+            reader.arena_bins(&mut binned, *address, arena);
+            // a thread that's exited has freed its tcache
+            if let Some(first) = first.filter(|first| !binned.contains_key(&first.address)) {
+                reader.tcache_bins(&mut binned, first);
+            }
+        }
+
+        let arenas = arenas
+            .into_iter()
+            .map(|(address, arena, heaps)| Arena {
+                address: Some(VirtAddr::new(address)),
+                main: address == main,
+                system_mem: field(&arena, ARENA_SYSTEM_MEM),
+                chunks: reader.chunks(heaps, &binned, true),
+            })
+            .collect();
+        Ok(HeapInfo {
+            arenas,
+            problems: reader.problems,
+        })
+    }
+}
@@ -0,0 +1,253 @@
+use crate::{
+    Process, Result, SdbError, Target,
+    stoppoint_collection::{Stoppoint, StoppointId, StoppointKind},
+    types::VirtAddr,
+};
+
+// jmp qword ptr [rip+0], followed by the absolute target address
+const JMP_ABSOLUTE: [u8; 6] = [0xff, 0x25, 0x00, 0x00, 0x00, 0x00];
+
+// ret
+const RET: [u8; 1] = [0xc3];
+
+// xor eax, eax; ret
+const RET0: [u8; 3] = [0x31, 0xc0, 0xc3];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HookReplacement {
+    // jump to another address, leaving the arguments in place
+    Jump(VirtAddr),
+    // return immediately
+    Nop,
+    // return 0 immediately
+    ReturnZero,
+}
+
+impl HookReplacement {
+    fn code(&self) -> Vec<u8> {
+        match self {
+            Self::Jump(address) => {
+                let mut code = JMP_ABSOLUTE.to_vec();
+                code.extend_from_slice(&address.addr().to_le_bytes());
+                code
+            }
+            Self::Nop => RET.to_vec(),
+            Self::ReturnZero => RET0.to_vec(),
+        }
+    }
+}
+
+impl std::fmt::Display for HookReplacement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Jump(address) => write!(f, "jump to {}", address),
+            Self::Nop => write!(f, "nop"),
+            Self::ReturnZero => write!(f, "ret0"),
+        }
+    }
+}
+
+impl std::str::FromStr for HookReplacement {
+    type Err = SdbError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "nop" => Ok(Self::Nop),
+            "ret0" => Ok(Self::ReturnZero),
+            _ => {
+                let address = s.strip_prefix("0x").unwrap_or(s);
+                u64::from_str_radix(address, 16)
+                    .map(|address| Self::Jump(VirtAddr::new(address)))
+                    .map_err(|_| SdbError::Other(format!("Invalid hook replacement '{}'", s)))
+            }
+        }
+    }
+}
+
+// rewrites the entry of a function so that it never runs
+#[derive(Debug)]
+pub struct FunctionHook {
+    id: StoppointId,
+    function: String,
+    address: VirtAddr,
+    replacement: HookReplacement,
+    is_enabled: bool,
+    saved_data: Vec<u8>,
+}
+
+impl Stoppoint for FunctionHook {
+    #[inline]
+    fn id(&self) -> StoppointId {
+        self.id
+    }
+
+    #[inline]
+    fn address(&self) -> VirtAddr {
+        self.address
+    }
+
+    #[inline]
+    fn is_enabled(&self) -> bool {
+        self.is_enabled
+    }
+
+    #[inline]
+    fn kind(&self) -> StoppointKind {
+        StoppointKind::Hook
+    }
+
+    // the patched function never traps, so there's nothing to count
+    #[inline]
+    fn hit_count(&self) -> u64 {
+        0
+    }
+
+    fn spec(&self) -> String {
+        format!("{}, {}", self.function, self.replacement)
+    }
+}
+
+impl FunctionHook {
+    #[inline]
+    pub fn function(&self) -> &str {
+        &self.function
+    }
+
+    #[inline]
+    pub fn replacement(&self) -> HookReplacement {
+        self.replacement
+    }
+
+    // what the patch was written over
+    #[inline]
+    pub(crate) fn saved_data(&self) -> &[u8] {
+        &self.saved_data
+    }
+
+    // the patch went away with the memory it was in
+    #[inline]
+    pub(crate) fn forget(&mut self) {
+        self.is_enabled = false;
+    }
+}
+
+impl Process {
+    #[inline]
+    pub fn function_hooks(&self) -> &crate::StoppointCollection<FunctionHook> {
+        &self.function_hooks
+    }
+
+    // the patch is written over the function's entry, size is the symbol's, 0 if it isn't known
+    pub(crate) fn create_function_hook_at(
+        &mut self,
+        function: String,
+        address: VirtAddr,
+        size: u64,
+        replacement: HookReplacement,
+    ) -> Result<StoppointId> {
+        if self.function_hooks.contains_address(address) {
+            return Err(SdbError::Stoppoint(format!(
+                "Function {} is already hooked",
+                function
+            )));
+        }
+
+        let code_size = replacement.code().len();
+        if size != 0 && (size as usize) < code_size {
+            return Err(SdbError::Other(format!(
+                "Function {} is too small to hook with {}",
+                function, replacement
+            )));
+        }
+
+        let id = self.next_stoppoint_id;
+        self.next_stoppoint_id += 1;
+
+        self.function_hooks.push(FunctionHook {
+            id,
+            function,
+            address,
+            replacement,
+            is_enabled: false,
+            saved_data: Vec::default(),
+        });
+        self.enable_function_hook(id)?;
+
+        Ok(id)
+    }
+
+    pub fn enable_function_hook(&mut self, id: StoppointId) -> Result<()> {
+        let hook = self.function_hooks.get_by_id(id)?;
+        if hook.is_enabled {
+            return Ok(());
+        }
+
+        let address = hook.address;
+        let code = hook.replacement.code();
+        let high = address + code.len() as i64;
+
+        // the patch would clobber (or be clobbered by) any int3s in the way
+        if self
+            .breakpoint_sites
+            .get_in_region(address, high)
+            .next()
+            .is_some()
+        {
+            return Err(SdbError::Stoppoint(format!(
+                "Can't hook {} over an existing breakpoint",
+                hook.function
+            )));
+        }
+
+        let saved_data = self.read_memory(address, code.len())?;
+        self.write_memory(address, &code)?;
+
+        let hook = self.function_hooks.get_by_id_mut(id)?;
+        hook.saved_data = saved_data;
+        hook.is_enabled = true;
+
+        Ok(())
+    }
+
+    pub fn disable_function_hook(&mut self, id: StoppointId) -> Result<()> {
+        let hook = self.function_hooks.get_by_id(id)?;
+        if !hook.is_enabled {
+            return Ok(());
+        }
+
+        self.write_memory(hook.address, &hook.saved_data)?;
+        self.function_hooks.get_by_id_mut(id)?.is_enabled = false;
+
+        Ok(())
+    }
+
+    pub fn remove_function_hook(&mut self, id: StoppointId) -> Result<()> {
+        self.disable_function_hook(id)?;
+        self.function_hooks.remove_by_id(id)?;
+        Ok(())
+    }
+}
+
+impl Target {
+    pub fn create_function_hook(
+        &mut self,
+        function: impl Into<String>,
+        replacement: HookReplacement,
+    ) -> Result<StoppointId> {
+        let function = function.into();
+
+        // the function may be in a library that was loaded since we last looked
+        if self.modules.find_function(&function).is_none() {
+            self.refresh_modules()?;
+        }
+
+        let (address, size) = self
+            .modules
+            .find_function(&function)
+            .map(|(elf, symbol)| (elf.symbol_address(symbol), symbol.size))
+            .ok_or_else(|| SdbError::Other(format!("Function {} not found", function)))?;
+
+        self.process
+            .create_function_hook_at(function, address, size, replacement)
+    }
+}
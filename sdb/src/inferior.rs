@@ -0,0 +1,214 @@
+use num_traits::ToPrimitive;
+
+use crate::{
+    Auxv, DwarfFunction, Elf, ElfCollection, LineEntry, Radix, RegisterId, RegisterValue, Result,
+    SdbError, VariablePath, VariableValue, types::VirtAddr,
+};
+
+/// Where and in what a backtrace frame is, innermost first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub pc: VirtAddr,
+    pub function: Option<String>,
+    pub location: Option<SourceLocation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: std::path::PathBuf,
+    pub line: u64,
+    pub column: u64,
+}
+
+impl std::fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.file.display(), self.line)
+    }
+}
+
+// stops runaway frame pointer chains
+const MAX_FRAMES: usize = 256;
+
+// strings are read a page at a time, so one that ends before an unmapped page still reads
+const PAGE_SIZE: u64 = 0x1000;
+
+// what the typed reads are made of, a read that comes up short is an error
+fn read_array<const N: usize>(
+    inferior: &(impl Inferior + ?Sized),
+    address: VirtAddr,
+) -> Result<[u8; N]> {
+    inferior
+        .read_memory(address, N)?
+        .try_into()
+        .map_err(|_| SdbError::Other(format!("Failed to read memory at {}", address)))
+}
+
+// shared with the process, which reads strings without its modules
+pub(crate) fn read_c_string(
+    read_memory: impl Fn(VirtAddr, usize) -> Result<Vec<u8>>,
+    mut address: VirtAddr,
+    max_len: usize,
+) -> Result<Vec<u8>> {
+    let mut string = Vec::new();
+    while string.len() < max_len {
+        let to_page_end = PAGE_SIZE - address.addr() % PAGE_SIZE;
+        let amount = to_page_end.min((max_len - string.len()) as u64) as usize;
+        let chunk = read_memory(address, amount)?;
+        if let Some(end) = chunk.iter().position(|&byte| byte == 0) {
+            string.extend_from_slice(&chunk[..end]);
+            break;
+        }
+        string.extend_from_slice(&chunk);
+        address += amount as i64;
+    }
+    Ok(string)
+}
+
+/// Read access to a program's registers, memory and modules
+///
+/// Implemented by live processes and core dumps alike,
+/// everything built on top only ever looks
+pub trait Inferior {
+    fn read_register_by_id(&self, id: RegisterId) -> Result<RegisterValue>;
+
+    /// Memory as the program sees it, without the int3s breakpoints have written over it
+    fn read_memory(&self, address: VirtAddr, amount: usize) -> Result<Vec<u8>>;
+
+    fn modules(&self) -> &ElfCollection;
+
+    /// The auxiliary vector the kernel started the program with
+    fn auxv(&self) -> Result<Auxv>;
+
+    fn get_pc(&self) -> Result<VirtAddr> {
+        let pc = self.read_register_by_id(RegisterId::rip)?;
+        Ok(VirtAddr::new(pc.to_u64().unwrap()))
+    }
+
+    /// Reads up to the NUL, a page at a time so a string near the end of a mapping still reads
+    ///
+    /// The NUL's left off, and max_len bytes come back if there isn't one in them
+    fn read_c_string(&self, address: VirtAddr, max_len: usize) -> Result<Vec<u8>> {
+        read_c_string(
+            |address, amount| self.read_memory(address, amount),
+            address,
+            max_len,
+        )
+    }
+
+    fn read_u8(&self, address: VirtAddr) -> Result<u8> {
+        read_array(self, address).map(u8::from_ne_bytes)
+    }
+
+    fn read_u16(&self, address: VirtAddr) -> Result<u16> {
+        read_array(self, address).map(u16::from_ne_bytes)
+    }
+
+    fn read_u32(&self, address: VirtAddr) -> Result<u32> {
+        read_array(self, address).map(u32::from_ne_bytes)
+    }
+
+    fn read_u64(&self, address: VirtAddr) -> Result<u64> {
+        read_array(self, address).map(u64::from_ne_bytes)
+    }
+
+    fn read_i32(&self, address: VirtAddr) -> Result<i32> {
+        read_array(self, address).map(i32::from_ne_bytes)
+    }
+
+    fn read_i64(&self, address: VirtAddr) -> Result<i64> {
+        read_array(self, address).map(i64::from_ne_bytes)
+    }
+
+    fn read_f32(&self, address: VirtAddr) -> Result<f32> {
+        read_array(self, address).map(f32::from_ne_bytes)
+    }
+
+    fn read_f64(&self, address: VirtAddr) -> Result<f64> {
+        read_array(self, address).map(f64::from_ne_bytes)
+    }
+
+    /// A parameter or local of the function the pc is in, or a global, or part of one
+    fn read_variable(&self, path: &VariablePath, radix: Radix) -> Result<VariableValue> {
+        crate::variables::read_variable(self, path, radix)
+    }
+
+    fn line_entry_at(&self, address: VirtAddr) -> Option<&LineEntry> {
+        let elf = self.modules().get_elf_containing_address(address)?;
+        elf.dwarf()?
+            .line_entry_at_address(elf.file_address(address)?)
+    }
+
+    fn function_at(&self, address: VirtAddr) -> Option<(&Elf, &DwarfFunction)> {
+        let elf = self.modules().get_elf_containing_address(address)?;
+        let function = elf
+            .dwarf()?
+            .function_containing_address(elf.file_address(address)?)?;
+        Some((elf, function))
+    }
+
+    fn function_name_at(&self, address: VirtAddr) -> Option<String> {
+        let elf = self.modules().get_elf_containing_address(address)?;
+
+        // prefer debug info, symbols don't cover everything
+        let function = elf
+            .dwarf()
+            .and_then(|dwarf| dwarf.function_containing_address(elf.file_address(address)?));
+        if let Some(function) = function {
+            return Some(function.name.clone());
+        }
+
+        elf.symbol_containing_address(address)
+            .map(|symbol| symbol.demangled_name.clone())
+    }
+
+    fn source_location_at(&self, address: VirtAddr) -> Option<SourceLocation> {
+        let entry = self.line_entry_at(address)?;
+        Some(SourceLocation {
+            file: entry.file.clone(),
+            line: entry.line,
+            column: entry.column,
+        })
+    }
+
+    /// Walks the frame pointer chain from the current pc
+    ///
+    /// Code built without frame pointers ends the backtrace early
+    fn backtrace(&self) -> Result<Vec<Frame>> {
+        let frame = |pc: VirtAddr| Frame {
+            pc,
+            function: self.function_name_at(pc),
+            location: self.source_location_at(pc),
+        };
+
+        let mut frames = vec![frame(self.get_pc()?)];
+        let mut frame_pointer = self.read_register_by_id(RegisterId::rbp)?.to_u64().unwrap();
+        while frame_pointer != 0 && frames.len() < MAX_FRAMES {
+            let Ok(return_address) = self.read_u64(VirtAddr::new(frame_pointer) + 8) else {
+                break;
+            };
+            let Ok(next) = self.read_u64(VirtAddr::new(frame_pointer)) else {
+                break;
+            };
+
+            // the stack grows down so callers' frames are always higher up
+            let return_address = VirtAddr::new(return_address);
+            if next <= frame_pointer
+                || self
+                    .modules()
+                    .get_elf_containing_address(return_address)
+                    .is_none()
+            {
+                break;
+            }
+
+            // report the call rather than the instruction after it
+            let mut caller = frame(return_address - 1);
+            caller.pc = return_address;
+            frames.push(caller);
+
+            frame_pointer = next;
+        }
+
+        Ok(frames)
+    }
+}
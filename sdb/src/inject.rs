@@ -0,0 +1,185 @@
+use nix::{
+    libc,
+    sys::{ptrace, signal, wait},
+    unistd::Pid,
+};
+use procfs::process::{MMPermissions, MMapPath};
+
+use crate::{Process, ProcessState, Result, SdbError, threads, types::VirtAddr};
+
+// syscall
+const SYSCALL_INSTRUCTION: [u8; 2] = [0x0f, 0x05];
+
+// where the syscall runs from, and what to put back after
+#[derive(Debug)]
+enum SyscallSite {
+    Gadget(VirtAddr),
+    Injected { address: VirtAddr, saved: i64 },
+}
+
+impl Process {
+    fn is_syscall_gadget(&self, tid: Pid, address: VirtAddr) -> bool {
+        crate::read_process_memory(tid, address, SYSCALL_INSTRUCTION.len())
+            .is_ok_and(|code| code == SYSCALL_INSTRUCTION)
+    }
+
+    // the vdso always has one, so it's looked at first
+    fn find_syscall_gadget(&self) -> Result<Option<VirtAddr>> {
+        let mut maps = procfs::process::Process::new(self.pid.as_raw())?
+            .maps()?
+            .into_iter()
+            .filter(|map| {
+                map.perms
+                    .contains(MMPermissions::READ | MMPermissions::EXECUTE)
+                    && map.pathname != MMapPath::Vsyscall
+            })
+            .collect::<Vec<_>>();
+        maps.sort_by_key(|map| map.pathname != MMapPath::Vdso);
+
+        for map in maps {
+            let (low, high) = map.address;
+            let Ok(code) = self.read_memory(VirtAddr::new(low), (high - low) as usize) else {
+                continue;
+            };
+
+            // reading skips breakpoints, so an int3 never looks like half a syscall
+            if let Some(offset) = code
+                .windows(SYSCALL_INSTRUCTION.len())
+                .position(|window| window == SYSCALL_INSTRUCTION)
+            {
+                return Ok(Some(VirtAddr::new(low + offset as u64)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn syscall_site(&mut self, tid: Pid, pc: VirtAddr) -> Result<SyscallSite> {
+        // the gadget's checked every time in case it's been unmapped or had a breakpoint put on it
+        if let Some(gadget) = self.syscall_gadget
+            && self.is_syscall_gadget(tid, gadget)
+        {
+            return Ok(SyscallSite::Gadget(gadget));
+        }
+
+        self.syscall_gadget = self.find_syscall_gadget()?;
+        if let Some(gadget) = self.syscall_gadget {
+            return Ok(SyscallSite::Gadget(gadget));
+        }
+
+        // whole words through ptrace, the pc may not be readable otherwise
+        let address = pc.addr() as ptrace::AddressType;
+        let saved = ptrace::read(tid, address).map_err(SdbError::ptrace_at("peekdata", tid, pc))?;
+        let mut code = saved.to_ne_bytes();
+        code[..SYSCALL_INSTRUCTION.len()].copy_from_slice(&SYSCALL_INSTRUCTION);
+        ptrace::write(tid, address, i64::from_ne_bytes(code))
+            .map_err(SdbError::ptrace_at("pokedata", tid, pc))?;
+
+        Ok(SyscallSite::Injected { address: pc, saved })
+    }
+
+    /// Makes the current thread run a syscall, leaving it as it was found
+    ///
+    /// The syscall runs from a syscall instruction already in the process if there is one,
+    /// otherwise one is written over the pc for the duration. Errors are returned as -errno
+    pub fn inject_syscall(&mut self, number: i64, args: &[u64]) -> Result<i64> {
+        if self.get_state() != ProcessState::Stopped {
+            return Err(SdbError::Other(
+                "The process has to be stopped to inject a syscall".to_owned(),
+            ));
+        }
+        if args.len() > self.syscall_abi().args.len() {
+            return Err(SdbError::Other(format!(
+                "Syscalls take at most {} arguments",
+                self.syscall_abi().args.len()
+            )));
+        }
+
+        let tid = self.current_thread;
+        let (ret, child) = self.inject_syscall_on(tid, number, args)?;
+        // nothing's expecting a child, it's let go
+        if let Some(child) = child {
+            self.release(&[child])?;
+        }
+        Ok(ret)
+    }
+
+    /// Runs a syscall on a thread, which doesn't have to be one of the process's,
+    /// a fork's child comes back with the result, stopped and left as the thread was found
+    pub(crate) fn inject_syscall_on(
+        &mut self,
+        tid: Pid,
+        number: i64,
+        args: &[u64],
+    ) -> Result<(i64, Option<Pid>)> {
+        let saved = ptrace::getregs(tid).map_err(SdbError::ptrace("getregs", tid))?;
+        let site = self.syscall_site(tid, VirtAddr::new(saved.rip))?;
+
+        let mut regs = saved;
+        regs.rip = match site {
+            SyscallSite::Gadget(address) | SyscallSite::Injected { address, .. } => address.addr(),
+        };
+        regs.rax = number as u64;
+        // stops the kernel restarting whatever syscall the thread was stopped in
+        regs.orig_rax = u64::MAX;
+        let arg_registers = [
+            &mut regs.rdi,
+            &mut regs.rsi,
+            &mut regs.rdx,
+            &mut regs.r10,
+            &mut regs.r8,
+            &mut regs.r9,
+        ];
+        for (register, arg) in arg_registers.into_iter().zip(args) {
+            *register = *arg;
+        }
+
+        let mut child = None;
+        let ret = ptrace::setregs(tid, regs)
+            .map_err(SdbError::ptrace("setregs", tid))
+            .and_then(|_| self.step_over_syscall(tid, number, &mut child));
+
+        // the child has a copy of everything that has to be put back
+        for tid in std::iter::once(tid).chain(child) {
+            if let SyscallSite::Injected { address, saved } = site {
+                ptrace::write(tid, address.addr() as ptrace::AddressType, saved)
+                    .map_err(SdbError::ptrace_at("pokedata", tid, address))?;
+            }
+            ptrace::setregs(tid, saved).map_err(SdbError::ptrace("setregs", tid))?;
+        }
+
+        ret.map(|ret| (ret, child))
+    }
+
+    // a fork stops partway through with the child it made, and signals that were waiting are
+    // held back until the syscall's done, like a checkpoint's SIGCHLD for a copy that was killed
+    fn step_over_syscall(&self, tid: Pid, number: i64, child: &mut Option<Pid>) -> Result<i64> {
+        let mut held = Vec::new();
+        let ret = loop {
+            ptrace::step(tid, None).map_err(SdbError::ptrace("singlestep", tid))?;
+            match threads::wait_for_thread(tid)? {
+                wait::WaitStatus::Stopped(_, signal::Signal::SIGTRAP) => {
+                    let regs = ptrace::getregs(tid).map_err(SdbError::ptrace("getregs", tid))?;
+                    break regs.rax as i64;
+                }
+                wait::WaitStatus::PtraceEvent(_, _, libc::PTRACE_EVENT_FORK) => {
+                    *child = Some(self.new_child(tid)?);
+                }
+                wait::WaitStatus::Stopped(_, signal) => held.push(signal),
+                status => {
+                    return Err(SdbError::Other(format!(
+                        "Syscall {} was interrupted: {:?}",
+                        number, status
+                    )));
+                }
+            }
+        };
+
+        for signal in held {
+            signal::kill(tid, signal).map_err(|err| {
+                SdbError::Other(format!("Couldn't send {} back: {}", signal, err))
+            })?;
+        }
+        Ok(ret)
+    }
+}
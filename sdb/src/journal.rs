@@ -0,0 +1,213 @@
+use nix::unistd::Pid;
+
+use crate::{
+    Process, RegisterId, RegisterValue, Result, WriteAssist, register_info::register_info_by_id,
+    types::VirtAddr,
+};
+
+/// A change made to the process on the user's behalf, and what it takes to put it back
+#[derive(Debug)]
+pub enum Mutation {
+    Memory {
+        address: VirtAddr,
+        original: Vec<u8>,
+        written: Vec<u8>,
+        // read-only pages have to be made writable again to put it back
+        assisted: bool,
+    },
+    Registers {
+        thread: Pid,
+        // in the order they were written
+        original: Vec<(RegisterId, RegisterValue)>,
+    },
+    /// Whatever a syscall did can't be taken back
+    Syscall {
+        thread: Pid,
+        number: i64,
+        args: Vec<u64>,
+        result: i64,
+    },
+}
+
+#[derive(Debug)]
+pub struct JournalEntry {
+    pub id: usize,
+    pub mutation: Mutation,
+}
+
+impl JournalEntry {
+    #[inline]
+    pub fn is_reversible(&self) -> bool {
+        !matches!(self.mutation, Mutation::Syscall { .. })
+    }
+}
+
+impl std::fmt::Display for JournalEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.id)?;
+        match &self.mutation {
+            Mutation::Memory {
+                address, written, ..
+            } => write!(f, "wrote {} bytes at {}", written.len(), address),
+            Mutation::Registers { thread, original } => write!(
+                f,
+                "wrote {} on thread {}",
+                original
+                    .iter()
+                    .map(|(id, _)| register_info_by_id(*id).name())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                thread
+            ),
+            Mutation::Syscall {
+                thread,
+                number,
+                args,
+                result,
+            } => write!(
+                f,
+                "syscall {}({}) on thread {} returned {}",
+                number,
+                args.iter()
+                    .map(|arg| format!("{:#x}", arg))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                thread,
+                result
+            ),
+        }
+    }
+}
+
+// oldest first, ids aren't reused after an undo
+#[derive(Debug, Default)]
+pub(crate) struct Journal {
+    entries: Vec<JournalEntry>,
+    next_id: usize,
+}
+
+impl Journal {
+    fn record(&mut self, mutation: Mutation) {
+        self.next_id += 1;
+        self.entries.push(JournalEntry {
+            id: self.next_id,
+            mutation,
+        });
+    }
+}
+
+impl Process {
+    /// What's been changed with the journaled writes, oldest first
+    #[inline]
+    pub fn journal(&self) -> &[JournalEntry] {
+        &self.journal.entries
+    }
+
+    /// Writes memory so it can be undone later, assisted makes read-only pages writable for it
+    pub fn write_memory_journaled(
+        &mut self,
+        address: VirtAddr,
+        data: &[u8],
+        assisted: bool,
+    ) -> Result<WriteAssist> {
+        let original = self.read_memory(address, data.len())?;
+        let assist = match assisted {
+            true => self.write_memory_assisted(address, data)?,
+            false => {
+                self.write_memory(address, data)?;
+                WriteAssist::None
+            }
+        };
+
+        self.journal.record(Mutation::Memory {
+            address,
+            original,
+            written: data.to_vec(),
+            assisted: assist.was_used(),
+        });
+        Ok(assist)
+    }
+
+    /// Writes registers of the current thread together so they can be undone later
+    pub fn write_registers_journaled(
+        &mut self,
+        values: impl IntoIterator<Item = (RegisterId, RegisterValue)>,
+    ) -> Result<()> {
+        let values = values.into_iter().collect::<Vec<_>>();
+        let original = values
+            .iter()
+            .map(|(id, _)| Ok((*id, self.read_register_by_id(*id)?)))
+            .collect::<Result<Vec<_>>>()?;
+        self.write_registers(values)?;
+
+        self.journal.record(Mutation::Registers {
+            thread: self.current_thread,
+            original,
+        });
+        Ok(())
+    }
+
+    /// Injects a syscall and notes it in the journal, though what it did can't be undone
+    pub fn inject_syscall_journaled(&mut self, number: i64, args: &[u64]) -> Result<i64> {
+        let result = self.inject_syscall(number, args)?;
+        self.journal.record(Mutation::Syscall {
+            thread: self.current_thread,
+            number,
+            args: args.to_vec(),
+            result,
+        });
+        Ok(result)
+    }
+
+    fn revert(&mut self, mutation: &Mutation) -> Result<()> {
+        match mutation {
+            Mutation::Memory {
+                address,
+                original,
+                assisted: true,
+                ..
+            } => {
+                self.write_memory_assisted(*address, original)?;
+            }
+            Mutation::Memory {
+                address, original, ..
+            } => self.write_memory(*address, original)?,
+            Mutation::Registers { thread, original } => {
+                let current = self.current_thread;
+                self.set_current_thread(*thread)?;
+                let ret = self.write_registers(original.iter().copied());
+                self.set_current_thread(current)?;
+                ret?;
+            }
+            Mutation::Syscall { .. } => (),
+        }
+
+        Ok(())
+    }
+
+    /// Puts back the latest change, None if there's nothing left to undo
+    ///
+    /// A syscall can't be undone, it's taken off the journal and returned
+    /// so the next undo carries on past it
+    pub fn undo_last(&mut self) -> Result<Option<JournalEntry>> {
+        let Some(entry) = self.journal.entries.pop() else {
+            return Ok(None);
+        };
+
+        // a change that couldn't be put back is still there to undo
+        if let Err(err) = self.revert(&entry.mutation) {
+            self.journal.entries.push(entry);
+            return Err(err);
+        }
+        Ok(Some(entry))
+    }
+
+    /// Puts back every change, newest first, and returns them in the order they were undone
+    pub fn undo_all(&mut self) -> Result<Vec<JournalEntry>> {
+        let mut undone = Vec::new();
+        while let Some(entry) = self.undo_last()? {
+            undone.push(entry);
+        }
+        Ok(undone)
+    }
+}
@@ -0,0 +1,616 @@
+use std::ffi::CString;
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+use std::path::{Path, PathBuf};
+
+use nix::{
+    errno::Errno,
+    fcntl, libc,
+    sys::{
+        ptrace,
+        signal::{self, Signal},
+        stat::Mode,
+        wait,
+    },
+    unistd,
+};
+
+use crate::{
+    OutputCapture, OutputStream, Pipe, Process, ProcessState, Result, SdbError, StopReason,
+    output::CapturedOutput,
+};
+
+/// Where one of a launched process's standard streams goes
+///
+/// Every fd is owned, so whatever happens during the launch they're closed once it's done
+#[derive(Debug, Default)]
+pub enum Redirect {
+    /// The debugger's own
+    #[default]
+    Inherit,
+
+    /// Replaced with this fd
+    Fd(OwnedFd),
+
+    /// Written to both, the process writes to a pipe the debugger copies out of
+    Tee {
+        capture: OwnedFd,
+        passthrough: OwnedFd,
+    },
+
+    /// Split into lines sent to event subscribers as [`crate::DebugEvent::Output`],
+    /// and written to the passthrough if there is one
+    Events { passthrough: Option<OwnedFd> },
+
+    /// Read from the file, or written to it truncated, opened again for every launch
+    File(PathBuf),
+
+    /// Kept by the [`OutputCapture`], and written to the passthrough if there is one,
+    /// also split into lines for event subscribers like [`Redirect::Events`] if `events` is set
+    Capture {
+        capture: OutputCapture,
+        passthrough: Option<OwnedFd>,
+        events: bool,
+    },
+}
+
+impl Redirect {
+    #[inline]
+    pub fn fd(fd: impl Into<OwnedFd>) -> Self {
+        Self::Fd(fd.into())
+    }
+
+    /// Redirects to a duplicate, leaving the caller's fd open
+    pub fn dup(fd: impl AsFd) -> Result<Self> {
+        Ok(Self::Fd(dup(fd)?))
+    }
+
+    #[inline]
+    pub fn tee(capture: impl Into<OwnedFd>, passthrough: impl Into<OwnedFd>) -> Self {
+        Self::Tee {
+            capture: capture.into(),
+            passthrough: passthrough.into(),
+        }
+    }
+
+    /// Captures the stream while still passing it through to the debugger's stdout
+    pub fn tee_stdout(capture: impl Into<OwnedFd>) -> Result<Self> {
+        Ok(Self::tee(capture, dup(std::io::stdout())?))
+    }
+
+    #[inline]
+    pub fn events() -> Self {
+        Self::Events { passthrough: None }
+    }
+
+    /// Sends the stream to event subscribers while still passing it through to a duplicate of `fd`
+    pub fn events_and_dup(fd: impl AsFd) -> Result<Self> {
+        Ok(Self::Events {
+            passthrough: Some(dup(fd)?),
+        })
+    }
+
+    #[inline]
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        Self::File(path.into())
+    }
+
+    /// The same redirect with its own duplicates of the fds
+    pub fn try_clone(&self) -> Result<Self> {
+        Ok(match self {
+            Self::Inherit => Self::Inherit,
+            Self::Fd(fd) => Self::Fd(dup(fd)?),
+            Self::Tee {
+                capture,
+                passthrough,
+            } => Self::Tee {
+                capture: dup(capture)?,
+                passthrough: dup(passthrough)?,
+            },
+            Self::Events { passthrough } => Self::Events {
+                passthrough: passthrough.as_ref().map(dup).transpose()?,
+            },
+            Self::File(path) => Self::File(path.clone()),
+            Self::Capture {
+                capture,
+                passthrough,
+                events,
+            } => Self::Capture {
+                capture: capture.clone(),
+                passthrough: passthrough.as_ref().map(dup).transpose()?,
+                events: *events,
+            },
+        })
+    }
+
+    // a file is opened for the way the stream goes
+    fn open(self, input: bool) -> Result<Self> {
+        let Self::File(path) = self else {
+            return Ok(self);
+        };
+
+        let flags = if input {
+            fcntl::OFlag::O_RDONLY
+        } else {
+            fcntl::OFlag::O_WRONLY | fcntl::OFlag::O_CREAT | fcntl::OFlag::O_TRUNC
+        };
+        let fd = fcntl::open(
+            &path,
+            flags | fcntl::OFlag::O_CLOEXEC,
+            Mode::from_bits_truncate(0o644),
+        )
+        .map_err(|errno| SdbError::Other(format!("Couldn't open {}: {}", path.display(), errno)))?;
+        Ok(Self::Fd(fd))
+    }
+}
+
+// one found on PATH doesn't depend on the working directory
+fn absolute_path(path: String) -> Result<String> {
+    if !path.contains('/') {
+        return Ok(path);
+    }
+    Ok(std::path::absolute(&path)
+        .map_err(|err| SdbError::Other(err.to_string()))?
+        .to_string_lossy()
+        .into_owned())
+}
+
+pub(crate) fn dup(fd: impl AsFd) -> Result<OwnedFd> {
+    fd.as_fd()
+        .try_clone_to_owned()
+        .map_err(|err| SdbError::Pipe(Errno::from_raw(err.raw_os_error().unwrap_or(0))))
+}
+
+/// How to launch a process
+#[derive(Debug)]
+pub struct LaunchConfig {
+    path: String,
+    args: Vec<String>,
+    debug: bool,
+    stdin: Redirect,
+    stdout: Redirect,
+    stderr: Redirect,
+    terminal: Option<OwnedFd>,
+    tty: Option<PathBuf>,
+    cwd: Option<PathBuf>,
+}
+
+impl LaunchConfig {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            args: Vec::new(),
+            debug: true,
+            stdin: Redirect::Inherit,
+            stdout: Redirect::Inherit,
+            stderr: Redirect::Inherit,
+            terminal: None,
+            tty: None,
+            cwd: None,
+        }
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn args(mut self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Whether to trace the process, on by default
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    pub fn stdin(mut self, redirect: Redirect) -> Self {
+        self.stdin = redirect;
+        self
+    }
+
+    pub fn stdout(mut self, redirect: Redirect) -> Self {
+        self.stdout = redirect;
+        self
+    }
+
+    pub fn stderr(mut self, redirect: Redirect) -> Self {
+        self.stderr = redirect;
+        self
+    }
+
+    /// Runs the process in its own session with this terminal, usually a [`crate::Pty`]'s slave,
+    /// as the controlling terminal and whichever standard streams aren't redirected
+    pub fn terminal(mut self, terminal: impl Into<OwnedFd>) -> Self {
+        self.terminal = Some(terminal.into());
+        self.tty = None;
+        self
+    }
+
+    /// Runs the process on the terminal at `path`, another window's `/dev/pts/N` say,
+    /// so its output and input stay out of the debugger's
+    pub fn tty(self, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let terminal = fcntl::open(
+            &path,
+            fcntl::OFlag::O_RDWR | fcntl::OFlag::O_NOCTTY | fcntl::OFlag::O_CLOEXEC,
+            Mode::empty(),
+        )
+        .map_err(|errno| SdbError::Other(format!("Couldn't open {}: {}", path.display(), errno)))?;
+        if !unistd::isatty(&terminal).unwrap_or(false) {
+            return Err(SdbError::Other(format!(
+                "{} isn't a terminal",
+                path.display()
+            )));
+        }
+
+        let mut this = self.terminal(terminal);
+        this.tty = Some(path);
+        Ok(this)
+    }
+
+    /// The terminal given with [`Self::tty`]
+    #[inline]
+    pub fn tty_path(&self) -> Option<&Path> {
+        self.tty.as_deref()
+    }
+
+    /// Starts the process in `dir` instead of the debugger's working directory,
+    /// a relative path to the program is still from the debugger's
+    pub fn cwd(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cwd = Some(dir.into());
+        self
+    }
+
+    #[inline]
+    pub fn working_directory(&self) -> Option<&Path> {
+        self.cwd.as_deref()
+    }
+
+    #[inline]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    // the program stays the same one wherever the debugger's working directory goes
+    pub(crate) fn absolute_path(mut self) -> Result<Self> {
+        self.path = absolute_path(self.path)?;
+        Ok(self)
+    }
+
+    /// The same launch again, the redirects get their own duplicates of the fds
+    pub fn try_clone(&self) -> Result<Self> {
+        Ok(Self {
+            path: self.path.clone(),
+            args: self.args.clone(),
+            debug: self.debug,
+            stdin: self.stdin.try_clone()?,
+            stdout: self.stdout.try_clone()?,
+            stderr: self.stderr.try_clone()?,
+            terminal: self.terminal.as_ref().map(dup).transpose()?,
+            tty: self.tty.clone(),
+            cwd: self.cwd.clone(),
+        })
+    }
+
+    #[inline]
+    pub fn launch(self) -> Result<Process> {
+        Process::launch_with(self)
+    }
+}
+
+// what's left of a Redirect for the child once any tee has its pipe
+enum ChildFd {
+    Inherit,
+    Fd(OwnedFd),
+}
+
+impl ChildFd {
+    // the pipe is created up front so there's nothing to clean up after the fork but the relay
+    fn prepare(
+        redirect: Redirect,
+        stream: OutputStream,
+        relays: &mut Vec<Relay>,
+        output: &mut CapturedOutput,
+    ) -> Result<Self> {
+        Ok(match redirect {
+            Redirect::Inherit => Self::Inherit,
+            Redirect::Fd(fd) => Self::Fd(fd),
+            Redirect::Tee {
+                capture,
+                passthrough,
+            } => {
+                let (read, write) =
+                    unistd::pipe2(fcntl::OFlag::O_CLOEXEC).map_err(SdbError::Pipe)?;
+                relays.push(Relay {
+                    read,
+                    outputs: vec![capture, passthrough],
+                });
+                Self::Fd(write)
+            }
+            Redirect::Events { passthrough } => Self::Fd(output.add(stream, passthrough)?),
+            Redirect::File(_) => unreachable!("files are opened before the fork"),
+            Redirect::Capture {
+                capture,
+                passthrough,
+                events,
+            } => {
+                let write = capture.add(passthrough)?;
+                // the lines are split out on the way, the capture's pipe is their passthrough
+                Self::Fd(if events {
+                    output.add(stream, Some(write))?
+                } else {
+                    write
+                })
+            }
+        })
+    }
+
+    fn replace(self, dup2: fn(OwnedFd) -> nix::Result<()>) -> nix::Result<()> {
+        match self {
+            Self::Inherit => Ok(()),
+            Self::Fd(fd) => dup2(fd),
+        }
+    }
+}
+
+// copies a teed stream to its outputs until the process closes it
+struct Relay {
+    read: OwnedFd,
+    outputs: Vec<OwnedFd>,
+}
+
+impl Relay {
+    fn spawn(self) {
+        std::thread::spawn(move || {
+            let Self { read, mut outputs } = self;
+            let mut buf = [0; 4096];
+            loop {
+                let count = match unistd::read(&read, &mut buf) {
+                    Ok(0) => break,
+                    Ok(count) => count,
+                    Err(Errno::EINTR) => continue,
+                    Err(_) => break,
+                };
+
+                // an output that's gone away is dropped, the rest keep getting everything
+                outputs.retain(|output| write_all(output, &buf[..count]).is_ok());
+            }
+        });
+    }
+}
+
+pub(crate) fn write_all(fd: &OwnedFd, mut data: &[u8]) -> nix::Result<()> {
+    while !data.is_empty() {
+        match unistd::write(fd, data) {
+            Ok(written) => data = &data[written..],
+            Err(Errno::EINTR) => continue,
+            Err(errno) => return Err(errno),
+        }
+    }
+    Ok(())
+}
+
+impl Process {
+    pub fn launch_with(config: LaunchConfig) -> Result<Self> {
+        let LaunchConfig {
+            path,
+            args,
+            debug,
+            stdin,
+            stdout,
+            stderr,
+            terminal,
+            cwd,
+            ..
+        } = config;
+
+        if matches!(
+            stdin,
+            Redirect::Tee { .. } | Redirect::Events { .. } | Redirect::Capture { .. }
+        ) {
+            return Err(SdbError::Other("stdin can't be captured".to_owned()));
+        }
+
+        let mut stdin = stdin.open(true)?;
+        let mut stdout = stdout.open(false)?;
+        let mut stderr = stderr.open(false)?;
+        if let Some(terminal) = &terminal {
+            for redirect in [&mut stdin, &mut stdout, &mut stderr] {
+                if matches!(redirect, Redirect::Inherit) {
+                    *redirect = Redirect::dup(terminal)?;
+                }
+            }
+        }
+
+        let argv0 = CString::new(path.as_str()).map_err(|err| SdbError::Other(err.to_string()))?;
+        // the child changes directory before exec'ing the program
+        let path = match cwd {
+            Some(_) => absolute_path(path)?,
+            None => path,
+        };
+        let path = CString::new(path).map_err(|err| SdbError::Other(err.to_string()))?;
+        let mut argv = vec![argv0];
+        for arg in args {
+            argv.push(CString::new(arg).map_err(|err| SdbError::Other(err.to_string()))?);
+        }
+
+        // stdin is never captured, what stream it'd be doesn't matter
+        let mut relays = Vec::new();
+        let mut output = CapturedOutput::default();
+        let stdin = ChildFd::prepare(stdin, OutputStream::Stdout, &mut relays, &mut output)?;
+        let stdout = ChildFd::prepare(stdout, OutputStream::Stdout, &mut relays, &mut output)?;
+        let stderr = ChildFd::prepare(stderr, OutputStream::Stderr, &mut relays, &mut output)?;
+
+        let mut channel = Pipe::new(true)?;
+
+        match unsafe { unistd::fork() } {
+            Ok(unistd::ForkResult::Parent { child }) => {
+                channel.close_write();
+
+                // the child has its own copies, the relays see EOF once it's done with them
+                drop((stdin, stdout, stderr));
+                for relay in relays {
+                    relay.spawn();
+                }
+
+                // the child's end is closed once it's exec'd or exited
+                let data = channel.read_to_end()?;
+                if !data.is_empty() {
+                    // the child exits right after reporting
+                    let _ = wait::waitpid(child, None);
+                    return Err(SdbError::Child(String::from_utf8(data).unwrap()));
+                }
+
+                let mut this = Self::new(child, true, debug);
+                this.output = output;
+                this.output.spawn_relay(this.subscribers.clone());
+                if debug {
+                    let mut reason = this.wait_on_signal()?;
+                    // anything but the exec's SIGTRAP is the kernel killing it for an exec that
+                    // failed past the point of no return, like a segment it couldn't map
+                    if reason.reason == ProcessState::Stopped && reason.info != libc::SIGTRAP {
+                        ptrace::cont(child, reason.signal())
+                            .map_err(SdbError::ptrace("cont", child))?;
+                        reason = this.wait_on_signal()?;
+                    }
+                    if reason.reason != ProcessState::Stopped {
+                        return Err(this.launch_error(&path.to_string_lossy(), &reason));
+                    }
+
+                    if let Err(err) = this.init_debugging() {
+                        // anything that went away during setup gets reported as such
+                        if let Ok(Some(reason)) = this.wait_on_signal_nonblocking()
+                            && reason.reason != ProcessState::Stopped
+                        {
+                            return Err(this.launch_error(&path.to_string_lossy(), &reason));
+                        }
+                        return Err(err);
+                    }
+                }
+                Ok(this)
+            }
+            Ok(unistd::ForkResult::Child) => {
+                channel.close_read();
+
+                // only the write ends are the child's business
+                drop((relays, output));
+
+                // a new session has no controlling terminal until it's given one
+                if let Some(terminal) = &terminal {
+                    if let Err(errno) = unistd::setsid() {
+                        Self::exit_with_perror(&channel, "terminal setup failed", errno);
+                    }
+                    // a terminal that's already another session's, like a shell's window,
+                    // can't be taken, the process still reads and writes it
+                    unsafe { libc::ioctl(terminal.as_raw_fd(), libc::TIOCSCTTY, 0) };
+                }
+
+                // otherwise a process group of its own, for the debugger to hand its terminal to
+                // while it runs and take back when it stops
+                if terminal.is_none()
+                    && let Err(errno) =
+                        unistd::setpgid(unistd::Pid::from_raw(0), unistd::Pid::from_raw(0))
+                {
+                    Self::exit_with_perror(&channel, "process group setup failed", errno);
+                }
+
+                if let Some(cwd) = &cwd
+                    && let Err(errno) = unistd::chdir(cwd.as_path())
+                {
+                    Self::exit_with_perror(&channel, "changing directory failed", errno);
+                }
+
+                for (fd, dup2, name) in [
+                    (stdin, unistd::dup2_stdin::<OwnedFd> as fn(_) -> _, "stdin"),
+                    (stdout, unistd::dup2_stdout::<OwnedFd>, "stdout"),
+                    (stderr, unistd::dup2_stderr::<OwnedFd>, "stderr"),
+                ] {
+                    if let Err(errno) = fd.replace(dup2) {
+                        Self::exit_with_perror(
+                            &channel,
+                            format!("{} replacement failed", name),
+                            errno,
+                        );
+                    }
+                }
+
+                if debug && let Err(errno) = ptrace::traceme() {
+                    Self::exit_with_perror(&channel, "tracing failed", errno);
+                }
+
+                let Err(errno) = unistd::execvp(path.as_c_str(), &argv);
+                Self::exit_with_perror(&channel, "exec failed", errno);
+
+                unreachable!();
+            }
+            Err(errno) => Err(SdbError::Fork(errno)),
+        }
+    }
+
+    /// Forks a child that runs `f` under trace instead of exec'ing a program
+    ///
+    /// The child is stopped before `f` is called and exits with whatever it returns. It's a copy
+    /// of this process, so anything that can be named here, a function to break on or a variable
+    /// to read, is at the same address in the child
+    ///
+    /// # Safety
+    ///
+    /// Only the calling thread is forked, `f` mustn't rely on anything the other threads may have
+    /// been in the middle of, like a lock they held
+    pub unsafe fn fork_with(f: impl FnOnce() -> i32) -> Result<Self> {
+        match unsafe { unistd::fork() } {
+            Ok(unistd::ForkResult::Parent { child }) => {
+                let mut this = Self::new(child, true, true);
+                let reason = this.wait_on_signal()?;
+                if reason.reason != ProcessState::Stopped {
+                    return Err(this.launch_error("forked child", &reason));
+                }
+                this.init_debugging()?;
+                Ok(this)
+            }
+            Ok(unistd::ForkResult::Child) => {
+                // a panic mustn't unwind into the parent's code, and nothing of the parent's
+                // like the test harness's exit handlers runs on the way out
+                let code = match ptrace::traceme().and_then(|_| signal::raise(Signal::SIGSTOP)) {
+                    Ok(()) => {
+                        std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).unwrap_or(-1)
+                    }
+                    Err(_) => -1,
+                };
+                unsafe { libc::_exit(code) }
+            }
+            Err(errno) => Err(SdbError::Fork(errno)),
+        }
+    }
+
+    // the child has already been reaped by the time this is called
+    pub(crate) fn launch_error(mut self, path: &str, reason: &StopReason) -> SdbError {
+        self.is_attached = false;
+        self.terminate_on_drop = false;
+
+        let mut message = match reason.reason {
+            ProcessState::Terminated => format!(
+                "{} terminated during launch with signal {:?}",
+                path,
+                reason.signal()
+            ),
+            _ => format!("{} exited during launch with status {}", path, reason.info),
+        };
+
+        // whatever it said on its way out, when its stderr comes through the debugger
+        let stderr = self.output.stderr_tail(&self.subscribers);
+        if !stderr.trim().is_empty() {
+            message.push_str(": ");
+            message.push_str(stderr.trim_end());
+        }
+        SdbError::Child(message)
+    }
+
+    fn exit_with_perror(channel: &Pipe, prefix: impl AsRef<str>, errno: Errno) {
+        let message = format!("{}: {}", prefix.as_ref(), errno);
+        let _ = channel.write(message);
+        std::process::exit(-1);
+    }
+}
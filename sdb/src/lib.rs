@@ -1,40 +1,177 @@
+mod alloc_trace;
+mod auxv;
 mod bit;
+mod breakpoint_site;
+mod bulk;
+mod call;
+mod checkpoint;
+mod checksec;
+mod controller;
+mod core_dump;
+mod debuginfo;
+mod disassembler;
+mod dprintf;
+mod dwarf;
+mod elf;
+mod event;
+pub mod expr;
+mod fatal;
+mod fork;
+mod got;
+mod heap;
+mod hook;
+mod inferior;
+mod inject;
+mod journal;
+mod launch;
+mod lifecycle;
+mod output;
+mod panic;
+mod perf;
+mod pidfd;
 mod pipe;
+mod proc_info;
+mod profile;
+mod protection;
+mod protocol;
+mod pty;
 mod register_info;
 mod registers;
+pub mod remote;
+mod rerun;
+mod search;
+mod signals;
+mod source;
+mod stoppoint_collection;
+mod syscall_abi;
+mod syscall_names;
+mod syscall_trace;
+mod target;
+mod teardown;
 mod test;
+mod threads;
+mod timings;
+mod tls;
+mod transport;
 mod types;
+mod variables;
+mod watchpoint;
 
-use std::cell::RefCell;
-use std::ffi::CString;
+use std::collections::BTreeMap;
+use std::ops::Range;
 use std::os::fd::OwnedFd;
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, AtomicI32, Ordering},
+};
+use std::time::{Duration, Instant};
 
 use nix::{
     errno::Errno,
     libc,
-    sys::{ptrace, signal, wait},
-    unistd::{self, Pid},
+    sys::{ptrace, signal, uio, wait},
+    unistd::Pid,
 };
 use num_traits::{FromPrimitive, ToPrimitive};
-use tracing::trace;
+use tracing::{trace, warn};
+
+use alloc_trace::AllocationTrace;
+use checkpoint::Checkpoints;
+use event::EventSubscribers;
+use fork::ForkState;
+use journal::Journal;
+use output::CapturedOutput;
+use signals::SignalPolicy;
+use watchpoint::DebugStatus;
+
+// not exported by libc
+const TRAP_BRKPT: i32 = 1;
+const TRAP_TRACE: i32 = 2;
+
+// the longest a wait with a timeout sleeps between checks
+const MAX_WAIT_INTERVAL: Duration = Duration::from_millis(10);
 
-use pipe::Pipe;
-use register_info::{RegisterId, register_info_by_id};
-use registers::{RegisterValue, Registers};
+pub use alloc_trace::{AllocFunction, Allocation, AllocationEvent};
+pub use auxv::{Auxv, AuxvKey};
+pub use breakpoint_site::{BreakpointSite, HitLimit};
+pub use bulk::{TransferMethod, TransferReport};
+pub use checkpoint::Checkpoint;
+pub use checksec::{Mitigations, Pie, Relro};
+pub use controller::ProcessController;
+pub use core_dump::{CoreTarget, CoreThread};
+pub use debuginfo::{debuginfod_enabled, set_debuginfod_enabled};
+pub use disassembler::Instruction;
+pub use dprintf::{LogArg, LogFormat};
+pub use dwarf::{
+    BaseEncoding, Dwarf, DwarfExpression, DwarfFunction, DwarfLocation, DwarfMember, DwarfType,
+    DwarfVariable, LineEntry, TypeId,
+};
+pub use elf::{Elf, ElfCollection, ElfSymbol, TlsTemplate, UnwindRow};
+pub use event::DebugEvent;
+pub use fatal::DEFAULT_FATAL_FUNCTIONS;
+pub use fork::FollowForkMode;
+pub use got::{GotEntry, GotStatus};
+pub use heap::{
+    Arena, ChunkState, ChunkTotals, HeapBin, HeapChunk, HeapInfo, HeapProblem, HeapStats,
+};
+pub use hook::{FunctionHook, HookReplacement};
+pub use inferior::{Frame, Inferior, SourceLocation};
+pub use journal::{JournalEntry, Mutation};
+pub use launch::{LaunchConfig, Redirect};
+pub use lifecycle::{EventCatchpoint, LifecycleEvent};
+pub use output::{OutputCapture, OutputStream};
+pub use perf::{PerfCounters, PerfEvent, PerfReading};
+pub use pipe::Pipe;
+pub use proc_info::{OpenFd, ProcStat, ProcStatus};
+pub use profile::{FunctionSamples, Profile};
+pub use protection::WriteAssist;
+pub use protocol::{Message, Request, Response};
+pub use pty::Pty;
+pub use register_info::{
+    RegisterFormat, RegisterId, RegisterInfo, RegisterType, register_info_by_dwarf,
+    register_info_by_id, register_info_by_name, register_infos,
+};
+pub use registers::RegisterValue;
+pub use search::{MemoryMatch, SearchRegions};
+pub use signals::SignalCatchpoint;
+pub use source::SourceMap;
+pub use stoppoint_collection::{Stoppoint, StoppointCollection, StoppointId, StoppointKind};
+pub use syscall_abi::SyscallAbi;
+pub use syscall_trace::{SyscallFilter, SyscallRecord};
+pub use target::{StopInfo, Target};
+pub use threads::{ThreadOpReport, ThreadOutcome, ThreadState};
+pub use timings::{OperationTimings, TimedOperation, Timings};
+pub use tls::{GlibcTcb, TlsBlock, TlsInfo};
+pub use transport::{DebugTransport, InProcessTransport, StreamTransport, serve_session};
+pub use types::VirtAddr;
+pub use variables::{Accessor, Radix, VariablePath, VariableValue};
+pub use watchpoint::{Watchpoint, WatchpointMode};
 
 #[derive(Debug, thiserror::Error)]
 pub enum SdbError {
     #[error("child error: {0}")]
     Child(String),
 
-    #[error("ptrace error: {0}")]
-    Ptrace(Errno),
+    #[error("ptrace error: {op} on {pid}{}: {errno}", at(.address))]
+    Ptrace {
+        op: &'static str,
+        pid: Pid,
+        address: Option<VirtAddr>,
+        errno: Errno,
+    },
 
     #[error("fork error: {0}")]
     Fork(Errno),
 
-    #[error("waitpid error: {0}")]
-    WaitPid(Errno),
+    #[error("waitpid error: {}: {errno}", waited_for(.pid))]
+    WaitPid { pid: Option<Pid>, errno: Errno },
+
+    #[error("signal error: {signal} to {pid}: {errno}")]
+    Signal {
+        signal: signal::Signal,
+        pid: Pid,
+        errno: Errno,
+    },
 
     #[error("pipe error: {0}")]
     Pipe(Errno),
@@ -45,16 +182,116 @@ pub enum SdbError {
     #[error("write error: {0}")]
     Write(Errno),
 
+    #[error("terminal error: {0}")]
+    Terminal(Errno),
+
     #[error("procfs error: {0}")]
     Procfs(#[from] procfs::ProcError),
 
     #[error("register error: {0}")]
     Register(String),
 
+    #[error("stoppoint error: {0}")]
+    Stoppoint(String),
+
+    #[error("elf error: {0}")]
+    Elf(String),
+
+    #[error("core error: {0}")]
+    Core(String),
+
+    #[error("dwarf error: {0}")]
+    Dwarf(#[from] gimli::Error),
+
+    #[error("controller error: {0}")]
+    Controller(String),
+
+    #[error("thread error: {0}")]
+    ThreadOp(ThreadOpReport),
+
+    #[error("transport error: {0}")]
+    Transport(String),
+
+    #[error("expression error: {0}")]
+    Expression(String),
+
+    #[error("variable error: {0}")]
+    Variable(String),
+
+    #[error("format error: {0}")]
+    Format(String),
+
+    #[error("source error: {0}")]
+    Source(String),
+
+    #[error("debug info error: {0}")]
+    DebugInfo(String),
+
+    #[error("perf error: {0}")]
+    Perf(String),
+
+    #[error("timeout error: no stop after {0:?}")]
+    Timeout(Duration),
+
     #[error("other error: {0}")]
     Other(String),
 }
 
+fn at(address: &Option<VirtAddr>) -> String {
+    address.map_or_else(String::new, |address| format!(" at {}", address))
+}
+
+fn waited_for(pid: &Option<Pid>) -> String {
+    pid.map_or_else(|| "any child".to_owned(), |pid| pid.to_string())
+}
+
+impl SdbError {
+    // for map_err, the errno's the only thing the call hands back
+    pub(crate) fn ptrace(op: &'static str, pid: Pid) -> impl FnOnce(Errno) -> Self {
+        move |errno| SdbError::Ptrace {
+            op,
+            pid,
+            address: None,
+            errno,
+        }
+    }
+
+    pub(crate) fn ptrace_at(
+        op: &'static str,
+        pid: Pid,
+        address: VirtAddr,
+    ) -> impl FnOnce(Errno) -> Self {
+        move |errno| SdbError::Ptrace {
+            op,
+            pid,
+            address: Some(address),
+            errno,
+        }
+    }
+
+    pub(crate) fn waitpid(pid: Option<Pid>) -> impl FnOnce(Errno) -> Self {
+        move |errno| SdbError::WaitPid { pid, errno }
+    }
+
+    pub(crate) fn signal(signal: signal::Signal, pid: Pid) -> impl FnOnce(Errno) -> Self {
+        move |errno| SdbError::Signal { signal, pid, errno }
+    }
+
+    /// The errno a failed system call left, for the errors that come from one
+    pub fn errno(&self) -> Option<Errno> {
+        match self {
+            SdbError::Ptrace { errno, .. }
+            | SdbError::WaitPid { errno, .. }
+            | SdbError::Signal { errno, .. } => Some(*errno),
+            SdbError::Fork(errno)
+            | SdbError::Pipe(errno)
+            | SdbError::Read(errno)
+            | SdbError::Write(errno) => Some(*errno),
+            _ => None,
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, SdbError>;
 
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
@@ -66,31 +303,345 @@ pub enum ProcessState {
     Terminated,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TrapType {
+    SoftwareBreak,
+    // a debug register's instruction breakpoint, sdb doesn't set any but they can be written
+    HardwareBreak,
+    Watchpoint,
+    SingleStep,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StopReason {
+    pub reason: ProcessState,
+
+    // exit code for Exited, signal number otherwise
+    pub info: i32,
+
+    pub trap_reason: Option<TrapType>,
+    pub breakpoint_site: Option<StoppointId>,
+
+    // set when the process stopped on entry to a fatal-path function
+    pub fatal_function: Option<String>,
+
+    // the catchpoint that stopped the process for the signal it received or the event
+    pub catchpoint: Option<StoppointId>,
+    pub lifecycle_event: Option<LifecycleEvent>,
+
+    // the watchpoint whose memory was accessed, and whether it was read or written
+    pub watchpoint: Option<StoppointId>,
+    pub watch_access: Option<WatchpointMode>,
+}
+
+impl From<wait::WaitStatus> for StopReason {
+    fn from(status: wait::WaitStatus) -> Self {
+        let (reason, info) = match status {
+            wait::WaitStatus::Exited(_, code) => (ProcessState::Exited, code),
+            wait::WaitStatus::Signaled(_, signal, _) => (ProcessState::Terminated, signal as i32),
+            wait::WaitStatus::Stopped(_, signal) => (ProcessState::Stopped, signal as i32),
+            // PTRACE_INTERRUPT reports SIGTRAP, but it's a plain stop as far as anyone else is concerned
+            wait::WaitStatus::PtraceEvent(_, signal::SIGTRAP, libc::PTRACE_EVENT_STOP) => {
+                (ProcessState::Stopped, libc::SIGSTOP)
+            }
+            wait::WaitStatus::PtraceEvent(_, signal, _) => (ProcessState::Stopped, signal as i32),
+            wait::WaitStatus::PtraceSyscall(_) => (ProcessState::Stopped, libc::SIGTRAP),
+            wait::WaitStatus::Continued(_) | wait::WaitStatus::StillAlive => {
+                (ProcessState::Running, 0)
+            }
+        };
+
+        Self {
+            reason,
+            info,
+            trap_reason: None,
+            breakpoint_site: None,
+            fatal_function: None,
+            catchpoint: None,
+            lifecycle_event: None,
+            watchpoint: None,
+            watch_access: None,
+        }
+    }
+}
+
+impl StopReason {
+    pub fn signal(&self) -> Option<signal::Signal> {
+        match self.reason {
+            ProcessState::Stopped | ProcessState::Terminated => {
+                signal::Signal::try_from(self.info).ok()
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Lightweight snapshot of what a process is doing
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ResourceUsage {
+    pub status: char,
+    pub cpu_time: Duration,
+    pub children_cpu_time: Duration,
+    pub rss: u64,
+}
+
+impl ResourceUsage {
+    fn sample(pid: Pid) -> Result<Self> {
+        let stat = procfs::process::Process::new(pid.as_raw())?.stat()?;
+
+        let ticks_per_second = procfs::ticks_per_second();
+        let ticks_to_duration =
+            |ticks: u64| Duration::from_secs_f64(ticks as f64 / ticks_per_second as f64);
+
+        Ok(Self {
+            status: stat.state,
+            cpu_time: ticks_to_duration(stat.utime + stat.stime),
+            children_cpu_time: ticks_to_duration((stat.cutime + stat.cstime).max(0) as u64),
+            rss: stat.rss * procfs::page_size(),
+        })
+    }
+}
+
+// state shared between a Process and its handles
+#[derive(Debug)]
+struct SharedState {
+    // can change when following a fork
+    pid: AtomicI32,
+    state: Mutex<ProcessState>,
+    // None where the kernel doesn't have pidfds
+    pidfd: Mutex<Option<Arc<OwnedFd>>>,
+    // attached with PTRACE_SEIZE rather than PTRACE_TRACEME
+    seized: AtomicBool,
+    // PTRACE_INTERRUPT has to come from the tracer, which picks this up while it waits
+    interrupting: AtomicBool,
+}
+
+impl SharedState {
+    fn new(pid: Pid) -> Self {
+        Self {
+            pid: AtomicI32::new(pid.as_raw()),
+            state: Mutex::new(ProcessState::default()),
+            pidfd: Mutex::new(pidfd::open(pid).map(Arc::new)),
+            seized: AtomicBool::new(false),
+            interrupting: AtomicBool::new(false),
+        }
+    }
+
+    #[inline]
+    fn pid(&self) -> Pid {
+        Pid::from_raw(self.pid.load(Ordering::Relaxed))
+    }
+
+    fn set_pid(&self, pid: Pid) {
+        self.pid.store(pid.as_raw(), Ordering::Relaxed);
+        *self.pidfd.lock().unwrap() = pidfd::open(pid).map(Arc::new);
+    }
+
+    #[inline]
+    fn pidfd(&self) -> Option<Arc<OwnedFd>> {
+        self.pidfd.lock().unwrap().clone()
+    }
+
+    // the pid can't have gone to another process while the pidfd says this one's still there
+    fn has_exited(&self) -> bool {
+        self.pidfd().is_some_and(|fd| pidfd::has_exited(&fd))
+    }
+
+    fn signal(&self, signal: signal::Signal) -> Result<()> {
+        let pid = self.pid();
+        match self.pidfd() {
+            Some(fd) => pidfd::send_signal(&fd, signal),
+            None => signal::kill(pid, signal),
+        }
+        .map_err(SdbError::signal(signal, pid))
+    }
+
+    // anything read through the pid after the process exited could be some other process's
+    fn check_not_exited(&self) -> Result<()> {
+        if self.has_exited() {
+            return Err(SdbError::Other("The process has exited".to_owned()));
+        }
+        Ok(())
+    }
+}
+
+/// Cheap, cloneable view of a Process that can be used from any thread
+///
+/// ptrace requests can only come from the tracer thread,
+/// so this only exposes operations that don't go through ptrace
+#[derive(Debug, Clone)]
+pub struct ProcessHandle {
+    shared: Arc<SharedState>,
+}
+
+impl ProcessHandle {
+    #[inline]
+    pub fn get_id(&self) -> Pid {
+        self.shared.pid()
+    }
+
+    #[inline]
+    pub fn get_state(&self) -> ProcessState {
+        *self.shared.state.lock().unwrap()
+    }
+
+    #[inline]
+    pub fn get_status(&self) -> Result<char> {
+        let process = procfs::process::Process::new(self.shared.pid().as_raw())?;
+        let state = process.stat()?.state;
+        self.shared.check_not_exited()?;
+        Ok(state)
+    }
+
+    #[inline]
+    pub fn get_resource_usage(&self) -> Result<ResourceUsage> {
+        let usage = ResourceUsage::sample(self.shared.pid())?;
+        self.shared.check_not_exited()?;
+        Ok(usage)
+    }
+
+    /// Stops the running process, the stop is reported by the next wait
+    ///
+    /// A seized process is interrupted by the thread waiting on it, without a SIGSTOP
+    #[inline]
+    pub fn interrupt(&self) -> Result<()> {
+        if self.shared.seized.load(Ordering::Relaxed) {
+            self.shared.interrupting.store(true, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        self.shared.signal(signal::SIGSTOP)
+    }
+
+    /// Whether the process has exited, even if it hasn't been waited on yet
+    ///
+    /// Always false where the kernel doesn't have pidfds
+    #[inline]
+    pub fn has_exited(&self) -> bool {
+        self.shared.has_exited()
+    }
+
+    /// A pidfd for the process, readable once it exits, to poll or epoll on
+    ///
+    /// None where the kernel doesn't have pidfds
+    pub fn pidfd(&self) -> Option<OwnedFd> {
+        self.shared.pidfd()?.try_clone().ok()
+    }
+
+    /// Reads memory without stopping the process, breakpoints show as int3
+    #[inline]
+    pub fn read_memory(&self, address: VirtAddr, amount: usize) -> Result<Vec<u8>> {
+        read_process_memory(self.shared.pid(), address, amount)
+    }
+}
+
+// process_vm_readv doesn't go through ptrace, so any thread can use it
+pub(crate) fn read_process_memory(pid: Pid, address: VirtAddr, amount: usize) -> Result<Vec<u8>> {
+    let mut ret = vec![0; amount];
+
+    // split the remote reads on page boundaries
+    // so that one unreadable page doesn't fail the whole read
+    let mut remote_iovecs = Vec::new();
+    let mut address = address.addr() as usize;
+    let mut remaining = amount;
+    while remaining > 0 {
+        let up_to_next_page = 0x1000 - (address & 0xfff);
+        let chunk_size = remaining.min(up_to_next_page);
+        remote_iovecs.push(uio::RemoteIoVec {
+            base: address,
+            len: chunk_size,
+        });
+        remaining -= chunk_size;
+        address += chunk_size;
+    }
+
+    let read = uio::process_vm_readv(
+        pid,
+        &mut [std::io::IoSliceMut::new(&mut ret)],
+        &remote_iovecs,
+    )
+    .map_err(SdbError::Read)?;
+    ret.truncate(read);
+
+    Ok(ret)
+}
+
+// what owns the process and decides about the stops at its breakpoints, the process only
+// knows them by address, what's layered on top of it knows which are there for what
+pub(crate) trait StopHandler {
+    fn process(&self) -> &Process;
+
+    fn process_mut(&mut self) -> &mut Process;
+
+    // returns true if the stop was handled internally
+    // and the thread should carry on
+    fn handle_internal_stop(&mut self, reason: &StopReason) -> Result<bool>;
+
+    // the program was replaced, the process has already forgotten what was set in the old one
+    fn handle_exec(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub struct Process {
     pid: Pid,
     terminate_on_drop: bool,
     is_attached: bool,
-    state: ProcessState,
-    registers: RefCell<Registers>,
+    shared: Arc<SharedState>,
+
+    threads: BTreeMap<Pid, ThreadState>,
+    current_thread: Pid,
+    // resuming only continues the current thread
+    scheduler_locking: bool,
+    // the thread that was, until it stops or exits
+    locked_thread: Option<Pid>,
+
+    next_stoppoint_id: StoppointId,
+    breakpoint_sites: StoppointCollection<BreakpointSite>,
+    function_hooks: StoppointCollection<FunctionHook>,
+    signal_catchpoints: StoppointCollection<SignalCatchpoint>,
+    event_catchpoints: StoppointCollection<EventCatchpoint>,
+    watchpoints: StoppointCollection<Watchpoint>,
+
+    fork: ForkState,
+    signals: SignalPolicy,
+    subscribers: EventSubscribers,
+    output: CapturedOutput,
+    timings: Timings,
+
+    // a syscall instruction found in the process to inject syscalls with
+    syscall_gadget: Option<VirtAddr>,
+
+    // memory that's been written to, which can't be read from a module's file anymore
+    patched: Mutex<Vec<Range<VirtAddr>>>,
+
+    // changes made on the user's behalf, to undo
+    journal: Journal,
+
+    // resuming stops at syscalls to report the ones this picks
+    syscall_trace: Option<SyscallFilter>,
+
+    // breakpoints on the allocator to keep track of what's allocated
+    allocation_trace: Option<AllocationTrace>,
+
+    // how the process ended, once it has
+    end_status: Option<wait::WaitStatus>,
+
+    // copies of the process to go back to
+    checkpoints: Checkpoints,
 }
 
 impl Drop for Process {
     #[allow(unused_must_use)]
     fn drop(&mut self) {
         if self.pid.as_raw() != 0 {
-            if self.is_attached {
-                // have to stop the process before detaching
-                trace!("Stopping process ...");
-                if self.state == ProcessState::Running {
-                    signal::kill(self.pid, signal::SIGSTOP);
-                    wait::waitpid(self.pid, None);
+            if self.is_attached && self.check_alive().is_ok() {
+                // best effort, there's no one left to report to
+                if let Err(err) = self.teardown() {
+                    warn!("Failed to detach cleanly: {}", err);
                 }
-
-                // detach and resume the process
-                trace!("Detaching and resuming process ...");
-                ptrace::detach(self.pid, None);
-                signal::kill(self.pid, signal::SIGCONT);
             }
 
             if self.terminate_on_drop {
@@ -102,32 +653,111 @@ impl Drop for Process {
     }
 }
 
+// on its own the process only steps over what's its own
+impl StopHandler for Process {
+    #[inline]
+    fn process(&self) -> &Process {
+        self
+    }
+
+    #[inline]
+    fn process_mut(&mut self) -> &mut Process {
+        self
+    }
+
+    fn handle_internal_stop(&mut self, reason: &StopReason) -> Result<bool> {
+        let Some(id) = reason.breakpoint_site else {
+            return Ok(false);
+        };
+
+        Ok(self.is_internal_stop(id)? || !self.record_breakpoint_hit(id)?)
+    }
+}
+
 impl Process {
     fn new(pid: Pid, terminate_on_drop: bool, is_attached: bool) -> Self {
         Self {
             pid,
             terminate_on_drop,
             is_attached,
-            state: ProcessState::default(),
-            registers: RefCell::new(Registers::new()),
+            shared: Arc::new(SharedState::new(pid)),
+            threads: BTreeMap::from([(pid, ThreadState::new(pid, ProcessState::default()))]),
+            current_thread: pid,
+            scheduler_locking: false,
+            locked_thread: None,
+            next_stoppoint_id: 1,
+            breakpoint_sites: StoppointCollection::default(),
+            function_hooks: StoppointCollection::default(),
+            signal_catchpoints: StoppointCollection::default(),
+            event_catchpoints: StoppointCollection::default(),
+            watchpoints: StoppointCollection::default(),
+            fork: ForkState::default(),
+            signals: SignalPolicy::default(),
+            subscribers: EventSubscribers::default(),
+            output: CapturedOutput::default(),
+            timings: Timings::default(),
+            syscall_gadget: None,
+            patched: Mutex::default(),
+            journal: Journal::default(),
+            syscall_trace: None,
+            allocation_trace: None,
+            end_status: None,
+            checkpoints: Checkpoints::default(),
+        }
+    }
+
+    // called once the process is stopped for the first time
+    fn init_debugging(&mut self) -> Result<()> {
+        self.attach_threads()?;
+        for tid in self.threads.keys() {
+            self.set_trace_options(*tid)?;
+        }
+
+        Ok(())
+    }
+
+    // the allocation trace's own stops, and a breakpoint for another thread, are only
+    // stepped over
+    pub(crate) fn is_internal_stop(&mut self, id: StoppointId) -> Result<bool> {
+        if self.handle_allocation_stop(id)? {
+            return Ok(true);
         }
+
+        Ok(self
+            .breakpoint_sites
+            .get_by_id(id)?
+            .thread()
+            .is_some_and(|tid| tid != self.current_thread))
+    }
+
+    // false for a hit the limit skips, which is stepped over like it never happened
+    #[inline]
+    pub(crate) fn record_breakpoint_hit(&mut self, id: StoppointId) -> Result<bool> {
+        Ok(self.breakpoint_sites.get_by_id_mut(id)?.record_hit())
+    }
+
+    #[inline]
+    fn set_state(&self, state: ProcessState) {
+        *self.shared.state.lock().unwrap() = state;
     }
 
-    fn read_all_registers(&mut self) -> Result<()> {
-        let regs = ptrace::getregs(self.pid).map_err(SdbError::Ptrace)?;
-        self.registers.borrow_mut().get_data_mut().regs = regs;
+    fn read_all_registers(&self, tid: Pid) -> Result<()> {
+        let mut registers = self.threads[&tid].registers.lock().unwrap();
+
+        let regs = ptrace::getregs(tid).map_err(SdbError::ptrace("getregs", tid))?;
+        registers.get_data_mut().regs = regs;
 
-        let regs =
-            ptrace::getregset::<ptrace::regset::NT_PRFPREG>(self.pid).map_err(SdbError::Ptrace)?;
-        self.registers.borrow_mut().get_data_mut().i387 = regs;
+        let regs = ptrace::getregset::<ptrace::regset::NT_PRFPREG>(tid)
+            .map_err(SdbError::ptrace("getfpregs", tid))?;
+        registers.get_data_mut().i387 = regs;
 
         for i in 0..8_usize {
             let id = RegisterId::dr0.to_usize().unwrap() + i;
             let info = register_info_by_id(RegisterId::from_usize(id).unwrap());
 
-            let data = ptrace::read_user(self.pid, info.offset as ptrace::AddressType)
-                .map_err(SdbError::Ptrace)?;
-            self.registers.borrow_mut().get_data_mut().u_debugreg[i] = data as u64;
+            let data = ptrace::read_user(tid, info.offset as ptrace::AddressType)
+                .map_err(SdbError::ptrace("peekuser", tid))?;
+            registers.get_data_mut().u_debugreg[i] = data as u64;
         }
 
         Ok(())
@@ -135,8 +765,12 @@ impl Process {
 
     pub fn attach(pid: i32) -> Result<Self> {
         let mut this = Self::new(Pid::from_raw(pid), false, true);
-        ptrace::attach(this.pid).map_err(SdbError::Ptrace)?;
+        this.shared.seized.store(true, Ordering::Relaxed);
+
+        this.threads.clear();
+        this.seize_thread(this.pid)?;
         this.wait_on_signal()?;
+        this.init_debugging()?;
 
         Ok(this)
     }
@@ -146,55 +780,11 @@ impl Process {
         debug: bool,
         stdout_replacement: Option<OwnedFd>,
     ) -> Result<Self> {
-        let path = CString::new(path.into()).unwrap();
-        let args: Vec<CString> = Vec::default();
-
-        let mut channel = Pipe::new(true)?;
-
-        match unsafe { unistd::fork() } {
-            Ok(unistd::ForkResult::Parent { child }) => {
-                channel.close_write();
-
-                let data = channel.read()?;
-                if !data.is_empty() {
-                    // TODO: waitpid(child) here?
-                    return Err(SdbError::Child(String::from_utf8(data).unwrap()));
-                }
-
-                let mut this = Self::new(child, true, debug);
-                if debug {
-                    this.wait_on_signal()?;
-                }
-                Ok(this)
-            }
-            Ok(unistd::ForkResult::Child) => {
-                channel.close_read();
-
-                if let Some(stdout_replacement) = stdout_replacement {
-                    if let Err(errno) = unistd::dup2_stdout(stdout_replacement) {
-                        Self::exit_with_perror(&channel, "stdout replacement failed", errno);
-                    }
-                }
-
-                if debug {
-                    if let Err(errno) = ptrace::traceme() {
-                        Self::exit_with_perror(&channel, "tracing failed", errno);
-                    }
-                }
-
-                let Err(errno) = unistd::execvp(path.as_c_str(), &args);
-                Self::exit_with_perror(&channel, "exec failed", errno);
-
-                unreachable!();
-            }
-            Err(errno) => Err(SdbError::Fork(errno)),
+        let mut config = LaunchConfig::new(path).debug(debug);
+        if let Some(stdout_replacement) = stdout_replacement {
+            config = config.stdout(Redirect::Fd(stdout_replacement));
         }
-    }
-
-    fn exit_with_perror(channel: &Pipe, prefix: impl AsRef<str>, errno: Errno) {
-        let message = format!("{}: {}", prefix.as_ref(), errno);
-        let _ = channel.write(message);
-        std::process::exit(-1);
+        Self::launch_with(config)
     }
 
     #[inline]
@@ -204,7 +794,14 @@ impl Process {
 
     #[inline]
     pub fn get_state(&self) -> ProcessState {
-        self.state
+        *self.shared.state.lock().unwrap()
+    }
+
+    #[inline]
+    pub fn handle(&self) -> ProcessHandle {
+        ProcessHandle {
+            shared: self.shared.clone(),
+        }
     }
 
     /*#[inline]
@@ -219,139 +816,4146 @@ impl Process {
 
     #[inline]
     pub fn get_status(&self) -> Result<char> {
-        let process = procfs::process::Process::new(self.pid.as_raw())?;
-        Ok(process.stat()?.state)
+        self.handle().get_status()
+    }
+
+    #[inline]
+    pub fn get_resource_usage(&self) -> Result<ResourceUsage> {
+        self.handle().get_resource_usage()
     }
 
-    pub fn wait_on_signal(&mut self) -> Result<wait::WaitStatus> {
-        let status = wait::waitpid(self.pid, None).map_err(SdbError::WaitPid)?;
-        trace!("Wait status {:?}", status);
-        match status {
-            wait::WaitStatus::Exited(..) => self.state = ProcessState::Exited,
-            wait::WaitStatus::Signaled(..) => self.state = ProcessState::Terminated,
-            wait::WaitStatus::Stopped(..) => self.state = ProcessState::Stopped,
-            _ => (),
+    /// Whether the process has exited, even if it hasn't been waited on yet
+    #[inline]
+    pub fn has_exited(&self) -> bool {
+        self.shared.has_exited()
+    }
+
+    /// The status the process exited with, None until it has or if a signal killed it
+    pub fn exit_code(&self) -> Option<i32> {
+        match self.end_status {
+            Some(wait::WaitStatus::Exited(_, code)) => Some(code),
+            _ => None,
         }
+    }
 
-        if self.is_attached && self.state == ProcessState::Stopped {
-            self.read_all_registers()?;
+    /// The signal that killed the process, None until one has
+    pub fn termination_signal(&self) -> Option<signal::Signal> {
+        match self.end_status {
+            Some(wait::WaitStatus::Signaled(_, signal, _)) => Some(signal),
+            _ => None,
         }
+    }
 
-        Ok(status)
+    pub fn wait_on_signal(&mut self) -> Result<StopReason> {
+        Self::wait_on_signal_with(self)
     }
 
-    pub fn resume(&mut self) -> Result<()> {
-        ptrace::cont(self.pid, None).map_err(SdbError::Ptrace)?;
-        self.state = ProcessState::Running;
+    // waits handing the stops at breakpoints to the process's owner
+    pub(crate) fn wait_on_signal_with(owner: &mut impl StopHandler) -> Result<StopReason> {
+        // a blocking wait couldn't notice an interrupt asked for from another thread
+        if owner.process().is_seized() {
+            let mut interval = Duration::from_micros(100);
+            loop {
+                if let Some(reason) = Self::wait_on_signal_nonblocking_with(owner)? {
+                    return Ok(reason);
+                }
+                std::thread::sleep(interval);
+                interval = (interval * 2).min(MAX_WAIT_INTERVAL);
+            }
+        }
 
-        Ok(())
+        let reason = Self::wait_with_flags(owner, None)?.unwrap();
+        owner.process_mut().timings.stop();
+        Ok(reason)
     }
 
-    // TODO: this is lame hack to avoid self-referencing in Registers
-    #[allow(clippy::missing_safety_doc)]
-    pub fn write_register_by_id(&self, id: RegisterId, val: RegisterValue) -> Result<()> {
-        self.registers.borrow_mut().write_by_id(id, val, self)
+    /// Returns None if the process hasn't stopped yet
+    pub fn wait_on_signal_nonblocking(&mut self) -> Result<Option<StopReason>> {
+        Self::wait_on_signal_nonblocking_with(self)
     }
 
-    pub(crate) fn write_user_area(&self, offset: usize, data: u64) -> Result<()> {
-        ptrace::write_user(
-            self.pid,
-            offset as ptrace::AddressType,
-            data as libc::c_long,
-        )
-        .map_err(SdbError::Ptrace)
+    pub(crate) fn wait_on_signal_nonblocking_with(
+        owner: &mut impl StopHandler,
+    ) -> Result<Option<StopReason>> {
+        let process = owner.process_mut();
+        if process.shared.interrupting.swap(false, Ordering::Relaxed) {
+            process.interrupt()?;
+        }
+
+        let reason = Self::wait_with_flags(owner, Some(wait::WaitPidFlag::WNOHANG))?;
+        if reason.is_some() {
+            owner.process_mut().timings.stop();
+        }
+        Ok(reason)
     }
 
-    // have to write fprs all at once
-    pub(crate) fn write_fprs(&self, fprs: libc::user_fpregs_struct) -> Result<()> {
-        ptrace::setregset::<ptrace::regset::NT_PRFPREG>(self.pid, fprs).map_err(SdbError::Ptrace)
+    /// Waits at most `timeout` for the process to stop, [`SdbError::Timeout`] if it hasn't
+    pub fn wait_on_signal_timeout(&mut self, timeout: Duration) -> Result<StopReason> {
+        Self::wait_on_signal_timeout_with(self, timeout)
     }
 
-    /*pub(crate) fn writegprs(&self, gprs: libc::user_regs_struct) -> Result<()> {
-        ptrace::setregs(self.pid, gprs).map_err(SdbError::Ptrace)
-    }*/
-}
+    pub(crate) fn wait_on_signal_timeout_with(
+        owner: &mut impl StopHandler,
+        timeout: Duration,
+    ) -> Result<StopReason> {
+        let deadline = Instant::now() + timeout;
+        // short at first for a stop that's about to happen, longer for one that isn't
+        let mut interval = Duration::from_micros(100);
+        loop {
+            if let Some(reason) = Self::wait_on_signal_nonblocking_with(owner)? {
+                return Ok(reason);
+            }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::pipe::Pipe;
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(SdbError::Timeout(timeout));
+            }
+            std::thread::sleep(interval.min(remaining));
+            interval = (interval * 2).min(MAX_WAIT_INTERVAL);
+        }
+    }
 
-    #[test]
-    fn process_attach_success() {
-        let target = Process::launch("yes", false, None).unwrap();
+    fn wait_with_flags(
+        owner: &mut impl StopHandler,
+        flags: Option<wait::WaitPidFlag>,
+    ) -> Result<Option<StopReason>> {
+        loop {
+            let process = owner.process_mut();
+            let Some(status) = process.wait_any(flags)? else {
+                return Ok(None);
+            };
+            trace!("Wait status {:?}", status);
 
-        let process = Process::attach(target.get_id().as_raw());
-        assert!(process.is_ok());
-        let process = process.unwrap();
+            if process.is_attached && process.handle_thread_status(status)? {
+                continue;
+            }
 
-        assert_eq!(process.get_status().unwrap(), 't');
-    }
+            let tid = status.pid().unwrap_or(process.pid);
+            let mut reason = StopReason::from(status);
+            process.record_thread_stop(tid, &reason);
+            process.set_state(reason.reason);
+            if matches!(
+                reason.reason,
+                ProcessState::Exited | ProcessState::Terminated
+            ) {
+                process.end_status = Some(status);
+            }
 
-    #[test]
-    fn process_attach_invalid_pid() {
-        let process = Process::attach(0);
-        assert!(process.is_err());
-        assert!(matches!(
-            process,
-            std::result::Result::Err(SdbError::Ptrace(..))
-        ));
-    }
+            if process.is_attached && reason.reason == ProcessState::Stopped {
+                process.current_thread = tid;
 
-    #[test]
-    fn process_launch_success() {
+                // a watched thread steps, every step that leaves the memory alone takes another,
+                // and a debug register's access that isn't what it watches is let go too,
+                // unless it was also a step that was asked for
+                let debug = process.take_debug_status(tid, status)?;
+                if let Some(fired) = process.watch_trap(debug) {
+                    match process.hit_watchpoint(tid, fired)? {
+                        Some((id, access)) => {
+                            reason.watchpoint = Some(id);
+                            reason.watch_access = Some(access);
+                        }
+                        None if debug.single_step && !process.is_watch_stepping(tid) => (),
+                        None => {
+                            process.resume_thread(tid)?;
+                            continue;
+                        }
+                    }
+                }
+
+                process.read_all_registers(tid)?;
+
+                if let wait::WaitStatus::PtraceSyscall(_) = status {
+                    process.handle_syscall_stop(tid)?;
+                    process.resume_thread(tid)?;
+                    continue;
+                }
+
+                if let wait::WaitStatus::Stopped(_, signal) = status {
+                    process.record_pending_signal(tid, signal);
+                    reason.catchpoint = process.hit_signal_catchpoint(signal);
+                    if reason.catchpoint.is_none() && !process.signal_stops(signal) {
+                        process.resume_thread(tid)?;
+                        continue;
+                    }
+                }
+
+                if let wait::WaitStatus::PtraceEvent(_, _, event) = status {
+                    let resume = process.handle_lifecycle_event(tid, event, &mut reason)?;
+                    if event == libc::PTRACE_EVENT_EXEC {
+                        owner.handle_exec()?;
+                    }
+
+                    // following a child moves us to its thread
+                    if resume {
+                        let process = owner.process_mut();
+                        process.resume_thread(process.current_thread)?;
+                        continue;
+                    }
+                }
+
+                owner
+                    .process_mut()
+                    .augment_stop_reason(&mut reason, debug)?;
+
+                if owner.handle_internal_stop(&reason)? {
+                    owner.process_mut().resume_thread(tid)?;
+                    continue;
+                }
+
+                let process = owner.process_mut();
+                process.record_thread_stop(tid, &reason);
+                process.stop_other_threads(Some(tid)).into_result()?;
+            }
+
+            let process = owner.process_mut();
+            process.locked_thread = None;
+            process.record_checkpoint_stop(&reason);
+            process.emit_stop_events(&reason)?;
+            return Ok(Some(reason));
+        }
+    }
+
+    // what dr6 says fired comes before a step that happened alongside it
+    fn augment_stop_reason(&mut self, reason: &mut StopReason, debug: DebugStatus) -> Result<()> {
+        if reason.info != libc::SIGTRAP {
+            return Ok(());
+        }
+
+        reason.trap_reason = Some(match debug.si_code {
+            libc::SI_KERNEL => TrapType::SoftwareBreak,
+            _ if debug.hardware_break => TrapType::HardwareBreak,
+            _ if reason.watchpoint.is_some() && debug.fired != 0 => TrapType::Watchpoint,
+            _ if debug.single_step => TrapType::SingleStep,
+            _ => TrapType::Unknown,
+        });
+
+        if reason.trap_reason == Some(TrapType::SoftwareBreak) {
+            // the pc is one past the int3
+            let address = self.get_pc()? - 1;
+            if self.breakpoint_sites.enabled_stoppoint_at_address(address) {
+                self.set_pc(address)?;
+
+                let id = self.breakpoint_sites.get_by_address(address)?.id();
+                reason.breakpoint_site = Some(id);
+                reason.fatal_function = self.fatal_function(id).map(str::to_owned);
+            }
+        }
+
+        Ok(())
+    }
+
+    // steps over an enabled breakpoint site at the thread's pc
+    // so that resuming doesn't immediately trap again
+    fn step_over_breakpoint(&mut self, tid: Pid) -> Result<Option<wait::WaitStatus>> {
+        let pc = self.threads[&tid]
+            .registers
+            .lock()
+            .unwrap()
+            .read_by_id(RegisterId::rip)?;
+        let pc = VirtAddr::new(pc.to_u64().unwrap());
+        if !self.breakpoint_sites.enabled_stoppoint_at_address(pc) {
+            return Ok(None);
+        }
+
+        // it hasn't been reported yet, so it should be hit again
+        if self.threads[&tid].rehit_breakpoint {
+            return Ok(None);
+        }
+
+        // a step onto an allocator function doesn't trap on its breakpoint
+        self.allocation_entry(tid, pc)?;
+
+        let site = self.breakpoint_sites.get_by_address_mut(pc)?;
+        site.disable(tid)?;
+
+        ptrace::step(tid, None).map_err(SdbError::ptrace("singlestep", tid))?;
+        let status = self.wait_for_step(tid)?;
+
+        self.breakpoint_sites.get_by_address_mut(pc)?.enable(tid)?;
+
+        Ok(Some(status))
+    }
+
+    /// Stops the running process, the stop is reported by the next wait
+    pub fn interrupt(&self) -> Result<()> {
+        if self.is_seized() {
+            return ptrace::interrupt(self.current_thread)
+                .map_err(SdbError::ptrace("interrupt", self.current_thread));
+        }
+
+        self.handle().interrupt()
+    }
+
+    #[inline]
+    pub(crate) fn is_seized(&self) -> bool {
+        self.shared.seized.load(Ordering::Relaxed)
+    }
+
+    pub fn resume(&mut self) -> Result<()> {
+        self.timings.start(TimedOperation::Resume);
+        self.settle_fork()?;
+        self.locked_thread = None;
+        if self.scheduler_locking || self.is_watching() {
+            self.locked_thread = Some(self.current_thread);
+            self.resume_thread(self.current_thread)?;
+            self.set_state(ProcessState::Running);
+            return Ok(());
+        }
+        self.resume_all_threads().into_result()
+    }
+
+    pub fn step_instruction(&mut self) -> Result<StopReason> {
+        Self::step_instruction_with(self)
+    }
+
+    pub(crate) fn step_instruction_with(owner: &mut impl StopHandler) -> Result<StopReason> {
+        let process = owner.process_mut();
+        process.timings.start(TimedOperation::Step);
+        process.settle_fork()?;
+
+        let tid = process.current_thread;
+        if let Some(status) = process.step_over_breakpoint(tid)? {
+            process.timings.stop();
+
+            let mut reason = StopReason::from(status);
+            process.set_state(reason.reason);
+            if reason.reason == ProcessState::Stopped {
+                let debug = process.take_debug_status(tid, status)?;
+                if let Some(fired) = process.watch_trap(debug)
+                    && let Some((id, access)) = process.hit_watchpoint(tid, fired)?
+                {
+                    reason.watchpoint = Some(id);
+                    reason.watch_access = Some(access);
+                }
+                process.read_all_registers(tid)?;
+                process.augment_stop_reason(&mut reason, debug)?;
+            }
+            process.record_thread_stop(tid, &reason);
+            process.record_checkpoint_stop(&reason);
+            return Ok(reason);
+        }
+
+        process.step_thread(tid)?;
+        process.set_state(ProcessState::Running);
+        Self::wait_on_signal_with(owner)
+    }
+
+    pub fn read_register_by_id(&self, id: RegisterId) -> Result<RegisterValue> {
+        self.current_thread_state()
+            .registers
+            .lock()
+            .unwrap()
+            .read_by_id(id)
+    }
+
+    pub fn get_pc(&self) -> Result<VirtAddr> {
+        let pc = self.read_register_by_id(RegisterId::rip)?;
+        Ok(VirtAddr::new(pc.to_u64().unwrap()))
+    }
+
+    pub fn set_pc(&self, address: VirtAddr) -> Result<()> {
+        self.write_register_by_id(RegisterId::rip, address.addr().into())
+    }
+
+    #[inline]
+    pub fn breakpoint_sites(&self) -> &StoppointCollection<BreakpointSite> {
+        &self.breakpoint_sites
+    }
+
+    pub fn create_breakpoint_site(
+        &mut self,
+        address: VirtAddr,
+        is_internal: bool,
+    ) -> Result<StoppointId> {
+        if self.breakpoint_sites.contains_address(address) {
+            return Err(SdbError::Stoppoint(format!(
+                "Breakpoint site already created at address {}",
+                address
+            )));
+        }
+
+        let id = self.next_stoppoint_id;
+        self.next_stoppoint_id += 1;
+
+        self.breakpoint_sites
+            .push(BreakpointSite::new(id, address, is_internal));
+        Ok(id)
+    }
+
+    pub fn enable_breakpoint_site(&mut self, id: StoppointId) -> Result<()> {
+        let tid = self.current_thread;
+        self.breakpoint_sites.get_by_id_mut(id)?.enable(tid)
+    }
+
+    pub fn disable_breakpoint_site(&mut self, id: StoppointId) -> Result<()> {
+        let tid = self.current_thread;
+        self.breakpoint_sites.get_by_id_mut(id)?.disable(tid)
+    }
+
+    /// Limits how often a hot breakpoint stops, None stops on every hit
+    pub fn set_breakpoint_hit_limit(
+        &mut self,
+        id: StoppointId,
+        limit: Option<HitLimit>,
+    ) -> Result<()> {
+        if let Some(HitLimit::MaxPerSecond(0) | HitLimit::Sample(0)) = limit {
+            return Err(SdbError::Stoppoint(
+                "A hit limit has to let some hits stop".to_owned(),
+            ));
+        }
+
+        self.breakpoint_sites
+            .get_by_id_mut(id)?
+            .set_hit_limit(limit);
+        Ok(())
+    }
+
+    /// Only stops at a breakpoint when the thread hits it, None stops for every thread
+    ///
+    /// A new run has new threads, so this doesn't carry over to one
+    pub fn set_breakpoint_thread(&mut self, id: StoppointId, tid: Option<Pid>) -> Result<()> {
+        if let Some(tid) = tid
+            && !self.threads.contains_key(&tid)
+        {
+            return Err(SdbError::Other(format!("No such thread {}", tid)));
+        }
+
+        self.breakpoint_sites.get_by_id_mut(id)?.set_thread(tid);
+        Ok(())
+    }
+
+    pub fn remove_breakpoint_site(&mut self, id: StoppointId) -> Result<()> {
+        self.disable_breakpoint_site(id)?;
+        self.breakpoint_sites.remove_by_id(id)?;
+        Ok(())
+    }
+
+    /// Every breakpoint, catchpoint, and hook, in the order they were set
+    pub fn stoppoints(&self) -> Vec<&dyn Stoppoint> {
+        let mut stoppoints = self
+            .breakpoint_sites
+            .iter()
+            .map(|site| site as &dyn Stoppoint)
+            .chain(
+                self.function_hooks
+                    .iter()
+                    .map(|hook| hook as &dyn Stoppoint),
+            )
+            .chain(
+                self.signal_catchpoints
+                    .iter()
+                    .map(|catchpoint| catchpoint as &dyn Stoppoint),
+            )
+            .chain(
+                self.event_catchpoints
+                    .iter()
+                    .map(|catchpoint| catchpoint as &dyn Stoppoint),
+            )
+            .chain(
+                self.watchpoints
+                    .iter()
+                    .map(|watchpoint| watchpoint as &dyn Stoppoint),
+            )
+            .collect::<Vec<_>>();
+        stoppoints.sort_by_key(|stoppoint| stoppoint.id());
+        stoppoints
+    }
+
+    pub fn enable_stoppoint(&mut self, id: StoppointId) -> Result<()> {
+        match self.stoppoint_kind(id)? {
+            StoppointKind::Hook => self.enable_function_hook(id),
+            _ if self.signal_catchpoints.contains_id(id) => self.enable_signal_catchpoint(id),
+            _ if self.event_catchpoints.contains_id(id) => self.enable_event_catchpoint(id),
+            _ if self.watchpoints.contains_id(id) => self.enable_watchpoint(id),
+            _ => self.enable_breakpoint_site(id),
+        }
+    }
+
+    pub fn disable_stoppoint(&mut self, id: StoppointId) -> Result<()> {
+        match self.stoppoint_kind(id)? {
+            StoppointKind::Hook => self.disable_function_hook(id),
+            _ if self.signal_catchpoints.contains_id(id) => self.disable_signal_catchpoint(id),
+            _ if self.event_catchpoints.contains_id(id) => self.disable_event_catchpoint(id),
+            _ if self.watchpoints.contains_id(id) => self.disable_watchpoint(id),
+            _ => self.disable_breakpoint_site(id),
+        }
+    }
+
+    pub fn remove_stoppoint(&mut self, id: StoppointId) -> Result<()> {
+        match self.stoppoint_kind(id)? {
+            StoppointKind::Hook => self.remove_function_hook(id),
+            _ if self.signal_catchpoints.contains_id(id) => self.remove_signal_catchpoint(id),
+            _ if self.event_catchpoints.contains_id(id) => self.remove_event_catchpoint(id),
+            _ if self.watchpoints.contains_id(id) => self.remove_watchpoint(id),
+            _ => self.remove_breakpoint_site(id),
+        }
+    }
+
+    fn stoppoint_kind(&self, id: StoppointId) -> Result<StoppointKind> {
+        if let Ok(site) = self.breakpoint_sites.get_by_id(id) {
+            return Ok(site.kind());
+        }
+        if let Ok(catchpoint) = self.signal_catchpoints.get_by_id(id) {
+            return Ok(catchpoint.kind());
+        }
+        if let Ok(catchpoint) = self.event_catchpoints.get_by_id(id) {
+            return Ok(catchpoint.kind());
+        }
+        if let Ok(watchpoint) = self.watchpoints.get_by_id(id) {
+            return Ok(watchpoint.kind());
+        }
+        Ok(self.function_hooks.get_by_id(id)?.kind())
+    }
+
+    #[inline]
+    pub fn read_memory(&self, address: VirtAddr, amount: usize) -> Result<Vec<u8>> {
+        read_process_memory(self.pid, address, amount)
+    }
+
+    pub fn read_memory_without_traps(&self, address: VirtAddr, amount: usize) -> Result<Vec<u8>> {
+        let mut memory = self.read_memory(address, amount)?;
+        self.hide_breakpoint_traps(address, &mut memory);
+
+        Ok(memory)
+    }
+
+    pub fn read_u64(&self, address: VirtAddr) -> Result<u64> {
+        self.read_memory_without_traps(address, 8)?
+            .try_into()
+            .map(u64::from_ne_bytes)
+            .map_err(|_| SdbError::Other(format!("Failed to read memory at {}", address)))
+    }
+
+    /// Reads up to the NUL, which is left off, or max_len bytes if there isn't one in them
+    pub fn read_c_string(&self, address: VirtAddr, max_len: usize) -> Result<Vec<u8>> {
+        inferior::read_c_string(
+            |address, amount| self.read_memory_without_traps(address, amount),
+            address,
+            max_len,
+        )
+    }
+
+    // whether anything's been written over memory in the range since the program was loaded
+    pub(crate) fn is_patched(&self, range: Range<VirtAddr>) -> bool {
+        self.patched
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|patch| patch.start < range.end && range.start < patch.end)
+    }
+
+    // what's written can't be read from a module's file anymore
+    fn record_patch(&self, range: Range<VirtAddr>) {
+        let mut patched = self.patched.lock().unwrap();
+        if !patched
+            .iter()
+            .any(|patch| patch.start <= range.start && range.end <= patch.end)
+        {
+            patched.push(range);
+        }
+    }
+
+    // puts back the bytes enabled breakpoints have replaced in memory read from address
+    pub(crate) fn hide_breakpoint_traps(&self, address: VirtAddr, memory: &mut [u8]) {
+        let high = address + memory.len() as i64;
+        for site in self.breakpoint_sites.get_in_region(address, high) {
+            if site.is_enabled() {
+                let offset = (site.address().addr() - address.addr()) as usize;
+                memory[offset] = site.saved_data();
+            }
+        }
+    }
+
+    pub fn write_memory(&self, address: VirtAddr, data: &[u8]) -> Result<()> {
+        let high = address + data.len() as i64;
+        self.record_patch(address..high);
+
+        let mut written = 0;
+        while written < data.len() {
+            let remaining = data.len() - written;
+            let target = address + written as i64;
+
+            let word = if remaining >= 8 {
+                u64::from_ne_bytes(data[written..written + 8].try_into().unwrap())
+            } else {
+                // partial word, have to preserve what's already there
+                let existing =
+                    ptrace::read(self.current_thread, target.addr() as ptrace::AddressType)
+                        .map_err(SdbError::ptrace_at("peekdata", self.current_thread, target))?;
+                let mut bytes = existing.to_ne_bytes();
+                bytes[..remaining].copy_from_slice(&data[written..]);
+                u64::from_ne_bytes(bytes)
+            };
+
+            ptrace::write(
+                self.current_thread,
+                target.addr() as ptrace::AddressType,
+                word as i64,
+            )
+            .map_err(SdbError::ptrace_at("pokedata", self.current_thread, target))?;
+            written += 8;
+        }
+
+        Ok(())
+    }
+
+    // TODO: this is lame hack to avoid self-referencing in Registers
+    #[allow(clippy::missing_safety_doc)]
+    pub fn write_register_by_id(&self, id: RegisterId, val: RegisterValue) -> Result<()> {
+        self.current_thread_state()
+            .registers
+            .lock()
+            .unwrap()
+            .write_by_id(id, val, self)
+    }
+
+    /// Writes several registers of the current thread together
+    ///
+    /// The general purpose and floating point registers are each written with one ptrace call.
+    /// Values are applied in order, so where registers overlap, like eax and rax, the later wins
+    pub fn write_registers(
+        &self,
+        values: impl IntoIterator<Item = (RegisterId, RegisterValue)>,
+    ) -> Result<()> {
+        self.current_thread_state()
+            .registers
+            .lock()
+            .unwrap()
+            .write_many(values, self)
+    }
+
+    pub(crate) fn write_user_area(&self, offset: usize, data: u64) -> Result<()> {
+        ptrace::write_user(
+            self.current_thread,
+            offset as ptrace::AddressType,
+            data as libc::c_long,
+        )
+        .map_err(SdbError::ptrace("pokeuser", self.current_thread))
+    }
+
+    // have to write fprs all at once
+    pub(crate) fn write_fprs(&self, fprs: libc::user_fpregs_struct) -> Result<()> {
+        ptrace::setregset::<ptrace::regset::NT_PRFPREG>(self.current_thread, fprs)
+            .map_err(SdbError::ptrace("setfpregs", self.current_thread))
+    }
+
+    pub(crate) fn write_gprs(&self, gprs: libc::user_regs_struct) -> Result<()> {
+        ptrace::setregs(self.current_thread, gprs)
+            .map_err(SdbError::ptrace("setregs", self.current_thread))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::fd::AsFd;
+    use std::path::Path;
+
+    use nix::poll::{PollFd, PollFlags};
+
+    use super::*;
+    use crate::expr::Expr;
+
+    #[test]
+    fn process_attach_success() {
+        let target = Process::launch("yes", false, None).unwrap();
+
+        let process = Process::attach(target.get_id().as_raw());
+        assert!(process.is_ok());
+        let process = process.unwrap();
+
+        assert_eq!(process.get_status().unwrap(), 't');
+    }
+
+    #[test]
+    fn process_attach_invalid_pid() {
+        let process = Process::attach(0);
+        assert!(process.is_err());
+        assert!(matches!(
+            process,
+            std::result::Result::Err(SdbError::Ptrace { .. })
+        ));
+    }
+
+    #[test]
+    fn process_attach_interrupts_without_signals() {
+        let target = Process::launch("yes", false, None).unwrap();
+        let mut process = Process::attach(target.get_id().as_raw()).unwrap();
+
+        // nothing was left queued by attaching
+        process.resume().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert_eq!(process.wait_on_signal_nonblocking().unwrap(), None);
+
+        process.interrupt().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Stopped);
+        assert_eq!(reason.signal(), Some(signal::SIGSTOP));
+    }
+
+    #[test]
+    fn handle_interrupts_seized_process_without_sigstop() {
+        let target = Process::launch("yes", false, None).unwrap();
+        let mut process = Process::attach(target.get_id().as_raw()).unwrap();
+        process.resume().unwrap();
+
+        // nothing's sent to the process, the waiting thread interrupts it
+        let sigstop = 1 << (libc::SIGSTOP - 1);
+        process.handle().interrupt().unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        let status = procfs::process::Process::new(target.get_id().as_raw())
+            .unwrap()
+            .status()
+            .unwrap();
+        assert_eq!(status.sigpnd & sigstop, 0);
+        assert_eq!(status.shdpnd & sigstop, 0);
+        assert_ne!(process.get_status().unwrap(), 't');
+
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Stopped);
+
+        // the way Ctrl-C gets through while the control thread waits
+        process.resume().unwrap();
+        let handle = process.handle();
+        let interrupter = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            handle.interrupt().unwrap();
+        });
+        let reason = process.wait_on_signal().unwrap();
+        interrupter.join().unwrap();
+        assert_eq!(reason.reason, ProcessState::Stopped);
+
+        // and no SIGSTOP is left to report once it runs again
+        process.resume().unwrap();
+        assert!(matches!(
+            process.wait_on_signal_timeout(Duration::from_millis(100)),
+            Err(SdbError::Timeout(_))
+        ));
+    }
+
+    #[test]
+    fn wait_on_signal_timeout() {
+        let mut process = Process::launch("test/targets/spin", true, None).unwrap();
+        process.resume().unwrap();
+
+        let started = Instant::now();
+        assert!(matches!(
+            process.wait_on_signal_timeout(Duration::from_millis(50)),
+            Err(SdbError::Timeout(_))
+        ));
+        assert!(started.elapsed() >= Duration::from_millis(50));
+
+        // still running, and the stop's there for the next wait
+        process.handle().interrupt().unwrap();
+        let reason = process
+            .wait_on_signal_timeout(Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(reason.reason, ProcessState::Stopped);
+        assert_eq!(reason.signal(), Some(signal::SIGSTOP));
+    }
+
+    #[test]
+    fn killed_from_outside() {
+        let mut process = Process::launch("test/targets/spin", true, None).unwrap();
+        let handle = process.handle();
+        let pidfd = handle.pidfd().unwrap();
+        assert!(!handle.has_exited());
+        assert!(handle.get_status().is_ok());
+
+        signal::kill(process.get_id(), signal::SIGKILL).unwrap();
+        let mut fds = [PollFd::new(pidfd.as_fd(), PollFlags::POLLIN)];
+        assert_eq!(nix::poll::poll(&mut fds, 5000u16).unwrap(), 1);
+
+        // gone before it's been waited on
+        assert!(handle.has_exited());
+        assert_eq!(handle.get_state(), ProcessState::Stopped);
+        assert!(handle.get_status().is_err());
+
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Terminated);
+        assert_eq!(reason.signal(), Some(signal::SIGKILL));
+    }
+
+    #[test]
+    fn detach_lets_the_process_go_and_kill_reaps_it() {
+        let target = Process::launch("yes", false, None).unwrap();
+        let pid = target.get_id();
+
+        let mut process = Process::attach(pid.as_raw()).unwrap();
+        process.detach().unwrap();
+        assert!(matches!(process.detach(), Err(SdbError::Other(..))));
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert_ne!(target.get_status().unwrap(), 't');
+        drop(process);
+
+        let mut process = Process::attach(pid.as_raw()).unwrap();
+        process.kill().unwrap();
+        assert_eq!(process.get_state(), ProcessState::Terminated);
+        assert!(test::process_exists(pid).is_err());
+        assert!(process.kill().is_err());
+    }
+
+    #[test]
+    fn detaching_puts_the_original_code_back() {
+        let tick = |target: &Target| {
+            let (elf, symbol) = target.modules().find_function("tick").unwrap();
+            elf.symbol_address(symbol)
+        };
+
+        let mut target = Target::launch("test/targets/counter", None).unwrap();
+        let address = tick(&target);
+        test::break_at_function(&mut target, "tick");
+        let process = target.process_mut();
+        let original = process.read_memory_without_traps(address, 1).unwrap();
+        process.resume().unwrap();
+        process.wait_on_signal().unwrap();
+        assert_eq!(process.read_memory(address, 1).unwrap(), [0xcc]);
+
+        process.detach().unwrap();
+        assert_eq!(process.read_memory(address, 1).unwrap(), original);
+        // still running through tick without a trap to stop it
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(matches!(process.get_status().unwrap(), 'R' | 'S'));
+        process.kill().unwrap();
+
+        // the same when the debugger goes away without detaching
+        let launched = Process::launch("test/targets/counter", false, None).unwrap();
+        let mut target = Target::attach(launched.get_id().as_raw()).unwrap();
+        let address = tick(&target);
+        test::break_at_function(&mut target, "tick");
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+        drop(target);
+
+        assert_eq!(launched.read_memory(address, 1).unwrap(), original);
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(matches!(launched.get_status().unwrap(), 'R' | 'S'));
+    }
+
+    #[test]
+    fn exit_code_and_termination_signal() {
+        let mut process = Process::launch("false", true, None).unwrap();
+        assert_eq!(process.exit_code(), None);
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Exited);
+        assert_eq!(process.exit_code(), Some(1));
+        assert_eq!(process.termination_signal(), None);
+
+        let mut process = Process::launch("test/targets/crash", true, None).unwrap();
+        process.resume().unwrap();
+        process.wait_on_signal().unwrap();
+        process.resume().unwrap();
+        process.wait_on_signal().unwrap();
+        assert_eq!(process.exit_code(), None);
+        assert_eq!(process.termination_signal(), Some(signal::SIGSEGV));
+
+        let mut process = Process::launch("yes", true, None).unwrap();
+        process.kill().unwrap();
+        assert_eq!(process.termination_signal(), Some(signal::SIGKILL));
+    }
+
+    #[test]
+    fn process_launch_success() {
         let process = Process::launch("yes", true, None);
         assert!(process.is_ok());
         let process = process.unwrap();
 
-        assert_eq!(test::process_exists(process.get_id()), Ok(()));
+        assert_eq!(test::process_exists(process.get_id()), Ok(()));
+    }
+
+    #[test]
+    fn process_launch_no_such_program() {
+        let process = Process::launch("you_do_not_have_to_be_good", true, None);
+        assert!(process.is_err());
+        assert!(matches!(
+            process,
+            std::result::Result::Err(SdbError::Child(..))
+        ));
+    }
+
+    #[test]
+    fn process_launch_dies_after_exec() {
+        // an executable whose only segment is in kernel space, exec gets past the point of no
+        // return before mapping it fails and the kernel kills the process
+        let address = 0xffff_8000_0000_0000_u64;
+        let mut elf = b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0".to_vec();
+        elf.extend(2_u16.to_le_bytes()); // ET_EXEC
+        elf.extend(0x3e_u16.to_le_bytes()); // x86-64
+        elf.extend(1_u32.to_le_bytes());
+        elf.extend(address.to_le_bytes());
+        elf.extend(64_u64.to_le_bytes()); // program headers
+        elf.extend(0_u64.to_le_bytes());
+        elf.extend(0_u32.to_le_bytes());
+        for half in [64_u16, 56, 1, 0, 0, 0] {
+            elf.extend(half.to_le_bytes());
+        }
+        elf.extend(1_u32.to_le_bytes()); // PT_LOAD
+        elf.extend(5_u32.to_le_bytes()); // R+X
+        for field in [0, address, address, 120, 0x1000, 0x1000] {
+            elf.extend(field.to_le_bytes());
+        }
+
+        let path = std::env::temp_dir().join(format!("sdb-unmappable-{}", std::process::id()));
+        std::fs::write(&path, elf).unwrap();
+        std::fs::set_permissions(&path, std::os::unix::fs::PermissionsExt::from_mode(0o755))
+            .unwrap();
+        let result = Process::launch(path.to_str().unwrap(), true, None);
+        std::fs::remove_file(&path).unwrap();
+        let Err(SdbError::Child(message)) = result else {
+            panic!("expected a child error, got {:?}", result.map(|_| ()));
+        };
+        assert!(
+            message.ends_with("terminated during launch with signal Some(SIGSEGV)"),
+            "{}",
+            message
+        );
+
+        // a program that exits right away still stops at exec, so it's only seen on resume
+        let mut process = Process::launch("false", true, None).unwrap();
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Exited);
+        assert_eq!(reason.info, 1);
+
+        // what it writes to stderr on its way out ends up in the error
+        let mut process = LaunchConfig::new("/bin/sh")
+            .args(["-c", "echo 'libgone.so: no such file' >&2; exit 127"])
+            .stderr(Redirect::events())
+            .launch()
+            .unwrap();
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Exited);
+        let SdbError::Child(message) = process.launch_error("/bin/sh", &reason) else {
+            panic!("expected a child error");
+        };
+        assert!(
+            message.ends_with("exited during launch with status 127: libgone.so: no such file"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn process_resume_success() {
+        {
+            let mut process = Process::launch("yes", true, None).unwrap();
+
+            let result = process.resume();
+            assert!(result.is_ok());
+
+            let status = process.get_status().unwrap();
+            assert!(status == 'R' || status == 'S');
+        }
+
+        {
+            let target = Process::launch("yes", false, None).unwrap();
+            let mut process = Process::attach(target.get_id().as_raw()).unwrap();
+
+            let result = process.resume();
+            assert!(result.is_ok());
+
+            let status = process.get_status().unwrap();
+            assert!(status == 'R' || status == 'S');
+        }
+    }
+
+    #[test]
+    fn process_resume_already_exited() {
+        let mut process = Process::launch("echo", true, None).unwrap();
+        process.resume().unwrap();
+        process.wait_on_signal().unwrap();
+
+        let result = process.resume();
+        assert!(matches!(
+            result,
+            std::result::Result::Err(SdbError::Ptrace { .. })
+        ));
+    }
+
+    #[test]
+    fn process_handle_is_thread_safe() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Process>();
+        assert_send_sync::<ProcessHandle>();
+
+        let mut process = Process::launch("yes", true, None).unwrap();
+        let handle = process.handle();
+        assert_eq!(handle.get_id(), process.get_id());
+
+        process.resume().unwrap();
+
+        let state = std::thread::spawn(move || handle.get_state())
+            .join()
+            .unwrap();
+        assert_eq!(state, ProcessState::Running);
+    }
+
+    #[test]
+    fn process_resource_usage() {
+        let mut process = Process::launch("yes", true, None).unwrap();
+        process.resume().unwrap();
+
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let usage = process.handle().get_resource_usage().unwrap();
+        assert!(usage.status == 'R' || usage.status == 'S');
+        assert!(usage.rss > 0);
+    }
+
+    #[test]
+    fn process_controller_works_across_threads() {
+        let mut channel = Pipe::new(false).unwrap();
+        let controller = std::sync::Arc::new(
+            ProcessController::launch("test/targets/reg_write", true, channel.write.take())
+                .unwrap(),
+        );
+
+        {
+            let controller = controller.clone();
+            std::thread::spawn(move || {
+                controller.resume().unwrap();
+                controller.wait_on_signal().unwrap();
+            })
+            .join()
+            .unwrap();
+        }
+
+        controller
+            .write_register_by_id(RegisterId::rsi, 0xcafecafe_u64.into())
+            .unwrap();
+        controller.resume().unwrap();
+        controller.wait_on_signal().unwrap();
+
+        let output = String::from_utf8(channel.read().unwrap()).unwrap();
+        assert_eq!(output, "0xcafecafe");
+    }
+
+    #[test]
+    fn launch_tees_stdout() {
+        let mut capture = Pipe::new(true).unwrap();
+        let mut passthrough = Pipe::new(true).unwrap();
+        let mut process = LaunchConfig::new("test/targets/step")
+            .stdout(Redirect::tee(
+                capture.take_write().unwrap(),
+                passthrough.take_write().unwrap(),
+            ))
+            .launch()
+            .unwrap();
+
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Exited);
+
+        assert_eq!(capture.read().unwrap(), b"3\n");
+        assert_eq!(passthrough.read().unwrap(), b"3\n");
+
+        // the relay closes its outputs once the process is done
+        assert!(capture.read().unwrap().is_empty());
+    }
+
+    #[test]
+    fn captured_output_comes_before_stops() {
+        let mut target = Target::launch_with(
+            LaunchConfig::new("test/targets/output")
+                .stdout(Redirect::events())
+                .stderr(Redirect::events()),
+        )
+        .unwrap();
+        let events = target.events().unwrap();
+        test::break_at_function(&mut target, "marker");
+
+        for _ in 0..3 {
+            target.process_mut().resume().unwrap();
+            target.wait_on_signal().unwrap();
+        }
+
+        let mut times = Vec::new();
+        let events = events
+            .try_iter()
+            .filter_map(|event| match event {
+                DebugEvent::Output { stream, line, time } => {
+                    times.push(time);
+                    Some(format!("{}: {}", stream.as_str(), line))
+                }
+                DebugEvent::BreakpointHit { .. } => Some("hit".to_owned()),
+                DebugEvent::Exited(code) => Some(format!("exited {}", code)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                "stdout: before",
+                "hit",
+                "stdout: partial",
+                "hit",
+                "stderr: after",
+                "exited 0"
+            ]
+        );
+        assert!(times.is_sorted());
+    }
+
+    #[test]
+    fn launch_redirect_dup_leaves_fd_open() {
+        let channel = Pipe::new(true).unwrap();
+        let mut process = LaunchConfig::new("test/targets/step")
+            .stdout(Redirect::dup(channel.write.as_ref().unwrap()).unwrap())
+            .launch()
+            .unwrap();
+
+        process.resume().unwrap();
+        process.wait_on_signal().unwrap();
+
+        assert_eq!(channel.read().unwrap(), b"3\n");
+        assert!(channel.write("still open").is_ok());
+    }
+
+    #[test]
+    fn launch_on_a_pty() {
+        let mut pty = Pty::new().unwrap();
+        pty.set_echo(false).unwrap();
+        let master = pty.take_master().unwrap();
+        let mut process = LaunchConfig::new("sh")
+            .args(["-c", "read line; echo got $line; tty"])
+            .terminal(pty.take_slave().unwrap())
+            .launch()
+            .unwrap();
+
+        launch::write_all(&master, b"hi\n").unwrap();
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Exited);
+
+        // EIO once the process has closed the last of the slave
+        let mut output = Vec::new();
+        let mut buf = [0; 1024];
+        while let Ok(count @ 1..) = nix::unistd::read(&master, &mut buf) {
+            output.extend_from_slice(&buf[..count]);
+        }
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("got hi\r\n/dev/pts/"), "{}", output);
+    }
+
+    #[test]
+    fn launched_process_has_its_own_process_group() {
+        let process = Process::launch("test/targets/step", true, None).unwrap();
+        let pid = process.get_id();
+        assert_eq!(nix::unistd::getpgid(Some(pid)).unwrap(), pid);
+        assert_eq!(
+            nix::unistd::getsid(Some(pid)).unwrap(),
+            nix::unistd::getsid(None).unwrap()
+        );
+
+        // on a terminal of its own it's in a session of its own
+        let mut pty = Pty::new().unwrap();
+        let process = LaunchConfig::new("test/targets/step")
+            .terminal(pty.take_slave().unwrap())
+            .launch()
+            .unwrap();
+        let pid = process.get_id();
+        assert_eq!(nix::unistd::getsid(Some(pid)).unwrap(), pid);
+    }
+
+    #[test]
+    fn set_inferior_tty() {
+        let mut pty = Pty::new().unwrap();
+        let master = pty.take_master().unwrap();
+        let tty = nix::unistd::ttyname(pty.take_slave().unwrap()).unwrap();
+
+        let mut target = Target::launch_with(LaunchConfig::new("tty")).unwrap();
+        assert!(target.set_inferior_tty("/dev/null").is_err());
+        assert_eq!(target.inferior_tty(), None);
+
+        // the running process keeps the terminal it has
+        target.set_inferior_tty(&tty).unwrap();
+        assert_eq!(target.inferior_tty(), Some(tty.as_path()));
+        target.restart(false).unwrap();
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+        drop(target);
+
+        let mut output = Vec::new();
+        let mut buf = [0; 1024];
+        while let Ok(count @ 1..) = nix::unistd::read(&master, &mut buf) {
+            output.extend_from_slice(&buf[..count]);
+        }
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!("{}\r\n", tty.display())
+        );
+    }
+
+    #[test]
+    fn launch_in_another_directory() {
+        let mut channel = Pipe::new(true).unwrap();
+        let mut process = LaunchConfig::new("pwd")
+            .cwd("/tmp")
+            .stdout(Redirect::fd(channel.take_write().unwrap()))
+            .launch()
+            .unwrap();
+        process.resume().unwrap();
+        process.wait_on_signal().unwrap();
+        assert_eq!(channel.read().unwrap(), b"/tmp\n");
+
+        // the program's still found from here, and found again on restart
+        let mut target =
+            Target::launch_with(LaunchConfig::new("test/targets/step").cwd("/")).unwrap();
+        let cwd = |target: &Target| {
+            std::fs::read_link(format!("/proc/{}/cwd", target.process().get_id())).unwrap()
+        };
+        assert_eq!(cwd(&target), Path::new("/"));
+
+        target.set_working_directory("/tmp");
+        assert_eq!(target.working_directory(), Some(Path::new("/tmp")));
+        target.restart(false).unwrap();
+        assert_eq!(cwd(&target), Path::new("/tmp"));
+    }
+
+    #[test]
+    fn launch_with_files() {
+        let dir = std::env::temp_dir().join(format!("sdb-redirect-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("in"), "fed in\n").unwrap();
+        std::fs::write(dir.join("out"), "left over from before\n").unwrap();
+
+        let mut target = Target::launch_with(
+            LaunchConfig::new("sh")
+                .args(["-c", "cat; echo oops >&2"])
+                .stdin(Redirect::file(dir.join("in")))
+                .stdout(Redirect::file(dir.join("out")))
+                .stderr(Redirect::file(dir.join("err"))),
+        )
+        .unwrap();
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+        let read = |name| std::fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read("out"), "fed in\n");
+        assert_eq!(read("err"), "oops\n");
+
+        // opened again, from the start
+        std::fs::write(dir.join("in"), "fed in again\n").unwrap();
+        target.restart(false).unwrap();
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+        assert_eq!(read("out"), "fed in again\n");
+
+        let result = LaunchConfig::new("sh")
+            .stdin(Redirect::file(dir.join("missing")))
+            .launch();
+        assert!(result.is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn pipe_reads() {
+        let data = (0..3000).map(|i| i as u8).collect::<Vec<_>>();
+
+        let mut channel = Pipe::new(true).unwrap().with_buffer_size(100);
+        channel.write(&data).unwrap();
+        assert_eq!(channel.read().unwrap(), data[..100]);
+        assert_eq!(channel.read_exact(1000).unwrap(), data[100..1100]);
+        channel.close_write();
+        assert_eq!(channel.read_to_end().unwrap(), data[1100..]);
+
+        let mut channel = Pipe::new(true).unwrap();
+        channel.write(&data[..10]).unwrap();
+        channel.close_write();
+        assert!(channel.read_exact(11).is_err());
+    }
+
+    #[test]
+    fn nonblocking_pipe() {
+        let mut channel = Pipe::new(true).unwrap();
+        channel.set_nonblocking(true).unwrap();
+        let mut process = LaunchConfig::new("test/targets/step")
+            .stdout(Redirect::fd(channel.take_write().unwrap()))
+            .launch()
+            .unwrap();
+        assert!(channel.write_fd().is_none());
+
+        // nothing's written until it runs
+        assert_eq!(channel.try_read().unwrap(), None);
+        assert!(!channel.wait_readable(Duration::ZERO).unwrap());
+
+        process.resume().unwrap();
+        assert!(channel.wait_readable(Duration::from_secs(5)).unwrap());
+        process.wait_on_signal().unwrap();
+        assert_eq!(channel.try_read().unwrap(), Some(b"3\n".to_vec()));
+    }
+
+    #[test]
+    fn output_capture() {
+        let output = OutputCapture::new();
+        let passthrough = Pipe::new(true).unwrap();
+        let stdout = output
+            .redirect_and_dup(passthrough.write_fd().unwrap())
+            .unwrap();
+        let mut target = Target::launch_with(
+            LaunchConfig::new("sh")
+                .args(["-c", "echo out; echo err >&2"])
+                .stdout(stdout)
+                .stderr(output.redirect()),
+        )
+        .unwrap();
+        assert!(output.contents().is_empty());
+
+        // each stream has a pipe of its own, which one's read first is up to the scheduler
+        let lines = |data: Vec<u8>| {
+            let mut lines = String::from_utf8(data)
+                .unwrap()
+                .lines()
+                .map(str::to_owned)
+                .collect::<Vec<_>>();
+            lines.sort();
+            lines
+        };
+
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+        assert_eq!(lines(output.wait_for_end()), ["err", "out"]);
+        assert_eq!(passthrough.read().unwrap(), b"out\n");
+
+        // a restart adds to what's there
+        target.restart(false).unwrap();
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+        output.wait_for_end();
+        assert_eq!(lines(output.take()), ["err", "err", "out", "out"]);
+        assert_eq!(output.text(), "");
+
+        assert!(
+            LaunchConfig::new("cat")
+                .stdin(output.redirect())
+                .launch()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn output_capture_with_events() {
+        let output = OutputCapture::new();
+        let passthrough = Pipe::new(true).unwrap();
+        let process = LaunchConfig::new("sh")
+            .args(["-c", "echo out; printf partial"])
+            .stdout(
+                output
+                    .redirect_events_and_dup(passthrough.write_fd().unwrap())
+                    .unwrap(),
+            )
+            .launch()
+            .unwrap();
+        let mut target = Target::new(process).unwrap();
+        let events = target.events().unwrap();
+
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+        assert_eq!(output.wait_for_end(), b"out\npartial");
+        assert_eq!(passthrough.read().unwrap(), b"out\npartial");
+        let lines = events
+            .try_iter()
+            .filter_map(|event| match event {
+                DebugEvent::Output { line, .. } => Some(line),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(lines, ["out", "partial"]);
+    }
+
+    #[test]
+    fn launch_failure_closes_redirects() {
+        let mut channel = Pipe::new(true).unwrap();
+        let result = LaunchConfig::new("you_do_not_have_to_be_good")
+            .stdout(Redirect::fd(channel.take_write().unwrap()))
+            .launch();
+        assert!(result.is_err());
+
+        // nothing is left holding the write end
+        assert!(channel.read().unwrap().is_empty());
+    }
+
+    #[test]
+    fn fork_with_reads_registers() {
+        let mut process = unsafe {
+            Process::fork_with(|| {
+                std::arch::asm!("mov rsi, 0xcafecafe", "int3", out("rsi") _);
+                0
+            })
+        }
+        .unwrap();
+
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Stopped);
+        assert_eq!(reason.signal(), Some(signal::Signal::SIGTRAP));
+        assert_eq!(
+            process
+                .read_register_by_id(RegisterId::rsi)
+                .unwrap()
+                .to_u64(),
+            Some(0xcafecafe)
+        );
+
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Exited);
+        assert_eq!(reason.info, 0);
+    }
+
+    #[test]
+    fn logpoints_print_and_carry_on() {
+        #[inline(never)]
+        extern "C" fn traced(count: u64, name: *const u8) {
+            std::hint::black_box((count, name));
+        }
+
+        let name = c"hello";
+        let process = unsafe {
+            Process::fork_with(|| {
+                for count in 0..3 {
+                    traced(count, name.as_ptr().cast());
+                }
+                0
+            })
+        }
+        .unwrap();
+        let mut target = Target::new(process).unwrap();
+        let events = target.events().unwrap();
+
+        let address = VirtAddr::new(traced as extern "C" fn(u64, *const u8) as usize as u64);
+        let process = target.process_mut();
+        let id = process.create_breakpoint_site(address, false).unwrap();
+        process.enable_breakpoint_site(id).unwrap();
+
+        let args = ["rdi", "$rsi", "rip", "*0"]
+            .map(|arg| arg.parse::<LogArg>().unwrap())
+            .to_vec();
+        let log = LogFormat::new("%d %s %p %x%%", args).unwrap();
+        target.set_breakpoint_log(id, Some(log)).unwrap();
+        assert_eq!(
+            target
+                .process()
+                .breakpoint_sites()
+                .get_by_id(id)
+                .unwrap()
+                .kind(),
+            StoppointKind::Logpoint
+        );
+
+        target.process_mut().resume().unwrap();
+        let reason = target.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Exited);
+        assert_eq!(reason.info, 0);
+
+        let messages = events
+            .try_iter()
+            .filter_map(|event| match event {
+                DebugEvent::Logged { message, .. } => Some(message),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(messages.len(), 3);
+        for (count, message) in messages.iter().enumerate() {
+            // reading address 0 fails without losing the rest of the line
+            assert!(message.starts_with(&format!("{} hello {:#x} <", count, address.addr())));
+            assert!(message.ends_with(">%"));
+        }
+        assert_eq!(
+            target
+                .process()
+                .breakpoint_sites()
+                .get_by_id(id)
+                .unwrap()
+                .hit_count(),
+            3
+        );
+
+        assert!(LogFormat::new("%d %d", vec![LogArg::Literal(1)]).is_err());
+        assert!(LogFormat::new("%q", vec![LogArg::Literal(1)]).is_err());
+    }
+
+    #[test]
+    fn source_map_matches_whole_components() {
+        let mut map = SourceMap::default();
+        map.substitute("/build/src", "/home/me/src");
+        map.substitute("/build", "/elsewhere");
+        map.substitute("/build/src", "/home/me/checkout");
+
+        let resolve = |map: &SourceMap, path: &str| map.resolve(Path::new(path));
+        assert_eq!(
+            resolve(&map, "/build/src/main.c"),
+            Path::new("/home/me/checkout/main.c")
+        );
+        assert_eq!(
+            resolve(&map, "/build/srcs/main.c"),
+            Path::new("/elsewhere/srcs/main.c")
+        );
+        assert_eq!(resolve(&map, "/other/main.c"), Path::new("/other/main.c"));
+
+        assert!(map.remove("/build"));
+        assert!(!map.remove("/build"));
+        assert_eq!(
+            resolve(&map, "/build/srcs/main.c"),
+            Path::new("/build/srcs/main.c")
+        );
+    }
+
+    #[test]
+    fn target_lists_source() {
+        let target = Target::launch("test/targets/variables", None).unwrap();
+
+        let location = target.find_source_location("area").unwrap();
+        assert!(location.file.ends_with("variables.c"));
+        assert_eq!(location.line, 25);
+        let location = target.find_source_location("variables.c:28").unwrap();
+        assert_eq!(location.line, 28);
+        assert!(target.find_source_location("nowhere.c:1").is_err());
+
+        let lines = target.source_lines(&location.file, 28, 29).unwrap();
+        assert_eq!(
+            lines,
+            [
+                (28, "    return width * height * factor;".to_owned()),
+                (29, "}".to_owned())
+            ]
+        );
+        assert!(target.source_lines(&location.file, 1000, 1010).is_err());
+    }
+
+    #[test]
+    fn logpoint_placeholders() {
+        let mut target = Target::launch("test/targets/variables", None).unwrap();
+        let events = target.events().unwrap();
+        let id = target.create_line_breakpoint("variables.c", 28).unwrap();
+
+        let log = LogFormat::new(
+            "{width}x{height} {shape->corners[1]} {numbers[3]:x} {{{factor + 1}}} {nothing}",
+            vec![],
+        )
+        .unwrap();
+        target.set_breakpoint_log(id, Some(log)).unwrap();
+
+        target.process_mut().resume().unwrap();
+        let reason = target.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Exited);
+        assert_eq!(reason.info, 0);
+
+        let messages = events
+            .try_iter()
+            .filter_map(|event| match event {
+                DebugEvent::Logged { message, .. } => Some(message),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // factor + 1 isn't a variable, and expressions only know symbols
+        assert_eq!(
+            messages,
+            [
+                "3x4 {x = 4, y = 6} 4 {<expression error: No symbol named factor>} \
+              <expression error: No symbol named nothing>"
+            ]
+        );
+
+        assert!(LogFormat::new("{width", vec![]).is_err());
+        assert!(LogFormat::new("width}", vec![]).is_err());
+        assert!(LogFormat::new("{width:q}", vec![]).is_err());
+        assert!(LogFormat::new("{}", vec![]).is_err());
+    }
+
+    #[test]
+    fn write_registers_in_a_batch() {
+        let mut process = unsafe {
+            Process::fork_with(|| {
+                std::arch::asm!("int3");
+                0
+            })
+        }
+        .unwrap();
+        process.resume().unwrap();
+        process.wait_on_signal().unwrap();
+
+        // every register reads back from how it's displayed
+        for info in register_infos() {
+            let value = process.read_register_by_id(info.id()).unwrap();
+            let parsed = RegisterValue::parse(info, &value.to_string()).unwrap();
+            assert_eq!(parsed.to_string(), value.to_string(), "{}", info.name());
+        }
+
+        let xmm1 = std::array::from_fn::<u8, 16, _>(|idx| idx as u8);
+        process
+            .write_registers([
+                (RegisterId::rsi, 0x1111_2222_3333_4444_u64.into()),
+                // later values win where they overlap
+                (RegisterId::esi, 0xcafecafe_u32.into()),
+                (RegisterId::st0, RegisterValue::LongDouble(-2.5)),
+                (RegisterId::xmm1, RegisterValue::Byte128(xmm1)),
+            ])
+            .unwrap();
+
+        // what the thread has now, not what was cached on the way in
+        process.read_all_registers(process.current_thread).unwrap();
+        let read = |id| process.read_register_by_id(id).unwrap().to_string();
+        assert_eq!(read(RegisterId::rsi), "0x11112222cafecafe");
+        assert_eq!(read(RegisterId::st0), "-2.5");
+        assert_eq!(
+            read(RegisterId::xmm1),
+            RegisterValue::Byte128(xmm1).to_string()
+        );
+
+        for value in [0.0, 1.0, -2.5, 1e300, f64::INFINITY] {
+            assert_eq!(types::extended_to_f64(types::f64_to_extended(value)), value);
+        }
+
+        let esi = register_info_by_id(RegisterId::esi);
+        assert!(RegisterValue::parse(esi, "0x100000000").is_err());
+        assert!(RegisterValue::parse(register_info_by_id(RegisterId::xmm1), "[0x01]").is_err());
+    }
+
+    #[test]
+    fn expressions_and_conditional_breakpoints() {
+        #[unsafe(no_mangle)]
+        static SDB_TEST_EXPR_VALUE: u64 = 42;
+
+        #[inline(never)]
+        extern "C" fn traced(count: u64) {
+            std::hint::black_box(count);
+        }
+
+        let process = unsafe {
+            Process::fork_with(|| {
+                for count in 0..10 {
+                    traced(count);
+                }
+                std::hint::black_box(&SDB_TEST_EXPR_VALUE);
+                0
+            })
+        }
+        .unwrap();
+        let mut target = Target::new(process).unwrap();
+
+        let address = VirtAddr::new(traced as extern "C" fn(u64) as usize as u64);
+        let process = target.process_mut();
+        let id = process.create_breakpoint_site(address, false).unwrap();
+        process.enable_breakpoint_site(id).unwrap();
+        target
+            .set_breakpoint_condition(id, Some("$rdi == 7".parse().unwrap()))
+            .unwrap();
+
+        target.process_mut().resume().unwrap();
+        let reason = target.wait_on_signal().unwrap();
+        assert_eq!(reason.breakpoint_site, Some(id));
+        // only hits where the condition holds count
+        assert_eq!(
+            target
+                .process()
+                .breakpoint_sites()
+                .get_by_id(id)
+                .unwrap()
+                .hit_count(),
+            1
+        );
+
+        let evaluate = |target: &Target, text: &str| text.parse::<Expr>()?.evaluate(target);
+        assert_eq!(evaluate(&target, "$rdi").unwrap(), 7);
+        assert_eq!(evaluate(&target, "1 + 2 * 3").unwrap(), 7);
+        assert_eq!(evaluate(&target, "(1 + 2) * 3").unwrap(), 9);
+        assert_eq!(evaluate(&target, "10 - 4 - 3").unwrap(), 3);
+        assert_eq!(evaluate(&target, "1 << 4 | 1").unwrap(), 17);
+        assert_eq!(evaluate(&target, "-1 < 0 && !0").unwrap(), 1);
+        assert_eq!(evaluate(&target, "0x10 >= 16 != 0").unwrap(), 1);
+        assert_eq!(evaluate(&target, "0 && 1 / 0").unwrap(), 0);
+        assert!(evaluate(&target, "1 / 0").is_err());
+        assert_eq!(
+            evaluate(&target, "*$rsp").unwrap() as u64,
+            target
+                .read_u64(VirtAddr::new(
+                    target
+                        .read_register_by_id(RegisterId::rsp)
+                        .unwrap()
+                        .to_u64()
+                        .unwrap()
+                ))
+                .unwrap()
+        );
+        assert_eq!(evaluate(&target, "*SDB_TEST_EXPR_VALUE - 40").unwrap(), 2);
+        assert!(evaluate(&target, "no_such_symbol").is_err());
+
+        assert!("1 +".parse::<Expr>().is_err());
+        assert!("(1".parse::<Expr>().is_err());
+        assert!("$nope".parse::<Expr>().is_err());
+        assert!("1 = 2".parse::<Expr>().is_err());
+        for text in [
+            "a + b * c",
+            "(a + b) * c",
+            "a - (b - c)",
+            "*($rsp + 8) & ~1",
+        ] {
+            assert_eq!(text.parse::<Expr>().unwrap().to_string(), text);
+        }
+
+        target.set_breakpoint_condition(id, None).unwrap();
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+        assert_eq!(evaluate(&target, "$rdi").unwrap(), 8);
+
+        target.process_mut().remove_breakpoint_site(id).unwrap();
+        target.process_mut().resume().unwrap();
+        let reason = target.wait_on_signal().unwrap();
+
+        assert_eq!(reason.reason, ProcessState::Exited);
+    }
+
+    #[test]
+    fn tls_blocks_and_tcb() {
+        thread_local! {
+            static SDB_TEST_TLS_VALUE: u64 = const { 42 };
+        }
+
+        // the child is a copy of this thread, thread-locals and all
+        let local = SDB_TEST_TLS_VALUE.with(|value| value as *const u64 as u64);
+        let target = Target::new(unsafe { Process::fork_with(|| 0) }.unwrap()).unwrap();
+
+        let tls = TlsInfo::read(&target).unwrap();
+        assert_ne!(tls.fs_base.addr(), 0);
+
+        let tcb = tls.tcb.unwrap();
+        assert_eq!(tcb.tcb, tls.fs_base);
+        assert_eq!(tcb.thread, tls.fs_base);
+        assert!(tcb.dtv_slots >= tls.blocks.len() as u64);
+
+        // the executable is always the first module with thread-locals
+        let exe = &tls.blocks[0];
+        assert_eq!(exe.path, std::env::current_exe().unwrap());
+        assert_eq!(exe.module_id, Some(1));
+        let address = exe.address.unwrap().addr();
+        assert!(address <= local && local < address + exe.template.size);
+        assert_eq!(target.read_u64(VirtAddr::new(local)).unwrap(), 42);
+
+        let libc = tls
+            .blocks
+            .iter()
+            .find(|block| block.path.to_string_lossy().contains("libc.so"))
+            .unwrap();
+        assert!(libc.module_id.unwrap() > 1);
+        // libc's block is in the static TLS area below the TCB
+        assert!(libc.address.unwrap() < tls.fs_base);
+    }
+
+    #[test]
+    fn heap_chunks_bins_and_problems() {
+        let mut target = Target::launch("test/targets/heap", None).unwrap();
+        test::break_at_function(&mut target, "inspect");
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+
+        let pointer = |name: &str| {
+            let (elf, symbol) = target.modules().find_variable(name).unwrap();
+            VirtAddr::new(target.read_u64(elf.symbol_address(symbol)).unwrap())
+        };
+        let (kept, thread_kept) = (pointer("kept"), pointer("thread_kept"));
+
+        let heap = target.heap().unwrap();
+        assert!(heap.problems.is_empty(), "{:?}", heap.problems);
+        let [main, thread] = &heap.arenas[..] else {
+            panic!("{:?}", heap.arenas);
+        };
+        assert!(main.main && main.address.is_some());
+
+        let stats = main.stats();
+        assert_eq!(stats.bins[&Some(HeapBin::Tcache)].count, 7);
+        assert_eq!(stats.bins[&Some(HeapBin::Fast)].count, 1);
+        assert!(stats.bins.contains_key(&Some(HeapBin::Unsorted)));
+        let chunk = main
+            .chunks
+            .iter()
+            .find(|chunk| chunk.user_address() == kept)
+            .unwrap();
+        assert_eq!((chunk.size, chunk.state), (0x70, ChunkState::InUse));
+        assert_eq!(main.chunks.last().unwrap().state, ChunkState::Top);
+
+        let chunk = thread
+            .chunks
+            .iter()
+            .find(|chunk| chunk.user_address() == thread_kept)
+            .unwrap();
+        assert!(chunk.non_main_arena());
+        assert_eq!((chunk.size, chunk.state), (0x50, ChunkState::InUse));
+
+        // the chunk before the kept one's free, so its prev_size has to match
+        target
+            .process_mut()
+            .write_memory(kept - 16, &0x1234u64.to_le_bytes())
+            .unwrap();
+        let problems = target.heap().unwrap().problems;
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].address, kept - 16);
+        assert!(problems[0].message.contains("prev_size 0x1234"));
+    }
+
+    #[test]
+    fn trace_allocations_outstanding() {
+        let mut target = Target::launch("test/targets/heap", None).unwrap();
+        let events = target.events().unwrap();
+        target.trace_allocations().unwrap();
+        test::break_at_function(&mut target, "inspect");
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+
+        let pointer = |name: &str| {
+            let (elf, symbol) = target.modules().find_variable(name).unwrap();
+            VirtAddr::new(target.read_u64(elf.symbol_address(symbol)).unwrap())
+        };
+        let (kept, thread_kept) = (pointer("kept"), pointer("thread_kept"));
+
+        let outstanding = target.process().outstanding_allocations();
+        let allocation = outstanding.iter().find(|a| a.address == kept).unwrap();
+        assert_eq!(allocation.size, 100);
+        assert_eq!(allocation.function, AllocFunction::Malloc);
+        assert_eq!(allocation.tid, target.process().pid);
+        assert_eq!(
+            target.function_name_at(allocation.callers[0]).as_deref(),
+            Some("main")
+        );
+        let allocation = outstanding
+            .iter()
+            .find(|a| a.address == thread_kept)
+            .unwrap();
+        assert_eq!(allocation.size, 64);
+        assert_ne!(allocation.tid, target.process().pid);
+        // the small blocks and the big one were freed
+        assert!(!outstanding.iter().any(|a| a.size == 24 || a.size == 0x500));
+
+        let events = events.try_iter().collect::<Vec<_>>();
+        let frees = events
+            .iter()
+            .filter(|event| {
+                matches!(event, DebugEvent::Allocation(event) if event.function == AllocFunction::Free)
+            })
+            .count();
+        assert!(frees >= 9, "{:?}", events);
+
+        target.process_mut().stop_tracing_allocations().unwrap();
+        assert!(target.process().outstanding_allocations().is_empty());
+        // inspect's and the dynamic linker's rendezvous are all that's left
+        assert_eq!(target.process().breakpoint_sites().iter().count(), 2);
+        target.process_mut().resume().unwrap();
+        assert_eq!(
+            target.wait_on_signal().unwrap().reason,
+            ProcessState::Exited
+        );
+    }
+
+    #[test]
+    fn got_slots_lazy_resolved_and_hooked() {
+        let mut target = Target::launch("test/targets/heap", None).unwrap();
+        let main = target.main_elf().unwrap().path().to_path_buf();
+        let got = |target: &Target, symbol: &str| {
+            let elf = target.modules().get_elf_by_path(&main).unwrap();
+            let entries = target.got(elf).unwrap();
+            entries
+                .into_iter()
+                .find(|entry| entry.symbol == symbol)
+                .unwrap()
+        };
+        assert_eq!(got(&target, "malloc").status, GotStatus::Unrelocated);
+
+        test::break_at_function(&mut target, "main");
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+        let entry = got(&target, "malloc");
+        assert_eq!(entry.status, GotStatus::Lazy);
+        let is_plt = |plt| target.main_elf().unwrap().is_plt(plt);
+        assert!(entry.plt.is_some_and(is_plt));
+        assert_eq!(got(&target, "__gmon_start__").status, GotStatus::Null);
+
+        test::break_at_function(&mut target, "inspect");
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+        // nothing's tracking modules, libc isn't known yet
+        target.refresh_modules().unwrap();
+        let entry = got(&target, "malloc");
+        assert_eq!(entry.status, GotStatus::Resolved);
+        let (libc, symbol) = target.modules().find_function("malloc").unwrap();
+        assert_eq!(entry.target, libc.symbol_address(symbol));
+
+        // the executable doesn't define malloc, so pointing the slot into it is a hook
+        let (elf, inspect) = target.modules().find_function("inspect").unwrap();
+        let inspect = elf.symbol_address(inspect);
+        target
+            .process_mut()
+            .write_memory(entry.slot, &inspect.addr().to_le_bytes())
+            .unwrap();
+        assert!(matches!(
+            got(&target, "malloc").status,
+            GotStatus::Unexpected(message) if message.contains("doesn't define malloc")
+        ));
+    }
+
+    #[test]
+    fn fork_with_shares_addresses() {
+        #[inline(never)]
+        extern "C" fn marker(value: u64) -> u64 {
+            std::hint::black_box(value)
+        }
+
+        let value = 0x1234_5678_u64;
+        let address = VirtAddr::new(&value as *const u64 as u64);
+        let mut process =
+            unsafe { Process::fork_with(|| if marker(value) == 0x1234_5678 { 7 } else { 1 }) }
+                .unwrap();
+
+        // the child's copy of the variable is where ours is
+        let bytes = process.read_memory(address, 8).unwrap();
+        assert_eq!(u64::from_ne_bytes(bytes.try_into().unwrap()), value);
+
+        // and so is its copy of the function
+        let function = VirtAddr::new(marker as extern "C" fn(u64) -> u64 as usize as u64);
+        let id = process.create_breakpoint_site(function, false).unwrap();
+        process.enable_breakpoint_site(id).unwrap();
+
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Stopped);
+        assert_eq!(process.get_pc().unwrap(), function);
+
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Exited);
+        assert_eq!(reason.info, 7);
+    }
+
+    #[test]
+    fn events_are_emitted() {
+        let mut target = Target::launch("test/targets/fatal", None).unwrap();
+        let events = target.events().unwrap();
+        target.catch_fatal(&["__assert_fail"]).unwrap();
+
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+
+        let events = events.try_iter().collect::<Vec<_>>();
+        assert!(events.iter().any(|event| matches!(
+            event,
+            DebugEvent::LibraryLoaded(path) if path.to_string_lossy().contains("libc")
+        )));
+        assert!(matches!(
+            events.last(),
+            Some(DebugEvent::BreakpointHit { .. })
+        ));
+    }
+
+    #[test]
+    fn function_hook_replaces_function() {
+        let mut target = Target::launch("test/targets/fatal", None).unwrap();
+
+        // libc is loaded by the time we get to main
+        test::break_at_function(&mut target, "main");
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+
+        target
+            .create_function_hook("__assert_fail", HookReplacement::Nop)
+            .unwrap();
+        assert_eq!(target.process().function_hooks().len(), 1);
+
+        target.process_mut().resume().unwrap();
+        let reason = target.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Exited);
+        assert_eq!(reason.info, 0);
+    }
+
+    #[test]
+    fn fork_follow_parent_scrubs_child() {
+        let mut target = Target::launch("test/targets/fork", None).unwrap();
+        let parent = target.process().get_id();
+        test::break_at_function(&mut target, "marker");
+        let process = target.process_mut();
+
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert!(reason.breakpoint_site.is_some());
+        assert_eq!(process.get_id(), parent);
+
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Exited);
+        assert_eq!(reason.info, 0);
+    }
+
+    #[test]
+    fn fork_follow_child() {
+        let mut target = Target::launch("test/targets/fork", None).unwrap();
+        let parent = target.process().get_id();
+        target
+            .process_mut()
+            .set_follow_fork_mode(FollowForkMode::Child);
+        test::break_at_function(&mut target, "marker");
+        let process = target.process_mut();
+
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert!(reason.breakpoint_site.is_some());
+        assert_ne!(process.get_id(), parent);
+        assert_eq!(process.handle().get_id(), process.get_id());
+
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Exited);
+        assert_eq!(reason.info, 0);
+    }
+
+    #[test]
+    fn read_only_memory_comes_from_the_file() {
+        let mut target = Target::launch("test/targets/step", None).unwrap();
+        let add = target
+            .modules()
+            .find_function("add")
+            .map(|(elf, symbol)| elf.symbol_address(symbol))
+            .unwrap();
+        let original = target.process().read_memory(add, 16).unwrap();
+
+        let id = target
+            .process_mut()
+            .create_breakpoint_site(add, false)
+            .unwrap();
+        target.process_mut().enable_breakpoint_site(id).unwrap();
+        assert_eq!(target.read_memory_without_traps(add, 16).unwrap(), original);
+
+        // behind the debugger's back, so only a read from the file misses it
+        let word = ptrace::read(
+            target.process().get_id(),
+            (add + 8).addr() as ptrace::AddressType,
+        )
+        .unwrap();
+        ptrace::write(
+            target.process().get_id(),
+            (add + 8).addr() as ptrace::AddressType,
+            !word,
+        )
+        .unwrap();
+        assert_eq!(target.read_memory_without_traps(add, 16).unwrap(), original);
+
+        // whereas anything written through the debugger is read back from the process
+        target
+            .process_mut()
+            .write_memory(add + 8, &word.to_ne_bytes())
+            .unwrap();
+        target.process_mut().write_memory(add + 4, &[0x90]).unwrap();
+        let mut patched = original.clone();
+        patched[4] = 0x90;
+        assert_eq!(target.read_memory_without_traps(add, 16).unwrap(), patched);
+    }
+
+    #[test]
+    fn breakpoint_site_restores_memory() {
+        let mut process = Process::launch("test/targets/fatal", true, None).unwrap();
+        let pc = process.get_pc().unwrap();
+        let original = process.read_memory(pc, 8).unwrap();
+
+        let id = process.create_breakpoint_site(pc, false).unwrap();
+        process.enable_breakpoint_site(id).unwrap();
+        assert_eq!(process.read_memory(pc, 1).unwrap(), [0xcc]);
+        assert_eq!(process.read_memory_without_traps(pc, 8).unwrap(), original);
+
+        process.remove_breakpoint_site(id).unwrap();
+        assert_eq!(process.read_memory(pc, 8).unwrap(), original);
+    }
+
+    #[test]
+    fn catch_fatal_stops_before_abort() {
+        let mut target = Target::launch("test/targets/fatal", None).unwrap();
+        target.catch_fatal(DEFAULT_FATAL_FUNCTIONS).unwrap();
+
+        target.process_mut().resume().unwrap();
+        let reason = target.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Stopped);
+        assert_eq!(reason.fatal_function.as_deref(), Some("__assert_fail"));
+
+        target.process_mut().resume().unwrap();
+        let reason = target.wait_on_signal().unwrap();
+        assert_eq!(reason.fatal_function.as_deref(), Some("abort"));
+    }
+
+    #[test]
+    fn stoppoints_are_managed_together() {
+        let mut target = Target::launch("test/targets/fatal", None).unwrap();
+
+        // the catchpoint is set once libc is loaded, which it is by the time we get to main
+        target.catch_fatal(&["abort"]).unwrap();
+        let breakpoint = test::break_at_function(&mut target, "main");
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+
+        let hook = target
+            .create_function_hook("__assert_fail", HookReplacement::Nop)
+            .unwrap();
+
+        let process = target.process_mut();
+        let stoppoints = process.stoppoints();
+        assert!(stoppoints.is_sorted_by_key(|stoppoint| stoppoint.id()));
+        let kinds = stoppoints
+            .iter()
+            .map(|stoppoint| (stoppoint.kind(), stoppoint.spec()))
+            .filter(|(kind, _)| *kind != StoppointKind::Internal)
+            .collect::<Vec<_>>();
+        assert_eq!(kinds.len(), 3);
+        assert_eq!(kinds[0].0, StoppointKind::Breakpoint);
+        assert_eq!(kinds[1], (StoppointKind::Catchpoint, "abort".to_owned()));
+        assert_eq!(
+            kinds[2],
+            (StoppointKind::Hook, "__assert_fail, nop".to_owned())
+        );
+
+        let main = process.breakpoint_sites().get_by_id(breakpoint).unwrap();
+        assert_eq!(main.hit_count(), 1);
+
+        process.disable_stoppoint(hook).unwrap();
+        assert!(
+            !process
+                .function_hooks()
+                .get_by_id(hook)
+                .unwrap()
+                .is_enabled()
+        );
+        process.enable_stoppoint(hook).unwrap();
+        process.remove_stoppoint(breakpoint).unwrap();
+        assert!(!process.breakpoint_sites().contains_id(breakpoint));
+        assert!(process.remove_stoppoint(breakpoint).is_err());
+
+        // the hook keeps the assertion from aborting
+        process.resume().unwrap();
+        let reason = target.wait_on_signal().unwrap();
+
+        assert_eq!(reason.reason, ProcessState::Exited);
+    }
+
+    #[test]
+    fn interrupt_running_process() {
+        let mut process = Process::launch("yes", true, None).unwrap();
+        process.resume().unwrap();
+        assert_eq!(process.wait_on_signal_nonblocking().unwrap(), None);
+
+        process.interrupt().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Stopped);
+        assert_eq!(reason.signal(), Some(signal::SIGSTOP));
+
+        // interrupting doesn't leave a pending SIGSTOP behind
+        process.resume().unwrap();
+        assert_eq!(process.wait_on_signal_nonblocking().unwrap(), None);
+    }
+
+    #[test]
+    fn threads_stop_together() {
+        let mut target = Target::launch("test/targets/threads", None).unwrap();
+        let events = target.events().unwrap();
+        test::break_at_function(&mut target, "marker");
+
+        let mut hits = 0;
+        loop {
+            target.process_mut().resume().unwrap();
+            let reason = target.wait_on_signal().unwrap();
+            if reason.reason == ProcessState::Exited {
+                assert_eq!(reason.info, 0);
+                break;
+            }
+
+            assert!(reason.breakpoint_site.is_some());
+            assert!(
+                target
+                    .process()
+                    .threads()
+                    .all(|thread| thread.state() == ProcessState::Stopped)
+            );
+            hits += 1;
+        }
+        assert_eq!(hits, 4);
+
+        let created = events
+            .try_iter()
+            .filter(|event| matches!(event, DebugEvent::ThreadCreated(..)))
+            .count();
+        assert_eq!(created, 4);
+    }
+
+    #[test]
+    fn thread_names() {
+        let mut target = Target::launch("test/targets/threads", None).unwrap();
+        let pid = target.process().get_id();
+        test::break_at_function(&mut target, "marker");
+        let process = target.process_mut();
+
+        process.resume().unwrap();
+        process.wait_on_signal().unwrap();
+
+        let current = process.current_thread();
+        let thread = process
+            .threads()
+            .find(|thread| thread.tid() == current)
+            .unwrap();
+        let name = thread.name().unwrap();
+        assert!(name.starts_with("worker-"));
+        assert_eq!(thread.kernel_state(), Some("tracing stop"));
+
+        assert_eq!(process.find_thread(&name).unwrap(), current);
+        assert_eq!(process.find_thread(&current.to_string()).unwrap(), current);
+        assert_eq!(process.find_thread("thr").unwrap(), pid);
+        assert!(process.find_thread("nobody").is_err());
+    }
+
+    #[test]
+    fn thread_local_variables() {
+        let mut target = Target::launch("test/targets/threads", None).unwrap();
+        test::break_at_function(&mut target, "marker");
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+        let stopped = target.process().current_thread();
+
+        let path = "thread_index".parse::<VariablePath>().unwrap();
+        let threads = target
+            .process()
+            .threads()
+            .map(|thread| (thread.tid(), thread.name().unwrap()))
+            .collect::<Vec<_>>();
+        for (tid, name) in threads {
+            target.process_mut().set_current_thread(tid).unwrap();
+            let text = target.read_variable(&path, Radix::Decimal).unwrap().text;
+
+            match name.strip_prefix("worker-") {
+                Some(index) if tid == stopped => assert_eq!(text, index),
+                // the others may not have got to setting theirs yet
+                Some(index) => assert!(text == index || text == "-1"),
+                // main, or a worker that hasn't named itself yet
+                None => assert_eq!(text, "-1"),
+            }
+        }
+    }
+
+    #[test]
+    fn thread_breakpoint() {
+        let mut target = Target::launch("test/targets/threads", None).unwrap();
+        let pid = target.process().get_id();
+        let id = test::break_at_function(&mut target, "marker");
+        let process = target.process_mut();
+
+        process.resume().unwrap();
+        process.wait_on_signal().unwrap();
+        assert_ne!(process.current_thread(), pid);
+
+        assert!(
+            process
+                .set_breakpoint_thread(id, Some(Pid::from_raw(1)))
+                .is_err()
+        );
+
+        // main never calls marker, so the other workers run straight through it
+        process.set_breakpoint_thread(id, Some(pid)).unwrap();
+        assert!(
+            process
+                .breakpoint_sites()
+                .get_by_id(id)
+                .unwrap()
+                .spec()
+                .ends_with(&format!("thread {}", pid))
+        );
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Exited);
+    }
+
+    #[test]
+    fn scheduler_locking() {
+        let mut target = Target::launch("test/targets/threads", None).unwrap();
+        let marker = test::break_at_function(&mut target, "marker");
+        let process = target.process_mut();
+
+        process.resume().unwrap();
+        process.wait_on_signal().unwrap();
+        let locked = process.current_thread();
+
+        let pcs = |process: &mut Process| {
+            let tids = process
+                .threads()
+                .map(|thread| thread.tid())
+                .filter(|tid| *tid != locked)
+                .collect::<Vec<_>>();
+            let pcs = tids
+                .into_iter()
+                .map(|tid| {
+                    process.set_current_thread(tid).unwrap();
+                    (tid, process.get_pc().unwrap())
+                })
+                .collect::<Vec<_>>();
+            process.set_current_thread(locked).unwrap();
+            pcs
+        };
+        let before = pcs(process);
+
+        // run the thread back out of marker
+        let rsp = process.read_register_by_id(RegisterId::rsp).unwrap();
+        let rsp = VirtAddr::new(rsp.to_u64().unwrap());
+        let data = process.read_memory(rsp, 8).unwrap();
+        let return_address = VirtAddr::new(u64::from_le_bytes(data.try_into().unwrap()));
+        let id = process
+            .create_breakpoint_site(return_address, false)
+            .unwrap();
+        process.enable_breakpoint_site(id).unwrap();
+
+        process.set_scheduler_locking(true);
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.breakpoint_site, Some(id));
+        assert_eq!(process.current_thread(), locked);
+        assert_eq!(pcs(process), before);
+
+        // once it exits the rest run, each to its own marker hit
+        let mut hits = 0;
+        loop {
+            process.resume().unwrap();
+            let reason = process.wait_on_signal().unwrap();
+            if reason.reason == ProcessState::Exited {
+                break;
+            }
+            assert_ne!(process.current_thread(), locked);
+            if reason.breakpoint_site == Some(marker) {
+                hits += 1;
+            }
+        }
+        assert_eq!(hits, 3);
+    }
+
+    #[test]
+    fn ptrace_errors_say_what_failed() {
+        let err =
+            SdbError::ptrace_at("peekdata", Pid::from_raw(42), VirtAddr::new(0x1000))(Errno::ESRCH);
+        assert_eq!(
+            err.to_string(),
+            "ptrace error: peekdata on 42 at 0x0000000000001000: ESRCH: No such process"
+        );
+        assert_eq!(err.errno(), Some(Errno::ESRCH));
+
+        let process = Process::attach(0);
+        assert!(matches!(
+            process,
+            Err(SdbError::Ptrace { op: "seize", pid, .. }) if pid.as_raw() == 0
+        ));
+    }
+
+    #[test]
+    fn thread_op_report_results() {
+        let report = ThreadOpReport {
+            outcomes: vec![
+                (Pid::from_raw(1), ThreadOutcome::Succeeded),
+                (Pid::from_raw(2), ThreadOutcome::Vanished),
+            ],
+        };
+        assert!(report.into_result().is_ok());
+
+        let report = ThreadOpReport {
+            outcomes: vec![(
+                Pid::from_raw(1),
+                ThreadOutcome::Failed(SdbError::ptrace("cont", Pid::from_raw(1))(Errno::ESRCH)),
+            )],
+        };
+        assert!(matches!(report.into_result(), Err(SdbError::Ptrace { .. })));
+
+        let report = ThreadOpReport {
+            outcomes: vec![
+                (Pid::from_raw(1), ThreadOutcome::Succeeded),
+                (
+                    Pid::from_raw(2),
+                    ThreadOutcome::Failed(SdbError::ptrace("cont", Pid::from_raw(2))(Errno::EPERM)),
+                ),
+            ],
+        };
+        assert!(matches!(
+            report.into_result(),
+            Err(SdbError::ThreadOp(report)) if report.failures().count() == 1
+        ));
+    }
+
+    #[test]
+    fn syscall_registers_at_exec() {
+        let process = Process::launch("yes", true, None).unwrap();
+
+        // the first stop is on the way out of execve
+        assert_eq!(
+            process.read_syscall_number().unwrap(),
+            libc::SYS_execve as u64
+        );
+        assert_eq!(process.read_syscall_return().unwrap(), 0);
+    }
+
+    #[test]
+    fn target_stop_info_has_source_context() {
+        let mut target = Target::launch("test/targets/fatal", None).unwrap();
+        let id = test::break_at_function(&mut target, "main");
+
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+
+        let info = target.stop_info().unwrap();
+        assert_eq!(info.breakpoint_site(), Some(id));
+        assert_eq!(info.function.as_deref(), Some("main"));
+
+        let location = info.location.unwrap();
+        assert!(location.file.ends_with("fatal.c"));
+        assert!(location.line > 0);
+    }
+
+    #[test]
+    fn restart_sets_breakpoints_again() {
+        let mut target = Target::launch("test/targets/step", None).unwrap();
+        let handle = target.process().handle();
+        let add = target.create_function_breakpoint("add").unwrap();
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+        let printf = target.create_function_breakpoint("printf").unwrap();
+        let first = target.process().get_id();
+
+        // stopped at the entry, before libc's been loaded
+        let reason = target.restart(false).unwrap();
+        assert_eq!(reason.reason, ProcessState::Stopped);
+        assert_ne!(target.process().get_id(), first);
+        assert_eq!(handle.get_id(), target.process().get_id());
+
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+        assert_eq!(target.stop_info().unwrap().breakpoint_site(), Some(add));
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+        assert_eq!(target.stop_info().unwrap().breakpoint_site(), Some(printf));
+
+        let reason = target.restart(true).unwrap();
+        assert_eq!(reason.breakpoint_site, None);
+        let info = target.stop_info().unwrap();
+        assert_eq!(info.function.as_deref(), Some("main"));
+        assert_eq!(info.location.unwrap().line, 10);
+    }
+
+    // stops at the first line of the body of add, called from line 10
+    fn step_into_add() -> Target {
+        let mut target = Target::launch("test/targets/step", None).unwrap();
+        target.create_line_breakpoint("step.c", 10).unwrap();
+
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+        assert_eq!(target.stop_info().unwrap().location.unwrap().line, 10);
+
+        target.step_in().unwrap();
+        target
+    }
+
+    #[test]
+    fn target_line_breakpoint() {
+        let mut target = Target::launch("test/targets/step", None).unwrap();
+        assert!(target.create_line_breakpoint("step.c", 100).is_err());
+
+        let id = target.create_line_breakpoint("step.c", 5).unwrap();
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+
+        let info = target.stop_info().unwrap();
+        assert_eq!(info.breakpoint_site(), Some(id));
+        assert_eq!(info.function.as_deref(), Some("add"));
+        assert_eq!(info.location.unwrap().line, 5);
+    }
+
+    #[test]
+    fn target_read_variables() {
+        let mut target = Target::launch("test/targets/variables", None).unwrap();
+        target.create_line_breakpoint("variables.c", 28).unwrap();
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+
+        let read =
+            |path: &str, radix| target.read_variable(&path.parse::<VariablePath>().unwrap(), radix);
+        let text = |path| read(path, Radix::Decimal).unwrap().text;
+
+        let width = read("width", Radix::Decimal).unwrap();
+        assert_eq!(width.text, "3");
+        assert_eq!(width.type_name, "int");
+        assert_eq!(width.scalar, Some(3));
+        assert!(width.address.is_some());
+        assert_eq!(text("height"), "4");
+        assert_eq!(text("factor"), "2");
+
+        assert_eq!(
+            read("shape", Radix::Decimal).unwrap().type_name,
+            "struct shape *"
+        );
+        assert_eq!(text("shape->corners[1]"), "{x = 4, y = 6}");
+        assert_eq!(text("shape->corners[1].y"), "6");
+        assert_eq!(read("shape->corners[1].y", Radix::Hex).unwrap().text, "0x6");
+        assert_eq!(
+            read("shape->corners", Radix::Decimal).unwrap().type_name,
+            "struct point [2]"
+        );
+        assert!(text("shape->name").ends_with(" \"box\""));
+        assert_eq!(text("shape->visible"), "1");
+        assert_eq!(text("shape->layer"), "-3");
+        assert_eq!(text("shape->scale"), "1.5");
+        assert_eq!(text("shape->color"), "BLUE");
+        assert_eq!(text("*shape->origin"), "{x = 0, y = 0}");
+        assert_eq!(text("shape[0].corners[0].x"), "1");
+
+        assert!(read("shape->corners[2]", Radix::Decimal).is_err());
+        assert!(read("shape->missing", Radix::Decimal).is_err());
+        assert!(read("nothing", Radix::Decimal).is_err());
+        assert!("1x".parse::<VariablePath>().is_err());
+        let path = "* shape->corners[1].x".parse::<VariablePath>().unwrap();
+        assert_eq!(path.to_string(), "*shape->corners[1].x");
+    }
+
+    #[test]
+    fn function_breakpoint_skips_prologue() {
+        let mut target = Target::launch("test/targets/variables", None).unwrap();
+        let entry = target.function_address("area").unwrap();
+        let id = target.create_function_breakpoint("area").unwrap();
+        let address = target
+            .process()
+            .breakpoint_sites()
+            .get_by_id(id)
+            .unwrap()
+            .address();
+        assert!(address > entry);
+        assert_eq!(target.past_prologue(address), address);
+
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+        let info = target.stop_info().unwrap();
+        assert_eq!(info.location.unwrap().line, 26);
+
+        // the parameters have been stored where the debug info says
+        let factor = target
+            .read_variable(&"factor".parse().unwrap(), Radix::Decimal)
+            .unwrap();
+        assert_eq!(factor.text, "2");
+    }
+
+    #[test]
+    fn finish_reads_return_values() {
+        let mut target = Target::launch("test/targets/returns", None).unwrap();
+        let mut finish = |function: &str| {
+            target.create_function_breakpoint(function).unwrap();
+            target.process_mut().resume().unwrap();
+            target.wait_on_signal().unwrap();
+            target.finish().unwrap().1.map(|value| value.text)
+        };
+
+        assert_eq!(finish("sum").as_deref(), Some("7"));
+        assert_eq!(finish("half").as_deref(), Some("2.5"));
+        assert_eq!(finish("third").as_deref(), Some("3"));
+        assert_eq!(finish("flip").as_deref(), Some("{x = 2, y = 1}"));
+        assert_eq!(finish("weigh").as_deref(), Some("{weight = 2, count = 8}"));
+        assert_eq!(
+            finish("fill").as_deref(),
+            Some("{values = {10, 11, 12, 13}}")
+        );
+        assert_eq!(finish("nothing"), None);
+        assert!(finish("greeting").unwrap().ends_with(" \"hello\""));
+    }
+
+    #[test]
+    fn return_now_pops_the_frame() {
+        let mut target = Target::launch("test/targets/returns", None).unwrap();
+        target.create_function_breakpoint("sum").unwrap();
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+
+        let pc = target.get_pc().unwrap();
+        let frames = target.backtrace().unwrap();
+        let caller_frame = target.read_register_by_id(RegisterId::rbp).unwrap();
+        let caller_frame = target
+            .read_u64(VirtAddr::new(caller_frame.to_u64().unwrap()))
+            .unwrap();
+
+        let caller = target.return_now(Some(40)).unwrap();
+        assert_eq!(caller, frames[1].pc);
+        assert_eq!(target.get_pc().unwrap(), caller);
+        assert_eq!(target.function_name_at(caller).as_deref(), Some("main"));
+        assert_eq!(
+            target
+                .read_register_by_id(RegisterId::rbp)
+                .unwrap()
+                .to_u64(),
+            Some(caller_frame)
+        );
+        assert_eq!(
+            target
+                .read_register_by_id(RegisterId::rax)
+                .unwrap()
+                .to_u64(),
+            Some(40)
+        );
+        assert_eq!(target.stop_info().unwrap().breakpoint_site(), None);
+
+        // it's journaled like any other register write
+        target.process_mut().undo_last().unwrap().unwrap();
+        assert_eq!(target.get_pc().unwrap(), pc);
+        target.return_now(Some(7)).unwrap();
+
+        target.process_mut().resume().unwrap();
+        let reason = target.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Exited);
+        assert_eq!(reason.info, 0);
+    }
+
+    #[test]
+    fn call_function_leaves_the_thread_as_it_was() {
+        let mut target = Target::launch("test/targets/returns", None).unwrap();
+        target.create_function_breakpoint("main").unwrap();
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+
+        let process = target.process_mut();
+        let pc = process.get_pc().unwrap();
+        let rsp = process
+            .read_register_by_id(RegisterId::rsp)
+            .unwrap()
+            .to_u64();
+        let sites = process.breakpoint_sites().iter().count();
+
+        let sum = target.function_address("sum").unwrap();
+        assert_eq!(target.process_mut().call_function(sum, &[3, 4]).unwrap(), 7);
+
+        let mut call = |name: &str, args: &[u64]| {
+            target
+                .call_function_by_name(name, args, Radix::Decimal)
+                .unwrap()
+                .map(|value| value.text)
+        };
+        // a struct of two ints is passed packed in one register
+        assert_eq!(
+            call("flip", &[1 | 2 << 32]).as_deref(),
+            Some("{x = 2, y = 1}")
+        );
+        assert_eq!(
+            call("weigh", &[8]).as_deref(),
+            Some("{weight = 2, count = 8}")
+        );
+        // returned in memory set aside on the stack
+        assert_eq!(
+            call("fill", &[10]).as_deref(),
+            Some("{values = {10, 11, 12, 13}}")
+        );
+        assert_eq!(call("nothing", &[]), None);
+        // libc has no debug info, so malloc returns a long
+        assert_ne!(call("malloc", &[16]).as_deref(), Some("0"));
+
+        let process = target.process_mut();
+        assert_eq!(process.get_pc().unwrap(), pc);
+        assert_eq!(
+            process
+                .read_register_by_id(RegisterId::rsp)
+                .unwrap()
+                .to_u64(),
+            rsp
+        );
+        assert_eq!(process.breakpoint_sites().iter().count(), sites);
+
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Exited);
+        assert_eq!(reason.info, 0);
+    }
+
+    #[test]
+    fn target_read_and_write_globals() {
+        let mut target = Target::launch("test/targets/variables", None).unwrap();
+        target.create_line_breakpoint("variables.c", 28).unwrap();
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+
+        let path = |path: &str| path.parse::<VariablePath>().unwrap();
+        let text = |target: &Target, name| {
+            target
+                .read_variable(&path(name), Radix::Decimal)
+                .unwrap()
+                .text
+        };
+
+        assert_eq!(text(&target, "numbers"), "{1, 2, 3, 4}");
+        assert_eq!(text(&target, "origin"), "{x = 0, y = 0}");
+        assert_eq!(text(&target, "ratio"), "0.5");
+        assert_eq!(text(&target, "favorite"), "GREEN");
+        let numbers = target
+            .read_variable(&path("numbers"), Radix::Decimal)
+            .unwrap();
+        assert_eq!(numbers.type_name, "int [4]");
+        let symbol = target
+            .modules()
+            .get_elf_containing_address(target.get_pc().unwrap())
+            .and_then(|elf| Some(elf.symbol_address(elf.symbols_by_name("numbers").next()?)));
+        assert_eq!(numbers.address, symbol);
+
+        let write = |target: &mut Target, name, value| {
+            target.write_variable(&path(name), value).unwrap().text
+        };
+        assert_eq!(write(&mut target, "numbers[2]", "30"), "30");
+        assert_eq!(write(&mut target, "ratio", "-2.25"), "-2.25");
+        assert_eq!(write(&mut target, "favorite", "BLUE"), "BLUE");
+        assert_eq!(write(&mut target, "origin.y", "1 + 6"), "7");
+        // locals on the stack can be set too
+        assert_eq!(write(&mut target, "factor", "3"), "3");
+        assert_eq!(text(&target, "numbers"), "{1, 2, 30, 4}");
+        assert_eq!(text(&target, "*shape->origin"), "{x = 0, y = 7}");
+
+        assert!(target.write_variable(&path("origin"), "1").is_err());
+        assert!(target.write_variable(&path("ratio"), "half").is_err());
+        assert!(target.write_variable(&path("nothing"), "1").is_err());
+
+        assert_eq!(target.process_mut().undo_all().unwrap().len(), 5);
+        assert_eq!(text(&target, "numbers"), "{1, 2, 3, 4}");
+        assert_eq!(text(&target, "ratio"), "0.5");
+        assert_eq!(text(&target, "factor"), "2");
+    }
+
+    #[test]
+    fn target_step_in_and_backtrace() {
+        let target = step_into_add();
+
+        let info = target.stop_info().unwrap();
+        assert_eq!(info.function.as_deref(), Some("add"));
+        assert_eq!(info.location.unwrap().line, 5);
+
+        let backtrace = target.backtrace().unwrap();
+        let functions = backtrace
+            .iter()
+            .map(|frame| frame.function.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(functions[..2], [Some("add"), Some("main")]);
+        assert_eq!(backtrace[1].location.as_ref().unwrap().line, 10);
+    }
+
+    #[test]
+    fn target_step_out() {
+        let mut target = step_into_add();
+
+        target.step_out().unwrap();
+        let info = target.stop_info().unwrap();
+        assert_eq!(info.function.as_deref(), Some("main"));
+        assert_eq!(info.location.unwrap().line, 10);
+    }
+
+    #[test]
+    fn target_step_over() {
+        let mut target = Target::launch("test/targets/step", None).unwrap();
+        target.create_line_breakpoint("step.c", 10).unwrap();
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+
+        target.step_over().unwrap();
+        let info = target.stop_info().unwrap();
+        assert_eq!(info.function.as_deref(), Some("main"));
+        assert_eq!(info.location.unwrap().line, 11);
+
+        // printf is in libc, which has no line info
+        target.step_over().unwrap();
+        assert_eq!(target.stop_info().unwrap().location.unwrap().line, 12);
+    }
+
+    #[test]
+    fn step_in_through_the_plt() {
+        let mut target = Target::launch("test/targets/step", None).unwrap();
+        target.create_line_breakpoint("step.c", 11).unwrap();
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+
+        // printf has no line info, so it's run back out of
+        target.step_in().unwrap();
+        let info = target.stop_info().unwrap();
+        assert_eq!(info.function.as_deref(), Some("main"));
+        assert_eq!(info.location.unwrap().line, 12);
+
+        // the stub and the dynamic linker binding it are stepped through to printf itself
+        target.set_step_into_no_debug(true);
+        target.restart(false).unwrap();
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+
+        target.step_in().unwrap();
+        let pc = target.get_pc().unwrap();
+        let (elf, printf) = target.modules().find_function("printf").unwrap();
+        assert_eq!(pc, elf.symbol_address(printf));
+        assert!(target.line_entry_at(pc).is_none());
+    }
+
+    #[test]
+    fn journal_undoes_writes_newest_first() {
+        let mut process = Process::launch("test/targets/step", true, None).unwrap();
+        let pc = process.get_pc().unwrap();
+        let code = process.read_memory(pc, 4).unwrap();
+        let rsi = process
+            .read_register_by_id(RegisterId::rsi)
+            .unwrap()
+            .to_u64()
+            .unwrap();
+
+        process
+            .write_memory_journaled(pc, &[0x90; 4], false)
+            .unwrap();
+        process
+            .write_registers_journaled([(RegisterId::rsi, 0xcafecafe_u64.into())])
+            .unwrap();
+        process
+            .write_memory_journaled(pc, &[0xcc; 4], false)
+            .unwrap();
+        let pid = process
+            .inject_syscall_journaled(libc::SYS_getpid, &[])
+            .unwrap();
+        assert_eq!(pid, process.get_id().as_raw() as i64);
+        assert_eq!(process.journal().len(), 4);
+
+        // the syscall comes off without anything being put back
+        let undone = process.undo_last().unwrap().unwrap();
+        assert!(!undone.is_reversible());
+        assert_eq!(process.read_memory(pc, 4).unwrap(), [0xcc; 4]);
+
+        let undone = process.undo_last().unwrap().unwrap();
+        assert!(matches!(undone.mutation, Mutation::Memory { .. }));
+        assert_eq!(process.read_memory(pc, 4).unwrap(), [0x90; 4]);
+
+        let undone = process.undo_all().unwrap();
+        assert_eq!(
+            undone.iter().map(|entry| entry.id).collect::<Vec<_>>(),
+            [2, 1]
+        );
+        assert_eq!(process.read_memory(pc, 4).unwrap(), code);
+        process.read_all_registers(process.current_thread).unwrap();
+        let read = process.read_register_by_id(RegisterId::rsi).unwrap();
+        assert_eq!(read.to_u64().unwrap(), rsi);
+
+        assert!(process.journal().is_empty());
+        assert!(process.undo_last().unwrap().is_none());
+
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Exited);
+        assert_eq!(reason.info, 0);
+    }
+
+    #[test]
+    fn inject_syscall_leaves_the_process_as_it_was() {
+        let mut process = Process::launch("test/targets/step", true, None).unwrap();
+        let pc = process.get_pc().unwrap();
+        let code = process.read_memory(pc, 8).unwrap();
+
+        let pid = process.inject_syscall(libc::SYS_getpid, &[]).unwrap();
+        assert_eq!(pid, process.get_id().as_raw() as i64);
+
+        // errors come back as -errno rather than failing
+        let ret = process
+            .inject_syscall(libc::SYS_close, &[u64::MAX])
+            .unwrap();
+        assert_eq!(ret, -(libc::EBADF as i64));
+
+        assert_eq!(process.get_pc().unwrap(), pc);
+        assert_eq!(process.read_memory(pc, 8).unwrap(), code);
+
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Exited);
+        assert_eq!(reason.info, 0);
+    }
+
+    #[test]
+    fn typed_reads_and_c_strings() {
+        let mut target = Target::launch("test/targets/returns", None).unwrap();
+        let sum = target.function_address("sum").unwrap();
+        let code = target.process().read_memory(sum, 1).unwrap()[0];
+        let id = target
+            .process_mut()
+            .create_breakpoint_site(sum, false)
+            .unwrap();
+        target.process_mut().enable_breakpoint_site(id).unwrap();
+
+        // the int3 isn't what's read back through anything that looks at the program
+        assert_eq!(target.process().read_memory(sum, 1).unwrap()[0], 0xcc);
+        assert_eq!(target.read_u8(sum).unwrap(), code);
+        assert_eq!(Inferior::read_memory(&target, sum, 1).unwrap(), [code]);
+        let deref = format!("*{:#x}", sum.addr()).parse::<Expr>().unwrap();
+        assert_eq!(deref.evaluate(&target).unwrap() as u8, code);
+
+        // across a page boundary on the stack
+        let process = target.process();
+        let rsp = process.read_register_by_id(RegisterId::rsp).unwrap();
+        let page = VirtAddr::new(rsp.to_u64().unwrap() & !0xfff);
+        process.write_memory(page - 3, b"hello\0").unwrap();
+        assert_eq!(target.read_c_string(page - 3, 64).unwrap(), b"hello");
+        assert_eq!(target.read_c_string(page - 3, 4).unwrap(), b"hell");
+        assert_eq!(
+            target.read_u16(page - 3).unwrap(),
+            u16::from_ne_bytes(*b"he")
+        );
+
+        process.write_memory(page, &1.5f64.to_ne_bytes()).unwrap();
+        assert_eq!(target.read_f64(page).unwrap(), 1.5);
+        process.write_memory(page, &(-2i32).to_ne_bytes()).unwrap();
+        assert_eq!(target.read_i32(page).unwrap(), -2);
+        assert_eq!(target.read_u32(page).unwrap(), -2i32 as u32);
+
+        // a read that comes up short
+        assert!(target.read_u64(VirtAddr::new(8)).is_err());
+    }
+
+    #[test]
+    fn search_memory_finds_patterns() {
+        let mut target = Target::launch("test/targets/returns", None).unwrap();
+        let sum = target.function_address("sum").unwrap();
+        let code = target.process().read_memory(sum, 16).unwrap();
+        let id = target
+            .process_mut()
+            .create_breakpoint_site(sum, false)
+            .unwrap();
+        target.process_mut().enable_breakpoint_site(id).unwrap();
+        let process = target.process();
+
+        // the string literal's in the executable's read only data
+        let regions = SearchRegions::default().name("returns");
+        let matches = process.search_memory(b"hello\0", &regions).unwrap();
+        assert!(!matches.is_empty());
+        for found in &matches {
+            assert!(found.mapping.ends_with("returns"));
+            assert_eq!(process.read_c_string(found.address, 64).unwrap(), b"hello");
+        }
+        let executable = regions.clone().permissions("x").unwrap();
+        assert!(
+            process
+                .search_memory(b"hello\0", &executable)
+                .unwrap()
+                .is_empty()
+        );
+
+        // the breakpoint doesn't hide the code under it
+        let matches = process.search_memory(&code, &executable).unwrap();
+        assert!(matches.iter().any(|found| found.address == sum));
+
+        assert!(
+            process
+                .search_memory(b"", &SearchRegions::default())
+                .is_err()
+        );
+        assert!(SearchRegions::default().permissions("rq").is_err());
+    }
+
+    #[test]
+    fn profile_samples_running_stacks() {
+        let mut target = Target::launch("test/targets/spin", None).unwrap();
+        let (profile, reason) = target
+            .profile(Duration::from_millis(5), |profile| profile.samples() < 20)
+            .unwrap();
+        assert_eq!(reason, None);
+        assert_eq!(target.process().get_state(), ProcessState::Stopped);
+        assert!(profile.samples() >= 20);
+
+        // nearly all the time's spent in inner
+        let functions = profile.functions();
+        assert_eq!(functions[0].name, "inner");
+        let outer = functions.iter().find(|f| f.name == "outer").unwrap();
+        assert_eq!(outer.own, 0);
+        assert!(outer.total > 0);
+        assert!(
+            profile
+                .folded()
+                .lines()
+                .any(|line| line.starts_with("main;outer;inner "))
+        );
+    }
+
+    #[test]
+    fn perf_counters_count_between_stops() {
+        let mut target = Target::launch("test/targets/spin", None).unwrap();
+        let outer = target.function_address("outer").unwrap();
+        let process = target.process_mut();
+        let id = process.create_breakpoint_site(outer, false).unwrap();
+        process.enable_breakpoint_site(id).unwrap();
+        process.resume().unwrap();
+        process.wait_on_signal().unwrap();
+
+        // software events work without a PMU
+        let mut counters =
+            PerfCounters::open(process, &[PerfEvent::TaskClock, PerfEvent::ContextSwitches])
+                .unwrap();
+        counters.enable().unwrap();
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.breakpoint_site, Some(id));
+        counters.disable().unwrap();
+
+        let readings = counters.read().unwrap();
+        assert_eq!(
+            readings.iter().map(|r| r.event).collect::<Vec<_>>(),
+            [PerfEvent::TaskClock, PerfEvent::ContextSwitches]
+        );
+        // a thousand calls to inner take well over a tenth of a millisecond
+        assert!(readings[0].value > 100_000);
+
+        // nothing's counted once they're disabled
+        process.resume().unwrap();
+        process.wait_on_signal().unwrap();
+        assert_eq!(counters.read().unwrap(), readings);
+
+        assert!("bogus".parse::<PerfEvent>().is_err());
+        assert_eq!(
+            "cache-misses".parse::<PerfEvent>().unwrap(),
+            PerfEvent::CacheMisses
+        );
     }
 
     #[test]
-    fn process_launch_no_such_program() {
-        let process = Process::launch("you_do_not_have_to_be_good", true, None);
-        assert!(process.is_err());
-        assert!(matches!(
-            process,
-            std::result::Result::Err(SdbError::Child(..))
-        ));
+    fn trace_syscalls_reports_decoded_calls() {
+        let traced = |filter: SyscallFilter| {
+            let mut target = Target::launch("test/targets/returns", None).unwrap();
+            let events = target.events().unwrap();
+            target.process_mut().trace_syscalls(filter);
+            target.process_mut().resume().unwrap();
+            let reason = target.wait_on_signal().unwrap();
+            assert_eq!(reason.reason, ProcessState::Exited);
+
+            events
+                .try_iter()
+                .filter_map(|event| match event {
+                    DebugEvent::Syscall(record) => Some(record),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let records = traced(SyscallFilter::all());
+        let libc = records
+            .iter()
+            .find(|record| {
+                record.name() == Some("openat")
+                    && record.args[1].contains("libc.so")
+                    && !record.result.as_ref().unwrap().starts_with('-')
+            })
+            .unwrap();
+        assert_eq!(libc.args[0], "AT_FDCWD");
+        assert!(libc.args[2].starts_with("O_RDONLY"));
+        assert!(libc.result.as_ref().unwrap().parse::<i32>().unwrap() >= 0);
+        let mmap = records
+            .iter()
+            .find(|record| record.name() == Some("mmap"))
+            .unwrap();
+        assert!(mmap.result.as_ref().unwrap().starts_with("0x"));
+
+        // it never returns, so it's reported on the way in
+        let last = records.last().unwrap();
+        assert_eq!(last.to_string(), "exit_group(0) = ?");
+
+        let records = traced(SyscallFilter::names(&["exit_group"]).unwrap());
+        assert_eq!(records.len(), 1);
+        assert!(SyscallFilter::names(&["bogus"]).is_err());
     }
 
     #[test]
-    fn process_resume_success() {
-        {
-            let mut process = Process::launch("yes", true, None).unwrap();
+    fn signal_catchpoints_stop_on_passed_signals() {
+        let mut process = Process::launch("test/targets/signals", true, None).unwrap();
+        assert!(process.catch_signal(Some(signal::SIGTRAP)).is_err());
 
-            let result = process.resume();
-            assert!(result.is_ok());
+        let alarm = process.catch_signal(Some(signal::SIGALRM)).unwrap();
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.signal(), Some(signal::SIGALRM));
+        assert_eq!(reason.catchpoint, Some(alarm));
 
-            let status = process.get_status().unwrap();
-            assert!(status == 'R' || status == 'S');
+        let catchpoint = process.signal_catchpoints().get_by_id(alarm).unwrap();
+        assert_eq!(catchpoint.kind(), StoppointKind::Catchpoint);
+        assert_eq!(catchpoint.spec(), "signal SIGALRM");
+        assert_eq!(catchpoint.hit_count(), 1);
+        assert!(process.stoppoints().iter().any(|point| point.id() == alarm));
+
+        // SIGUSR1 stops anyway, but it's only caught while the catchpoint's enabled
+        let all = process.catch_signal(None).unwrap();
+        process.disable_stoppoint(all).unwrap();
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.signal(), Some(signal::SIGUSR1));
+        assert_eq!(reason.catchpoint, None);
+
+        // the handler still ran for both
+        process.remove_stoppoint(alarm).unwrap();
+        process.remove_stoppoint(all).unwrap();
+        assert!(process.signal_catchpoints().is_empty());
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Exited);
+        assert_eq!(reason.info, 0);
+    }
+
+    #[test]
+    fn event_catchpoints_stop_on_fork_exec_and_exit() {
+        let mut process = Process::launch("test/targets/lifecycle", true, None).unwrap();
+        let fork = process.catch_event(LifecycleEvent::Fork).unwrap();
+        let exec = process.catch_event(LifecycleEvent::Exec).unwrap();
+        let exit = process.catch_event(LifecycleEvent::Exit).unwrap();
+
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.catchpoint, Some(fork));
+        assert_eq!(reason.lifecycle_event, Some(LifecycleEvent::Fork));
+        let child = process.forked_child().unwrap();
+        assert_ne!(child, process.pid);
+
+        // following the parent lets the child go when resumed
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(process.forked_child(), None);
+        assert_eq!(reason.catchpoint, Some(exec));
+        assert_eq!(reason.lifecycle_event, Some(LifecycleEvent::Exec));
+        assert_eq!(process.threads().count(), 1);
+
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.catchpoint, Some(exit));
+        assert_eq!(reason.lifecycle_event, Some(LifecycleEvent::Exit));
+        assert_eq!(reason.reason, ProcessState::Stopped);
+
+        let spec = process
+            .stoppoints()
+            .iter()
+            .filter(|stoppoint| stoppoint.kind() == StoppointKind::Catchpoint)
+            .map(|stoppoint| (stoppoint.spec(), stoppoint.hit_count()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            spec,
+            [
+                ("fork".to_owned(), 1),
+                ("exec".to_owned(), 1),
+                ("exit".to_owned(), 1)
+            ]
+        );
+
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Exited);
+        assert_eq!(reason.info, 3);
+    }
+
+    #[test]
+    fn caught_fork_follows_the_side_picked_at_the_stop() {
+        let mut process = Process::launch("test/targets/lifecycle", true, None).unwrap();
+        process.catch_event(LifecycleEvent::Fork).unwrap();
+        process.catch_event(LifecycleEvent::Exit).unwrap();
+        process.resume().unwrap();
+        process.wait_on_signal().unwrap();
+        let child = process.forked_child().unwrap();
+
+        process.set_follow_fork_mode(FollowForkMode::Child);
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(process.pid, child);
+        assert_eq!(reason.lifecycle_event, Some(LifecycleEvent::Exit));
+
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Exited);
+        assert_eq!(reason.info, 0);
+    }
+
+    #[test]
+    fn catch_panic_reads_the_message() {
+        for (args, message) in [
+            (&[][..], "value 3 is too big"),
+            (&["static"][..], "static message"),
+        ] {
+            let config = LaunchConfig::new("test/targets/panic").args(args.iter().copied());
+            let mut target = Target::launch_with(config).unwrap();
+            let id = target.catch_panic().unwrap();
+            assert!(target.catch_panic().is_err());
+
+            target.process_mut().resume().unwrap();
+            let reason = target.wait_on_signal().unwrap();
+            assert_eq!(reason.breakpoint_site, Some(id));
+            assert_eq!(reason.fatal_function.as_deref(), Some("rust_panic"));
+            assert_eq!(target.process().panic_message().as_deref(), Some(message));
         }
+    }
 
-        {
-            let target = Process::launch("yes", false, None).unwrap();
-            let mut process = Process::attach(target.get_id().as_raw()).unwrap();
+    #[test]
+    fn read_memory_bulk_methods_agree() {
+        let mut process = Process::launch("test/targets/step", true, None).unwrap();
+        let pc = process.get_pc().unwrap();
+        let id = process.create_breakpoint_site(pc + 4, false).unwrap();
+        process.enable_breakpoint_site(id).unwrap();
 
-            let result = process.resume();
-            assert!(result.is_ok());
+        let map = procfs::process::Process::new(process.get_id().as_raw())
+            .unwrap()
+            .maps()
+            .unwrap()
+            .into_iter()
+            .find(|map| (map.address.0..map.address.1).contains(&pc.addr()))
+            .unwrap();
+        let address = VirtAddr::new(map.address.0);
+        let size = (map.address.1 - map.address.0) as usize;
+        let expected = process.read_memory_without_traps(address, size).unwrap();
 
-            let status = process.get_status().unwrap();
-            assert!(status == 'R' || status == 'S');
+        for method in [
+            TransferMethod::ProcessVmReadv,
+            TransferMethod::ProcMem,
+            TransferMethod::Memfd,
+        ] {
+            let (memory, report) = process.read_memory_bulk(address, size, method).unwrap();
+            assert_eq!(memory, expected, "{}", method);
+            assert_eq!(report.method, method);
+            assert_eq!(report.bytes, size);
         }
+        assert_eq!(process.get_pc().unwrap(), pc);
+
+        // the memfd was closed again
+        let fds = std::fs::read_dir(format!("/proc/{}/fd", process.get_id()))
+            .unwrap()
+            .count();
+        process
+            .read_memory_bulk(address, size, TransferMethod::Memfd)
+            .unwrap();
+        assert_eq!(
+            std::fs::read_dir(format!("/proc/{}/fd", process.get_id()))
+                .unwrap()
+                .count(),
+            fds
+        );
     }
 
     #[test]
-    fn process_resume_already_exited() {
-        let mut process = Process::launch("echo", true, None).unwrap();
+    fn software_watchpoint() {
+        let mut target = Target::launch("test/targets/counter", None).unwrap();
+        let (address, _) = target.variable_address("counter").unwrap();
+        let tick = target.function_address("tick").unwrap();
+        assert!(
+            target
+                .process_mut()
+                .create_watchpoint(address, 0, WatchpointMode::Write)
+                .is_err()
+        );
+
+        let events = target.events().unwrap();
+        let process = target.process_mut();
+        // bigger than a debug register could watch
+        let id = process
+            .create_watchpoint(address, 16, WatchpointMode::Write)
+            .unwrap();
+        for count in 1..=2_u64 {
+            let process = target.process_mut();
+            process.resume().unwrap();
+            let reason = target.wait_on_signal().unwrap();
+            let process = target.process();
+            assert_eq!(reason.watchpoint, Some(id));
+            let event = events.try_iter().last().unwrap();
+            assert!(matches!(
+                event,
+                DebugEvent::WatchpointHit { id: hit, old: Some(old), new }
+                    if hit == id
+                        && old[..8] == (count - 1).to_le_bytes()
+                        && new[..8] == count.to_le_bytes()
+            ));
+
+            let watchpoint = process.watchpoints().get_by_id(id).unwrap();
+            assert_eq!(
+                watchpoint.previous().unwrap()[..8],
+                (count - 1).to_le_bytes()
+            );
+            assert_eq!(watchpoint.value()[..8], count.to_le_bytes());
+            assert_eq!(watchpoint.hit_count(), count);
+            // stopped right after the instruction that wrote it
+            let pc = process.get_pc().unwrap();
+            assert_eq!(
+                target
+                    .modules()
+                    .get_elf_containing_address(pc)
+                    .and_then(|elf| {
+                        Some(elf.symbol_address(elf.symbol_containing_address(pc)?))
+                    }),
+                Some(tick)
+            );
+        }
+
+        let process = target.process_mut();
+        process.remove_stoppoint(id).unwrap();
+        assert!(process.watchpoints().is_empty());
+    }
+
+    #[test]
+    fn hardware_watchpoints() {
+        let mut target = Target::launch("test/targets/counter", None).unwrap();
+        let (address, _) = target.variable_address("counter").unwrap();
+        let process = target.process_mut();
+        assert!(
+            process
+                .create_watchpoint(address, 16, WatchpointMode::Read)
+                .is_err()
+        );
+        assert!(process.watchpoints().is_empty());
+
+        let stop = |process: &mut Process| {
+            process.resume().unwrap();
+            let reason = process.wait_on_signal().unwrap();
+            (reason.watchpoint.unwrap(), reason.watch_access.unwrap())
+        };
+
+        // tick reads counter and then writes it back one more
+        let write = process
+            .create_watchpoint(address, 8, WatchpointMode::Write)
+            .unwrap();
+        let watchpoint = process.watchpoints().get_by_id(write).unwrap();
+        assert_eq!(watchpoint.debug_register(), Some(0));
+        assert!(!process.is_watching());
+        assert_eq!(stop(process), (write, WatchpointMode::Write));
+        let watchpoint = process.watchpoints().get_by_id(write).unwrap();
+        assert_eq!(watchpoint.value(), 1_u64.to_le_bytes());
+        process.disable_stoppoint(write).unwrap();
+        assert_eq!(
+            process
+                .watchpoints()
+                .get_by_id(write)
+                .unwrap()
+                .debug_register(),
+            None
+        );
+
+        let read = process
+            .create_watchpoint(address, 8, WatchpointMode::Read)
+            .unwrap();
+        assert_eq!(stop(process), (read, WatchpointMode::Read));
+        let watchpoint = process.watchpoints().get_by_id(read).unwrap();
+        assert_eq!(watchpoint.value(), 1_u64.to_le_bytes());
+        assert_eq!(watchpoint.hit_count(), 1);
+        process.remove_stoppoint(read).unwrap();
+
+        // stopped between that read and the write
+        let access = process
+            .create_watchpoint(address, 8, WatchpointMode::ReadWrite)
+            .unwrap();
+        assert_eq!(stop(process), (access, WatchpointMode::Write));
+        assert_eq!(stop(process), (access, WatchpointMode::Read));
+        assert_eq!(stop(process), (access, WatchpointMode::Write));
+        let watchpoint = process.watchpoints().get_by_id(access).unwrap();
+        assert_eq!(watchpoint.previous(), Some(&2_u64.to_le_bytes()[..]));
+        assert_eq!(watchpoint.value(), 3_u64.to_le_bytes());
+        assert_eq!(watchpoint.hit_count(), 3);
+    }
+
+    #[test]
+    fn steps_report_the_watchpoints_they_trigger() {
+        let mut target = Target::launch("test/targets/counter", None).unwrap();
+        let (address, _) = target.variable_address("counter").unwrap();
+        target.create_function_breakpoint("tick").unwrap();
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+
+        // ++counter is a load, an add, and a store, the store sets off both debug registers
+        // but only the write watchpoint is for a change
+        let process = target.process_mut();
+        let read = process
+            .create_watchpoint(address, 8, WatchpointMode::Read)
+            .unwrap();
+        let write = process
+            .create_watchpoint(address, 8, WatchpointMode::Write)
+            .unwrap();
+        let steps = (0..3)
+            .map(|_| {
+                let reason = process.step_instruction().unwrap();
+                (reason.watchpoint, reason.trap_reason.unwrap())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            steps,
+            [
+                (Some(read), TrapType::Watchpoint),
+                (None, TrapType::SingleStep),
+                (Some(write), TrapType::Watchpoint),
+            ]
+        );
+        let read = process.watchpoints().get_by_id(read).unwrap();
+        assert_eq!(read.hit_count(), 1);
+        assert_eq!(read.value(), 1_u64.to_le_bytes());
+
+        // dr6 was cleared, so the next step doesn't look like a watchpoint again
+        let reason = process.step_instruction().unwrap();
+        assert_eq!(reason.watchpoint, None);
+        assert_eq!(reason.trap_reason, Some(TrapType::SingleStep));
+    }
+
+    #[test]
+    fn disassemble_functions() {
+        let mut target = Target::launch("test/targets/counter", None).unwrap();
+        let tick = target.function_address("tick").unwrap();
+        let main = target.function_address("main").unwrap();
+        target.create_function_breakpoint("tick").unwrap();
+        let process = target.process();
+
+        // the breakpoint's int3 is hidden
+        let instructions = process.disassemble(tick, 3).unwrap();
+        let text = instructions
+            .iter()
+            .map(|instruction| instruction.text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            text,
+            [
+                "push   %rbp",
+                "mov    %rsp,%rbp",
+                "mov    0x2edc(%rip),%rax"
+            ]
+        );
+        assert_eq!(
+            instructions[2].referenced,
+            target
+                .variable_address("counter")
+                .map(|(address, _)| address)
+        );
+
+        let instructions = process.disassemble_range(main, tick + 0x100).unwrap();
+        let call = instructions
+            .iter()
+            .find(|instruction| instruction.text.starts_with("call"))
+            .unwrap();
+        assert_eq!(call.target, Some(tick));
+        assert_eq!(call.text, format!("call   {:#x}", tick.addr()));
+        assert_eq!(call.bytes[0], 0xe8);
+        assert!(
+            instructions
+                .windows(2)
+                .all(|pair| pair[0].address + pair[0].bytes.len() as i64 == pair[1].address)
+        );
+    }
+
+    #[test]
+    fn hardware_breakpoints_written_by_hand() {
+        let mut target = Target::launch("test/targets/counter", None).unwrap();
+        let tick = target.function_address("tick").unwrap();
+        let process = target.process_mut();
+
+        // dr0 on tick's first instruction, locally enabled with R/W and LEN 00
+        process
+            .write_register_by_id(RegisterId::dr0, tick.addr().into())
+            .unwrap();
+        process
+            .write_register_by_id(RegisterId::dr7, 1_u64.into())
+            .unwrap();
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.trap_reason, Some(TrapType::HardwareBreak));
+        assert_eq!(reason.breakpoint_site, None);
+        assert_eq!(reason.watchpoint, None);
+        assert_eq!(process.get_pc().unwrap(), tick);
+    }
+
+    #[test]
+    fn reverse_continue_replays_from_a_checkpoint() {
+        let mut target = Target::launch("test/targets/counter", None).unwrap();
+        let id = target.create_function_breakpoint("tick").unwrap();
+        let (counter, _) = target.variable_address("counter").unwrap();
+        let process = target.process_mut();
         process.resume().unwrap();
         process.wait_on_signal().unwrap();
 
-        let result = process.resume();
-        assert!(matches!(
-            result,
-            std::result::Result::Err(SdbError::Ptrace(..))
-        ));
+        let checkpoint = process.create_checkpoint().unwrap().id;
+        for _ in 0..3 {
+            process.resume().unwrap();
+            process.wait_on_signal().unwrap();
+        }
+        assert_eq!(process.read_u64(counter).unwrap(), 3);
+
+        // the copy that replaced it stops at the hit before, and carries on from there
+        let pid = process.pid;
+        let reason = target.reverse_continue().unwrap();
+        assert_eq!(reason.breakpoint_site, Some(id));
+        let process = target.process_mut();
+        assert_ne!(process.pid, pid);
+        assert_eq!(process.read_u64(counter).unwrap(), 2);
+        process.resume().unwrap();
+        process.wait_on_signal().unwrap();
+        assert_eq!(process.read_u64(counter).unwrap(), 3);
+
+        // carried on from a hit, it's that hit that's gone back to
+        let hit = process.get_pc().unwrap();
+        process.step_instruction().unwrap();
+        let reason = target.reverse_continue().unwrap();
+        assert_eq!(reason.breakpoint_site, Some(id));
+        let process = target.process_mut();
+        assert_eq!(process.get_pc().unwrap(), hit);
+        assert_eq!(process.read_u64(counter).unwrap(), 3);
+
+        // the hits were counted for the breakpoints there were
+        let extra = process.create_breakpoint_site(hit + 1, false).unwrap();
+        process.enable_breakpoint_site(extra).unwrap();
+        assert!(target.reverse_continue().is_err());
+        target.process_mut().remove_stoppoint(extra).unwrap();
+
+        // with no hit since the checkpoint it's back where that was taken
+        for expected in [2, 1, 0] {
+            target.reverse_continue().unwrap();
+            assert_eq!(target.process().read_u64(counter).unwrap(), expected);
+        }
+        assert_eq!(target.process().checkpoints().len(), 1);
+
+        // a checkpoint can be gone back to after the process has ended
+        let pc = target.get_pc().unwrap();
+        target.process_mut().kill().unwrap();
+        target.restore_checkpoint(checkpoint).unwrap();
+        let process = target.process_mut();
+        assert_eq!(process.read_u64(counter).unwrap(), 0);
+        assert_eq!(process.get_pc().unwrap(), pc);
+        process.resume().unwrap();
+        assert_eq!(process.wait_on_signal().unwrap().breakpoint_site, Some(id));
+    }
+
+    #[test]
+    fn local_watchpoint_leaves_scope() {
+        let mut target = Target::launch("test/targets/step", None).unwrap();
+        target.create_function_breakpoint("add").unwrap();
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+
+        let path = "sum".parse::<VariablePath>().unwrap();
+        // whatever was on the stack could already be what it's set to
+        target.write_variable(&path, "-1").unwrap();
+        let id = target
+            .create_variable_watchpoint(&path, None, WatchpointMode::Write)
+            .unwrap();
+        let process = target.process_mut();
+        let watchpoint = process.watchpoints().get_by_id(id).unwrap();
+        assert_eq!(watchpoint.size(), 4);
+        assert_eq!(watchpoint.frame().unwrap().0, process.current_thread());
+
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.watchpoint, Some(id));
+        let watchpoint = process.watchpoints().get_by_id(id).unwrap();
+        assert_eq!(watchpoint.previous(), Some(&(-1_i32).to_le_bytes()[..]));
+        assert_eq!(watchpoint.value(), 3_i32.to_le_bytes());
+
+        // add returning stops in main with the watchpoint disabled for good
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.watchpoint, Some(id));
+        let watchpoint = process.watchpoints().get_by_id(id).unwrap();
+        assert!(watchpoint.is_out_of_scope());
+        assert!(!watchpoint.is_enabled());
+        assert_eq!(watchpoint.hit_count(), 1);
+        assert!(process.enable_stoppoint(id).is_err());
+        assert_eq!(
+            target.function_name_at(target.get_pc().unwrap()).as_deref(),
+            Some("main")
+        );
+    }
+
+    #[test]
+    fn handle_reads_memory_while_running() {
+        let mut target = Target::launch("test/targets/counter", None).unwrap();
+        let (address, size) = target.variable_address("counter").unwrap();
+        assert_eq!(size, 8);
+        assert!(target.function_address("counter").is_none());
+
+        let handle = target.process().handle();
+        let read = || {
+            i64::from_ne_bytes(
+                handle
+                    .read_memory(address, size)
+                    .unwrap()
+                    .try_into()
+                    .unwrap(),
+            )
+        };
+        assert_eq!(read(), 0);
+
+        target.process_mut().resume().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let first = read();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert!(read() > first);
+        assert_eq!(handle.get_state(), ProcessState::Running);
+    }
+
+    #[test]
+    fn breakpoint_hit_limits() {
+        let mut target = Target::launch("test/targets/counter", None).unwrap();
+        let (counter, size) = target.variable_address("counter").unwrap();
+        let id = target.create_function_breakpoint("tick").unwrap();
+        let process = target.process_mut();
+        let read = |process: &Process| {
+            i64::from_ne_bytes(
+                process
+                    .read_memory(counter, size)
+                    .unwrap()
+                    .try_into()
+                    .unwrap(),
+            )
+        };
+
+        // the first hit and every 100th after it
+        process
+            .set_breakpoint_hit_limit(id, Some(HitLimit::Sample(100)))
+            .unwrap();
+        for expected in [0, 100, 200] {
+            process.resume().unwrap();
+            let reason = process.wait_on_signal().unwrap();
+            assert_eq!(reason.breakpoint_site, Some(id));
+            assert_eq!(read(process), expected);
+        }
+        let site = process.breakpoint_sites().get_by_id(id).unwrap();
+        assert_eq!(site.hit_count(), 201);
+        assert_eq!(site.skipped_count(), 198);
+
+        // two stops and then nothing until the second is up
+        process
+            .set_breakpoint_hit_limit(id, Some(HitLimit::MaxPerSecond(2)))
+            .unwrap();
+        let started = std::time::Instant::now();
+        for _ in 0..3 {
+            process.resume().unwrap();
+            process.wait_on_signal().unwrap();
+        }
+        assert!(started.elapsed() >= std::time::Duration::from_secs(1));
+        assert!(
+            process
+                .breakpoint_sites()
+                .get_by_id(id)
+                .unwrap()
+                .skipped_count()
+                > 198
+        );
+
+        assert!(
+            process
+                .set_breakpoint_hit_limit(id, Some(HitLimit::Sample(0)))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn write_memory_assisted_restores_protections() {
+        let mut target = Target::launch("test/targets/step", None).unwrap();
+        target.create_line_breakpoint("step.c", 10).unwrap();
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+
+        let pc = target.process().get_pc().unwrap();
+        let add = target.function_address("add").unwrap();
+        let process = target.process_mut();
+        let original = process.read_memory(add, 4).unwrap();
+
+        let perms = |process: &Process| {
+            let maps = procfs::process::Process::new(process.get_id().as_raw())
+                .unwrap()
+                .maps()
+                .unwrap();
+            maps.into_iter()
+                .find(|map| map.address.0 <= add.addr() && add.addr() < map.address.1)
+                .unwrap()
+                .perms
+        };
+        let before = perms(process);
+        assert!(!before.contains(procfs::process::MMPermissions::WRITE));
+
+        let assist = process
+            .write_memory_assisted(add, &[0xcc, 0xcc, 0xcc, 0xcc])
+            .unwrap();
+        assert!(assist.was_used());
+        assert_eq!(
+            process.read_memory(add, 4).unwrap(),
+            [0xcc, 0xcc, 0xcc, 0xcc]
+        );
+        assert_eq!(perms(process), before);
+        assert_eq!(process.get_pc().unwrap(), pc);
+
+        process.write_memory_assisted(add, &original).unwrap();
+
+        // the stack is already writable
+        let rsp = process.read_register_by_id(RegisterId::rsp).unwrap();
+        let below_stack = VirtAddr::new(rsp.to_u64().unwrap() - 8);
+        let assist = process.write_memory_assisted(below_stack, &[1]).unwrap();
+        assert_eq!(assist, WriteAssist::None);
+
+        process.resume().unwrap();
+        let reason = process.wait_on_signal().unwrap();
+        assert_eq!(reason.reason, ProcessState::Exited);
+        assert_eq!(reason.info, 0);
+    }
+
+    #[test]
+    fn core_target_backtrace() {
+        let Some(core) = test::dump_core("test/targets/crash", "crash") else {
+            eprintln!("Skipping, cores aren't written somewhere predictable");
+            return;
+        };
+
+        let target = CoreTarget::open("test/targets/crash", &core).unwrap();
+        std::fs::remove_dir_all(core.parent().unwrap()).unwrap();
+
+        assert_eq!(target.signal(), Some(signal::SIGSEGV));
+        assert_eq!(target.threads().len(), 1);
+
+        let backtrace = target.backtrace().unwrap();
+        let frames = backtrace
+            .iter()
+            .map(|frame| {
+                (
+                    frame.function.as_deref(),
+                    frame.location.as_ref().map(|location| location.line),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            frames[..2],
+            [(Some("crash"), Some(4)), (Some("main"), Some(10))]
+        );
+
+        // code isn't in the core, it comes from the executable
+        let pc = target.get_pc().unwrap();
+        assert_eq!(target.read_memory(pc, 16).unwrap().len(), 16);
+    }
+
+    #[test]
+    fn timings_count_resumes_and_steps() {
+        let mut target = Target::launch("test/targets/step", None).unwrap();
+        assert!(target.process().timings().is_empty());
+
+        test::break_at_function(&mut target, "add");
+        let process = target.process_mut();
+
+        process.resume().unwrap();
+        process.wait_on_signal().unwrap();
+        process.step_instruction().unwrap();
+        process.step_instruction().unwrap();
+
+        let resume = process.timings().get(TimedOperation::Resume).unwrap();
+        assert_eq!(resume.count(), 1);
+        assert_eq!(resume.min(), resume.max());
+
+        let step = process.timings().get(TimedOperation::Step).unwrap();
+        assert_eq!(step.count(), 2);
+        assert!(step.min() <= step.mean() && step.mean() <= step.max());
+        assert!(step.total() >= step.max());
+
+        process.reset_timings();
+        assert!(process.timings().is_empty());
+    }
+
+    #[test]
+    fn proc_info() {
+        let file = std::env::temp_dir().join(format!("sdb-proc-info-{}", std::process::id()));
+        let process = LaunchConfig::new("cat")
+            .args(["-", "extra"])
+            .stdout(Redirect::file(&file))
+            .launch()
+            .unwrap();
+        let pid = nix::unistd::getpid().as_raw();
+
+        assert_eq!(process.cmdline().unwrap(), ["cat", "-", "extra"]);
+        let environ = process.environ().unwrap();
+        assert!(environ.iter().any(|var| var.starts_with("PATH=")));
+
+        let status = process.proc_status().unwrap();
+        assert_eq!(status.name, "cat");
+        assert!(status.state.starts_with('t'));
+        // traced by this thread
+        assert_eq!(status.tracer_pid, nix::unistd::gettid().as_raw());
+        assert_eq!(status.threads, 1);
+
+        let stat = process.proc_stat().unwrap();
+        assert_eq!(stat.state, 't');
+        assert_eq!(stat.ppid, pid);
+        assert_eq!(stat.pgrp, process.get_id().as_raw());
+
+        let fds = process.open_fds().unwrap();
+        let stdout = fds.iter().find(|fd| fd.fd == 1).unwrap();
+        assert_eq!(stdout.target, file);
+        assert_eq!(stdout.position, Some(0));
+        let access = stdout.flags.unwrap() as i32 & libc::O_ACCMODE;
+        assert_eq!(access, libc::O_WRONLY);
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn auxv() {
+        let target = Target::launch("test/targets/step", None).unwrap();
+        let auxv = target.process().auxv().unwrap();
+
+        let main = target.main_elf().unwrap();
+        assert!(main.path().ends_with("test/targets/step"));
+        assert_eq!(auxv.entry(), Some(main.entry_point()));
+        assert_eq!(auxv.get(AuxvKey::PageSize), Some(0x1000));
+        assert!(auxv.interpreter_base().is_some());
+
+        let execfn = auxv.get(AuxvKey::Execfn).unwrap();
+        let execfn = target.read_c_string(VirtAddr::new(execfn), 256).unwrap();
+        assert!(execfn.ends_with(b"test/targets/step"));
+
+        let data = [6u64, 0x1000, 99, 1, 0, 0, 9, 1]
+            .iter()
+            .flat_map(|word| word.to_ne_bytes())
+            .collect::<Vec<_>>();
+        let auxv = Auxv::parse(&data).unwrap();
+        assert_eq!(
+            auxv.iter().collect::<Vec<_>>(),
+            [(AuxvKey::PageSize, 0x1000), (AuxvKey::Other(99), 1)]
+        );
+        assert!(Auxv::parse(&data[..24]).is_err());
+    }
+
+    #[test]
+    fn write_core_round_trips() {
+        let mut target = Target::launch("test/targets/step", None).unwrap();
+        target.create_function_breakpoint("add").unwrap();
+        target.process_mut().resume().unwrap();
+        target.wait_on_signal().unwrap();
+
+        let path = std::env::temp_dir().join(format!("sdb-write-core-{}", std::process::id()));
+        target.process().write_core(&path).unwrap();
+        let core = CoreTarget::open("test/targets/step", &path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(core.get_id(), target.process().get_id());
+        assert_eq!(core.signal(), Some(signal::SIGTRAP));
+        assert_eq!(core.get_pc().unwrap(), target.process().get_pc().unwrap());
+        assert_eq!(
+            core.read_register_by_id(RegisterId::rsp).unwrap().to_u64(),
+            target
+                .process()
+                .read_register_by_id(RegisterId::rsp)
+                .unwrap()
+                .to_u64()
+        );
+        assert_eq!(core.backtrace().unwrap(), target.backtrace().unwrap());
+        assert_eq!(
+            Inferior::auxv(&core).unwrap(),
+            target.process().auxv().unwrap()
+        );
+
+        // the breakpoint isn't part of the dumped code
+        let pc = core.get_pc().unwrap();
+        assert_eq!(
+            core.read_memory(pc, 8).unwrap(),
+            target.process().read_memory_without_traps(pc, 8).unwrap()
+        );
+    }
+
+    #[test]
+    fn stripped_elf_finds_debuglink() {
+        let dir = std::env::temp_dir().join(format!("sdb-debuglink-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let exe = dir.join("variables");
+        std::fs::copy("test/targets/variables", &exe).unwrap();
+
+        let run = |program: &str, args: &[&str]| {
+            let status = std::process::Command::new(program)
+                .args(args)
+                .current_dir(&dir)
+                .status()
+                .unwrap();
+            assert!(status.success(), "{} {:?}", program, args);
+        };
+        run(
+            "objcopy",
+            &["--only-keep-debug", "variables", "variables.debug"],
+        );
+        run("strip", &["--strip-all", "variables"]);
+        run(
+            "objcopy",
+            &["--add-gnu-debuglink=variables.debug", "variables"],
+        );
+
+        let elf = Elf::open(&exe).unwrap();
+        let debug_file = elf.debug_file().map(Path::to_path_buf);
+        let has_area = elf.symbols_by_name("area").next().is_some();
+        let functions = elf.dwarf().map(|dwarf| dwarf.functions().len());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(elf.build_id().is_some());
+        assert_eq!(debug_file, Some(dir.join("variables.debug")));
+        assert!(has_area);
+        assert_eq!(functions, Some(2));
+    }
+
+    #[test]
+    fn checksec_mitigations() {
+        let hardened = Elf::open("test/targets/hardened")
+            .unwrap()
+            .mitigations()
+            .unwrap();
+        assert_eq!(hardened.pie, Pie::Enabled);
+        assert_eq!(hardened.relro, Relro::Full);
+        assert!(hardened.nx && hardened.canary);
+        assert_eq!(hardened.fortified, ["__snprintf_chk"]);
+        // copy's destination could be anything, so its strcpy isn't checked
+        assert_eq!(hardened.fortifiable, ["strcpy"]);
+
+        let plain = Elf::open("test/targets/heap")
+            .unwrap()
+            .mitigations()
+            .unwrap();
+        assert_eq!(plain.pie, Pie::Enabled);
+        assert_eq!(plain.relro, Relro::Partial);
+        assert!(plain.nx && !plain.canary);
+        assert!(plain.fortified.is_empty() && plain.fortifiable.is_empty());
+
+        let target = Target::launch("test/targets/heap", None).unwrap();
+        let main = target.main_elf().unwrap().path().to_path_buf();
+        let linker = target
+            .modules()
+            .iter()
+            .find(|elf| elf.path() != main)
+            .unwrap();
+        assert_eq!(linker.mitigations().unwrap().pie, Pie::SharedObject);
+    }
+
+    #[test]
+    fn debuglink_crc() {
+        assert_eq!(debuginfo::crc32(b""), 0);
+        assert_eq!(debuginfo::crc32(b"123456789"), 0xcbf43926);
+    }
+
+    #[test]
+    fn debuginfod_http_get() {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            for body in [Some("debug info"), None] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = [0; 1024];
+                let len = stream.read(&mut request).unwrap();
+                assert!(request[..len].starts_with(b"GET /buildid/abcd/debuginfo HTTP/1.0\r\n"));
+                match body {
+                    Some(body) => write!(stream, "HTTP/1.0 200 OK\r\n\r\n{}", body),
+                    None => write!(stream, "HTTP/1.0 404 Not Found\r\n\r\n"),
+                }
+                .unwrap();
+            }
+        });
+
+        let url = format!("http://127.0.0.1:{}/buildid/abcd/debuginfo", port);
+        assert_eq!(debuginfo::http_get(&url).unwrap(), b"debug info");
+        let err = debuginfo::http_get(&url).unwrap_err();
+        assert!(err.to_string().contains("HTTP 404"));
+        server.join().unwrap();
+
+        assert!(debuginfo::http_get("https://example.com/").is_err());
+    }
+
+    #[test]
+    fn protocol_round_trips() {
+        let requests = [
+            Request::BreakLine {
+                file: "dir with\ttab/step.c".into(),
+                line: 10,
+            },
+            Request::WriteMemory {
+                address: VirtAddr::new(0x1000),
+                data: vec![0x00, 0xcc, 0xff],
+            },
+            Request::ReadRegister("rip".to_owned()),
+        ];
+        for request in requests {
+            assert_eq!(Request::decode(&request.encode()).unwrap(), request);
+        }
+
+        let message = Message::Response(
+            7,
+            Response::Backtrace(vec![Frame {
+                pc: VirtAddr::new(0x1234),
+                function: Some("main".to_owned()),
+                location: Some(SourceLocation {
+                    file: "a\\b\nc.c".into(),
+                    line: 3,
+                    column: 0,
+                }),
+            }]),
+        );
+        assert_eq!(Message::decode(&message.encode()).unwrap(), message);
+
+        let message = Message::Event(DebugEvent::Output {
+            stream: OutputStream::Stderr,
+            line: "tab\there".to_owned(),
+            time: std::time::UNIX_EPOCH + std::time::Duration::from_micros(1_700_000_000_123_456),
+        });
+        assert_eq!(Message::decode(&message.encode()).unwrap(), message);
+
+        let message = Message::Event(DebugEvent::Syscall(SyscallRecord {
+            tid: Pid::from_raw(42),
+            number: 1,
+            args: vec!["1".to_owned(), "\"a\\tb\"".to_owned(), "4".to_owned()],
+            result: Some("4".to_owned()),
+        }));
+        assert_eq!(Message::decode(&message.encode()).unwrap(), message);
+
+        let message = Message::Event(DebugEvent::WatchpointHit {
+            id: 3,
+            old: None,
+            new: vec![0x01, 0x00],
+        });
+        assert_eq!(Message::decode(&message.encode()).unwrap(), message);
+
+        assert!(Request::decode("stepi\textra").is_err());
+        assert!(Message::decode("response\t1\tregister\tnope").is_err());
+    }
+
+    // breaks in add and checks what a session can see from there
+    fn exercise_transport(transport: &dyn DebugTransport) {
+        let events = transport.events().unwrap();
+
+        let Response::Breakpoint(id) = transport
+            .request(Request::BreakFunction("add".to_owned()))
+            .unwrap()
+        else {
+            panic!("expected a breakpoint");
+        };
+        assert_eq!(transport.request(Request::Resume).unwrap(), Response::Done);
+
+        let Response::Stopped(reason) = transport.request(Request::Wait).unwrap() else {
+            panic!("expected a stop");
+        };
+        assert_eq!(reason.breakpoint_site, Some(id));
+        // events may arrive after the response
+        assert!(
+            std::iter::from_fn(|| events.recv_timeout(Duration::from_secs(1)).ok()).any(
+                |event| matches!(event, DebugEvent::BreakpointHit { id: hit, .. } if hit == id)
+            )
+        );
+
+        let Response::StopInfo(info) = transport.request(Request::StopInfo).unwrap() else {
+            panic!("expected stop info");
+        };
+        assert_eq!(info.function.as_deref(), Some("add"));
+
+        let Response::Register(pc) = transport
+            .request(Request::ReadRegister("rip".to_owned()))
+            .unwrap()
+        else {
+            panic!("expected a register");
+        };
+        assert_eq!(Some(VirtAddr::new(pc)), info.pc);
+
+        assert!(
+            transport
+                .request(Request::ReadRegister("nope".to_owned()))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn in_process_transport() {
+        let controller = ProcessController::launch("test/targets/step", true, None).unwrap();
+        exercise_transport(&InProcessTransport::new(controller));
+    }
+
+    #[test]
+    fn stream_transport() {
+        let controller = ProcessController::launch("test/targets/step", true, None).unwrap();
+        let (client, server) = std::os::unix::net::UnixStream::pair().unwrap();
+
+        std::thread::scope(|scope| {
+            let serving = scope.spawn(|| {
+                let reader = server.try_clone().unwrap();
+                serve_session(&controller, reader, server)
+            });
+
+            exercise_transport(&StreamTransport::from_unix(client).unwrap());
+
+            // hanging up ends the session
+            serving.join().unwrap().unwrap();
+        });
+    }
+
+    #[test]
+    fn gdb_remote_server() {
+        let controller = ProcessController::launch("test/targets/step", true, None).unwrap();
+        let add = controller
+            .execute_target(|target| target.function_address("add"))
+            .unwrap()
+            .unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = remote::Server::new(InProcessTransport::new(controller));
+        std::thread::scope(|scope| {
+            let serving = scope.spawn(|| server.serve(listener.accept().unwrap().0));
+            let mut gdb = std::net::TcpStream::connect(address).unwrap();
+            let mut exchange = |packet: &str| test::gdb_exchange(&mut gdb, packet);
+
+            assert!(exchange("qSupported:swbreak+").contains("qXfer:features:read+"));
+            assert!(exchange("qXfer:features:read:target.xml:0,20000").starts_with("l<?xml"));
+            assert_eq!(exchange("QStartNoAckMode"), "OK");
+            assert!(exchange("?").starts_with("T05"));
+
+            assert_eq!(exchange(&format!("Z0,{:x},1", add.addr())), "OK");
+            let stop = exchange("vCont;c");
+            assert!(stop.starts_with("T05"));
+            assert!(stop.contains("swbreak:;"));
+
+            // rip is register 16, in target byte order
+            let rip = add
+                .addr()
+                .to_le_bytes()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<String>();
+            assert_eq!(exchange("p10"), rip);
+            assert_eq!(&exchange("g")[16 * 16..17 * 16], rip);
+
+            // breakpoints don't show up in memory
+            assert_ne!(exchange(&format!("m{:x},1", add.addr())), "cc");
+
+            assert_eq!(exchange(&format!("z0,{:x},1", add.addr())), "OK");
+            assert_eq!(exchange("vCont;c"), "W00");
+
+            assert_eq!(exchange("D"), "OK");
+            serving.join().unwrap().unwrap();
+        });
+    }
+
+    #[test]
+    fn gdb_remote_process() {
+        let controller = ProcessController::launch("test/targets/step", true, None).unwrap();
+        let add = controller
+            .execute_target(|target| target.function_address("add"))
+            .unwrap()
+            .unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = remote::Server::new(InProcessTransport::new(controller));
+        std::thread::scope(|scope| {
+            let serving = scope.spawn(|| server.serve(listener.accept().unwrap().0));
+            let remote = remote::RemoteProcess::connect(address).unwrap();
+
+            let Response::Breakpoint(id) = remote.request(Request::BreakAddress(add)).unwrap()
+            else {
+                panic!("expected a breakpoint");
+            };
+            remote.request(Request::Resume).unwrap();
+            let Response::Stopped(reason) = remote.request(Request::Wait).unwrap() else {
+                panic!("expected a stop");
+            };
+            assert_eq!(reason.breakpoint_site, Some(id));
+            assert_eq!(remote.get_pc().unwrap(), add);
+            assert_ne!(remote.read_memory(add, 1).unwrap(), [0xcc]);
+
+            let Response::Stopped(reason) = remote.request(Request::StepInstruction).unwrap()
+            else {
+                panic!("expected a stop");
+            };
+            assert_eq!(reason.trap_reason, Some(TrapType::SingleStep));
+            assert_ne!(remote.get_pc().unwrap(), add);
+
+            // there's no debug info on this side
+            assert!(remote.request(Request::StepIn).is_err());
+
+            remote.request(Request::RemoveBreakpoint(id)).unwrap();
+            remote.request(Request::Resume).unwrap();
+            let Response::Stopped(reason) = remote.request(Request::Wait).unwrap() else {
+                panic!("expected a stop");
+            };
+            assert_eq!(reason.reason, ProcessState::Exited);
+
+            drop(remote);
+            serving.join().unwrap().unwrap();
+        });
     }
 
     #[test]
@@ -373,4 +4977,27 @@ mod tests {
         let output = String::from_utf8(channel.read().unwrap()).unwrap();
         assert_eq!(output, "0xcafecafe");
     }
+
+    #[test]
+    fn output_capture_of_register_write() {
+        let output = OutputCapture::new();
+        let mut process = LaunchConfig::new("test/targets/reg_write")
+            .stdout(output.redirect())
+            .launch()
+            .unwrap();
+        process.resume().unwrap();
+        process.wait_on_signal().unwrap();
+
+        process
+            .write_register_by_id(RegisterId::rsi, 0xcafecafe_u64.into())
+            .unwrap();
+        process.resume().unwrap();
+        process.wait_on_signal().unwrap();
+
+        // printed before the last trap, and everything's in once it's exited
+        process.resume().unwrap();
+        process.wait_on_signal().unwrap();
+        output.wait_for_end();
+        assert_eq!(output.text(), "0xcafecafe");
+    }
 }
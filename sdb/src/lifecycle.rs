@@ -0,0 +1,245 @@
+use nix::{libc, unistd::Pid};
+use tracing::trace;
+
+use crate::{
+    Process, Result, SdbError,
+    stoppoint_collection::{Stoppoint, StoppointId, StoppointKind},
+    types::VirtAddr,
+};
+
+/// The points in a process's life ptrace stops it at
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LifecycleEvent {
+    Fork,
+    Vfork,
+    Exec,
+    Exit,
+}
+
+impl LifecycleEvent {
+    pub const ALL: [LifecycleEvent; 4] = [
+        LifecycleEvent::Fork,
+        LifecycleEvent::Vfork,
+        LifecycleEvent::Exec,
+        LifecycleEvent::Exit,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LifecycleEvent::Fork => "fork",
+            LifecycleEvent::Vfork => "vfork",
+            LifecycleEvent::Exec => "exec",
+            LifecycleEvent::Exit => "exit",
+        }
+    }
+
+    fn from_ptrace(event: i32) -> Option<Self> {
+        match event {
+            libc::PTRACE_EVENT_FORK => Some(LifecycleEvent::Fork),
+            libc::PTRACE_EVENT_VFORK => Some(LifecycleEvent::Vfork),
+            libc::PTRACE_EVENT_EXEC => Some(LifecycleEvent::Exec),
+            libc::PTRACE_EVENT_EXIT => Some(LifecycleEvent::Exit),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for LifecycleEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for LifecycleEvent {
+    type Err = SdbError;
+
+    fn from_str(name: &str) -> Result<Self> {
+        LifecycleEvent::ALL
+            .into_iter()
+            .find(|event| event.name() == name)
+            .ok_or_else(|| SdbError::Stoppoint(format!("Unknown event {}", name)))
+    }
+}
+
+/// Stops the process when it forks, execs, or exits
+#[derive(Debug)]
+pub struct EventCatchpoint {
+    id: StoppointId,
+    event: LifecycleEvent,
+    is_enabled: bool,
+    hit_count: u64,
+}
+
+impl Stoppoint for EventCatchpoint {
+    #[inline]
+    fn id(&self) -> StoppointId {
+        self.id
+    }
+
+    #[inline]
+    fn address(&self) -> VirtAddr {
+        VirtAddr::default()
+    }
+
+    #[inline]
+    fn has_address(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn is_enabled(&self) -> bool {
+        self.is_enabled
+    }
+
+    #[inline]
+    fn kind(&self) -> StoppointKind {
+        StoppointKind::Catchpoint
+    }
+
+    #[inline]
+    fn hit_count(&self) -> u64 {
+        self.hit_count
+    }
+
+    #[inline]
+    fn spec(&self) -> String {
+        self.event.to_string()
+    }
+}
+
+impl EventCatchpoint {
+    #[inline]
+    pub fn event(&self) -> LifecycleEvent {
+        self.event
+    }
+}
+
+impl Process {
+    #[inline]
+    pub fn event_catchpoints(&self) -> &crate::StoppointCollection<EventCatchpoint> {
+        &self.event_catchpoints
+    }
+
+    /// Stops the process when the event happens, a fork stops before either side is followed
+    pub fn catch_event(&mut self, event: LifecycleEvent) -> Result<StoppointId> {
+        let id = self.next_stoppoint_id;
+        self.next_stoppoint_id += 1;
+
+        self.event_catchpoints.push(EventCatchpoint {
+            id,
+            event,
+            is_enabled: true,
+            hit_count: 0,
+        });
+        self.update_trace_options()?;
+
+        Ok(id)
+    }
+
+    #[inline]
+    pub fn enable_event_catchpoint(&mut self, id: StoppointId) -> Result<()> {
+        self.event_catchpoints.get_by_id_mut(id)?.is_enabled = true;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn disable_event_catchpoint(&mut self, id: StoppointId) -> Result<()> {
+        self.event_catchpoints.get_by_id_mut(id)?.is_enabled = false;
+        Ok(())
+    }
+
+    pub fn remove_event_catchpoint(&mut self, id: StoppointId) -> Result<()> {
+        self.event_catchpoints.remove_by_id(id)?;
+        self.update_trace_options()
+    }
+
+    // exit stops are only asked for while something wants them
+    pub(crate) fn catches_exits(&self) -> bool {
+        self.event_catchpoints
+            .iter()
+            .any(|catchpoint| catchpoint.event == LifecycleEvent::Exit)
+    }
+
+    pub(crate) fn update_trace_options(&self) -> Result<()> {
+        if !self.is_attached {
+            return Ok(());
+        }
+
+        for tid in self.threads.keys() {
+            self.set_trace_options(*tid)?;
+        }
+        Ok(())
+    }
+
+    // counts the hit on the first catchpoint that catches the event
+    fn hit_event_catchpoint(&mut self, event: LifecycleEvent) -> Option<StoppointId> {
+        let catchpoint = self
+            .event_catchpoints
+            .iter_mut()
+            .find(|catchpoint| catchpoint.is_enabled && catchpoint.event == event)?;
+        catchpoint.hit_count += 1;
+        Some(catchpoint.id)
+    }
+
+    // returns true if the event was handled and the process should be resumed,
+    // a caught one is set on the reason to be reported
+    pub(crate) fn handle_lifecycle_event(
+        &mut self,
+        tid: Pid,
+        event: i32,
+        reason: &mut crate::StopReason,
+    ) -> Result<bool> {
+        let lifecycle = LifecycleEvent::from_ptrace(event);
+        let catchpoint = match lifecycle {
+            // other threads exiting isn't the process exiting
+            Some(LifecycleEvent::Exit) if tid != self.pid => None,
+            Some(event) => self.hit_event_catchpoint(event),
+            None => None,
+        };
+        if catchpoint.is_some() {
+            reason.catchpoint = catchpoint;
+            reason.lifecycle_event = lifecycle;
+        }
+
+        let resume = match lifecycle {
+            Some(LifecycleEvent::Exec) => {
+                self.handle_exec()?;
+                true
+            }
+            Some(LifecycleEvent::Exit) => true,
+            _ => self.handle_fork_event(tid, event, catchpoint.is_some())?,
+        };
+        Ok(resume && catchpoint.is_none())
+    }
+
+    // the old image is gone and every int3 and patch along with it
+    fn handle_exec(&mut self) -> Result<()> {
+        trace!("Process {} exec'd", self.pid);
+
+        // only the thread group leader's left, whichever thread called exec
+        self.reset_threads(self.pid);
+        self.read_all_registers(self.pid)?;
+
+        for site in self.breakpoint_sites.iter_mut() {
+            site.forget();
+        }
+        for hook in self.function_hooks.iter_mut() {
+            hook.forget();
+        }
+        self.patched.lock().unwrap().clear();
+
+        // the debugger's own were set on the old program's libraries
+        let internal = self
+            .breakpoint_sites
+            .iter()
+            .filter(|site| site.kind() != StoppointKind::Breakpoint)
+            .filter(|site| site.kind() != StoppointKind::Logpoint)
+            .map(|site| site.id())
+            .collect::<Vec<_>>();
+        for id in internal {
+            self.breakpoint_sites.remove_by_id(id)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,323 @@
+use std::os::fd::{AsFd, OwnedFd};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use nix::{
+    errno::Errno,
+    fcntl,
+    poll::{self, PollFd, PollFlags, PollTimeout},
+    unistd,
+};
+
+use crate::{
+    DebugEvent, Redirect, Result, SdbError,
+    event::EventSubscribers,
+    launch::{dup, write_all},
+};
+
+/// Which of a process's streams some output was written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+impl OutputStream {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+        }
+    }
+}
+
+// how much of stderr is kept for a launch that fails, enough for what a program says on its way out
+const STDERR_TAIL: usize = 4096;
+
+// one stream the process writes to a pipe, split into lines as it's read
+#[derive(Debug)]
+struct Capture {
+    stream: OutputStream,
+    read: Arc<OwnedFd>,
+    passthrough: Option<OwnedFd>,
+
+    // the start of a line that's still being written
+    partial: Vec<u8>,
+    closed: bool,
+
+    // the last of what's been written to stderr
+    tail: Vec<u8>,
+}
+
+impl Capture {
+    fn emit_lines(&mut self, subscribers: &EventSubscribers) {
+        let time = SystemTime::now();
+        while let Some(end) = self.partial.iter().position(|&byte| byte == b'\n') {
+            let line = self.partial.drain(..=end).collect::<Vec<_>>();
+            self.emit(subscribers, &line[..end], time);
+        }
+    }
+
+    fn emit(&self, subscribers: &EventSubscribers, line: &[u8], time: SystemTime) {
+        subscribers.emit(DebugEvent::Output {
+            stream: self.stream,
+            line: String::from_utf8_lossy(line).into_owned(),
+            time,
+        });
+    }
+
+    // reads whatever is in the pipe without waiting for more
+    fn read_available(&mut self, subscribers: &EventSubscribers) {
+        let mut buf = [0; 4096];
+        while !self.closed {
+            let count = match unistd::read(&self.read, &mut buf) {
+                Ok(0) => {
+                    self.closed = true;
+                    break;
+                }
+                Ok(count) => count,
+                Err(Errno::EINTR) => continue,
+                Err(Errno::EAGAIN) => break,
+                Err(_) => {
+                    self.closed = true;
+                    break;
+                }
+            };
+
+            // a passthrough that's gone away is dropped, the events keep coming
+            if let Some(passthrough) = &self.passthrough
+                && write_all(passthrough, &buf[..count]).is_err()
+            {
+                self.passthrough = None;
+            }
+
+            if self.stream == OutputStream::Stderr {
+                self.tail.extend_from_slice(&buf[..count]);
+                let excess = self.tail.len().saturating_sub(STDERR_TAIL);
+                self.tail.drain(..excess);
+            }
+
+            self.partial.extend_from_slice(&buf[..count]);
+            self.emit_lines(subscribers);
+        }
+
+        if self.closed {
+            self.flush(subscribers);
+            // whatever it passes through to may be waiting for the end too
+            self.passthrough = None;
+        }
+    }
+
+    // a line without its newline yet is emitted as it is
+    fn flush(&mut self, subscribers: &EventSubscribers) {
+        if !self.partial.is_empty() {
+            let line = std::mem::take(&mut self.partial);
+            self.emit(subscribers, &line, SystemTime::now());
+        }
+    }
+}
+
+/// The streams of a process that are sent to event subscribers
+#[derive(Debug, Default, Clone)]
+pub(crate) struct CapturedOutput {
+    captures: Arc<Mutex<Vec<Capture>>>,
+}
+
+impl CapturedOutput {
+    /// Makes the pipe for a stream, returning the end for the process to write to
+    pub fn add(&mut self, stream: OutputStream, passthrough: Option<OwnedFd>) -> Result<OwnedFd> {
+        let (read, write) = unistd::pipe2(fcntl::OFlag::O_CLOEXEC).map_err(SdbError::Pipe)?;
+
+        // only the debugger's end, the process writes the way it always does
+        fcntl::fcntl(&read, fcntl::FcntlArg::F_SETFL(fcntl::OFlag::O_NONBLOCK))
+            .map_err(SdbError::Pipe)?;
+
+        self.captures.lock().unwrap().push(Capture {
+            stream,
+            read: Arc::new(read),
+            passthrough,
+            partial: Vec::new(),
+            closed: false,
+            tail: Vec::new(),
+        });
+        Ok(write)
+    }
+
+    /// Relays the streams as they're written to until the process closes them
+    pub fn spawn_relay(&self, subscribers: EventSubscribers) {
+        if self.captures.lock().unwrap().is_empty() {
+            return;
+        }
+
+        let this = self.clone();
+        std::thread::spawn(move || {
+            loop {
+                let reads = this
+                    .captures
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|capture| !capture.closed)
+                    .map(|capture| capture.read.clone())
+                    .collect::<Vec<_>>();
+                if reads.is_empty() {
+                    break;
+                }
+
+                let mut fds = reads
+                    .iter()
+                    .map(|read| PollFd::new(read.as_fd(), PollFlags::POLLIN))
+                    .collect::<Vec<_>>();
+                match poll::poll(&mut fds, PollTimeout::NONE) {
+                    Ok(_) | Err(Errno::EINTR) => (),
+                    Err(_) => break,
+                }
+
+                // a stop may have drained it in the meantime, which just leaves nothing to read
+                this.drain_available(&subscribers);
+            }
+        });
+    }
+
+    fn drain_available(&self, subscribers: &EventSubscribers) {
+        for capture in self.captures.lock().unwrap().iter_mut() {
+            capture.read_available(subscribers);
+        }
+    }
+
+    /// Emits everything written so far, partial lines included
+    ///
+    /// The relay reads and emits under the same lock, so nothing it's read can come after this
+    pub fn drain(&self, subscribers: &EventSubscribers) {
+        for capture in self.captures.lock().unwrap().iter_mut() {
+            capture.read_available(subscribers);
+            capture.flush(subscribers);
+        }
+    }
+
+    /// The last few KiB written to stderr, once everything written so far has been emitted
+    pub fn stderr_tail(&self, subscribers: &EventSubscribers) -> String {
+        self.drain(subscribers);
+        self.captures
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|capture| capture.stream == OutputStream::Stderr)
+            .map(|capture| String::from_utf8_lossy(&capture.tail).into_owned())
+            .collect()
+    }
+}
+
+/// Everything a launched process writes to the streams redirected here, kept to be read whenever
+///
+/// Clones are handles to the same output, which carries on across restarts
+#[derive(Debug, Default, Clone)]
+pub struct OutputCapture {
+    shared: Arc<(Mutex<CaptureState>, Condvar)>,
+}
+
+#[derive(Debug, Default)]
+struct CaptureState {
+    data: Vec<u8>,
+    // pipes that haven't been closed by the processes writing to them
+    open: usize,
+}
+
+impl OutputCapture {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Redirects a stream here, stdout and stderr can both be
+    #[inline]
+    pub fn redirect(&self) -> Redirect {
+        Redirect::Capture {
+            capture: self.clone(),
+            passthrough: None,
+            events: false,
+        }
+    }
+
+    /// Redirects a stream here while still passing it through to a duplicate of `fd`
+    pub fn redirect_and_dup(&self, fd: impl AsFd) -> Result<Redirect> {
+        Ok(Redirect::Capture {
+            capture: self.clone(),
+            passthrough: Some(dup(fd)?),
+            events: false,
+        })
+    }
+
+    /// Redirects a stream here and to event subscribers as [`DebugEvent::Output`] lines,
+    /// while still passing it through to a duplicate of `fd`
+    pub fn redirect_events_and_dup(&self, fd: impl AsFd) -> Result<Redirect> {
+        Ok(Redirect::Capture {
+            capture: self.clone(),
+            passthrough: Some(dup(fd)?),
+            events: true,
+        })
+    }
+
+    /// Everything written so far
+    pub fn contents(&self) -> Vec<u8> {
+        self.state().data.clone()
+    }
+
+    /// Everything written so far, invalid UTF-8 replaced
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.state().data).into_owned()
+    }
+
+    /// Everything written so far, leaving nothing
+    pub fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut self.state().data)
+    }
+
+    /// Waits until every process writing here has closed its streams, then returns everything
+    pub fn wait_for_end(&self) -> Vec<u8> {
+        let (state, closed) = &*self.shared;
+        let state = closed
+            .wait_while(state.lock().unwrap(), |state| state.open > 0)
+            .unwrap();
+        state.data.clone()
+    }
+
+    fn state(&self) -> MutexGuard<'_, CaptureState> {
+        self.shared.0.lock().unwrap()
+    }
+
+    /// Makes the pipe for a stream and starts collecting from it,
+    /// returning the end for the process to write to
+    pub(crate) fn add(&self, passthrough: Option<OwnedFd>) -> Result<OwnedFd> {
+        let (read, write) = unistd::pipe2(fcntl::OFlag::O_CLOEXEC).map_err(SdbError::Pipe)?;
+        self.state().open += 1;
+
+        let this = self.clone();
+        std::thread::spawn(move || {
+            let mut passthrough = passthrough;
+            let mut buf = [0; 4096];
+            loop {
+                let count = match unistd::read(&read, &mut buf) {
+                    Ok(0) => break,
+                    Ok(count) => count,
+                    Err(Errno::EINTR) => continue,
+                    Err(_) => break,
+                };
+
+                // a passthrough that's gone away is dropped, the capture keeps everything
+                if let Some(fd) = &passthrough
+                    && write_all(fd, &buf[..count]).is_err()
+                {
+                    passthrough = None;
+                }
+                this.state().data.extend_from_slice(&buf[..count]);
+            }
+
+            let (state, closed) = &*this.shared;
+            state.lock().unwrap().open -= 1;
+            closed.notify_all();
+        });
+
+        Ok(write)
+    }
+}
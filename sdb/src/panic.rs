@@ -0,0 +1,100 @@
+use num_traits::ToPrimitive;
+
+use crate::{Process, RegisterId, Result, SdbError, StoppointId, Target, types::VirtAddr};
+
+// rust_panic is handed the payload once the message has been formatted,
+// the panic handler comes before that and only stops
+const PANIC_FUNCTIONS: &[&str] = &["rust_panic", "rust_begin_unwind"];
+
+// longer than any message anyone means to read
+const MAX_MESSAGE: u64 = 1 << 20;
+
+impl Target {
+    /// Stops the process when a Rust panic starts unwinding, reported like a fatal function
+    pub fn catch_panic(&mut self) -> Result<StoppointId> {
+        if self.find_panic_function().is_none() {
+            self.refresh_modules()?;
+        }
+        let (address, function) = self.find_panic_function().ok_or_else(|| {
+            SdbError::Stoppoint("No panic handler found, is it a Rust program?".to_owned())
+        })?;
+
+        if self.process.breakpoint_sites.contains_address(address) {
+            return Err(SdbError::Stoppoint(format!(
+                "{} is already caught",
+                function
+            )));
+        }
+
+        let id = self.process.create_breakpoint_site(address, true)?;
+        self.process
+            .breakpoint_sites
+            .get_by_id_mut(id)?
+            .catch(function.to_owned());
+        self.process.enable_breakpoint_site(id)?;
+
+        Ok(id)
+    }
+
+    fn find_panic_function(&self) -> Option<(VirtAddr, &'static str)> {
+        PANIC_FUNCTIONS.iter().find_map(|&function| {
+            let (elf, symbol) = self.modules.find_function(function)?;
+            Some((elf.symbol_address(symbol), function))
+        })
+    }
+}
+
+impl Process {
+    /// The message of the panic the process is stopped in rust_panic for, if it can be read
+    pub fn panic_message(&self) -> Option<String> {
+        let id = self.stop_reason()?.breakpoint_site?;
+        if self.fatal_function(id)? != PANIC_FUNCTIONS[0] {
+            return None;
+        }
+
+        // rust_panic(payload: &mut dyn PanicPayload), still on its first instruction
+        let data = self.read_register_by_id(RegisterId::rdi).ok()?.to_u64()?;
+        let vtable = self.read_register_by_id(RegisterId::rsi).ok()?.to_u64()?;
+
+        // a vtable starts with drop_in_place, then the size
+        let size = self.read_word(VirtAddr::new(vtable + 8))?;
+        if size == 0 || size > 32 || size % 8 != 0 {
+            return None;
+        }
+        let words = (0..size / 8)
+            .map(|idx| self.read_word(VirtAddr::new(data + idx * 8)))
+            .collect::<Option<Vec<_>>>()?;
+
+        // a &str, or a String alongside the Arguments it was formatted from
+        match words.as_slice() {
+            [pointer, len] => self.read_str(*pointer, *len),
+            words => words.windows(3).find_map(|string| self.read_string(string)),
+        }
+    }
+
+    fn read_word(&self, address: VirtAddr) -> Option<u64> {
+        let bytes = self.read_memory_without_traps(address, 8).ok()?;
+        Some(u64::from_le_bytes(bytes.try_into().ok()?))
+    }
+
+    fn read_str(&self, pointer: u64, len: u64) -> Option<String> {
+        if pointer == 0 || len > MAX_MESSAGE {
+            return None;
+        }
+        let bytes = self
+            .read_memory_without_traps(VirtAddr::new(pointer), len as usize)
+            .ok()?;
+        String::from_utf8(bytes).ok()
+    }
+
+    // which of capacity and pointer comes first has changed between Rust versions
+    fn read_string(&self, words: &[u64]) -> Option<String> {
+        let &[first, second, len] = words else {
+            return None;
+        };
+        [(first, second), (second, first)]
+            .into_iter()
+            .filter(|(capacity, _)| *capacity >= len)
+            .find_map(|(_, pointer)| self.read_str(pointer, len))
+    }
+}
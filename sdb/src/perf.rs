@@ -0,0 +1,267 @@
+use std::fs::File;
+use std::io::Read;
+use std::os::fd::{AsRawFd, FromRawFd};
+
+use nix::{errno::Errno, libc, unistd::Pid};
+
+use crate::{Process, Result, SdbError};
+
+// perf_event_attr as the first version of it was, the kernel takes any size it knows
+const ATTR_SIZE: u32 = 64;
+
+const PERF_TYPE_HARDWARE: u32 = 0;
+const PERF_TYPE_SOFTWARE: u32 = 1;
+
+const FLAG_DISABLED: u64 = 1 << 0;
+const FLAG_INHERIT: u64 = 1 << 1;
+const FLAG_EXCLUDE_KERNEL: u64 = 1 << 5;
+const FLAG_EXCLUDE_HV: u64 = 1 << 6;
+
+const PERF_FORMAT_TOTAL_TIME_ENABLED: u64 = 1 << 0;
+const PERF_FORMAT_TOTAL_TIME_RUNNING: u64 = 1 << 1;
+
+const PERF_FLAG_FD_CLOEXEC: libc::c_ulong = 1 << 3;
+
+// _IO('$', n)
+const PERF_EVENT_IOC_ENABLE: libc::c_ulong = 0x2400;
+const PERF_EVENT_IOC_DISABLE: libc::c_ulong = 0x2401;
+
+#[repr(C)]
+#[derive(Default)]
+struct PerfEventAttr {
+    kind: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+}
+
+/// What a counter counts, the hardware ones need a PMU the kernel lets us use
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PerfEvent {
+    Cycles,
+    Instructions,
+    CacheReferences,
+    CacheMisses,
+    Branches,
+    BranchMisses,
+    TaskClock,
+    PageFaults,
+    ContextSwitches,
+}
+
+impl PerfEvent {
+    pub const ALL: [PerfEvent; 9] = [
+        PerfEvent::Cycles,
+        PerfEvent::Instructions,
+        PerfEvent::CacheReferences,
+        PerfEvent::CacheMisses,
+        PerfEvent::Branches,
+        PerfEvent::BranchMisses,
+        PerfEvent::TaskClock,
+        PerfEvent::PageFaults,
+        PerfEvent::ContextSwitches,
+    ];
+
+    /// The name perf stat gives it
+    pub fn name(&self) -> &'static str {
+        match self {
+            PerfEvent::Cycles => "cycles",
+            PerfEvent::Instructions => "instructions",
+            PerfEvent::CacheReferences => "cache-references",
+            PerfEvent::CacheMisses => "cache-misses",
+            PerfEvent::Branches => "branches",
+            PerfEvent::BranchMisses => "branch-misses",
+            PerfEvent::TaskClock => "task-clock",
+            PerfEvent::PageFaults => "page-faults",
+            PerfEvent::ContextSwitches => "context-switches",
+        }
+    }
+
+    // type and config for perf_event_attr
+    fn config(&self) -> (u32, u64) {
+        match self {
+            PerfEvent::Cycles => (PERF_TYPE_HARDWARE, 0),
+            PerfEvent::Instructions => (PERF_TYPE_HARDWARE, 1),
+            PerfEvent::CacheReferences => (PERF_TYPE_HARDWARE, 2),
+            PerfEvent::CacheMisses => (PERF_TYPE_HARDWARE, 3),
+            PerfEvent::Branches => (PERF_TYPE_HARDWARE, 4),
+            PerfEvent::BranchMisses => (PERF_TYPE_HARDWARE, 5),
+            PerfEvent::TaskClock => (PERF_TYPE_SOFTWARE, 1),
+            PerfEvent::PageFaults => (PERF_TYPE_SOFTWARE, 2),
+            PerfEvent::ContextSwitches => (PERF_TYPE_SOFTWARE, 3),
+        }
+    }
+}
+
+impl std::fmt::Display for PerfEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for PerfEvent {
+    type Err = SdbError;
+
+    fn from_str(name: &str) -> Result<Self> {
+        PerfEvent::ALL
+            .into_iter()
+            .find(|event| event.name() == name)
+            .ok_or_else(|| SdbError::Perf(format!("Unknown counter {}", name)))
+    }
+}
+
+/// A counter's total over every thread it was opened on
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PerfReading {
+    pub event: PerfEvent,
+    pub value: u64,
+    /// Set if the kernel had to share the PMU and the value's scaled up from part of the time
+    pub scaled: bool,
+}
+
+/// Counters scoped to one process's threads, they only count while its threads run
+///
+/// Everything the debugger does while the process is stopped stays out of them,
+/// so enabling at one breakpoint and disabling at the next measures what's between
+#[derive(Default)]
+pub struct PerfCounters {
+    // one file per event per thread
+    counters: Vec<(PerfEvent, File)>,
+    enabled: bool,
+}
+
+fn open_counter(event: PerfEvent, tid: Pid) -> Result<File> {
+    let (kind, config) = event.config();
+    let attr = PerfEventAttr {
+        kind,
+        size: ATTR_SIZE,
+        config,
+        read_format: PERF_FORMAT_TOTAL_TIME_ENABLED | PERF_FORMAT_TOTAL_TIME_RUNNING,
+        // unprivileged users can only count their own code
+        flags: FLAG_DISABLED | FLAG_INHERIT | FLAG_EXCLUDE_KERNEL | FLAG_EXCLUDE_HV,
+        ..Default::default()
+    };
+
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_perf_event_open,
+            &attr as *const PerfEventAttr,
+            tid.as_raw(),
+            -1 as libc::c_int,
+            -1 as libc::c_int,
+            PERF_FLAG_FD_CLOEXEC,
+        )
+    };
+    match Errno::result(fd) {
+        Ok(fd) => Ok(unsafe { File::from_raw_fd(fd as i32) }),
+        Err(Errno::ENOENT | Errno::EOPNOTSUPP) => Err(SdbError::Perf(format!(
+            "{} isn't supported on this machine",
+            event
+        ))),
+        Err(Errno::EACCES | Errno::EPERM) => Err(SdbError::Perf(format!(
+            "Not allowed to count {}, see /proc/sys/kernel/perf_event_paranoid",
+            event
+        ))),
+        Err(err) => Err(SdbError::Perf(format!("Failed to open {}: {}", event, err))),
+    }
+}
+
+impl PerfCounters {
+    /// Opens the events on every thread the process has, and any they start later, disabled
+    pub fn open(process: &Process, events: &[PerfEvent]) -> Result<Self> {
+        let mut counters = Self::default();
+        for &event in events {
+            counters.add(process, event)?;
+        }
+        Ok(counters)
+    }
+
+    /// Adds an event, counting from when the others are next enabled
+    pub fn add(&mut self, process: &Process, event: PerfEvent) -> Result<()> {
+        let files = process
+            .threads()
+            .map(|thread| open_counter(event, thread.tid()))
+            .collect::<Result<Vec<_>>>()?;
+        for file in files {
+            if self.enabled {
+                Errno::result(unsafe { libc::ioctl(file.as_raw_fd(), PERF_EVENT_IOC_ENABLE, 0) })
+                    .map_err(|err| SdbError::Perf(format!("{}: {}", event, err)))?;
+            }
+            self.counters.push((event, file));
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.counters.is_empty()
+    }
+
+    fn ioctl_all(&self, request: libc::c_ulong) -> Result<()> {
+        for (event, file) in &self.counters {
+            Errno::result(unsafe { libc::ioctl(file.as_raw_fd(), request, 0) })
+                .map_err(|err| SdbError::Perf(format!("{}: {}", event, err)))?;
+        }
+        Ok(())
+    }
+
+    pub fn enable(&mut self) -> Result<()> {
+        self.ioctl_all(PERF_EVENT_IOC_ENABLE)?;
+        self.enabled = true;
+        Ok(())
+    }
+
+    /// Stops counting, what's been counted stays for reading
+    pub fn disable(&mut self) -> Result<()> {
+        self.ioctl_all(PERF_EVENT_IOC_DISABLE)?;
+        self.enabled = false;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Each event's total so far, in the order they were opened
+    pub fn read(&self) -> Result<Vec<PerfReading>> {
+        let mut readings: Vec<PerfReading> = Vec::new();
+        for (event, file) in &self.counters {
+            // value, time enabled, time running
+            let mut buffer = [0u8; 24];
+            (&*file)
+                .read_exact(&mut buffer)
+                .map_err(|err| SdbError::Perf(format!("Failed to read {}: {}", event, err)))?;
+            let [value, enabled, running] = std::array::from_fn(|idx| {
+                u64::from_ne_bytes(buffer[idx * 8..idx * 8 + 8].try_into().unwrap())
+            });
+
+            let scaled = running < enabled;
+            let value = match (scaled, running) {
+                (true, 0) => 0,
+                (true, _) => (value as u128 * enabled as u128 / running as u128) as u64,
+                (false, _) => value,
+            };
+
+            match readings.iter_mut().find(|reading| reading.event == *event) {
+                Some(reading) => {
+                    reading.value += value;
+                    reading.scaled |= scaled;
+                }
+                None => readings.push(PerfReading {
+                    event: *event,
+                    value,
+                    scaled,
+                }),
+            }
+        }
+
+        Ok(readings)
+    }
+}
@@ -0,0 +1,41 @@
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd};
+
+use nix::{
+    errno::Errno,
+    libc,
+    poll::{PollFd, PollFlags, PollTimeout, poll},
+    sys::signal::Signal,
+    unistd::Pid,
+};
+
+// a pidfd keeps referring to the process it was opened for,
+// a pid can be handed to some other process once this one's reaped
+
+// None on kernels older than 5.3 or when the process is already gone
+pub(crate) fn open(pid: Pid) -> Option<OwnedFd> {
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid.as_raw(), 0) };
+    if fd < 0 {
+        return None;
+    }
+    // pidfd_open always sets close-on-exec
+    Some(unsafe { OwnedFd::from_raw_fd(fd as i32) })
+}
+
+pub(crate) fn send_signal(fd: &OwnedFd, signal: Signal) -> nix::Result<()> {
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_pidfd_send_signal,
+            fd.as_raw_fd(),
+            signal as i32,
+            std::ptr::null::<libc::siginfo_t>(),
+            0,
+        )
+    };
+    Errno::result(ret).map(drop)
+}
+
+// a pidfd is readable once the process has exited, reaped or not
+pub(crate) fn has_exited(fd: &OwnedFd) -> bool {
+    let mut fds = [PollFd::new(fd.as_fd(), PollFlags::POLLIN)];
+    matches!(poll(&mut fds, PollTimeout::ZERO), Ok(n) if n > 0)
+}
@@ -1,13 +1,22 @@
-use std::os::fd::{AsFd, OwnedFd};
+use std::os::fd::{AsFd, BorrowedFd, OwnedFd};
+use std::time::Duration;
 
-use nix::{fcntl, unistd};
+use nix::{
+    errno::Errno,
+    fcntl,
+    poll::{self, PollFd, PollFlags, PollTimeout},
+    unistd,
+};
 
 use crate::{Result, SdbError};
 
+const DEFAULT_BUFFER_SIZE: usize = 1024;
+
 #[derive(Debug)]
 pub struct Pipe {
     read: Option<OwnedFd>,
     pub(crate) write: Option<OwnedFd>,
+    buffer_size: usize,
 }
 
 impl Pipe {
@@ -21,16 +30,112 @@ impl Pipe {
         Ok(Self {
             read: Some(read),
             write: Some(write),
+            buffer_size: DEFAULT_BUFFER_SIZE,
         })
     }
 
+    /// How much a single [`Self::read`] can return, 1024 bytes by default
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size.max(1);
+        self
+    }
+
+    /// Whatever's there, up to the buffer size, waiting for some if there's none yet
     pub fn read(&self) -> Result<Vec<u8>> {
-        if let Some(read) = &self.read {
-            let mut buf = [0; 1024];
-            let read = unistd::read(read, &mut buf).map_err(SdbError::Read)?;
-            Ok(buf[0..read].to_vec())
-        } else {
-            Err(SdbError::Other("Invalid Pipe Read".to_owned()))
+        let mut buf = vec![0; self.buffer_size];
+        let read = self.read_some(&mut buf)?;
+        buf.truncate(read);
+        Ok(buf)
+    }
+
+    /// Everything until every write end is closed
+    pub fn read_to_end(&self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut buf = vec![0; self.buffer_size];
+        loop {
+            match self.read_some(&mut buf)? {
+                0 => return Ok(data),
+                read => data.extend_from_slice(&buf[..read]),
+            }
+        }
+    }
+
+    /// Exactly `amount` bytes, it's an error if the write ends are closed before then
+    pub fn read_exact(&self, amount: usize) -> Result<Vec<u8>> {
+        let mut data = vec![0; amount];
+        let mut filled = 0;
+        while filled < amount {
+            match self.read_some(&mut data[filled..])? {
+                0 => {
+                    return Err(SdbError::Other(format!(
+                        "Pipe closed after {} of {} bytes",
+                        filled, amount
+                    )));
+                }
+                read => filled += read,
+            }
+        }
+        Ok(data)
+    }
+
+    /// Reads on the read end return straight away, [`Self::try_read`] tells no data from EOF
+    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<()> {
+        let Some(read) = &self.read else {
+            return Err(SdbError::Other("Invalid Pipe Read".to_owned()));
+        };
+
+        let flags = fcntl::fcntl(read, fcntl::FcntlArg::F_GETFL).map_err(SdbError::Pipe)?;
+        let mut flags = fcntl::OFlag::from_bits_truncate(flags);
+        flags.set(fcntl::OFlag::O_NONBLOCK, nonblocking);
+        fcntl::fcntl(read, fcntl::FcntlArg::F_SETFL(flags)).map_err(SdbError::Pipe)?;
+        Ok(())
+    }
+
+    /// None if there's nothing to read yet in non-blocking mode, empty at EOF
+    pub fn try_read(&self) -> Result<Option<Vec<u8>>> {
+        match self.read() {
+            Err(SdbError::Read(Errno::EAGAIN)) => Ok(None),
+            result => result.map(Some),
+        }
+    }
+
+    /// Waits up to `timeout` for there to be something to read, or EOF
+    pub fn wait_readable(&self, timeout: Duration) -> Result<bool> {
+        let Some(read) = &self.read else {
+            return Err(SdbError::Other("Invalid Pipe Read".to_owned()));
+        };
+
+        let timeout = PollTimeout::try_from(timeout).unwrap_or(PollTimeout::MAX);
+        let mut fds = [PollFd::new(read.as_fd(), PollFlags::POLLIN)];
+        loop {
+            match poll::poll(&mut fds, timeout) {
+                Ok(ready) => return Ok(ready > 0),
+                Err(Errno::EINTR) => continue,
+                Err(errno) => return Err(SdbError::Pipe(errno)),
+            }
+        }
+    }
+
+    /// The read end, to poll alongside other fds
+    #[inline]
+    pub fn read_fd(&self) -> Option<BorrowedFd<'_>> {
+        self.read.as_ref().map(AsFd::as_fd)
+    }
+
+    #[inline]
+    pub fn write_fd(&self) -> Option<BorrowedFd<'_>> {
+        self.write.as_ref().map(AsFd::as_fd)
+    }
+
+    fn read_some(&self, buf: &mut [u8]) -> Result<usize> {
+        let Some(read) = &self.read else {
+            return Err(SdbError::Other("Invalid Pipe Read".to_owned()));
+        };
+        loop {
+            match unistd::read(read, buf) {
+                Err(Errno::EINTR) => continue,
+                result => return result.map_err(SdbError::Read),
+            }
         }
     }
 
@@ -49,4 +154,15 @@ impl Pipe {
     pub fn close_write(&mut self) {
         self.write = None;
     }
+
+    /// Hands over the read end, to give to a process or a Redirect
+    #[inline]
+    pub fn take_read(&mut self) -> Option<OwnedFd> {
+        self.read.take()
+    }
+
+    #[inline]
+    pub fn take_write(&mut self) -> Option<OwnedFd> {
+        self.write.take()
+    }
 }
@@ -0,0 +1,184 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::{Process, Result, SdbError};
+
+// status reports sizes in kB
+const KB: u64 = 1024;
+
+/// What /proc/pid/status says about the process, sizes in bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcStatus {
+    pub name: String,
+    /// Like "t (tracing stop)"
+    pub state: String,
+    pub ppid: i32,
+    pub tracer_pid: i32,
+    pub uid: u32,
+    pub euid: u32,
+    pub gid: u32,
+    pub egid: u32,
+    pub threads: u64,
+    pub vm_peak: Option<u64>,
+    pub vm_size: Option<u64>,
+    pub vm_hwm: Option<u64>,
+    pub vm_rss: Option<u64>,
+    pub voluntary_context_switches: Option<u64>,
+    pub nonvoluntary_context_switches: Option<u64>,
+    // signal masks, bit n - 1 is signal n
+    pub signals_pending: u64,
+    pub signals_blocked: u64,
+    pub signals_ignored: u64,
+    pub signals_caught: u64,
+}
+
+/// What /proc/pid/stat says about the process, times since it started and sizes in bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcStat {
+    pub comm: String,
+    pub state: char,
+    pub ppid: i32,
+    pub pgrp: i32,
+    pub session: i32,
+    pub tty_nr: i32,
+    pub minor_faults: u64,
+    pub major_faults: u64,
+    pub user_time: Duration,
+    pub system_time: Duration,
+    pub priority: i64,
+    pub nice: i64,
+    pub threads: i64,
+    /// How long after boot the process started
+    pub start_time: Duration,
+    pub vsize: u64,
+    pub rss: u64,
+    /// The CPU it last ran on
+    pub processor: Option<i32>,
+}
+
+/// A file descriptor the process has open
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenFd {
+    pub fd: i32,
+    /// What the fd links to, like a path, "pipe:[1234]", or "socket:[5678]"
+    pub target: PathBuf,
+    /// The file offset, None if it couldn't be read
+    pub position: Option<u64>,
+    /// The open flags, in octal in fdinfo
+    pub flags: Option<u32>,
+}
+
+impl Process {
+    fn procfs(&self) -> Result<procfs::process::Process> {
+        Ok(procfs::process::Process::new(self.pid.as_raw())?)
+    }
+
+    pub fn proc_status(&self) -> Result<ProcStatus> {
+        let status = self.procfs()?.status()?;
+        let bytes = |kb: Option<u64>| kb.map(|kb| kb * KB);
+
+        Ok(ProcStatus {
+            name: status.name,
+            state: status.state,
+            ppid: status.ppid,
+            tracer_pid: status.tracerpid,
+            uid: status.ruid,
+            euid: status.euid,
+            gid: status.rgid,
+            egid: status.egid,
+            threads: status.threads,
+            vm_peak: bytes(status.vmpeak),
+            vm_size: bytes(status.vmsize),
+            vm_hwm: bytes(status.vmhwm),
+            vm_rss: bytes(status.vmrss),
+            voluntary_context_switches: status.voluntary_ctxt_switches,
+            nonvoluntary_context_switches: status.nonvoluntary_ctxt_switches,
+            signals_pending: status.sigpnd | status.shdpnd,
+            signals_blocked: status.sigblk,
+            signals_ignored: status.sigign,
+            signals_caught: status.sigcgt,
+        })
+    }
+
+    pub fn proc_stat(&self) -> Result<ProcStat> {
+        let stat = self.procfs()?.stat()?;
+        let ticks =
+            |ticks: u64| Duration::from_secs_f64(ticks as f64 / procfs::ticks_per_second() as f64);
+
+        Ok(ProcStat {
+            comm: stat.comm,
+            state: stat.state,
+            ppid: stat.ppid,
+            pgrp: stat.pgrp,
+            session: stat.session,
+            tty_nr: stat.tty_nr,
+            minor_faults: stat.minflt,
+            major_faults: stat.majflt,
+            user_time: ticks(stat.utime),
+            system_time: ticks(stat.stime),
+            priority: stat.priority,
+            nice: stat.nice,
+            threads: stat.num_threads,
+            start_time: ticks(stat.starttime),
+            vsize: stat.vsize,
+            rss: stat.rss * procfs::page_size(),
+            processor: stat.processor,
+        })
+    }
+
+    /// The arguments the process was started with, as it may have changed them since
+    pub fn cmdline(&self) -> Result<Vec<String>> {
+        Ok(self.procfs()?.cmdline()?)
+    }
+
+    /// The environment the process was started with, as NAME=value in its order
+    pub fn environ(&self) -> Result<Vec<String>> {
+        let data = std::fs::read(format!("/proc/{}/environ", self.pid))
+            .map_err(|err| SdbError::Other(format!("Couldn't read the environment: {}", err)))?;
+        Ok(data
+            .split(|&byte| byte == 0)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| String::from_utf8_lossy(entry).into_owned())
+            .collect())
+    }
+
+    /// The process's open file descriptors, lowest first
+    pub fn open_fds(&self) -> Result<Vec<OpenFd>> {
+        let dir = format!("/proc/{}/fd", self.pid);
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|err| SdbError::Other(format!("Couldn't read {}: {}", dir, err)))?;
+
+        let mut fds = Vec::new();
+        for entry in entries.flatten() {
+            let Some(fd) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.parse().ok())
+            else {
+                continue;
+            };
+            // closed since the directory was read
+            let Ok(target) = std::fs::read_link(entry.path()) else {
+                continue;
+            };
+
+            let info = std::fs::read_to_string(format!("/proc/{}/fdinfo/{}", self.pid, fd))
+                .unwrap_or_default();
+            let field = |name: &str| {
+                info.lines()
+                    .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+                    .map(str::trim)
+            };
+
+            fds.push(OpenFd {
+                fd,
+                target,
+                position: field("pos").and_then(|pos| pos.parse().ok()),
+                flags: field("flags").and_then(|flags| u32::from_str_radix(flags, 8).ok()),
+            });
+        }
+        fds.sort_by_key(|fd| fd.fd);
+
+        Ok(fds)
+    }
+}
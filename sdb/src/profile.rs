@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use nix::sys::signal::Signal;
+
+use crate::{Frame, Inferior, ProcessState, Result, StopReason, Target};
+
+// what a frame with no symbol is called, as perf does
+const UNKNOWN_FUNCTION: &str = "[unknown]";
+
+/// How often a function was sampled, on top of the stack or anywhere in it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionSamples {
+    pub name: String,
+    pub own: u64,
+    pub total: u64,
+}
+
+/// Stacks sampled from a process, counted by how often each one came up
+#[derive(Debug, Default, Clone)]
+pub struct Profile {
+    // outermost function first
+    stacks: HashMap<Vec<String>, u64>,
+    samples: u64,
+}
+
+impl Profile {
+    /// Adds a backtrace, innermost frame first as backtraces are
+    pub fn record(&mut self, frames: &[Frame]) {
+        let stack = frames
+            .iter()
+            .rev()
+            .map(|frame| {
+                frame
+                    .function
+                    .clone()
+                    .unwrap_or_else(|| UNKNOWN_FUNCTION.to_owned())
+            })
+            .collect();
+        *self.stacks.entry(stack).or_default() += 1;
+        self.samples += 1;
+    }
+
+    #[inline]
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    /// Each distinct stack, outermost function first, and how often it was sampled
+    pub fn stacks(&self) -> impl Iterator<Item = (&[String], u64)> {
+        self.stacks
+            .iter()
+            .map(|(stack, count)| (stack.as_slice(), *count))
+    }
+
+    /// Every function sampled, the ones most often on top of the stack first
+    pub fn functions(&self) -> Vec<FunctionSamples> {
+        let mut functions: HashMap<&str, FunctionSamples> = HashMap::new();
+        for (stack, &count) in &self.stacks {
+            // recursion only counts once towards the total
+            let mut seen = Vec::new();
+            for name in stack {
+                if seen.contains(&name) {
+                    continue;
+                }
+                seen.push(name);
+                functions
+                    .entry(name)
+                    .or_insert_with(|| FunctionSamples {
+                        name: name.clone(),
+                        own: 0,
+                        total: 0,
+                    })
+                    .total += count;
+            }
+            if let Some(top) = stack.last() {
+                functions.get_mut(top.as_str()).unwrap().own += count;
+            }
+        }
+
+        let mut functions = functions.into_values().collect::<Vec<_>>();
+        functions.sort_by(|a, b| {
+            (b.own, b.total)
+                .cmp(&(a.own, a.total))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        functions
+    }
+
+    /// One line per stack, `outer;inner count`, as flamegraph.pl and inferno read
+    pub fn folded(&self) -> String {
+        let mut lines = self
+            .stacks
+            .iter()
+            .map(|(stack, count)| format!("{} {}\n", stack.join(";"), count))
+            .collect::<Vec<_>>();
+        lines.sort();
+        lines.concat()
+    }
+}
+
+impl Target {
+    // every thread's stack where it's stopped, the current thread's put back after
+    fn sample_threads(&mut self, profile: &mut Profile) {
+        let current = self.process.current_thread;
+        let tids = self
+            .process
+            .threads
+            .values()
+            .filter(|thread| thread.state() == ProcessState::Stopped)
+            .map(|thread| thread.tid())
+            .collect::<Vec<_>>();
+        for tid in tids {
+            self.process.current_thread = tid;
+            // a thread caught somewhere odd still counts, as far as we could unwind it
+            if let Ok(frames) = self.backtrace() {
+                profile.record(&frames);
+            }
+        }
+        self.process.current_thread = current;
+    }
+
+    /// Lets the process run, stopping it every interval to sample every thread's stack
+    ///
+    /// Sampling goes on while keep_going says so and the process doesn't stop on its own.
+    /// The stop that ended it comes back with the profile, None if keep_going did
+    pub fn profile(
+        &mut self,
+        interval: Duration,
+        mut keep_going: impl FnMut(&Profile) -> bool,
+    ) -> Result<(Profile, Option<StopReason>)> {
+        let mut profile = Profile::default();
+        while keep_going(&profile) {
+            self.process.resume()?;
+            std::thread::sleep(interval);
+            if let Some(reason) = self.wait_on_signal_nonblocking()? {
+                return Ok((profile, Some(reason)));
+            }
+
+            self.process.interrupt()?;
+            let reason = self.wait_on_signal()?;
+            // a breakpoint or signal can beat the interrupt
+            if reason.reason != ProcessState::Stopped
+                || reason.breakpoint_site.is_some()
+                || reason.signal() != Some(Signal::SIGSTOP)
+            {
+                return Ok((profile, Some(reason)));
+            }
+
+            self.sample_threads(&mut profile);
+        }
+
+        Ok((profile, None))
+    }
+}
@@ -0,0 +1,130 @@
+use std::ops::Range;
+
+use nix::{errno::Errno, libc};
+use procfs::process::MMPermissions;
+
+use crate::{Process, Result, SdbError, types::VirtAddr};
+
+const PAGE_SIZE: u64 = 0x1000;
+
+/// What it took to get a write into the process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteAssist {
+    /// Every page written to was already writable
+    None,
+
+    /// These pages were mprotected writable for the write and then put back
+    Mprotect(Vec<Range<VirtAddr>>),
+}
+
+impl WriteAssist {
+    #[inline]
+    pub fn was_used(&self) -> bool {
+        matches!(self, Self::Mprotect(_))
+    }
+}
+
+// a page aligned range that isn't writable, and the protections to put back
+#[derive(Debug)]
+struct ProtectedRegion {
+    range: Range<u64>,
+    protections: i32,
+}
+
+fn protections(perms: MMPermissions) -> i32 {
+    let mut protections = libc::PROT_NONE;
+    if perms.contains(MMPermissions::READ) {
+        protections |= libc::PROT_READ;
+    }
+    if perms.contains(MMPermissions::WRITE) {
+        protections |= libc::PROT_WRITE;
+    }
+    if perms.contains(MMPermissions::EXECUTE) {
+        protections |= libc::PROT_EXEC;
+    }
+    protections
+}
+
+impl Process {
+    fn mprotect(&mut self, range: &Range<u64>, protections: i32) -> Result<()> {
+        let ret = self.inject_syscall(
+            libc::SYS_mprotect,
+            &[range.start, range.end - range.start, protections as u64],
+        )?;
+        if ret < 0 {
+            return Err(SdbError::Write(Errno::from_raw(-ret as i32)));
+        }
+
+        Ok(())
+    }
+
+    fn protected_regions(&self, address: VirtAddr, amount: usize) -> Result<Vec<ProtectedRegion>> {
+        let start = address.addr();
+        let end = start + amount as u64;
+
+        let mut regions = Vec::new();
+        let mut covered = start;
+        for map in procfs::process::Process::new(self.pid.as_raw())?.maps()? {
+            let (low, high) = map.address;
+            if high <= covered || low >= end {
+                continue;
+            }
+            if low > covered {
+                break;
+            }
+
+            if !map.perms.contains(MMPermissions::WRITE) {
+                let page = covered & !(PAGE_SIZE - 1);
+                regions.push(ProtectedRegion {
+                    range: page..high.min(end.next_multiple_of(PAGE_SIZE)),
+                    protections: protections(map.perms),
+                });
+            }
+            covered = high;
+        }
+
+        if covered < end {
+            return Err(SdbError::Write(Errno::EFAULT));
+        }
+
+        Ok(regions)
+    }
+
+    /// Writes memory, making read-only pages writable around the write
+    ///
+    /// ptrace can force writes into most private mappings anyway, but not every
+    /// kernel or mapping allows it. The original protections are always put back
+    pub fn write_memory_assisted(&mut self, address: VirtAddr, data: &[u8]) -> Result<WriteAssist> {
+        let regions = self.protected_regions(address, data.len())?;
+        if regions.is_empty() {
+            self.write_memory(address, data)?;
+            return Ok(WriteAssist::None);
+        }
+
+        let mut unprotected = Vec::new();
+        let mut ret = Ok(());
+        for region in &regions {
+            ret = self.mprotect(&region.range, region.protections | libc::PROT_WRITE);
+            if ret.is_err() {
+                break;
+            }
+            unprotected.push(region);
+        }
+
+        if ret.is_ok() {
+            ret = self.write_memory(address, data);
+        }
+
+        for region in &unprotected {
+            self.mprotect(&region.range, region.protections)?;
+        }
+        ret?;
+
+        Ok(WriteAssist::Mprotect(
+            regions
+                .into_iter()
+                .map(|region| VirtAddr::new(region.range.start)..VirtAddr::new(region.range.end))
+                .collect(),
+        ))
+    }
+}
@@ -0,0 +1,549 @@
+use std::fmt::{Display, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, UNIX_EPOCH};
+
+use nix::{sys::signal::Signal, unistd::Pid};
+
+use crate::{
+    AllocFunction, AllocationEvent, DebugEvent, Frame, OutputStream, ProcessState, Result,
+    SdbError, SourceLocation, StopInfo, StopReason, SyscallRecord, TrapType,
+    stoppoint_collection::StoppointId, types::VirtAddr,
+};
+
+/// Everything a session can ask of the debugger
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    Resume,
+    Wait,
+    Interrupt,
+    StepInstruction,
+    StepIn,
+    StepOver,
+    StepOut,
+    StopInfo,
+    Backtrace,
+    ReadRegister(String),
+    WriteRegister(String, u64),
+    ReadMemory { address: VirtAddr, amount: usize },
+    WriteMemory { address: VirtAddr, data: Vec<u8> },
+    BreakFunction(String),
+    BreakLine { file: PathBuf, line: u64 },
+    BreakAddress(VirtAddr),
+    RemoveBreakpoint(StoppointId),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    Done,
+    Stopped(StopReason),
+    StopInfo(StopInfo),
+    Backtrace(Vec<Frame>),
+    Register(u64),
+    Memory(Vec<u8>),
+    Breakpoint(StoppointId),
+    Error(String),
+}
+
+/// What the debugger sends back, responses carry the id of their request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Response(u64, Response),
+    Event(DebugEvent),
+}
+
+// messages are a line of tab separated fields, starting with a tag
+struct Encoder {
+    line: String,
+}
+
+impl Encoder {
+    fn new(tag: &str) -> Self {
+        Self {
+            line: tag.to_owned(),
+        }
+    }
+
+    fn num(mut self, value: impl Display) -> Self {
+        write!(self.line, "\t{}", value).unwrap();
+        self
+    }
+
+    fn str(mut self, value: &str) -> Self {
+        self.line.push('\t');
+        for c in value.chars() {
+            match c {
+                '\\' => self.line.push_str("\\\\"),
+                '\t' => self.line.push_str("\\t"),
+                '\n' => self.line.push_str("\\n"),
+                c => self.line.push(c),
+            }
+        }
+        self
+    }
+
+    fn bytes(mut self, value: &[u8]) -> Self {
+        self.line.push('\t');
+        for byte in value {
+            write!(self.line, "{:02x}", byte).unwrap();
+        }
+        self
+    }
+
+    fn opt<T>(self, value: Option<T>, f: impl FnOnce(Self, T) -> Self) -> Self {
+        match value {
+            Some(value) => f(self.num(1), value),
+            None => self.num(0),
+        }
+    }
+
+    fn finish(self) -> String {
+        self.line
+    }
+}
+
+struct Decoder<'a> {
+    fields: std::str::Split<'a, char>,
+}
+
+fn malformed(what: impl Display) -> SdbError {
+    SdbError::Transport(format!("Malformed message: {}", what))
+}
+
+impl<'a> Decoder<'a> {
+    fn new(line: &'a str) -> Self {
+        Self {
+            fields: line.trim_end_matches(['\r', '\n']).split('\t'),
+        }
+    }
+
+    fn field(&mut self) -> Result<&'a str> {
+        self.fields.next().ok_or_else(|| malformed("missing field"))
+    }
+
+    fn num<T: FromStr>(&mut self) -> Result<T> {
+        let field = self.field()?;
+        field.parse().map_err(|_| malformed(field))
+    }
+
+    fn str(&mut self) -> Result<String> {
+        let mut value = String::new();
+        let mut chars = self.field()?.chars();
+        while let Some(c) = chars.next() {
+            value.push(match c {
+                '\\' => match chars.next() {
+                    Some('\\') => '\\',
+                    Some('t') => '\t',
+                    Some('n') => '\n',
+                    _ => return Err(malformed("bad escape")),
+                },
+                c => c,
+            });
+        }
+        Ok(value)
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>> {
+        let field = self.field()?;
+        (0..field.len())
+            .step_by(2)
+            .map(|idx| {
+                field
+                    .get(idx..idx + 2)
+                    .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                    .ok_or_else(|| malformed(field))
+            })
+            .collect()
+    }
+
+    fn opt<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<Option<T>> {
+        match self.num::<u8>()? {
+            0 => Ok(None),
+            _ => f(self).map(Some),
+        }
+    }
+
+    fn address(&mut self) -> Result<VirtAddr> {
+        Ok(VirtAddr::new(self.num()?))
+    }
+
+    fn finish(mut self) -> Result<()> {
+        match self.fields.next() {
+            Some(field) => Err(malformed(format!("unexpected {}", field))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Request {
+    pub fn encode(&self) -> String {
+        match self {
+            Self::Resume => Encoder::new("resume"),
+            Self::Wait => Encoder::new("wait"),
+            Self::Interrupt => Encoder::new("interrupt"),
+            Self::StepInstruction => Encoder::new("stepi"),
+            Self::StepIn => Encoder::new("step"),
+            Self::StepOver => Encoder::new("next"),
+            Self::StepOut => Encoder::new("finish"),
+            Self::StopInfo => Encoder::new("stop-info"),
+            Self::Backtrace => Encoder::new("backtrace"),
+            Self::ReadRegister(name) => Encoder::new("read-register").str(name),
+            Self::WriteRegister(name, value) => Encoder::new("write-register").str(name).num(value),
+            Self::ReadMemory { address, amount } => {
+                Encoder::new("read-memory").num(address.addr()).num(amount)
+            }
+            Self::WriteMemory { address, data } => {
+                Encoder::new("write-memory").num(address.addr()).bytes(data)
+            }
+            Self::BreakFunction(name) => Encoder::new("break-function").str(name),
+            Self::BreakLine { file, line } => Encoder::new("break-line")
+                .str(&file.to_string_lossy())
+                .num(line),
+            Self::BreakAddress(address) => Encoder::new("break-address").num(address.addr()),
+            Self::RemoveBreakpoint(id) => Encoder::new("remove-breakpoint").num(id),
+        }
+        .finish()
+    }
+
+    pub fn decode(line: &str) -> Result<Self> {
+        let mut decoder = Decoder::new(line);
+        let request = match decoder.field()? {
+            "resume" => Self::Resume,
+            "wait" => Self::Wait,
+            "interrupt" => Self::Interrupt,
+            "stepi" => Self::StepInstruction,
+            "step" => Self::StepIn,
+            "next" => Self::StepOver,
+            "finish" => Self::StepOut,
+            "stop-info" => Self::StopInfo,
+            "backtrace" => Self::Backtrace,
+            "read-register" => Self::ReadRegister(decoder.str()?),
+            "write-register" => Self::WriteRegister(decoder.str()?, decoder.num()?),
+            "read-memory" => Self::ReadMemory {
+                address: decoder.address()?,
+                amount: decoder.num()?,
+            },
+            "write-memory" => Self::WriteMemory {
+                address: decoder.address()?,
+                data: decoder.bytes()?,
+            },
+            "break-function" => Self::BreakFunction(decoder.str()?),
+            "break-line" => Self::BreakLine {
+                file: decoder.str()?.into(),
+                line: decoder.num()?,
+            },
+            "break-address" => Self::BreakAddress(decoder.address()?),
+            "remove-breakpoint" => Self::RemoveBreakpoint(decoder.num()?),
+            tag => return Err(malformed(format!("unknown request {}", tag))),
+        };
+        decoder.finish()?;
+
+        Ok(request)
+    }
+}
+
+fn encode_state(state: ProcessState) -> &'static str {
+    match state {
+        ProcessState::Stopped => "stopped",
+        ProcessState::Running => "running",
+        ProcessState::Exited => "exited",
+        ProcessState::Terminated => "terminated",
+    }
+}
+
+fn encode_stop_reason(encoder: Encoder, reason: &StopReason) -> Encoder {
+    encoder
+        .str(encode_state(reason.reason))
+        .num(reason.info)
+        .opt(reason.trap_reason, |encoder, trap| {
+            encoder.str(match trap {
+                TrapType::SoftwareBreak => "software-break",
+                TrapType::HardwareBreak => "hardware-break",
+                TrapType::Watchpoint => "watchpoint",
+                TrapType::SingleStep => "single-step",
+                TrapType::Unknown => "unknown",
+            })
+        })
+        .opt(reason.breakpoint_site, Encoder::num)
+        .opt(reason.fatal_function.as_deref(), Encoder::str)
+        .opt(reason.catchpoint, Encoder::num)
+        .opt(reason.lifecycle_event, |encoder, event| {
+            encoder.str(event.name())
+        })
+        .opt(reason.watchpoint, Encoder::num)
+        .opt(reason.watch_access, |encoder, access| {
+            encoder.str(access.name())
+        })
+}
+
+fn decode_stop_reason(decoder: &mut Decoder) -> Result<StopReason> {
+    Ok(StopReason {
+        reason: match decoder.field()? {
+            "stopped" => ProcessState::Stopped,
+            "running" => ProcessState::Running,
+            "exited" => ProcessState::Exited,
+            "terminated" => ProcessState::Terminated,
+            state => return Err(malformed(state)),
+        },
+        info: decoder.num()?,
+        trap_reason: decoder.opt(|decoder| match decoder.field()? {
+            "software-break" => Ok(TrapType::SoftwareBreak),
+            "hardware-break" => Ok(TrapType::HardwareBreak),
+            "watchpoint" => Ok(TrapType::Watchpoint),
+            "single-step" => Ok(TrapType::SingleStep),
+            "unknown" => Ok(TrapType::Unknown),
+            trap => Err(malformed(trap)),
+        })?,
+        breakpoint_site: decoder.opt(Decoder::num)?,
+        fatal_function: decoder.opt(Decoder::str)?,
+        catchpoint: decoder.opt(Decoder::num)?,
+        lifecycle_event: decoder.opt(|decoder| decoder.field()?.parse())?,
+        watchpoint: decoder.opt(Decoder::num)?,
+        watch_access: decoder.opt(|decoder| decoder.field()?.parse())?,
+    })
+}
+
+fn encode_location(encoder: Encoder, location: Option<&SourceLocation>) -> Encoder {
+    encoder.opt(location, |encoder, location| {
+        encoder
+            .str(&location.file.to_string_lossy())
+            .num(location.line)
+            .num(location.column)
+    })
+}
+
+fn decode_location(decoder: &mut Decoder) -> Result<Option<SourceLocation>> {
+    decoder.opt(|decoder| {
+        Ok(SourceLocation {
+            file: decoder.str()?.into(),
+            line: decoder.num()?,
+            column: decoder.num()?,
+        })
+    })
+}
+
+impl Response {
+    fn encode(&self, encoder: Encoder) -> Encoder {
+        match self {
+            Self::Done => encoder.str("done"),
+            Self::Stopped(reason) => encode_stop_reason(encoder.str("stopped"), reason),
+            Self::StopInfo(info) => {
+                let encoder = encode_stop_reason(encoder.str("stop-info"), &info.reason)
+                    .num(info.thread)
+                    .opt(info.pc, |encoder, pc| encoder.num(pc.addr()))
+                    .opt(info.function.as_deref(), Encoder::str);
+                encode_location(encoder, info.location.as_ref())
+            }
+            Self::Backtrace(frames) => frames.iter().fold(
+                encoder.str("backtrace").num(frames.len()),
+                |encoder, frame| {
+                    let encoder = encoder
+                        .num(frame.pc.addr())
+                        .opt(frame.function.as_deref(), Encoder::str);
+                    encode_location(encoder, frame.location.as_ref())
+                },
+            ),
+            Self::Register(value) => encoder.str("register").num(value),
+            Self::Memory(data) => encoder.str("memory").bytes(data),
+            Self::Breakpoint(id) => encoder.str("breakpoint").num(id),
+            Self::Error(message) => encoder.str("error").str(message),
+        }
+    }
+
+    fn decode(decoder: &mut Decoder) -> Result<Self> {
+        Ok(match decoder.field()? {
+            "done" => Self::Done,
+            "stopped" => Self::Stopped(decode_stop_reason(decoder)?),
+            "stop-info" => Self::StopInfo(StopInfo {
+                reason: decode_stop_reason(decoder)?,
+                thread: Pid::from_raw(decoder.num()?),
+                pc: decoder.opt(Decoder::address)?,
+                function: decoder.opt(Decoder::str)?,
+                location: decode_location(decoder)?,
+            }),
+            "backtrace" => Self::Backtrace(
+                (0..decoder.num::<usize>()?)
+                    .map(|_| {
+                        Ok(Frame {
+                            pc: decoder.address()?,
+                            function: decoder.opt(Decoder::str)?,
+                            location: decode_location(decoder)?,
+                        })
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+            "register" => Self::Register(decoder.num()?),
+            "memory" => Self::Memory(decoder.bytes()?),
+            "breakpoint" => Self::Breakpoint(decoder.num()?),
+            "error" => Self::Error(decoder.str()?),
+            tag => return Err(malformed(format!("unknown response {}", tag))),
+        })
+    }
+
+    /// Turns an error sent back by the debugger into an error here
+    pub fn into_result(self) -> Result<Self> {
+        match self {
+            Self::Error(message) => Err(SdbError::Transport(message)),
+            response => Ok(response),
+        }
+    }
+}
+
+fn encode_event(encoder: Encoder, event: &DebugEvent) -> Encoder {
+    match event {
+        DebugEvent::Stopped(reason) => encode_stop_reason(encoder.str("stopped"), reason),
+        DebugEvent::BreakpointHit { id, address } => {
+            encoder.str("breakpoint-hit").num(id).num(address.addr())
+        }
+        DebugEvent::WatchpointHit { id, old, new } => encoder
+            .str("watchpoint-hit")
+            .num(id)
+            .opt(old.as_deref(), Encoder::bytes)
+            .bytes(new),
+        DebugEvent::LibraryLoaded(path) => {
+            encoder.str("library-loaded").str(&path.to_string_lossy())
+        }
+        DebugEvent::LibraryUnloaded(path) => {
+            encoder.str("library-unloaded").str(&path.to_string_lossy())
+        }
+        DebugEvent::ThreadCreated(tid) => encoder.str("thread-created").num(tid),
+        DebugEvent::ThreadExited(tid) => encoder.str("thread-exited").num(tid),
+        DebugEvent::Exited(code) => encoder.str("exited").num(code),
+        DebugEvent::Terminated(signal) => encoder
+            .str("terminated")
+            .opt(*signal, |encoder, signal| encoder.num(signal as i32)),
+        DebugEvent::Logged { id, message } => encoder.str("logged").num(id).str(message),
+        DebugEvent::Syscall(record) => record
+            .args
+            .iter()
+            .fold(
+                encoder
+                    .str("syscall")
+                    .num(record.tid)
+                    .num(record.number)
+                    .num(record.args.len()),
+                |encoder, arg| encoder.str(arg),
+            )
+            .opt(record.result.as_deref(), |encoder, result| {
+                encoder.str(result)
+            }),
+        DebugEvent::Allocation(event) => {
+            let encoder = event.args.iter().fold(
+                encoder
+                    .str("allocation")
+                    .num(event.tid)
+                    .str(event.function.name())
+                    .num(event.args.len()),
+                |encoder, arg| encoder.num(arg),
+            );
+            event.callers.iter().fold(
+                encoder
+                    .opt(event.result, |encoder, result| encoder.num(result.addr()))
+                    .num(event.callers.len()),
+                |encoder, caller| encoder.num(caller.addr()),
+            )
+        }
+        DebugEvent::Output { stream, line, time } => {
+            encoder.str("output").str(stream.as_str()).str(line).num(
+                time.duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_micros(),
+            )
+        }
+    }
+}
+
+fn decode_event(decoder: &mut Decoder) -> Result<DebugEvent> {
+    Ok(match decoder.field()? {
+        "stopped" => DebugEvent::Stopped(decode_stop_reason(decoder)?),
+        "breakpoint-hit" => DebugEvent::BreakpointHit {
+            id: decoder.num()?,
+            address: decoder.address()?,
+        },
+        "watchpoint-hit" => DebugEvent::WatchpointHit {
+            id: decoder.num()?,
+            old: decoder.opt(Decoder::bytes)?,
+            new: decoder.bytes()?,
+        },
+        "library-loaded" => DebugEvent::LibraryLoaded(decoder.str()?.into()),
+        "library-unloaded" => DebugEvent::LibraryUnloaded(decoder.str()?.into()),
+        "thread-created" => DebugEvent::ThreadCreated(Pid::from_raw(decoder.num()?)),
+        "thread-exited" => DebugEvent::ThreadExited(Pid::from_raw(decoder.num()?)),
+        "exited" => DebugEvent::Exited(decoder.num()?),
+        "terminated" => DebugEvent::Terminated(
+            decoder.opt(|decoder| Signal::try_from(decoder.num::<i32>()?).map_err(malformed))?,
+        ),
+        "logged" => DebugEvent::Logged {
+            id: decoder.num()?,
+            message: decoder.str()?,
+        },
+        "syscall" => {
+            let tid = Pid::from_raw(decoder.num()?);
+            let number = decoder.num()?;
+            let count: usize = decoder.num()?;
+            let args = (0..count)
+                .map(|_| decoder.str())
+                .collect::<Result<Vec<_>>>()?;
+            DebugEvent::Syscall(SyscallRecord {
+                tid,
+                number,
+                args,
+                result: decoder.opt(|decoder| decoder.str())?,
+            })
+        }
+        "allocation" => {
+            let tid = Pid::from_raw(decoder.num()?);
+            let function = decoder.field()?;
+            let function = AllocFunction::by_name(function)
+                .ok_or_else(|| malformed(format!("unknown allocator function {}", function)))?;
+            let count: usize = decoder.num()?;
+            let args = (0..count)
+                .map(|_| decoder.num())
+                .collect::<Result<Vec<_>>>()?;
+            let result = decoder.opt(|decoder| decoder.address())?;
+            let count: usize = decoder.num()?;
+            let callers = (0..count)
+                .map(|_| decoder.address())
+                .collect::<Result<Vec<_>>>()?;
+            DebugEvent::Allocation(AllocationEvent {
+                tid,
+                function,
+                args,
+                result,
+                callers,
+            })
+        }
+        "output" => DebugEvent::Output {
+            stream: match decoder.field()? {
+                "stdout" => OutputStream::Stdout,
+                "stderr" => OutputStream::Stderr,
+                stream => return Err(malformed(format!("unknown stream {}", stream))),
+            },
+            line: decoder.str()?,
+            time: UNIX_EPOCH + Duration::from_micros(decoder.num()?),
+        },
+        tag => return Err(malformed(format!("unknown event {}", tag))),
+    })
+}
+
+impl Message {
+    pub fn encode(&self) -> String {
+        match self {
+            Self::Response(id, response) => response.encode(Encoder::new("response").num(id)),
+            Self::Event(event) => encode_event(Encoder::new("event"), event),
+        }
+        .finish()
+    }
+
+    pub fn decode(line: &str) -> Result<Self> {
+        let mut decoder = Decoder::new(line);
+        let message = match decoder.field()? {
+            "response" => Self::Response(decoder.num()?, Response::decode(&mut decoder)?),
+            "event" => Self::Event(decode_event(&mut decoder)?),
+            tag => return Err(malformed(format!("unknown message {}", tag))),
+        };
+        decoder.finish()?;
+
+        Ok(message)
+    }
+}
@@ -0,0 +1,59 @@
+use std::os::fd::{AsFd, OwnedFd};
+
+use nix::{
+    fcntl,
+    pty::{self, OpenptyResult},
+    sys::termios::{self, LocalFlags, SetArg},
+};
+
+use crate::{Result, SdbError};
+
+/// A pseudo-terminal pair, the slave goes to the process and the debugger keeps the master
+///
+/// What the process writes to the slave is read from the master and what's written to the
+/// master is the process's input
+#[derive(Debug)]
+pub struct Pty {
+    master: Option<OwnedFd>,
+    slave: Option<OwnedFd>,
+}
+
+impl Pty {
+    pub fn new() -> Result<Self> {
+        let OpenptyResult { master, slave } =
+            pty::openpty(None, None).map_err(SdbError::Terminal)?;
+
+        // only the launched process gets the slave, and only as its standard streams
+        for fd in [&master, &slave] {
+            fcntl::fcntl(fd, fcntl::FcntlArg::F_SETFD(fcntl::FdFlag::FD_CLOEXEC))
+                .map_err(SdbError::Terminal)?;
+        }
+
+        Ok(Self {
+            master: Some(master),
+            slave: Some(slave),
+        })
+    }
+
+    /// Whether the terminal echoes input back to the master, a program can still change it
+    pub fn set_echo(&self, echo: bool) -> Result<()> {
+        let Some(fd) = self.slave.as_ref().or(self.master.as_ref()) else {
+            return Err(SdbError::Other("Invalid Pty".to_owned()));
+        };
+
+        let mut attrs = termios::tcgetattr(fd.as_fd()).map_err(SdbError::Terminal)?;
+        attrs.local_flags.set(LocalFlags::ECHO, echo);
+        termios::tcsetattr(fd.as_fd(), SetArg::TCSANOW, &attrs).map_err(SdbError::Terminal)
+    }
+
+    #[inline]
+    pub fn take_master(&mut self) -> Option<OwnedFd> {
+        self.master.take()
+    }
+
+    /// Hands over the slave, to give to [`crate::LaunchConfig::terminal`]
+    #[inline]
+    pub fn take_slave(&mut self) -> Option<OwnedFd> {
+        self.slave.take()
+    }
+}
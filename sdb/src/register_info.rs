@@ -45,7 +45,9 @@ macro_rules! fpr_size {
 // TODO: there's probably a way to turn this into a more X-macro style?
 // where the enum and the array are automatically kept in sync
 
-#[derive(Debug, PartialEq, Eq, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+#[derive(
+    Debug, Copy, Clone, PartialEq, Eq, Hash, num_derive::FromPrimitive, num_derive::ToPrimitive,
+)]
 pub enum RegisterId {
     // 64-bit GPRs
     rax,
@@ -72,6 +74,8 @@ pub enum RegisterId {
     ss,
     ds,
     es,
+    fs_base,
+    gs_base,
 
     // special ptrace value
     orig_rax,
@@ -195,7 +199,7 @@ pub enum RegisterId {
     dr7,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum RegisterType {
     Gpr,
     SubGpr,
@@ -222,6 +226,28 @@ pub struct RegisterInfo {
     pub(crate) format: RegisterFormat,
 }
 
+impl RegisterInfo {
+    #[inline]
+    pub fn id(&self) -> RegisterId {
+        self.id
+    }
+
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    #[inline]
+    pub fn register_type(&self) -> RegisterType {
+        self.r#type
+    }
+
+    #[inline]
+    pub fn format(&self) -> &RegisterFormat {
+        &self.format
+    }
+}
+
 macro_rules! define_gpr_64 {
     ($name:ident, $dwarf_id:literal) => {
         RegisterInfo {
@@ -341,7 +367,7 @@ macro_rules! define_fpr_xmm {
             name: stringify!($name),
             dwarf_id: 17 + $number,
             size: 16,
-            offset: (fpr_offset!(st_space) + $number * 16),
+            offset: (fpr_offset!(xmm_space) + $number * 16),
             r#type: RegisterType::Fpr,
             format: RegisterFormat::Vector,
         }
@@ -388,6 +414,8 @@ const REGISTER_INFOS: &[RegisterInfo] = &[
     define_gpr_64!(ss, 52),
     define_gpr_64!(ds, 53),
     define_gpr_64!(es, 50),
+    define_gpr_64!(fs_base, 58),
+    define_gpr_64!(gs_base, 59),
     // special ptrace value
     define_gpr_64!(orig_rax, -1),
     // 32-bit subregisters
@@ -502,6 +530,11 @@ const REGISTER_INFOS: &[RegisterInfo] = &[
 ];
 
 // TODO: if we use a BTreeMap, this can go away?
+#[inline]
+pub fn register_infos() -> &'static [RegisterInfo] {
+    REGISTER_INFOS
+}
+
 pub fn register_info_by_id(id: RegisterId) -> &'static RegisterInfo {
     REGISTER_INFOS.iter().find(|&info| info.id == id).unwrap()
 }
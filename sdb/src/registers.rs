@@ -10,7 +10,7 @@ use crate::{
     types::{self, Byte64, Byte128},
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum RegisterValue {
     Int8(i8),
     Int16(i16),
@@ -30,7 +30,10 @@ pub enum RegisterValue {
 impl RegisterValue {
     #[inline]
     fn is_float(&self) -> bool {
-        matches!(self, Self::Float(..) | Self::Double(..))
+        matches!(
+            self,
+            Self::Float(..) | Self::Double(..) | Self::LongDouble(..)
+        )
     }
 
     #[inline]
@@ -65,19 +68,17 @@ impl RegisterValue {
             if info.format == RegisterFormat::DoubleFloat {
                 return match self {
                     RegisterValue::Float(v) => types::byte128_8(v.to_f64().unwrap().to_ne_bytes()),
-                    RegisterValue::Double(v) => types::byte128_8(v.to_ne_bytes()),
+                    RegisterValue::Double(v) | RegisterValue::LongDouble(v) => {
+                        types::byte128_8(v.to_ne_bytes())
+                    }
                     _ => unreachable!(),
                 };
             }
 
             if info.format == RegisterFormat::LongDouble {
-                // widen to 64-bit float until https://github.com/rust-lang/rfcs/pull/3453 is implemented
-                return match self {
-                    RegisterValue::Float(v) => types::byte128_8(v.to_f64().unwrap().to_ne_bytes()),
-                    RegisterValue::Double(v) => types::byte128_8(v.to_f64().unwrap().to_ne_bytes()),
-                    RegisterValue::LongDouble(v) => types::byte128_8(v.to_ne_bytes()),
-                    _ => unreachable!(),
-                };
+                let mut wide = Byte128::default();
+                wide[..10].copy_from_slice(&types::f64_to_extended(self.to_f64().unwrap()));
+                return wide;
             }
         } else if self.is_signed() && info.format == RegisterFormat::UInt {
             return match info.size {
@@ -121,6 +122,148 @@ impl RegisterValue {
     }
 }
 
+impl std::fmt::Display for RegisterValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // integers are shown as zero padded hex of the register's width
+        let width = self.get_size() * 2 + 2;
+        match self {
+            Self::Int8(v) => write!(f, "{:#0width$x}", v),
+            Self::Int16(v) => write!(f, "{:#0width$x}", v),
+            Self::Int32(v) => write!(f, "{:#0width$x}", v),
+            Self::Int64(v) => write!(f, "{:#0width$x}", v),
+            Self::UInt8(v) => write!(f, "{:#0width$x}", v),
+            Self::UInt16(v) => write!(f, "{:#0width$x}", v),
+            Self::UInt32(v) => write!(f, "{:#0width$x}", v),
+            Self::UInt64(v) => write!(f, "{:#0width$x}", v),
+            Self::Float(v) => write!(f, "{}", v),
+            Self::Double(v) | Self::LongDouble(v) => write!(f, "{}", v),
+            Self::Byte64(v) => write_bytes(f, v),
+            Self::Byte128(v) => write_bytes(f, v),
+        }
+    }
+}
+
+fn write_bytes(f: &mut std::fmt::Formatter<'_>, bytes: &[u8]) -> std::fmt::Result {
+    let bytes = bytes
+        .iter()
+        .map(|byte| format!("{:#04x}", byte))
+        .collect::<Vec<_>>();
+    write!(f, "[{}]", bytes.join(", "))
+}
+
+fn parse_error(info: &RegisterInfo, text: &str, err: impl std::fmt::Display) -> SdbError {
+    SdbError::Register(format!("Bad value {} for {}: {}", text, info.name(), err))
+}
+
+fn parse_uint(info: &RegisterInfo, text: &str) -> Result<u64> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse(),
+    }
+    .map_err(|err| parse_error(info, text, err))
+}
+
+impl RegisterValue {
+    /// Reads a value for a register the way it's displayed
+    ///
+    /// Integers can also be decimal, and are sized to the register
+    pub fn parse(info: &RegisterInfo, text: &str) -> Result<Self> {
+        let text = text.trim();
+        Ok(match info.format {
+            RegisterFormat::UInt => {
+                let value = parse_uint(info, text)?;
+                let too_big = |err| parse_error(info, text, err);
+                match info.size {
+                    1 => Self::UInt8(value.try_into().map_err(too_big)?),
+                    2 => Self::UInt16(value.try_into().map_err(too_big)?),
+                    4 => Self::UInt32(value.try_into().map_err(too_big)?),
+                    _ => Self::UInt64(value),
+                }
+            }
+            RegisterFormat::DoubleFloat | RegisterFormat::LongDouble => {
+                let value = text
+                    .parse::<f64>()
+                    .map_err(|err| parse_error(info, text, err))?;
+                if info.format == RegisterFormat::LongDouble {
+                    Self::LongDouble(value)
+                } else {
+                    Self::Double(value)
+                }
+            }
+            RegisterFormat::Vector => {
+                let bytes = text
+                    .strip_prefix('[')
+                    .and_then(|text| text.strip_suffix(']'))
+                    .ok_or_else(|| parse_error(info, text, "expected [byte, ...]"))?
+                    .split(',')
+                    .map(|byte| {
+                        let byte = parse_uint(info, byte.trim())?;
+                        u8::try_from(byte).map_err(|err| parse_error(info, text, err))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                if bytes.len() != info.size {
+                    return Err(parse_error(
+                        info,
+                        text,
+                        format!("expected {} bytes", info.size),
+                    ));
+                }
+
+                if info.size == 8 {
+                    Self::Byte64(bytes.try_into().unwrap())
+                } else {
+                    Self::Byte128(bytes.try_into().unwrap())
+                }
+            }
+        })
+    }
+}
+
+impl ToPrimitive for RegisterValue {
+    fn to_i64(&self) -> Option<i64> {
+        match self {
+            Self::Int8(v) => v.to_i64(),
+            Self::Int16(v) => v.to_i64(),
+            Self::Int32(v) => v.to_i64(),
+            Self::Int64(v) => v.to_i64(),
+            Self::UInt8(v) => v.to_i64(),
+            Self::UInt16(v) => v.to_i64(),
+            Self::UInt32(v) => v.to_i64(),
+            Self::UInt64(v) => v.to_i64(),
+            Self::Float(v) => v.to_i64(),
+            Self::Double(v) | Self::LongDouble(v) => v.to_i64(),
+            Self::Byte64(v) => Some(i64::from_ne_bytes(*v)),
+            Self::Byte128(..) => None,
+        }
+    }
+
+    fn to_u64(&self) -> Option<u64> {
+        match self {
+            Self::Int8(v) => v.to_u64(),
+            Self::Int16(v) => v.to_u64(),
+            Self::Int32(v) => v.to_u64(),
+            Self::Int64(v) => v.to_u64(),
+            Self::UInt8(v) => v.to_u64(),
+            Self::UInt16(v) => v.to_u64(),
+            Self::UInt32(v) => v.to_u64(),
+            Self::UInt64(v) => v.to_u64(),
+            Self::Float(v) => v.to_u64(),
+            Self::Double(v) | Self::LongDouble(v) => v.to_u64(),
+            Self::Byte64(v) => Some(u64::from_ne_bytes(*v)),
+            Self::Byte128(..) => None,
+        }
+    }
+
+    fn to_f64(&self) -> Option<f64> {
+        match self {
+            Self::Float(v) => v.to_f64(),
+            Self::Double(v) | Self::LongDouble(v) => Some(*v),
+            Self::Byte64(..) | Self::Byte128(..) => None,
+            _ => self.to_i64().and_then(|v| v.to_f64()),
+        }
+    }
+}
+
 impl From<i8> for RegisterValue {
     fn from(value: i8) -> Self {
         Self::Int8(value)
@@ -186,6 +329,10 @@ pub struct Registers {
     data: libc::user,
 }
 
+// the raw pointers in libc::user (u_ar0, u_fpstate) are just values
+// copied out of the tracee's user area, they are never dereferenced
+unsafe impl Send for Registers {}
+
 impl Registers {
     pub(crate) fn new() -> Self {
         let data = MaybeUninit::<libc::user>::zeroed();
@@ -194,31 +341,35 @@ impl Registers {
         }
     }
 
+    pub(crate) fn get_data(&self) -> &libc::user {
+        &self.data
+    }
+
     pub(crate) fn get_data_mut(&mut self) -> &mut libc::user {
         &mut self.data
     }
 
-    /*pub unsafe fn read(&self, info: &RegisterInfo) -> Result<RegisterValue> {
-        let bytes = as_bytes(&self.data);
+    pub(crate) fn read(&self, info: &RegisterInfo) -> Result<RegisterValue> {
+        let bytes = &as_bytes(&self.data)[info.offset..info.offset + info.size];
         let val = match info.format {
             RegisterFormat::UInt => match info.size {
-                1 => RegisterValue::UInt8(unsafe { from_bytes::<u8>(&bytes[info.offset..]) }),
-                2 => RegisterValue::UInt16(unsafe { from_bytes::<u16>(&bytes[info.offset..]) }),
-                4 => RegisterValue::UInt32(unsafe { from_bytes::<u32>(&bytes[info.offset..]) }),
-                8 => RegisterValue::UInt64(unsafe { from_bytes::<u64>(&bytes[info.offset..]) }),
+                1 => RegisterValue::UInt8(u8::from_ne_bytes(bytes.try_into().unwrap())),
+                2 => RegisterValue::UInt16(u16::from_ne_bytes(bytes.try_into().unwrap())),
+                4 => RegisterValue::UInt32(u32::from_ne_bytes(bytes.try_into().unwrap())),
+                8 => RegisterValue::UInt64(u64::from_ne_bytes(bytes.try_into().unwrap())),
                 _ => return Err(SdbError::Register("Unexpected register size".to_owned())),
             },
             RegisterFormat::DoubleFloat => {
-                RegisterValue::Double(unsafe { from_bytes::<f64>(&bytes[info.offset..]) })
+                RegisterValue::Double(f64::from_ne_bytes(bytes[..8].try_into().unwrap()))
             }
             RegisterFormat::LongDouble => {
-                RegisterValue::LongDouble(unsafe { from_bytes::<u128>(&bytes[info.offset..]) })
+                RegisterValue::LongDouble(types::extended_to_f64(bytes[..10].try_into().unwrap()))
             }
             RegisterFormat::Vector => {
                 if info.size == 8 {
-                    RegisterValue::Byte64(unsafe { from_bytes::<Byte64>(&bytes[info.offset..]) })
+                    RegisterValue::Byte64(bytes.try_into().unwrap())
                 } else {
-                    RegisterValue::Byte128(unsafe { from_bytes::<Byte128>(&bytes[info.offset..]) })
+                    RegisterValue::Byte128(bytes.try_into().unwrap())
                 }
             }
         };
@@ -226,9 +377,9 @@ impl Registers {
         Ok(val)
     }
 
-    pub unsafe fn read_by_id_as(&self, id: RegisterId) -> Result<RegisterValue> {
-        unsafe { self.read(register_info_by_id(id)) }
-    }*/
+    pub(crate) fn read_by_id(&self, id: RegisterId) -> Result<RegisterValue> {
+        self.read(register_info_by_id(id))
+    }
 
     fn write(&mut self, info: &RegisterInfo, val: RegisterValue, process: &Process) -> Result<()> {
         if val.get_size() > info.size {
@@ -259,6 +410,54 @@ impl Registers {
         }
     }
 
+    // every value goes into the cached copy first, so the gprs and fprs are each written once
+    pub(crate) fn write_many(
+        &mut self,
+        values: impl IntoIterator<Item = (RegisterId, RegisterValue)>,
+        process: &Process,
+    ) -> Result<()> {
+        let mut gprs = false;
+        let mut fprs = false;
+        let mut debug = Vec::new();
+        for (id, val) in values {
+            let info = register_info_by_id(id);
+            if val.get_size() > info.size {
+                return Err(SdbError::Register(format!(
+                    "{} is too big for {}",
+                    val,
+                    info.name()
+                )));
+            }
+
+            let bytes = as_bytes_mut(&mut self.data);
+            let wide = val.widen(info);
+            let changed = bytes[info.offset..info.offset + info.size] != wide[..info.size];
+            bytes[info.offset..info.offset + info.size].copy_from_slice(&wide[..info.size]);
+
+            match info.r#type {
+                RegisterType::Gpr | RegisterType::SubGpr => gprs = true,
+                RegisterType::Fpr => fprs = true,
+                // some debug registers can't be written at all, leaving them be is fine
+                RegisterType::Dr if changed => debug.push(info),
+                RegisterType::Dr => (),
+            }
+        }
+
+        if gprs {
+            process.write_gprs(self.data.regs)?;
+        }
+        if fprs {
+            process.write_fprs(self.data.i387)?;
+        }
+        for info in debug {
+            let bytes = as_bytes(&self.data);
+            let data = u64::from_ne_bytes(bytes[info.offset..info.offset + 8].try_into().unwrap());
+            process.write_user_area(info.offset, data)?;
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn write_by_id(
         &mut self,
         id: RegisterId,
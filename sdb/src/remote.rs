@@ -0,0 +1,177 @@
+use std::fmt::Write as _;
+
+use nix::libc;
+
+use crate::{Result, SdbError};
+
+mod process;
+mod server;
+
+pub use process::RemoteProcess;
+pub use server::Server;
+
+// a register in the order gdb numbers them, with sdb's name for it if sdb has one
+struct GdbRegister {
+    sdb_name: Option<&'static str>,
+    size: usize,
+
+    // the GPRs can be written, the rest are read only
+    writable: bool,
+}
+
+const fn gpr(name: &'static str, size: usize) -> GdbRegister {
+    GdbRegister {
+        sdb_name: Some(name),
+        size,
+        writable: true,
+    }
+}
+
+const fn fpr(name: Option<&'static str>, size: usize) -> GdbRegister {
+    GdbRegister {
+        sdb_name: name,
+        size,
+        writable: false,
+    }
+}
+
+const REGISTERS: &[GdbRegister] = &[
+    gpr("rax", 8),
+    gpr("rbx", 8),
+    gpr("rcx", 8),
+    gpr("rdx", 8),
+    gpr("rsi", 8),
+    gpr("rdi", 8),
+    gpr("rbp", 8),
+    gpr("rsp", 8),
+    gpr("r8", 8),
+    gpr("r9", 8),
+    gpr("r10", 8),
+    gpr("r11", 8),
+    gpr("r12", 8),
+    gpr("r13", 8),
+    gpr("r14", 8),
+    gpr("r15", 8),
+    gpr("rip", 8),
+    gpr("eflags", 4),
+    gpr("cs", 4),
+    gpr("ss", 4),
+    gpr("ds", 4),
+    gpr("es", 4),
+    gpr("fs", 4),
+    gpr("gs", 4),
+    // st0-7 don't fit in a u64
+    fpr(None, 10),
+    fpr(None, 10),
+    fpr(None, 10),
+    fpr(None, 10),
+    fpr(None, 10),
+    fpr(None, 10),
+    fpr(None, 10),
+    fpr(None, 10),
+    fpr(Some("fcw"), 4),
+    fpr(Some("fsw"), 4),
+    fpr(Some("ftw"), 4),
+    fpr(None, 4),
+    fpr(None, 4),
+    fpr(None, 4),
+    fpr(None, 4),
+    fpr(Some("fop"), 4),
+    // xmm0-15 don't either
+    fpr(None, 16),
+    fpr(None, 16),
+    fpr(None, 16),
+    fpr(None, 16),
+    fpr(None, 16),
+    fpr(None, 16),
+    fpr(None, 16),
+    fpr(None, 16),
+    fpr(None, 16),
+    fpr(None, 16),
+    fpr(None, 16),
+    fpr(None, 16),
+    fpr(None, 16),
+    fpr(None, 16),
+    fpr(None, 16),
+    fpr(None, 16),
+    fpr(Some("mxcsr"), 4),
+];
+
+// gdb numbers signals its own way, most match Linux but not all
+fn gdb_signal(signal: i32) -> i32 {
+    match signal {
+        libc::SIGBUS => 10,
+        libc::SIGUSR1 => 30,
+        libc::SIGUSR2 => 31,
+        libc::SIGCHLD => 20,
+        libc::SIGCONT => 19,
+        libc::SIGSTOP => 17,
+        libc::SIGTSTP => 18,
+        signal => signal,
+    }
+}
+
+fn linux_signal(signal: i32) -> i32 {
+    match signal {
+        10 => libc::SIGBUS,
+        30 => libc::SIGUSR1,
+        31 => libc::SIGUSR2,
+        20 => libc::SIGCHLD,
+        19 => libc::SIGCONT,
+        17 => libc::SIGSTOP,
+        18 => libc::SIGTSTP,
+        signal => signal,
+    }
+}
+
+// gdb's number for a register sdb knows by name
+fn register_number(name: &str) -> Option<(usize, &'static GdbRegister)> {
+    REGISTERS
+        .iter()
+        .enumerate()
+        .find(|(_, register)| register.sdb_name == Some(name))
+}
+
+fn checksum(data: &str) -> u8 {
+    data.bytes().fold(0, u8::wrapping_add)
+}
+
+// for binary replies, like qXfer
+fn escape(data: &str) -> String {
+    let mut escaped = String::with_capacity(data.len());
+    for c in data.chars() {
+        if matches!(c, '#' | '$' | '}' | '*') {
+            escaped.push('}');
+            escaped.push((c as u8 ^ 0x20) as char);
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(malformed(hex));
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(&hex[idx..idx + 2], 16).map_err(|_| malformed(hex)))
+        .collect()
+}
+
+fn parse_hex(hex: &str) -> Result<u64> {
+    u64::from_str_radix(hex, 16).map_err(|_| malformed(hex))
+}
+
+fn malformed(packet: &str) -> SdbError {
+    SdbError::Transport(format!("Malformed packet: {}", packet))
+}
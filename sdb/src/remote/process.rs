@@ -0,0 +1,441 @@
+use std::collections::BTreeMap;
+use std::io::{BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{
+    Mutex,
+    atomic::{AtomicBool, Ordering},
+    mpsc,
+};
+
+use nix::{libc, unistd::Pid};
+use tracing::{info, trace};
+
+use super::{checksum, from_hex, linux_signal, malformed, parse_hex, register_number, to_hex};
+use crate::{
+    Auxv, DebugEvent, DebugTransport, ElfCollection, Inferior, ProcessState, RegisterId,
+    RegisterValue, Request, Response, Result, SdbError, StopInfo, StopReason, StoppointId,
+    TrapType, event::EventSubscribers, register_info_by_id, types::VirtAddr,
+};
+
+// memory is read and written in chunks that fit in a packet
+const MEMORY_CHUNK_SIZE: usize = 0x800;
+
+fn transport_error(err: std::io::Error) -> SdbError {
+    SdbError::Transport(err.to_string())
+}
+
+#[derive(Debug, Default)]
+struct State {
+    breakpoints: BTreeMap<StoppointId, VirtAddr>,
+    next_stoppoint_id: StoppointId,
+    last_stop: Option<StopReason>,
+    thread: Option<Pid>,
+    subscribers: EventSubscribers,
+}
+
+/// Debugs a process behind a gdb remote protocol server, like gdbserver or sdb's own
+///
+/// Only what the protocol can express is supported, so there are no modules,
+/// and nothing that needs debug info like function breakpoints or source stepping
+#[derive(Debug)]
+pub struct RemoteProcess {
+    writer: Mutex<TcpStream>,
+
+    // held for a whole exchange so that replies go to whoever asked
+    reader: Mutex<BufReader<TcpStream>>,
+    no_ack: AtomicBool,
+
+    state: Mutex<State>,
+    modules: ElfCollection,
+}
+
+impl RemoteProcess {
+    /// Connects to a server and finds out why the process is stopped
+    pub fn connect(address: impl ToSocketAddrs) -> Result<Self> {
+        let stream = TcpStream::connect(address).map_err(transport_error)?;
+        stream.set_nodelay(true).map_err(transport_error)?;
+        let reader = stream.try_clone().map_err(transport_error)?;
+
+        let process = Self {
+            writer: Mutex::new(stream),
+            reader: Mutex::new(BufReader::new(reader)),
+            no_ack: AtomicBool::new(false),
+            state: Mutex::new(State::default()),
+            modules: ElfCollection::default(),
+        };
+
+        let supported = process.exchange("qSupported:swbreak+;hwbreak+")?;
+        if supported
+            .split(';')
+            .any(|feature| feature == "QStartNoAckMode+")
+            && process.exchange("QStartNoAckMode")? == "OK"
+        {
+            process.no_ack.store(true, Ordering::Relaxed);
+        }
+
+        let reply = process.exchange("?")?;
+        process.record_stop(&reply, false)?;
+
+        Ok(process)
+    }
+
+    fn send(&self, packet: &str) -> Result<()> {
+        trace!("Sending {}", packet);
+        let mut writer = self.writer.lock().unwrap();
+        write!(writer, "${}#{:02x}", packet, checksum(packet)).map_err(transport_error)?;
+        writer.flush().map_err(transport_error)
+    }
+
+    fn receive(&self, reader: &mut BufReader<TcpStream>) -> Result<String> {
+        let mut next = || {
+            let mut byte = [0];
+            reader.read_exact(&mut byte).map_err(transport_error)?;
+            Ok::<_, SdbError>(byte[0])
+        };
+
+        // skips acks
+        while next()? != b'$' {}
+        let mut packet = Vec::new();
+        loop {
+            match next()? {
+                b'#' => break,
+                // run length encoding repeats the last character
+                b'*' => {
+                    let count = next()?.saturating_sub(29) as usize;
+                    let last = *packet.last().ok_or_else(|| malformed("*"))?;
+                    packet.extend(std::iter::repeat_n(last, count));
+                }
+                byte => packet.push(byte),
+            }
+        }
+        next()?;
+        next()?;
+
+        if !self.no_ack.load(Ordering::Relaxed) {
+            self.writer
+                .lock()
+                .unwrap()
+                .write_all(b"+")
+                .map_err(transport_error)?;
+        }
+
+        let packet = String::from_utf8_lossy(&packet).into_owned();
+        trace!("Received {}", packet);
+        Ok(packet)
+    }
+
+    fn exchange(&self, packet: &str) -> Result<String> {
+        let mut reader = self.reader.lock().unwrap();
+        self.send(packet)?;
+        let reply = self.receive(&mut reader)?;
+
+        if reply.len() == 3 && reply.starts_with('E') {
+            return Err(SdbError::Transport(format!(
+                "{} failed with {}",
+                packet, reply
+            )));
+        }
+        Ok(reply)
+    }
+
+    // waits out console output until the stop reply
+    fn wait_for_stop_reply(&self, packet: Option<&str>) -> Result<String> {
+        let mut reader = self.reader.lock().unwrap();
+        if let Some(packet) = packet {
+            self.send(packet)?;
+        }
+
+        loop {
+            let reply = self.receive(&mut reader)?;
+            match reply.strip_prefix('O') {
+                Some(output) if reply != "OK" => {
+                    let output = from_hex(output)?;
+                    info!("{}", String::from_utf8_lossy(&output).trim_end());
+                }
+                _ => return Ok(reply),
+            }
+        }
+    }
+
+    fn record_stop(&self, reply: &str, stepped: bool) -> Result<StopReason> {
+        let kind = reply.chars().next().ok_or_else(|| malformed(reply))?;
+        let value = reply.get(1..3).ok_or_else(|| malformed(reply))?;
+        let value = parse_hex(value)? as i32;
+
+        let mut reason = StopReason {
+            reason: ProcessState::Stopped,
+            info: linux_signal(value),
+            trap_reason: None,
+            breakpoint_site: None,
+            fatal_function: None,
+            catchpoint: None,
+            lifecycle_event: None,
+            watchpoint: None,
+            watch_access: None,
+        };
+        let mut thread = None;
+        match kind {
+            'W' => {
+                reason.reason = ProcessState::Exited;
+                reason.info = value;
+            }
+            'X' => reason.reason = ProcessState::Terminated,
+            'S' => (),
+            'T' => {
+                for pair in reply[3..].split(';').filter(|pair| !pair.is_empty()) {
+                    let (key, value) = pair.split_once(':').unwrap_or((pair, ""));
+                    match key {
+                        // may be pid.tid when the server speaks multiprocess
+                        "thread" => {
+                            let tid = value.rsplit('.').next().unwrap_or(value);
+                            thread = Some(Pid::from_raw(parse_hex(tid)? as i32));
+                        }
+                        "swbreak" => reason.trap_reason = Some(TrapType::SoftwareBreak),
+                        "hwbreak" => reason.trap_reason = Some(TrapType::HardwareBreak),
+                        _ => (),
+                    }
+                }
+            }
+            _ => return Err(malformed(reply)),
+        }
+
+        if reason.reason == ProcessState::Stopped
+            && reason.info == libc::SIGTRAP
+            && reason.trap_reason.is_none()
+            && stepped
+        {
+            reason.trap_reason = Some(TrapType::SingleStep);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if thread.is_some() {
+            state.thread = thread;
+        }
+        state.last_stop = Some(reason.clone());
+        Ok(reason)
+    }
+
+    // matches a stop up with the breakpoint it hit, and lets subscribers know
+    fn finish_stop(&self, mut reason: StopReason) -> Result<StopReason> {
+        let pc = match reason.reason {
+            ProcessState::Stopped => Some(self.get_pc()?),
+            _ => None,
+        };
+
+        let mut state = self.state.lock().unwrap();
+        if reason.info == libc::SIGTRAP
+            && let Some(pc) = pc
+        {
+            reason.breakpoint_site = state
+                .breakpoints
+                .iter()
+                .find(|(_, address)| **address == pc)
+                .map(|(id, _)| *id);
+            state.last_stop = Some(reason.clone());
+        }
+
+        let event = match (reason.reason, reason.breakpoint_site, pc) {
+            (ProcessState::Exited, _, _) => DebugEvent::Exited(reason.info),
+            (ProcessState::Terminated, _, _) => DebugEvent::Terminated(reason.signal()),
+            (_, Some(id), Some(address)) => DebugEvent::BreakpointHit { id, address },
+            _ => DebugEvent::Stopped(reason.clone()),
+        };
+        state.subscribers.emit(event);
+
+        Ok(reason)
+    }
+
+    fn read_register(&self, name: &str) -> Result<u64> {
+        let (number, _) = register_number(name)
+            .ok_or_else(|| SdbError::Register(format!("No such register {}", name)))?;
+        let reply = self.exchange(&format!("p{:x}", number))?;
+        if reply.is_empty() || reply.starts_with('x') {
+            return Err(SdbError::Register(format!("{} is unavailable", name)));
+        }
+
+        let mut bytes = [0; 8];
+        let value = from_hex(&reply)?;
+        let size = value.len().min(8);
+        bytes[..size].copy_from_slice(&value[..size]);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn write_register(&self, name: &str, value: u64) -> Result<()> {
+        let (number, register) = register_number(name)
+            .ok_or_else(|| SdbError::Register(format!("No such register {}", name)))?;
+        let value = &value.to_le_bytes()[..register.size.min(8)];
+        self.exchange(&format!("P{:x}={}", number, to_hex(value)))?;
+
+        Ok(())
+    }
+
+    fn read_remote_memory(&self, address: VirtAddr, amount: usize) -> Result<Vec<u8>> {
+        let mut memory = Vec::with_capacity(amount);
+        while memory.len() < amount {
+            let chunk = (amount - memory.len()).min(MEMORY_CHUNK_SIZE);
+            let start = address + memory.len() as i64;
+            let data = match self.exchange(&format!("m{:x},{:x}", start.addr(), chunk)) {
+                Ok(reply) => from_hex(&reply)?,
+                // like a short read
+                Err(_) if !memory.is_empty() => break,
+                Err(err) => return Err(err),
+            };
+            if data.is_empty() {
+                break;
+            }
+            memory.extend(data);
+        }
+
+        Ok(memory)
+    }
+
+    fn write_remote_memory(&self, address: VirtAddr, data: &[u8]) -> Result<()> {
+        for (idx, chunk) in data.chunks(MEMORY_CHUNK_SIZE).enumerate() {
+            let start = address + (idx * MEMORY_CHUNK_SIZE) as i64;
+            self.exchange(&format!(
+                "M{:x},{:x}:{}",
+                start.addr(),
+                chunk.len(),
+                to_hex(chunk)
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    fn stop_info(&self) -> Result<StopInfo> {
+        let (reason, thread) = {
+            let state = self.state.lock().unwrap();
+            (state.last_stop.clone(), state.thread)
+        };
+        let reason = reason.ok_or_else(|| SdbError::Transport("Not stopped yet".to_owned()))?;
+
+        let pc = match reason.reason {
+            ProcessState::Stopped => Some(self.get_pc()?),
+            _ => None,
+        };
+        Ok(StopInfo {
+            reason,
+            thread: thread.unwrap_or(Pid::from_raw(0)),
+            pc,
+            function: None,
+            location: None,
+        })
+    }
+
+    fn create_breakpoint(&self, address: VirtAddr) -> Result<StoppointId> {
+        let reply = self.exchange(&format!("Z0,{:x},1", address.addr()))?;
+        if reply != "OK" {
+            return Err(SdbError::Stoppoint(format!(
+                "The server can't set breakpoints at {}",
+                address
+            )));
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_stoppoint_id;
+        state.next_stoppoint_id += 1;
+        state.breakpoints.insert(id, address);
+        Ok(id)
+    }
+
+    fn remove_breakpoint(&self, id: StoppointId) -> Result<()> {
+        let address = self
+            .state
+            .lock()
+            .unwrap()
+            .breakpoints
+            .remove(&id)
+            .ok_or_else(|| SdbError::Stoppoint(format!("No breakpoint with id {}", id)))?;
+        self.exchange(&format!("z0,{:x},1", address.addr()))?;
+
+        Ok(())
+    }
+}
+
+impl DebugTransport for RemoteProcess {
+    fn request(&self, request: Request) -> Result<Response> {
+        Ok(match request {
+            Request::Resume => {
+                self.send("c")?;
+                Response::Done
+            }
+            Request::Wait => {
+                let reply = self.wait_for_stop_reply(None)?;
+                let reason = self.record_stop(&reply, false)?;
+                Response::Stopped(self.finish_stop(reason)?)
+            }
+            // doesn't wait on anything so it gets through while waiting
+            Request::Interrupt => {
+                self.writer
+                    .lock()
+                    .unwrap()
+                    .write_all(&[0x03])
+                    .map_err(transport_error)?;
+                Response::Done
+            }
+            Request::StepInstruction => {
+                let reply = self.wait_for_stop_reply(Some("s"))?;
+                let reason = self.record_stop(&reply, true)?;
+                Response::Stopped(self.finish_stop(reason)?)
+            }
+            Request::StopInfo => Response::StopInfo(self.stop_info()?),
+            Request::Backtrace => Response::Backtrace(self.backtrace()?),
+            Request::ReadRegister(name) => Response::Register(self.read_register(&name)?),
+            Request::WriteRegister(name, value) => {
+                self.write_register(&name, value)?;
+                Response::Done
+            }
+            Request::ReadMemory { address, amount } => {
+                Response::Memory(self.read_remote_memory(address, amount)?)
+            }
+            Request::WriteMemory { address, data } => {
+                self.write_remote_memory(address, &data)?;
+                Response::Done
+            }
+            Request::BreakAddress(address) => {
+                Response::Breakpoint(self.create_breakpoint(address)?)
+            }
+            Request::RemoveBreakpoint(id) => {
+                self.remove_breakpoint(id)?;
+                Response::Done
+            }
+            Request::StepIn
+            | Request::StepOver
+            | Request::StepOut
+            | Request::BreakFunction(_)
+            | Request::BreakLine { .. } => {
+                return Err(SdbError::Transport(format!(
+                    "{:?} needs debug info, which remote targets don't have",
+                    request
+                )));
+            }
+        })
+    }
+
+    fn events(&self) -> Result<mpsc::Receiver<DebugEvent>> {
+        Ok(self.state.lock().unwrap().subscribers.subscribe())
+    }
+}
+
+impl Inferior for RemoteProcess {
+    fn read_register_by_id(&self, id: RegisterId) -> Result<RegisterValue> {
+        let name = register_info_by_id(id).name();
+        Ok(self.read_register(name)?.into())
+    }
+
+    fn read_memory(&self, address: VirtAddr, amount: usize) -> Result<Vec<u8>> {
+        self.read_remote_memory(address, amount)
+    }
+
+    // nothing's loaded, there's no way to get at the remote files
+    fn modules(&self) -> &ElfCollection {
+        &self.modules
+    }
+
+    fn auxv(&self) -> Result<Auxv> {
+        Err(SdbError::Transport(
+            "The auxiliary vector can't be read remotely".to_owned(),
+        ))
+    }
+}
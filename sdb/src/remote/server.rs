@@ -0,0 +1,552 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+
+use nix::libc;
+use tracing::{debug, info, trace, warn};
+
+use super::{
+    GdbRegister, REGISTERS, checksum, escape, from_hex, gdb_signal, malformed, parse_hex, to_hex,
+};
+use crate::{
+    DebugTransport, ProcessState, Request, Response, Result, SdbError, StopReason, StoppointId,
+    TrapType, types::VirtAddr,
+};
+
+// the packet size advertised to gdb, in hex
+const PACKET_SIZE: &str = "4000";
+
+// gdb won't use a remote x86-64 target without the core, x87 and SSE registers
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <architecture>i386:x86-64</architecture>
+  <osabi>GNU/Linux</osabi>
+  <feature name="org.gnu.gdb.i386.core">
+    <flags id="i386_eflags" size="4">
+      <field name="CF" start="0" end="0"/>
+      <field name="" start="1" end="1"/>
+      <field name="PF" start="2" end="2"/>
+      <field name="AF" start="4" end="4"/>
+      <field name="ZF" start="6" end="6"/>
+      <field name="SF" start="7" end="7"/>
+      <field name="TF" start="8" end="8"/>
+      <field name="IF" start="9" end="9"/>
+      <field name="DF" start="10" end="10"/>
+      <field name="OF" start="11" end="11"/>
+    </flags>
+    <reg name="rax" bitsize="64" type="int64"/>
+    <reg name="rbx" bitsize="64" type="int64"/>
+    <reg name="rcx" bitsize="64" type="int64"/>
+    <reg name="rdx" bitsize="64" type="int64"/>
+    <reg name="rsi" bitsize="64" type="int64"/>
+    <reg name="rdi" bitsize="64" type="int64"/>
+    <reg name="rbp" bitsize="64" type="data_ptr"/>
+    <reg name="rsp" bitsize="64" type="data_ptr"/>
+    <reg name="r8" bitsize="64" type="int64"/>
+    <reg name="r9" bitsize="64" type="int64"/>
+    <reg name="r10" bitsize="64" type="int64"/>
+    <reg name="r11" bitsize="64" type="int64"/>
+    <reg name="r12" bitsize="64" type="int64"/>
+    <reg name="r13" bitsize="64" type="int64"/>
+    <reg name="r14" bitsize="64" type="int64"/>
+    <reg name="r15" bitsize="64" type="int64"/>
+    <reg name="rip" bitsize="64" type="code_ptr"/>
+    <reg name="eflags" bitsize="32" type="i386_eflags"/>
+    <reg name="cs" bitsize="32" type="int32"/>
+    <reg name="ss" bitsize="32" type="int32"/>
+    <reg name="ds" bitsize="32" type="int32"/>
+    <reg name="es" bitsize="32" type="int32"/>
+    <reg name="fs" bitsize="32" type="int32"/>
+    <reg name="gs" bitsize="32" type="int32"/>
+    <reg name="st0" bitsize="80" type="i387_ext"/>
+    <reg name="st1" bitsize="80" type="i387_ext"/>
+    <reg name="st2" bitsize="80" type="i387_ext"/>
+    <reg name="st3" bitsize="80" type="i387_ext"/>
+    <reg name="st4" bitsize="80" type="i387_ext"/>
+    <reg name="st5" bitsize="80" type="i387_ext"/>
+    <reg name="st6" bitsize="80" type="i387_ext"/>
+    <reg name="st7" bitsize="80" type="i387_ext"/>
+    <reg name="fctrl" bitsize="32" type="int" group="float"/>
+    <reg name="fstat" bitsize="32" type="int" group="float"/>
+    <reg name="ftag" bitsize="32" type="int" group="float"/>
+    <reg name="fiseg" bitsize="32" type="int" group="float"/>
+    <reg name="fioff" bitsize="32" type="int" group="float"/>
+    <reg name="foseg" bitsize="32" type="int" group="float"/>
+    <reg name="fooff" bitsize="32" type="int" group="float"/>
+    <reg name="fop" bitsize="32" type="int" group="float"/>
+  </feature>
+  <feature name="org.gnu.gdb.i386.sse">
+    <vector id="v4f" type="ieee_single" count="4"/>
+    <vector id="v2d" type="ieee_double" count="2"/>
+    <vector id="v16i8" type="int8" count="16"/>
+    <vector id="v8i16" type="int16" count="8"/>
+    <vector id="v4i32" type="int32" count="4"/>
+    <vector id="v2i64" type="int64" count="2"/>
+    <union id="vec128">
+      <field name="v4_float" type="v4f"/>
+      <field name="v2_double" type="v2d"/>
+      <field name="v16_int8" type="v16i8"/>
+      <field name="v8_int16" type="v8i16"/>
+      <field name="v4_int32" type="v4i32"/>
+      <field name="v2_int64" type="v2i64"/>
+      <field name="uint128" type="uint128"/>
+    </union>
+    <reg name="xmm0" bitsize="128" type="vec128" regnum="40"/>
+    <reg name="xmm1" bitsize="128" type="vec128"/>
+    <reg name="xmm2" bitsize="128" type="vec128"/>
+    <reg name="xmm3" bitsize="128" type="vec128"/>
+    <reg name="xmm4" bitsize="128" type="vec128"/>
+    <reg name="xmm5" bitsize="128" type="vec128"/>
+    <reg name="xmm6" bitsize="128" type="vec128"/>
+    <reg name="xmm7" bitsize="128" type="vec128"/>
+    <reg name="xmm8" bitsize="128" type="vec128"/>
+    <reg name="xmm9" bitsize="128" type="vec128"/>
+    <reg name="xmm10" bitsize="128" type="vec128"/>
+    <reg name="xmm11" bitsize="128" type="vec128"/>
+    <reg name="xmm12" bitsize="128" type="vec128"/>
+    <reg name="xmm13" bitsize="128" type="vec128"/>
+    <reg name="xmm14" bitsize="128" type="vec128"/>
+    <reg name="xmm15" bitsize="128" type="vec128"/>
+    <reg name="mxcsr" bitsize="32" type="int" group="vector"/>
+  </feature>
+</target>
+"#;
+// what the connection reader and a pending wait send to the session
+enum Incoming {
+    Packet(String),
+    BadChecksum,
+    Interrupt,
+    Stopped(Result<Response>),
+    Closed,
+}
+
+fn read_packets(reader: impl Read, sender: mpsc::Sender<Incoming>) {
+    let mut bytes = BufReader::new(reader).bytes();
+    let mut next = || bytes.next().and_then(|byte| byte.ok());
+
+    while let Some(byte) = next() {
+        let incoming = match byte {
+            // acks, which don't matter over TCP
+            b'+' | b'-' => continue,
+            0x03 => Incoming::Interrupt,
+            b'$' => {
+                let mut data = Vec::new();
+                loop {
+                    match next() {
+                        Some(b'#') => break,
+                        Some(byte) => data.push(byte),
+                        None => {
+                            let _ = sender.send(Incoming::Closed);
+                            return;
+                        }
+                    }
+                }
+                let (Some(high), Some(low)) = (next(), next()) else {
+                    break;
+                };
+
+                let data = String::from_utf8_lossy(&data).into_owned();
+                let expected = std::str::from_utf8(&[high, low])
+                    .ok()
+                    .and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+                if expected == Some(checksum(&data)) {
+                    Incoming::Packet(data)
+                } else {
+                    Incoming::BadChecksum
+                }
+            }
+            byte => {
+                trace!("Ignoring {:#04x} between packets", byte);
+                continue;
+            }
+        };
+
+        if sender.send(incoming).is_err() {
+            return;
+        }
+    }
+
+    let _ = sender.send(Incoming::Closed);
+}
+
+// what handling a packet leaves the session to do
+enum Reply {
+    Packet(String),
+
+    // the stop reply is sent once the wait finishes
+    Resumed,
+
+    // ends the session, after the packet if there is one
+    Close(Option<String>),
+}
+
+struct Session<'a> {
+    transport: &'a dyn DebugTransport,
+    writer: TcpStream,
+    no_ack: bool,
+    breakpoints: HashMap<VirtAddr, StoppointId>,
+
+    // set when gdb asks for a stop, which it expects to see as SIGINT
+    interrupted: bool,
+}
+
+impl Session<'_> {
+    fn write_packet(&mut self, data: &str) -> Result<()> {
+        trace!("Sending {}", data);
+        write!(self.writer, "${}#{:02x}", data, checksum(data))
+            .and_then(|_| self.writer.flush())
+            .map_err(|err| SdbError::Transport(err.to_string()))
+    }
+
+    fn write_ack(&mut self, ack: &[u8]) -> Result<()> {
+        if self.no_ack {
+            return Ok(());
+        }
+
+        self.writer
+            .write_all(ack)
+            .map_err(|err| SdbError::Transport(err.to_string()))
+    }
+
+    fn request(&self, request: Request) -> Result<Response> {
+        self.transport.request(request)
+    }
+
+    fn stop_reply(&mut self, reason: &StopReason) -> Result<String> {
+        let interrupted = std::mem::take(&mut self.interrupted);
+
+        Ok(match reason.reason {
+            ProcessState::Exited => format!("W{:02x}", reason.info as u8),
+            ProcessState::Terminated => format!("X{:02x}", gdb_signal(reason.info)),
+            _ => {
+                let signal = if interrupted {
+                    libc::SIGINT
+                } else {
+                    gdb_signal(reason.info)
+                };
+                let mut reply = format!("T{:02x}", signal);
+
+                if let Response::StopInfo(info) = self.request(Request::StopInfo)? {
+                    let _ = write!(reply, "thread:{:x};", info.thread.as_raw());
+                }
+                match reason.trap_reason {
+                    Some(TrapType::SoftwareBreak) => reply += "swbreak:;",
+                    Some(TrapType::HardwareBreak) => reply += "hwbreak:;",
+                    _ => (),
+                }
+                reply
+            }
+        })
+    }
+
+    fn current_stop_reply(&mut self) -> Result<String> {
+        match self.request(Request::StopInfo)? {
+            Response::StopInfo(info) => self.stop_reply(&info.reason),
+            _ => Ok("S05".to_owned()),
+        }
+    }
+
+    fn read_register(&self, register: &GdbRegister) -> String {
+        let value = register.sdb_name.and_then(|name| {
+            match self.request(Request::ReadRegister(name.to_owned())) {
+                Ok(Response::Register(value)) => Some(value),
+                _ => None,
+            }
+        });
+
+        match value {
+            Some(value) => to_hex(&value.to_le_bytes()[..register.size.min(8)]),
+            // gdb shows these as unavailable
+            None => "xx".repeat(register.size),
+        }
+    }
+
+    fn write_register(&self, register: &GdbRegister, hex: &str) -> Result<()> {
+        let (Some(name), true) = (register.sdb_name, register.writable) else {
+            return Ok(());
+        };
+        if hex.starts_with('x') {
+            return Ok(());
+        }
+
+        let mut bytes = [0; 8];
+        let value = from_hex(hex)?;
+        bytes[..value.len().min(8)].copy_from_slice(&value[..value.len().min(8)]);
+        self.request(Request::WriteRegister(
+            name.to_owned(),
+            u64::from_le_bytes(bytes),
+        ))?;
+
+        Ok(())
+    }
+
+    fn read_features(&self, annex: &str) -> Result<String> {
+        let (name, range) = annex.split_once(':').ok_or_else(|| malformed(annex))?;
+        if name != "target.xml" {
+            return Ok("E00".to_owned());
+        }
+
+        let (offset, length) = range.split_once(',').ok_or_else(|| malformed(annex))?;
+        let offset = (parse_hex(offset)? as usize).min(TARGET_XML.len());
+        let length = parse_hex(length)? as usize;
+
+        let end = (offset + length).min(TARGET_XML.len());
+        let more = if end < TARGET_XML.len() { 'm' } else { 'l' };
+        Ok(format!("{}{}", more, escape(&TARGET_XML[offset..end])))
+    }
+
+    fn resume(&mut self, action: &str) -> Result<Reply> {
+        // signals to deliver are dropped, sdb can't pass them on yet
+        match action.chars().next() {
+            Some('c' | 'C') => {
+                self.request(Request::Resume)?;
+                Ok(Reply::Resumed)
+            }
+            Some('s' | 'S') => match self.request(Request::StepInstruction)? {
+                Response::Stopped(reason) => Ok(Reply::Packet(self.stop_reply(&reason)?)),
+                _ => Ok(Reply::Packet(self.current_stop_reply()?)),
+            },
+            _ => Ok(Reply::Packet(String::new())),
+        }
+    }
+
+    fn handle_packet(&mut self, packet: &str) -> Result<Reply> {
+        let reply = match packet {
+            "?" => self.current_stop_reply()?,
+            "g" => REGISTERS
+                .iter()
+                .map(|register| self.read_register(register))
+                .collect(),
+            "k" => return Ok(Reply::Close(None)),
+            "vCont?" => "vCont;c;C;s;S".to_owned(),
+            "vMustReplyEmpty" => String::new(),
+            "QStartNoAckMode" => {
+                // this packet's been acknowledged already, nothing after it is
+                self.no_ack = true;
+                "OK".to_owned()
+            }
+            "qAttached" => "1".to_owned(),
+            "qSymbol::" => "OK".to_owned(),
+            "qsThreadInfo" => "l".to_owned(),
+            "qfThreadInfo" | "qC" => match self.request(Request::StopInfo)? {
+                Response::StopInfo(info) if packet == "qC" => {
+                    format!("QC{:x}", info.thread.as_raw())
+                }
+                Response::StopInfo(info) => format!("m{:x}", info.thread.as_raw()),
+                _ => String::new(),
+            },
+            _ if packet.starts_with('D') => return Ok(Reply::Close(Some("OK".to_owned()))),
+            _ if packet.starts_with('H') => "OK".to_owned(),
+            _ if packet.starts_with("qSupported") => format!(
+                "PacketSize={};qXfer:features:read+;QStartNoAckMode+;swbreak+;hwbreak+;vContSupported+",
+                PACKET_SIZE
+            ),
+            _ if let Some(annex) = packet.strip_prefix("qXfer:features:read:") => {
+                self.read_features(annex)?
+            }
+            _ if let Some(action) = packet.strip_prefix("vCont;") => {
+                // there's only one thread to apply actions to
+                return self.resume(action);
+            }
+            _ if packet.starts_with(['c', 'C', 's', 'S']) => return self.resume(packet),
+            _ if let Some(registers) = packet.strip_prefix('G') => {
+                let mut offset = 0;
+                for register in REGISTERS {
+                    let Some(hex) = registers.get(offset..offset + register.size * 2) else {
+                        break;
+                    };
+                    self.write_register(register, hex)?;
+                    offset += register.size * 2;
+                }
+                "OK".to_owned()
+            }
+            _ if let Some(number) = packet.strip_prefix('p') => {
+                match REGISTERS.get(parse_hex(number)? as usize) {
+                    Some(register) => self.read_register(register),
+                    None => "E00".to_owned(),
+                }
+            }
+            _ if let Some(assignment) = packet.strip_prefix('P') => {
+                let (number, value) = assignment
+                    .split_once('=')
+                    .ok_or_else(|| malformed(packet))?;
+                match REGISTERS.get(parse_hex(number)? as usize) {
+                    Some(register) => {
+                        self.write_register(register, value)?;
+                        "OK".to_owned()
+                    }
+                    None => "E00".to_owned(),
+                }
+            }
+            _ if let Some(range) = packet.strip_prefix('m') => {
+                let (address, amount) = range.split_once(',').ok_or_else(|| malformed(packet))?;
+                let request = Request::ReadMemory {
+                    address: VirtAddr::new(parse_hex(address)?),
+                    amount: parse_hex(amount)? as usize,
+                };
+                match self.request(request)? {
+                    Response::Memory(data) if !data.is_empty() => to_hex(&data),
+                    _ => "E01".to_owned(),
+                }
+            }
+            _ if let Some(write) = packet.strip_prefix('M') => {
+                let (range, data) = write.split_once(':').ok_or_else(|| malformed(packet))?;
+                let (address, _) = range.split_once(',').ok_or_else(|| malformed(packet))?;
+                self.request(Request::WriteMemory {
+                    address: VirtAddr::new(parse_hex(address)?),
+                    data: from_hex(data)?,
+                })?;
+                "OK".to_owned()
+            }
+            _ if let Some(breakpoint) = packet.strip_prefix("Z0,") => {
+                let (address, _) = breakpoint
+                    .split_once(',')
+                    .ok_or_else(|| malformed(packet))?;
+                let address = VirtAddr::new(parse_hex(address)?);
+                if !self.breakpoints.contains_key(&address)
+                    && let Response::Breakpoint(id) =
+                        self.request(Request::BreakAddress(address))?
+                {
+                    self.breakpoints.insert(address, id);
+                }
+                "OK".to_owned()
+            }
+            _ if let Some(breakpoint) = packet.strip_prefix("z0,") => {
+                let (address, _) = breakpoint
+                    .split_once(',')
+                    .ok_or_else(|| malformed(packet))?;
+                let address = VirtAddr::new(parse_hex(address)?);
+                if let Some(id) = self.breakpoints.remove(&address) {
+                    self.request(Request::RemoveBreakpoint(id))?;
+                }
+                "OK".to_owned()
+            }
+            // anything else isn't supported
+            _ => String::new(),
+        };
+
+        Ok(Reply::Packet(reply))
+    }
+}
+
+/// Serves the GDB remote serial protocol so gdb and lldb can drive a session
+///
+/// Only x86-64 and a single thread are described to the debugger,
+/// and x87 and SSE registers show as unavailable
+pub struct Server<T: DebugTransport> {
+    transport: T,
+}
+
+impl<T: DebugTransport> Server<T> {
+    pub fn new(transport: T) -> Self {
+        Self { transport }
+    }
+
+    #[inline]
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Waits for a debugger to connect and serves it until it goes away
+    pub fn listen(&self, address: impl ToSocketAddrs) -> Result<()> {
+        let listener =
+            TcpListener::bind(address).map_err(|err| SdbError::Transport(err.to_string()))?;
+        let (stream, peer) = listener
+            .accept()
+            .map_err(|err| SdbError::Transport(err.to_string()))?;
+        info!("Serving gdb at {}", peer);
+
+        self.serve(stream)
+    }
+
+    pub fn serve(&self, stream: TcpStream) -> Result<()> {
+        let transport_error = |err: std::io::Error| SdbError::Transport(err.to_string());
+        stream.set_nodelay(true).map_err(transport_error)?;
+        let reader = stream.try_clone().map_err(transport_error)?;
+
+        let (sender, incoming) = mpsc::channel();
+        let reader_sender = sender.clone();
+        std::thread::spawn(move || read_packets(reader, reader_sender));
+
+        let mut session = Session {
+            transport: &self.transport,
+            writer: stream,
+            no_ack: false,
+            breakpoints: HashMap::new(),
+            interrupted: false,
+        };
+
+        std::thread::scope(|scope| {
+            let mut running = false;
+            while let Ok(incoming) = incoming.recv() {
+                let reply = match incoming {
+                    Incoming::Packet(packet) => {
+                        session.write_ack(b"+")?;
+                        if running {
+                            debug!("Ignoring {} while running", packet);
+                            continue;
+                        }
+
+                        trace!("Received {}", packet);
+                        session.handle_packet(&packet).unwrap_or_else(|err| {
+                            warn!("Failed to handle {}: {}", packet, err);
+                            Reply::Packet("E01".to_owned())
+                        })
+                    }
+                    Incoming::BadChecksum => {
+                        session.write_ack(b"-")?;
+                        continue;
+                    }
+                    Incoming::Interrupt => {
+                        if running {
+                            session.interrupted = true;
+                            session.request(Request::Interrupt)?;
+                        }
+                        continue;
+                    }
+                    Incoming::Stopped(response) => {
+                        running = false;
+                        let reply = match response.and_then(Response::into_result) {
+                            Ok(Response::Stopped(reason)) => session.stop_reply(&reason)?,
+                            Ok(_) => session.current_stop_reply()?,
+                            Err(err) => {
+                                warn!("Failed waiting for a stop: {}", err);
+                                "E01".to_owned()
+                            }
+                        };
+                        Reply::Packet(reply)
+                    }
+                    Incoming::Closed => break,
+                };
+
+                match reply {
+                    Reply::Packet(packet) => session.write_packet(&packet)?,
+                    Reply::Resumed => {
+                        running = true;
+                        let sender = sender.clone();
+                        let transport = session.transport;
+                        scope.spawn(move || {
+                            let _ =
+                                sender.send(Incoming::Stopped(transport.request(Request::Wait)));
+                        });
+                    }
+                    Reply::Close(packet) => {
+                        if let Some(packet) = packet {
+                            session.write_packet(&packet)?;
+                        }
+                        break;
+                    }
+                }
+            }
+
+            // a wait still pending would never finish
+            if running {
+                session.request(Request::Interrupt)?;
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,113 @@
+use std::path::PathBuf;
+
+use tracing::trace;
+
+use crate::{
+    BreakpointSite, HitLimit, LogFormat, Process, ProcessState, Result, Target,
+    expr::Expr,
+    stoppoint_collection::{Stoppoint, StoppointId, StoppointKind},
+};
+
+// a breakpoint as where it is in its file, to set again wherever the file's loaded next
+#[derive(Debug, Clone)]
+pub(crate) struct PendingBreakpoint {
+    id: StoppointId,
+    path: PathBuf,
+    file_address: u64,
+    is_enabled: bool,
+    catching: Option<String>,
+    condition: Option<Expr>,
+    log: Option<LogFormat>,
+    hit_limit: Option<HitLimit>,
+}
+
+impl Target {
+    // the breakpoints the user set, the debugger's own are set up again by what made them
+    pub(crate) fn relocatable_breakpoints(&self) -> Vec<PendingBreakpoint> {
+        let fatal = self.fatal.functions();
+        let sites = self
+            .process
+            .breakpoint_sites
+            .iter()
+            .filter(|site| match site.kind() {
+                StoppointKind::Breakpoint | StoppointKind::Logpoint => true,
+                StoppointKind::Catchpoint => site
+                    .caught_function()
+                    .is_some_and(|function| !fatal.iter().any(|fatal| fatal == function)),
+                _ => false,
+            });
+
+        let mut pending = sites
+            .filter_map(|site| {
+                let elf = self.modules.get_elf_containing_address(site.address())?;
+                Some(PendingBreakpoint {
+                    id: site.id(),
+                    path: elf.path().to_path_buf(),
+                    file_address: elf.file_address(site.address())?,
+                    is_enabled: site.is_enabled(),
+                    catching: site.caught_function().map(str::to_owned),
+                    condition: site.condition().cloned(),
+                    log: site.log().cloned(),
+                    hit_limit: site.hit_limit(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // ones still waiting on their library from the last run
+        pending.extend(self.pending_breakpoints.iter().cloned());
+        pending
+    }
+
+    // sets the pending breakpoints whose files have been loaded
+    pub(crate) fn resolve_pending_breakpoints(&mut self) -> Result<()> {
+        let pending = std::mem::take(&mut self.pending_breakpoints);
+        for breakpoint in pending {
+            let Some(elf) = self.modules.get_elf_by_path(&breakpoint.path) else {
+                self.pending_breakpoints.push(breakpoint);
+                continue;
+            };
+            let address = elf.virtual_address(breakpoint.file_address);
+            if self.process.breakpoint_sites.contains_address(address) {
+                continue;
+            }
+            trace!("Setting breakpoint {} at {}", breakpoint.id, address);
+
+            let mut site = BreakpointSite::new(breakpoint.id, address, false);
+            if let Some(function) = breakpoint.catching {
+                site.catch(function);
+            }
+            site.set_condition(breakpoint.condition);
+            site.set_log(breakpoint.log);
+            site.set_hit_limit(breakpoint.hit_limit);
+            self.process.breakpoint_sites.push(site);
+
+            if breakpoint.is_enabled {
+                self.process.enable_breakpoint_site(breakpoint.id)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Process {
+    /// Takes over what was set up on the process this one is a new run of
+    ///
+    /// Catchpoints, signal handling, scheduler locking, and event subscribers carry over
+    /// as they were, the breakpoints are the target's to set again
+    pub(crate) fn take_over(&mut self, old: &mut Process) -> Result<()> {
+        // handles to the old process follow along to this one
+        self.shared = old.shared.clone();
+        self.shared.set_pid(self.pid);
+        self.set_state(ProcessState::Stopped);
+
+        self.next_stoppoint_id = old.next_stoppoint_id;
+        self.subscribers = std::mem::take(&mut old.subscribers);
+        self.signals = std::mem::take(&mut old.signals);
+        self.set_follow_fork_mode(old.follow_fork_mode());
+        self.set_scheduler_locking(old.scheduler_locking());
+        self.syscall_trace = old.syscall_trace.take();
+        self.signal_catchpoints = std::mem::take(&mut old.signal_catchpoints);
+        self.event_catchpoints = std::mem::take(&mut old.event_catchpoints);
+        self.update_trace_options()
+    }
+}
@@ -0,0 +1,126 @@
+use procfs::process::{MMPermissions, MMapPath};
+use tracing::debug;
+
+use crate::{Process, Result, SdbError, types::VirtAddr};
+
+// mappings are read this much at a time, a match can straddle two reads
+const SEARCH_CHUNK_SIZE: usize = 1 << 20;
+
+/// Which of the process's mappings a memory search looks through, every readable one by default
+#[derive(Debug, Clone, Default)]
+pub struct SearchRegions {
+    permissions: MMPermissions,
+    name: Option<String>,
+}
+
+impl SearchRegions {
+    /// Only mappings with at least these permissions, letters from rwx
+    pub fn permissions(mut self, permissions: &str) -> Result<Self> {
+        for c in permissions.chars() {
+            self.permissions |= match c {
+                'r' => MMPermissions::READ,
+                'w' => MMPermissions::WRITE,
+                'x' => MMPermissions::EXECUTE,
+                c => {
+                    return Err(SdbError::Other(format!(
+                        "Unknown permission '{}', they're r, w, and x",
+                        c
+                    )));
+                }
+            };
+        }
+        Ok(self)
+    }
+
+    /// Only mappings whose file or name, like libc or [heap], has this in it
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+}
+
+/// Where a pattern was found, and what's mapped there
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryMatch {
+    pub address: VirtAddr,
+    /// The file, or a name like [heap], empty for anonymous memory
+    pub mapping: String,
+}
+
+fn mapping_name(path: &MMapPath) -> String {
+    match path {
+        MMapPath::Path(path) => path.display().to_string(),
+        MMapPath::Heap => "[heap]".to_owned(),
+        MMapPath::Stack => "[stack]".to_owned(),
+        MMapPath::TStack(tid) => format!("[stack:{}]", tid),
+        MMapPath::Vdso => "[vdso]".to_owned(),
+        MMapPath::Vvar => "[vvar]".to_owned(),
+        MMapPath::Vsyscall => "[vsyscall]".to_owned(),
+        MMapPath::Rollup => "[rollup]".to_owned(),
+        MMapPath::Anonymous => String::new(),
+        MMapPath::Vsys(key) => format!("SYSV{:08x}", key),
+        MMapPath::Other(name) => name.clone(),
+    }
+}
+
+impl Process {
+    /// Every address the pattern's at in the readable mappings the regions pick, lowest first
+    ///
+    /// Breakpoints don't get in the way of a match. A mapping that can't be read is skipped
+    pub fn search_memory(
+        &self,
+        pattern: &[u8],
+        regions: &SearchRegions,
+    ) -> Result<Vec<MemoryMatch>> {
+        if pattern.is_empty() {
+            return Err(SdbError::Other("Nothing to search for".to_owned()));
+        }
+
+        let permissions = regions.permissions | MMPermissions::READ;
+        let mut matches = Vec::new();
+        for map in procfs::process::Process::new(self.pid.as_raw())?.maps()? {
+            let name = mapping_name(&map.pathname);
+            if !map.perms.contains(permissions)
+                || map.pathname == MMapPath::Vsyscall
+                || regions
+                    .name
+                    .as_ref()
+                    .is_some_and(|wanted| !name.contains(wanted.as_str()))
+            {
+                continue;
+            }
+
+            let (low, high) = map.address;
+            let mut start = low;
+            while start < high {
+                let amount = ((high - start) as usize).min(SEARCH_CHUNK_SIZE + pattern.len() - 1);
+                let memory = match self.read_memory_without_traps(VirtAddr::new(start), amount) {
+                    Ok(memory) => memory,
+                    Err(err) => {
+                        debug!("Skipping {:#x}-{:#x} {}: {}", low, high, name, err);
+                        break;
+                    }
+                };
+
+                for (offset, _) in memory
+                    .windows(pattern.len())
+                    .enumerate()
+                    .filter(|(_, window)| *window == pattern)
+                {
+                    matches.push(MemoryMatch {
+                        address: VirtAddr::new(start + offset as u64),
+                        mapping: name.clone(),
+                    });
+                }
+
+                // the rest of the mapping can't be read
+                if memory.len() < amount {
+                    break;
+                }
+                start += SEARCH_CHUNK_SIZE as u64;
+            }
+        }
+
+        Ok(matches)
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashSet;
+
+use nix::{sys::signal::Signal, unistd::Pid};
+
+use crate::{
+    Process, Result, SdbError,
+    stoppoint_collection::{Stoppoint, StoppointId, StoppointKind},
+    types::VirtAddr,
+};
+
+// signals that are routine enough to pass straight through
+const DEFAULT_NOSTOP: &[Signal] = &[
+    Signal::SIGCHLD,
+    Signal::SIGWINCH,
+    Signal::SIGURG,
+    Signal::SIGALRM,
+    Signal::SIGVTALRM,
+    Signal::SIGPROF,
+    Signal::SIGIO,
+];
+
+#[derive(Debug)]
+pub(crate) struct SignalPolicy {
+    nostop: HashSet<Signal>,
+}
+
+impl Default for SignalPolicy {
+    fn default() -> Self {
+        Self {
+            nostop: DEFAULT_NOSTOP.iter().copied().collect(),
+        }
+    }
+}
+
+/// Stops the process when it receives a signal, even one that's otherwise passed through
+#[derive(Debug)]
+pub struct SignalCatchpoint {
+    id: StoppointId,
+    // None catches every signal but the debugger's own
+    signal: Option<Signal>,
+    is_enabled: bool,
+    hit_count: u64,
+}
+
+impl Stoppoint for SignalCatchpoint {
+    #[inline]
+    fn id(&self) -> StoppointId {
+        self.id
+    }
+
+    // signals don't happen anywhere in particular
+    #[inline]
+    fn address(&self) -> VirtAddr {
+        VirtAddr::default()
+    }
+
+    #[inline]
+    fn has_address(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn is_enabled(&self) -> bool {
+        self.is_enabled
+    }
+
+    #[inline]
+    fn kind(&self) -> StoppointKind {
+        StoppointKind::Catchpoint
+    }
+
+    #[inline]
+    fn hit_count(&self) -> u64 {
+        self.hit_count
+    }
+
+    fn spec(&self) -> String {
+        match self.signal {
+            Some(signal) => format!("signal {}", signal),
+            None => "signal all".to_owned(),
+        }
+    }
+}
+
+impl SignalCatchpoint {
+    #[inline]
+    pub fn signal(&self) -> Option<Signal> {
+        self.signal
+    }
+
+    #[inline]
+    fn catches(&self, signal: Signal) -> bool {
+        self.is_enabled && self.signal.is_none_or(|caught| caught == signal)
+    }
+}
+
+// SIGTRAP is ours and SIGSTOP is how we stop the process ourselves
+#[inline]
+fn is_debugger_signal(signal: Signal) -> bool {
+    matches!(signal, Signal::SIGTRAP | Signal::SIGSTOP)
+}
+
+impl Process {
+    #[inline]
+    pub fn signal_stops(&self, signal: Signal) -> bool {
+        !self.signals.nostop.contains(&signal)
+    }
+
+    /// Controls whether receiving the signal stops the process
+    /// or is silently passed through to it
+    pub fn set_signal_stops(&mut self, signal: Signal, stop: bool) {
+        if stop {
+            self.signals.nostop.remove(&signal);
+        } else {
+            self.signals.nostop.insert(signal);
+        }
+    }
+
+    pub(crate) fn record_pending_signal(&mut self, tid: Pid, signal: Signal) {
+        let Some(thread) = self.threads.get_mut(&tid) else {
+            return;
+        };
+
+        thread.pending_signal = (!is_debugger_signal(signal)).then_some(signal);
+    }
+
+    #[inline]
+    pub fn signal_catchpoints(&self) -> &crate::StoppointCollection<SignalCatchpoint> {
+        &self.signal_catchpoints
+    }
+
+    /// Stops the process when it receives the signal, or any signal with None,
+    /// whether or not it would stop otherwise
+    pub fn catch_signal(&mut self, signal: Option<Signal>) -> Result<StoppointId> {
+        if let Some(signal) = signal.filter(|signal| is_debugger_signal(*signal)) {
+            return Err(SdbError::Stoppoint(format!(
+                "{} is used by the debugger and can't be caught",
+                signal
+            )));
+        }
+
+        let id = self.next_stoppoint_id;
+        self.next_stoppoint_id += 1;
+
+        self.signal_catchpoints.push(SignalCatchpoint {
+            id,
+            signal,
+            is_enabled: true,
+            hit_count: 0,
+        });
+
+        Ok(id)
+    }
+
+    #[inline]
+    pub fn enable_signal_catchpoint(&mut self, id: StoppointId) -> Result<()> {
+        self.signal_catchpoints.get_by_id_mut(id)?.is_enabled = true;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn disable_signal_catchpoint(&mut self, id: StoppointId) -> Result<()> {
+        self.signal_catchpoints.get_by_id_mut(id)?.is_enabled = false;
+        Ok(())
+    }
+
+    pub fn remove_signal_catchpoint(&mut self, id: StoppointId) -> Result<()> {
+        self.signal_catchpoints.remove_by_id(id)?;
+        Ok(())
+    }
+
+    // counts the hit on the first catchpoint that catches the signal
+    pub(crate) fn hit_signal_catchpoint(&mut self, signal: Signal) -> Option<StoppointId> {
+        if is_debugger_signal(signal) {
+            return None;
+        }
+
+        let catchpoint = self
+            .signal_catchpoints
+            .iter_mut()
+            .find(|catchpoint| catchpoint.catches(signal))?;
+        catchpoint.hit_count += 1;
+        Some(catchpoint.id)
+    }
+}
@@ -0,0 +1,104 @@
+use std::path::{Path, PathBuf};
+
+use crate::{Result, SdbError, SourceLocation, Target};
+
+/// Where the paths in the debug info are on this machine, for sources built somewhere else
+#[derive(Debug, Default, Clone)]
+pub struct SourceMap {
+    // checked in the order they were added
+    substitutions: Vec<(PathBuf, PathBuf)>,
+}
+
+impl SourceMap {
+    /// Paths under from are looked for under to instead, replacing an earlier rule for from
+    pub fn substitute(&mut self, from: impl Into<PathBuf>, to: impl Into<PathBuf>) {
+        let (from, to) = (from.into(), to.into());
+        match self.substitutions.iter_mut().find(|(old, _)| *old == from) {
+            Some(rule) => rule.1 = to,
+            None => self.substitutions.push((from, to)),
+        }
+    }
+
+    /// Returns false if there was no rule for from
+    pub fn remove(&mut self, from: impl AsRef<Path>) -> bool {
+        let len = self.substitutions.len();
+        self.substitutions.retain(|(old, _)| old != from.as_ref());
+        self.substitutions.len() != len
+    }
+
+    #[inline]
+    pub fn substitutions(&self) -> &[(PathBuf, PathBuf)] {
+        &self.substitutions
+    }
+
+    /// The first rule that matches whole path components, or the path as it is
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        self.substitutions
+            .iter()
+            .find_map(|(from, to)| Some(to.join(path.strip_prefix(from).ok()?)))
+            .unwrap_or_else(|| path.to_path_buf())
+    }
+}
+
+impl Target {
+    /// A file:line or the start of a function, the file only has to match the end of the path
+    pub fn find_source_location(&self, location: &str) -> Result<SourceLocation> {
+        let modules = &self.modules;
+
+        if let Some((file, line)) = location.rsplit_once(':')
+            && let Ok(line) = line.parse()
+        {
+            return modules
+                .iter()
+                .filter_map(|elf| elf.dwarf())
+                .flat_map(|dwarf| dwarf.line_table())
+                .find(|entry| entry.file.ends_with(file))
+                .map(|entry| SourceLocation {
+                    file: entry.file.clone(),
+                    line,
+                    column: 0,
+                })
+                .ok_or_else(|| SdbError::Source(format!("No source file {}", file)));
+        }
+
+        modules
+            .iter()
+            .filter_map(|elf| elf.dwarf())
+            .flat_map(|dwarf| dwarf.functions())
+            .find(|function| function.name == location)
+            .and_then(|function| {
+                Some(SourceLocation {
+                    file: function.file.clone()?,
+                    line: function.line?,
+                    column: 0,
+                })
+            })
+            .ok_or_else(|| SdbError::Source(format!("No source for {}", location)))
+    }
+
+    /// Lines first through last of a file from the debug info, fewer at the end of the file
+    pub fn source_lines(&self, file: &Path, first: u64, last: u64) -> Result<Vec<(u64, String)>> {
+        let path = self.source_map().resolve(file);
+        let text = std::fs::read(&path)
+            .map_err(|err| SdbError::Source(format!("{}: {}", path.display(), err)))?;
+        let text = String::from_utf8_lossy(&text);
+
+        let first = first.max(1);
+        let lines = text
+            .lines()
+            .zip(1..)
+            .skip(first as usize - 1)
+            .take_while(|(_, number)| *number <= last)
+            .map(|(line, number)| (number, line.to_owned()))
+            .collect::<Vec<_>>();
+        if lines.is_empty() {
+            return Err(SdbError::Source(format!(
+                "Line {} is past the end of {}",
+                first,
+                path.display()
+            )));
+        }
+
+        Ok(lines)
+    }
+}
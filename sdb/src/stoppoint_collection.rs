@@ -0,0 +1,174 @@
+use crate::{Result, SdbError, types::VirtAddr};
+
+pub type StoppointId = i32;
+
+/// What a stoppoint is for, they all share one set of ids
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StoppointKind {
+    Breakpoint,
+    // stops on entry to a function the process only calls when it's about to die,
+    // or when it receives a signal
+    Catchpoint,
+    // prints a message and carries on instead of stopping
+    Logpoint,
+    Hook,
+    // stops when memory changes rather than at an address
+    Watchpoint,
+    // set by the debugger for its own purposes
+    Internal,
+}
+
+impl std::fmt::Display for StoppointKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Breakpoint => write!(f, "breakpoint"),
+            Self::Catchpoint => write!(f, "catchpoint"),
+            Self::Logpoint => write!(f, "logpoint"),
+            Self::Hook => write!(f, "hook"),
+            Self::Watchpoint => write!(f, "watchpoint"),
+            Self::Internal => write!(f, "internal"),
+        }
+    }
+}
+
+pub trait Stoppoint {
+    fn id(&self) -> StoppointId;
+
+    fn address(&self) -> VirtAddr;
+
+    /// False for the ones that aren't set on an address
+    #[inline]
+    fn has_address(&self) -> bool {
+        true
+    }
+
+    fn is_enabled(&self) -> bool;
+
+    fn kind(&self) -> StoppointKind;
+
+    /// Every time the process has run into it
+    fn hit_count(&self) -> u64;
+
+    /// What it was set on, for listing
+    #[inline]
+    fn spec(&self) -> String {
+        self.address().to_string()
+    }
+
+    #[inline]
+    fn at_address(&self, address: VirtAddr) -> bool {
+        self.address() == address
+    }
+
+    #[inline]
+    fn in_range(&self, low: VirtAddr, high: VirtAddr) -> bool {
+        low <= self.address() && self.address() < high
+    }
+}
+
+#[derive(Debug)]
+pub struct StoppointCollection<T> {
+    stoppoints: Vec<T>,
+}
+
+impl<T> Default for StoppointCollection<T> {
+    fn default() -> Self {
+        Self {
+            stoppoints: Vec::default(),
+        }
+    }
+}
+
+impl<T: Stoppoint> StoppointCollection<T> {
+    pub(crate) fn push(&mut self, stoppoint: T) -> &mut T {
+        self.stoppoints.push(stoppoint);
+        self.stoppoints.last_mut().unwrap()
+    }
+
+    #[inline]
+    pub fn contains_id(&self, id: StoppointId) -> bool {
+        self.stoppoints.iter().any(|point| point.id() == id)
+    }
+
+    #[inline]
+    pub fn contains_address(&self, address: VirtAddr) -> bool {
+        self.stoppoints
+            .iter()
+            .any(|point| point.at_address(address))
+    }
+
+    #[inline]
+    pub fn enabled_stoppoint_at_address(&self, address: VirtAddr) -> bool {
+        self.stoppoints
+            .iter()
+            .any(|point| point.at_address(address) && point.is_enabled())
+    }
+
+    pub fn get_by_id(&self, id: StoppointId) -> Result<&T> {
+        self.stoppoints
+            .iter()
+            .find(|point| point.id() == id)
+            .ok_or_else(|| SdbError::Stoppoint(format!("Invalid stoppoint id {}", id)))
+    }
+
+    pub(crate) fn get_by_id_mut(&mut self, id: StoppointId) -> Result<&mut T> {
+        self.stoppoints
+            .iter_mut()
+            .find(|point| point.id() == id)
+            .ok_or_else(|| SdbError::Stoppoint(format!("Invalid stoppoint id {}", id)))
+    }
+
+    pub fn get_by_address(&self, address: VirtAddr) -> Result<&T> {
+        self.stoppoints
+            .iter()
+            .find(|point| point.at_address(address))
+            .ok_or_else(|| {
+                SdbError::Stoppoint(format!("Stoppoint with address {} not found", address))
+            })
+    }
+
+    pub(crate) fn get_by_address_mut(&mut self, address: VirtAddr) -> Result<&mut T> {
+        self.stoppoints
+            .iter_mut()
+            .find(|point| point.at_address(address))
+            .ok_or_else(|| {
+                SdbError::Stoppoint(format!("Stoppoint with address {} not found", address))
+            })
+    }
+
+    pub fn get_in_region(&self, low: VirtAddr, high: VirtAddr) -> impl Iterator<Item = &T> {
+        self.stoppoints
+            .iter()
+            .filter(move |point| point.in_range(low, high))
+    }
+
+    // callers are responsible for disabling the stoppoint first
+    pub(crate) fn remove_by_id(&mut self, id: StoppointId) -> Result<T> {
+        let idx = self
+            .stoppoints
+            .iter()
+            .position(|point| point.id() == id)
+            .ok_or_else(|| SdbError::Stoppoint(format!("Invalid stoppoint id {}", id)))?;
+        Ok(self.stoppoints.remove(idx))
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.stoppoints.iter()
+    }
+
+    #[inline]
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.stoppoints.iter_mut()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.stoppoints.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.stoppoints.is_empty()
+    }
+}
@@ -0,0 +1,85 @@
+use num_traits::ToPrimitive;
+
+use crate::{Process, RegisterInfo, Result, SdbError, register_info_by_name};
+
+/// Where the kernel's syscall calling convention keeps things
+///
+/// Registers are named rather than identified so that
+/// other architectures can be described without their register tables
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SyscallAbi {
+    pub number: &'static str,
+    pub args: [&'static str; 6],
+    pub return_value: &'static str,
+}
+
+impl SyscallAbi {
+    // orig_rax keeps the number after rax is overwritten with the return value
+    pub const X86_64: Self = Self {
+        number: "orig_rax",
+        args: ["rdi", "rsi", "rdx", "r10", "r8", "r9"],
+        return_value: "rax",
+    };
+
+    // the return value overwrites the first argument
+    pub const AARCH64: Self = Self {
+        number: "x8",
+        args: ["x0", "x1", "x2", "x3", "x4", "x5"],
+        return_value: "x0",
+    };
+
+    #[cfg(target_arch = "x86_64")]
+    pub const NATIVE: Self = Self::X86_64;
+
+    #[cfg(target_arch = "aarch64")]
+    pub const NATIVE: Self = Self::AARCH64;
+}
+
+fn register(name: &str) -> Result<&'static RegisterInfo> {
+    register_info_by_name(name)
+        .ok_or_else(|| SdbError::Register(format!("No register {} on this architecture", name)))
+}
+
+impl Process {
+    #[inline]
+    pub fn syscall_abi(&self) -> SyscallAbi {
+        SyscallAbi::NATIVE
+    }
+
+    fn read_abi_register(&self, name: &str) -> Result<u64> {
+        let value = self.read_register_by_id(register(name)?.id())?;
+        Ok(value.to_u64().unwrap())
+    }
+
+    fn write_abi_register(&self, name: &str, value: u64) -> Result<()> {
+        self.write_register_by_id(register(name)?.id(), value.into())
+    }
+
+    pub fn read_syscall_number(&self) -> Result<u64> {
+        self.read_abi_register(self.syscall_abi().number)
+    }
+
+    /// Changing the number at syscall entry changes which syscall runs,
+    /// an invalid number skips it entirely
+    pub fn write_syscall_number(&self, number: u64) -> Result<()> {
+        self.write_abi_register(self.syscall_abi().number, number)
+    }
+
+    pub fn read_syscall_args(&self) -> Result<[u64; 6]> {
+        let mut args = [0; 6];
+        for (arg, name) in args.iter_mut().zip(self.syscall_abi().args) {
+            *arg = self.read_abi_register(name)?;
+        }
+
+        Ok(args)
+    }
+
+    /// Only meaningful at syscall exit, errors are returned as -errno
+    pub fn read_syscall_return(&self) -> Result<i64> {
+        Ok(self.read_abi_register(self.syscall_abi().return_value)? as i64)
+    }
+
+    pub fn write_syscall_return(&self, value: i64) -> Result<()> {
+        self.write_abi_register(self.syscall_abi().return_value, value as u64)
+    }
+}
@@ -0,0 +1,477 @@
+use nix::{errno::Errno, libc, sys::signal::Signal, unistd::Pid};
+
+use crate::{
+    DebugEvent, Process, Result, SdbError,
+    syscall_names::{syscall_name, syscall_number},
+    types::VirtAddr,
+};
+
+// strings and buffers are cut off here, like strace's default
+const MAX_SHOWN: usize = 32;
+
+// how many strings of an argv are shown
+const MAX_STRINGS: usize = 16;
+
+/// Which syscalls a trace reports, all of them unless it's given names
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyscallFilter {
+    numbers: Option<Vec<u64>>,
+}
+
+impl SyscallFilter {
+    #[inline]
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Only these syscalls, by the names the kernel gives them
+    pub fn names(names: &[impl AsRef<str>]) -> Result<Self> {
+        let numbers = names
+            .iter()
+            .map(|name| {
+                syscall_number(name.as_ref())
+                    .ok_or_else(|| SdbError::Other(format!("No syscall named {}", name.as_ref())))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            numbers: Some(numbers),
+        })
+    }
+
+    pub fn matches(&self, number: u64) -> bool {
+        self.numbers
+            .as_ref()
+            .is_none_or(|numbers| numbers.contains(&number))
+    }
+}
+
+/// A syscall the process made, with its arguments decoded as far as sdb knows how
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyscallRecord {
+    pub tid: Pid,
+    pub number: u64,
+    pub args: Vec<String>,
+    /// None for one that didn't come back, like exit_group
+    pub result: Option<String>,
+}
+
+impl SyscallRecord {
+    #[inline]
+    pub fn name(&self) -> Option<&'static str> {
+        syscall_name(self.number)
+    }
+}
+
+impl std::fmt::Display for SyscallRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{}", name)?,
+            None => write!(f, "syscall_{}", self.number)?,
+        }
+        write!(
+            f,
+            "({}) = {}",
+            self.args.join(", "),
+            self.result.as_deref().unwrap_or("?")
+        )
+    }
+}
+
+// a syscall a thread's in, waiting for its exit stop
+#[derive(Debug, Clone)]
+pub(crate) struct PendingSyscall {
+    number: u64,
+    args: [u64; 6],
+    text: Vec<String>,
+}
+
+// whether a syscall stop is the entry or the exit, nix's wrapper doesn't pass the buffer's size
+fn syscall_stop_op(tid: Pid) -> Result<u8> {
+    let mut info = std::mem::MaybeUninit::<libc::ptrace_syscall_info>::zeroed();
+    let ret = unsafe {
+        libc::ptrace(
+            libc::PTRACE_GET_SYSCALL_INFO,
+            tid.as_raw(),
+            std::mem::size_of::<libc::ptrace_syscall_info>(),
+            info.as_mut_ptr(),
+        )
+    };
+    Errno::result(ret).map_err(SdbError::ptrace("get_syscall_info", tid))?;
+    Ok(unsafe { info.assume_init() }.op)
+}
+
+// how an argument is shown
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Arg {
+    Int,
+    Hex,
+    Fd,
+    // an fd or AT_FDCWD
+    DirFd,
+    Str,
+    StrArray,
+    // bytes going in, the count's the argument at this index
+    InBuf(usize),
+    // bytes coming out, as many as the syscall returned
+    OutBuf,
+    OpenFlags,
+    Mode,
+    Prot,
+    MapFlags,
+    Access,
+    Whence,
+    Sig,
+}
+
+use Arg::*;
+
+fn signature(name: &str) -> Option<&'static [Arg]> {
+    Some(match name {
+        "read" => &[Fd, OutBuf, Int],
+        "write" => &[Fd, InBuf(2), Int],
+        "pread64" => &[Fd, OutBuf, Int, Int],
+        "pwrite64" => &[Fd, InBuf(2), Int, Int],
+        "readv" | "writev" => &[Fd, Hex, Int],
+        "open" => &[Str, OpenFlags, Mode],
+        "openat" => &[DirFd, Str, OpenFlags, Mode],
+        "creat" => &[Str, Mode],
+        "close" => &[Fd],
+        "stat" | "lstat" => &[Str, Hex],
+        "fstat" => &[Fd, Hex],
+        "newfstatat" => &[DirFd, Str, Hex, Hex],
+        "statx" => &[DirFd, Str, Hex, Hex, Hex],
+        "lseek" => &[Fd, Int, Whence],
+        "mmap" => &[Hex, Int, Prot, MapFlags, Fd, Hex],
+        "mprotect" => &[Hex, Int, Prot],
+        "munmap" => &[Hex, Int],
+        "brk" => &[Hex],
+        "ioctl" => &[Fd, Hex, Hex],
+        "access" => &[Str, Access],
+        "faccessat" | "faccessat2" => &[DirFd, Str, Access],
+        "pipe" => &[Hex],
+        "pipe2" => &[Hex, OpenFlags],
+        "dup" => &[Fd],
+        "dup2" => &[Fd, Fd],
+        "dup3" => &[Fd, Fd, OpenFlags],
+        "fcntl" => &[Fd, Int, Hex],
+        "nanosleep" => &[Hex, Hex],
+        "clock_nanosleep" => &[Int, Int, Hex, Hex],
+        "getpid" | "gettid" | "getppid" | "getuid" | "geteuid" | "getgid" | "getegid" => &[],
+        "kill" => &[Int, Sig],
+        "tgkill" => &[Int, Int, Sig],
+        "rt_sigaction" => &[Sig, Hex, Hex, Int],
+        "rt_sigprocmask" => &[Int, Hex, Hex, Int],
+        "exit" | "exit_group" => &[Int],
+        "execve" => &[Str, StrArray, Hex],
+        "unlink" | "rmdir" | "chdir" => &[Str],
+        "unlinkat" => &[DirFd, Str, Hex],
+        "mkdir" => &[Str, Mode],
+        "mkdirat" => &[DirFd, Str, Mode],
+        "rename" => &[Str, Str],
+        "readlink" => &[Str, OutBuf, Int],
+        "readlinkat" => &[DirFd, Str, OutBuf, Int],
+        "getcwd" => &[OutBuf, Int],
+        "socket" => &[Int, Int, Int],
+        "connect" | "bind" => &[Fd, Hex, Int],
+        "wait4" => &[Int, Hex, Hex, Hex],
+        "arch_prctl" => &[Hex, Hex],
+        "set_tid_address" => &[Hex],
+        "set_robust_list" => &[Hex, Int],
+        "futex" => &[Hex, Int, Int, Hex, Hex, Int],
+        "getrandom" => &[Hex, Int, Hex],
+        "prlimit64" => &[Int, Int, Hex, Hex],
+        "rseq" => &[Hex, Int, Hex, Hex],
+        _ => return None,
+    })
+}
+
+// syscalls that hand back an address rather than a number
+fn returns_address(name: &str) -> bool {
+    matches!(name, "mmap" | "brk" | "mremap" | "shmat")
+}
+
+// the syscall never comes back to report a result
+fn never_returns(name: &str) -> bool {
+    matches!(name, "exit" | "exit_group")
+}
+
+// quoted with C escapes and cut off at MAX_SHOWN like strace does
+fn quote(bytes: &[u8], truncated: bool) -> String {
+    let mut text = String::from("\"");
+    for &byte in bytes.iter().take(MAX_SHOWN) {
+        match byte {
+            b'\n' => text.push_str("\\n"),
+            b'\t' => text.push_str("\\t"),
+            b'\r' => text.push_str("\\r"),
+            b'"' => text.push_str("\\\""),
+            b'\\' => text.push_str("\\\\"),
+            0x20..=0x7e => text.push(byte as char),
+            _ => text.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    text.push('"');
+    if truncated || bytes.len() > MAX_SHOWN {
+        text.push_str("...");
+    }
+    text
+}
+
+// the names of the set bits, what's left over in hex
+fn flags(value: u64, names: &[(u64, &str)]) -> Vec<String> {
+    let mut rest = value;
+    let mut set = names
+        .iter()
+        .filter(|(bit, _)| *bit != 0 && value & bit == *bit)
+        .map(|(bit, name)| {
+            rest &= !bit;
+            name.to_string()
+        })
+        .collect::<Vec<_>>();
+    if rest != 0 {
+        set.push(format!("{:#x}", rest));
+    }
+    set
+}
+
+fn open_flags(value: u64) -> String {
+    let mode = match value as i32 & libc::O_ACCMODE {
+        libc::O_WRONLY => "O_WRONLY",
+        libc::O_RDWR => "O_RDWR",
+        _ => "O_RDONLY",
+    };
+    let mut set = vec![mode.to_owned()];
+    set.extend(flags(
+        value & !(libc::O_ACCMODE as u64),
+        &[
+            (libc::O_CREAT as u64, "O_CREAT"),
+            (libc::O_EXCL as u64, "O_EXCL"),
+            (libc::O_NOCTTY as u64, "O_NOCTTY"),
+            (libc::O_TRUNC as u64, "O_TRUNC"),
+            (libc::O_APPEND as u64, "O_APPEND"),
+            (libc::O_NONBLOCK as u64, "O_NONBLOCK"),
+            (libc::O_DIRECTORY as u64, "O_DIRECTORY"),
+            (libc::O_NOFOLLOW as u64, "O_NOFOLLOW"),
+            (libc::O_CLOEXEC as u64, "O_CLOEXEC"),
+            (libc::O_PATH as u64, "O_PATH"),
+        ],
+    ));
+    set.join("|")
+}
+
+fn prot(value: u64) -> String {
+    if value == 0 {
+        return "PROT_NONE".to_owned();
+    }
+    flags(
+        value,
+        &[
+            (libc::PROT_READ as u64, "PROT_READ"),
+            (libc::PROT_WRITE as u64, "PROT_WRITE"),
+            (libc::PROT_EXEC as u64, "PROT_EXEC"),
+        ],
+    )
+    .join("|")
+}
+
+fn map_flags(value: u64) -> String {
+    flags(
+        value,
+        &[
+            (libc::MAP_SHARED as u64, "MAP_SHARED"),
+            (libc::MAP_PRIVATE as u64, "MAP_PRIVATE"),
+            (libc::MAP_FIXED as u64, "MAP_FIXED"),
+            (libc::MAP_ANONYMOUS as u64, "MAP_ANONYMOUS"),
+            (libc::MAP_DENYWRITE as u64, "MAP_DENYWRITE"),
+            (libc::MAP_NORESERVE as u64, "MAP_NORESERVE"),
+            (libc::MAP_POPULATE as u64, "MAP_POPULATE"),
+            (libc::MAP_STACK as u64, "MAP_STACK"),
+            (libc::MAP_FIXED_NOREPLACE as u64, "MAP_FIXED_NOREPLACE"),
+        ],
+    )
+    .join("|")
+}
+
+fn access(value: u64) -> String {
+    if value == 0 {
+        return "F_OK".to_owned();
+    }
+    flags(
+        value,
+        &[
+            (libc::R_OK as u64, "R_OK"),
+            (libc::W_OK as u64, "W_OK"),
+            (libc::X_OK as u64, "X_OK"),
+        ],
+    )
+    .join("|")
+}
+
+impl Process {
+    /// Reports the syscalls the filter picks as [`DebugEvent::Syscall`]s from the next resume,
+    /// the process carries on through them without stopping
+    pub fn trace_syscalls(&mut self, filter: SyscallFilter) {
+        self.syscall_trace = Some(filter);
+    }
+
+    pub fn stop_tracing_syscalls(&mut self) {
+        self.syscall_trace = None;
+        for thread in self.threads.values_mut() {
+            thread.pending_syscall = None;
+        }
+    }
+
+    #[inline]
+    pub fn syscall_trace(&self) -> Option<&SyscallFilter> {
+        self.syscall_trace.as_ref()
+    }
+
+    fn format_string(&self, address: u64) -> String {
+        if address == 0 {
+            return "NULL".to_owned();
+        }
+        match self.read_c_string(VirtAddr::new(address), MAX_SHOWN + 1) {
+            Ok(bytes) => quote(&bytes, false),
+            Err(_) => format!("{:#x}", address),
+        }
+    }
+
+    fn format_buffer(&self, address: u64, count: u64) -> String {
+        let amount = (count as usize).min(MAX_SHOWN);
+        match self.read_memory_without_traps(VirtAddr::new(address), amount) {
+            Ok(bytes) if bytes.len() == amount => quote(&bytes, count as usize > MAX_SHOWN),
+            _ => format!("{:#x}", address),
+        }
+    }
+
+    // a NULL terminated array of string pointers, like argv
+    fn format_strings(&self, address: u64) -> String {
+        if address == 0 {
+            return "NULL".to_owned();
+        }
+        let mut strings = Vec::new();
+        for idx in 0..=MAX_STRINGS {
+            let Ok(pointer) = self.read_u64(VirtAddr::new(address) + (idx as i64 * 8)) else {
+                return format!("{:#x}", address);
+            };
+            if pointer == 0 {
+                return format!("[{}]", strings.join(", "));
+            }
+            if idx == MAX_STRINGS {
+                break;
+            }
+            strings.push(self.format_string(pointer));
+        }
+        format!("[{}, ...]", strings.join(", "))
+    }
+
+    // what's known at entry, output buffers are filled in at exit
+    fn format_arg(&self, arg: Arg, value: u64, args: &[u64; 6]) -> String {
+        match arg {
+            Int => (value as i64).to_string(),
+            DirFd if value as i32 == libc::AT_FDCWD => "AT_FDCWD".to_owned(),
+            // fds are ints, the top half of the register's whatever was there
+            Fd | DirFd => (value as i32).to_string(),
+            Hex | OutBuf => format!("{:#x}", value),
+            Str => self.format_string(value),
+            StrArray => self.format_strings(value),
+            InBuf(count) => self.format_buffer(value, args[count]),
+            OpenFlags => open_flags(value),
+            Mode => format!("0{:o}", value),
+            Prot => prot(value),
+            MapFlags => map_flags(value),
+            Access => access(value),
+            Whence => match value as i32 {
+                libc::SEEK_SET => "SEEK_SET".to_owned(),
+                libc::SEEK_CUR => "SEEK_CUR".to_owned(),
+                libc::SEEK_END => "SEEK_END".to_owned(),
+                whence => whence.to_string(),
+            },
+            Sig => Signal::try_from(value as i32)
+                .map_or_else(|_| value.to_string(), |signal| signal.as_str().to_owned()),
+        }
+    }
+
+    fn format_entry(&self, number: u64, args: &[u64; 6]) -> Vec<String> {
+        match syscall_name(number).and_then(signature) {
+            Some(signature) => signature
+                .iter()
+                .zip(args)
+                .map(|(arg, value)| self.format_arg(*arg, *value, args))
+                .collect(),
+            // no idea how many it takes
+            None => args.iter().map(|arg| format!("{:#x}", arg)).collect(),
+        }
+    }
+
+    fn format_return(&self, pending: &mut PendingSyscall, ret: i64) -> String {
+        let name = syscall_name(pending.number).unwrap_or_default();
+        if (-4095..0).contains(&ret) {
+            let errno = Errno::from_raw(-ret as i32);
+            return format!("-1 {:?} ({})", errno, errno.desc());
+        }
+
+        if let Some(signature) = signature(name) {
+            for (text, (arg, value)) in pending
+                .text
+                .iter_mut()
+                .zip(signature.iter().zip(pending.args))
+            {
+                if *arg == OutBuf {
+                    *text = self.format_buffer(value, ret as u64);
+                }
+            }
+        }
+
+        match returns_address(name) {
+            true => format!("{:#x}", ret),
+            false => ret.to_string(),
+        }
+    }
+
+    // the thread's stopped at a syscall's entry or exit, it's resumed by the caller
+    pub(crate) fn handle_syscall_stop(&mut self, tid: Pid) -> Result<()> {
+        let Some(filter) = &self.syscall_trace else {
+            return Ok(());
+        };
+
+        let op = syscall_stop_op(tid)?;
+        if op == libc::PTRACE_SYSCALL_INFO_ENTRY {
+            let number = self.read_syscall_number()?;
+            if !filter.matches(number) {
+                return Ok(());
+            }
+            let args = self.read_syscall_args()?;
+            let pending = PendingSyscall {
+                number,
+                args,
+                text: self.format_entry(number, &args),
+            };
+
+            if syscall_name(number).is_some_and(never_returns) {
+                self.subscribers.emit(DebugEvent::Syscall(SyscallRecord {
+                    tid,
+                    number,
+                    args: pending.text,
+                    result: None,
+                }));
+                return Ok(());
+            }
+            self.threads.get_mut(&tid).unwrap().pending_syscall = Some(pending);
+        } else if op == libc::PTRACE_SYSCALL_INFO_EXIT {
+            let Some(mut pending) = self.threads.get_mut(&tid).unwrap().pending_syscall.take()
+            else {
+                return Ok(());
+            };
+            let ret = self.read_syscall_return()?;
+            let result = self.format_return(&mut pending, ret);
+            self.subscribers.emit(DebugEvent::Syscall(SyscallRecord {
+                tid,
+                number: pending.number,
+                args: pending.text,
+                result: Some(result),
+            }));
+        }
+
+        Ok(())
+    }
+}
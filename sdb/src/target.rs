@@ -0,0 +1,881 @@
+use std::collections::HashMap;
+use std::os::fd::OwnedFd;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use nix::{sys::signal::Signal, unistd::Pid};
+
+use num_traits::ToPrimitive;
+use tracing::warn;
+
+use crate::{
+    Auxv, DebugEvent, Elf, ElfCollection, Inferior, LaunchConfig, LineEntry, LogFormat, Process,
+    ProcessState, Radix, Redirect, RegisterId, RegisterValue, Result, SdbError, SourceLocation,
+    SourceMap, StopHandler, StopReason, VariableValue,
+    expr::Expr,
+    fatal::FatalBreakpoints,
+    register_info_by_dwarf,
+    rerun::PendingBreakpoint,
+    stoppoint_collection::{Stoppoint, StoppointId},
+    types::VirtAddr,
+};
+
+/// Everything known about where and why the process last stopped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StopInfo {
+    pub reason: StopReason,
+    pub thread: Pid,
+
+    // None once the process is gone
+    pub pc: Option<VirtAddr>,
+    pub function: Option<String>,
+    pub location: Option<SourceLocation>,
+}
+
+impl StopInfo {
+    #[inline]
+    pub fn breakpoint_site(&self) -> Option<StoppointId> {
+        self.reason.breakpoint_site
+    }
+
+    #[inline]
+    pub fn signal(&self) -> Option<Signal> {
+        self.reason.signal()
+    }
+}
+
+// enough to cover the longest x86-64 instruction
+const MAX_INSTRUCTION_SIZE: u64 = 15;
+
+/// A program being debugged
+///
+/// Owns the Process and layers symbol and debug info lookups on top of it
+#[derive(Debug)]
+pub struct Target {
+    pub(crate) process: Process,
+    pub(crate) modules: ElfCollection,
+    rendezvous_site: Option<StoppointId>,
+    pub(crate) fatal: FatalBreakpoints,
+
+    // breakpoints from an earlier run waiting on their library to be loaded
+    pub(crate) pending_breakpoints: Vec<PendingBreakpoint>,
+
+    source_map: SourceMap,
+
+    // how the process was launched, to launch it again
+    launch: Option<LaunchConfig>,
+
+    // step stops in functions without line info rather than running back out of them
+    step_into_no_debug: bool,
+}
+
+impl Target {
+    pub fn new(process: Process) -> Result<Self> {
+        let mut target = Self {
+            process,
+            modules: ElfCollection::default(),
+            rendezvous_site: None,
+            fatal: FatalBreakpoints::default(),
+            pending_breakpoints: Vec::new(),
+            source_map: SourceMap::default(),
+            launch: None,
+            step_into_no_debug: false,
+        };
+        if target.process.is_attached {
+            target.refresh_modules()?;
+        }
+        Ok(target)
+    }
+
+    pub fn launch(path: impl Into<String>, stdout_replacement: Option<OwnedFd>) -> Result<Self> {
+        let mut config = LaunchConfig::new(path);
+        if let Some(stdout_replacement) = stdout_replacement {
+            config = config.stdout(Redirect::Fd(stdout_replacement));
+        }
+        Self::launch_with(config)
+    }
+
+    /// Launches the process, keeping the config and duplicates of its fds for restart
+    pub fn launch_with(config: LaunchConfig) -> Result<Self> {
+        let saved = config.try_clone()?.absolute_path()?;
+        let mut target = Self::new(Process::launch_with(config)?)?;
+        target.launch = Some(saved);
+        Ok(target)
+    }
+
+    pub fn attach(pid: i32) -> Result<Self> {
+        Self::new(Process::attach(pid)?)
+    }
+
+    #[inline]
+    pub fn process(&self) -> &Process {
+        &self.process
+    }
+
+    #[inline]
+    pub fn process_mut(&mut self) -> &mut Process {
+        &mut self.process
+    }
+
+    #[inline]
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+
+    #[inline]
+    pub fn source_map_mut(&mut self) -> &mut SourceMap {
+        &mut self.source_map
+    }
+
+    #[inline]
+    pub fn modules(&self) -> &ElfCollection {
+        &self.modules
+    }
+
+    pub fn refresh_modules(&mut self) -> Result<()> {
+        let changes = self.modules.refresh(self.process.get_id().as_raw())?;
+        for path in changes.loaded {
+            self.process
+                .subscribers
+                .emit(DebugEvent::LibraryLoaded(path));
+        }
+        for path in changes.unloaded {
+            self.process
+                .subscribers
+                .emit(DebugEvent::LibraryUnloaded(path));
+        }
+
+        Ok(())
+    }
+
+    // keeps the loaded modules in sync as libraries are loaded and unloaded
+    // this is only installed when something needs it since it
+    // requires someone to be waiting on the process to make progress
+    pub(crate) fn track_modules(&mut self) -> Result<()> {
+        if !self.process.is_attached || self.rendezvous_site.is_some() {
+            return Ok(());
+        }
+
+        // the dynamic linker calls this whenever the set of loaded libraries changes
+        let rendezvous = self.modules.iter().find_map(|elf| {
+            elf.symbols_by_name("_dl_debug_state")
+                .next()
+                .map(|symbol| elf.symbol_address(symbol))
+        });
+
+        if let Some(address) = rendezvous {
+            let id = self.process.create_breakpoint_site(address, true)?;
+            self.process.enable_breakpoint_site(id)?;
+            self.rendezvous_site = Some(id);
+        }
+
+        Ok(())
+    }
+
+    /// The ELF of the executable itself, the one whose entry point the kernel started
+    ///
+    /// Found by where it was loaded rather than by path, so it's still found
+    /// if the file's been moved or deleted since
+    pub fn main_elf(&self) -> Option<&Elf> {
+        let entry = self.process.auxv().ok()?.entry()?;
+        self.modules
+            .get_elf_containing_address(entry)
+            .filter(|elf| elf.entry_point() == entry)
+            .or_else(|| {
+                let exe = procfs::process::Process::new(self.process.get_id().as_raw())
+                    .and_then(|process| process.exe())
+                    .ok()?;
+                self.modules.get_elf_by_path(exe)
+            })
+    }
+
+    pub fn wait_on_signal(&mut self) -> Result<StopReason> {
+        Process::wait_on_signal_with(self)
+    }
+
+    /// Returns None if the process hasn't stopped yet
+    pub fn wait_on_signal_nonblocking(&mut self) -> Result<Option<StopReason>> {
+        Process::wait_on_signal_nonblocking_with(self)
+    }
+
+    /// Waits at most `timeout` for the process to stop, [`SdbError::Timeout`] if it hasn't
+    pub fn wait_on_signal_timeout(&mut self, timeout: Duration) -> Result<StopReason> {
+        Process::wait_on_signal_timeout_with(self, timeout)
+    }
+
+    pub fn step_instruction(&mut self) -> Result<StopReason> {
+        Process::step_instruction_with(self)
+    }
+
+    /// Only stops at a breakpoint when the condition isn't 0, None stops every time
+    pub fn set_breakpoint_condition(
+        &mut self,
+        id: StoppointId,
+        condition: Option<Expr>,
+    ) -> Result<()> {
+        self.process
+            .breakpoint_sites
+            .get_by_id_mut(id)?
+            .set_condition(condition);
+        Ok(())
+    }
+
+    /// Makes a breakpoint print instead of stopping, None makes it stop again
+    ///
+    /// The message goes to event subscribers as a [`DebugEvent::Logged`]
+    pub fn set_breakpoint_log(&mut self, id: StoppointId, log: Option<LogFormat>) -> Result<()> {
+        self.process
+            .breakpoint_sites
+            .get_by_id_mut(id)?
+            .set_log(log);
+        Ok(())
+    }
+
+    /// Memory as the program sees it, code and constants nothing's written over come from the
+    /// module's file, which already has the bytes any breakpoints have replaced
+    pub fn read_memory_without_traps(&self, address: VirtAddr, amount: usize) -> Result<Vec<u8>> {
+        match self.read_only_memory(address, amount) {
+            Some(memory) => Ok(memory),
+            None => self.process.read_memory_without_traps(address, amount),
+        }
+    }
+
+    fn read_only_memory(&self, address: VirtAddr, amount: usize) -> Option<Vec<u8>> {
+        let memory = self.modules.read_only_bytes(address, amount)?;
+
+        let high = address + amount as i64;
+        if self.process.is_patched(address..high) {
+            return None;
+        }
+
+        // a breakpoint that saved something else means the file isn't what's mapped
+        if self
+            .process
+            .breakpoint_sites()
+            .get_in_region(address, high)
+            .filter(|site| site.is_enabled())
+            .any(|site| {
+                memory[(site.address().addr() - address.addr()) as usize] != site.saved_data()
+            })
+        {
+            return None;
+        }
+
+        Some(memory.to_vec())
+    }
+
+    pub fn function_address(&self, name: impl AsRef<str>) -> Option<VirtAddr> {
+        let (elf, symbol) = self.modules().find_function(name)?;
+        Some(elf.symbol_address(symbol))
+    }
+
+    /// Where a global variable lives and how big it is
+    pub fn variable_address(&self, name: impl AsRef<str>) -> Option<(VirtAddr, usize)> {
+        let (elf, symbol) = self.modules().find_variable(name)?;
+        Some((elf.symbol_address(symbol), symbol.size as usize))
+    }
+
+    /// Breaks once the function's prologue has run, so its parameters and locals can be read
+    pub fn create_function_breakpoint(&mut self, name: impl AsRef<str>) -> Result<StoppointId> {
+        let name = name.as_ref();
+
+        // the function may be in a library that's been loaded since we last looked
+        let address = match self.function_address(name) {
+            Some(address) => address,
+            None => {
+                self.refresh_modules()?;
+                self.function_address(name)
+                    .ok_or_else(|| SdbError::Stoppoint(format!("No function named {}", name)))?
+            }
+        };
+
+        let id = self
+            .process
+            .create_breakpoint_site(self.past_prologue(address), false)?;
+        self.process.enable_breakpoint_site(id)?;
+        Ok(id)
+    }
+
+    /// Where a function with debug info has its frame and locals set up, the entry for one without
+    pub fn past_prologue(&self, entry: VirtAddr) -> VirtAddr {
+        let Some((elf, function)) = self.function_at(entry) else {
+            return entry;
+        };
+        if elf.virtual_address(function.low_pc) != entry {
+            return entry;
+        }
+
+        elf.dwarf().map_or(entry, |dwarf| {
+            elf.virtual_address(dwarf.prologue_end(function))
+        })
+    }
+
+    /// Breaks on the first statement of a source line
+    ///
+    /// The file only has to match the end of the path in the debug info
+    pub fn create_line_breakpoint(
+        &mut self,
+        file: impl AsRef<Path>,
+        line: u64,
+    ) -> Result<StoppointId> {
+        let file = file.as_ref();
+        let address = self
+            .modules
+            .iter()
+            .filter_map(|elf| {
+                let entry = elf
+                    .dwarf()?
+                    .line_table()
+                    .iter()
+                    .filter(|entry| {
+                        entry.is_stmt
+                            && !entry.end_sequence
+                            && entry.line == line
+                            && entry.file.ends_with(file)
+                    })
+                    .min_by_key(|entry| entry.address)?;
+                Some(elf.virtual_address(entry.address))
+            })
+            .min()
+            .ok_or_else(|| {
+                SdbError::Stoppoint(format!("No code at {}:{}", file.display(), line))
+            })?;
+
+        let id = self.process.create_breakpoint_site(address, false)?;
+        self.process.enable_breakpoint_site(id)?;
+        Ok(id)
+    }
+
+    pub fn stop_info(&self) -> Result<StopInfo> {
+        let reason = self
+            .process
+            .stop_reason()
+            .cloned()
+            .ok_or_else(|| SdbError::Other("Process hasn't stopped yet".to_owned()))?;
+
+        let pc = match reason.reason {
+            ProcessState::Stopped => Some(self.process.get_pc()?),
+            _ => None,
+        };
+
+        Ok(StopInfo {
+            reason,
+            thread: self.process.current_thread(),
+            pc,
+            function: pc.and_then(|pc| self.function_name_at(pc)),
+            location: pc.and_then(|pc| self.source_location_at(pc)),
+        })
+    }
+
+    fn read_register(&self, id: RegisterId) -> Result<u64> {
+        Ok(self.process.read_register_by_id(id)?.to_u64().unwrap())
+    }
+
+    // runs until the given address is hit, or something else stops the process
+    fn run_until(&mut self, address: VirtAddr) -> Result<StopReason> {
+        // there may already be a breakpoint there, one only tracing allocations is taken over
+        let temporary = match self.process.breakpoint_sites().contains_address(address) {
+            true => self.process.take_allocation_return_site(address),
+            false => {
+                let id = self.process.create_breakpoint_site(address, true)?;
+                self.process.enable_breakpoint_site(id)?;
+                Some(id)
+            }
+        };
+
+        self.process.resume()?;
+        let mut reason = self.wait_on_signal()?;
+
+        if let Some(id) = temporary
+            && reason.reason == ProcessState::Stopped
+        {
+            self.process.remove_breakpoint_site(id)?;
+
+            // the site is gone, so don't report it
+            if reason.breakpoint_site == Some(id) {
+                reason.breakpoint_site = None;
+                let tid = self.process.current_thread();
+                self.process.record_thread_stop(tid, &reason);
+            }
+        }
+
+        Ok(reason)
+    }
+
+    /// Kills the process if it's still there and launches it again as it was launched
+    ///
+    /// Breakpoints are set again where they are in their files, wherever the files are loaded
+    /// this time, and keep their ids. The new process is stopped at its entry,
+    /// or run to main if `to_main` and there is one
+    pub fn restart(&mut self, to_main: bool) -> Result<StopReason> {
+        let config = self
+            .launch
+            .as_ref()
+            .ok_or_else(|| SdbError::Other("Only a launched process can be restarted".to_owned()))?
+            .try_clone()?;
+
+        if !matches!(
+            self.process.get_state(),
+            ProcessState::Exited | ProcessState::Terminated
+        ) {
+            self.process.kill()?;
+        }
+
+        // the old run's modules say where the breakpoints are in their files
+        let pending = self.relocatable_breakpoints();
+        let fatal = self.fatal.functions().to_vec();
+
+        let mut process = Process::launch_with(config)?;
+        self.modules = ElfCollection::default();
+        self.modules.refresh(process.get_id().as_raw())?;
+        process.take_over(&mut self.process)?;
+        self.process = process;
+
+        self.rendezvous_site = None;
+        self.fatal = FatalBreakpoints::default();
+        self.pending_breakpoints = pending;
+        if !self.pending_breakpoints.is_empty() {
+            self.track_modules()?;
+        }
+        self.resolve_pending_breakpoints()?;
+
+        // after the breakpoints, a function they're on is left to them
+        if !fatal.is_empty() {
+            self.catch_fatal(&fatal)?;
+        }
+
+        let main = self
+            .main_elf()
+            .and_then(|elf| elf.symbols_by_name("main").next().map(|main| (elf, main)))
+            .map(|(elf, main)| elf.symbol_address(main));
+        match main {
+            Some(main) if to_main => self.run_until(self.past_prologue(main)),
+            _ => self
+                .process
+                .stop_reason()
+                .cloned()
+                .ok_or_else(|| SdbError::Other("Process hasn't stopped yet".to_owned())),
+        }
+    }
+
+    /// Runs the process on the terminal at `path` from the next restart on
+    pub fn set_inferior_tty(&mut self, path: impl Into<PathBuf>) -> Result<()> {
+        let config = self
+            .launch
+            .as_ref()
+            .ok_or_else(|| SdbError::Other("Only a launched process has a terminal".to_owned()))?
+            .try_clone()?
+            .tty(path)?;
+        self.launch = Some(config);
+        Ok(())
+    }
+
+    /// Starts the process in `dir` from the next restart on, a process that wasn't launched
+    /// can't be restarted so it's left as it is
+    pub fn set_working_directory(&mut self, dir: impl Into<PathBuf>) {
+        self.launch = self.launch.take().map(|config| config.cwd(dir));
+    }
+
+    /// Where the process is started, the debugger's working directory if None
+    #[inline]
+    pub fn working_directory(&self) -> Option<&Path> {
+        self.launch.as_ref()?.working_directory()
+    }
+
+    /// The terminal the process is launched on, if it isn't the debugger's
+    #[inline]
+    pub fn inferior_tty(&self) -> Option<&Path> {
+        self.launch.as_ref()?.tty_path()
+    }
+
+    #[inline]
+    pub fn step_into_no_debug(&self) -> bool {
+        self.step_into_no_debug
+    }
+
+    /// Whether step stops at the start of a function without line info, like libc's,
+    /// rather than running until it returns. PLT stubs and the dynamic linker are stepped
+    /// through either way
+    #[inline]
+    pub fn set_step_into_no_debug(&mut self, step_into: bool) {
+        self.step_into_no_debug = step_into;
+    }
+
+    /// Runs until the current function returns
+    pub fn step_out(&mut self) -> Result<StopReason> {
+        let frame_pointer = self.read_register(RegisterId::rbp)?;
+        let return_address = self.read_u64(VirtAddr::new(frame_pointer) + 8)?;
+
+        self.run_until(VirtAddr::new(return_address))
+    }
+
+    /// Runs until the current function returns, with what it returned
+    ///
+    /// The value is None for void, for a function without debug info,
+    /// or if something else stopped the process first
+    pub fn finish(&mut self) -> Result<(StopReason, Option<VariableValue>)> {
+        let function = self
+            .function_at(self.process.get_pc()?)
+            .map(|(elf, function)| (elf.path().to_path_buf(), function.low_pc));
+        let frame_pointer = self.read_register(RegisterId::rbp)?;
+
+        let reason = self.step_out()?;
+
+        // the return address and saved rbp have been popped off the frame
+        let returned = reason.reason == ProcessState::Stopped
+            && reason.breakpoint_site.is_none()
+            && self.read_register(RegisterId::rsp)? == frame_pointer + 16;
+        let Some((path, low_pc)) = function.filter(|_| returned) else {
+            return Ok((reason, None));
+        };
+
+        let elf = self.modules().get_elf_by_path(path);
+        let function =
+            elf.and_then(|elf| Some((elf, elf.dwarf()?.function_containing_address(low_pc)?)));
+        let value = match function {
+            Some((elf, function)) => {
+                crate::variables::return_value(self, elf, function, Radix::Decimal)?
+            }
+            None => None,
+        };
+        Ok((reason, value))
+    }
+
+    /// Pops the current frame without running the rest of it, as though it returned value,
+    /// and returns where the caller carries on from
+    ///
+    /// The caller's registers are put back as the unwind info says, or from the frame pointer
+    /// without any. The registers are journaled, so undo puts the frame back
+    pub fn return_now(&mut self, value: Option<u64>) -> Result<VirtAddr> {
+        let pc = self.process.get_pc()?;
+        let row = self
+            .modules
+            .get_elf_containing_address(pc)
+            .and_then(|elf| elf.unwind_row(elf.file_address(pc)?));
+
+        let mut registers = Vec::new();
+        let (cfa, return_address) = match row {
+            Some(row) => {
+                let (register, offset) = row.cfa;
+                let base = register_info_by_dwarf(register as i32)
+                    .ok_or_else(|| SdbError::Other(format!("No DWARF register {}", register)))?;
+                let cfa = self.read_register(base.id())?.wrapping_add_signed(offset);
+
+                let mut return_address = None;
+                for (register, offset) in row.saved {
+                    let saved = self.read_u64(VirtAddr::new(cfa.wrapping_add_signed(offset)))?;
+                    match register_info_by_dwarf(register as i32) {
+                        Some(info) if info.id() == RegisterId::rip => return_address = Some(saved),
+                        Some(info) => registers.push((info.id(), saved)),
+                        None => (),
+                    }
+                }
+                // the return address is just under the frame when nothing says otherwise
+                let return_address = match return_address {
+                    Some(address) => address,
+                    None => self.read_u64(VirtAddr::new(cfa - 8))?,
+                };
+                (cfa, return_address)
+            }
+            None => {
+                let frame_pointer = self.read_register(RegisterId::rbp)?;
+                registers.push((
+                    RegisterId::rbp,
+                    self.read_u64(VirtAddr::new(frame_pointer))?,
+                ));
+                (
+                    frame_pointer + 16,
+                    self.read_u64(VirtAddr::new(frame_pointer) + 8)?,
+                )
+            }
+        };
+
+        registers.push((RegisterId::rsp, cfa));
+        registers.push((RegisterId::rip, return_address));
+        if let Some(value) = value {
+            registers.push((RegisterId::rax, value));
+        }
+        self.process.write_registers_journaled(
+            registers
+                .into_iter()
+                .map(|(id, value)| (id, RegisterValue::from(value))),
+        )?;
+
+        // it's not at the breakpoint it stopped at anymore
+        if let Some(mut reason) = self.process.stop_reason().cloned() {
+            reason.breakpoint_site = None;
+            let tid = self.process.current_thread();
+            self.process.record_thread_stop(tid, &reason);
+        }
+
+        Ok(VirtAddr::new(return_address))
+    }
+
+    // a call pushes a return address just past the instruction it was made from
+    fn called_from(&self, pc: VirtAddr, stack_pointer: u64) -> Result<Option<VirtAddr>> {
+        let new_stack_pointer = self.read_register(RegisterId::rsp)?;
+        if new_stack_pointer + 8 != stack_pointer {
+            return Ok(None);
+        }
+
+        let return_address = self.read_u64(VirtAddr::new(new_stack_pointer))?;
+        Ok(
+            (return_address > pc.addr() && return_address <= pc.addr() + MAX_INSTRUCTION_SIZE)
+                .then(|| VirtAddr::new(return_address)),
+        )
+    }
+
+    // PLT stubs and the dynamic linker resolving where they go
+    fn is_trampoline(&self, pc: VirtAddr) -> bool {
+        let Some(elf) = self.modules().get_elf_containing_address(pc) else {
+            return false;
+        };
+        elf.is_plt(pc)
+            || self
+                .process
+                .auxv()
+                .is_ok_and(|auxv| auxv.interpreter_base() == Some(elf.load_bias()))
+    }
+
+    fn is_function_entry(&self, pc: VirtAddr) -> bool {
+        let modules = self.modules();
+        modules
+            .get_elf_containing_address(pc)
+            .is_some_and(|elf| elf.is_plt(pc))
+            || modules
+                .get_elf_containing_symbol_address(pc)
+                .and_then(|elf| Some(elf.symbol_address(elf.symbol_containing_address(pc)?)))
+                == Some(pc)
+    }
+
+    // single steps, going through PLT stubs and the dynamic linker into the function they
+    // resolve, and running back out of a function without line info unless stepping into those
+    fn step_line_instruction(&mut self) -> Result<StopReason> {
+        let pc = self.process.get_pc()?;
+        let stack_pointer = self.read_register(RegisterId::rsp)?;
+
+        let mut reason = self.step_instruction()?;
+        if reason.reason != ProcessState::Stopped
+            || self.line_entry_at(self.process.get_pc()?).is_some()
+        {
+            return Ok(reason);
+        }
+
+        // called, or jumped to like a tail call, the return address is on top either way
+        if self.called_from(pc, stack_pointer)?.is_none()
+            && !self.is_function_entry(self.process.get_pc()?)
+        {
+            return Ok(reason);
+        }
+        let entry_stack_pointer = self.read_register(RegisterId::rsp)?;
+
+        // the dynamic linker's own calls are run over, it's thousands of instructions
+        while self.is_trampoline(self.process.get_pc()?) {
+            let pc = self.process.get_pc()?;
+            let stack_pointer = self.read_register(RegisterId::rsp)?;
+
+            reason = self.step_instruction()?;
+            if reason.reason != ProcessState::Stopped {
+                return Ok(reason);
+            }
+
+            if let Some(return_address) = self.called_from(pc, stack_pointer)? {
+                reason = self.run_until(return_address)?;
+                if reason.reason != ProcessState::Stopped
+                    || self.process.get_pc()? != return_address
+                {
+                    return Ok(reason);
+                }
+            }
+        }
+
+        if self.step_into_no_debug
+            || self.line_entry_at(self.process.get_pc()?).is_some()
+            || self.read_register(RegisterId::rsp)? != entry_stack_pointer
+        {
+            return Ok(reason);
+        }
+
+        let return_address = self.read_u64(VirtAddr::new(entry_stack_pointer))?;
+        self.run_until(VirtAddr::new(return_address))
+    }
+
+    fn is_new_line(&self, start: Option<&LineEntry>) -> Result<bool> {
+        let pc = self.process.get_pc()?;
+        let Some(entry) = self.line_entry_at(pc) else {
+            return Ok(false);
+        };
+
+        // stop at the start of a statement, not partway through one
+        Ok(entry.is_stmt
+            && self
+                .modules
+                .get_elf_containing_address(pc)
+                .map(|elf| elf.virtual_address(entry.address))
+                == Some(pc)
+            && start.is_none_or(|start| start.line != entry.line || start.file != entry.file))
+    }
+
+    // a function's first line is its prologue, the body starts on the next one
+    fn skip_prologue(&mut self) -> Result<Option<StopReason>> {
+        let pc = self.process.get_pc()?;
+        let Some((elf, function)) = self.function_at(pc) else {
+            return Ok(None);
+        };
+        if elf.virtual_address(function.low_pc) != pc {
+            return Ok(None);
+        }
+
+        let start = self.line_entry_at(pc).cloned();
+        loop {
+            let reason = self.step_line_instruction()?;
+            if reason.reason != ProcessState::Stopped || self.is_new_line(start.as_ref())? {
+                return Ok(Some(reason));
+            }
+        }
+    }
+
+    /// Steps to the next source line, entering any functions that are called
+    pub fn step_in(&mut self) -> Result<StopReason> {
+        let start = self.line_entry_at(self.process.get_pc()?).cloned();
+        loop {
+            let reason = self.step_line_instruction()?;
+            if reason.reason != ProcessState::Stopped {
+                return Ok(reason);
+            }
+
+            if self.line_entry_at(self.process.get_pc()?).is_none() {
+                // returned out of the function we started in
+                return Ok(reason);
+            }
+
+            if self.is_new_line(start.as_ref())? {
+                return Ok(self.skip_prologue()?.unwrap_or(reason));
+            }
+        }
+    }
+
+    /// Steps to the next source line, running over any functions that are called
+    pub fn step_over(&mut self) -> Result<StopReason> {
+        let start = self.line_entry_at(self.process.get_pc()?).cloned();
+        loop {
+            let pc = self.process.get_pc()?;
+            let stack_pointer = self.read_register(RegisterId::rsp)?;
+
+            let reason = self.step_instruction()?;
+            if reason.reason != ProcessState::Stopped {
+                return Ok(reason);
+            }
+
+            if let Some(return_address) = self.called_from(pc, stack_pointer)? {
+                let reason = self.run_until(return_address)?;
+                if reason.reason != ProcessState::Stopped
+                    || self.process.get_pc()? != return_address
+                {
+                    return Ok(reason);
+                }
+            }
+
+            if self.line_entry_at(self.process.get_pc()?).is_none() {
+                // returned out of the function we started in
+                return Ok(reason);
+            }
+
+            if self.is_new_line(start.as_ref())? {
+                return Ok(reason);
+            }
+        }
+    }
+}
+
+impl Inferior for Target {
+    fn read_register_by_id(&self, id: RegisterId) -> Result<RegisterValue> {
+        self.process.read_register_by_id(id)
+    }
+
+    fn read_memory(&self, address: VirtAddr, amount: usize) -> Result<Vec<u8>> {
+        Target::read_memory_without_traps(self, address, amount)
+    }
+
+    fn modules(&self) -> &ElfCollection {
+        &self.modules
+    }
+
+    fn auxv(&self) -> Result<Auxv> {
+        self.process.auxv()
+    }
+}
+
+impl StopHandler for Target {
+    #[inline]
+    fn process(&self) -> &Process {
+        &self.process
+    }
+
+    #[inline]
+    fn process_mut(&mut self) -> &mut Process {
+        &mut self.process
+    }
+
+    fn handle_internal_stop(&mut self, reason: &StopReason) -> Result<bool> {
+        let Some(id) = reason.breakpoint_site else {
+            return Ok(false);
+        };
+
+        if Some(id) == self.rendezvous_site {
+            self.refresh_modules()?;
+            self.resolve_pending_breakpoints()?;
+            self.resolve_fatal_breakpoints()?;
+            self.resolve_allocation_breakpoints()?;
+            return Ok(true);
+        }
+
+        if self.process.is_internal_stop(id)? {
+            return Ok(true);
+        }
+
+        // a condition that doesn't hold isn't a hit, one that can't be evaluated stops to say so
+        let site = self.process.breakpoint_sites.get_by_id(id)?;
+        if let Some(condition) = site.condition().cloned() {
+            match condition.evaluate(self) {
+                Ok(0) => return Ok(true),
+                Ok(_) => (),
+                Err(err) => {
+                    warn!("Breakpoint {} condition {} failed: {}", id, condition, err);
+                    return Ok(false);
+                }
+            }
+        }
+
+        if !self.process.record_breakpoint_hit(id)? {
+            return Ok(true);
+        }
+
+        let Some(log) = self.process.breakpoint_sites.get_by_id(id)?.log().cloned() else {
+            return Ok(false);
+        };
+        let message = self.format_log(&log);
+        self.process
+            .subscribers
+            .emit(DebugEvent::Logged { id, message });
+        Ok(true)
+    }
+
+    // the same files can be mapped somewhere else now
+    fn handle_exec(&mut self) -> Result<()> {
+        let tracking = self.rendezvous_site.take().is_some();
+
+        for path in self.modules.sync(HashMap::new()).unloaded {
+            self.process
+                .subscribers
+                .emit(DebugEvent::LibraryUnloaded(path));
+        }
+        self.refresh_modules()?;
+        if tracking {
+            self.track_modules()?;
+            self.resolve_fatal_breakpoints()?;
+        }
+
+        Ok(())
+    }
+}
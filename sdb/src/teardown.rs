@@ -0,0 +1,146 @@
+use nix::sys::{ptrace, signal, wait};
+use tracing::trace;
+
+use crate::{Process, ProcessState, Result, SdbError, Stoppoint};
+
+impl Process {
+    pub(crate) fn check_alive(&self) -> Result<()> {
+        match self.get_state() {
+            ProcessState::Exited | ProcessState::Terminated => {
+                Err(SdbError::Other("The process has already ended".to_owned()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Lets the process run on without the debugger, every int3 is taken out first
+    pub fn detach(&mut self) -> Result<()> {
+        if !self.is_attached {
+            return Err(SdbError::Other("Not attached to the process".to_owned()));
+        }
+        self.check_alive()?;
+
+        // whatever's left undone can't be retried once the threads are gone
+        let result = self.teardown();
+        self.is_attached = false;
+        self.terminate_on_drop = false;
+        result
+    }
+
+    /// Kills the process and reaps it
+    pub fn kill(&mut self) -> Result<()> {
+        self.check_alive()?;
+
+        // a caught fork's child would be left stopped
+        if self.is_attached {
+            self.release_forked_child()?;
+        }
+
+        trace!("Killing process {} ...", self.pid);
+        self.shared.signal(signal::SIGKILL)?;
+
+        // the leader's status only comes once the others have been reaped
+        let mut tids = self.threads.keys().copied().collect::<Vec<_>>();
+        tids.retain(|tid| *tid != self.pid);
+        tids.push(self.pid);
+        for tid in tids {
+            match wait::waitpid(tid, Some(wait::WaitPidFlag::__WALL)) {
+                Ok(status) if tid == self.pid => self.end_status = Some(status),
+                Ok(_) | Err(nix::errno::Errno::ECHILD) => (),
+                Err(errno) => return Err(SdbError::waitpid(Some(tid))(errno)),
+            }
+        }
+        self.forget_stashed_statuses();
+        // reaped by someone else, it was still our SIGKILL
+        self.end_status
+            .get_or_insert(wait::WaitStatus::Signaled(self.pid, signal::SIGKILL, false));
+
+        self.is_attached = false;
+        self.terminate_on_drop = false;
+        self.set_state(ProcessState::Terminated);
+
+        Ok(())
+    }
+
+    // every step runs even if an earlier one failed, the first error is returned
+    pub(crate) fn teardown(&mut self) -> Result<()> {
+        // have to stop the process before detaching
+        trace!("Stopping process ...");
+        let stopped = self.stop_all_threads().into_result();
+
+        let results = [
+            stopped,
+            // a caught fork's child has our int3s too
+            self.release_forked_child(),
+            // leaving int3s behind would crash the process later
+            self.restore_all_sites(),
+            self.detach_threads(),
+        ];
+        results.into_iter().collect()
+    }
+
+    // puts back every byte a breakpoint or hook wrote over, then reads them back
+    // a trap left behind would kill the process the next time it got there
+    fn restore_all_sites(&mut self) -> Result<()> {
+        let mut patched = self
+            .breakpoint_sites
+            .iter()
+            .filter(|site| site.is_enabled())
+            .map(|site| (site.address(), vec![site.saved_data()]))
+            .collect::<Vec<_>>();
+        patched.extend(
+            self.function_hooks
+                .iter()
+                .filter(|hook| hook.is_enabled())
+                .map(|hook| (hook.address(), hook.saved_data().to_vec())),
+        );
+
+        let mut results = Vec::new();
+        for site in self.breakpoint_sites.iter_mut() {
+            results.push(site.disable(self.current_thread));
+        }
+
+        let hooks = self
+            .function_hooks
+            .iter()
+            .map(|hook| hook.id())
+            .collect::<Vec<_>>();
+        for id in hooks {
+            results.push(self.disable_function_hook(id));
+        }
+
+        for (address, saved) in patched {
+            // memory that's been unmapped since doesn't need putting back
+            let Ok(memory) = self.read_memory(address, saved.len()) else {
+                continue;
+            };
+            if memory != saved {
+                results.push(Err(SdbError::Stoppoint(format!(
+                    "Couldn't restore the original code at {}",
+                    address
+                ))));
+            }
+        }
+
+        results.into_iter().collect()
+    }
+
+    fn detach_threads(&mut self) -> Result<()> {
+        trace!("Detaching and resuming process ...");
+        let mut results = self
+            .threads
+            .keys()
+            .map(|tid| ptrace::detach(*tid, None).map_err(SdbError::ptrace("detach", *tid)))
+            .collect::<Vec<_>>();
+        if !self.is_seized() {
+            // the process was stopped with SIGSTOP
+            results.push(
+                signal::kill(self.pid, signal::SIGCONT)
+                    .map_err(SdbError::signal(signal::SIGCONT, self.pid)),
+            );
+        }
+        self.forget_stashed_statuses();
+
+        results.into_iter().collect()
+    }
+}
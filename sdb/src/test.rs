@@ -1,7 +1,84 @@
 #![cfg(test)]
 
-use nix::{sys::signal, unistd::Pid};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::Command;
+
+use nix::{libc, sys::signal, unistd::Pid};
+
+use crate::{StoppointId, Target};
 
 pub fn process_exists(pid: Pid) -> nix::Result<()> {
-    Ok(signal::kill(pid, None)?)
+    signal::kill(pid, None)
+}
+
+pub fn break_at_function(target: &mut Target, name: &str) -> StoppointId {
+    let (elf, symbol) = target.modules().find_function(name).unwrap();
+    let address = elf.symbol_address(symbol);
+
+    let process = target.process_mut();
+    let id = process.create_breakpoint_site(address, false).unwrap();
+    process.enable_breakpoint_site(id).unwrap();
+    id
+}
+
+// None if the kernel doesn't write cores to the crashing process's directory
+pub fn dump_core(path: &str, name: &str) -> Option<PathBuf> {
+    let pattern = std::fs::read_to_string("/proc/sys/kernel/core_pattern").ok()?;
+    let pattern = pattern.trim();
+    if pattern.contains(['|', '/', '%']) {
+        return None;
+    }
+    let uses_pid = std::fs::read_to_string("/proc/sys/kernel/core_uses_pid")
+        .is_ok_and(|uses_pid| uses_pid.trim() == "1");
+
+    let dir = std::env::temp_dir().join(format!("sdb-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut command = Command::new(std::fs::canonicalize(path).unwrap());
+    command.current_dir(&dir);
+    unsafe {
+        command.pre_exec(|| {
+            let limit = libc::rlimit {
+                rlim_cur: libc::RLIM_INFINITY,
+                rlim_max: libc::RLIM_INFINITY,
+            };
+            libc::setrlimit(libc::RLIMIT_CORE, &limit);
+            Ok(())
+        });
+    }
+
+    let mut child = command.spawn().unwrap();
+    let pid = child.id();
+    child.wait().unwrap();
+
+    let core = match uses_pid {
+        true => dir.join(format!("{}.{}", pattern, pid)),
+        false => dir.join(pattern),
+    };
+    core.exists().then_some(core)
+}
+
+// sends a gdb remote packet and waits for the reply, skipping acks
+pub fn gdb_exchange(stream: &mut TcpStream, packet: &str) -> String {
+    let checksum = packet.bytes().fold(0_u8, u8::wrapping_add);
+    write!(stream, "${}#{:02x}", packet, checksum).unwrap();
+
+    // byte at a time, anything buffered past the reply would be lost
+    let mut next = || {
+        let mut byte = [0];
+        stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    };
+    while next() != b'$' {}
+    let reply = std::iter::from_fn(|| Some(next()))
+        .take_while(|byte| *byte != b'#')
+        .collect();
+    next();
+    next();
+
+    stream.write_all(b"+").unwrap();
+    String::from_utf8(reply).unwrap()
 }
@@ -0,0 +1,694 @@
+use std::cell::RefCell;
+use std::sync::Mutex;
+
+use nix::{
+    errno::Errno,
+    libc,
+    sys::{ptrace, signal::Signal, wait},
+    unistd::Pid,
+};
+use tracing::trace;
+
+use crate::{
+    DebugEvent, Process, ProcessState, Result, SdbError, StopReason, registers::Registers,
+    syscall_trace::PendingSyscall,
+};
+
+thread_local! {
+    // wait statuses for tracees that belong to another Process on this thread
+    // (or for new threads whose clone event we haven't seen yet)
+    static STASHED_STATUSES: RefCell<Vec<wait::WaitStatus>> = const { RefCell::new(Vec::new()) };
+}
+
+#[derive(Debug)]
+pub struct ThreadState {
+    tid: Pid,
+    state: ProcessState,
+    pub(crate) registers: Mutex<Registers>,
+
+    // a stop we asked for that hasn't been reported yet
+    pending_stop: bool,
+
+    // delivered to the thread on the next resume
+    pub(crate) pending_signal: Option<Signal>,
+
+    // rewound onto a breakpoint it hit while others were being reported,
+    // cleared once it's reported a stop of its own
+    pub(crate) rehit_breakpoint: bool,
+
+    reason: Option<StopReason>,
+
+    // entered while syscalls are traced, reported when it returns
+    pub(crate) pending_syscall: Option<PendingSyscall>,
+}
+
+impl ThreadState {
+    pub(crate) fn new(tid: Pid, state: ProcessState) -> Self {
+        Self {
+            tid,
+            state,
+            registers: Mutex::new(Registers::new()),
+            pending_stop: false,
+            pending_signal: None,
+            rehit_breakpoint: false,
+            reason: None,
+            pending_syscall: None,
+        }
+    }
+
+    #[inline]
+    pub fn tid(&self) -> Pid {
+        self.tid
+    }
+
+    #[inline]
+    pub fn state(&self) -> ProcessState {
+        self.state
+    }
+
+    // why the thread last stopped
+    #[inline]
+    pub fn reason(&self) -> Option<&StopReason> {
+        self.reason.as_ref()
+    }
+
+    /// The name the thread has given itself, or the program's if it hasn't
+    pub fn name(&self) -> Option<String> {
+        let comm = std::fs::read_to_string(format!("/proc/{}/comm", self.tid)).ok()?;
+        Some(comm.trim_end_matches('\n').to_owned())
+    }
+
+    /// What the kernel says the thread is doing, like "sleeping" or "tracing stop"
+    pub fn kernel_state(&self) -> Option<&'static str> {
+        let stat = procfs::process::Process::new(self.tid.as_raw())
+            .ok()?
+            .stat()
+            .ok()?;
+        Some(match stat.state {
+            'R' => "running",
+            'S' => "sleeping",
+            'D' => "disk sleep",
+            'T' => "stopped",
+            't' => "tracing stop",
+            'Z' => "zombie",
+            'X' => "dead",
+            'I' => "idle",
+            'P' => "parked",
+            _ => "unknown",
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum ThreadOutcome {
+    Succeeded,
+    // the thread exited out from under the operation
+    Vanished,
+    Failed(SdbError),
+}
+
+/// Per-thread results of an operation applied to every thread
+#[derive(Debug, Default)]
+pub struct ThreadOpReport {
+    pub outcomes: Vec<(Pid, ThreadOutcome)>,
+}
+
+impl std::fmt::Display for ThreadOpReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let failures = self
+            .failures()
+            .map(|(tid, err)| format!("{}: {}", tid, err))
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "{} of {} threads failed ({})",
+            failures.len(),
+            self.outcomes.len(),
+            failures.join(", ")
+        )
+    }
+}
+
+impl ThreadOpReport {
+    #[inline]
+    pub fn is_success(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = (Pid, &SdbError)> {
+        self.outcomes
+            .iter()
+            .filter_map(|(tid, outcome)| match outcome {
+                ThreadOutcome::Failed(err) => Some((*tid, err)),
+                _ => None,
+            })
+    }
+
+    // a single failed thread is just reported as its own error
+    pub fn into_result(mut self) -> Result<()> {
+        if self.is_success() {
+            return Ok(());
+        }
+
+        if self.outcomes.len() == 1
+            && let Some((_, ThreadOutcome::Failed(err))) = self.outcomes.pop()
+        {
+            return Err(err);
+        }
+
+        Err(SdbError::ThreadOp(self))
+    }
+}
+
+// waits for a specific thread, including any status wait_any already collected
+pub(crate) fn wait_for_thread(tid: Pid) -> Result<wait::WaitStatus> {
+    let stashed = STASHED_STATUSES.with_borrow_mut(|stashed| {
+        let idx = stashed
+            .iter()
+            .position(|status| status.pid() == Some(tid))?;
+        Some(stashed.remove(idx))
+    });
+    if let Some(status) = stashed {
+        return Ok(status);
+    }
+
+    wait::waitpid(tid, Some(wait::WaitPidFlag::__WALL)).map_err(SdbError::waitpid(Some(tid)))
+}
+
+// SIGSTOP for traced threads, PTRACE_EVENT_STOP for seized ones
+// (new threads of a seized process also start with one)
+fn is_requested_stop(status: wait::WaitStatus) -> bool {
+    matches!(
+        status,
+        wait::WaitStatus::Stopped(_, Signal::SIGSTOP)
+            | wait::WaitStatus::PtraceEvent(_, Signal::SIGTRAP, libc::PTRACE_EVENT_STOP)
+    )
+}
+
+fn tgkill(pid: Pid, tid: Pid, signal: Signal) -> nix::Result<()> {
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_tgkill,
+            pid.as_raw(),
+            tid.as_raw(),
+            signal as libc::c_int,
+        )
+    };
+    Errno::result(ret).map(drop)
+}
+
+impl Process {
+    #[inline]
+    pub fn threads(&self) -> impl Iterator<Item = &ThreadState> {
+        self.threads.values()
+    }
+
+    #[inline]
+    pub fn current_thread(&self) -> Pid {
+        self.current_thread
+    }
+
+    // why the current thread last stopped
+    #[inline]
+    pub fn stop_reason(&self) -> Option<&StopReason> {
+        self.current_thread_state().reason()
+    }
+
+    #[inline]
+    pub fn scheduler_locking(&self) -> bool {
+        self.scheduler_locking
+    }
+
+    /// Whether resuming only continues the current thread, leaving the others stopped
+    ///
+    /// Stepping one thread through a race this way can deadlock if it waits on a stopped thread.
+    /// If the thread exits, the others are resumed
+    #[inline]
+    pub fn set_scheduler_locking(&mut self, locking: bool) {
+        self.scheduler_locking = locking;
+    }
+
+    pub fn set_current_thread(&mut self, tid: Pid) -> Result<()> {
+        if !self.threads.contains_key(&tid) {
+            return Err(SdbError::Other(format!("No such thread {}", tid)));
+        }
+
+        self.current_thread = tid;
+        Ok(())
+    }
+
+    /// A thread by its tid, its name, or the start of its name if only one thread's starts that way
+    pub fn find_thread(&self, spec: &str) -> Result<Pid> {
+        if let Ok(tid) = spec.parse() {
+            let tid = Pid::from_raw(tid);
+            if self.threads.contains_key(&tid) {
+                return Ok(tid);
+            }
+        }
+
+        let names = self
+            .threads()
+            .filter_map(|thread| Some((thread.tid, thread.name()?)))
+            .collect::<Vec<_>>();
+        if let Some((tid, _)) = names.iter().find(|(_, name)| name == spec) {
+            return Ok(*tid);
+        }
+
+        let matches = names
+            .iter()
+            .filter(|(_, name)| name.starts_with(spec))
+            .collect::<Vec<_>>();
+        match matches.as_slice() {
+            [] => Err(SdbError::Other(format!("No thread named {}", spec))),
+            [(tid, _)] => Ok(*tid),
+            matches => Err(SdbError::Other(format!(
+                "{} matches {} threads: {}",
+                spec,
+                matches.len(),
+                matches
+                    .iter()
+                    .map(|(tid, name)| format!("{} ({})", name, tid))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn current_thread_state(&self) -> &ThreadState {
+        &self.threads[&self.current_thread]
+    }
+
+    fn trace_options(&self) -> ptrace::Options {
+        let options = ptrace::Options::PTRACE_O_TRACECLONE
+            | ptrace::Options::PTRACE_O_TRACEFORK
+            | ptrace::Options::PTRACE_O_TRACEVFORK
+            | ptrace::Options::PTRACE_O_TRACEVFORKDONE
+            // an event stop instead of a SIGTRAP that looks like any other
+            | ptrace::Options::PTRACE_O_TRACEEXEC
+            // tells syscall stops apart from SIGTRAPs
+            | ptrace::Options::PTRACE_O_TRACESYSGOOD;
+        match self.catches_exits() {
+            true => options | ptrace::Options::PTRACE_O_TRACEEXIT,
+            false => options,
+        }
+    }
+
+    pub(crate) fn set_trace_options(&self, tid: Pid) -> Result<()> {
+        ptrace::setoptions(tid, self.trace_options()).map_err(SdbError::ptrace("setoptions", tid))
+    }
+
+    // seizing doesn't stop the thread, it has to be interrupted
+    pub(crate) fn seize_thread(&mut self, tid: Pid) -> Result<()> {
+        ptrace::seize(tid, self.trace_options()).map_err(SdbError::ptrace("seize", tid))?;
+        ptrace::interrupt(tid).map_err(SdbError::ptrace("interrupt", tid))?;
+
+        self.threads
+            .insert(tid, ThreadState::new(tid, ProcessState::Running));
+        Ok(())
+    }
+
+    // asks a running thread to stop, the stop still has to be waited on
+    fn request_stop(&mut self, tid: Pid) -> Result<()> {
+        if self.is_seized() {
+            // doesn't touch the thread's signals
+            ptrace::interrupt(tid).map_err(SdbError::ptrace("interrupt", tid))?;
+        } else {
+            tgkill(self.pid, tid, Signal::SIGSTOP)
+                .map_err(SdbError::signal(Signal::SIGSTOP, tid))?;
+        }
+
+        self.threads.get_mut(&tid).unwrap().pending_stop = true;
+        Ok(())
+    }
+
+    // attaches to any threads of an attached process that we aren't tracing yet
+    pub(crate) fn attach_threads(&mut self) -> Result<()> {
+        loop {
+            let tasks = procfs::process::Process::new(self.pid.as_raw())?.tasks()?;
+
+            let mut attached_any = false;
+            for task in tasks.flatten() {
+                let tid = Pid::from_raw(task.tid);
+                if self.threads.contains_key(&tid) {
+                    continue;
+                }
+
+                // the thread may have exited since we listed it
+                if self.seize_thread(tid).is_err() {
+                    continue;
+                }
+                self.threads.get_mut(&tid).unwrap().pending_stop = true;
+
+                if let ThreadOutcome::Failed(err) = self.stop_thread(tid) {
+                    return Err(err);
+                }
+                attached_any = true;
+            }
+
+            // threads can be created while we're attaching
+            if !attached_any {
+                return Ok(());
+            }
+        }
+    }
+
+    // waits for the next status from any of our threads
+    pub(crate) fn wait_any(
+        &mut self,
+        flags: Option<wait::WaitPidFlag>,
+    ) -> Result<Option<wait::WaitStatus>> {
+        if !self.is_attached {
+            let status =
+                wait::waitpid(self.pid, flags).map_err(SdbError::waitpid(Some(self.pid)))?;
+            return Ok((status != wait::WaitStatus::StillAlive).then_some(status));
+        }
+
+        let stashed = STASHED_STATUSES.with_borrow_mut(|stashed| {
+            let idx = stashed.iter().position(|status| {
+                status
+                    .pid()
+                    .is_some_and(|pid| self.threads.contains_key(&pid))
+            })?;
+            Some(stashed.remove(idx))
+        });
+        if stashed.is_some() {
+            return Ok(stashed);
+        }
+
+        let flags = flags.unwrap_or(wait::WaitPidFlag::empty())
+            | wait::WaitPidFlag::__WALL
+            | wait::WaitPidFlag::__WNOTHREAD;
+        loop {
+            let status = wait::waitpid(None, Some(flags)).map_err(SdbError::waitpid(None))?;
+            let Some(pid) = status.pid() else {
+                return Ok(None);
+            };
+
+            if self.threads.contains_key(&pid) {
+                return Ok(Some(status));
+            }
+
+            trace!("Stashing wait status {:?}", status);
+            STASHED_STATUSES.with_borrow_mut(|stashed| stashed.push(status));
+        }
+    }
+
+    pub(crate) fn forget_stashed_statuses(&self) {
+        STASHED_STATUSES.with_borrow_mut(|stashed| {
+            stashed.retain(|status| {
+                !status
+                    .pid()
+                    .is_some_and(|pid| self.threads.contains_key(&pid))
+            })
+        });
+    }
+
+    // returns true if the status was thread bookkeeping that's been handled
+    pub(crate) fn handle_thread_status(&mut self, status: wait::WaitStatus) -> Result<bool> {
+        let Some(tid) = status.pid() else {
+            return Ok(false);
+        };
+
+        match status {
+            wait::WaitStatus::Exited(..) | wait::WaitStatus::Signaled(..) if tid != self.pid => {
+                trace!("Thread {} exited", tid);
+                self.remove_thread(tid);
+                // nothing would ever stop again once the only running thread's gone
+                if self.locked_thread == Some(tid) {
+                    self.locked_thread = None;
+                    self.resume_all_threads().into_result()?;
+                }
+                Ok(true)
+            }
+            _ if is_requested_stop(status) && self.threads[&tid].pending_stop => {
+                let thread = self.threads.get_mut(&tid).unwrap();
+                thread.pending_stop = false;
+                thread.state = ProcessState::Stopped;
+                // a new thread doesn't get the debug registers from the one that started it
+                if self.uses_debug_registers() {
+                    self.write_debug_registers(tid)?;
+                }
+                // a locked scheduler leaves new threads where they started
+                if self.locked_thread.is_none() {
+                    self.resume_thread(tid)?;
+                }
+                Ok(true)
+            }
+            wait::WaitStatus::PtraceEvent(_, _, libc::PTRACE_EVENT_CLONE) => {
+                self.thread_created(tid)?;
+                self.threads.get_mut(&tid).unwrap().state = ProcessState::Stopped;
+                self.resume_thread(tid)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn thread_created(&mut self, parent: Pid) -> Result<Pid> {
+        let tid = ptrace::getevent(parent).map_err(SdbError::ptrace("geteventmsg", parent))?;
+        let tid = Pid::from_raw(tid as i32);
+        trace!("Thread {} created {}", parent, tid);
+
+        // new threads start out stopped
+        let mut thread = ThreadState::new(tid, ProcessState::Running);
+        thread.pending_stop = true;
+        self.threads.insert(tid, thread);
+
+        self.subscribers.emit(DebugEvent::ThreadCreated(tid));
+        Ok(tid)
+    }
+
+    fn remove_thread(&mut self, tid: Pid) {
+        self.threads.remove(&tid);
+        if self.current_thread == tid {
+            self.current_thread = self.pid;
+        }
+
+        self.subscribers.emit(DebugEvent::ThreadExited(tid));
+    }
+
+    pub(crate) fn record_thread_stop(&mut self, tid: Pid, reason: &StopReason) {
+        if let Some(thread) = self.threads.get_mut(&tid) {
+            thread.state = reason.reason;
+            thread.reason = Some(reason.clone());
+            thread.rehit_breakpoint = false;
+        }
+    }
+
+    pub(crate) fn resume_thread(&mut self, tid: Pid) -> Result<()> {
+        self.step_over_breakpoint(tid)?;
+
+        let tracing_syscalls = self.syscall_trace.is_some();
+        let watching = self.locked_thread == Some(tid) && self.is_watching();
+        let thread = self.threads.get_mut(&tid).unwrap();
+        let signal = thread.pending_signal.take();
+        match (watching, tracing_syscalls) {
+            (true, _) => ptrace::step(tid, signal).map_err(SdbError::ptrace("singlestep", tid)),
+            (false, true) => ptrace::syscall(tid, signal).map_err(SdbError::ptrace("syscall", tid)),
+            (false, false) => ptrace::cont(tid, signal).map_err(SdbError::ptrace("cont", tid)),
+        }?;
+        thread.state = ProcessState::Running;
+
+        Ok(())
+    }
+
+    pub(crate) fn step_thread(&mut self, tid: Pid) -> Result<()> {
+        ptrace::step(tid, None).map_err(SdbError::ptrace("singlestep", tid))?;
+        self.threads.get_mut(&tid).unwrap().state = ProcessState::Running;
+
+        Ok(())
+    }
+
+    pub fn resume_all_threads(&mut self) -> ThreadOpReport {
+        let mut report = ThreadOpReport::default();
+
+        let tids = self
+            .threads
+            .values()
+            .filter(|thread| thread.state != ProcessState::Running)
+            .map(|thread| thread.tid)
+            .collect::<Vec<_>>();
+        for tid in tids {
+            let outcome = self.thread_outcome(tid, |this| this.resume_thread(tid));
+            report.outcomes.push((tid, outcome));
+        }
+
+        // the process is running if anything made it out
+        if self.is_any_thread(ProcessState::Running) {
+            self.set_state(ProcessState::Running);
+        }
+
+        report
+    }
+
+    pub fn stop_all_threads(&mut self) -> ThreadOpReport {
+        let report = self.stop_other_threads(None);
+        if self.is_any_thread(ProcessState::Stopped) && !self.is_any_thread(ProcessState::Running) {
+            self.set_state(ProcessState::Stopped);
+        }
+
+        report
+    }
+
+    #[inline]
+    fn is_any_thread(&self, state: ProcessState) -> bool {
+        self.threads.values().any(|thread| thread.state == state)
+    }
+
+    // all-stop: once one thread stops, everything else does too
+    pub(crate) fn stop_other_threads(&mut self, except: Option<Pid>) -> ThreadOpReport {
+        let mut report = ThreadOpReport::default();
+
+        // stopping a thread can discover new ones
+        loop {
+            let tids = self
+                .threads
+                .values()
+                .filter(|thread| {
+                    Some(thread.tid) != except && thread.state == ProcessState::Running
+                })
+                .map(|thread| thread.tid)
+                .collect::<Vec<_>>();
+            if tids.is_empty() {
+                return report;
+            }
+
+            for tid in tids {
+                let outcome = self.stop_thread(tid);
+                report.outcomes.push((tid, outcome));
+            }
+        }
+    }
+
+    fn stop_thread(&mut self, tid: Pid) -> ThreadOutcome {
+        self.thread_outcome(tid, |this| {
+            if !this.threads[&tid].pending_stop {
+                this.request_stop(tid)?;
+            }
+
+            let status = wait_for_thread(tid)?;
+            trace!("Thread {} stop status {:?}", tid, status);
+
+            match status {
+                wait::WaitStatus::Exited(..) | wait::WaitStatus::Signaled(..) => {
+                    if tid != this.pid {
+                        this.remove_thread(tid);
+                    }
+                    return Err(SdbError::WaitPid {
+                        pid: Some(tid),
+                        errno: Errno::ESRCH,
+                    });
+                }
+                _ if is_requested_stop(status) => {
+                    this.threads.get_mut(&tid).unwrap().pending_stop = false;
+                }
+                wait::WaitStatus::PtraceEvent(_, _, libc::PTRACE_EVENT_CLONE) => {
+                    // picked up by the next pass
+                    this.thread_created(tid)?;
+                }
+                wait::WaitStatus::PtraceEvent(_, _, event) => {
+                    this.handle_fork_event(tid, event, false)?;
+
+                    // following a child replaces all of our threads
+                    if !this.threads.contains_key(&tid) {
+                        return Ok(());
+                    }
+                }
+                wait::WaitStatus::Stopped(_, signal) => {
+                    // it stopped for something else before our SIGSTOP arrived
+                    // keep the signal for later and make sure breakpoints are hit again
+                    let thread = this.threads.get_mut(&tid).unwrap();
+                    thread.reason = Some(StopReason::from(status));
+                    this.record_pending_signal(tid, signal);
+
+                    this.read_all_registers(tid)?;
+                    if signal == Signal::SIGTRAP && this.rewind_breakpoint(tid)? {
+                        this.threads.get_mut(&tid).unwrap().rehit_breakpoint = true;
+                    }
+                    this.threads.get_mut(&tid).unwrap().state = ProcessState::Stopped;
+                    return Ok(());
+                }
+                _ => (),
+            }
+
+            this.threads.get_mut(&tid).unwrap().state = ProcessState::Stopped;
+            this.read_all_registers(tid)
+        })
+    }
+
+    // a thread that stopped on a breakpoint we aren't reporting
+    // will hit it again once it's resumed
+    // returns true if the thread was moved back onto a breakpoint
+    fn rewind_breakpoint(&mut self, tid: Pid) -> Result<bool> {
+        let info = ptrace::getsiginfo(tid).map_err(SdbError::ptrace("getsiginfo", tid))?;
+        if info.si_code != libc::SI_KERNEL {
+            return Ok(false);
+        }
+
+        let current = std::mem::replace(&mut self.current_thread, tid);
+        let pc = self.get_pc()? - 1;
+        let result = if self.breakpoint_sites.enabled_stoppoint_at_address(pc) {
+            self.set_pc(pc).map(|_| true)
+        } else {
+            Ok(false)
+        };
+        self.current_thread = current;
+
+        result
+    }
+
+    // signals can turn up before a step finishes, they're kept for the next resume
+    pub(crate) fn wait_for_step(&mut self, tid: Pid) -> Result<wait::WaitStatus> {
+        loop {
+            let status = wait_for_thread(tid)?;
+            match status {
+                _ if is_requested_stop(status) => {
+                    self.threads.get_mut(&tid).unwrap().pending_stop = false;
+                }
+                wait::WaitStatus::Stopped(_, signal) if signal != Signal::SIGTRAP => {
+                    self.record_pending_signal(tid, signal);
+                }
+                status => return Ok(status),
+            }
+
+            ptrace::step(tid, None).map_err(SdbError::ptrace("singlestep", tid))?;
+        }
+    }
+
+    fn thread_outcome(
+        &mut self,
+        tid: Pid,
+        op: impl FnOnce(&mut Self) -> Result<()>,
+    ) -> ThreadOutcome {
+        match op(self) {
+            Ok(()) => ThreadOutcome::Succeeded,
+            // losing the leader means losing the process, that's a real failure
+            Err(SdbError::Ptrace {
+                errno: Errno::ESRCH,
+                ..
+            })
+            | Err(SdbError::Signal {
+                errno: Errno::ESRCH,
+                ..
+            })
+            | Err(SdbError::WaitPid {
+                errno: Errno::ESRCH | Errno::ECHILD,
+                ..
+            }) if tid != self.pid => {
+                if self.threads.contains_key(&tid) {
+                    self.remove_thread(tid);
+                }
+                ThreadOutcome::Vanished
+            }
+            Err(err) => ThreadOutcome::Failed(err),
+        }
+    }
+
+    pub(crate) fn reset_threads(&mut self, tid: Pid) {
+        self.threads.clear();
+        self.threads
+            .insert(tid, ThreadState::new(tid, ProcessState::Stopped));
+        self.current_thread = tid;
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::Process;
+
+/// Something that lets the process run until it next stops
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TimedOperation {
+    Resume,
+    Step,
+}
+
+impl fmt::Display for TimedOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Resume => write!(f, "resume"),
+            Self::Step => write!(f, "step"),
+        }
+    }
+}
+
+/// Latencies from issuing an operation to the stop that ends it
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct OperationTimings {
+    count: u64,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+}
+
+impl OperationTimings {
+    fn record(&mut self, latency: Duration) {
+        self.min = match self.count {
+            0 => latency,
+            _ => self.min.min(latency),
+        };
+        self.max = self.max.max(latency);
+        self.total += latency;
+        self.count += 1;
+    }
+
+    #[inline]
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    #[inline]
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    #[inline]
+    pub fn min(&self) -> Duration {
+        self.min
+    }
+
+    #[inline]
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    pub fn mean(&self) -> Duration {
+        match self.count {
+            0 => Duration::ZERO,
+            count => self.total.div_f64(count as f64),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Timings {
+    operations: BTreeMap<TimedOperation, OperationTimings>,
+
+    // the operation waiting on a stop
+    pending: Option<(TimedOperation, Instant)>,
+}
+
+impl Timings {
+    #[inline]
+    pub(crate) fn start(&mut self, operation: TimedOperation) {
+        self.pending = Some((operation, Instant::now()));
+    }
+
+    // waits with nothing started aren't counted, like the first one after launching
+    pub(crate) fn stop(&mut self) {
+        if let Some((operation, started)) = self.pending.take() {
+            self.operations
+                .entry(operation)
+                .or_default()
+                .record(started.elapsed());
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, operation: TimedOperation) -> Option<&OperationTimings> {
+        self.operations.get(&operation)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (TimedOperation, &OperationTimings)> {
+        self.operations
+            .iter()
+            .map(|(operation, timings)| (*operation, timings))
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+}
+
+impl Process {
+    #[inline]
+    pub fn timings(&self) -> &Timings {
+        &self.timings
+    }
+
+    pub fn reset_timings(&mut self) {
+        self.timings.operations.clear();
+    }
+}
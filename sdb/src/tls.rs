@@ -0,0 +1,191 @@
+use std::path::PathBuf;
+
+use num_traits::ToPrimitive;
+
+use crate::{Elf, Inferior, RegisterId, Result, TlsTemplate, types::VirtAddr};
+
+// what glibc puts in a DTV slot for a module whose block hasn't been allocated yet
+const TLS_DTV_UNALLOCATED: u64 = u64::MAX;
+
+// bounds walking a corrupt link map
+const MAX_LINK_MAPS: usize = 1024;
+
+const PAGE_SIZE: u64 = 0x1000;
+
+/// The start of glibc's thread control block on x86-64, `tcbhead_t`, which fs points at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlibcTcb {
+    /// Points back at the TCB itself
+    pub tcb: VirtAddr,
+    /// The thread's dynamic thread vector, a block address per module id
+    pub dtv: VirtAddr,
+    /// The thread's `struct pthread`, the same address as the TCB on x86-64
+    pub thread: VirtAddr,
+    pub multiple_threads: i32,
+    pub stack_guard: u64,
+    pub pointer_guard: u64,
+    pub dtv_slots: u64,
+    pub dtv_generation: u64,
+}
+
+/// One module's thread-locals for the current thread
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsBlock {
+    pub path: PathBuf,
+    /// glibc's module id, the block's index in the DTV
+    pub module_id: Option<u64>,
+    /// None until the thread first uses a dlopened module's thread-locals
+    pub address: Option<VirtAddr>,
+    pub template: TlsTemplate,
+}
+
+/// The current thread's segment bases and thread-local storage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsInfo {
+    pub fs_base: VirtAddr,
+    pub gs_base: VirtAddr,
+    /// None without glibc's `_r_debug`, its layout is all this knows
+    pub tcb: Option<GlibcTcb>,
+    /// Every module with thread-locals, in the order the dynamic linker loaded them if it's known
+    pub blocks: Vec<TlsBlock>,
+}
+
+fn read_register(inferior: &(impl Inferior + ?Sized), id: RegisterId) -> Result<VirtAddr> {
+    Ok(VirtAddr::new(
+        inferior
+            .read_register_by_id(id)?
+            .to_u64()
+            .unwrap_or_default(),
+    ))
+}
+
+fn read_tcb(inferior: &(impl Inferior + ?Sized), fs_base: VirtAddr) -> Result<GlibcTcb> {
+    let read = |offset: u64| inferior.read_u64(VirtAddr::new(fs_base.addr() + offset));
+
+    let dtv = VirtAddr::new(read(8)?);
+    Ok(GlibcTcb {
+        tcb: VirtAddr::new(read(0)?),
+        dtv,
+        thread: VirtAddr::new(read(16)?),
+        // gscope_flag is the other half
+        multiple_threads: read(24)? as u32 as i32,
+        stack_guard: read(40)?,
+        pointer_guard: read(48)?,
+        // the length is in the slot before the one the TCB points to
+        dtv_slots: inferior.read_u64(VirtAddr::new(dtv.addr().wrapping_sub(16)))?,
+        dtv_generation: inferior.read_u64(dtv)?,
+    })
+}
+
+// the loaded modules in the dynamic linker's order, by load bias and name
+fn link_maps(inferior: &(impl Inferior + ?Sized)) -> Option<Vec<(VirtAddr, String)>> {
+    let (elf, symbol) = inferior.modules().find_variable("_r_debug")?;
+    let r_debug = elf.symbol_address(symbol);
+
+    let mut maps = Vec::new();
+    let mut link_map = inferior.read_u64(r_debug + 8).ok()?;
+    while link_map != 0 && maps.len() < MAX_LINK_MAPS {
+        let link_map_address = VirtAddr::new(link_map);
+        let load_bias = VirtAddr::new(inferior.read_u64(link_map_address).ok()?);
+        let name = match inferior.read_u64(link_map_address + 8).ok()? {
+            0 => String::new(),
+            name => String::from_utf8_lossy(
+                &inferior
+                    .read_c_string(VirtAddr::new(name), PAGE_SIZE as usize)
+                    .ok()?,
+            )
+            .into_owned(),
+        };
+        maps.push((load_bias, name));
+        link_map = inferior.read_u64(link_map_address + 24).ok()?;
+    }
+    Some(maps)
+}
+
+// symlinked library directories mean the names don't always match, the load bias does
+fn module_for_link_map<'a>(
+    modules: &[&'a Elf],
+    load_bias: VirtAddr,
+    name: &str,
+) -> Option<&'a Elf> {
+    let mut candidates = modules
+        .iter()
+        .copied()
+        .filter(|elf| elf.load_bias() == load_bias);
+    let first = candidates.next()?;
+    Some(
+        std::iter::once(first)
+            .chain(candidates)
+            .find(|elf| elf.path().as_os_str() == name)
+            .unwrap_or(first),
+    )
+}
+
+impl TlsInfo {
+    /// Reads the current thread's thread-local layout
+    ///
+    /// Module ids are taken to be handed out in load order, which glibc does
+    /// unless a module with thread-locals has been dlclosed and its id reused
+    pub fn read(inferior: &(impl Inferior + ?Sized)) -> Result<Self> {
+        let fs_base = read_register(inferior, RegisterId::fs_base)?;
+        let gs_base = read_register(inferior, RegisterId::gs_base)?;
+
+        let modules = inferior
+            .modules()
+            .iter()
+            .filter(|elf| elf.tls_template().is_some())
+            .collect::<Vec<_>>();
+        let block = |elf: &Elf| TlsBlock {
+            path: elf.path().to_path_buf(),
+            module_id: None,
+            address: None,
+            template: elf.tls_template().unwrap(),
+        };
+
+        let link_maps = link_maps(inferior).filter(|_| fs_base.addr() != 0);
+        let Some(link_maps) = link_maps else {
+            return Ok(Self {
+                fs_base,
+                gs_base,
+                tcb: None,
+                blocks: modules.into_iter().map(block).collect(),
+            });
+        };
+
+        let tcb = read_tcb(inferior, fs_base).ok();
+
+        let mut blocks = Vec::new();
+        for (load_bias, name) in link_maps {
+            let Some(elf) = module_for_link_map(&modules, load_bias, &name) else {
+                continue;
+            };
+
+            let module_id = blocks.len() as u64 + 1;
+            let address = tcb
+                .as_ref()
+                .filter(|tcb| module_id <= tcb.dtv_slots)
+                .and_then(|tcb| inferior.read_u64(tcb.dtv + 16 * module_id as i64).ok())
+                .filter(|&address| address != 0 && address != TLS_DTV_UNALLOCATED)
+                .map(VirtAddr::new);
+            blocks.push(TlsBlock {
+                module_id: Some(module_id),
+                address,
+                ..block(elf)
+            });
+        }
+
+        // anything the dynamic linker doesn't know about goes last
+        for elf in modules {
+            if !blocks.iter().any(|block| block.path == elf.path()) {
+                blocks.push(block(elf));
+            }
+        }
+
+        Ok(Self {
+            fs_base,
+            gs_base,
+            tcb,
+            blocks,
+        })
+    }
+}
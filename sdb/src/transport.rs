@@ -0,0 +1,374 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicU64, Ordering},
+    mpsc,
+};
+
+use num_traits::ToPrimitive;
+use tracing::{trace, warn};
+
+use crate::{
+    DebugEvent, Inferior, ProcessController, Result, SdbError, Target,
+    protocol::{Message, Request, Response},
+    register_info_by_name,
+};
+
+/// A session with a debugger, wherever it's running
+///
+/// Requests block until they're answered, except that
+/// an Interrupt can be sent while another request waits
+pub trait DebugTransport: Send + Sync {
+    fn request(&self, request: Request) -> Result<Response>;
+
+    /// Subscribes to events from the debugged process
+    fn events(&self) -> Result<mpsc::Receiver<DebugEvent>>;
+
+    /// The controller of a process in this one, for what requests can't express
+    fn local_controller(&self) -> Option<&ProcessController> {
+        None
+    }
+}
+
+// runs a request against the target on its control thread
+fn dispatch(target: &mut Target, request: Request) -> Result<Response> {
+    let register = |name: &str| {
+        register_info_by_name(name)
+            .ok_or_else(|| SdbError::Register(format!("No such register {}", name)))
+    };
+
+    Ok(match request {
+        Request::Resume => {
+            target.process_mut().resume()?;
+            Response::Done
+        }
+        Request::Wait => Response::Stopped(target.wait_on_signal()?),
+        // usually sent before getting here, the control thread may be busy waiting
+        Request::Interrupt => {
+            target.process().interrupt()?;
+            Response::Done
+        }
+        Request::StepInstruction => Response::Stopped(target.step_instruction()?),
+        Request::StepIn => Response::Stopped(target.step_in()?),
+        Request::StepOver => Response::Stopped(target.step_over()?),
+        Request::StepOut => Response::Stopped(target.step_out()?),
+        Request::StopInfo => Response::StopInfo(target.stop_info()?),
+        Request::Backtrace => Response::Backtrace(target.backtrace()?),
+        Request::ReadRegister(name) => {
+            let value = target.read_register_by_id(register(&name)?.id())?;
+            Response::Register(
+                value.to_u64().ok_or_else(|| {
+                    SdbError::Register(format!("{} doesn't fit in 64 bits", name))
+                })?,
+            )
+        }
+        Request::WriteRegister(name, value) => {
+            target
+                .process()
+                .write_register_by_id(register(&name)?.id(), value.into())?;
+            Response::Done
+        }
+        Request::ReadMemory { address, amount } => {
+            Response::Memory(target.read_memory_without_traps(address, amount)?)
+        }
+        Request::WriteMemory { address, data } => {
+            target.process_mut().write_memory(address, &data)?;
+            Response::Done
+        }
+        Request::BreakFunction(name) => {
+            Response::Breakpoint(target.create_function_breakpoint(name)?)
+        }
+        Request::BreakLine { file, line } => {
+            Response::Breakpoint(target.create_line_breakpoint(file, line)?)
+        }
+        Request::BreakAddress(address) => {
+            let process = target.process_mut();
+            let id = process.create_breakpoint_site(address, false)?;
+            process.enable_breakpoint_site(id)?;
+            Response::Breakpoint(id)
+        }
+        Request::RemoveBreakpoint(id) => {
+            target.process_mut().remove_breakpoint_site(id)?;
+            Response::Done
+        }
+    })
+}
+
+/// Talks to a debugger in this process
+#[derive(Debug)]
+pub struct InProcessTransport {
+    controller: ProcessController,
+}
+
+impl InProcessTransport {
+    pub fn new(controller: ProcessController) -> Self {
+        Self { controller }
+    }
+
+    #[inline]
+    pub fn controller(&self) -> &ProcessController {
+        &self.controller
+    }
+}
+
+impl DebugTransport for InProcessTransport {
+    fn request(&self, request: Request) -> Result<Response> {
+        self.controller.request(request)
+    }
+
+    fn events(&self) -> Result<mpsc::Receiver<DebugEvent>> {
+        self.controller.events()
+    }
+
+    fn local_controller(&self) -> Option<&ProcessController> {
+        Some(&self.controller)
+    }
+}
+
+// a controller borrowed for a session is as good as one owned by it
+impl DebugTransport for ProcessController {
+    fn request(&self, request: Request) -> Result<Response> {
+        if request == Request::Interrupt {
+            self.handle().interrupt()?;
+            return Ok(Response::Done);
+        }
+
+        self.execute_target(move |target| dispatch(target, request))?
+    }
+
+    fn events(&self) -> Result<mpsc::Receiver<DebugEvent>> {
+        self.execute_target(|target| target.events())?
+    }
+
+    fn local_controller(&self) -> Option<&ProcessController> {
+        Some(self)
+    }
+}
+
+// responses are matched up with their requests by id
+#[derive(Debug, Default)]
+struct Pending {
+    responses: HashMap<u64, mpsc::SyncSender<Response>>,
+    subscribers: Vec<mpsc::Sender<DebugEvent>>,
+    closed: bool,
+}
+
+/// Talks to a debugger serving a session over a stream
+pub struct StreamTransport {
+    writer: Mutex<Box<dyn Write + Send>>,
+    next_id: AtomicU64,
+    pending: Arc<Mutex<Pending>>,
+
+    // the reader holds its own handle to the stream, so dropping the writer isn't enough
+    shutdown: Box<dyn Fn() + Send + Sync>,
+}
+
+impl Drop for StreamTransport {
+    fn drop(&mut self) {
+        (self.shutdown)();
+    }
+}
+
+fn transport_error(err: std::io::Error) -> SdbError {
+    SdbError::Transport(err.to_string())
+}
+
+impl StreamTransport {
+    pub fn connect_unix(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_unix(UnixStream::connect(path).map_err(transport_error)?)
+    }
+
+    pub fn connect_tcp(address: impl ToSocketAddrs) -> Result<Self> {
+        Self::from_tcp(TcpStream::connect(address).map_err(transport_error)?)
+    }
+
+    /// Connects over TCP to host:port, or otherwise to a unix socket at the path
+    pub fn connect(address: &str) -> Result<Self> {
+        match address.parse::<SocketAddr>() {
+            Ok(address) => Self::connect_tcp(address),
+            Err(_) => Self::connect_unix(address),
+        }
+    }
+
+    pub fn from_unix(stream: UnixStream) -> Result<Self> {
+        let reader = stream.try_clone().map_err(transport_error)?;
+        let shutdown = stream.try_clone().map_err(transport_error)?;
+        Ok(Self::new(reader, stream, move || {
+            let _ = shutdown.shutdown(Shutdown::Both);
+        }))
+    }
+
+    pub fn from_tcp(stream: TcpStream) -> Result<Self> {
+        stream.set_nodelay(true).map_err(transport_error)?;
+        let reader = stream.try_clone().map_err(transport_error)?;
+        let shutdown = stream.try_clone().map_err(transport_error)?;
+        Ok(Self::new(reader, stream, move || {
+            let _ = shutdown.shutdown(Shutdown::Both);
+        }))
+    }
+
+    fn new(
+        reader: impl Read + Send + 'static,
+        writer: impl Write + Send + 'static,
+        shutdown: impl Fn() + Send + Sync + 'static,
+    ) -> Self {
+        let pending = Arc::new(Mutex::new(Pending::default()));
+
+        let reader_pending = pending.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(reader).lines() {
+                let message = match line
+                    .map_err(transport_error)
+                    .and_then(|line| Message::decode(&line))
+                {
+                    Ok(message) => message,
+                    Err(err) => {
+                        warn!("Dropping session: {}", err);
+                        break;
+                    }
+                };
+
+                let mut pending = reader_pending.lock().unwrap();
+                match message {
+                    Message::Response(id, response) => {
+                        if let Some(sender) = pending.responses.remove(&id) {
+                            let _ = sender.send(response);
+                        }
+                    }
+                    Message::Event(event) => pending
+                        .subscribers
+                        .retain(|sender| sender.send(event.clone()).is_ok()),
+                }
+            }
+
+            // wakes anyone still waiting
+            let mut pending = reader_pending.lock().unwrap();
+            pending.closed = true;
+            pending.responses.clear();
+            pending.subscribers.clear();
+        });
+
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+            next_id: AtomicU64::new(0),
+            pending,
+            shutdown: Box::new(shutdown),
+        }
+    }
+}
+
+impl DebugTransport for StreamTransport {
+    fn request(&self, request: Request) -> Result<Response> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = mpsc::sync_channel(1);
+        {
+            let mut pending = self.pending.lock().unwrap();
+            if pending.closed {
+                return Err(SdbError::Transport("Session closed".to_owned()));
+            }
+            pending.responses.insert(id, sender);
+        }
+
+        let mut writer = self.writer.lock().unwrap();
+        writeln!(writer, "{}\t{}", id, request.encode()).map_err(transport_error)?;
+        writer.flush().map_err(transport_error)?;
+        drop(writer);
+
+        receiver
+            .recv()
+            .map_err(|_| SdbError::Transport("Session closed".to_owned()))?
+            .into_result()
+    }
+
+    fn events(&self) -> Result<mpsc::Receiver<DebugEvent>> {
+        let (sender, receiver) = mpsc::channel();
+        self.pending.lock().unwrap().subscribers.push(sender);
+        Ok(receiver)
+    }
+}
+
+/// Serves a session over a stream until the other end hangs up
+///
+/// Requests are run in order, but an interrupt is sent
+/// straight away so that it can stop a pending wait
+pub fn serve_session(
+    controller: &ProcessController,
+    reader: impl Read + Send,
+    writer: impl Write + Send,
+) -> Result<()> {
+    // None closes the session
+    let (sender, receiver) = mpsc::channel::<Option<Message>>();
+
+    let events = controller.execute_target(|target| target.events())??;
+    let event_sender = sender.clone();
+    std::thread::spawn(move || {
+        for event in events {
+            if event_sender.send(Some(Message::Event(event))).is_err() {
+                break;
+            }
+        }
+    });
+
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let mut writer = writer;
+            while let Ok(Some(message)) = receiver.recv() {
+                trace!("Sending {:?}", message);
+                let sent = writeln!(writer, "{}", message.encode()).and_then(|_| writer.flush());
+                if sent.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let handle = controller.handle();
+        for line in BufReader::new(reader).lines() {
+            let line = line.map_err(transport_error)?;
+            let (id, request) = line
+                .split_once('\t')
+                .ok_or_else(|| SdbError::Transport(format!("Malformed request: {}", line)))?;
+            let id = id
+                .parse::<u64>()
+                .map_err(|_| SdbError::Transport(format!("Malformed request: {}", line)))?;
+
+            let request = match Request::decode(request) {
+                Ok(request) => request,
+                Err(err) => {
+                    let _ = sender.send(Some(Message::Response(
+                        id,
+                        Response::Error(err.to_string()),
+                    )));
+                    continue;
+                }
+            };
+            trace!("Received {:?}", request);
+
+            if request == Request::Interrupt {
+                let response = match handle.interrupt() {
+                    Ok(()) => Response::Done,
+                    Err(err) => Response::Error(err.to_string()),
+                };
+                let _ = sender.send(Some(Message::Response(id, response)));
+                continue;
+            }
+
+            let sender = sender.clone();
+            controller.submit_target(move |target| {
+                let response = dispatch(target, request)
+                    .unwrap_or_else(|err| Response::Error(err.to_string()));
+                let _ = sender.send(Some(Message::Response(id, response)));
+            })?;
+        }
+
+        // everything queued before this has been answered once it's run
+        let sender = sender.clone();
+        controller.submit_target(move |_| {
+            let _ = sender.send(None);
+        })?;
+        Ok(())
+    })
+}
@@ -29,3 +29,116 @@ pub fn byte128_64(v: &Byte64) -> Byte128 {
     ret[..8].copy_from_slice(v);
     ret
 }
+
+// x87 80-bit extended precision to the closest f64
+pub fn extended_to_f64(v: [u8; 10]) -> f64 {
+    let mantissa = u64::from_le_bytes(v[..8].try_into().unwrap());
+    let sign_exponent = u16::from_le_bytes([v[8], v[9]]);
+    let sign = if sign_exponent & 0x8000 != 0 {
+        -1.0
+    } else {
+        1.0
+    };
+    let exponent = (sign_exponent & 0x7fff) as i32;
+
+    if exponent == 0x7fff {
+        return if mantissa << 1 == 0 {
+            sign * f64::INFINITY
+        } else {
+            f64::NAN
+        };
+    }
+
+    // the integer bit is explicit, so the mantissa is a 1.63 fixed point value
+    let exponent = if exponent == 0 {
+        -16382
+    } else {
+        exponent - 16383
+    };
+    sign * mantissa as f64 * 2.0_f64.powi(exponent - 63)
+}
+
+// f64 to x87 80-bit extended precision, which holds every f64 exactly
+pub fn f64_to_extended(v: f64) -> [u8; 10] {
+    let bits = v.to_bits();
+    let sign = ((bits >> 63) as u16) << 15;
+    let exponent = ((bits >> 52) & 0x7ff) as u16;
+    let fraction = bits & ((1 << 52) - 1);
+
+    let (exponent, mantissa) = match exponent {
+        0 if fraction == 0 => (0, 0),
+        // subnormals are normal with the extra exponent range
+        0 => {
+            let shift = fraction.leading_zeros() as u16;
+            (15372 - shift, fraction << shift)
+        }
+        0x7ff => (0x7fff, 1 << 63 | fraction << 11),
+        exponent => (exponent - 1023 + 16383, 1 << 63 | fraction << 11),
+    };
+
+    let mut ret = [0; 10];
+    ret[..8].copy_from_slice(&mantissa.to_le_bytes());
+    ret[8..].copy_from_slice(&(sign | exponent).to_le_bytes());
+    ret
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VirtAddr(u64);
+
+impl VirtAddr {
+    #[inline]
+    pub const fn new(addr: u64) -> Self {
+        Self(addr)
+    }
+
+    #[inline]
+    pub const fn addr(&self) -> u64 {
+        self.0
+    }
+}
+
+impl From<u64> for VirtAddr {
+    fn from(value: u64) -> Self {
+        Self(value)
+    }
+}
+
+impl std::ops::Add<i64> for VirtAddr {
+    type Output = Self;
+
+    fn add(self, rhs: i64) -> Self::Output {
+        Self(self.0.wrapping_add_signed(rhs))
+    }
+}
+
+impl std::ops::AddAssign<i64> for VirtAddr {
+    fn add_assign(&mut self, rhs: i64) {
+        *self = *self + rhs;
+    }
+}
+
+impl std::ops::Sub<i64> for VirtAddr {
+    type Output = Self;
+
+    fn sub(self, rhs: i64) -> Self::Output {
+        Self(self.0.wrapping_add_signed(-rhs))
+    }
+}
+
+impl std::ops::SubAssign<i64> for VirtAddr {
+    fn sub_assign(&mut self, rhs: i64) {
+        *self = *self - rhs;
+    }
+}
+
+impl std::fmt::Display for VirtAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#018x}", self.0)
+    }
+}
+
+impl std::fmt::LowerHex for VirtAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::LowerHex::fmt(&self.0, f)
+    }
+}
@@ -0,0 +1,1134 @@
+//! Reading a function's parameters and locals, and globals, the way their types say
+//!
+//! `print box.corners[1].x` finds `box` in the debug info of the function the pc is in,
+//! or among the globals if it isn't one of its, works out where it is from its DWARF
+//! location, and shows it as C would write it
+
+use std::str::FromStr;
+
+use num_traits::ToPrimitive;
+
+use crate::{
+    BaseEncoding, Dwarf, DwarfExpression, DwarfFunction, DwarfType, DwarfVariable, Elf, Inferior,
+    RegisterId, RegisterValue, Result, SdbError, Target, TlsInfo, TypeId, expr::Expr,
+    register_info::register_info_by_dwarf, types::VirtAddr,
+};
+
+// arrays longer than this are cut short
+const MAX_ELEMENTS: u64 = 200;
+
+// char pointers are shown as the string they point to, up to this
+const MAX_STRING: usize = 200;
+
+// typedefs and qualifiers more than this deep are taken to be a loop
+const MAX_TYPE_DEPTH: usize = 64;
+
+// what a leaf function can use below the stack pointer without moving it
+const RED_ZONE: u64 = 128;
+
+/// One step from a variable to part of it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Accessor {
+    /// `.field`
+    Field(String),
+    /// `[index]`, of an array or what a pointer points to
+    Index(u64),
+    /// `->field`
+    Arrow(String),
+}
+
+/// A variable and the parts of it to get to, `name`, `*name`, or `name.field[2]->next`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariablePath {
+    pub deref: bool,
+    pub name: String,
+    pub accessors: Vec<Accessor>,
+}
+
+fn variable_error(message: impl Into<String>) -> SdbError {
+    SdbError::Variable(message.into())
+}
+
+fn identifier(text: &str) -> (&str, &str) {
+    let len = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(text.len());
+    text.split_at(len)
+}
+
+fn field_name(text: &str) -> Result<(String, &str)> {
+    let (field, rest) = identifier(text);
+    match field.is_empty() {
+        true => Err(variable_error(format!(
+            "Missing field name before {}",
+            rest
+        ))),
+        false => Ok((field.to_owned(), rest)),
+    }
+}
+
+impl FromStr for VariablePath {
+    type Err = SdbError;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let text = text.trim();
+        let (deref, text) = match text.strip_prefix('*') {
+            Some(text) => (true, text.trim_start()),
+            None => (false, text),
+        };
+
+        let (name, mut rest) = identifier(text);
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(variable_error(format!("{} isn't a variable", text)));
+        }
+
+        let mut accessors = Vec::new();
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('.') {
+                let (field, after) = field_name(after)?;
+                accessors.push(Accessor::Field(field));
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix("->") {
+                let (field, after) = field_name(after)?;
+                accessors.push(Accessor::Arrow(field));
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let (index, after) = after
+                    .split_once(']')
+                    .ok_or_else(|| variable_error("Missing ]"))?;
+                let index = index.trim();
+                let index = match index.strip_prefix("0x") {
+                    Some(hex) => u64::from_str_radix(hex, 16),
+                    None => index.parse(),
+                }
+                .map_err(|err| variable_error(format!("Index {}: {}", index, err)))?;
+                accessors.push(Accessor::Index(index));
+                rest = after;
+            } else {
+                return Err(variable_error(format!("Unexpected {}", rest)));
+            }
+        }
+
+        Ok(Self {
+            deref,
+            name: name.to_owned(),
+            accessors,
+        })
+    }
+}
+
+impl std::fmt::Display for VariablePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.deref {
+            write!(f, "*")?;
+        }
+        write!(f, "{}", self.name)?;
+        for accessor in &self.accessors {
+            match accessor {
+                Accessor::Field(field) => write!(f, ".{}", field)?,
+                Accessor::Index(index) => write!(f, "[{}]", index)?,
+                Accessor::Arrow(field) => write!(f, "->{}", field)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How integers in a value are shown
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Radix {
+    #[default]
+    Decimal,
+    Hex,
+}
+
+/// A variable, or part of one, read out of the process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableValue {
+    pub type_name: String,
+    /// None if it's in registers or had to be worked out
+    pub address: Option<VirtAddr>,
+    /// The bits of a value that fits in 64, to show it some other way
+    pub scalar: Option<u64>,
+    /// Formatted like gdb does, `{x = 1, y = 2}` for a struct
+    pub text: String,
+}
+
+fn value_bytes(value: RegisterValue) -> Vec<u8> {
+    match value {
+        RegisterValue::Byte128(bytes) => bytes.to_vec(),
+        RegisterValue::Byte64(bytes) => bytes.to_vec(),
+        RegisterValue::Float(value) => value.to_le_bytes().to_vec(),
+        RegisterValue::Double(value) | RegisterValue::LongDouble(value) => {
+            value.to_le_bytes().to_vec()
+        }
+        value => value.to_u64().unwrap_or_default().to_le_bytes().to_vec(),
+    }
+}
+
+// where a value is, memory or bytes already read out of registers
+#[derive(Debug, Clone)]
+enum Place {
+    Memory(VirtAddr),
+    Bytes(Vec<u8>),
+}
+
+impl Place {
+    fn offset(&self, offset: u64) -> Self {
+        match self {
+            Self::Memory(address) => Self::Memory(*address + offset as i64),
+            Self::Bytes(bytes) => {
+                Self::Bytes(bytes.get(offset as usize..).unwrap_or_default().to_vec())
+            }
+        }
+    }
+
+    fn address(&self) -> Option<VirtAddr> {
+        match self {
+            Self::Memory(address) => Some(*address),
+            Self::Bytes(_) => None,
+        }
+    }
+}
+
+// a value of a type, with how many of an array's dimensions have already been indexed
+#[derive(Debug, Clone)]
+struct Object {
+    type_id: Option<TypeId>,
+    indexed: usize,
+    place: Place,
+}
+
+struct Context<'a, I: Inferior + ?Sized> {
+    inferior: &'a I,
+    elf: &'a Elf,
+    dwarf: &'a Dwarf,
+    // None for a global
+    function: Option<&'a DwarfFunction>,
+    radix: Radix,
+}
+
+impl<I: Inferior + ?Sized> Context<'_, I> {
+    // past typedefs and qualifiers to what the value really is
+    fn resolve(&self, mut type_id: Option<TypeId>) -> Option<&DwarfType> {
+        for _ in 0..MAX_TYPE_DEPTH {
+            match self.dwarf.get_type(type_id?) {
+                DwarfType::Typedef { target, .. } | DwarfType::Qualified { target, .. } => {
+                    type_id = *target
+                }
+                r#type => return Some(r#type),
+            }
+        }
+        None
+    }
+
+    fn size(&self, type_id: Option<TypeId>) -> u64 {
+        match self.resolve(type_id) {
+            Some(
+                DwarfType::Base { size, .. }
+                | DwarfType::Pointer { size, .. }
+                | DwarfType::Struct { size, .. }
+                | DwarfType::Enum { size, .. },
+            ) => *size,
+            Some(DwarfType::Array { element, counts }) => self.array_size(*element, counts),
+            _ => 0,
+        }
+    }
+
+    fn array_size(&self, element: Option<TypeId>, counts: &[Option<u64>]) -> u64 {
+        counts
+            .iter()
+            .map(|count| count.unwrap_or_default())
+            .product::<u64>()
+            * self.size(element)
+    }
+
+    fn object_size(&self, object: &Object) -> u64 {
+        match self.resolve(object.type_id) {
+            Some(DwarfType::Array { element, counts }) => {
+                self.array_size(*element, &counts[object.indexed..])
+            }
+            _ => self.size(object.type_id),
+        }
+    }
+
+    fn type_name(&self, type_id: Option<TypeId>) -> String {
+        let Some(id) = type_id else {
+            return "void".to_owned();
+        };
+        match self.dwarf.get_type(id) {
+            DwarfType::Base { name, .. } | DwarfType::Typedef { name, .. } => name.clone(),
+            DwarfType::Pointer { pointee, .. } => {
+                let pointee = self.type_name(*pointee);
+                match pointee.ends_with('*') {
+                    true => format!("{}*", pointee),
+                    false => format!("{} *", pointee),
+                }
+            }
+            DwarfType::Struct { keyword, name, .. } => {
+                format!("{} {}", keyword, name.as_deref().unwrap_or("{...}"))
+            }
+            DwarfType::Array { element, counts } => self.array_name(*element, counts),
+            DwarfType::Enum { name, .. } => {
+                format!("enum {}", name.as_deref().unwrap_or("{...}"))
+            }
+            // const applies to what's left of it, so a const pointer is char *const
+            DwarfType::Qualified { qualifier, target } => match self.resolve(*target) {
+                Some(DwarfType::Pointer { .. }) => {
+                    format!("{}{}", self.type_name(*target), qualifier)
+                }
+                _ => format!("{} {}", qualifier, self.type_name(*target)),
+            },
+            DwarfType::Function => "function".to_owned(),
+            DwarfType::Unsupported(tag) => tag.to_string(),
+        }
+    }
+
+    fn array_name(&self, element: Option<TypeId>, counts: &[Option<u64>]) -> String {
+        let dimensions = counts
+            .iter()
+            .map(|count| match count {
+                Some(count) => format!("[{}]", count),
+                None => "[]".to_owned(),
+            })
+            .collect::<String>();
+        format!("{} {}", self.type_name(element), dimensions)
+    }
+
+    fn object_type_name(&self, object: &Object) -> String {
+        match self.resolve(object.type_id) {
+            Some(DwarfType::Array { element, counts }) if object.indexed > 0 => {
+                self.array_name(*element, &counts[object.indexed..])
+            }
+            _ => self.type_name(object.type_id),
+        }
+    }
+
+    fn read(&self, place: &Place, size: u64) -> Result<Vec<u8>> {
+        match place {
+            Place::Memory(address) => self.inferior.read_memory(*address, size as usize),
+            Place::Bytes(bytes) => {
+                let mut bytes = bytes.clone();
+                bytes.resize(size as usize, 0);
+                Ok(bytes)
+            }
+        }
+    }
+
+    fn read_scalar(&self, place: &Place, size: u64) -> Result<u64> {
+        let bytes = self.read(place, size.min(8))?;
+        let mut value = [0; 8];
+        value[..bytes.len()].copy_from_slice(&bytes);
+        Ok(u64::from_le_bytes(value))
+    }
+
+    fn register(&self, dwarf_id: u16) -> Result<RegisterValue> {
+        let info = register_info_by_dwarf(dwarf_id as i32)
+            .ok_or_else(|| variable_error(format!("No register with DWARF number {}", dwarf_id)))?;
+        self.inferior.read_register_by_id(info.id())
+    }
+
+    fn register_u64(&self, dwarf_id: u16) -> Result<u64> {
+        Ok(self.register(dwarf_id)?.to_u64().unwrap_or_default())
+    }
+
+    fn register_bytes(&self, dwarf_id: u16) -> Result<Vec<u8>> {
+        Ok(value_bytes(self.register(dwarf_id)?))
+    }
+
+    fn file_pc(&self) -> Result<u64> {
+        let pc = self.inferior.get_pc()?;
+        self.elf
+            .file_address(pc)
+            .ok_or_else(|| variable_error(format!("{} isn't in {}", pc, self.elf.path().display())))
+    }
+
+    // the unwind info says where the frame starts, which is usually what the frame base is
+    fn call_frame_cfa(&self) -> Result<u64> {
+        let (register, offset) = self
+            .elf
+            .cfa_rule(self.file_pc()?)
+            .ok_or_else(|| variable_error("No unwind info for the current pc"))?;
+        Ok(self.register_u64(register)?.wrapping_add_signed(offset))
+    }
+
+    fn evaluate<'e>(
+        &self,
+        expression: &'e DwarfExpression,
+    ) -> Result<Vec<gimli::Piece<gimli::EndianSlice<'e, gimli::RunTimeEndian>>>> {
+        let bytes = gimli::EndianSlice::new(&expression.bytes, self.dwarf.endian());
+        let mut evaluation = gimli::Expression(bytes).evaluation(expression.encoding);
+
+        let mut result = evaluation.evaluate()?;
+        loop {
+            result = match result {
+                gimli::EvaluationResult::Complete => break,
+                gimli::EvaluationResult::RequiresMemory { address, size, .. } => {
+                    let value =
+                        self.read_scalar(&Place::Memory(VirtAddr::new(address)), size as u64)?;
+                    evaluation.resume_with_memory(gimli::Value::Generic(value))?
+                }
+                gimli::EvaluationResult::RequiresRegister { register, .. } => {
+                    let value = self.register_u64(register.0)?;
+                    evaluation.resume_with_register(gimli::Value::Generic(value))?
+                }
+                gimli::EvaluationResult::RequiresFrameBase => {
+                    let frame_base = self.frame_base()?;
+                    evaluation.resume_with_frame_base(frame_base)?
+                }
+                gimli::EvaluationResult::RequiresCallFrameCfa => {
+                    evaluation.resume_with_call_frame_cfa(self.call_frame_cfa()?)?
+                }
+                // DW_OP_addr is a file address
+                gimli::EvaluationResult::RequiresRelocatedAddress(address) => evaluation
+                    .resume_with_relocated_address(self.elf.virtual_address(address).addr())?,
+                gimli::EvaluationResult::RequiresTls(offset) => {
+                    evaluation.resume_with_tls(self.tls_address(offset)?)?
+                }
+                result => {
+                    return Err(variable_error(format!(
+                        "Unsupported DWARF expression, it needs {:?}",
+                        result
+                    )));
+                }
+            };
+        }
+
+        Ok(evaluation.result())
+    }
+
+    // DW_OP_form_tls_address has an offset into the module's block, the current thread's is found
+    // the way glibc would, through the DTV
+    fn tls_address(&self, offset: u64) -> Result<u64> {
+        let tls = TlsInfo::read(self.inferior)?;
+        let block = tls
+            .blocks
+            .iter()
+            .find(|block| block.path == self.elf.path())
+            .ok_or_else(|| {
+                variable_error(format!(
+                    "{} has no thread-locals",
+                    self.elf.path().display()
+                ))
+            })?;
+        let address = block.address.ok_or_else(|| {
+            variable_error(format!(
+                "The current thread hasn't allocated {}'s thread-locals yet",
+                self.elf.path().display()
+            ))
+        })?;
+        Ok(address.addr() + offset)
+    }
+
+    // a frame base in a register is that register's value
+    fn frame_base(&self) -> Result<u64> {
+        let function = self
+            .function
+            .ok_or_else(|| variable_error("Globals have no frame base"))?;
+        let frame_base = function
+            .frame_base
+            .as_ref()
+            .ok_or_else(|| variable_error(format!("{} has no frame base", function.name)))?;
+        match self.evaluate(frame_base)?.as_slice() {
+            [piece] => match piece.location {
+                gimli::Location::Address { address } => Ok(address),
+                gimli::Location::Register { register } => self.register_u64(register.0),
+                gimli::Location::Value { value } => Ok(value.to_u64(u64::MAX)?),
+                _ => Err(variable_error("Unsupported frame base")),
+            },
+            _ => Err(variable_error("Unsupported frame base")),
+        }
+    }
+
+    fn locate(&self, name: &str, expression: &DwarfExpression) -> Result<Place> {
+        let pieces = self.evaluate(expression)?;
+        if let [
+            gimli::Piece {
+                location: gimli::Location::Address { address },
+                ..
+            },
+        ] = pieces.as_slice()
+        {
+            return Ok(Place::Memory(VirtAddr::new(*address)));
+        }
+
+        // anything else is read out now, a piece at a time
+        let mut bytes = Vec::new();
+        for piece in pieces {
+            let size = piece.size_in_bits.map(|bits| bits.div_ceil(8) as usize);
+            let mut piece_bytes = match piece.location {
+                gimli::Location::Register { register } => self.register_bytes(register.0)?,
+                gimli::Location::Value { value } => value.to_u64(u64::MAX)?.to_le_bytes().to_vec(),
+                gimli::Location::Bytes { value } => value.to_vec(),
+                gimli::Location::Address { address } => self.read(
+                    &Place::Memory(VirtAddr::new(address)),
+                    size.unwrap_or(8) as u64,
+                )?,
+                gimli::Location::Empty => {
+                    return Err(variable_error(format!("{} has been optimized out", name)));
+                }
+                gimli::Location::ImplicitPointer { .. } => {
+                    return Err(variable_error(format!(
+                        "{} is an implicit pointer, which isn't supported",
+                        name
+                    )));
+                }
+            };
+            if let Some(size) = size {
+                piece_bytes.resize(size, 0);
+            }
+            bytes.extend_from_slice(&piece_bytes);
+        }
+        Ok(Place::Bytes(bytes))
+    }
+
+    fn deref(&self, object: &Object) -> Result<Object> {
+        match self.resolve(object.type_id) {
+            Some(DwarfType::Pointer { pointee, size }) => {
+                let address = self.read_scalar(&object.place, *size)?;
+                if address == 0 {
+                    return Err(variable_error("Null pointer"));
+                }
+                Ok(Object {
+                    type_id: *pointee,
+                    indexed: 0,
+                    place: Place::Memory(VirtAddr::new(address)),
+                })
+            }
+            Some(DwarfType::Array { .. }) => self.index(object, 0),
+            _ => Err(variable_error(format!(
+                "A {} can't be dereferenced",
+                self.object_type_name(object)
+            ))),
+        }
+    }
+
+    fn index(&self, object: &Object, index: u64) -> Result<Object> {
+        match self.resolve(object.type_id) {
+            Some(DwarfType::Array { element, counts }) => {
+                if let Some(count) = counts[object.indexed]
+                    && index >= count
+                {
+                    return Err(variable_error(format!(
+                        "Index {} is past the end of a {}",
+                        index,
+                        self.object_type_name(object)
+                    )));
+                }
+
+                // arrays of arrays are indexed a dimension at a time
+                let rest = &counts[object.indexed + 1..];
+                let stride = self.array_size(*element, rest);
+                let place = object.place.offset(index * stride);
+                Ok(match rest.is_empty() {
+                    true => Object {
+                        type_id: *element,
+                        indexed: 0,
+                        place,
+                    },
+                    false => Object {
+                        type_id: object.type_id,
+                        indexed: object.indexed + 1,
+                        place,
+                    },
+                })
+            }
+            Some(DwarfType::Pointer { pointee, .. }) => {
+                let target = self.deref(object)?;
+                let stride = self.size(*pointee);
+                Ok(Object {
+                    place: target.place.offset(index * stride),
+                    ..target
+                })
+            }
+            _ => Err(variable_error(format!(
+                "A {} can't be indexed",
+                self.object_type_name(object)
+            ))),
+        }
+    }
+
+    fn field(&self, object: &Object, field: &str) -> Result<Object> {
+        let Some(DwarfType::Struct { members, .. }) = self.resolve(object.type_id) else {
+            return Err(variable_error(format!(
+                "A {} has no fields",
+                self.object_type_name(object)
+            )));
+        };
+
+        for member in members {
+            match &member.name {
+                Some(name) if name == field => return self.member(object, member),
+                // fields of anonymous structs and unions belong to the one around them
+                None => {
+                    if let Ok(inner) = self.member(object, member)
+                        && let Ok(found) = self.field(&inner, field)
+                    {
+                        return Ok(found);
+                    }
+                }
+                Some(_) => (),
+            }
+        }
+
+        Err(variable_error(format!(
+            "A {} has no field {}",
+            self.object_type_name(object),
+            field
+        )))
+    }
+
+    // a bitfield is read out and shifted down, it has no address of its own
+    fn member(&self, object: &Object, member: &crate::DwarfMember) -> Result<Object> {
+        let place = object.place.offset(member.bit_offset / 8);
+        let Some(bit_size) = member.bit_size else {
+            return Ok(Object {
+                type_id: member.type_id,
+                indexed: 0,
+                place,
+            });
+        };
+
+        let shift = member.bit_offset % 8;
+        let bytes = (shift + bit_size).div_ceil(8);
+        let value = (self.read_scalar(&place, bytes)? >> shift) & (u64::MAX >> (64 - bit_size));
+
+        // sign extend signed fields so -1 shows as -1
+        let value = match self.resolve(member.type_id) {
+            Some(DwarfType::Base {
+                encoding: BaseEncoding::Signed | BaseEncoding::SignedChar,
+                ..
+            }) if value >> (bit_size - 1) & 1 == 1 => value | !(u64::MAX >> (64 - bit_size)),
+            _ => value,
+        };
+        Ok(Object {
+            type_id: member.type_id,
+            indexed: 0,
+            place: Place::Bytes(value.to_le_bytes().to_vec()),
+        })
+    }
+
+    fn format_integer(&self, value: u64, size: u64, signed: bool) -> String {
+        let bits = (size.clamp(1, 8) * 8) as u32;
+        let value = value & (u64::MAX >> (64 - bits));
+        match (self.radix, signed) {
+            (Radix::Hex, _) => format!("{:#x}", value),
+            (Radix::Decimal, true) => {
+                let shift = 64 - bits;
+                (((value << shift) as i64) >> shift).to_string()
+            }
+            (Radix::Decimal, false) => value.to_string(),
+        }
+    }
+
+    fn format_base(&self, encoding: BaseEncoding, size: u64, place: &Place) -> Result<String> {
+        let bytes = self.read(place, size)?;
+        let value = self.read_scalar(&Place::Bytes(bytes.clone()), size)?;
+        Ok(match encoding {
+            BaseEncoding::Signed => self.format_integer(value, size, true),
+            BaseEncoding::Unsigned => self.format_integer(value, size, false),
+            BaseEncoding::SignedChar | BaseEncoding::UnsignedChar => format!(
+                "{} {:?}",
+                self.format_integer(value, size, encoding == BaseEncoding::SignedChar),
+                char::from(value as u8)
+            ),
+            BaseEncoding::Bool => match value {
+                0 => "false".to_owned(),
+                1 => "true".to_owned(),
+                value => value.to_string(),
+            },
+            BaseEncoding::Float => match size {
+                4 => f32::from_bits(value as u32).to_string(),
+                8 => f64::from_bits(value).to_string(),
+                10 | 16 => {
+                    crate::types::extended_to_f64(bytes[..10].try_into().unwrap()).to_string()
+                }
+                size => format!("<{} byte float>", size),
+            },
+        })
+    }
+
+    fn is_char(&self, type_id: Option<TypeId>) -> bool {
+        matches!(
+            self.resolve(type_id),
+            Some(DwarfType::Base {
+                encoding: BaseEncoding::SignedChar | BaseEncoding::UnsignedChar,
+                size: 1,
+                ..
+            })
+        )
+    }
+
+    fn read_string(&self, address: VirtAddr) -> Result<String> {
+        let string = self.inferior.read_c_string(address, MAX_STRING)?;
+        let text = format!("{:?}", String::from_utf8_lossy(&string));
+        Ok(match string.len() {
+            MAX_STRING => text + "...",
+            _ => text,
+        })
+    }
+
+    fn format(&self, object: &Object) -> Result<String> {
+        let Some(r#type) = self.resolve(object.type_id) else {
+            return Ok("<void>".to_owned());
+        };
+
+        Ok(match r#type {
+            DwarfType::Base { encoding, size, .. } => {
+                self.format_base(*encoding, *size, &object.place)?
+            }
+            DwarfType::Enum {
+                size, enumerators, ..
+            } => {
+                let value = self.read_scalar(&object.place, *size)?;
+                let signed = self.format_integer(value, *size, true);
+                enumerators
+                    .iter()
+                    .find(|(_, enumerator)| Ok(*enumerator) == signed.parse::<i64>())
+                    .map(|(name, _)| name.clone())
+                    .unwrap_or(signed)
+            }
+            DwarfType::Pointer { pointee, size } => {
+                let address = self.read_scalar(&object.place, *size)?;
+                let mut text = format!("{:#x}", address);
+                if address != 0 && self.is_char(*pointee) {
+                    let string = self
+                        .read_string(VirtAddr::new(address))
+                        .unwrap_or_else(|err| format!("<{}>", err));
+                    text += &format!(" {}", string);
+                } else if let Some(DwarfType::Function) = self.resolve(*pointee)
+                    && let Some(name) = self.inferior.function_name_at(VirtAddr::new(address))
+                {
+                    text += &format!(" <{}>", name);
+                }
+                text
+            }
+            DwarfType::Struct { members, .. } => {
+                let mut fields = Vec::new();
+                for member in members {
+                    let value = self
+                        .member(object, member)
+                        .and_then(|member| self.format(&member))
+                        .unwrap_or_else(|err| format!("<{}>", err));
+                    fields.push(match &member.name {
+                        Some(name) => format!("{} = {}", name, value),
+                        None => value,
+                    });
+                }
+                format!("{{{}}}", fields.join(", "))
+            }
+            DwarfType::Array { element, counts } => {
+                let count = counts[object.indexed];
+                let rest = &counts[object.indexed + 1..];
+
+                // char arrays are strings up to the first NUL
+                if rest.is_empty()
+                    && self.is_char(*element)
+                    && let Some(count) = count
+                {
+                    let bytes = self.read(&object.place, count.min(MAX_STRING as u64))?;
+                    let end = bytes
+                        .iter()
+                        .position(|&byte| byte == 0)
+                        .unwrap_or(bytes.len());
+                    return Ok(format!("{:?}", String::from_utf8_lossy(&bytes[..end])));
+                }
+
+                let Some(count) = count else {
+                    return Ok("<unknown size>".to_owned());
+                };
+                let mut elements = Vec::new();
+                for index in 0..count.min(MAX_ELEMENTS) {
+                    elements.push(self.format(&self.index(object, index)?)?);
+                }
+                if count > MAX_ELEMENTS {
+                    elements.push("...".to_owned());
+                }
+                format!("{{{}}}", elements.join(", "))
+            }
+            DwarfType::Function => "<function>".to_owned(),
+            DwarfType::Typedef { .. } | DwarfType::Qualified { .. } => unreachable!(),
+            DwarfType::Unsupported(tag) => format!("<{}>", tag),
+        })
+    }
+
+    // the bytes to write for a value of the object's type
+    fn encode(&self, object: &Object, text: &str) -> Result<Vec<u8>> {
+        let integer = |text: &str| -> Result<u64> {
+            Ok(text.parse::<Expr>()?.evaluate(self.inferior)? as u64)
+        };
+
+        let size = self.object_size(object);
+        let value = match self.resolve(object.type_id) {
+            Some(DwarfType::Base {
+                encoding: BaseEncoding::Float,
+                ..
+            }) => {
+                let value = text
+                    .parse::<f64>()
+                    .map_err(|err| variable_error(format!("{}: {}", text, err)))?;
+                let mut bytes = match size {
+                    4 => (value as f32).to_le_bytes().to_vec(),
+                    8 => value.to_le_bytes().to_vec(),
+                    10 | 16 => crate::types::f64_to_extended(value).to_vec(),
+                    size => {
+                        return Err(variable_error(format!("{} byte floats can't be set", size)));
+                    }
+                };
+                bytes.resize(size as usize, 0);
+                return Ok(bytes);
+            }
+            Some(DwarfType::Base {
+                encoding: BaseEncoding::Bool,
+                ..
+            }) => match text {
+                "true" => 1,
+                "false" => 0,
+                text => integer(text)?,
+            },
+            Some(DwarfType::Base { .. } | DwarfType::Pointer { .. }) => integer(text)?,
+            Some(DwarfType::Enum { enumerators, .. }) => {
+                match enumerators.iter().find(|(name, _)| name == text) {
+                    Some((_, value)) => *value as u64,
+                    None => integer(text)?,
+                }
+            }
+            _ => {
+                return Err(variable_error(format!(
+                    "A {} can't be set, only numbers, pointers, and enums can",
+                    self.object_type_name(object)
+                )));
+            }
+        };
+
+        Ok(value.to_le_bytes()[..size.min(8) as usize].to_vec())
+    }
+
+    // the scalars a value's made of, by byte offset and size, and whether they're floats
+    fn leaves(&self, type_id: Option<TypeId>, offset: u64, leaves: &mut Vec<(u64, u64, bool)>) {
+        match self.resolve(type_id) {
+            Some(DwarfType::Struct { members, .. }) => {
+                for member in members {
+                    self.leaves(member.type_id, offset + member.bit_offset / 8, leaves);
+                }
+            }
+            Some(DwarfType::Array { element, counts }) => {
+                let stride = self.size(*element);
+                for index in 0..self.array_size(*element, counts) / stride.max(1) {
+                    self.leaves(*element, offset + index * stride, leaves);
+                }
+            }
+            Some(DwarfType::Base { encoding, size, .. }) => {
+                leaves.push((offset, *size, *encoding == BaseEncoding::Float))
+            }
+            Some(_) => leaves.push((offset, self.size(type_id), false)),
+            None => (),
+        }
+    }
+
+    // a struct or array that's too big, or has a long double in it, is returned in memory
+    fn in_memory(&self, type_id: Option<TypeId>) -> bool {
+        if !matches!(
+            self.resolve(type_id),
+            Some(DwarfType::Struct { .. } | DwarfType::Array { .. })
+        ) {
+            return false;
+        }
+
+        let size = self.size(type_id);
+        let mut leaves = Vec::new();
+        if size <= 16 {
+            self.leaves(type_id, 0, &mut leaves);
+        }
+        size > 16 || leaves.iter().any(|&(_, size, float)| float && size > 8)
+    }
+
+    // where the SysV ABI leaves a function's return value, each eightbyte of a small struct
+    // goes in the next of rax and rdx, or xmm0 and xmm1 if it's all floats
+    fn return_place(&self, type_id: Option<TypeId>) -> Result<Place> {
+        let register = |id| self.inferior.read_register_by_id(id).map(value_bytes);
+        let rax = register(RegisterId::rax)?;
+        let size = self.size(type_id);
+
+        match self.resolve(type_id) {
+            Some(DwarfType::Base {
+                encoding: BaseEncoding::Float,
+                size: 10 | 16,
+                ..
+            }) => {
+                let RegisterValue::LongDouble(value) =
+                    self.inferior.read_register_by_id(RegisterId::st0)?
+                else {
+                    return Err(variable_error("st0 isn't a long double"));
+                };
+                Ok(Place::Bytes(crate::types::f64_to_extended(value).to_vec()))
+            }
+            Some(DwarfType::Base {
+                encoding: BaseEncoding::Float,
+                ..
+            }) => Ok(Place::Bytes(register(RegisterId::xmm0)?)),
+            Some(DwarfType::Base { .. } | DwarfType::Pointer { .. } | DwarfType::Enum { .. }) => {
+                Ok(Place::Bytes(rax))
+            }
+            Some(DwarfType::Struct { .. } | DwarfType::Array { .. }) => {
+                // it's in memory the caller passed, and rax points at it afterwards
+                if self.in_memory(type_id) {
+                    let address = u64::from_le_bytes(rax[..8].try_into().unwrap());
+                    return Ok(Place::Memory(VirtAddr::new(address)));
+                }
+
+                let mut leaves = Vec::new();
+                self.leaves(type_id, 0, &mut leaves);
+
+                let mut integers = [rax, register(RegisterId::rdx)?].into_iter();
+                let mut floats =
+                    [register(RegisterId::xmm0)?, register(RegisterId::xmm1)?].into_iter();
+                let mut bytes = Vec::new();
+                for eightbyte in 0..size.div_ceil(8) {
+                    let range = eightbyte * 8..eightbyte * 8 + 8;
+                    let sse = leaves
+                        .iter()
+                        .filter(|(offset, ..)| range.contains(offset))
+                        .all(|&(_, _, float)| float);
+                    let register = match sse {
+                        true => floats.next(),
+                        false => integers.next(),
+                    };
+                    bytes.extend_from_slice(&register.unwrap()[..8]);
+                }
+                bytes.truncate(size as usize);
+                Ok(Place::Bytes(bytes))
+            }
+            _ => Err(variable_error(format!(
+                "A returned {} can't be read",
+                self.type_name(type_id)
+            ))),
+        }
+    }
+
+    fn value(&self, object: &Object) -> Result<VariableValue> {
+        let size = self.object_size(object);
+        let scalar =
+            match self.resolve(object.type_id) {
+                Some(
+                    DwarfType::Base { .. } | DwarfType::Pointer { .. } | DwarfType::Enum { .. },
+                ) if size <= 8 => Some(self.read_scalar(&object.place, size)?),
+                _ => None,
+            };
+
+        Ok(VariableValue {
+            type_name: self.object_type_name(object),
+            address: object.place.address(),
+            scalar,
+            text: self.format(object)?,
+        })
+    }
+}
+
+// a local of the function the pc is in hides a global with the same name,
+// globals of the module the pc is in come before those of other modules
+fn find_variable<'a, I: Inferior + ?Sized>(
+    inferior: &'a I,
+    name: &str,
+    radix: Radix,
+) -> Result<(Context<'a, I>, &'a DwarfVariable, u64)> {
+    let pc = inferior.get_pc()?;
+    let here = inferior.modules().get_elf_containing_address(pc);
+
+    if let Some(elf) = here
+        && let Some(dwarf) = elf.dwarf()
+    {
+        let file_pc = elf.file_address(pc).unwrap_or_default();
+        if let Some(function) = dwarf.function_containing_address(file_pc)
+            && let Some(variable) = function
+                .variables
+                .iter()
+                .rev()
+                .find(|variable| variable.name == name && variable.in_scope(file_pc))
+        {
+            let context = Context {
+                inferior,
+                elf,
+                dwarf,
+                function: Some(function),
+                radix,
+            };
+            return Ok((context, variable, file_pc));
+        }
+    }
+
+    let modules = here.into_iter().chain(
+        inferior
+            .modules()
+            .iter()
+            .filter(|elf| here.is_none_or(|here| !std::ptr::eq(*elf, here))),
+    );
+    for elf in modules {
+        let Some(variable) = elf.dwarf().and_then(|dwarf| dwarf.global(name)) else {
+            continue;
+        };
+        let context = Context {
+            inferior,
+            elf,
+            dwarf: elf.dwarf().unwrap(),
+            function: None,
+            radix,
+        };
+        return Ok((context, variable, elf.file_address(pc).unwrap_or_default()));
+    }
+
+    Err(variable_error(format!("No variable named {}", name)))
+}
+
+// where the path leads, with the context to read it in
+fn locate_path<'a, I: Inferior + ?Sized>(
+    inferior: &'a I,
+    path: &VariablePath,
+    radix: Radix,
+) -> Result<(Context<'a, I>, Object)> {
+    let (context, variable, file_pc) = find_variable(inferior, &path.name, radix)?;
+
+    let location = variable
+        .locations
+        .iter()
+        .find(|location| {
+            location
+                .range
+                .as_ref()
+                .is_none_or(|range| range.contains(&file_pc))
+        })
+        .ok_or_else(|| variable_error(format!("{} has been optimized out", variable.name)))?;
+
+    let mut object = Object {
+        type_id: variable.type_id,
+        indexed: 0,
+        place: context.locate(&variable.name, &location.expression)?,
+    };
+    for accessor in &path.accessors {
+        object = match accessor {
+            Accessor::Field(field) => context.field(&object, field)?,
+            Accessor::Index(index) => context.index(&object, *index)?,
+            Accessor::Arrow(field) => context.field(&context.deref(&object)?, field)?,
+        };
+    }
+    if path.deref {
+        object = context.deref(&object)?;
+    }
+
+    Ok((context, object))
+}
+
+/// Reads a parameter or local of the function the pc is in, or a global
+pub(crate) fn read_variable(
+    inferior: &(impl Inferior + ?Sized),
+    path: &VariablePath,
+    radix: Radix,
+) -> Result<VariableValue> {
+    let (context, object) = locate_path(inferior, path, radix)?;
+    context.value(&object)
+}
+
+/// Where a variable, or part of one, is in memory and how big it is, with the CFA of
+/// the frame it's in if it's on the stack
+pub(crate) fn variable_memory(
+    inferior: &(impl Inferior + ?Sized),
+    path: &VariablePath,
+) -> Result<(VirtAddr, u64, Option<VirtAddr>)> {
+    let (context, object) = locate_path(inferior, path, Radix::Decimal)?;
+    let Place::Memory(address) = object.place else {
+        return Err(variable_error(format!("{} isn't in memory", path)));
+    };
+
+    // a static local is the function's but not in its frame, which runs from the red zone
+    // below the stack pointer up to the CFA
+    let frame = match context.function {
+        Some(_) => {
+            let cfa = context.call_frame_cfa()?;
+            let sp = inferior
+                .read_register_by_id(RegisterId::rsp)?
+                .to_u64()
+                .unwrap_or_default();
+            (sp.saturating_sub(RED_ZONE)..cfa)
+                .contains(&address.addr())
+                .then(|| VirtAddr::new(cfa))
+        }
+        None => None,
+    };
+
+    Ok((address, context.object_size(&object), frame))
+}
+
+/// How much memory a caller has to pass a function for its return value, 0 if it's in registers
+pub(crate) fn returned_in_memory(
+    inferior: &(impl Inferior + ?Sized),
+    elf: &Elf,
+    function: &DwarfFunction,
+) -> u64 {
+    let Some(dwarf) = elf.dwarf() else {
+        return 0;
+    };
+
+    let context = Context {
+        inferior,
+        elf,
+        dwarf,
+        function: Some(function),
+        radix: Radix::Decimal,
+    };
+    match context.in_memory(function.return_type) {
+        true => context.size(function.return_type),
+        false => 0,
+    }
+}
+
+/// What a function that's just returned left for its caller, None for void
+pub(crate) fn return_value(
+    inferior: &(impl Inferior + ?Sized),
+    elf: &Elf,
+    function: &DwarfFunction,
+    radix: Radix,
+) -> Result<Option<VariableValue>> {
+    let Some(dwarf) = elf.dwarf() else {
+        return Ok(None);
+    };
+    if function.return_type.is_none() {
+        return Ok(None);
+    }
+
+    let context = Context {
+        inferior,
+        elf,
+        dwarf,
+        function: Some(function),
+        radix,
+    };
+    let object = Object {
+        type_id: function.return_type,
+        indexed: 0,
+        place: context.return_place(function.return_type)?,
+    };
+    Ok(Some(context.value(&object)?))
+}
+
+impl Target {
+    /// Sets a variable, or part of one, that's in memory, so it can be undone
+    ///
+    /// Integers, chars, pointers, and enums take an expression, enums their enumerators too,
+    /// and floats a number like 1.5. Returns what it reads as afterwards
+    pub fn write_variable(&mut self, path: &VariablePath, value: &str) -> Result<VariableValue> {
+        let (address, bytes) = {
+            let (context, object) = locate_path(&*self, path, Radix::Decimal)?;
+            let Place::Memory(address) = object.place else {
+                return Err(variable_error(format!(
+                    "{} isn't in memory, it can't be set",
+                    path
+                )));
+            };
+            (address, context.encode(&object, value.trim())?)
+        };
+
+        self.process
+            .write_memory_journaled(address, &bytes, false)?;
+        self.read_variable(path, Radix::Decimal)
+    }
+}
@@ -0,0 +1,550 @@
+use std::str::FromStr;
+
+use nix::{
+    libc,
+    sys::{ptrace, signal::Signal, wait},
+    unistd::Pid,
+};
+
+use crate::{
+    Process, RegisterId, Result, SdbError, TRAP_BRKPT, TRAP_TRACE, Target, VariablePath,
+    register_info::register_info_by_id,
+    stoppoint_collection::{Stoppoint, StoppointId, StoppointKind},
+    types::VirtAddr,
+};
+
+// dr0 to dr3 hold the addresses, dr7 says what each one watches
+const DEBUG_REGISTERS: usize = 4;
+
+// dr6's bit for a trap that was a single-step
+const DR6_SINGLE_STEP: u64 = 1 << 14;
+
+/// What a thread's SIGTRAP was, from its siginfo and dr6
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct DebugStatus {
+    pub(crate) si_code: i32,
+    // the debug registers whose condition was met, B0 to B3
+    pub(crate) fired: u64,
+    // any of those that are an instruction breakpoint rather than a watchpoint
+    pub(crate) hardware_break: bool,
+    pub(crate) single_step: bool,
+}
+
+/// What accesses a watchpoint stops for, and which one it stopped for
+///
+/// The debug registers can't watch just reads, so a read watchpoint watches both and
+/// an access that changed the memory is taken as a write, like gdb does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchpointMode {
+    Write,
+    Read,
+    ReadWrite,
+}
+
+impl WatchpointMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Write => "write",
+            Self::Read => "read",
+            Self::ReadWrite => "access",
+        }
+    }
+
+    // dr7's R/W bits
+    fn condition(&self) -> u64 {
+        match self {
+            Self::Write => 0b01,
+            Self::Read | Self::ReadWrite => 0b11,
+        }
+    }
+}
+
+impl std::fmt::Display for WatchpointMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for WatchpointMode {
+    type Err = SdbError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "write" => Ok(Self::Write),
+            "read" => Ok(Self::Read),
+            "access" => Ok(Self::ReadWrite),
+            _ => Err(SdbError::Stoppoint(format!(
+                "Unknown watchpoint mode {}",
+                s
+            ))),
+        }
+    }
+}
+
+// dr7's LEN bits, None if a debug register can't watch that many bytes
+fn debug_register_length(size: usize) -> Option<u64> {
+    match size {
+        1 => Some(0b00),
+        2 => Some(0b01),
+        8 => Some(0b10),
+        4 => Some(0b11),
+        _ => None,
+    }
+}
+
+/// Stops the process when the bytes it watches are accessed
+///
+/// Up to four aligned 1, 2, 4, or 8 byte watchpoints go in the debug registers and cost nothing.
+/// Any other write watchpoint, or a local's, is checked in software after every instruction
+/// the current thread runs instead, which watches any size but runs the thread far slower,
+/// and leaves the other threads stopped while it's enabled
+#[derive(Debug)]
+pub struct Watchpoint {
+    id: StoppointId,
+    address: VirtAddr,
+    size: usize,
+    mode: WatchpointMode,
+    is_enabled: bool,
+    hit_count: u64,
+    // the debug register it's in while it's enabled, None if it's checked in software
+    slot: Option<usize>,
+
+    // what the memory held when it was last checked
+    value: Vec<u8>,
+    // what it held before the change that last stopped the process
+    previous: Option<Vec<u8>>,
+
+    // a local's thread and the CFA of its frame, the frame's returned once the stack's back above it
+    frame: Option<(Pid, VirtAddr)>,
+    out_of_scope: bool,
+}
+
+impl Stoppoint for Watchpoint {
+    #[inline]
+    fn id(&self) -> StoppointId {
+        self.id
+    }
+
+    #[inline]
+    fn address(&self) -> VirtAddr {
+        self.address
+    }
+
+    #[inline]
+    fn is_enabled(&self) -> bool {
+        self.is_enabled
+    }
+
+    #[inline]
+    fn kind(&self) -> StoppointKind {
+        StoppointKind::Watchpoint
+    }
+
+    #[inline]
+    fn hit_count(&self) -> u64 {
+        self.hit_count
+    }
+
+    fn spec(&self) -> String {
+        match self.mode {
+            WatchpointMode::Write => format!("{} bytes", self.size),
+            mode => format!("{} bytes on {}", self.size, mode),
+        }
+    }
+
+    #[inline]
+    fn at_address(&self, address: VirtAddr) -> bool {
+        self.address <= address && address < self.address + self.size as i64
+    }
+
+    #[inline]
+    fn in_range(&self, low: VirtAddr, high: VirtAddr) -> bool {
+        self.address < high && low < self.address + self.size as i64
+    }
+}
+
+impl Watchpoint {
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    #[inline]
+    pub fn mode(&self) -> WatchpointMode {
+        self.mode
+    }
+
+    /// The debug register it's in, None if it's disabled or checked in software
+    #[inline]
+    pub fn debug_register(&self) -> Option<usize> {
+        self.slot
+    }
+
+    /// What the memory holds as of the last stop
+    #[inline]
+    pub fn value(&self) -> &[u8] {
+        &self.value
+    }
+
+    /// What the memory held before the change it last stopped for
+    #[inline]
+    pub fn previous(&self) -> Option<&[u8]> {
+        self.previous.as_deref()
+    }
+
+    /// The thread and frame of the local it watches, None for anything else
+    #[inline]
+    pub fn frame(&self) -> Option<(Pid, VirtAddr)> {
+        self.frame
+    }
+
+    /// Whether the frame of the local it watches has returned, which disables it for good
+    #[inline]
+    pub fn is_out_of_scope(&self) -> bool {
+        self.out_of_scope
+    }
+}
+
+impl Process {
+    #[inline]
+    pub fn watchpoints(&self) -> &crate::StoppointCollection<Watchpoint> {
+        &self.watchpoints
+    }
+
+    // resuming single-steps the current thread while anything's watched in software
+    #[inline]
+    pub(crate) fn is_watching(&self) -> bool {
+        self.watchpoints
+            .iter()
+            .any(|watchpoint| watchpoint.is_enabled && watchpoint.slot.is_none())
+    }
+
+    #[inline]
+    pub(crate) fn uses_debug_registers(&self) -> bool {
+        self.watchpoints
+            .iter()
+            .any(|watchpoint| watchpoint.slot.is_some())
+    }
+
+    /// Stops the process when any of the `size` bytes at `address` are accessed the way `mode` says
+    #[inline]
+    pub fn create_watchpoint(
+        &mut self,
+        address: VirtAddr,
+        size: usize,
+        mode: WatchpointMode,
+    ) -> Result<StoppointId> {
+        self.push_watchpoint(address, size, mode, None)
+    }
+
+    pub(crate) fn push_watchpoint(
+        &mut self,
+        address: VirtAddr,
+        size: usize,
+        mode: WatchpointMode,
+        frame: Option<(Pid, VirtAddr)>,
+    ) -> Result<StoppointId> {
+        if size == 0 {
+            return Err(SdbError::Stoppoint(
+                "A watchpoint has to watch at least a byte".to_owned(),
+            ));
+        }
+
+        let id = self.next_stoppoint_id;
+        self.watchpoints.push(Watchpoint {
+            id,
+            address,
+            size,
+            mode,
+            is_enabled: false,
+            hit_count: 0,
+            slot: None,
+            value: Vec::new(),
+            previous: None,
+            frame,
+            out_of_scope: false,
+        });
+        if let Err(err) = self.enable_watchpoint(id) {
+            self.watchpoints.remove_by_id(id)?;
+            return Err(err);
+        }
+        self.next_stoppoint_id += 1;
+
+        Ok(id)
+    }
+
+    // the first free debug register if it can watch this, a local's frame returning
+    // is only noticed by stepping so it's never in one
+    fn free_debug_register(&self, watchpoint: &Watchpoint) -> Option<usize> {
+        debug_register_length(watchpoint.size)?;
+        if watchpoint.frame.is_some()
+            || !watchpoint
+                .address
+                .addr()
+                .is_multiple_of(watchpoint.size as u64)
+        {
+            return None;
+        }
+
+        (0..DEBUG_REGISTERS).find(|slot| {
+            !self
+                .watchpoints
+                .iter()
+                .any(|other| other.slot == Some(*slot))
+        })
+    }
+
+    // the debug registers are per thread, every thread gets them all,
+    // dr7's cleared first so no half written watchpoint fires
+    pub(crate) fn write_debug_registers(&self, tid: Pid) -> Result<()> {
+        let mut dr7 = 0;
+        for watchpoint in self.watchpoints.iter() {
+            let Some(slot) = watchpoint.slot else {
+                continue;
+            };
+            let length = debug_register_length(watchpoint.size).unwrap_or_default();
+            dr7 |= 1 << (slot * 2);
+            dr7 |= (watchpoint.mode.condition() | length << 2) << (16 + slot * 4);
+        }
+
+        let write = |id: RegisterId, value: u64| {
+            let offset = register_info_by_id(id).offset;
+            ptrace::write_user(tid, offset as ptrace::AddressType, value as libc::c_long)
+                .map_err(SdbError::ptrace("pokeuser", tid))
+        };
+        write(RegisterId::dr7, 0)?;
+        for watchpoint in self.watchpoints.iter() {
+            if let Some(slot) = watchpoint.slot {
+                write(debug_register_id(slot), watchpoint.address.addr())?;
+            }
+        }
+        write(RegisterId::dr7, dr7)
+    }
+
+    fn write_all_debug_registers(&self) -> Result<()> {
+        for thread in self.threads() {
+            self.write_debug_registers(thread.tid())?;
+        }
+        Ok(())
+    }
+
+    // what changed while it was disabled isn't a hit
+    pub fn enable_watchpoint(&mut self, id: StoppointId) -> Result<()> {
+        let watchpoint = self.watchpoints.get_by_id(id)?;
+        if watchpoint.out_of_scope {
+            return Err(SdbError::Stoppoint(format!(
+                "Watchpoint {}'s frame has returned, it can't be enabled",
+                id
+            )));
+        }
+        if watchpoint.is_enabled {
+            return Ok(());
+        }
+
+        let slot = self.free_debug_register(watchpoint);
+        if slot.is_none() && watchpoint.mode != WatchpointMode::Write {
+            return Err(SdbError::Stoppoint(format!(
+                "A {} watchpoint needs a free debug register, which watches 1, 2, 4, or 8 aligned bytes that aren't a local",
+                watchpoint.mode
+            )));
+        }
+        let value = self.read_memory_without_traps(watchpoint.address, watchpoint.size)?;
+
+        let watchpoint = self.watchpoints.get_by_id_mut(id)?;
+        watchpoint.value = value;
+        watchpoint.slot = slot;
+        watchpoint.is_enabled = true;
+        if slot.is_some() {
+            self.write_all_debug_registers()?;
+        }
+        Ok(())
+    }
+
+    pub fn disable_watchpoint(&mut self, id: StoppointId) -> Result<()> {
+        let watchpoint = self.watchpoints.get_by_id_mut(id)?;
+        watchpoint.is_enabled = false;
+        if watchpoint.slot.take().is_some() {
+            self.write_all_debug_registers()?;
+        }
+        Ok(())
+    }
+
+    pub fn remove_watchpoint(&mut self, id: StoppointId) -> Result<()> {
+        self.disable_watchpoint(id)?;
+        self.watchpoints.remove_by_id(id)?;
+        Ok(())
+    }
+
+    // decodes a SIGTRAP, dr6's bits are sticky so it's cleared for the next one,
+    // anything else is left alone and has the default
+    // what's watched holds whatever it did in a checkpoint's copy, which has its own thread
+    pub(crate) fn refresh_watchpoints(&mut self) -> Result<()> {
+        let watched = self
+            .watchpoints
+            .iter()
+            .map(|watchpoint| (watchpoint.id, watchpoint.address, watchpoint.size))
+            .collect::<Vec<_>>();
+        for (id, address, size) in watched {
+            let value = self.read_memory_without_traps(address, size)?;
+
+            let pid = self.pid;
+            let watchpoint = self.watchpoints.get_by_id_mut(id)?;
+            watchpoint.value = value;
+            watchpoint.previous = None;
+            watchpoint.frame = watchpoint.frame.map(|(_, cfa)| (pid, cfa));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn take_debug_status(
+        &self,
+        tid: Pid,
+        status: wait::WaitStatus,
+    ) -> Result<DebugStatus> {
+        if status != wait::WaitStatus::Stopped(tid, Signal::SIGTRAP) {
+            return Ok(DebugStatus::default());
+        }
+
+        let info = ptrace::getsiginfo(tid).map_err(SdbError::ptrace("getsiginfo", tid))?;
+        let read = |id: RegisterId| {
+            let offset = register_info_by_id(id).offset as ptrace::AddressType;
+            ptrace::read_user(tid, offset)
+                .map(|value| value as u64)
+                .map_err(SdbError::ptrace("peekuser", tid))
+        };
+        let dr6 = read(RegisterId::dr6)?;
+        if dr6 != 0 {
+            let offset = register_info_by_id(RegisterId::dr6).offset as ptrace::AddressType;
+            ptrace::write_user(tid, offset, 0).map_err(SdbError::ptrace("pokeuser", tid))?;
+        }
+
+        // a debug register's R/W bits are 00 for an instruction
+        let fired = dr6 & ((1 << DEBUG_REGISTERS) - 1);
+        let hardware_break = fired != 0 && {
+            let dr7 = read(RegisterId::dr7)?;
+            (0..DEBUG_REGISTERS)
+                .any(|slot| fired & (1 << slot) != 0 && (dr7 >> (16 + slot * 4)) & 0b11 == 0)
+        };
+
+        Ok(DebugStatus {
+            si_code: info.si_code,
+            fired,
+            hardware_break,
+            // stepping over a syscall reports the step as a breakpoint, and leaves dr6 alone
+            single_step: dr6 & DR6_SINGLE_STEP != 0
+                || matches!(info.si_code, TRAP_TRACE | TRAP_BRKPT),
+        })
+    }
+
+    // the debug registers of watchpoints that fired, and whether the software ones need
+    // checking, which any step does, None if the trap has nothing to do with watchpoints
+    pub(crate) fn watch_trap(&self, debug: DebugStatus) -> Option<u64> {
+        let ours = self
+            .watchpoints
+            .iter()
+            .filter_map(|watchpoint| watchpoint.slot)
+            .fold(0, |mask, slot| mask | 1 << slot);
+        let fired = debug.fired & ours;
+        match fired != 0 || (debug.single_step && self.is_watching()) {
+            true => Some(fired),
+            false => None,
+        }
+    }
+
+    // the thread's stepping for software watchpoints, not because it was asked to
+    #[inline]
+    pub(crate) fn is_watch_stepping(&self, tid: Pid) -> bool {
+        self.locked_thread == Some(tid) && self.is_watching()
+    }
+
+    // a local whose frame's returned disables its watchpoint and stops the process first,
+    // otherwise counts the hit on the first watchpoint whose memory has been accessed the way
+    // it watches, software ones by whether it's changed and hardware ones by the debug registers
+    // that `fired`, the others are caught up on the next check
+    pub(crate) fn hit_watchpoint(
+        &mut self,
+        tid: Pid,
+        fired: u64,
+    ) -> Result<Option<(StoppointId, WatchpointMode)>> {
+        if self
+            .watchpoints
+            .iter()
+            .any(|watchpoint| watchpoint.frame.is_some())
+        {
+            let sp = ptrace::getregs(tid)
+                .map_err(SdbError::ptrace("getregs", tid))?
+                .rsp;
+            if let Some(watchpoint) = self.watchpoints.iter_mut().find(|watchpoint| {
+                watchpoint.is_enabled
+                    && watchpoint
+                        .frame
+                        .is_some_and(|(thread, cfa)| thread == tid && sp >= cfa.addr())
+            }) {
+                watchpoint.is_enabled = false;
+                watchpoint.out_of_scope = true;
+                return Ok(Some((watchpoint.id, WatchpointMode::Write)));
+            }
+        }
+
+        let watched = self
+            .watchpoints
+            .iter()
+            .filter(|watchpoint| {
+                watchpoint.is_enabled && watchpoint.slot.is_none_or(|slot| fired & (1 << slot) != 0)
+            })
+            .map(|watchpoint| (watchpoint.id, watchpoint.address, watchpoint.size))
+            .collect::<Vec<_>>();
+        for (id, address, size) in watched {
+            let value = self.read_memory_without_traps(address, size)?;
+
+            let watchpoint = self.watchpoints.get_by_id_mut(id)?;
+            let access = match value == watchpoint.value {
+                true => WatchpointMode::Read,
+                false => WatchpointMode::Write,
+            };
+            if access == WatchpointMode::Write {
+                watchpoint.previous = Some(std::mem::replace(&mut watchpoint.value, value));
+            }
+            // a write watchpoint that didn't change anything or a read one that did isn't a hit
+            if !matches!(
+                (watchpoint.mode, access),
+                (WatchpointMode::ReadWrite, _)
+                    | (WatchpointMode::Write, WatchpointMode::Write)
+                    | (WatchpointMode::Read, WatchpointMode::Read)
+            ) {
+                continue;
+            }
+            watchpoint.hit_count += 1;
+            return Ok(Some((id, access)));
+        }
+
+        Ok(None)
+    }
+}
+
+fn debug_register_id(slot: usize) -> RegisterId {
+    match slot {
+        0 => RegisterId::dr0,
+        1 => RegisterId::dr1,
+        2 => RegisterId::dr2,
+        _ => RegisterId::dr3,
+    }
+}
+
+impl Target {
+    /// Stops the process when a variable, or part of one, is accessed, all of it unless `size` says
+    ///
+    /// A parameter or local of the function the pc is in is watched in the current thread's
+    /// frame, and the watchpoint's disabled once that frame returns
+    pub fn create_variable_watchpoint(
+        &mut self,
+        path: &VariablePath,
+        size: Option<usize>,
+        mode: WatchpointMode,
+    ) -> Result<StoppointId> {
+        let (address, variable_size, frame) = crate::variables::variable_memory(&*self, path)?;
+        let frame = frame.map(|cfa| (self.process.current_thread, cfa));
+        self.process
+            .push_watchpoint(address, size.unwrap_or(variable_size as usize), mode, frame)
+    }
+}
@@ -0,0 +1,19 @@
+#include <unistd.h>
+
+// watched from outside while the process runs
+volatile long counter;
+
+// a hot function to break on
+void tick(void) {
+    ++counter;
+}
+
+int main(void) {
+    // about five seconds
+    for (int i = 0; i < 5000; ++i) {
+        tick();
+        usleep(1000);
+    }
+
+    return 0;
+}
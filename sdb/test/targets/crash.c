@@ -0,0 +1,12 @@
+#include <stddef.h>
+
+void crash(int *pointer) {
+    *pointer = 42;
+}
+
+int main(void) {
+    // the compiler can't prove this is null at -O0
+    int *volatile pointer = NULL;
+    crash(pointer);
+    return 0;
+}
@@ -0,0 +1,11 @@
+#include <assert.h>
+#include <stdlib.h>
+
+int main(int argc, char **argv) {
+    (void)argv;
+
+    // always fails, but the compiler can't know that
+    assert(argc > 1);
+
+    return EXIT_SUCCESS;
+}
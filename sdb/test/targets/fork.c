@@ -0,0 +1,24 @@
+#include <stdio.h>
+#include <sys/wait.h>
+#include <unistd.h>
+
+void marker(void) {
+    puts("marker");
+    fflush(stdout);
+}
+
+int main(void) {
+    pid_t pid = fork();
+
+    // both sides run through the breakpoint
+    marker();
+
+    if (pid == 0) {
+        return 0;
+    }
+
+    // fail if the child died from a stray int3
+    int status;
+    waitpid(pid, &status, 0);
+    return WIFEXITED(status) && WEXITSTATUS(status) == 0 ? 0 : 1;
+}
@@ -0,0 +1,17 @@
+#include <stdio.h>
+#include <string.h>
+
+// built with the usual hardening flags:
+// -O2 -fstack-protector-strong -D_FORTIFY_SOURCE=2 -Wl,-z,relro,-z,now
+
+// the destination's size isn't known here, so this one can't be checked
+__attribute__((noinline)) void copy(char *to, const char *from) { strcpy(to, from); }
+
+int main(int argc, char **argv) {
+    char greeting[32];
+    snprintf(greeting, sizeof(greeting), "hello %s", argc > 1 ? argv[1] : "world");
+    char copied[32];
+    copy(copied, greeting);
+    puts(copied);
+    return 0;
+}
@@ -0,0 +1,38 @@
+#include <pthread.h>
+#include <stdlib.h>
+
+void *kept;
+void *thread_kept;
+
+// broken on once everything's allocated and freed
+void inspect(void) {}
+
+static void *allocate(void *arg) {
+    (void)arg;
+    // goes in the thread's own arena
+    thread_kept = malloc(64);
+    return NULL;
+}
+
+int main(void) {
+    // a tcache bin's worth and one more, which goes in a fast bin
+    void *small[8];
+    for (int i = 0; i < 8; ++i) {
+        small[i] = malloc(24);
+    }
+    // too big for the tcache, kept off the top so it's binned when freed
+    void *big = malloc(0x500);
+    kept = malloc(100);
+
+    for (int i = 0; i < 8; ++i) {
+        free(small[i]);
+    }
+    free(big);
+
+    pthread_t thread;
+    pthread_create(&thread, NULL, allocate, NULL);
+    pthread_join(thread, NULL);
+
+    inspect();
+    return 0;
+}
@@ -0,0 +1,22 @@
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+
+// broken on to see which output came before it
+void marker(void) {}
+
+static void say(int fd, const char *text) {
+    write(fd, text, strlen(text));
+}
+
+int main(void) {
+    say(STDOUT_FILENO, "before\n");
+    marker();
+
+    // the rest of the line never comes
+    say(STDOUT_FILENO, "partial");
+    marker();
+
+    say(STDERR_FILENO, "after\n");
+    return 0;
+}
@@ -0,0 +1,15 @@
+fn check(value: i32) {
+    if value > 2 {
+        panic!("value {} is too big", value);
+    }
+}
+
+fn main() {
+    let args = std::env::args().count();
+    if args > 1 {
+        panic!("static message");
+    }
+    for value in 0..5 {
+        check(value);
+    }
+}
@@ -0,0 +1,57 @@
+#include <stdlib.h>
+
+struct point {
+    int x;
+    int y;
+};
+
+struct mixed {
+    double weight;
+    long count;
+};
+
+struct big {
+    long values[4];
+};
+
+int sum(int a, int b) {
+    return a + b;
+}
+
+double half(double value) {
+    return value / 2;
+}
+
+long double third(long double value) {
+    return value / 3;
+}
+
+struct point flip(struct point point) {
+    struct point flipped = {point.y, point.x};
+    return flipped;
+}
+
+struct mixed weigh(long count) {
+    struct mixed mixed = {count * 0.25, count};
+    return mixed;
+}
+
+struct big fill(long start) {
+    struct big big = {{start, start + 1, start + 2, start + 3}};
+    return big;
+}
+
+const char *greeting(void) {
+    return "hello";
+}
+
+void nothing(void) {
+}
+
+int main(void) {
+    struct point point = {1, 2};
+    long total = sum(3, 4) + (long)half(5) + (long)third(9) + flip(point).x +
+                 weigh(8).count + fill(10).values[3];
+    nothing();
+    return greeting()[0] == 'h' && total > 0 ? EXIT_SUCCESS : EXIT_FAILURE;
+}
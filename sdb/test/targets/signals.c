@@ -0,0 +1,21 @@
+#include <signal.h>
+#include <stdio.h>
+
+static volatile sig_atomic_t handled = 0;
+
+void handler(int signal) {
+    (void)signal;
+    handled++;
+}
+
+int main(void) {
+    signal(SIGALRM, handler);
+    signal(SIGUSR1, handler);
+
+    // SIGALRM is passed through by default, SIGUSR1 stops
+    raise(SIGALRM);
+    raise(SIGUSR1);
+
+    printf("%d\n", handled);
+    return handled == 2 ? 0 : 1;
+}
@@ -0,0 +1,23 @@
+// burns time in known functions for sampling
+volatile long total;
+
+void inner(void) {
+    for (int i = 0; i < 1000; ++i) {
+        total += i;
+    }
+}
+
+void outer(void) {
+    for (int i = 0; i < 1000; ++i) {
+        inner();
+    }
+}
+
+int main(void) {
+    // a few seconds
+    for (int i = 0; i < 2000; ++i) {
+        outer();
+    }
+
+    return 0;
+}
@@ -0,0 +1,13 @@
+#include <stdio.h>
+#include <stdlib.h>
+
+int add(int a, int b) {
+    int sum = a + b;
+    return sum;
+}
+
+int main(void) {
+    int x = add(1, 2);
+    printf("%d\n", x);
+    return x == 3 ? EXIT_SUCCESS : EXIT_FAILURE;
+}
@@ -0,0 +1,36 @@
+#define _GNU_SOURCE
+#include <pthread.h>
+#include <stdio.h>
+
+#define THREAD_COUNT 4
+
+__thread int thread_index = -1;
+
+void marker(void) {
+    puts("marker");
+    fflush(stdout);
+}
+
+void *worker(void *arg) {
+    char name[16];
+    snprintf(name, sizeof(name), "worker-%d", (int)(long)arg);
+    pthread_setname_np(pthread_self(), name);
+    thread_index = (int)(long)arg;
+    marker();
+    return NULL;
+}
+
+int main(void) {
+    pthread_t threads[THREAD_COUNT];
+    for (int i = 0; i < THREAD_COUNT; ++i) {
+        if (pthread_create(&threads[i], NULL, worker, (void *)(long)i) != 0) {
+            return 1;
+        }
+    }
+
+    for (int i = 0; i < THREAD_COUNT; ++i) {
+        pthread_join(threads[i], NULL);
+    }
+
+    return 0;
+}
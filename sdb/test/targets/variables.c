@@ -0,0 +1,37 @@
+#include <stdlib.h>
+
+enum color { RED, GREEN, BLUE };
+
+struct point {
+    int x;
+    int y;
+};
+
+struct shape {
+    const char *name;
+    struct point corners[2];
+    struct point *origin;
+    unsigned visible : 1;
+    int layer : 4;
+    double scale;
+    enum color color;
+};
+
+struct point origin = {0, 0};
+int numbers[4] = {1, 2, 3, 4};
+double ratio = 0.5;
+enum color favorite = GREEN;
+
+int area(struct shape *shape, int factor) {
+    int width = shape->corners[1].x - shape->corners[0].x;
+    int height = shape->corners[1].y - shape->corners[0].y;
+    return width * height * factor;
+}
+
+int main(void) {
+    struct shape box = {
+        "box", {{1, 2}, {4, 6}}, &origin, 1, -3, 1.5, BLUE,
+    };
+    int result = area(&box, 2);
+    return result == 24 ? EXIT_SUCCESS : EXIT_FAILURE;
+}
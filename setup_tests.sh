@@ -3,3 +3,18 @@
 # TODO: any way to do this when running tests?
 
 cc sdb/test/targets/reg_write.s -pie -o sdb/test/targets/reg_write
+cc sdb/test/targets/fatal.c -g -pie -o sdb/test/targets/fatal
+cc sdb/test/targets/fork.c -g -O0 -pie -o sdb/test/targets/fork
+cc sdb/test/targets/threads.c -g -O0 -pie -pthread -o sdb/test/targets/threads
+cc sdb/test/targets/step.c -g -O0 -pie -o sdb/test/targets/step
+cc sdb/test/targets/crash.c -g -O0 -pie -o sdb/test/targets/crash
+cc sdb/test/targets/counter.c -g -O0 -pie -o sdb/test/targets/counter
+cc sdb/test/targets/output.c -g -O0 -pie -o sdb/test/targets/output
+cc sdb/test/targets/variables.c -g -O0 -pie -o sdb/test/targets/variables
+cc sdb/test/targets/returns.c -g -O0 -pie -o sdb/test/targets/returns
+cc sdb/test/targets/spin.c -g -O0 -pie -o sdb/test/targets/spin
+cc sdb/test/targets/signals.c -g -O0 -pie -o sdb/test/targets/signals
+cc sdb/test/targets/lifecycle.c -g -O0 -pie -o sdb/test/targets/lifecycle
+rustc -g sdb/test/targets/panic.rs -o sdb/test/targets/panic
+cc sdb/test/targets/heap.c -g -O0 -pie -pthread -o sdb/test/targets/heap
+cc sdb/test/targets/hardened.c -g -O2 -pie -fstack-protector-strong -D_FORTIFY_SOURCE=2 -Wl,-z,relro,-z,now -o sdb/test/targets/hardened